@@ -1,11 +1,19 @@
-use crate::config::{Config, expand_tilde};
+use crate::config::{Config, ConfigState, KillScope, expand_tilde};
+use crate::config_layers::ConfigSource;
+use crate::config_transfer::{export_config, import_config, ConfigExport, ConfigImportMode, ConfigImportOutcome};
+use crate::config_watcher::broadcast_config_change;
 use crate::error::AppError;
+use crate::settings::persist_config_key;
 use crate::utils::audit_logger::audit_log;
+use crate::AppHandle;
 
-use serde_json::Value;
-use std::path::PathBuf;
-use std::sync::{Arc, RwLock as StdRwLock};
-use tauri::{AppHandle, State};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tauri::State;
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::oneshot;
 use tracing::{info, warn};
 
 
@@ -13,125 +21,287 @@ use tracing::{info, warn};
 pub struct SetConfigValuePayload {
     key: String,
     value: Value,
+    // Also write the new value to settings.json so it survives a restart.
+    #[serde(default)]
+    persist: bool,
+}
+
+/// The Config field a set_config_value key writes to, for source tracking.
+fn config_field_for_key(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "allowedDirectories" => "allowed_directories",
+        "blockedCommands" => "blocked_commands",
+        "defaultShell" => "default_shell",
+        "logLevel" => "log_level",
+        "fileReadLineLimit" => "file_read_line_limit",
+        "fileWriteLineLimit" => "file_write_line_limit",
+        "exposeProcessEnvironment" => "expose_process_environment",
+        "killScope" => "kill_scope",
+        "killAllowedProcessNames" => "kill_allowed_process_names",
+        "disabledTools" => "disabled_tools",
+        _ => return None,
+    })
 }
 
 #[tauri::command(async)]
 pub async fn get_config_command(
-    config_state: State<'_, Arc<StdRwLock<Config>>>,
+    config_state: State<'_, Arc<ConfigState>>,
     audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
 ) -> Result<Config, String> {
     audit_log(&audit_logger_state, "ui_get_config", &serde_json::Value::Null).await;
 
-    let config_guard = config_state.read().map_err(|e| {
-        AppError::ConfigError(format!("Failed to acquire read lock on config: {}", e)).to_string()
-    })?;
-    Ok(config_guard.clone())
+    Ok(Config::clone(&config_state.snapshot()))
+}
+
+/// JSON Schemas for Config, settings.json and config.toml, used by the settings page to build its form.
+#[tauri::command]
+pub fn get_config_schema_command() -> Value {
+    crate::config::config_json_schemas()
+}
+
+#[tauri::command(async)]
+pub async fn export_config_command(
+    include_machine_paths: Option<bool>,
+    config_state: State<'_, Arc<ConfigState>>,
+    audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
+) -> Result<ConfigExport, String> {
+    audit_log(&audit_logger_state, "ui_export_config", &serde_json::json!({ "include_machine_paths": include_machine_paths })).await;
+    Ok(export_config(&config_state.snapshot(), include_machine_paths.unwrap_or(false)))
+}
+
+#[tauri::command(async)]
+pub async fn import_config_command(
+    app_handle: AppHandle,
+    blob: Value,
+    mode: Option<ConfigImportMode>,
+    config_state: State<'_, Arc<ConfigState>>,
+    audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
+) -> Result<ConfigImportOutcome, String> {
+    audit_log(&audit_logger_state, "ui_import_config", &serde_json::json!({ "mode": format!("{:?}", mode.unwrap_or_default()) })).await;
+    import_config(&app_handle, config_state.inner(), blob, mode.unwrap_or_default(), true).map_err(|e| e.to_string())
 }
 
 #[tauri::command(async)]
 pub async fn set_config_value_command(
-    _app_handle: AppHandle,
+    app_handle: AppHandle,
     payload: SetConfigValuePayload,
-    config_state: State<'_, Arc<StdRwLock<Config>>>,
+    config_state: State<'_, Arc<ConfigState>>,
     audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
 ) -> Result<String, String> {
     audit_log(&audit_logger_state, "ui_set_config_value", &serde_json::to_value(&payload).unwrap_or_default()).await;
 
-    let mut config_guard = config_state.write().map_err(|e| {
-        AppError::ConfigError(format!("Failed to acquire write lock on config: {}", e)).to_string()
-    })?;
-
     let key = payload.key.as_str();
     let value_to_set = payload.value;
-    // let mut _update_applied = true; // No longer strictly needed due to early returns
 
     info!(key = %key, value = ?value_to_set, "UI: Attempting to set config value");
 
-    match key {
-        "allowedDirectories" => {
-            let new_dirs_str_values: Vec<String> = match value_to_set {
-                Value::Array(arr_val) => arr_val.into_iter().filter_map(|v| v.as_str().map(String::from)).collect(),
-                Value::String(str_val) => str_val.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
-                _ => return Err(AppError::InvalidInputArgument("allowedDirectories must be a JSON array of strings or a comma-separated string".to_string()).to_string()),
-            };
-
-            let mut new_dirs_pathbuf = Vec::new();
-            for s_path in new_dirs_str_values {
-                if s_path.is_empty() { continue; }
-                match expand_tilde(&s_path) {
-                    Ok(p) => new_dirs_pathbuf.push(p.canonicalize().unwrap_or(p)),
-                    Err(e) => return Err(AppError::InvalidPath(format!("Invalid path in allowedDirectories '{}': {}", s_path, e)).to_string()),
+    // filesRoot cannot change under running tools, but can be saved for the next start.
+    if key == "filesRoot" && payload.persist {
+        let Some(str_val) = value_to_set.as_str() else {
+            return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+        };
+        let new_root = expand_tilde(str_val).map_err(|e| AppError::InvalidPath(format!("Invalid filesRoot '{}': {}", str_val, e)).to_string())?;
+        if !new_root.is_dir() {
+            return Err(AppError::InvalidPath(format!("filesRoot '{}' is not an existing directory", new_root.display())).to_string());
+        }
+        let mut next_start_config = Config::clone(&config_state.snapshot());
+        next_start_config.files_root = new_root.canonicalize().unwrap_or(new_root);
+        let settings_file = next_start_config.settings_file.clone();
+        persist_config_key(&settings_file, key, &next_start_config).map_err(|e| AppError::ConfigError(e.to_string()).to_string())?;
+        broadcast_config_change(&app_handle, "ui", &next_start_config, &[], &["files_root".to_string()]);
+        return Ok(format!("Saved filesRoot '{}' to {}. It takes effect after a restart.", next_start_config.files_root.display(), settings_file.display()));
+    }
+
+    // Nothing is published if the value is rejected.
+    let ((), config_snapshot) = config_state.try_update(|config_guard: &mut Config| -> Result<(), String> {
+        match key {
+            "allowedDirectories" => {
+                let new_dirs_str_values: Vec<String> = match value_to_set {
+                    Value::Array(arr_val) => arr_val.into_iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+                    Value::String(str_val) => str_val.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+                    _ => return Err(AppError::InvalidInputArgument("allowedDirectories must be a JSON array of strings or a comma-separated string".to_string()).to_string()),
+                };
+
+                let mut new_dirs_pathbuf = Vec::new();
+                for s_path in new_dirs_str_values {
+                    if s_path.is_empty() { continue; }
+                    match expand_tilde(&s_path) {
+                        Ok(p) => new_dirs_pathbuf.push(p.canonicalize().unwrap_or(p)),
+                        Err(e) => return Err(AppError::InvalidPath(format!("Invalid path in allowedDirectories '{}': {}", s_path, e)).to_string()),
+                    }
                 }
-            }
 
-            let is_files_root_broad = config_guard.files_root == PathBuf::from("/") ||
-                                    (cfg!(windows) && config_guard.files_root.parent().is_none() && config_guard.files_root.is_absolute());
-            if !is_files_root_broad && !new_dirs_pathbuf.iter().any(|ad| ad == &config_guard.files_root) {
-                new_dirs_pathbuf.push(config_guard.files_root.clone());
-            }
-            new_dirs_pathbuf.sort();
-            new_dirs_pathbuf.dedup();
-            config_guard.allowed_directories = new_dirs_pathbuf;
-            info!(new_allowed_dirs = ?config_guard.allowed_directories, "Updated allowedDirectories");
-        },
-        "blockedCommands" => {
-            let new_cmds: Vec<String> = match value_to_set {
-                Value::Array(arr_val) => arr_val.into_iter().filter_map(|v| v.as_str().map(String::from)).collect(),
-                Value::String(str_val) => str_val.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
-                _ => return Err(AppError::InvalidInputArgument("blockedCommands must be a JSON array of strings or a comma-separated string".to_string()).to_string()),
-            };
-            config_guard.blocked_commands = new_cmds;
-            info!(new_blocked_cmds = ?config_guard.blocked_commands, "Updated blockedCommands");
-        },
-        "defaultShell" => {
-            if let Some(str_val) = value_to_set.as_str() {
-                config_guard.default_shell = if str_val.trim().is_empty() { None } else { Some(str_val.trim().to_string()) };
-            } else if value_to_set.is_null() {
-                 config_guard.default_shell = None;
-            } else {
-                warn!(key=key, "set_config_value: value for defaultShell was not a string or null");
-                 return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
-            }
-            info!(new_default_shell = ?config_guard.default_shell, "Updated defaultShell");
-        },
-        "logLevel" => {
-            if let Some(str_val) = value_to_set.as_str() {
-                config_guard.log_level = str_val.to_string();
-                warn!("Log level set to '{}' in config. A full application restart may be needed for tracing subscriber changes to take complete effect.", str_val);
-            } else {
-                warn!(key=key, "set_config_value: value for logLevel was not a string");
-                return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
-            }
-            info!(new_log_level = %config_guard.log_level, "Updated logLevel");
-        },
-        "fileReadLineLimit" => {
-            if let Some(num_val) = value_to_set.as_u64() {
-                config_guard.file_read_line_limit = num_val as usize;
-            } else {
-                warn!(key=key, "set_config_value: value for fileReadLineLimit was not u64");
-                return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+                let is_files_root_broad = config_guard.files_root == PathBuf::from("/") ||
+                                        (cfg!(windows) && config_guard.files_root.parent().is_none() && config_guard.files_root.is_absolute());
+                if !is_files_root_broad && !new_dirs_pathbuf.iter().any(|ad| ad == &config_guard.files_root) {
+                    new_dirs_pathbuf.push(config_guard.files_root.clone());
+                }
+                new_dirs_pathbuf.sort();
+                new_dirs_pathbuf.dedup();
+                config_guard.allowed_directories = new_dirs_pathbuf;
+                info!(new_allowed_dirs = ?config_guard.allowed_directories, "Updated allowedDirectories");
+            },
+            "blockedCommands" => {
+                let new_cmds: Vec<String> = match value_to_set {
+                    Value::Array(arr_val) => arr_val.into_iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+                    Value::String(str_val) => str_val.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+                    _ => return Err(AppError::InvalidInputArgument("blockedCommands must be a JSON array of strings or a comma-separated string".to_string()).to_string()),
+                };
+                crate::config::check_blocked_commands(&new_cmds).map_err(|e| AppError::InvalidInputArgument(format!("{:#}", e)).to_string())?;
+                config_guard.blocked_commands = new_cmds;
+                info!(new_blocked_cmds = ?config_guard.blocked_commands, "Updated blockedCommands");
+            },
+            "defaultShell" => {
+                if let Some(str_val) = value_to_set.as_str() {
+                    config_guard.default_shell = if str_val.trim().is_empty() { None } else { Some(str_val.trim().to_string()) };
+                } else if value_to_set.is_null() {
+                     config_guard.default_shell = None;
+                } else {
+                    warn!(key=key, "set_config_value: value for defaultShell was not a string or null");
+                     return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+                }
+                info!(new_default_shell = ?config_guard.default_shell, "Updated defaultShell");
+            },
+            "logLevel" => {
+                if let Some(str_val) = value_to_set.as_str() {
+                    crate::logging::apply_log_level(str_val).map_err(|e| AppError::InvalidInputArgument(e).to_string())?;
+                    config_guard.log_level = str_val.to_lowercase();
+                } else {
+                    warn!(key=key, "set_config_value: value for logLevel was not a string");
+                    return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+                }
+                info!(new_log_level = %config_guard.log_level, "Updated logLevel");
+            },
+            "fileReadLineLimit" => {
+                if let Some(num_val) = value_to_set.as_u64() {
+                    config_guard.file_read_line_limit = num_val as usize;
+                } else {
+                    warn!(key=key, "set_config_value: value for fileReadLineLimit was not u64");
+                    return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+                }
+                info!(new_read_limit = %config_guard.file_read_line_limit, "Updated fileReadLineLimit");
+            },
+             "fileWriteLineLimit" => {
+                if let Some(num_val) = value_to_set.as_u64() {
+                    config_guard.file_write_line_limit = num_val as usize;
+                } else {
+                    warn!(key=key, "set_config_value: value for fileWriteLineLimit was not u64");
+                     return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+                }
+                info!(new_write_limit = %config_guard.file_write_line_limit, "Updated fileWriteLineLimit");
+            },
+            "exposeProcessEnvironment" => {
+                if let Some(bool_val) = value_to_set.as_bool() {
+                    config_guard.expose_process_environment = bool_val;
+                } else {
+                    warn!(key=key, "set_config_value: value for exposeProcessEnvironment was not a boolean");
+                    return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+                }
+                info!(expose_process_environment = %config_guard.expose_process_environment, "Updated exposeProcessEnvironment");
+            },
+            "killScope" => {
+                let Some(str_val) = value_to_set.as_str() else {
+                    warn!(key=key, "set_config_value: value for killScope was not a string");
+                    return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+                };
+                config_guard.kill_scope = KillScope::from_str(str_val).map_err(|e| AppError::InvalidInputArgument(e.to_string()).to_string())?;
+                info!(kill_scope = ?config_guard.kill_scope, "Updated killScope");
+            },
+            "killAllowedProcessNames" => {
+                let new_names: Vec<String> = match value_to_set {
+                    Value::Array(arr_val) => arr_val.into_iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+                    Value::String(str_val) => str_val.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+                    _ => return Err(AppError::InvalidInputArgument("killAllowedProcessNames must be a JSON array of strings or a comma-separated string".to_string()).to_string()),
+                };
+                config_guard.kill_allowed_process_names = new_names;
+                info!(kill_allowed_process_names = ?config_guard.kill_allowed_process_names, "Updated killAllowedProcessNames");
+            },
+            "disabledTools" => {
+                let new_tools: Vec<String> = match value_to_set {
+                    Value::Array(arr_val) => arr_val.into_iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+                    Value::String(str_val) => str_val.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+                    _ => return Err(AppError::InvalidInputArgument("disabledTools must be a JSON array of strings or a comma-separated string".to_string()).to_string()),
+                };
+                config_guard.disabled_tools = new_tools;
+                info!(disabled_tools = ?config_guard.disabled_tools, "Updated disabledTools");
+            },
+            "filesRoot" | "mcpLogDir" | "auditLogFile" | "fuzzySearchLogFile" => {
+                 warn!(key=key, "set_config_value: Dynamically changing this path is not supported via this command.");
+                 return Err(AppError::ConfigError(format!("Configuration key '{}' cannot be changed at runtime through this command.", key)).to_string());
             }
-            info!(new_read_limit = %config_guard.file_read_line_limit, "Updated fileReadLineLimit");
-        },
-         "fileWriteLineLimit" => {
-            if let Some(num_val) = value_to_set.as_u64() {
-                config_guard.file_write_line_limit = num_val as usize;
-            } else {
-                warn!(key=key, "set_config_value: value for fileWriteLineLimit was not u64");
-                 return Err(AppError::InvalidInputArgument(format!("Invalid value type for config key '{}'", key)).to_string());
+            _ => {
+                warn!(key=key, "set_config_value: Unknown or unhandled config key");
+                return Err(AppError::InvalidInputArgument(format!("Unknown or read-only config key: {}", key)).to_string());
             }
-            info!(new_write_limit = %config_guard.file_write_line_limit, "Updated fileWriteLineLimit");
-        },
-        "filesRoot" | "mcpLogDir" | "auditLogFile" | "fuzzySearchLogFile" => {
-             warn!(key=key, "set_config_value: Dynamically changing this path is not supported via this command.");
-             return Err(AppError::ConfigError(format!("Configuration key '{}' cannot be changed at runtime through this command.", key)).to_string());
         }
-        _ => {
-            warn!(key=key, "set_config_value: Unknown or unhandled config key");
-            return Err(AppError::InvalidInputArgument(format!("Unknown or read-only config key: {}", key)).to_string());
+        if let Some(field) = config_field_for_key(key) {
+            let source = if payload.persist { ConfigSource::Settings } else { ConfigSource::Runtime };
+            config_guard.value_sources.insert(field.to_string(), source);
         }
+        Ok(())
+    })?;
+    info!(key = %key, "Successfully set config value via UI command");
+    let changed_keys: Vec<String> = config_field_for_key(key).map(|field| vec![field.to_string()]).unwrap_or_default();
+    broadcast_config_change(&app_handle, "ui", &config_snapshot, &changed_keys, &[]);
+    if !payload.persist {
+        return Ok(format!("Successfully set config key '{}'. Changes are in-memory for the current session.", key));
     }
+    persist_config_key(&config_snapshot.settings_file, key, &config_snapshot).map_err(|e| AppError::ConfigError(e.to_string()).to_string())?;
+    Ok(format!("Successfully set config key '{}' and saved it to {}.", key, config_snapshot.settings_file.display()))
+}
 
-    info!(key = %key, "Successfully set config value via UI command");
-    Ok(format!("Successfully set config key '{}'. Changes are in-memory for the current session.", key))
-}
\ No newline at end of file
+/// What the folder picker commands return; None from the command means the picker was cancelled.
+#[derive(serde::Serialize)]
+pub struct PickedDirectory {
+    path: PathBuf,
+    message: String,
+}
+
+/// Opens the OS folder picker and returns the chosen directory, canonicalized.
+async fn pick_folder(app_handle: &AppHandle, title: &str, start_dir: &Path) -> Result<Option<PathBuf>, String> {
+    let (reply, picked) = oneshot::channel();
+    app_handle.dialog().file().set_title(title).set_directory(start_dir).pick_folder(move |folder| { let _ = reply.send(folder); });
+    let Some(folder) = picked.await.map_err(|_| AppError::Unknown("The folder picker closed without an answer".to_string()).to_string())? else { return Ok(None) };
+    let path = folder.into_path().map_err(|e| AppError::InvalidPath(format!("The picked folder is not a local path: {}", e)).to_string())?;
+    let path = path.canonicalize().map_err(|e| AppError::InvalidPath(format!("Cannot resolve picked folder '{}': {}", path.display(), e)).to_string())?;
+    if !path.is_dir() {
+        return Err(AppError::InvalidPath(format!("'{}' is not a directory", path.display())).to_string());
+    }
+    Ok(Some(path))
+}
+
+/// Lets the user choose FILES_ROOT with the native folder picker and saves it to settings.json
+/// for the next start, as set_config_value("filesRoot", persist: true) does.
+#[tauri::command(async)]
+pub async fn pick_directory_for_files_root(
+    app_handle: AppHandle,
+    config_state: State<'_, Arc<ConfigState>>,
+    audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
+) -> Result<Option<PickedDirectory>, String> {
+    let current_root = config_state.snapshot().files_root.clone();
+    let Some(path) = pick_folder(&app_handle, "Choose the files root", &current_root).await? else { return Ok(None) };
+    audit_log(&audit_logger_state, "ui_pick_directory_for_files_root", &json!({ "path": path })).await;
+    let payload = SetConfigValuePayload { key: "filesRoot".to_string(), value: json!(path), persist: true };
+    let message = set_config_value_command(app_handle, payload, config_state, audit_logger_state).await?;
+    Ok(Some(PickedDirectory { path, message }))
+}
+
+/// Adds a directory chosen with the native folder picker to allowed_directories, effective at
+/// once and saved to settings.json.
+#[tauri::command(async)]
+pub async fn pick_allowed_directory(
+    app_handle: AppHandle,
+    config_state: State<'_, Arc<ConfigState>>,
+    audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
+) -> Result<Option<PickedDirectory>, String> {
+    let config = config_state.snapshot();
+    let Some(path) = pick_folder(&app_handle, "Choose a directory to allow", &config.files_root).await? else { return Ok(None) };
+    audit_log(&audit_logger_state, "ui_pick_allowed_directory", &json!({ "path": path })).await;
+    if config.allowed_directories.contains(&path) {
+        return Ok(Some(PickedDirectory { message: format!("'{}' is already an allowed directory.", path.display()), path }));
+    }
+    let dirs: Vec<PathBuf> = config.allowed_directories.iter().cloned().chain(std::iter::once(path.clone())).collect();
+    let payload = SetConfigValuePayload { key: "allowedDirectories".to_string(), value: json!(dirs), persist: true };
+    let message = set_config_value_command(app_handle, payload, config_state, audit_logger_state).await?;
+    Ok(Some(PickedDirectory { path, message }))
+}
@@ -12,16 +12,15 @@
 // If you need UI-specific wrappers around MCP logic, define them here.
 // Example:
 /*
-use crate::config::Config;
+use crate::config::ConfigState;
 use crate::error::AppError;
 use crate::utils::audit_logger::audit_log;
 use crate::mcp::tool_impl::filesystem as mcp_fs_impl;
 use crate::mcp::handler::ToolDependencies;
+use crate::AppHandle;
 
-use std::sync::{Arc, RwLock as StdRwLock};
-use tauri::{AppHandle, Manager, State};
-use sysinfo::System as SysinfoSystem;
-use tokio::sync::Mutex as TokioMutex;
+use std::sync::Arc;
+use tauri::State;
 use tracing::instrument;
 
 pub use crate::mcp::tool_impl::filesystem::{
@@ -29,22 +28,15 @@ pub use crate::mcp::tool_impl::filesystem::{
     FileContentMCP as FileContent,
 };
 
-fn get_tool_dependencies_for_ui(app_handle: &AppHandle, config_state: &State<'_, Arc<StdRwLock<Config>>>) -> ToolDependencies {
-    ToolDependencies {
-        app_handle: app_handle.clone(),
-        config_state: config_state.inner().clone(),
-        audit_logger: app_handle.state::<Arc<crate::utils::audit_logger::AuditLogger>>().inner().clone(),
-        fuzzy_search_logger: app_handle.state::<Arc<crate::utils::fuzzy_search_logger::FuzzySearchLogger>>().inner().clone(),
-        active_sessions_map: app_handle.state::<crate::commands::terminal_commands::ActiveSessionsMap>().inner().clone(),
-        sysinfo_state: app_handle.state::<Arc<TokioMutex<SysinfoSystem>>>().inner().clone(),
-    }
+fn get_tool_dependencies_for_ui(app_handle: &AppHandle, config_state: &State<'_, Arc<ConfigState>>) -> ToolDependencies {
+    ToolDependencies::from_app_handle(app_handle, config_state.inner().clone())
 }
 
 #[tauri::command(async)]
 #[instrument(skip(app_handle, config_state, audit_logger_state, params), fields(path = %params.path))]
 pub async fn read_file_command_ui_wrapper( // Example wrapper
     app_handle: AppHandle,
-    config_state: State<'_, Arc<StdRwLock<Config>>>,
+    config_state: State<'_, Arc<ConfigState>>,
     audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
     params: ReadFileParams,
 ) -> Result<FileContent, AppError> {
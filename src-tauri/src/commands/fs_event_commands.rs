@@ -0,0 +1,26 @@
+use crate::utils::audit_logger::{audit_log, AuditLogger};
+use crate::utils::fs_events::{FsEventSubscriptions, FsSubscriptionUI};
+
+use serde_json::json;
+use std::sync::Arc;
+use tauri::State;
+
+/// Starts emitting `fs_changed` events for changes under `path` (a file or directory within
+/// the allowed directories). `recursive` defaults to true.
+#[tauri::command(async)]
+pub async fn subscribe_fs_events(
+    path: String,
+    recursive: Option<bool>,
+    subscriptions: State<'_, Arc<FsEventSubscriptions>>,
+    audit_logger_state: State<'_, Arc<AuditLogger>>,
+) -> Result<FsSubscriptionUI, String> {
+    let recursive = recursive.unwrap_or(true);
+    audit_log(&audit_logger_state, "ui_subscribe_fs_events", &json!({ "path": path, "recursive": recursive })).await;
+    subscriptions.subscribe(&path, recursive).map_err(|e| e.to_string())
+}
+
+/// Stops the events of a subscribe_fs_events call; returns false if it was already stopped.
+#[tauri::command]
+pub fn unsubscribe_fs_events(subscription_id: String, subscriptions: State<'_, Arc<FsEventSubscriptions>>) -> Result<bool, String> {
+    subscriptions.unsubscribe(&subscription_id).map_err(|e| e.to_string())
+}
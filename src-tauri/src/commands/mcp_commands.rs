@@ -0,0 +1,117 @@
+use crate::config::{parse_transports, Config, ConfigState, TransportMode};
+use crate::config_layers::ConfigSource;
+use crate::config_validation::{validate_config, ConfigIssueSeverity};
+use crate::config_watcher::broadcast_config_change;
+use crate::error::AppError;
+use crate::mcp::metrics::ServerStatsMCP;
+use crate::mcp::server_control::{McpServerControl, McpServerStatus};
+use crate::utils::audit_logger::audit_log;
+use crate::AppHandle;
+
+use std::sync::Arc;
+use tauri::State;
+use tracing::info;
+
+// The Config fields set_mcp_transport can change.
+const TRANSPORT_KEYS: &[&str] = &["mcp_transports", "mcp_sse_host", "mcp_sse_port", "mcp_ws_host", "mcp_ws_port", "mcp_ws_path", "mcp_http_host", "mcp_http_port", "mcp_http_path"];
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct SetMcpTransportPayload {
+    // MCP_TRANSPORT syntax: a comma-separated list of stdio, sse, websocket and http, or "disabled".
+    transport: String,
+    // Applied to every network transport in `transport`; unset keeps the current value.
+    #[serde(default)]
+    host: Option<String>,
+    // Each transport listens on its own port and path, so these need exactly one network transport.
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    path: Option<String>,
+}
+
+/// Checks the new transport settings and applies them to `config`, returning the changed keys.
+fn apply_transport_settings(config: &mut Config, payload: &SetMcpTransportPayload) -> Result<Vec<String>, AppError> {
+    let transports = parse_transports(&payload.transport).map_err(|e| AppError::InvalidInputArgument(format!("{:#}", e)))?;
+    if transports.contains(&TransportMode::Stdio) != config.mcp_transports.contains(&TransportMode::Stdio) {
+        return Err(AppError::InvalidInputArgument("The STDIO transport cannot be added or removed while the app runs, since its client starts this process; change MCP_TRANSPORT and restart the app instead".to_string()));
+    }
+    let network: Vec<TransportMode> = transports.iter().filter(|t| **t != TransportMode::Stdio).cloned().collect();
+    if (payload.port.is_some() || payload.path.is_some()) && network.len() != 1 {
+        return Err(AppError::InvalidInputArgument(format!("port and path need exactly one network transport, but '{}' has {}", payload.transport, network.len())));
+    }
+
+    let before = serde_json::to_value(&*config).unwrap_or_default();
+    config.mcp_transports = transports;
+    for transport in &network {
+        let (host, port, path) = match transport {
+            TransportMode::Sse => (&mut config.mcp_sse_host, &mut config.mcp_sse_port, None),
+            TransportMode::WebSocket => (&mut config.mcp_ws_host, &mut config.mcp_ws_port, Some(&mut config.mcp_ws_path)),
+            TransportMode::Http => (&mut config.mcp_http_host, &mut config.mcp_http_port, Some(&mut config.mcp_http_path)),
+            TransportMode::Stdio => continue,
+        };
+        if let Some(new_host) = &payload.host { *host = Some(new_host.clone()); }
+        if payload.port.is_some() { *port = payload.port; }
+        match (path, &payload.path) {
+            (Some(path), Some(new_path)) => *path = Some(new_path.clone()),
+            (None, Some(_)) => return Err(AppError::InvalidInputArgument("The SSE transport serves the fixed paths /sse and /messages; path applies to websocket and http only".to_string())),
+            _ => {}
+        }
+    }
+
+    // The running server owns the current ports, so binding is left to the restart, which reports failures per transport.
+    let errors: Vec<String> = validate_config(config, false).issues.into_iter()
+        .filter(|i| i.severity == ConfigIssueSeverity::Error && i.key.starts_with("mcp_"))
+        .map(|i| i.message)
+        .collect();
+    if !errors.is_empty() {
+        return Err(AppError::ConfigError(errors.join("; ")));
+    }
+    let after = serde_json::to_value(&*config).unwrap_or_default();
+    Ok(TRANSPORT_KEYS.iter().filter(|k| before.get(**k) != after.get(**k)).map(|k| k.to_string()).collect())
+}
+
+#[tauri::command]
+pub fn get_mcp_server_status(control: State<'_, Arc<McpServerControl>>) -> McpServerStatus {
+    control.status()
+}
+
+/// The same stats as the get_server_stats tool.
+#[tauri::command(async)]
+pub async fn get_mcp_server_stats(control: State<'_, Arc<McpServerControl>>) -> Result<ServerStatsMCP, String> {
+    Ok(control.handler().server_stats().await)
+}
+
+/// Stops and relaunches the network transports with the current config; STDIO keeps running.
+#[tauri::command(async)]
+pub async fn restart_mcp_server(
+    control: State<'_, Arc<McpServerControl>>,
+    audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
+) -> Result<McpServerStatus, String> {
+    audit_log(&audit_logger_state, "ui_restart_mcp_server", &serde_json::Value::Null).await;
+    control.inner().restart().await.map_err(|e| e.to_string())
+}
+
+/// Switches the MCP transports (and optionally a listen address) for this session, then restarts
+/// the server. Set MCP_TRANSPORT in .env to keep the change after the app restarts.
+#[tauri::command(async)]
+pub async fn set_mcp_transport(
+    app_handle: AppHandle,
+    payload: SetMcpTransportPayload,
+    control: State<'_, Arc<McpServerControl>>,
+    config_state: State<'_, Arc<ConfigState>>,
+    audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
+) -> Result<McpServerStatus, String> {
+    audit_log(&audit_logger_state, "ui_set_mcp_transport", &serde_json::to_value(&payload).unwrap_or_default()).await;
+
+    // A rejected payload leaves the published config as it was.
+    let (changed_keys, config_snapshot) = config_state.try_update(|config| {
+        let changed_keys = apply_transport_settings(config, &payload).map_err(|e| e.to_string())?;
+        for key in &changed_keys {
+            config.value_sources.insert(key.clone(), ConfigSource::Runtime);
+        }
+        Ok::<_, String>(changed_keys)
+    })?;
+    info!(transport = %payload.transport, changed_keys = ?changed_keys, "UI: Switching MCP transports");
+    broadcast_config_change(&app_handle, "ui", &config_snapshot, &changed_keys, &[]);
+    control.inner().restart().await.map_err(|e| e.to_string())
+}
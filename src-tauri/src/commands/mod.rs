@@ -1,10 +1,13 @@
 
 pub mod config_commands;
+pub mod secret_commands;
 pub mod filesystem_commands;
 pub mod ripgrep_commands;
 pub mod terminal_commands;
 pub mod process_commands;
 pub mod edit_commands;
+pub mod mcp_commands;
+pub mod fs_event_commands;
 
 // A simple greet command for initial testing
 #[tauri::command]
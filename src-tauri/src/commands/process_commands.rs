@@ -7,11 +7,12 @@ use crate::error::AppError;
 use crate::utils::audit_logger::audit_log;
 use crate::mcp::tool_impl::process as mcp_process_impl;
 use crate::mcp::handler::ToolDependencies;
-use crate::config::Config;
+use crate::config::ConfigState;
+use crate::AppHandle;
 
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, RwLock as StdRwLock};
-use tauri::{AppHandle, Manager, State};
+use std::sync::Arc;
+use tauri::State;
 use tokio::sync::Mutex as TokioMutex;
 use tracing::instrument;
 
@@ -40,22 +41,15 @@ pub struct KillProcessResult {
 
 pub type SysinfoState = Arc<TokioMutex<sysinfo::System>>;
 
-fn get_tool_dependencies_for_ui(app_handle: &AppHandle, config_state: &State<'_, Arc<StdRwLock<Config>>>) -> ToolDependencies {
-    ToolDependencies {
-        app_handle: app_handle.clone(),
-        config_state: config_state.inner().clone(),
-        audit_logger: app_handle.state::<Arc<crate::utils::audit_logger::AuditLogger>>().inner().clone(),
-        fuzzy_search_logger: app_handle.state::<Arc<crate::utils::fuzzy_search_logger::FuzzySearchLogger>>().inner().clone(),
-        active_sessions_map: app_handle.state::<crate::commands::terminal_commands::ActiveSessionsMap>().inner().clone(),
-        sysinfo_state: app_handle.state::<SysinfoState>().inner().clone(),
-    }
+fn get_tool_dependencies_for_ui(app_handle: &AppHandle, config_state: &State<'_, Arc<ConfigState>>) -> ToolDependencies {
+    ToolDependencies::from_app_handle(app_handle, config_state.inner().clone())
 }
 
 #[tauri::command(async)]
 #[instrument(skip(app_handle, audit_logger_state, config_state, _sysinfo_state))]
 pub async fn list_processes_command_ui_wrapper(
     app_handle: AppHandle,
-    config_state: State<'_, Arc<StdRwLock<Config>>>,
+    config_state: State<'_, Arc<ConfigState>>,
     audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
     _sysinfo_state: State<'_, SysinfoState>,
 ) -> Result<Vec<ProcessInfo>, AppError> {
@@ -74,7 +68,7 @@ pub async fn list_processes_command_ui_wrapper(
 #[instrument(skip(app_handle, audit_logger_state, config_state, _sysinfo_state, params), fields(pid = %params.pid))]
 pub async fn kill_process_command_ui_wrapper(
     app_handle: AppHandle,
-    config_state: State<'_, Arc<StdRwLock<Config>>>,
+    config_state: State<'_, Arc<ConfigState>>,
     audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
     _sysinfo_state: State<'_, SysinfoState>,
     params: KillProcessParams,
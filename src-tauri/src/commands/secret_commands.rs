@@ -0,0 +1,63 @@
+use crate::config::ConfigState;
+use crate::config_watcher::broadcast_config_change;
+use crate::error::AppError;
+use crate::secrets;
+use crate::settings::persist_config_key;
+use crate::utils::audit_logger::audit_log;
+use crate::AppHandle;
+
+use serde_json::json;
+use std::sync::Arc;
+use tauri::State;
+
+/// Names of stored secrets are tracked in settings.json so get_config can show which ones are
+/// set; the values only ever live in the OS keyring.
+fn update_secret_index(app_handle: &AppHandle, config_state: &Arc<ConfigState>, name: &str, present: bool) -> Result<(), AppError> {
+    let ((), config_snapshot) = config_state.update(|config| {
+        config.secret_names.retain(|n| n != name);
+        if present {
+            config.secret_names.push(name.to_string());
+            config.secret_names.sort();
+        }
+    });
+    broadcast_config_change(app_handle, "ui", &config_snapshot, &["secret_names".to_string()], &[]);
+    persist_config_key(&config_snapshot.settings_file, "secretNames", &config_snapshot).map_err(|e| AppError::ConfigError(e.to_string()))
+}
+
+#[tauri::command(async)]
+pub async fn set_secret_command(
+    app_handle: AppHandle,
+    name: String,
+    value: String,
+    config_state: State<'_, Arc<ConfigState>>,
+    audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
+) -> Result<String, String> {
+    // Never log the value.
+    audit_log(&audit_logger_state, "ui_set_secret", &json!({ "name": name })).await;
+
+    let name_for_keyring = name.clone();
+    tokio::task::spawn_blocking(move || secrets::set_secret(&name_for_keyring, &value))
+        .await
+        .map_err(|e| AppError::ConfigError(format!("Keyring task failed: {}", e)).to_string())?
+        .map_err(|e| e.to_string())?;
+    update_secret_index(&app_handle, config_state.inner(), &name, true).map_err(|e| e.to_string())?;
+    Ok(format!("Stored secret '{}' in the OS keyring.", name))
+}
+
+#[tauri::command(async)]
+pub async fn delete_secret_command(
+    app_handle: AppHandle,
+    name: String,
+    config_state: State<'_, Arc<ConfigState>>,
+    audit_logger_state: State<'_, Arc<crate::utils::audit_logger::AuditLogger>>,
+) -> Result<String, String> {
+    audit_log(&audit_logger_state, "ui_delete_secret", &json!({ "name": name })).await;
+
+    let name_for_keyring = name.clone();
+    let existed = tokio::task::spawn_blocking(move || secrets::delete_secret(&name_for_keyring))
+        .await
+        .map_err(|e| AppError::ConfigError(format!("Keyring task failed: {}", e)).to_string())?
+        .map_err(|e| e.to_string())?;
+    update_secret_index(&app_handle, config_state.inner(), &name, false).map_err(|e| e.to_string())?;
+    Ok(if existed { format!("Deleted secret '{}'.", name) } else { format!("No secret named '{}' was stored.", name) })
+}
@@ -3,8 +3,10 @@
 // If UI needs direct calls to terminal logic not via MCP, define them here.
 // For this iteration, this file will only contain the necessary type definitions.
 
+use crate::utils::limits::truncate_output;
+
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use tauri_plugin_shell::process::CommandChild;
 use tokio::sync::Mutex as TokioMutex; // Keep TokioMutex for ActiveSession
@@ -16,15 +18,129 @@ pub struct ExecuteCommandResultUI {
     pub message: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionOutputLine {
+    pub index: usize,
+    pub stream: String,
+    pub text: String,
+}
+
 #[derive(Debug)]
 pub struct ActiveSession {
     pub process_child: Arc<TokioMutex<Option<CommandChild>>>,
     pub command_str: String,
     pub exit_code: Arc<TokioMutex<Option<i32>>>,
     pub start_time_system: std::time::SystemTime,
-    #[allow(dead_code)] // session_id is used as key in map and for SessionInfoMCP, but not read directly from ActiveSession instance itself
     pub session_id: String,
     pub pid: Option<u32>,
+    // The lines the session produced, indexed from 0, so clients can re-read earlier output.
+    pub output: Arc<TokioMutex<SessionOutput>>,
+    pub execution_target: String,
+    // Program and args to run after the session is killed (e.g. `docker rm -f <name>`).
+    pub cleanup_command: Option<Vec<String>>,
+    // ClientIdentity::owner of the network client that started it; None for the UI and stdio.
+    pub owner: Option<String>,
+}
+
+impl ActiveSession {
+    pub async fn append_output(&self, stream: &str, text: String) -> usize {
+        self.output.lock().await.push(stream, text)
+    }
+}
+
+/// Lines and bytes a stream's ring buffer dropped to stay within its limit.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DroppedOutput {
+    pub lines: u64,
+    pub bytes: u64,
+}
+
+/// The newest lines of one stream, at most `max_bytes` of text in total.
+#[derive(Debug)]
+struct OutputRing {
+    lines: VecDeque<SessionOutputLine>,
+    bytes: usize,
+    max_bytes: usize,
+    dropped: DroppedOutput,
+}
+
+impl OutputRing {
+    fn new(max_bytes: usize) -> Self {
+        Self { lines: VecDeque::new(), bytes: 0, max_bytes, dropped: DroppedOutput::default() }
+    }
+
+    fn push(&mut self, mut line: SessionOutputLine) {
+        // A single line over the limit keeps its beginning, so the ring never holds more than max_bytes.
+        let full_len = line.text.len();
+        if truncate_output(&mut line.text, self.max_bytes) { self.dropped.bytes += (full_len - line.text.len()) as u64; }
+        self.bytes += line.text.len();
+        self.lines.push_back(line);
+        while self.bytes > self.max_bytes {
+            let Some(oldest) = self.lines.pop_front() else { break };
+            self.bytes -= oldest.text.len();
+            self.dropped.lines += 1;
+            self.dropped.bytes += oldest.text.len() as u64;
+        }
+    }
+
+    fn range(&self, from: usize, to: usize) -> impl Iterator<Item = &SessionOutputLine> {
+        let start = self.lines.partition_point(|l| l.index < from);
+        self.lines.range(start..).take_while(move |l| l.index < to)
+    }
+}
+
+/// A session's output with one byte-bounded ring buffer per stream. Line indexes keep counting
+/// across dropped lines, so a reader's cursor stays valid and gaps show what was lost.
+#[derive(Debug)]
+pub struct SessionOutput {
+    next_index: usize,
+    stdout: OutputRing,
+    stderr: OutputRing,
+}
+
+impl SessionOutput {
+    pub fn new(max_bytes_per_stream: usize) -> Self {
+        Self { next_index: 0, stdout: OutputRing::new(max_bytes_per_stream), stderr: OutputRing::new(max_bytes_per_stream) }
+    }
+
+    /// Stores a line of "stdout" or "stderr" and returns its index.
+    pub fn push(&mut self, stream: &str, text: String) -> usize {
+        let index = self.next_index;
+        self.next_index += 1;
+        let line = SessionOutputLine { index, stream: stream.to_string(), text };
+        if stream == "stderr" { self.stderr.push(line) } else { self.stdout.push(line) }
+        index
+    }
+
+    /// Lines produced so far, including dropped ones.
+    pub fn total_lines(&self) -> usize {
+        self.next_index
+    }
+
+    /// Retained lines with indexes in `from..to`, in the order they were produced.
+    pub fn lines(&self, from: usize, to: usize) -> Vec<SessionOutputLine> {
+        let mut stdout = self.stdout.range(from, to).peekable();
+        let mut stderr = self.stderr.range(from, to).peekable();
+        let mut lines = Vec::new();
+        loop {
+            let next = match (stdout.peek(), stderr.peek()) {
+                (Some(out), Some(err)) => if out.index < err.index { stdout.next() } else { stderr.next() },
+                (Some(_), None) => stdout.next(),
+                (None, Some(_)) => stderr.next(),
+                (None, None) => break,
+            };
+            lines.extend(next.cloned());
+        }
+        lines
+    }
+
+    pub fn dropped_stdout(&self) -> DroppedOutput {
+        self.stdout.dropped
+    }
+
+    pub fn dropped_stderr(&self) -> DroppedOutput {
+        self.stderr.dropped
+    }
 }
 
 pub type ActiveSessionsMap = Arc<TokioMutex<HashMap<String, Arc<ActiveSession>>>>;
@@ -1,37 +1,554 @@
+use crate::config_layers::{load_layers, ConfigLayer, ConfigLayerFile, ConfigSource, LimitsLayer};
+use crate::config_profiles::available_profiles;
+use crate::settings::{persisted_files_root, settings_file_path, UserAppSettings};
 use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use once_cell::sync::Lazy;
 use regex::Regex;
 use shellexpand;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use tauri::Manager;
 use tracing::warn;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+// An allowed_directories entry naming a whole Windows drive, like `C:\` or `D:`.
+static DRIVE_ROOT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-zA-Z]:[\\/]?$").expect("drive root regex compiles"));
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct Config {
+    // The active workspace root: relative paths resolve against it and commands start in it.
     pub files_root: PathBuf,
+    // Named roots switchable with set_active_workspace. Empty means the single files_root.
+    #[serde(default)]
+    pub workspaces: Vec<WorkspaceRoot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_workspace: Option<String>,
     pub allowed_directories: Vec<PathBuf>,
+    // Globs that are never readable or writable, even inside allowed_directories.
+    #[serde(default)]
+    pub denied_globs: Vec<String>,
     pub blocked_commands: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_shell: Option<String>,
     pub log_level: String,
-    pub mcp_transport_mode: TransportMode,
+    // Transports started together, sharing one handler. Empty when MCP_TRANSPORT is 'disabled'.
+    pub mcp_transports: Vec<TransportMode>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mcp_sse_host: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mcp_sse_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_ws_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_ws_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_ws_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_http_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_http_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_http_path: Option<String>,
+    // PEM certificate chain and private key for serving the WebSocket and HTTP transports over TLS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_tls_cert: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_tls_key: Option<PathBuf>,
+    // Without a cert/key, generate a self-signed certificate once and reuse it.
+    #[serde(default)]
+    pub mcp_tls_self_signed: bool,
+    // Browser origins (e.g. "https://app.example.com") allowed to call the network transports.
+    #[serde(default)]
+    pub mcp_cors_allowed_origins: Vec<String>,
+    // Request headers browsers may send besides the ones MCP needs.
+    #[serde(default)]
+    pub mcp_cors_allowed_headers: Vec<String>,
+    #[serde(default)]
+    pub mcp_cors_allow_credentials: bool,
+    // OTLP/gRPC collector (e.g. "http://localhost:4317") that tool call spans and metrics are exported to. Only read at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
     pub file_read_line_limit: usize,
     pub file_write_line_limit: usize,
+    // get_process_details only returns process environments when this is enabled; they often hold secrets.
+    #[serde(default)]
+    pub expose_process_environment: bool,
+    // Keeps the git tools to reading: git_add, git_stage_hunks, git_commit, branch creation and checkout are disabled.
+    #[serde(default)]
+    pub git_read_only: bool,
+    // A restore that rewrites at least this many files in a git repository first records the working tree on a refs/mcp-checkpoints/ ref. 0 disables it.
+    #[serde(default)]
+    pub git_checkpoint_min_files: usize,
+    // Disables every tool that writes files, runs commands or signals processes. Set at startup only.
+    #[serde(default)]
+    pub read_only: bool,
+    // Which processes kill_process / kill_processes_matching may signal.
+    #[serde(default)]
+    pub kill_scope: KillScope,
+    // Process names that may always be killed, even outside the session scope.
+    #[serde(default)]
+    pub kill_allowed_process_names: Vec<String>,
+    // Tools hidden from tools/list and rejected when called. Changing it sends notifications/tools/list_changed.
+    #[serde(default)]
+    pub disabled_tools: Vec<String>,
+    // Put before every tool name in tools/list and expected on calls, e.g. "rg_", so clients that
+    // aggregate several servers see no collisions. Config keys such as disabled_tools use the plain names.
+    #[serde(default)]
+    pub tool_name_prefix: Option<String>,
+    // Tools whose calls wait for the client's approval via elicitation; "destructive" stands for all destructive tools.
+    #[serde(default)]
+    pub approval_required_tools: Vec<String>,
+    // Regexes; execute_command calls whose command matches one also wait for approval.
+    #[serde(default)]
+    pub approval_command_patterns: Vec<String>,
+    // Regexes whose matches are replaced in audit entries and error messages, on top of the built-in secret shapes.
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    // Regexes for argument keys whose values are replaced whole, on top of password, token, secret and the like.
+    #[serde(default)]
+    pub redact_keys: Vec<String>,
+    // Past this, content, old_string, new_string, command and pattern arguments are replaced in audit entries; 0 keeps them.
+    #[serde(default = "default_redact_max_value_bytes")]
+    pub redact_max_value_bytes: usize,
+    // Other strings in audit entries and error data are cut to this; 0 keeps them whole.
+    #[serde(default = "default_redact_max_string_bytes")]
+    pub redact_max_string_bytes: usize,
+    // Allow, deny or confirm rules by tool and path, checked in order before every tool call; the first match decides.
+    #[serde(default)]
+    pub policy_rules: Vec<PolicyRule>,
+    // What happens to running execute_command sessions when the app exits.
+    #[serde(default)]
+    pub shutdown_session_policy: ShutdownSessionPolicy,
+    // How long shutdown waits for running tool calls to finish.
+    #[serde(default = "default_shutdown_drain_timeout_ms")]
+    pub shutdown_drain_timeout_ms: u64,
+    // Size, concurrency and timeout ceilings shared by the filesystem, search, edit and terminal tools.
+    #[serde(default)]
+    pub limits: LimitsConfig,
     pub audit_log_file: PathBuf,
+    // Past this size the audit and fuzzy search logs are rotated; 0 never rotates by size.
     pub audit_log_max_size_bytes: u64,
+    // Rotate them once the current file is this old as well; 0 never rotates by age.
+    #[serde(default = "default_audit_log_max_age_hours")]
+    pub audit_log_max_age_hours: u64,
+    // Rotated files kept per log, newest first; 0 keeps any number.
+    #[serde(default = "default_audit_log_keep_files")]
+    pub audit_log_keep_files: usize,
+    // Rotated files older than this are deleted; 0 keeps them regardless of age.
+    #[serde(default = "default_audit_log_retention_days")]
+    pub audit_log_retention_days: u64,
+    // Gzip rotated files.
+    #[serde(default = "default_audit_log_compress")]
+    pub audit_log_compress: bool,
+    // Where audit entries are written. Only read at startup.
+    #[serde(default = "default_audit_sinks")]
+    pub audit_sinks: Vec<AuditSinkKind>,
+    // udp://host:port or unix:///path; unset means the local syslog daemon.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_syslog_address: Option<String>,
+    // Receives audit entries in JSON batches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_webhook_url: Option<String>,
+    // Keyring secret sent to audit_webhook_url as a bearer token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_webhook_token_secret: Option<String>,
     pub fuzzy_search_log_file: PathBuf,
     pub mcp_log_dir: PathBuf,
+    // Where snapshot_workspace writes `<id>.tar.gz` and `<id>.json`.
+    #[serde(default)]
+    pub snapshot_dir: PathBuf,
+    // Snapshots kept per directory; older ones are deleted after a new one is taken. 0 keeps all.
+    #[serde(default = "default_snapshot_keep")]
+    pub snapshot_keep: usize,
+    // Where set_config_value persists changes; values there override .env on the next start.
+    #[serde(default)]
+    pub settings_file: PathBuf,
+    #[serde(default)]
+    pub ssh_hosts: Vec<SshHostConfig>,
+    #[serde(default)]
+    pub container_targets: Vec<ContainerTargetConfig>,
+    // Other MCP servers whose tools are re-exported alongside these. Only read at startup.
+    #[serde(default)]
+    pub downstream_servers: Vec<DownstreamServerConfig>,
+    // Tools backed by a command or a WASM module, registered next to the built-in ones. Only read at startup.
+    #[serde(default)]
+    pub custom_tools: Vec<CustomToolConfig>,
+    // Every `<name>.wasm` here becomes a custom tool `<name>`, described by an optional `<name>.json`. Only read at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wasm_tools_dir: Option<PathBuf>,
+    // Names of secrets held in the OS keyring (set_secret_command). Values are never part of Config.
+    #[serde(default)]
+    pub secret_names: Vec<String>,
+    // The named profile (safe, dev, ci or a [profiles.<name>] table) applied on top of the config files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+    // Which layer (default, system, user, workspace, env, settings, runtime) supplied each key.
+    #[serde(skip)]
+    pub value_sources: BTreeMap<String, ConfigSource>,
+    #[serde(skip)]
+    pub layer_files: Vec<ConfigLayerFile>,
+    // Built-in profiles plus any [profiles.<name>] tables from the config files.
+    #[serde(skip)]
+    pub available_profiles: Vec<String>,
+}
+
+pub const DEFAULT_DENIED_GLOBS: &[&str] = &["**/.ssh/**", "**/.gnupg/**", "**/.aws/credentials", "**/*.pem", "**/*.key", "**/.env", "**/.env.*", "**/id_rsa*", "**/id_ed25519*"];
+
+pub const DEFAULT_BLOCKED_COMMANDS: &str = "sudo,su,rm,mkfs,fdisk,dd,reboot,shutdown,poweroff,halt,format,mount,umount,passwd,adduser,useradd,usermod,groupadd";
+
+/// Resource ceilings applied uniformly by the tools (the `[limits]` table in config.toml).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct LimitsConfig {
+    // Largest file read_file, read_multiple_files, write_file and edit_block will handle, and the largest URL body.
+    pub max_file_bytes: u64,
+    // Cap on text returned by one call: command output, session output and search matches.
+    pub max_output_bytes: usize,
+    // Running execute_command sessions allowed at once.
+    pub max_concurrent_sessions: usize,
+    // search_code and search_files calls allowed at once.
+    pub max_concurrent_searches: usize,
+    // Ceiling for every per-call `timeout_ms`; larger requests are clamped to it.
+    pub max_timeout_ms: u64,
+    pub url_fetch_timeout_ms: u64,
+    // Used by search_code and search_files when the call sets no timeout.
+    pub search_timeout_ms: u64,
+    // Calls per minute each client may make to each tool of a class; 0 is unlimited.
+    #[serde(default)]
+    pub read_calls_per_minute: u32,
+    #[serde(default)]
+    pub search_calls_per_minute: u32,
+    // Writes, commands and process signals (the tools read_only mode disables).
+    #[serde(default)]
+    pub exec_calls_per_minute: u32,
+    // Tool calls running at once over all transports; further calls wait in a queue.
+    #[serde(default = "default_max_concurrent_calls")]
+    pub max_concurrent_calls: usize,
+    // How long a queued call waits for a slot before failing.
+    #[serde(default = "default_call_queue_timeout_ms")]
+    pub call_queue_timeout_ms: u64,
+    // Largest tools/call result sent as one message; larger ones become a message_too_large error with a raw_handle.
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    // Budget for calls whose client sets no _meta.timeout_ms or _meta.deadline; 0 is none.
+    #[serde(default)]
+    pub call_deadline_ms: u64,
+    // How long path validation reuses a canonicalized path and its metadata; 0 disables the cache.
+    #[serde(default = "default_path_cache_ttl_ms")]
+    pub path_cache_ttl_ms: u64,
+    // Output kept per stream of a command session; older lines are dropped first.
+    #[serde(default = "default_max_session_output_bytes")]
+    pub max_session_output_bytes: usize,
+}
+
+fn default_max_concurrent_calls() -> usize { LimitsConfig::default().max_concurrent_calls }
+fn default_call_queue_timeout_ms() -> u64 { LimitsConfig::default().call_queue_timeout_ms }
+fn default_max_message_bytes() -> usize { LimitsConfig::default().max_message_bytes }
+fn default_path_cache_ttl_ms() -> u64 { LimitsConfig::default().path_cache_ttl_ms }
+fn default_max_session_output_bytes() -> usize { LimitsConfig::default().max_session_output_bytes }
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 10 * 1024 * 1024,
+            max_output_bytes: 1024 * 1024,
+            max_concurrent_sessions: 16,
+            max_concurrent_searches: 4,
+            max_timeout_ms: 600_000,
+            url_fetch_timeout_ms: 30_000,
+            search_timeout_ms: 30_000,
+            read_calls_per_minute: 0,
+            search_calls_per_minute: 0,
+            exec_calls_per_minute: 0,
+            max_concurrent_calls: 32,
+            call_queue_timeout_ms: 30_000,
+            // Fits a base64 image of max_file_bytes.
+            max_message_bytes: 16 * 1024 * 1024,
+            call_deadline_ms: 0,
+            path_cache_ttl_ms: 2_000,
+            max_session_output_bytes: 8 * 1024 * 1024,
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)] // Added Eq
+impl LimitsConfig {
+    /// Starts from the defaults and applies every key the merged `[limits]` layers set.
+    pub fn from_layer(layer: Option<&LimitsLayer>) -> Self {
+        let mut limits = Self::default();
+        let Some(layer) = layer else { return limits };
+        macro_rules! apply_limits {
+            ($($field:ident),* $(,)?) => { $( if let Some(v) = layer.$field { limits.$field = v; } )* };
+        }
+        apply_limits!(max_file_bytes, max_output_bytes, max_concurrent_sessions, max_concurrent_searches, max_timeout_ms, url_fetch_timeout_ms, search_timeout_ms, read_calls_per_minute, search_calls_per_minute, exec_calls_per_minute, max_concurrent_calls, call_queue_timeout_ms, max_message_bytes, call_deadline_ms, path_cache_ttl_ms, max_session_output_bytes);
+        limits
+    }
+}
+
+/// One root of a multi-root workspace (`[[workspaces]]` in config.toml, or the WORKSPACES JSON array).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct WorkspaceRoot {
+    pub name: String,
+    pub path: PathBuf,
+    // When set, only these directories (relative ones resolve against `path`) are usable inside this root.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_directories: Vec<PathBuf>,
+    // Globs relative to `path`, denied in addition to the global denied_globs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_globs: Vec<String>,
+}
+
+/// A named remote machine that `execute_command` can target with `execution_target: "ssh:<name>"`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct SshHostConfig {
+    pub name: String,
+    pub host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_file: Option<PathBuf>,
+    // Remote directory that corresponds to FILES_ROOT; commands start here.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_root: Option<String>,
+}
+
+/// A container that `execute_command` can target with `execution_target: "container:<name>"`.
+/// Set `container` to exec into a running container, or `image` to start a throwaway
+/// `run --rm` container with FILES_ROOT mounted at `workdir`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ContainerTargetConfig {
+    pub name: String,
+    // "docker" or "podman"; defaults to docker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default = "default_container_workdir")]
+    pub workdir: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_run_args: Vec<String>,
+}
+fn default_container_workdir() -> String { "/workspace".to_string() }
+
+/// Another MCP server whose tools are re-exported as `<name>__<tool>`. Set `command` to launch it
+/// over STDIO, or `url` to connect to its Streamable HTTP endpoint.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DownstreamServerConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    // Added to the environment the command inherits.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    // Keyring secret sent to `url` as a bearer token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_secret: Option<String>,
+}
+
+/// A site-specific tool declared in config. Set `command` to run a program or `wasm` to run a
+/// WASI module; either gets the call's arguments as JSON on stdin and answers on stdout.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct CustomToolConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // JSON Schema of the arguments, with `properties` and `required`; without it any object is accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    // `{name}` in an entry is replaced with the argument `name`, or removed when the call has none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    // Added to the environment the command inherits.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wasm: Option<PathBuf>,
+    // Unless set, the tool counts as mutating: hidden in read_only mode and from read tokens.
+    #[serde(default)]
+    pub read_only: bool,
+    // Covered by `destructive` in approval_required_tools and policy_rules.
+    #[serde(default)]
+    pub destructive: bool,
+    // Lets an argument put a leading `-` into an `args` entry, where the program would read it as an option.
+    #[serde(default)]
+    pub allow_dash_args: bool,
+    // Capped at limits.max_timeout_ms, which is also the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)] // Added Eq
 pub enum TransportMode {
     Stdio,
     Sse,
-    Disabled,
+    WebSocket,
+    // Streamable HTTP, the transport of MCP 2025-03-26.
+    Http,
+}
+
+impl TransportMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransportMode::Stdio => "stdio",
+            TransportMode::Sse => "sse",
+            TransportMode::WebSocket => "websocket",
+            TransportMode::Http => "http",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum KillScope {
+    // Any process the server's user can signal. Opt-in.
+    Any,
+    // Only processes started by execute_command sessions (and their descendants), plus kill_allowed_process_names.
+    #[default]
+    Sessions,
+}
+
+/// What a matching policy rule does with a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEffect {
+    Allow,
+    Deny,
+    // Waits for the client's approval, as approval_required_tools does.
+    Confirm,
+}
+
+/// One `[[policy_rules]]` entry (or an element of the POLICY_RULES JSON array).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct PolicyRule {
+    // Tool names, which may use * and ?, "category:<name>", "mutating" or "destructive"; empty matches every tool.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+    // Globs for the call's path arguments; ~ expands and relative ones resolve against files_root. Empty matches any call.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    pub effect: PolicyEffect,
+    // Shown in the denial or the approval prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditSinkKind {
+    // audit_log_file, rotated per the audit_log_* keys; the only sink query_audit_log reads.
+    File,
+    // RFC 5424 messages to audit_syslog_address.
+    Syslog,
+    // JSON POSTs to audit_webhook_url.
+    Webhook,
+    // OTLP log records to otlp_endpoint; needs the otel feature.
+    Otlp,
+}
+
+impl AuditSinkKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditSinkKind::File => "file",
+            AuditSinkKind::Syslog => "syslog",
+            AuditSinkKind::Webhook => "webhook",
+            AuditSinkKind::Otlp => "otlp",
+        }
+    }
+}
+
+impl FromStr for AuditSinkKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "file" => Ok(AuditSinkKind::File),
+            "syslog" => Ok(AuditSinkKind::Syslog),
+            "webhook" => Ok(AuditSinkKind::Webhook),
+            "otlp" => Ok(AuditSinkKind::Otlp),
+            _ => Err(anyhow::anyhow!("Invalid audit sink: {}. Valid options are 'file', 'syslog', 'webhook', 'otlp'.", s)),
+        }
+    }
+}
+
+/// Where the syslog audit sink sends its messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyslogAddress {
+    // host:port
+    Udp(String),
+    Unix(PathBuf),
+}
+
+impl SyslogAddress {
+    /// The local syslog daemon: /dev/log on Unix, UDP port 514 on localhost elsewhere.
+    pub fn local() -> Self {
+        if cfg!(unix) { SyslogAddress::Unix(PathBuf::from("/dev/log")) } else { SyslogAddress::Udp("127.0.0.1:514".to_string()) }
+    }
+}
+
+impl FromStr for SyslogAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(host_port) = s.strip_prefix("udp://").filter(|h| h.rsplit_once(':').is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())) {
+            return Ok(SyslogAddress::Udp(host_port.to_string()));
+        }
+        match s.strip_prefix("unix://") {
+            Some(path) if cfg!(unix) && path.starts_with('/') => Ok(SyslogAddress::Unix(PathBuf::from(path))),
+            _ => Err(anyhow::anyhow!("Invalid audit syslog address: {}. Use udp://host:port{}.", s, if cfg!(unix) { " or unix:///path/to/socket" } else { "" })),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum ShutdownSessionPolicy {
+    // Kill them, running each session's cleanup command, before the app exits.
+    #[default]
+    Terminate,
+    // Leave them running; their PIDs are logged and sent in the shutdown notification.
+    Detach,
+}
+
+impl FromStr for ShutdownSessionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "terminate" => Ok(ShutdownSessionPolicy::Terminate),
+            "detach" => Ok(ShutdownSessionPolicy::Detach),
+            _ => Err(anyhow::anyhow!("Invalid shutdown session policy: {}. Valid options are 'terminate', 'detach'.", s)),
+        }
+    }
+}
+
+fn default_shutdown_drain_timeout_ms() -> u64 { 10_000 }
+fn default_audit_log_max_age_hours() -> u64 { 24 }
+fn default_audit_log_keep_files() -> usize { 10 }
+fn default_snapshot_keep() -> usize { 10 }
+fn default_audit_log_retention_days() -> u64 { 30 }
+fn default_audit_log_compress() -> bool { true }
+fn default_audit_sinks() -> Vec<AuditSinkKind> { vec![AuditSinkKind::File] }
+fn default_redact_max_value_bytes() -> usize { 256 }
+fn default_redact_max_string_bytes() -> usize { 4096 }
+
+impl FromStr for KillScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "any" => Ok(KillScope::Any),
+            "sessions" => Ok(KillScope::Sessions),
+            _ => Err(anyhow::anyhow!("Invalid kill scope: {}. Valid options are 'any', 'sessions'.", s)),
+        }
+    }
 }
 
 impl FromStr for TransportMode {
@@ -41,23 +558,118 @@ impl FromStr for TransportMode {
         match s.to_lowercase().as_str() {
             "stdio" => Ok(TransportMode::Stdio),
             "sse" => Ok(TransportMode::Sse),
-            "disabled" => Ok(TransportMode::Disabled),
-            _ => Err(anyhow::anyhow!("Invalid MCP transport mode: {}. Valid options are 'stdio', 'sse', 'disabled'.", s)),
+            "websocket" | "ws" => Ok(TransportMode::WebSocket),
+            "http" | "streamable-http" => Ok(TransportMode::Http),
+            _ => Err(anyhow::anyhow!("Invalid MCP transport mode: {}. Valid options are 'stdio', 'sse', 'websocket', 'http', 'disabled'.", s)),
+        }
+    }
+}
+
+/// MCP_TRANSPORT: one transport, a comma-separated list such as "stdio,http", or "disabled".
+pub fn parse_transports(value: &str) -> Result<Vec<TransportMode>> {
+    let mut transports: Vec<TransportMode> = Vec::new();
+    for part in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        if part.eq_ignore_ascii_case("disabled") {
+            if value.split(',').filter(|p| !p.trim().is_empty()).count() > 1 {
+                anyhow::bail!("MCP_TRANSPORT '{}' combines 'disabled' with other transports", value);
+            }
+            return Ok(Vec::new());
         }
+        let transport = TransportMode::from_str(part)?;
+        if !transports.contains(&transport) { transports.push(transport); }
     }
+    Ok(transports)
 }
 
 pub fn expand_tilde(path_str: &str) -> Result<PathBuf, anyhow::Error> {
     Ok(PathBuf::from(shellexpand::tilde(path_str).as_ref()))
 }
 
+/// Expands and canonicalizes each root and its allowed_directories, rejecting duplicate names.
+fn resolve_workspaces(roots: &[WorkspaceRoot]) -> Result<Vec<WorkspaceRoot>> {
+    let mut resolved: Vec<WorkspaceRoot> = Vec::with_capacity(roots.len());
+    for root in roots {
+        if root.name.trim().is_empty() {
+            anyhow::bail!("Every [[workspaces]] entry needs a non-empty name");
+        }
+        if resolved.iter().any(|w| w.name == root.name) {
+            anyhow::bail!("Duplicate workspace name '{}'", root.name);
+        }
+        let path = expand_tilde(&root.path.to_string_lossy())?;
+        let path = path.canonicalize().context(format!("Workspace '{}' root does not exist: {}", root.name, path.display()))?;
+        if !path.is_dir() {
+            anyhow::bail!("Workspace '{}' root is not a directory: {}", root.name, path.display());
+        }
+        let allowed_directories = root.allowed_directories.iter()
+            .map(|d| {
+                let d = expand_tilde(&d.to_string_lossy())?;
+                let d = if d.is_absolute() { d } else { path.join(d) };
+                Ok(d.canonicalize().unwrap_or(d))
+            })
+            .collect::<Result<Vec<PathBuf>>>()?;
+        resolved.push(WorkspaceRoot { name: root.name.clone(), path, allowed_directories, denied_globs: root.denied_globs.clone() });
+    }
+    Ok(resolved)
+}
+
+/// The workspace root containing `path` (the most specific one if roots nest).
+pub fn workspace_containing<'a>(workspaces: &'a [WorkspaceRoot], path: &Path) -> Option<&'a WorkspaceRoot> {
+    workspaces.iter().filter(|w| path.starts_with(&w.path)).max_by_key(|w| w.path.components().count())
+}
+
 impl Config {
-    pub fn load(app_handle: &tauri::AppHandle) -> Result<Self> {
+    pub fn workspace_for_path(&self, path: &Path) -> Option<&WorkspaceRoot> {
+        workspace_containing(&self.workspaces, path)
+    }
+
+    /// Where `.mcp-rg.toml` lookups for `path` stop: its workspace root, else files_root.
+    pub fn root_for_path(&self, path: &Path) -> &Path {
+        self.workspace_for_path(path).map(|w| w.path.as_path()).unwrap_or(&self.files_root)
+    }
+
+    pub fn load(app_handle: &crate::AppHandle) -> Result<Self> {
+        Self::load_with_profile(app_handle, None)
+    }
+
+    /// Like `load`, but `profile_override` wins over --profile, MCP_PROFILE and the files.
+    pub fn load_with_profile(app_handle: &crate::AppHandle, profile_override: Option<&str>) -> Result<Self> {
         dotenvy::dotenv().ok();
 
-        let files_root_str = std::env::var("FILES_ROOT")
-            .context("FILES_ROOT environment variable must be set (e.g., ~/mcp_files or an absolute path)")?;
-        let initial_files_root = expand_tilde(&files_root_str)?;
+        let layered = load_layers(app_handle, profile_override)?;
+        let layer = &layered.values;
+        let mut value_sources = layered.sources.clone();
+
+        let settings_file = settings_file_path(app_handle)?;
+        let user_settings = UserAppSettings::load(&settings_file).unwrap_or_else(|e| {
+            warn!(path = %settings_file.display(), error = %e, "Ignoring unreadable settings file");
+            UserAppSettings::default()
+        });
+
+        let mut workspaces = resolve_workspaces(layer.workspaces.as_deref().unwrap_or_default())?;
+        let selected_workspace = match &layer.active_workspace {
+            Some(name) => Some(workspaces.iter().find(|w| &w.name == name)
+                .with_context(|| format!("active_workspace '{}' is not one of the configured workspaces", name))?),
+            None => None,
+        };
+
+        let initial_files_root = match (persisted_files_root(&user_settings), selected_workspace) {
+            (Some(root), _) => {
+                value_sources.insert("files_root".to_string(), ConfigSource::Settings);
+                root
+            }
+            (None, Some(workspace)) => {
+                if let Some(source) = layered.sources.get("active_workspace") { value_sources.insert("files_root".to_string(), *source); }
+                workspace.path.clone()
+            }
+            (None, None) => match (layer.files_root.as_deref(), workspaces.first()) {
+                (Some(files_root_str), _) => expand_tilde(files_root_str)?,
+                (None, Some(first)) => {
+                    if let Some(source) = layered.sources.get("workspaces") { value_sources.insert("files_root".to_string(), *source); }
+                    first.path.clone()
+                }
+                (None, None) => anyhow::bail!("files_root must be set in config.toml or the FILES_ROOT environment variable (e.g., ~/mcp_files or an absolute path), or at least one [[workspaces]] root configured"),
+            },
+        };
 
         let files_root = initial_files_root.canonicalize().or_else(|e| {
             warn!(path = %initial_files_root.display(), error = %e, "FILES_ROOT failed to canonicalize, attempting to create it.");
@@ -69,15 +681,29 @@ impl Config {
             anyhow::bail!("FILES_ROOT is not a valid directory: {:?}", files_root);
         }
 
-        let allowed_directories_str = std::env::var("ALLOWED_DIRECTORIES").unwrap_or_default();
-        let mut allowed_directories: Vec<PathBuf> = if allowed_directories_str.is_empty() {
+        // A files_root that is not one of the named roots joins them as "default".
+        let active_workspace = if workspaces.is_empty() {
+            None
+        } else {
+            if !workspaces.iter().any(|w| w.path == files_root) {
+                if workspaces.iter().any(|w| w.name == "default") {
+                    anyhow::bail!("files_root {} is not a configured workspace and the name 'default' is already taken", files_root.display());
+                }
+                workspaces.insert(0, WorkspaceRoot { name: "default".to_string(), path: files_root.clone(), allowed_directories: Vec::new(), denied_globs: Vec::new() });
+            }
+            workspaces.iter().find(|w| w.path == files_root).map(|w| w.name.clone())
+        };
+
+        let allowed_directories_list = layer.allowed_directories.clone().unwrap_or_default();
+        let is_full_access_entry = |s: &str| s == "/" || (cfg!(windows) && DRIVE_ROOT.is_match(s));
+        let mut allowed_directories: Vec<PathBuf> = if allowed_directories_list.is_empty() {
             vec![files_root.clone()]
-        } else if allowed_directories_str == "/" || (cfg!(windows) && Regex::new(r"^[a-zA-Z]:[\\/]?$").unwrap().is_match(&allowed_directories_str)) {
-            warn!("ALLOWED_DIRECTORIES is set to full filesystem access ('{}'). This is highly permissive.", allowed_directories_str);
-            vec![PathBuf::from(allowed_directories_str.trim_end_matches(|c| c == '/' || c == '\\'))]
+        } else if allowed_directories_list.len() == 1 && is_full_access_entry(&allowed_directories_list[0]) {
+            warn!("ALLOWED_DIRECTORIES is set to full filesystem access ('{}'). This is highly permissive.", allowed_directories_list[0]);
+            vec![PathBuf::from(allowed_directories_list[0].trim_end_matches(|c| c == '/' || c == '\\'))]
         } else {
-            allowed_directories_str
-                .split(',')
+            allowed_directories_list
+                .iter()
                 .map(|s| s.trim())
                 .filter(|s| !s.is_empty())
                 .map(|s| expand_tilde(s).context(format!("Failed to expand tilde for allowed_directory: {}", s)))
@@ -95,46 +721,91 @@ impl Config {
                 allowed_directories.push(files_root.clone());
             }
         }
+        // Every workspace root is reachable; per-root allowed_directories narrow it further.
+        allowed_directories.extend(workspaces.iter().map(|w| w.path.clone()));
         allowed_directories.sort();
         allowed_directories.dedup();
 
-        let blocked_commands_str = std::env::var("BLOCKED_COMMANDS")
-            .unwrap_or_else(|_| "sudo,su,rm,mkfs,fdisk,dd,reboot,shutdown,poweroff,halt,format,mount,umount,passwd,adduser,useradd,usermod,groupadd".to_string());
-        let blocked_commands = blocked_commands_str
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<String>>();
+        let denied_globs = layer.denied_globs.clone().unwrap_or_else(|| DEFAULT_DENIED_GLOBS.iter().map(|s| s.to_string()).collect());
 
-        let default_shell = std::env::var("DEFAULT_SHELL").ok().filter(|s| !s.is_empty());
-        let log_level = std::env::var("LOG_LEVEL").unwrap_or_else(|_| "info".to_string());
+        let blocked_commands = layer.blocked_commands.clone().unwrap_or_else(|| {
+            DEFAULT_BLOCKED_COMMANDS.split(',').map(str::to_string).collect()
+        });
+
+        let default_shell = layer.default_shell.clone().filter(|s| !s.is_empty());
+        let log_level = layer.log_level.clone().unwrap_or_else(|| "info".to_string());
 
-        let mcp_transport_mode_str = std::env::var("MCP_TRANSPORT").unwrap_or_else(|_| {
+        let mcp_transport_mode_str = layer.mcp_transport.clone().unwrap_or_else(|| {
             if cfg!(feature = "mcp-sse-server") { "sse".to_string() }
             else if cfg!(feature = "mcp-stdio-server") { "stdio".to_string() }
             else { "disabled".to_string() }
         });
-        let mcp_transport_mode = TransportMode::from_str(&mcp_transport_mode_str)?;
-        let mcp_sse_host = std::env::var("MCP_SSE_HOST").ok();
-        let mcp_sse_port = std::env::var("MCP_SSE_PORT")
-            .ok()
-            .and_then(|s| s.parse::<u16>().ok());
-
-        let file_read_line_limit = std::env::var("FILE_READ_LINE_LIMIT")
-            .unwrap_or_else(|_| "1000".to_string())
-            .parse::<usize>()
-            .context("Invalid FILE_READ_LINE_LIMIT")?;
-        let file_write_line_limit = std::env::var("FILE_WRITE_LINE_LIMIT")
-            .unwrap_or_else(|_| "50".to_string())
-            .parse::<usize>()
-            .context("Invalid FILE_WRITE_LINE_LIMIT")?;
-        
+        let mcp_transports = parse_transports(&mcp_transport_mode_str)?;
+        let mcp_sse_host = layer.mcp_sse_host.clone();
+        let mcp_sse_port = layer.mcp_sse_port;
+        let mcp_ws_host = layer.mcp_ws_host.clone();
+        let mcp_ws_port = layer.mcp_ws_port;
+        // axum routes must start with "/".
+        let route_path = |path: &Option<String>| path.clone().filter(|p| !p.is_empty()).map(|p| if p.starts_with('/') { p } else { format!("/{}", p) });
+        let mcp_ws_path = route_path(&layer.mcp_ws_path);
+        let mcp_http_host = layer.mcp_http_host.clone();
+        let mcp_http_port = layer.mcp_http_port;
+        let mcp_http_path = route_path(&layer.mcp_http_path);
+        let tls_path = |path: &Option<String>| path.as_deref().filter(|p| !p.is_empty()).map(expand_tilde).transpose();
+        let mcp_tls_cert = tls_path(&layer.mcp_tls_cert)?;
+        let mcp_tls_key = tls_path(&layer.mcp_tls_key)?;
+        if mcp_tls_cert.is_some() != mcp_tls_key.is_some() {
+            anyhow::bail!("MCP_TLS_CERT and MCP_TLS_KEY must be set together");
+        }
+        let mcp_tls_self_signed = layer.mcp_tls_self_signed.unwrap_or(false);
+        let mcp_cors_allowed_origins: Vec<String> = layer.mcp_cors_allowed_origins.clone().unwrap_or_default().into_iter().map(|o| o.trim_end_matches('/').to_string()).collect();
+        let mcp_cors_allowed_headers = layer.mcp_cors_allowed_headers.clone().unwrap_or_default();
+        let mcp_cors_allow_credentials = layer.mcp_cors_allow_credentials.unwrap_or(false);
+        // Browsers refuse credentialed responses for "*", and a wildcard with credentials would hand every site the user's session.
+        if mcp_cors_allow_credentials && mcp_cors_allowed_origins.iter().any(|o| o == "*") {
+            anyhow::bail!("MCP_CORS_ALLOW_CREDENTIALS cannot be used with MCP_CORS_ALLOWED_ORIGINS=*; list the origins instead");
+        }
+        let otlp_endpoint = layer.otlp_endpoint.clone().filter(|e| !e.is_empty());
+        if let Some(endpoint) = &otlp_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                anyhow::bail!("OTLP_ENDPOINT must be an http:// or https:// URL, got '{}'", endpoint);
+            }
+        }
+
+        let file_read_line_limit = layer.file_read_line_limit.unwrap_or(1000);
+        let file_write_line_limit = layer.file_write_line_limit.unwrap_or(50);
+        let expose_process_environment = layer.expose_process_environment.unwrap_or(false);
+        let git_read_only = layer.git_read_only.unwrap_or(false);
+        let git_checkpoint_min_files = layer.git_checkpoint_min_files.unwrap_or(0);
+        let read_only = layer.read_only.unwrap_or(false);
+        if read_only {
+            warn!("Read-only mode: write, edit, move, execute and kill tools are disabled");
+        }
+
+        let kill_scope = KillScope::from_str(layer.kill_scope.as_deref().unwrap_or("sessions"))?;
+        let kill_allowed_process_names = layer.kill_allowed_process_names.clone().unwrap_or_default();
+        let disabled_tools = layer.disabled_tools.clone().unwrap_or_default();
+        let tool_name_prefix = layer.tool_name_prefix.clone().filter(|p| !p.is_empty());
+        if let Some(prefix) = &tool_name_prefix { check_tool_name_prefix(prefix)?; }
+        let approval_required_tools = layer.approval_required_tools.clone().unwrap_or_default();
+        let approval_command_patterns = layer.approval_command_patterns.clone().unwrap_or_default();
+        let policy_rules = layer.policy_rules.clone().unwrap_or_default();
+        check_policy_rules(&policy_rules)?;
+        let redact_patterns = layer.redact_patterns.clone().unwrap_or_default();
+        crate::utils::redaction::check_patterns(&redact_patterns, "REDACT_PATTERNS")?;
+        let redact_keys = layer.redact_keys.clone().unwrap_or_default();
+        crate::utils::redaction::check_patterns(&redact_keys, "REDACT_KEYS")?;
+        let redact_max_value_bytes = layer.redact_max_value_bytes.unwrap_or_else(default_redact_max_value_bytes);
+        let redact_max_string_bytes = layer.redact_max_string_bytes.unwrap_or_else(default_redact_max_string_bytes);
+        let shutdown_session_policy = ShutdownSessionPolicy::from_str(layer.shutdown_session_policy.as_deref().unwrap_or("terminate"))?;
+        let shutdown_drain_timeout_ms = layer.shutdown_drain_timeout_ms.unwrap_or_else(default_shutdown_drain_timeout_ms);
+        let limits = LimitsConfig::from_layer(layer.limits.as_ref());
+
         let app_log_dir_base = app_handle.path().app_log_dir()
             .context("Failed to get app log directory from Tauri")?;
 
-        let mcp_log_dir_env_var = std::env::var("MCP_LOG_DIR").ok();
-        let mcp_log_dir_path = match mcp_log_dir_env_var {
-            Some(dir_str) if !dir_str.is_empty() => expand_tilde(&dir_str)?,
+        let mcp_log_dir_path = match layer.mcp_log_dir.as_deref() {
+            Some(dir_str) if !dir_str.is_empty() => expand_tilde(dir_str)?,
             _ => app_log_dir_base.join("mcp-rg-editor-logs"),
         };
 
@@ -144,41 +815,273 @@ impl Config {
         let mcp_log_dir = mcp_log_dir_path.canonicalize().context(format!("Failed to canonicalize MCP_LOG_DIR: {}", mcp_log_dir_path.display()))?;
 
         let audit_log_file = mcp_log_dir.join("audit_tool_calls.log");
-        let audit_log_max_size_bytes = std::env::var("AUDIT_LOG_MAX_SIZE_MB")
-            .unwrap_or_else(|_| "10".to_string())
-            .parse::<u64>()
-            .map(|mb| mb * 1024 * 1024) 
-            .unwrap_or(10 * 1024 * 1024); 
+        let audit_log_max_size_bytes = layer.audit_log_max_size_mb.unwrap_or(10) * 1024 * 1024;
+        let audit_log_max_age_hours = layer.audit_log_max_age_hours.unwrap_or_else(default_audit_log_max_age_hours);
+        let audit_log_keep_files = layer.audit_log_keep_files.unwrap_or_else(default_audit_log_keep_files);
+        let audit_log_retention_days = layer.audit_log_retention_days.unwrap_or_else(default_audit_log_retention_days);
+        let audit_log_compress = layer.audit_log_compress.unwrap_or_else(default_audit_log_compress);
         let fuzzy_search_log_file = mcp_log_dir.join("fuzzy_search_attempts.log");
+        let snapshot_dir_path = match layer.snapshot_dir.as_deref() {
+            Some(dir_str) if !dir_str.is_empty() => expand_tilde(dir_str)?,
+            _ => app_handle.path().app_data_dir().context("Failed to get app data directory from Tauri")?.join("snapshots"),
+        };
+        if !snapshot_dir_path.exists() {
+            std::fs::create_dir_all(&snapshot_dir_path).context(format!("Failed to create SNAPSHOT_DIR: {}", snapshot_dir_path.display()))?;
+        }
+        let snapshot_dir = snapshot_dir_path.canonicalize().context(format!("Failed to canonicalize SNAPSHOT_DIR: {}", snapshot_dir_path.display()))?;
+        let snapshot_keep = layer.snapshot_keep.unwrap_or_else(default_snapshot_keep);
+        let mut audit_sinks = Vec::new();
+        for name in layer.audit_sinks.clone().unwrap_or_default() {
+            let sink = AuditSinkKind::from_str(&name)?;
+            if !audit_sinks.contains(&sink) { audit_sinks.push(sink); }
+        }
+        if audit_sinks.is_empty() { audit_sinks = default_audit_sinks(); }
+        let audit_syslog_address = layer.audit_syslog_address.clone().filter(|a| !a.is_empty());
+        if let Some(address) = &audit_syslog_address { SyslogAddress::from_str(address)?; }
+        let audit_webhook_url = layer.audit_webhook_url.clone().filter(|u| !u.is_empty());
+        if let Some(url) = &audit_webhook_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                anyhow::bail!("AUDIT_WEBHOOK_URL must be an http:// or https:// URL, got '{}'", url);
+            }
+        }
+        if audit_sinks.contains(&AuditSinkKind::Webhook) && audit_webhook_url.is_none() {
+            anyhow::bail!("AUDIT_SINKS includes 'webhook' but AUDIT_WEBHOOK_URL is not set");
+        }
+        if audit_sinks.contains(&AuditSinkKind::Otlp) && otlp_endpoint.is_none() {
+            anyhow::bail!("AUDIT_SINKS includes 'otlp' but OTLP_ENDPOINT is not set");
+        }
+        let audit_webhook_token_secret = layer.audit_webhook_token_secret.clone().filter(|s| !s.is_empty());
 
-        Ok(Config {
+        let ssh_hosts = layer.ssh_hosts.clone().unwrap_or_default();
+        let container_targets = layer.container_targets.clone().unwrap_or_default();
+        for target in &container_targets {
+            if target.container.is_none() == target.image.is_none() {
+                anyhow::bail!("CONTAINER_TARGETS entry '{}' must set exactly one of 'container' or 'image'", target.name);
+            }
+        }
+
+        let downstream_servers = layer.downstream_servers.clone().unwrap_or_default();
+        check_downstream_servers(&downstream_servers)?;
+        let custom_tools = layer.custom_tools.clone().unwrap_or_default();
+        check_custom_tools(&custom_tools)?;
+        let wasm_tools_dir = layer.wasm_tools_dir.clone().filter(|d| !d.as_os_str().is_empty());
+
+        let mut config = Config {
             files_root,
+            workspaces,
+            active_workspace,
             allowed_directories,
+            denied_globs,
             blocked_commands,
             default_shell,
             log_level,
-            mcp_transport_mode,
+            mcp_transports,
             mcp_sse_host,
             mcp_sse_port,
+            mcp_ws_host,
+            mcp_ws_port,
+            mcp_ws_path,
+            mcp_http_host,
+            mcp_http_port,
+            mcp_http_path,
+            mcp_tls_cert,
+            mcp_tls_key,
+            mcp_tls_self_signed,
+            mcp_cors_allowed_origins,
+            mcp_cors_allowed_headers,
+            mcp_cors_allow_credentials,
+            otlp_endpoint,
             file_read_line_limit,
             file_write_line_limit,
+            expose_process_environment,
+            git_read_only,
+            git_checkpoint_min_files,
+            read_only,
+            kill_scope,
+            kill_allowed_process_names,
+            disabled_tools,
+            tool_name_prefix,
+            approval_required_tools,
+            approval_command_patterns,
+            redact_patterns,
+            redact_keys,
+            redact_max_value_bytes,
+            redact_max_string_bytes,
+            policy_rules,
+            shutdown_session_policy,
+            shutdown_drain_timeout_ms,
+            limits,
             audit_log_file,
             audit_log_max_size_bytes,
+            audit_log_max_age_hours,
+            audit_log_keep_files,
+            audit_log_retention_days,
+            audit_log_compress,
+            audit_sinks,
+            audit_syslog_address,
+            audit_webhook_url,
+            audit_webhook_token_secret,
             fuzzy_search_log_file,
             mcp_log_dir,
-        })
+            snapshot_dir,
+            snapshot_keep,
+            settings_file,
+            ssh_hosts,
+            container_targets,
+            downstream_servers,
+            custom_tools,
+            wasm_tools_dir,
+            secret_names: Vec::new(),
+            active_profile: layer.profile.clone(),
+            available_profiles: available_profiles(layer.profiles.as_ref()),
+            value_sources,
+            layer_files: layered.files,
+        };
+        user_settings.apply_to(&mut config);
+        Ok(config)
+    }
+}
+
+/// JSON Schemas for the runtime Config (as returned by get_config), settings.json and a
+/// config.toml layer. Backs the get_config_schema tool and `--print-config-schema`.
+pub fn config_json_schemas() -> serde_json::Value {
+    serde_json::json!({
+        "config": schemars::schema_for!(Config),
+        "settings_json": schemars::schema_for!(UserAppSettings),
+        "config_toml": schemars::schema_for!(ConfigLayer),
+    })
+}
+
+/// blocked_commands entries starting with this are regexes matched against the whole command line.
+pub const BLOCKED_COMMAND_REGEX_PREFIX: &str = "re:";
+
+/// The regex a blocked_commands entry matches against the trimmed command line. A plain entry is
+/// a command name, or a command and its first arguments such as `cargo publish`, that must start
+/// the line after any `VAR=value` assignments; a `re:` entry is used verbatim and matched anywhere.
+pub fn blocked_command_regex(entry: &str) -> Result<Regex> {
+    match entry.strip_prefix(BLOCKED_COMMAND_REGEX_PREFIX) {
+        Some(pattern) => Regex::new(pattern).context(format!("Invalid blocked command regex: {}", pattern)),
+        None => {
+            let words: Vec<String> = entry.split_whitespace().map(regex::escape).collect();
+            Regex::new(&format!(r"^(?:[a-zA-Z_][a-zA-Z0-9_]*=\S*\s+)*{}(?:\s.*|$)", words.join(r"\s+")))
+                .context(format!("Invalid regex for blocked command: {}", entry))
+        }
+    }
+}
+
+/// Rejects a blocked_commands list containing a `re:` entry that does not compile.
+pub fn check_blocked_commands(entries: &[String]) -> Result<()> {
+    entries.iter().try_for_each(|entry| blocked_command_regex(entry).map(|_| ()))
+}
+
+/// Clients commonly accept only letters, digits, '_' and '-' in tool names.
+pub fn check_tool_name_prefix(prefix: &str) -> Result<()> {
+    if let Some(bad) = prefix.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '_' || *c == '-')) {
+        anyhow::bail!("TOOL_NAME_PREFIX '{}' contains '{}'; tool names may only use letters, digits, '_' and '-'", prefix, bad);
+    }
+    Ok(())
+}
+
+/// Names become tool namespaces, so they are limited to letters, digits and '-' and must be unique.
+pub fn check_downstream_servers(servers: &[DownstreamServerConfig]) -> Result<()> {
+    for (i, server) in servers.iter().enumerate() {
+        if server.name.is_empty() || !server.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            anyhow::bail!("DOWNSTREAM_SERVERS name '{}' must be non-empty and use only letters, digits and '-'", server.name);
+        }
+        if servers[..i].iter().any(|s| s.name == server.name) {
+            anyhow::bail!("DOWNSTREAM_SERVERS has two servers named '{}'", server.name);
+        }
+        if server.command.is_none() == server.url.is_none() {
+            anyhow::bail!("DOWNSTREAM_SERVERS entry '{}' must set exactly one of 'command' or 'url'", server.name);
+        }
+    }
+    Ok(())
+}
+
+/// Custom tool names become tool names as they are, so they follow the MCP tool name rules and
+/// cannot contain the `__` of downstream tools.
+pub fn check_custom_tool_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 64 || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') || name.contains(crate::mcp::downstream::NAMESPACE_SEPARATOR) {
+        anyhow::bail!("Custom tool name '{}' must be 1-64 letters, digits, '_' or '-', without '{}'", name, crate::mcp::downstream::NAMESPACE_SEPARATOR);
+    }
+    Ok(())
+}
+
+pub fn check_custom_tools(tools: &[CustomToolConfig]) -> Result<()> {
+    for (i, tool) in tools.iter().enumerate() {
+        check_custom_tool_name(&tool.name)?;
+        if tools[..i].iter().any(|t| t.name == tool.name) {
+            anyhow::bail!("CUSTOM_TOOLS has two tools named '{}'", tool.name);
+        }
+        if tool.command.is_none() == tool.wasm.is_none() {
+            anyhow::bail!("CUSTOM_TOOLS entry '{}' must set exactly one of 'command' or 'wasm'", tool.name);
+        }
+        if tool.input_schema.as_ref().is_some_and(|s| !s.is_object()) {
+            anyhow::bail!("CUSTOM_TOOLS entry '{}' has an input_schema that is not a JSON object", tool.name);
+        }
+    }
+    Ok(())
+}
+
+/// Rejects rules whose path globs do not parse, or whose tools list names an unknown category.
+pub fn check_policy_rules(rules: &[PolicyRule]) -> Result<()> {
+    for (i, rule) in rules.iter().enumerate() {
+        for pattern in &rule.paths {
+            globset::Glob::new(pattern).context(format!("Invalid path glob in policy_rules[{}]: {}", i, pattern))?;
+        }
+        for selector in &rule.tools {
+            if let Some(category) = selector.strip_prefix("category:") {
+                if !crate::mcp::handler::TOOL_CATEGORIES.contains(&category) {
+                    anyhow::bail!("policy_rules[{}] names unknown tool category '{}'; valid ones are {}", i, category, crate::mcp::handler::TOOL_CATEGORIES.join(", "));
+                }
+            } else {
+                globset::Glob::new(selector).context(format!("Invalid tool pattern in policy_rules[{}]: {}", i, selector))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The live config shared by the tools, commands and watchers. Readers take an immutable
+/// snapshot, which costs a reference count and may be held across awaits; writers publish a
+/// changed copy, one writer at a time, so concurrent updates are not lost.
+pub struct ConfigState {
+    current: ArcSwap<Config>,
+    writer: Mutex<()>,
+}
+
+impl ConfigState {
+    pub fn new(config: Config) -> Self {
+        Self { current: ArcSwap::from_pointee(config), writer: Mutex::new(()) }
+    }
+
+    /// The config as of now. Later updates do not change a snapshot already taken.
+    pub fn snapshot(&self) -> Arc<Config> {
+        self.current.load_full()
+    }
+
+    /// Applies `change` to a copy of the config and publishes it, returning the closure's value
+    /// and the new snapshot.
+    pub fn update<R>(&self, change: impl FnOnce(&mut Config) -> R) -> (R, Arc<Config>) {
+        match self.try_update(|config| Ok::<R, std::convert::Infallible>(change(config))) {
+            Ok(updated) => updated,
+            Err(never) => match never {},
+        }
     }
 
-    pub fn get_blocked_command_regexes(&self) -> Result<Vec<Regex>> {
-        self.blocked_commands
-            .iter()
-            .map(|s| Regex::new(&format!(r"^(?:[a-zA-Z_][a-zA-Z0-9_]*=[^ ]* )*{}(?:\s.*|$)", regex::escape(s)))
-                .context(format!("Invalid regex for blocked command: {}", s)))
-            .collect()
+    /// Like `update`, but nothing is published when `change` fails.
+    pub fn try_update<R, E>(&self, change: impl FnOnce(&mut Config) -> Result<R, E>) -> Result<(R, Arc<Config>), E> {
+        // A panicking writer leaves the published config untouched, so the lock stays usable.
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let mut config = Config::clone(&self.current.load());
+        let result = change(&mut config)?;
+        let snapshot = Arc::new(config);
+        self.current.store(snapshot.clone());
+        Ok((result, snapshot))
     }
 }
 
-pub fn init_config_state(app_handle: &tauri::AppHandle) -> std::sync::Arc<std::sync::RwLock<Config>> {
+pub fn init_config_state(app_handle: &crate::AppHandle) -> Arc<ConfigState> {
     let config = Config::load(app_handle).expect("Failed to load configuration at startup");
-    std::sync::Arc::new(std::sync::RwLock::new(config))
+    Arc::new(ConfigState::new(config))
 }
\ No newline at end of file
@@ -0,0 +1,492 @@
+use crate::config::{Config, ContainerTargetConfig, CustomToolConfig, DownstreamServerConfig, PolicyRule, SshHostConfig, WorkspaceRoot};
+use crate::config_profiles::resolve_profile;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+use tracing::{debug, info};
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Where an effective config value came from, lowest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    Default,
+    System,
+    User,
+    Workspace,
+    // The named profile selected with --profile, MCP_PROFILE, `profile = "..."` or use_profile.
+    Profile,
+    Env,
+    // settings.json, written by set_config_value with persist: true.
+    Settings,
+    // Changed in memory by set_config_value during this session.
+    Runtime,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigLayerFile {
+    pub source: ConfigSource,
+    pub path: PathBuf,
+    pub loaded: bool,
+}
+
+/// One layer of configuration as written in config.toml. Every key is optional; later layers
+/// override earlier ones key by key. Environment variables are read into the same shape.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub files_root: Option<String>,
+    pub workspaces: Option<Vec<WorkspaceRoot>>,
+    pub active_workspace: Option<String>,
+    pub allowed_directories: Option<Vec<String>>,
+    pub denied_globs: Option<Vec<String>>,
+    pub blocked_commands: Option<Vec<String>>,
+    pub default_shell: Option<String>,
+    pub log_level: Option<String>,
+    pub mcp_transport: Option<String>,
+    pub mcp_sse_host: Option<String>,
+    pub mcp_sse_port: Option<u16>,
+    pub mcp_ws_host: Option<String>,
+    pub mcp_ws_port: Option<u16>,
+    pub mcp_ws_path: Option<String>,
+    pub mcp_http_host: Option<String>,
+    pub mcp_http_port: Option<u16>,
+    pub mcp_http_path: Option<String>,
+    pub mcp_tls_cert: Option<String>,
+    pub mcp_tls_key: Option<String>,
+    pub mcp_tls_self_signed: Option<bool>,
+    pub mcp_cors_allowed_origins: Option<Vec<String>>,
+    pub mcp_cors_allowed_headers: Option<Vec<String>>,
+    pub mcp_cors_allow_credentials: Option<bool>,
+    pub otlp_endpoint: Option<String>,
+    pub file_read_line_limit: Option<usize>,
+    pub file_write_line_limit: Option<usize>,
+    pub expose_process_environment: Option<bool>,
+    pub git_read_only: Option<bool>,
+    pub git_checkpoint_min_files: Option<usize>,
+    pub read_only: Option<bool>,
+    pub kill_scope: Option<String>,
+    pub kill_allowed_process_names: Option<Vec<String>>,
+    pub disabled_tools: Option<Vec<String>>,
+    pub tool_name_prefix: Option<String>,
+    pub approval_required_tools: Option<Vec<String>>,
+    pub approval_command_patterns: Option<Vec<String>>,
+    pub redact_patterns: Option<Vec<String>>,
+    pub policy_rules: Option<Vec<PolicyRule>>,
+    pub redact_keys: Option<Vec<String>>,
+    pub redact_max_value_bytes: Option<usize>,
+    pub redact_max_string_bytes: Option<usize>,
+    pub shutdown_session_policy: Option<String>,
+    pub shutdown_drain_timeout_ms: Option<u64>,
+    pub mcp_log_dir: Option<String>,
+    pub snapshot_dir: Option<String>,
+    pub snapshot_keep: Option<usize>,
+    pub audit_log_max_size_mb: Option<u64>,
+    pub audit_log_max_age_hours: Option<u64>,
+    pub audit_log_keep_files: Option<usize>,
+    pub audit_log_retention_days: Option<u64>,
+    pub audit_log_compress: Option<bool>,
+    pub audit_sinks: Option<Vec<String>>,
+    pub audit_syslog_address: Option<String>,
+    pub audit_webhook_url: Option<String>,
+    pub audit_webhook_token_secret: Option<String>,
+    pub limits: Option<LimitsLayer>,
+    pub ssh_hosts: Option<Vec<SshHostConfig>>,
+    pub container_targets: Option<Vec<ContainerTargetConfig>>,
+    pub downstream_servers: Option<Vec<DownstreamServerConfig>>,
+    pub custom_tools: Option<Vec<CustomToolConfig>>,
+    pub wasm_tools_dir: Option<PathBuf>,
+    // Name of the profile to apply on top of the config files.
+    pub profile: Option<String>,
+    // User-defined profiles (`[profiles.<name>]` tables); they replace built-ins of the same name.
+    pub profiles: Option<BTreeMap<String, ConfigLayer>>,
+}
+
+/// The `[limits]` table. Layers merge it key by key, like the top-level keys.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsLayer {
+    pub max_file_bytes: Option<u64>,
+    pub max_output_bytes: Option<usize>,
+    pub max_concurrent_sessions: Option<usize>,
+    pub max_concurrent_searches: Option<usize>,
+    pub max_timeout_ms: Option<u64>,
+    pub url_fetch_timeout_ms: Option<u64>,
+    pub search_timeout_ms: Option<u64>,
+    pub read_calls_per_minute: Option<u32>,
+    pub search_calls_per_minute: Option<u32>,
+    pub exec_calls_per_minute: Option<u32>,
+    pub max_concurrent_calls: Option<usize>,
+    pub call_queue_timeout_ms: Option<u64>,
+    pub max_message_bytes: Option<usize>,
+    pub call_deadline_ms: Option<u64>,
+    pub path_cache_ttl_ms: Option<u64>,
+    pub max_session_output_bytes: Option<usize>,
+}
+
+impl LimitsLayer {
+    fn from_env() -> Result<Option<Self>> {
+        let layer = Self {
+            max_file_bytes: env_parsed("MAX_FILE_BYTES")?,
+            max_output_bytes: env_parsed("MAX_OUTPUT_BYTES")?,
+            max_concurrent_sessions: env_parsed("MAX_CONCURRENT_SESSIONS")?,
+            max_concurrent_searches: env_parsed("MAX_CONCURRENT_SEARCHES")?,
+            max_timeout_ms: env_parsed("MAX_TIMEOUT_MS")?,
+            url_fetch_timeout_ms: env_parsed("URL_FETCH_TIMEOUT_MS")?,
+            search_timeout_ms: env_parsed("SEARCH_TIMEOUT_MS")?,
+            read_calls_per_minute: env_parsed("READ_CALLS_PER_MINUTE")?,
+            search_calls_per_minute: env_parsed("SEARCH_CALLS_PER_MINUTE")?,
+            exec_calls_per_minute: env_parsed("EXEC_CALLS_PER_MINUTE")?,
+            max_concurrent_calls: env_parsed("MAX_CONCURRENT_CALLS")?,
+            call_queue_timeout_ms: env_parsed("CALL_QUEUE_TIMEOUT_MS")?,
+            max_message_bytes: env_parsed("MAX_MESSAGE_BYTES")?,
+            call_deadline_ms: env_parsed("CALL_DEADLINE_MS")?,
+            path_cache_ttl_ms: env_parsed("PATH_CACHE_TTL_MS")?,
+            max_session_output_bytes: env_parsed("MAX_SESSION_OUTPUT_BYTES")?,
+        };
+        Ok((layer != Self::default()).then_some(layer))
+    }
+
+    fn merge_from(&mut self, other: LimitsLayer) {
+        macro_rules! merge_limits {
+            ($($field:ident),* $(,)?) => { $( if other.$field.is_some() { self.$field = other.$field; } )* };
+        }
+        merge_limits!(max_file_bytes, max_output_bytes, max_concurrent_sessions, max_concurrent_searches, max_timeout_ms, url_fetch_timeout_ms, search_timeout_ms, read_calls_per_minute, search_calls_per_minute, exec_calls_per_minute, max_concurrent_calls, call_queue_timeout_ms, max_message_bytes, call_deadline_ms, path_cache_ttl_ms, max_session_output_bytes);
+    }
+}
+
+impl From<&crate::config::LimitsConfig> for LimitsLayer {
+    fn from(limits: &crate::config::LimitsConfig) -> Self {
+        Self {
+            max_file_bytes: Some(limits.max_file_bytes),
+            max_output_bytes: Some(limits.max_output_bytes),
+            max_concurrent_sessions: Some(limits.max_concurrent_sessions),
+            max_concurrent_searches: Some(limits.max_concurrent_searches),
+            max_timeout_ms: Some(limits.max_timeout_ms),
+            url_fetch_timeout_ms: Some(limits.url_fetch_timeout_ms),
+            search_timeout_ms: Some(limits.search_timeout_ms),
+            read_calls_per_minute: Some(limits.read_calls_per_minute),
+            search_calls_per_minute: Some(limits.search_calls_per_minute),
+            exec_calls_per_minute: Some(limits.exec_calls_per_minute),
+            max_concurrent_calls: Some(limits.max_concurrent_calls),
+            call_queue_timeout_ms: Some(limits.call_queue_timeout_ms),
+            max_message_bytes: Some(limits.max_message_bytes),
+            call_deadline_ms: Some(limits.call_deadline_ms),
+            path_cache_ttl_ms: Some(limits.path_cache_ttl_ms),
+            max_session_output_bytes: Some(limits.max_session_output_bytes),
+        }
+    }
+}
+
+fn env_string(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|s| !s.trim().is_empty())
+}
+
+fn env_list(name: &str) -> Option<Vec<String>> {
+    env_string(name).map(|s| s.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+}
+
+fn env_bool(name: &str) -> Option<bool> {
+    env_string(name).map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+fn env_parsed<T: std::str::FromStr>(name: &str) -> Result<Option<T>>
+where
+    T::Err: std::fmt::Display,
+{
+    env_string(name)
+        .map(|s| s.trim().parse::<T>().map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e)))
+        .transpose()
+}
+
+impl ConfigLayer {
+    pub fn from_toml_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).context(format!("Failed to read config file: {}", path.display()))?;
+        toml::from_str(&contents).context(format!("Invalid config file: {}", path.display()))
+    }
+
+    /// The environment layer. Variable names match the keys upper-cased (FILES_ROOT, LOG_LEVEL, ...);
+    /// lists are comma-separated and SSH_HOSTS / CONTAINER_TARGETS are JSON arrays.
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            files_root: env_string("FILES_ROOT"),
+            workspaces: env_string("WORKSPACES")
+                .map(|json_str| serde_json::from_str::<Vec<WorkspaceRoot>>(&json_str)
+                    .context("Invalid WORKSPACES: expected a JSON array like [{\"name\":\"api\",\"path\":\"~/src/api\"}]"))
+                .transpose()?,
+            active_workspace: env_string("ACTIVE_WORKSPACE"),
+            allowed_directories: env_list("ALLOWED_DIRECTORIES"),
+            denied_globs: env_list("DENIED_GLOBS"),
+            blocked_commands: env_list("BLOCKED_COMMANDS"),
+            default_shell: env_string("DEFAULT_SHELL"),
+            log_level: env_string("LOG_LEVEL"),
+            mcp_transport: env_string("MCP_TRANSPORT"),
+            mcp_sse_host: env_string("MCP_SSE_HOST"),
+            // An unparsable port falls back to the default, as it always has.
+            mcp_sse_port: env_parsed("MCP_SSE_PORT")?,
+            mcp_ws_host: env_string("MCP_WS_HOST"),
+            mcp_ws_port: env_parsed("MCP_WS_PORT")?,
+            mcp_ws_path: env_string("MCP_WS_PATH"),
+            mcp_http_host: env_string("MCP_HTTP_HOST"),
+            mcp_http_port: env_parsed("MCP_HTTP_PORT")?,
+            mcp_http_path: env_string("MCP_HTTP_PATH"),
+            mcp_tls_cert: env_string("MCP_TLS_CERT"),
+            mcp_tls_key: env_string("MCP_TLS_KEY"),
+            mcp_tls_self_signed: env_bool("MCP_TLS_SELF_SIGNED"),
+            mcp_cors_allowed_origins: env_list("MCP_CORS_ALLOWED_ORIGINS"),
+            mcp_cors_allowed_headers: env_list("MCP_CORS_ALLOWED_HEADERS"),
+            mcp_cors_allow_credentials: env_bool("MCP_CORS_ALLOW_CREDENTIALS"),
+            // The standard OpenTelemetry variable works too.
+            otlp_endpoint: env_string("OTLP_ENDPOINT").or_else(|| env_string("OTEL_EXPORTER_OTLP_ENDPOINT")),
+            file_read_line_limit: env_parsed("FILE_READ_LINE_LIMIT")?,
+            file_write_line_limit: env_parsed("FILE_WRITE_LINE_LIMIT")?,
+            expose_process_environment: env_bool("EXPOSE_PROCESS_ENVIRONMENT"),
+            git_read_only: env_bool("GIT_READ_ONLY"),
+            git_checkpoint_min_files: env_parsed("GIT_CHECKPOINT_MIN_FILES")?,
+            // `--read-only` on the command line counts as the environment layer.
+            read_only: if std::env::args().skip(1).any(|a| a == "--read-only") { Some(true) } else { env_bool("READ_ONLY") },
+            kill_scope: env_string("KILL_SCOPE"),
+            kill_allowed_process_names: env_list("KILL_ALLOWED_PROCESS_NAMES"),
+            disabled_tools: env_list("DISABLED_TOOLS"),
+            tool_name_prefix: env_string("TOOL_NAME_PREFIX"),
+            approval_required_tools: env_list("APPROVAL_REQUIRED_TOOLS"),
+            approval_command_patterns: env_list("APPROVAL_COMMAND_PATTERNS"),
+            redact_patterns: env_list("REDACT_PATTERNS"),
+            policy_rules: env_string("POLICY_RULES")
+                .map(|json_str| serde_json::from_str::<Vec<PolicyRule>>(&json_str)
+                    .context("Invalid POLICY_RULES: expected a JSON array like [{\"tools\":[\"edit_block\"],\"paths\":[\"~/projects/**\"],\"effect\":\"allow\"}]"))
+                .transpose()?,
+            redact_keys: env_list("REDACT_KEYS"),
+            redact_max_value_bytes: env_parsed("REDACT_MAX_VALUE_BYTES")?,
+            redact_max_string_bytes: env_parsed("REDACT_MAX_STRING_BYTES")?,
+            shutdown_session_policy: env_string("SHUTDOWN_SESSION_POLICY"),
+            shutdown_drain_timeout_ms: env_parsed("SHUTDOWN_DRAIN_TIMEOUT_MS")?,
+            mcp_log_dir: env_string("MCP_LOG_DIR"),
+            snapshot_dir: env_string("SNAPSHOT_DIR"),
+            snapshot_keep: env_parsed("SNAPSHOT_KEEP")?,
+            audit_log_max_size_mb: env_parsed("AUDIT_LOG_MAX_SIZE_MB")?,
+            audit_log_max_age_hours: env_parsed("AUDIT_LOG_MAX_AGE_HOURS")?,
+            audit_log_keep_files: env_parsed("AUDIT_LOG_KEEP_FILES")?,
+            audit_log_retention_days: env_parsed("AUDIT_LOG_RETENTION_DAYS")?,
+            audit_log_compress: env_bool("AUDIT_LOG_COMPRESS"),
+            audit_sinks: env_list("AUDIT_SINKS"),
+            audit_syslog_address: env_string("AUDIT_SYSLOG_ADDRESS"),
+            audit_webhook_url: env_string("AUDIT_WEBHOOK_URL"),
+            audit_webhook_token_secret: env_string("AUDIT_WEBHOOK_TOKEN_SECRET"),
+            limits: LimitsLayer::from_env()?,
+            ssh_hosts: env_string("SSH_HOSTS")
+                .map(|json_str| serde_json::from_str::<Vec<SshHostConfig>>(&json_str)
+                    .context("Invalid SSH_HOSTS: expected a JSON array like [{\"name\":\"build\",\"host\":\"10.0.0.5\",\"user\":\"ci\",\"remote_root\":\"/srv/work\"}]"))
+                .transpose()?,
+            container_targets: env_string("CONTAINER_TARGETS")
+                .map(|json_str| serde_json::from_str::<Vec<ContainerTargetConfig>>(&json_str)
+                    .context("Invalid CONTAINER_TARGETS: expected a JSON array like [{\"name\":\"rust\",\"image\":\"rust:1.78\"}]"))
+                .transpose()?,
+            downstream_servers: env_string("DOWNSTREAM_SERVERS")
+                .map(|json_str| serde_json::from_str::<Vec<DownstreamServerConfig>>(&json_str)
+                    .context("Invalid DOWNSTREAM_SERVERS: expected a JSON array like [{\"name\":\"db\",\"command\":\"npx\",\"args\":[\"-y\",\"some-mcp-server\"]}]"))
+                .transpose()?,
+            custom_tools: env_string("CUSTOM_TOOLS")
+                .map(|json_str| serde_json::from_str::<Vec<CustomToolConfig>>(&json_str)
+                    .context("Invalid CUSTOM_TOOLS: expected a JSON array like [{\"name\":\"lint\",\"command\":\"./scripts/lint.sh\",\"args\":[\"{path}\"]}]"))
+                .transpose()?,
+            wasm_tools_dir: env_string("WASM_TOOLS_DIR").map(PathBuf::from),
+            profile: env_string("MCP_PROFILE"),
+            profiles: None,
+        })
+    }
+}
+
+/// `--profile <name>` or `--profile=<name>` on the command line.
+fn cli_profile() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--profile" { return args.next(); }
+        if let Some(name) = arg.strip_prefix("--profile=") { return Some(name.to_string()); }
+    }
+    None
+}
+
+/// The merged layers plus which layer supplied each key (keyed by Config field name).
+#[derive(Debug, Default)]
+pub struct LayeredConfig {
+    pub values: ConfigLayer,
+    pub sources: BTreeMap<String, ConfigSource>,
+    pub files: Vec<ConfigLayerFile>,
+}
+
+impl LayeredConfig {
+    fn merge(&mut self, layer: ConfigLayer, source: ConfigSource) {
+        macro_rules! merge_keys {
+            ($($field:ident => $config_key:literal),* $(,)?) => {
+                $(
+                    if layer.$field.is_some() {
+                        self.values.$field = layer.$field;
+                        self.sources.insert($config_key.to_string(), source);
+                    }
+                )*
+            };
+        }
+        merge_keys!(
+            files_root => "files_root",
+            workspaces => "workspaces",
+            active_workspace => "active_workspace",
+            allowed_directories => "allowed_directories",
+            denied_globs => "denied_globs",
+            blocked_commands => "blocked_commands",
+            default_shell => "default_shell",
+            log_level => "log_level",
+            mcp_transport => "mcp_transports",
+            mcp_sse_host => "mcp_sse_host",
+            mcp_sse_port => "mcp_sse_port",
+            mcp_ws_host => "mcp_ws_host",
+            mcp_ws_port => "mcp_ws_port",
+            mcp_ws_path => "mcp_ws_path",
+            mcp_http_host => "mcp_http_host",
+            mcp_http_port => "mcp_http_port",
+            mcp_http_path => "mcp_http_path",
+            mcp_tls_cert => "mcp_tls_cert",
+            mcp_tls_key => "mcp_tls_key",
+            mcp_tls_self_signed => "mcp_tls_self_signed",
+            mcp_cors_allowed_origins => "mcp_cors_allowed_origins",
+            mcp_cors_allowed_headers => "mcp_cors_allowed_headers",
+            mcp_cors_allow_credentials => "mcp_cors_allow_credentials",
+            otlp_endpoint => "otlp_endpoint",
+            file_read_line_limit => "file_read_line_limit",
+            file_write_line_limit => "file_write_line_limit",
+            expose_process_environment => "expose_process_environment",
+            git_read_only => "git_read_only",
+            git_checkpoint_min_files => "git_checkpoint_min_files",
+            read_only => "read_only",
+            kill_scope => "kill_scope",
+            kill_allowed_process_names => "kill_allowed_process_names",
+            disabled_tools => "disabled_tools",
+            tool_name_prefix => "tool_name_prefix",
+            approval_required_tools => "approval_required_tools",
+            approval_command_patterns => "approval_command_patterns",
+            redact_patterns => "redact_patterns",
+            policy_rules => "policy_rules",
+            redact_keys => "redact_keys",
+            redact_max_value_bytes => "redact_max_value_bytes",
+            redact_max_string_bytes => "redact_max_string_bytes",
+            shutdown_session_policy => "shutdown_session_policy",
+            shutdown_drain_timeout_ms => "shutdown_drain_timeout_ms",
+            mcp_log_dir => "mcp_log_dir",
+            snapshot_dir => "snapshot_dir",
+            snapshot_keep => "snapshot_keep",
+            audit_log_max_size_mb => "audit_log_max_size_bytes",
+            audit_log_max_age_hours => "audit_log_max_age_hours",
+            audit_log_keep_files => "audit_log_keep_files",
+            audit_log_retention_days => "audit_log_retention_days",
+            audit_log_compress => "audit_log_compress",
+            audit_sinks => "audit_sinks",
+            audit_syslog_address => "audit_syslog_address",
+            audit_webhook_url => "audit_webhook_url",
+            audit_webhook_token_secret => "audit_webhook_token_secret",
+            ssh_hosts => "ssh_hosts",
+            container_targets => "container_targets",
+            downstream_servers => "downstream_servers",
+            custom_tools => "custom_tools",
+            wasm_tools_dir => "wasm_tools_dir",
+        );
+        // The [limits] table merges per key; its source is the highest layer that set any of them.
+        if let Some(limits) = layer.limits {
+            self.values.limits.get_or_insert_with(LimitsLayer::default).merge_from(limits);
+            self.sources.insert("limits".to_string(), source);
+        }
+        if let Some(name) = layer.profile {
+            self.values.profile = Some(name);
+            self.sources.insert("active_profile".to_string(), source);
+        }
+        if let Some(profiles) = layer.profiles {
+            self.values.profiles.get_or_insert_with(BTreeMap::new).extend(profiles);
+        }
+    }
+}
+
+/// Every serialized config value annotated with the layer it came from, plus the layer files
+/// that were considered. Backs the show_effective_config tool.
+pub fn effective_config_report(config: &Config) -> serde_json::Value {
+    let values = match serde_json::to_value(config) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let annotated: serde_json::Map<String, serde_json::Value> = values.into_iter().map(|(key, value)| {
+        let source = config.value_sources.get(&key).copied().unwrap_or(ConfigSource::Default);
+        (key, serde_json::json!({ "value": value, "source": source }))
+    }).collect();
+    serde_json::json!({
+        "precedence": ["default", "system", "user", "workspace", "profile", "env", "settings", "runtime"],
+        "values": annotated,
+        "layer_files": config.layer_files,
+    })
+}
+
+/// The per-user config.toml in Tauri's app config directory, next to settings.json.
+pub fn user_config_path(app_handle: &crate::AppHandle) -> Option<PathBuf> {
+    app_handle.path().app_config_dir().ok().map(|dir| dir.join(CONFIG_FILE_NAME))
+}
+
+fn system_config_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("PROGRAMDATA").map(|dir| PathBuf::from(dir).join("mcp-rg-editor").join(CONFIG_FILE_NAME))
+    } else {
+        Some(PathBuf::from("/etc/mcp-rg-editor").join(CONFIG_FILE_NAME))
+    }
+}
+
+/// The workspace layer lives next to where the server was launched, so a project checkout
+/// can carry its own `.mcp-rg-editor/config.toml`.
+fn workspace_config_path() -> Option<PathBuf> {
+    std::env::current_dir().ok().map(|dir| dir.join(".mcp-rg-editor").join(CONFIG_FILE_NAME))
+}
+
+/// Loads system → user → workspace config.toml files, then the selected profile, then the
+/// environment on top. `MCP_CONFIG_FILE` replaces the workspace path when set. The profile is
+/// `profile_override` if given, else `--profile`, else `MCP_PROFILE`, else the files' `profile` key.
+pub fn load_layers(app_handle: &crate::AppHandle, profile_override: Option<&str>) -> Result<LayeredConfig> {
+    let user_config_path = user_config_path(app_handle);
+    let workspace_path = env_string("MCP_CONFIG_FILE").map(PathBuf::from).or_else(workspace_config_path);
+    let candidates = [
+        (ConfigSource::System, system_config_path()),
+        (ConfigSource::User, user_config_path),
+        (ConfigSource::Workspace, workspace_path),
+    ];
+
+    let mut layered = LayeredConfig::default();
+    for (source, path) in candidates {
+        let Some(path) = path else { continue };
+        let loaded = path.is_file();
+        if loaded {
+            let layer = ConfigLayer::from_toml_file(&path)?;
+            info!(path = %path.display(), layer = ?source, "Loaded config layer");
+            layered.merge(layer, source);
+        } else {
+            debug!(path = %path.display(), layer = ?source, "Config layer file not present");
+        }
+        layered.files.push(ConfigLayerFile { source, path, loaded });
+    }
+
+    let mut env_layer = ConfigLayer::from_env()?;
+    let selected_profile = match (profile_override, cli_profile(), env_layer.profile.take()) {
+        (Some(name), _, _) => Some((name.to_string(), ConfigSource::Runtime)),
+        (None, Some(name), _) | (None, None, Some(name)) => Some((name, ConfigSource::Env)),
+        (None, None, None) => {
+            let file_source = layered.sources.get("active_profile").copied().unwrap_or(ConfigSource::Default);
+            layered.values.profile.clone().map(|name| (name, file_source))
+        }
+    };
+    if let Some((name, selected_by)) = selected_profile {
+        let profile_layer = resolve_profile(&name, layered.values.profiles.as_ref())?;
+        info!(profile = %name, selected_by = ?selected_by, "Applying config profile");
+        layered.merge(profile_layer, ConfigSource::Profile);
+        layered.values.profile = Some(name);
+        layered.sources.insert("active_profile".to_string(), selected_by);
+    }
+    layered.merge(env_layer, ConfigSource::Env);
+    Ok(layered)
+}
@@ -0,0 +1,109 @@
+use crate::config::{Config, DEFAULT_BLOCKED_COMMANDS};
+use crate::config_layers::{ConfigLayer, ConfigSource, LimitsLayer};
+use crate::config_watcher::{copy_changed_values, ConfigReloadOutcome};
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+pub const BUILT_IN_PROFILES: &[&str] = &["safe", "dev", "ci"];
+
+/// Keys a profile switch applies while the server is running. Anything else a profile sets
+/// (files_root, transport, ...) only takes effect on the next start.
+pub const PROFILE_SWITCHABLE_KEYS: &[&str] = &[
+    "allowed_directories",
+    "blocked_commands",
+    "default_shell",
+    "log_level",
+    "file_read_line_limit",
+    "file_write_line_limit",
+    "expose_process_environment",
+    "git_read_only",
+    "kill_scope",
+    "kill_allowed_process_names",
+    "disabled_tools",
+    "approval_required_tools",
+    "approval_command_patterns",
+    "shutdown_session_policy",
+    "shutdown_drain_timeout_ms",
+    "limits",
+];
+
+fn blocked_with(extra: &[&str]) -> Vec<String> {
+    DEFAULT_BLOCKED_COMMANDS.split(',').chain(extra.iter().copied()).map(str::to_string).collect()
+}
+
+fn built_in_profile(name: &str) -> Option<ConfigLayer> {
+    let layer = match name {
+        // Locked down: FILES_ROOT only, no network or remote tools, small writes, session-only kills, rate-limited searches and execs.
+        "safe" => ConfigLayer {
+            allowed_directories: Some(Vec::new()),
+            blocked_commands: Some(blocked_with(&["curl", "wget", "ssh", "scp", "rsync", "nc", "git", "docker", "podman", "kill", "pkill", "killall"])),
+            file_read_line_limit: Some(500),
+            file_write_line_limit: Some(50),
+            expose_process_environment: Some(false),
+            git_read_only: Some(true),
+            kill_scope: Some("sessions".to_string()),
+            kill_allowed_process_names: Some(Vec::new()),
+            limits: Some(LimitsLayer { max_file_bytes: Some(2 * 1024 * 1024), max_concurrent_sessions: Some(4), max_timeout_ms: Some(120_000), search_calls_per_minute: Some(60), exec_calls_per_minute: Some(30), ..Default::default() }),
+            ..Default::default()
+        },
+        // Permissive local development.
+        "dev" => ConfigLayer {
+            blocked_commands: Some(blocked_with(&[])),
+            file_read_line_limit: Some(5000),
+            file_write_line_limit: Some(1000),
+            expose_process_environment: Some(true),
+            kill_scope: Some("any".to_string()),
+            ..Default::default()
+        },
+        // Unattended runs: generous limits, but nothing outside the server's own sessions is touched.
+        "ci" => ConfigLayer {
+            blocked_commands: Some(blocked_with(&[])),
+            file_read_line_limit: Some(5000),
+            file_write_line_limit: Some(2000),
+            expose_process_environment: Some(false),
+            kill_scope: Some("sessions".to_string()),
+            ..Default::default()
+        },
+        _ => return None,
+    };
+    Some(layer)
+}
+
+/// Every profile name that can be selected: the built-ins plus `[profiles.<name>]` tables.
+pub fn available_profiles(user_profiles: Option<&BTreeMap<String, ConfigLayer>>) -> Vec<String> {
+    let mut names: Vec<String> = BUILT_IN_PROFILES.iter().map(|s| s.to_string()).collect();
+    names.extend(user_profiles.into_iter().flat_map(|p| p.keys().cloned()));
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// The layer for a profile name. A `[profiles.<name>]` table replaces a built-in of the same name.
+pub fn resolve_profile(name: &str, user_profiles: Option<&BTreeMap<String, ConfigLayer>>) -> Result<ConfigLayer> {
+    let layer = user_profiles.and_then(|p| p.get(name).cloned())
+        .or_else(|| built_in_profile(name))
+        .ok_or_else(|| anyhow::anyhow!("Unknown config profile '{}'. Available profiles: {}", name, available_profiles(user_profiles).join(", ")))?;
+    if layer.profile.is_some() || layer.profiles.is_some() {
+        anyhow::bail!("Config profile '{}' cannot select or define other profiles", name);
+    }
+    Ok(layer)
+}
+
+/// Applies a config reloaded with a new profile to the running config. Keys set by the old or
+/// the new profile are switched, including ones changed with set_config_value this session,
+/// because picking a profile is an explicit request for its values.
+pub fn apply_profile_switch(current: &mut Config, reloaded: &Config) -> ConfigReloadOutcome {
+    let from_profile = |config: &Config, key: &str| config.value_sources.get(key) == Some(&ConfigSource::Profile);
+    let applied = copy_changed_values(current, reloaded, |config, key| {
+        key == "active_profile" || (PROFILE_SWITCHABLE_KEYS.contains(&key) && (from_profile(config, key) || from_profile(reloaded, key)))
+    });
+    let mut restart_required = Vec::new();
+    if let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) = (serde_json::to_value(&*current), serde_json::to_value(reloaded)) {
+        for (key, new_value) in new {
+            if from_profile(reloaded, &key) && !PROFILE_SWITCHABLE_KEYS.contains(&key.as_str()) && old.get(&key) != Some(&new_value) {
+                restart_required.push(key);
+            }
+        }
+    }
+    ConfigReloadOutcome { applied, restart_required }
+}
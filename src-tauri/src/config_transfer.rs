@@ -0,0 +1,314 @@
+use crate::config::{check_blocked_commands, check_custom_tools, check_downstream_servers, check_policy_rules, check_tool_name_prefix, parse_transports, AuditSinkKind, Config, ConfigState, KillScope, ShutdownSessionPolicy, SyslogAddress};
+use crate::config_layers::{user_config_path, ConfigLayer, ConfigSource, LimitsLayer};
+use crate::config_watcher::reload_config;
+use crate::error::AppError;
+use crate::utils::path_utils::compile_denied_globs;
+use crate::utils::redaction::check_patterns;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::info;
+
+pub const CONFIG_EXPORT_FORMAT: &str = "mcp-rg-editor-config";
+pub const CONFIG_EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// config.toml keys that widen what clients may reach or run, or weaken the checks and the audit
+/// trail. An import over MCP may not set them, and its replace mode keeps their current values.
+pub const SECURITY_KEYS: &[&str] = &[
+    "files_root",
+    "workspaces",
+    "allowed_directories",
+    "denied_globs",
+    "blocked_commands",
+    "default_shell",
+    "mcp_sse_host",
+    "mcp_ws_host",
+    "mcp_http_host",
+    "mcp_cors_allowed_origins",
+    "mcp_cors_allow_credentials",
+    "expose_process_environment",
+    "git_read_only",
+    "read_only",
+    "kill_scope",
+    "kill_allowed_process_names",
+    "disabled_tools",
+    "approval_required_tools",
+    "approval_command_patterns",
+    "policy_rules",
+    "redact_patterns",
+    "redact_keys",
+    "mcp_log_dir",
+    "audit_sinks",
+    "audit_syslog_address",
+    "audit_webhook_url",
+    "audit_webhook_token_secret",
+    "ssh_hosts",
+    "container_targets",
+    "downstream_servers",
+    "custom_tools",
+    "wasm_tools_dir",
+    "profile",
+    "profiles",
+];
+
+/// A portable config blob. `config` uses the config.toml key names, so an export can also be
+/// pasted into a config.toml by hand. Secrets, log paths and value sources are never included.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigExport {
+    pub format: String,
+    pub format_version: u32,
+    #[serde(default)]
+    pub exported_at: String,
+    #[serde(default)]
+    pub app_version: String,
+    pub config: ConfigLayer,
+}
+
+/// Builds the export. Without `include_machine_paths`, files_root, workspaces, allowed_directories,
+/// mcp_log_dir, snapshot_dir, the TLS certificate and key, and SSH identity files are left out because they rarely exist on another machine.
+pub fn export_config(config: &Config, include_machine_paths: bool) -> ConfigExport {
+    let path_str = |p: &Path| p.to_string_lossy().into_owned();
+    let ssh_hosts = config.ssh_hosts.iter().cloned().map(|mut host| {
+        if !include_machine_paths { host.identity_file = None; }
+        host
+    }).collect();
+    let layer = ConfigLayer {
+        files_root: include_machine_paths.then(|| path_str(&config.files_root)),
+        workspaces: include_machine_paths.then(|| config.workspaces.clone()),
+        active_workspace: if include_machine_paths { config.active_workspace.clone() } else { None },
+        allowed_directories: include_machine_paths.then(|| config.allowed_directories.iter().map(|d| path_str(d)).collect()),
+        denied_globs: Some(config.denied_globs.clone()),
+        blocked_commands: Some(config.blocked_commands.clone()),
+        default_shell: config.default_shell.clone(),
+        log_level: Some(config.log_level.clone()),
+        mcp_transport: Some(if config.mcp_transports.is_empty() { "disabled".to_string() } else { config.mcp_transports.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(",") }),
+        mcp_sse_host: config.mcp_sse_host.clone(),
+        mcp_sse_port: config.mcp_sse_port,
+        mcp_ws_host: config.mcp_ws_host.clone(),
+        mcp_ws_port: config.mcp_ws_port,
+        mcp_ws_path: config.mcp_ws_path.clone(),
+        mcp_http_host: config.mcp_http_host.clone(),
+        mcp_http_port: config.mcp_http_port,
+        mcp_http_path: config.mcp_http_path.clone(),
+        mcp_tls_cert: if include_machine_paths { config.mcp_tls_cert.as_deref().map(path_str) } else { None },
+        mcp_tls_key: if include_machine_paths { config.mcp_tls_key.as_deref().map(path_str) } else { None },
+        mcp_tls_self_signed: Some(config.mcp_tls_self_signed),
+        mcp_cors_allowed_origins: Some(config.mcp_cors_allowed_origins.clone()),
+        mcp_cors_allowed_headers: Some(config.mcp_cors_allowed_headers.clone()),
+        mcp_cors_allow_credentials: Some(config.mcp_cors_allow_credentials),
+        otlp_endpoint: config.otlp_endpoint.clone(),
+        file_read_line_limit: Some(config.file_read_line_limit),
+        file_write_line_limit: Some(config.file_write_line_limit),
+        expose_process_environment: Some(config.expose_process_environment),
+        git_read_only: Some(config.git_read_only),
+        git_checkpoint_min_files: Some(config.git_checkpoint_min_files),
+        read_only: Some(config.read_only),
+        kill_scope: Some(format!("{:?}", config.kill_scope).to_lowercase()),
+        kill_allowed_process_names: Some(config.kill_allowed_process_names.clone()),
+        disabled_tools: Some(config.disabled_tools.clone()),
+        tool_name_prefix: config.tool_name_prefix.clone(),
+        approval_required_tools: Some(config.approval_required_tools.clone()),
+        approval_command_patterns: Some(config.approval_command_patterns.clone()),
+        redact_patterns: Some(config.redact_patterns.clone()),
+        policy_rules: Some(config.policy_rules.clone()),
+        redact_keys: Some(config.redact_keys.clone()),
+        redact_max_value_bytes: Some(config.redact_max_value_bytes),
+        redact_max_string_bytes: Some(config.redact_max_string_bytes),
+        shutdown_session_policy: Some(format!("{:?}", config.shutdown_session_policy).to_lowercase()),
+        shutdown_drain_timeout_ms: Some(config.shutdown_drain_timeout_ms),
+        mcp_log_dir: include_machine_paths.then(|| path_str(&config.mcp_log_dir)),
+        snapshot_dir: include_machine_paths.then(|| path_str(&config.snapshot_dir)),
+        snapshot_keep: Some(config.snapshot_keep),
+        audit_log_max_size_mb: Some(config.audit_log_max_size_bytes / (1024 * 1024)),
+        audit_log_max_age_hours: Some(config.audit_log_max_age_hours),
+        audit_log_keep_files: Some(config.audit_log_keep_files),
+        audit_log_retention_days: Some(config.audit_log_retention_days),
+        audit_log_compress: Some(config.audit_log_compress),
+        audit_sinks: Some(config.audit_sinks.iter().map(|s| s.as_str().to_string()).collect()),
+        audit_syslog_address: config.audit_syslog_address.clone(),
+        audit_webhook_url: config.audit_webhook_url.clone(),
+        audit_webhook_token_secret: config.audit_webhook_token_secret.clone(),
+        limits: Some(LimitsLayer::from(&config.limits)),
+        ssh_hosts: Some(ssh_hosts),
+        container_targets: Some(config.container_targets.clone()),
+        // env often carries credentials, so it is left out like secrets.
+        downstream_servers: Some(config.downstream_servers.iter().cloned().map(|mut server| { server.env.clear(); server }).collect()),
+        custom_tools: Some(config.custom_tools.iter().cloned().map(|mut tool| { tool.env.clear(); tool }).collect()),
+        wasm_tools_dir: config.wasm_tools_dir.clone(),
+        // The active profile's values are already part of the effective values above.
+        profile: None,
+        profiles: None,
+    };
+    ConfigExport {
+        format: CONFIG_EXPORT_FORMAT.to_string(),
+        format_version: CONFIG_EXPORT_FORMAT_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        config: layer,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigImportMode {
+    // Imported keys replace the same keys in the user config.toml; other keys stay.
+    #[default]
+    Merge,
+    // The user config.toml becomes exactly the imported config.
+    Replace,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShadowedKey {
+    pub key: String,
+    pub source: ConfigSource,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigImportOutcome {
+    pub written_to: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+    pub imported_keys: Vec<String>,
+    pub applied: Vec<String>,
+    pub restart_required: Vec<String>,
+    // Imported keys that a higher layer (workspace config.toml, env, settings.json, runtime) still overrides.
+    pub shadowed: Vec<ShadowedKey>,
+}
+
+fn invalid(message: String) -> AppError {
+    AppError::InvalidInputArgument(message)
+}
+
+/// Rejects values that would only fail later, when the config is loaded.
+fn check_layer(layer: &ConfigLayer) -> Result<(), AppError> {
+    if let Some(scope) = &layer.kill_scope { KillScope::from_str(scope).map_err(|e| invalid(e.to_string()))?; }
+    for sink in layer.audit_sinks.iter().flatten() { AuditSinkKind::from_str(sink).map_err(|e| invalid(e.to_string()))?; }
+    if let Some(address) = &layer.audit_syslog_address { SyslogAddress::from_str(address).map_err(|e| invalid(e.to_string()))?; }
+    if let Some(policy) = &layer.shutdown_session_policy { ShutdownSessionPolicy::from_str(policy).map_err(|e| invalid(e.to_string()))?; }
+    if let Some(transport) = &layer.mcp_transport { parse_transports(transport).map_err(|e| invalid(e.to_string()))?; }
+    if let Some(globs) = &layer.denied_globs { compile_denied_globs(globs)?; }
+    if let Some(blocked) = &layer.blocked_commands { check_blocked_commands(blocked).map_err(|e| invalid(format!("{:#}", e)))?; }
+    if let Some(servers) = &layer.downstream_servers { check_downstream_servers(servers).map_err(|e| invalid(e.to_string()))?; }
+    if let Some(tools) = &layer.custom_tools { check_custom_tools(tools).map_err(|e| invalid(e.to_string()))?; }
+    if let Some(prefix) = &layer.tool_name_prefix { check_tool_name_prefix(prefix).map_err(|e| invalid(e.to_string()))?; }
+    if let Some(rules) = &layer.policy_rules { check_policy_rules(rules).map_err(|e| invalid(format!("{:#}", e)))?; }
+    if let Some(patterns) = &layer.redact_patterns { check_patterns(patterns, "redact_patterns").map_err(|e| invalid(format!("{:#}", e)))?; }
+    if let Some(keys) = &layer.redact_keys { check_patterns(keys, "redact_keys").map_err(|e| invalid(format!("{:#}", e)))?; }
+    if let Some(patterns) = &layer.approval_command_patterns {
+        if let Some(e) = patterns.iter().find_map(|p| regex::Regex::new(p).err()) { return Err(invalid(format!("Invalid approval_command_patterns entry: {}", e))); }
+    }
+    if let Some(level) = &layer.log_level {
+        crate::logging::parse_log_level(level).ok_or_else(|| invalid(format!("Invalid log_level '{}'", level)))?;
+    }
+    if let Some(targets) = &layer.container_targets {
+        if let Some(bad) = targets.iter().find(|t| t.container.is_none() == t.image.is_none()) {
+            return Err(invalid(format!("container_targets entry '{}' must set exactly one of 'container' or 'image'", bad.name)));
+        }
+    }
+    Ok(())
+}
+
+// config.toml key name -> Config field name, where they differ.
+fn config_field_for_layer_key(key: &str) -> &str {
+    match key {
+        "mcp_transport" => "mcp_transports",
+        "audit_log_max_size_mb" => "audit_log_max_size_bytes",
+        "profile" => "active_profile",
+        other => other,
+    }
+}
+
+fn write_toml_atomic(path: &Path, layer: &ConfigLayer) -> Result<(), AppError> {
+    let toml_str = toml::to_string_pretty(layer).map_err(|e| AppError::ConfigError(format!("Failed to serialize config.toml: {}", e)))?;
+    let parent_dir = path.parent().ok_or_else(|| AppError::ConfigError(format!("{} has no parent directory", path.display())))?;
+    std::fs::create_dir_all(parent_dir).map_err(|e| AppError::StdIoError(e.to_string()))?;
+    let tmp_path = path.with_extension(format!("toml.tmp-{}", std::process::id()));
+    {
+        let mut tmp_file = std::fs::File::create(&tmp_path).map_err(|e| AppError::StdIoError(e.to_string()))?;
+        tmp_file.write_all(toml_str.as_bytes()).and_then(|_| tmp_file.sync_all()).map_err(|e| AppError::StdIoError(e.to_string()))?;
+    }
+    std::fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        AppError::StdIoError(format!("Failed to replace {}: {}", path.display(), e))
+    })
+}
+
+/// Writes an export produced by `export_config` into the user config.toml (keeping a
+/// `config.toml.bak`) and hot-reloads it. Comments in the existing file are not preserved.
+/// Without `allow_security_keys` (the MCP tool), blobs setting SECURITY_KEYS are rejected.
+pub fn import_config(app_handle: &crate::AppHandle, config_state: &Arc<ConfigState>, blob: Value, mode: ConfigImportMode, allow_security_keys: bool) -> Result<ConfigImportOutcome, AppError> {
+    let export: ConfigExport = serde_json::from_value(blob).map_err(|e| invalid(format!("Not a valid config export: {}", e)))?;
+    if export.format != CONFIG_EXPORT_FORMAT {
+        return Err(invalid(format!("Unexpected export format '{}', expected '{}'", export.format, CONFIG_EXPORT_FORMAT)));
+    }
+    if export.format_version > CONFIG_EXPORT_FORMAT_VERSION {
+        return Err(invalid(format!("Config export version {} is newer than this app supports ({})", export.format_version, CONFIG_EXPORT_FORMAT_VERSION)));
+    }
+    check_layer(&export.config)?;
+
+    let imported_value = serde_json::to_value(&export.config).map_err(|e| AppError::SerdeJsonError(e.to_string()))?;
+    let imported_keys: Vec<String> = imported_value.as_object()
+        .map(|m| m.iter().filter(|(_, v)| !v.is_null()).map(|(k, _)| k.clone()).collect())
+        .unwrap_or_default();
+    if !allow_security_keys {
+        let protected: Vec<&str> = imported_keys.iter().map(String::as_str).filter(|key| SECURITY_KEYS.contains(key)).collect();
+        if !protected.is_empty() {
+            return Err(invalid(format!("import_config cannot change {} over MCP; import this blob from the app instead", protected.join(", "))));
+        }
+    }
+
+    let path = user_config_path(app_handle).ok_or_else(|| AppError::ConfigError("Could not determine the user config directory".to_string()))?;
+    let existing = if path.is_file() {
+        Some(ConfigLayer::from_toml_file(&path).map_err(|e| AppError::ConfigError(format!("{:#}", e)))?)
+    } else { None };
+
+    let merged = match (mode, &existing) {
+        (ConfigImportMode::Merge, Some(existing_layer)) => {
+            let mut merged_map = match serde_json::to_value(existing_layer) { Ok(Value::Object(m)) => m, _ => serde_json::Map::new() };
+            if let Value::Object(imported_map) = imported_value {
+                merged_map.extend(imported_map.into_iter().filter(|(_, v)| !v.is_null()));
+            }
+            serde_json::from_value(Value::Object(merged_map)).map_err(|e| AppError::ConfigError(format!("Failed to merge imported config: {}", e)))?
+        }
+        (ConfigImportMode::Replace, Some(existing_layer)) if !allow_security_keys => {
+            let mut replaced_map = match imported_value { Value::Object(m) => m, _ => serde_json::Map::new() };
+            if let Ok(Value::Object(existing_map)) = serde_json::to_value(existing_layer) {
+                replaced_map.extend(existing_map.into_iter().filter(|(k, v)| SECURITY_KEYS.contains(&k.as_str()) && !v.is_null()));
+            }
+            serde_json::from_value(Value::Object(replaced_map)).map_err(|e| AppError::ConfigError(format!("Failed to replace the config: {}", e)))?
+        }
+        _ => export.config,
+    };
+
+    let backup = match existing {
+        Some(_) => {
+            let backup_path = path.with_extension("toml.bak");
+            std::fs::copy(&path, &backup_path).map_err(|e| AppError::StdIoError(format!("Failed to back up {}: {}", path.display(), e)))?;
+            Some(backup_path)
+        }
+        None => None,
+    };
+    write_toml_atomic(&path, &merged)?;
+    info!(path = %path.display(), mode = ?mode, keys = ?imported_keys, "Imported config");
+
+    let outcome = reload_config(app_handle, config_state, "import")
+        .ok_or_else(|| AppError::ConfigError(format!("Imported config was written to {} but failed to load; restore {} if needed", path.display(), backup.as_deref().unwrap_or(&path).display())))?;
+    let shadowed = {
+        let config = config_state.snapshot();
+        imported_keys.iter().filter_map(|key| {
+            let field = config_field_for_layer_key(key);
+            match config.value_sources.get(field) {
+                Some(source) if *source > ConfigSource::User => Some(ShadowedKey { key: key.clone(), source: *source }),
+                _ => None,
+            }
+        }).collect()
+    };
+
+    Ok(ConfigImportOutcome { written_to: path, backup, imported_keys, applied: outcome.applied, restart_required: outcome.restart_required, shadowed })
+}
@@ -0,0 +1,253 @@
+use crate::config::{blocked_command_regex, AuditSinkKind, Config, TransportMode};
+use crate::mcp::custom_tools;
+use crate::utils::path_utils::compile_denied_globs;
+use std::path::Path;
+use serde::Serialize;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigIssueSeverity {
+    // Some tools will fail until this is fixed.
+    Error,
+    // Works, but probably not as intended.
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigIssue {
+    pub severity: ConfigIssueSeverity,
+    pub key: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigValidationReport {
+    pub ok: bool,
+    pub issues: Vec<ConfigIssue>,
+    // Checks that were not run, with the reason (e.g. the SSE port is bound by this server).
+    pub skipped: Vec<String>,
+}
+
+fn is_wildcard(host: &str) -> bool {
+    host == "0.0.0.0" || host == "::"
+}
+
+fn issue(severity: ConfigIssueSeverity, key: &str, message: String) -> ConfigIssue {
+    ConfigIssue { severity, key: key.to_string(), message }
+}
+
+/// Validates the effective config without changing it. `check_listen_port` tries to bind each
+/// network transport's address, which is only meaningful before this server has bound it itself.
+pub fn validate_config(config: &Config, check_listen_port: bool) -> ConfigValidationReport {
+    use ConfigIssueSeverity::{Error, Warning};
+    let mut issues = Vec::new();
+    let mut skipped = Vec::new();
+
+    if !config.files_root.is_dir() {
+        issues.push(issue(Error, "files_root", format!("files_root '{}' is not a directory", config.files_root.display())));
+    } else if std::fs::metadata(&config.files_root).map(|m| m.permissions().readonly()).unwrap_or(false) {
+        issues.push(issue(Warning, "files_root", format!("files_root '{}' is read-only; write tools will fail", config.files_root.display())));
+    }
+    for dir in &config.allowed_directories {
+        if !dir.exists() {
+            issues.push(issue(Warning, "allowed_directories", format!("Allowed directory '{}' does not exist", dir.display())));
+        } else if !dir.is_dir() {
+            issues.push(issue(Warning, "allowed_directories", format!("Allowed directory '{}' is not a directory", dir.display())));
+        }
+    }
+
+    for workspace in &config.workspaces {
+        for dir in workspace.allowed_directories.iter().filter(|d| !d.is_dir()) {
+            issues.push(issue(Warning, "workspaces", format!("Workspace '{}' allows '{}', which is not a directory", workspace.name, dir.display())));
+        }
+        if let Err(e) = compile_denied_globs(&workspace.denied_globs) {
+            issues.push(issue(Error, "workspaces", format!("Workspace '{}': {}", workspace.name, e)));
+        }
+    }
+
+    for pattern in &config.denied_globs {
+        if let Err(e) = compile_denied_globs(std::slice::from_ref(pattern)) {
+            issues.push(issue(Error, "denied_globs", e.to_string()));
+        }
+    }
+
+    for blocked in &config.blocked_commands {
+        if let Err(e) = blocked_command_regex(blocked) {
+            issues.push(issue(Error, "blocked_commands", format!("{:#}", e)));
+        }
+    }
+
+    for pattern in &config.approval_command_patterns {
+        if let Err(e) = regex::Regex::new(pattern) {
+            issues.push(issue(Error, "approval_command_patterns", format!("'{}' is not a valid regex, so every execute_command call will wait for approval: {}", pattern, e)));
+        }
+    }
+
+    if config.file_read_line_limit == 0 {
+        issues.push(issue(Error, "file_read_line_limit", "file_read_line_limit is 0; every read will return nothing".to_string()));
+    }
+    if config.file_write_line_limit == 0 {
+        issues.push(issue(Error, "file_write_line_limit", "file_write_line_limit is 0; every write will be rejected".to_string()));
+    }
+    let limits = &config.limits;
+    for (key, value) in [
+        ("limits.max_file_bytes", limits.max_file_bytes),
+        ("limits.max_output_bytes", limits.max_output_bytes as u64),
+        ("limits.max_concurrent_sessions", limits.max_concurrent_sessions as u64),
+        ("limits.max_concurrent_searches", limits.max_concurrent_searches as u64),
+        ("limits.max_timeout_ms", limits.max_timeout_ms),
+        ("limits.max_concurrent_calls", limits.max_concurrent_calls as u64),
+        ("limits.max_message_bytes", limits.max_message_bytes as u64),
+    ] {
+        if value == 0 {
+            issues.push(issue(Error, key, format!("{} is 0; every call it applies to will be rejected or cut off", key)));
+        }
+    }
+    if limits.search_timeout_ms > limits.max_timeout_ms || limits.url_fetch_timeout_ms > limits.max_timeout_ms || limits.call_deadline_ms > limits.max_timeout_ms {
+        issues.push(issue(Warning, "limits.max_timeout_ms", format!("limits.max_timeout_ms ({}) is below a default timeout; those calls are clamped to it", limits.max_timeout_ms)));
+    }
+    if limits.max_message_bytes < limits.max_output_bytes {
+        issues.push(issue(Warning, "limits.max_message_bytes", format!("limits.max_message_bytes ({}) is below limits.max_output_bytes ({}); large outputs will come back as message_too_large errors", limits.max_message_bytes, limits.max_output_bytes)));
+    }
+    if !matches!(config.log_level.to_lowercase().as_str(), "trace" | "debug" | "info" | "warn" | "error") {
+        issues.push(issue(Warning, "log_level", format!("Unknown log level '{}'; 'info' is used instead", config.log_level)));
+    }
+
+    if which::which("rg").is_err() {
+        issues.push(issue(Error, "ripgrep", "rg (ripgrep) was not found on PATH; search_code will fail".to_string()));
+    }
+    if let Some(shell) = &config.default_shell {
+        if which::which(shell).is_err() {
+            issues.push(issue(Error, "default_shell", format!("default_shell '{}' was not found", shell)));
+        }
+    }
+    if !config.ssh_hosts.is_empty() && which::which("ssh").is_err() {
+        issues.push(issue(Error, "ssh_hosts", "ssh_hosts are configured but no ssh client was found on PATH".to_string()));
+    }
+    for target in &config.container_targets {
+        let runtime = target.runtime.as_deref().unwrap_or("docker");
+        if which::which(runtime).is_err() {
+            issues.push(issue(Error, "container_targets", format!("Container target '{}' uses runtime '{}', which was not found on PATH", target.name, runtime)));
+        }
+    }
+
+    for server in &config.downstream_servers {
+        if let Some(command) = &server.command {
+            if which::which(command).is_err() {
+                issues.push(issue(Error, "downstream_servers", format!("Downstream server '{}' runs '{}', which was not found on PATH", server.name, command)));
+            }
+        }
+        if let Some(secret) = &server.token_secret {
+            if !config.secret_names.contains(secret) {
+                issues.push(issue(Warning, "downstream_servers", format!("Downstream server '{}' uses token_secret '{}', which is not in the keyring", server.name, secret)));
+            }
+        }
+    }
+
+    let builtin_tools = crate::mcp::tool_registry::ToolRegistry::builtin();
+    let discovered = config.wasm_tools_dir.as_deref().map(custom_tools::discover_wasm_tools).unwrap_or_default();
+    if let Some(dir) = config.wasm_tools_dir.as_deref().filter(|d| !d.is_dir()) {
+        issues.push(issue(Warning, "wasm_tools_dir", format!("wasm_tools_dir {} is not a directory", dir.display())));
+    }
+    for tool in config.custom_tools.iter().chain(&discovered) {
+        if builtin_tools.get(&tool.name).is_some() {
+            issues.push(issue(Error, "custom_tools", format!("Custom tool '{}' has the name of a built-in tool and is not registered", tool.name)));
+        }
+        if let Some(command) = &tool.command {
+            let found = if command.contains(std::path::MAIN_SEPARATOR) || command.contains('/') { custom_tools::resolve(Path::new(command), &config.files_root).is_file() } else { which::which(command).is_ok() };
+            if !found {
+                issues.push(issue(Error, "custom_tools", format!("Custom tool '{}' runs '{}', which was not found", tool.name, command)));
+            }
+        }
+        if let Some(module) = &tool.wasm {
+            if !cfg!(feature = "wasm-tools") {
+                issues.push(issue(Error, "custom_tools", format!("Custom tool '{}' is a WASM module, but this build lacks the wasm-tools feature", tool.name)));
+            } else if !custom_tools::resolve(module, &config.files_root).is_file() {
+                issues.push(issue(Error, "custom_tools", format!("Custom tool '{}' uses module {}, which does not exist", tool.name, module.display())));
+            }
+        }
+    }
+
+    if config.otlp_endpoint.is_some() && !cfg!(feature = "otel") {
+        issues.push(issue(Error, "otlp_endpoint", "otlp_endpoint is set but the otel feature is not compiled in, so nothing is exported".to_string()));
+    }
+    if config.audit_sinks.contains(&AuditSinkKind::Otlp) && !cfg!(feature = "otel") {
+        issues.push(issue(Error, "audit_sinks", "audit_sinks includes 'otlp' but the otel feature is not compiled in, so audit entries are not exported".to_string()));
+    }
+    if !config.audit_sinks.contains(&AuditSinkKind::File) {
+        issues.push(issue(Warning, "audit_sinks", "audit_sinks does not include 'file', so query_audit_log finds nothing".to_string()));
+    }
+    if let Some(secret) = &config.audit_webhook_token_secret {
+        if !config.secret_names.contains(secret) {
+            issues.push(issue(Warning, "audit_webhook_token_secret", format!("audit_webhook_token_secret '{}' is not in the keyring, so the webhook gets no token", secret)));
+        }
+    }
+
+    let auth = crate::mcp::auth::auth_configured(&config.secret_names);
+    let tls = config.mcp_tls_cert.is_some() || config.mcp_tls_self_signed;
+    let mut listeners: Vec<(&str, String, u16)> = Vec::new();
+    for transport in &config.mcp_transports {
+        // (feature compiled in, feature name, label, config key prefix, host, port)
+        let (compiled, feature, label, key, host, port) = match transport {
+            TransportMode::Stdio => {
+                if !cfg!(feature = "mcp-stdio-server") {
+                    issues.push(issue(Error, "mcp_transports", "MCP_TRANSPORT includes 'stdio' but the mcp-stdio-server feature is not compiled in".to_string()));
+                }
+                continue;
+            }
+            TransportMode::Sse => (cfg!(feature = "mcp-sse-server"), "mcp-sse-server", "SSE", "mcp_sse", config.mcp_sse_host.clone(), config.mcp_sse_port.unwrap_or(3030)),
+            TransportMode::WebSocket => (cfg!(feature = "mcp-ws-server"), "mcp-ws-server", "WebSocket", "mcp_ws", config.mcp_ws_host.clone(), config.mcp_ws_port.unwrap_or(3031)),
+            TransportMode::Http => (cfg!(feature = "mcp-http-server"), "mcp-http-server", "HTTP", "mcp_http", config.mcp_http_host.clone(), config.mcp_http_port.unwrap_or(3032)),
+        };
+        if !compiled {
+            issues.push(issue(Error, "mcp_transports", format!("MCP_TRANSPORT includes '{}' but the {} feature is not compiled in", transport.as_str(), feature)));
+        }
+        let host = host.unwrap_or_else(|| "127.0.0.1".to_string());
+        if let Some((other, _, _)) = listeners.iter().find(|(_, h, p)| *p == port && (h == &host || is_wildcard(h) || is_wildcard(&host))) {
+            issues.push(issue(Error, &format!("{}_port", key), format!("{} and {} servers both listen on port {}", other, label, port)));
+        } else if check_listen_port {
+            if let Err(e) = std::net::TcpListener::bind((host.as_str(), port)) {
+                issues.push(issue(Error, &format!("{}_port", key), format!("Cannot bind {} address {}:{}: {}", label, host, port, e)));
+            }
+        } else {
+            skipped.push(format!("{} port check for {}:{} (the running server owns this port)", label, host, port));
+        }
+        let loopback = matches!(host.as_str(), "127.0.0.1" | "localhost" | "::1");
+        if !loopback && !tls {
+            issues.push(issue(Warning, "mcp_tls_cert", format!("{} server listens on {} without TLS; tokens and file contents cross the network unencrypted", label, host)));
+        }
+        if is_wildcard(&host) && !auth {
+            issues.push(issue(Warning, &format!("{}_host", key), format!("{} server listens on all interfaces ({}) without MCP_AUTH_TOKENS or mcp_token.* secrets; any machine on the network can call tools", label, host)));
+        }
+        listeners.push((label, host, port));
+    }
+    if !listeners.is_empty() {
+        if tls && !cfg!(feature = "mcp-tls") {
+            issues.push(issue(Error, "mcp_tls_cert", "TLS is configured but the mcp-tls feature is not compiled in, so the server will not start".to_string()));
+        }
+        for (tls_key, path) in [("mcp_tls_cert", &config.mcp_tls_cert), ("mcp_tls_key", &config.mcp_tls_key)] {
+            if let Some(path) = path.as_ref().filter(|p| !p.is_file()) {
+                issues.push(issue(Error, tls_key, format!("{} '{}' is not a file", tls_key, path.display())));
+            }
+        }
+        if config.mcp_cors_allowed_origins.iter().any(|o| o == "*") {
+            issues.push(issue(Warning, "mcp_cors_allowed_origins", "MCP_CORS_ALLOWED_ORIGINS is '*'; any website the user visits can call tools here, so list the client origins instead".to_string()));
+        }
+    }
+
+    let ok = !issues.iter().any(|i| i.severity == Error);
+    ConfigValidationReport { ok, issues, skipped }
+}
+
+/// Logs every issue at startup so misconfiguration shows up before the first failing tool call.
+pub fn log_startup_self_check(config: &Config) {
+    let report = validate_config(config, true);
+    if report.issues.is_empty() {
+        info!("Config self-check passed");
+        return;
+    }
+    for i in &report.issues {
+        warn!(severity = ?i.severity, key = %i.key, "Config self-check: {}", i.message);
+    }
+}
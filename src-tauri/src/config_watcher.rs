@@ -0,0 +1,224 @@
+use crate::config::{Config, ConfigState};
+use crate::config_layers::ConfigSource;
+use crate::mcp::notifications::{McpNotifier, CONFIG_CHANGED_NOTIFICATION, TOOLS_LIST_CHANGED_NOTIFICATION};
+use crate::AppHandle;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{Emitter, Manager};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::{debug, error, info, warn};
+
+pub const CONFIG_CHANGED_EVENT: &str = "config_changed";
+
+// Editors often write a file as several events (truncate, write, rename); wait for them to settle.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Keys that can change under running tools without a restart.
+const HOT_RELOADABLE_KEYS: &[&str] = &[
+    "denied_globs",
+    "blocked_commands",
+    "default_shell",
+    "log_level",
+    "file_read_line_limit",
+    "file_write_line_limit",
+    "expose_process_environment",
+    "git_read_only",
+    "git_checkpoint_min_files",
+    "kill_scope",
+    "kill_allowed_process_names",
+    "disabled_tools",
+    "tool_name_prefix",
+    "approval_required_tools",
+    "approval_command_patterns",
+    "redact_patterns",
+    "policy_rules",
+    "redact_keys",
+    "redact_max_value_bytes",
+    "redact_max_string_bytes",
+    "snapshot_keep",
+    "shutdown_session_policy",
+    "shutdown_drain_timeout_ms",
+    "limits",
+    "active_profile",
+];
+
+#[derive(Debug, Default)]
+pub struct ConfigReloadOutcome {
+    pub applied: Vec<String>,
+    // Changed on disk but only picked up after a restart (files_root, transport, ports, ...).
+    pub restart_required: Vec<String>,
+}
+
+/// Copies every value that differs and passes `should_copy(current, key)` from `reloaded` into
+/// `current`, together with its source. Returns the copied keys.
+pub fn copy_changed_values(current: &mut Config, reloaded: &Config, should_copy: impl Fn(&Config, &str) -> bool) -> Vec<String> {
+    let mut copied = Vec::new();
+    macro_rules! copy_keys {
+        ($($field:ident),* $(,)?) => {
+            $(
+                let key = stringify!($field);
+                if current.$field != reloaded.$field && should_copy(current, key) {
+                    current.$field = reloaded.$field.clone();
+                    match reloaded.value_sources.get(key) {
+                        Some(source) => { current.value_sources.insert(key.to_string(), *source); }
+                        None => { current.value_sources.remove(key); }
+                    }
+                    copied.push(key.to_string());
+                }
+            )*
+        };
+    }
+    copy_keys!(
+        allowed_directories,
+        denied_globs,
+        blocked_commands,
+        default_shell,
+        log_level,
+        file_read_line_limit,
+        file_write_line_limit,
+        expose_process_environment,
+        git_read_only,
+        git_checkpoint_min_files,
+        kill_scope,
+        kill_allowed_process_names,
+        disabled_tools,
+        tool_name_prefix,
+        approval_required_tools,
+        approval_command_patterns,
+        redact_patterns,
+        policy_rules,
+        redact_keys,
+        redact_max_value_bytes,
+        redact_max_string_bytes,
+        snapshot_keep,
+        shutdown_session_policy,
+        shutdown_drain_timeout_ms,
+        limits,
+        active_profile,
+    );
+    copied
+}
+
+fn is_runtime_override(config: &Config, key: &str) -> bool {
+    config.value_sources.get(key) == Some(&ConfigSource::Runtime)
+}
+
+/// Copies hot-reloadable values from `reloaded` into `current`. Keys changed in memory during
+/// this session (source `Runtime`) are left alone so an unrelated file edit does not revert them.
+pub fn apply_reloaded_config(current: &mut Config, reloaded: &Config) -> ConfigReloadOutcome {
+    let applied = copy_changed_values(current, reloaded, |config, key| HOT_RELOADABLE_KEYS.contains(&key) && !is_runtime_override(config, key));
+    let mut outcome = ConfigReloadOutcome { applied, ..Default::default() };
+
+    if let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) = (serde_json::to_value(&*current), serde_json::to_value(reloaded)) {
+        for (key, new_value) in new {
+            if HOT_RELOADABLE_KEYS.contains(&key.as_str()) || is_runtime_override(current, &key) {
+                continue;
+            }
+            if old.get(&key) != Some(&new_value) {
+                outcome.restart_required.push(key);
+            }
+        }
+    }
+    outcome
+}
+
+/// Tells the UI (`config_changed` event) and MCP clients (`notifications/config/changed`) which
+/// keys changed and their new values. Every path that mutates the shared config calls this.
+pub fn broadcast_config_change(app_handle: &AppHandle, source: &str, config: &Config, changed_keys: &[String], restart_required_keys: &[String]) {
+    if changed_keys.is_empty() && restart_required_keys.is_empty() {
+        return;
+    }
+    let values: serde_json::Map<String, serde_json::Value> = match serde_json::to_value(config) {
+        Ok(serde_json::Value::Object(all)) => all.into_iter().filter(|(k, _)| changed_keys.contains(k)).collect(),
+        _ => serde_json::Map::new(),
+    };
+    let payload = json!({ "source": source, "changed_keys": changed_keys, "restart_required_keys": restart_required_keys, "values": values });
+    app_handle.emit(CONFIG_CHANGED_EVENT, payload.clone()).unwrap_or_else(|e| error!("Emit config_changed failed: {}", e));
+    match app_handle.try_state::<Arc<McpNotifier>>() {
+        Some(notifier) => {
+            notifier.notify(CONFIG_CHANGED_NOTIFICATION, payload);
+            if changed_keys.iter().any(|k| k == "disabled_tools" || k == "tool_name_prefix" || k == "read_only") {
+                notifier.notify(TOOLS_LIST_CHANGED_NOTIFICATION, json!({}));
+            }
+        }
+        None => debug!("MCP notifier not initialized; config change not sent to MCP clients"),
+    }
+}
+
+fn watched_files(config: &Config) -> HashSet<PathBuf> {
+    std::iter::once(config.settings_file.clone())
+        .chain(config.layer_files.iter().map(|f| f.path.clone()))
+        .collect()
+}
+
+/// Watches settings.json and every config.toml layer and applies safe changes to the shared
+/// config, emitting `config_changed` to the UI and `notifications/config/changed` to MCP clients.
+/// Parent directories are watched so layer files that do not exist yet are picked up when created.
+pub fn spawn_config_watcher(app_handle: AppHandle, config_state: Arc<ConfigState>) {
+    let files = watched_files(&config_state.snapshot());
+
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<notify::Result<notify::Event>>();
+    let mut watcher: RecommendedWatcher = match notify::recommended_watcher(move |event| { let _ = event_tx.send(event); }) {
+        Ok(w) => w,
+        Err(e) => { warn!(error = %e, "Failed to create config file watcher; hot-reload disabled"); return; }
+    };
+    let watched_dirs: HashSet<PathBuf> = files.iter().filter_map(|f| f.parent().map(PathBuf::from)).filter(|d| d.is_dir()).collect();
+    for dir in &watched_dirs {
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            warn!(dir = %dir.display(), error = %e, "Failed to watch config directory");
+        }
+    }
+    info!(files = ?files, "Watching config files for changes");
+
+    tauri::async_runtime::spawn(async move {
+        // The watcher stops when dropped, so it lives as long as this task.
+        let _watcher = watcher;
+        while let Some(event) = event_rx.recv().await {
+            let touches_config = |event: &notify::Result<notify::Event>| match event {
+                Ok(ev) => ev.paths.iter().any(|p| files.contains(p)),
+                Err(e) => { warn!(error = %e, "Config watcher error"); false }
+            };
+            if !touches_config(&event) { continue; }
+            // Swallow the rest of the burst before reloading once.
+            tokio::time::sleep(RELOAD_DEBOUNCE).await;
+            while event_rx.try_recv().is_ok() {}
+            reload_config(&app_handle, &config_state, "file");
+        }
+        debug!("Config watcher channel closed");
+    });
+}
+
+/// Reloads every config layer and applies the hot-reloadable differences. `source` labels the
+/// resulting config_changed broadcast. Returns None if the config could not be reloaded.
+pub fn reload_config(app_handle: &AppHandle, config_state: &Arc<ConfigState>, source: &str) -> Option<ConfigReloadOutcome> {
+    // A profile picked with use_profile stays selected across file reloads.
+    let current = config_state.snapshot();
+    let profile_override = if is_runtime_override(&current, "active_profile") { current.active_profile.clone() } else { None };
+    let reloaded = match Config::load_with_profile(app_handle, profile_override.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            // Keep running on the last good config; a half-edited file must not take the server down.
+            warn!(error = %e, "Config file changed but failed to load; keeping current config");
+            return None;
+        }
+    };
+    let (outcome, config_snapshot) = config_state.update(|config| apply_reloaded_config(config, &reloaded));
+    if !outcome.restart_required.is_empty() {
+        warn!(keys = ?outcome.restart_required, "Config keys changed on disk but require a restart to take effect");
+    }
+    if outcome.applied.is_empty() {
+        debug!("Config files changed; no hot-reloadable values differ");
+        return Some(outcome);
+    }
+    if outcome.applied.iter().any(|k| k == "log_level") {
+        if let Err(e) = crate::logging::apply_log_level(&reloaded.log_level) { warn!("{}", e); }
+    }
+    info!(keys = ?outcome.applied, "Hot-reloaded config");
+    broadcast_config_change(app_handle, source, &config_snapshot, &outcome.applied, &outcome.restart_required);
+    Some(outcome)
+}
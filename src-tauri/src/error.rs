@@ -1,4 +1,5 @@
 use serde::Serialize;
+use serde_json::{json, Value};
 use thiserror::Error;
 
 #[derive(Error, Debug, Serialize)]
@@ -12,11 +13,11 @@ pub enum AppError {
     #[error("Ripgrep error: {0}")]
     RipgrepError(String),
 
-    #[error("Path traversal attempt: {0}")]
-    PathTraversal(String),
+    #[error("Path traversal attempt: {message}")]
+    PathTraversal { path: String, message: String },
 
-    #[error("Path not allowed: {0}")]
-    PathNotAllowed(String),
+    #[error("Path not allowed: {message}")]
+    PathNotAllowed { path: String, message: String },
 
     #[error("Invalid path: {0}")]
     InvalidPath(String),
@@ -33,6 +34,23 @@ pub enum AppError {
     #[error("Process error: {0}")]
     ProcessError(String),
 
+    #[error("Process not allowed by kill scope policy: {0}")]
+    ProcessNotAllowed(String),
+
+    #[error("Server is in read-only mode: {0}")]
+    ReadOnlyMode(String),
+
+    // `limit` is the config key, e.g. limits.max_file_bytes; `max` its value and `actual` what the call needed.
+    #[error("Limit exceeded: {message}")]
+    LimitExceeded { message: String, limit: String, max: u64, actual: u64 },
+
+    #[error("Line limit exceeded: {message}")]
+    LineLimitExceeded { message: String, limit: String, max: u64, actual: u64 },
+
+    // retry_after_ms is also sent as RPC error data so clients can back off without parsing the message.
+    #[error("Rate limited: {message}")]
+    RateLimited { message: String, tool_class: String, retry_after_ms: u64 },
+
     #[error("Session not found for ID: {0}")]
     SessionNotFound(String),
 
@@ -48,6 +66,19 @@ pub enum AppError {
     #[error("Operation timed out: {0}")]
     TimeoutError(String),
 
+    #[error("Cancelled by the client: {0}")]
+    Cancelled(String),
+
+    #[error("Server is shutting down: {0}")]
+    ShuttingDown(String),
+
+    #[error("Not approved: {0}")]
+    ApprovalDenied(String),
+
+    // `rule` is the index of the matching policy_rules entry.
+    #[error("Denied by policy: {message}")]
+    PolicyDenied { message: String, rule: usize },
+
     #[error("Invalid input argument: {0}")]
     InvalidInputArgument(String),
 
@@ -60,10 +91,152 @@ pub enum AppError {
     #[error("MCP SDK error: {0}")]
     McpSdkError(String),
 
+    #[error("Downstream MCP server error: {0}")]
+    DownstreamError(String),
+
+    #[error("Git error: {0}")]
+    GitError(String),
+
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
 
+/// Stable numeric codes for AppError, listed in the README under "Error Codes". Tool call errors
+/// carry them as `data.error` (the name) and `data.error_code`, so clients can branch on them
+/// instead of on the message. A number is never reused for a different error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    // 1xxx: the call was rejected before it ran.
+    InvalidArgument = 1001,
+    InvalidPath = 1002,
+    PathTraversal = 1003,
+    PathNotAllowed = 1004,
+    ProcessNotAllowed = 1005,
+    CommandBlocked = 1006,
+    ReadOnlyMode = 1007,
+    ApprovalDenied = 1008,
+    PolicyDenied = 1009,
+    // 2xxx: a limit was hit.
+    LimitExceeded = 2001,
+    LineLimitExceeded = 2002,
+    RateLimited = 2003,
+    Timeout = 2004,
+    // 3xxx: the operation itself failed.
+    IoError = 3001,
+    RipgrepError = 3002,
+    CommandFailed = 3003,
+    ProcessError = 3004,
+    SessionNotFound = 3005,
+    EditFailed = 3006,
+    HttpError = 3007,
+    DownstreamError = 3008,
+    GitError = 3009,
+    // 4xxx: the call was stopped.
+    Cancelled = 4001,
+    ShuttingDown = 4002,
+    // 5xxx: server-side problems.
+    ConfigError = 5001,
+    JsonError = 5002,
+    TauriError = 5003,
+    PluginError = 5004,
+    McpSdkError = 5005,
+    Unknown = 5999,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidArgument => "INVALID_ARGUMENT",
+            Self::InvalidPath => "INVALID_PATH",
+            Self::PathTraversal => "PATH_TRAVERSAL",
+            Self::PathNotAllowed => "PATH_NOT_ALLOWED",
+            Self::ProcessNotAllowed => "PROCESS_NOT_ALLOWED",
+            Self::CommandBlocked => "COMMAND_BLOCKED",
+            Self::ReadOnlyMode => "READ_ONLY_MODE",
+            Self::ApprovalDenied => "APPROVAL_DENIED",
+            Self::PolicyDenied => "POLICY_DENIED",
+            Self::LimitExceeded => "LIMIT_EXCEEDED",
+            Self::LineLimitExceeded => "LINE_LIMIT_EXCEEDED",
+            Self::RateLimited => "RATE_LIMITED",
+            Self::Timeout => "TIMEOUT",
+            Self::IoError => "IO_ERROR",
+            Self::RipgrepError => "RIPGREP_ERROR",
+            Self::CommandFailed => "COMMAND_FAILED",
+            Self::ProcessError => "PROCESS_ERROR",
+            Self::SessionNotFound => "SESSION_NOT_FOUND",
+            Self::EditFailed => "EDIT_FAILED",
+            Self::HttpError => "HTTP_ERROR",
+            Self::DownstreamError => "DOWNSTREAM_ERROR",
+            Self::GitError => "GIT_ERROR",
+            Self::Cancelled => "CANCELLED",
+            Self::ShuttingDown => "SHUTTING_DOWN",
+            Self::ConfigError => "CONFIG_ERROR",
+            Self::JsonError => "JSON_ERROR",
+            Self::TauriError => "TAURI_ERROR",
+            Self::PluginError => "PLUGIN_ERROR",
+            Self::McpSdkError => "MCP_SDK_ERROR",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl AppError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::StdIoError(_) | AppError::TokioIoError(_) => ErrorCode::IoError,
+            AppError::RipgrepError(_) => ErrorCode::RipgrepError,
+            AppError::PathTraversal { .. } => ErrorCode::PathTraversal,
+            AppError::PathNotAllowed { .. } => ErrorCode::PathNotAllowed,
+            AppError::InvalidPath(_) => ErrorCode::InvalidPath,
+            AppError::ConfigError(_) => ErrorCode::ConfigError,
+            AppError::CommandExecutionError(_) => ErrorCode::CommandFailed,
+            AppError::CommandBlocked(_) => ErrorCode::CommandBlocked,
+            AppError::ProcessError(_) => ErrorCode::ProcessError,
+            AppError::ProcessNotAllowed(_) => ErrorCode::ProcessNotAllowed,
+            AppError::ReadOnlyMode(_) => ErrorCode::ReadOnlyMode,
+            AppError::LimitExceeded { .. } => ErrorCode::LimitExceeded,
+            AppError::LineLimitExceeded { .. } => ErrorCode::LineLimitExceeded,
+            AppError::RateLimited { .. } => ErrorCode::RateLimited,
+            AppError::SessionNotFound(_) => ErrorCode::SessionNotFound,
+            AppError::EditError(_) => ErrorCode::EditFailed,
+            AppError::SerdeJsonError(_) => ErrorCode::JsonError,
+            AppError::ReqwestError(_) => ErrorCode::HttpError,
+            AppError::TimeoutError(_) => ErrorCode::Timeout,
+            AppError::Cancelled(_) => ErrorCode::Cancelled,
+            AppError::ShuttingDown(_) => ErrorCode::ShuttingDown,
+            AppError::ApprovalDenied(_) => ErrorCode::ApprovalDenied,
+            AppError::PolicyDenied { .. } => ErrorCode::PolicyDenied,
+            AppError::InvalidInputArgument(_) => ErrorCode::InvalidArgument,
+            AppError::TauriApiError(_) => ErrorCode::TauriError,
+            AppError::PluginError { .. } => ErrorCode::PluginError,
+            AppError::McpSdkError(_) => ErrorCode::McpSdkError,
+            AppError::DownstreamError(_) => ErrorCode::DownstreamError,
+            AppError::GitError(_) => ErrorCode::GitError,
+            AppError::Unknown(_) => ErrorCode::Unknown,
+        }
+    }
+
+    /// The `data` of the JSON-RPC error: the code, plus the values the message was built from.
+    pub fn data(&self) -> Value {
+        let code = self.code();
+        let mut data = json!({ "error": code.as_str(), "error_code": code as i32 });
+        let details = match self {
+            AppError::PathTraversal { path, .. } | AppError::PathNotAllowed { path, .. } => json!({ "path": path }),
+            AppError::LimitExceeded { limit, max, actual, .. } | AppError::LineLimitExceeded { limit, max, actual, .. } => json!({ "limit": limit, "max": max, "actual": actual }),
+            AppError::RateLimited { tool_class, retry_after_ms, .. } => json!({ "tool_class": tool_class, "retry_after_ms": retry_after_ms }),
+            AppError::CommandBlocked(command) => json!({ "command": command }),
+            AppError::SessionNotFound(session_id) => json!({ "session_id": session_id }),
+            AppError::PolicyDenied { rule, .. } => json!({ "rule": rule }),
+            AppError::PluginError { plugin, .. } => json!({ "plugin": plugin }),
+            _ => Value::Null,
+        };
+        if let (Some(data), Value::Object(details)) = (data.as_object_mut(), details) {
+            data.extend(details);
+        }
+        data
+    }
+}
+
 // Removed: impl From<std::io::Error> for AppError to resolve conflict.
 // Manually map std::io::Error where needed: .map_err(|e| AppError::StdIoError(e.to_string()))
 
@@ -103,6 +276,12 @@ impl From<reqwest::Error> for AppError {
     }
 }
 
+impl From<git2::Error> for AppError {
+    fn from(err: git2::Error) -> Self {
+        AppError::GitError(err.message().to_string())
+    }
+}
+
 impl From<tauri::Error> for AppError {
     fn from(err: tauri::Error) -> Self {
         AppError::TauriApiError(format!("{:?}", err))
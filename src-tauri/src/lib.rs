@@ -2,120 +2,83 @@
 
 mod commands;
 mod config;
+mod config_layers;
+mod config_profiles;
+mod config_transfer;
+mod config_validation;
+mod config_watcher;
 mod error;
+mod logging;
 mod utils;
 mod mcp;
+mod secrets;
+mod settings;
+mod shutdown;
+mod telemetry;
+mod tray;
 
 use crate::commands::terminal_commands::ActiveSessionsMap;
-use crate::config::{Config, init_config_state, TransportMode as AppTransportMode};
-use crate::mcp::handler::EnhancedServerHandler;
+use crate::config::init_config_state;
+use crate::mcp::notifications::McpNotifier;
+use crate::mcp::server_control::McpServerControl;
 use crate::mcp::McpServerLaunchParams;
 
 use std::sync::Arc;
 use tauri::Manager;
-use tracing::Level;
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
 
-use rust_mcp_sdk::McpServer;
-use rust_mcp_sdk::mcp_server::{server_runtime, ServerRuntime as McpServerRuntime};
-use rust_mcp_sdk::error::McpSdkError; // Make sure McpSdkError is in scope
-use rust_mcp_schema::{InitializeResult as McpInitializeResult, Implementation as McpImplementation, ServerCapabilities as McpServerCapabilities, ServerCapabilitiesTools as McpServerCapabilitiesTools, LATEST_PROTOCOL_VERSION as MCP_LATEST_PROTOCOL_VERSION};
-use rust_mcp_transport::{StdioTransport as McpStdioTransport, TransportOptions as McpTransportOptions};
-
-
-#[cfg(feature = "mcp-sse-server")]
-use rust_mcp_sdk::mcp_server::{
-    hyper_server::create_server as create_mcp_sse_server,
-    HyperServerOptions as McpHyperServerOptions,
-    HyperServer as McpHyperServerRuntime // This is an alias for the HyperServer struct from the SDK
-    // Note: If McpHyperServerRuntime was intended to be a different type, this alias might need adjustment
-    // based on what `rust-mcp-sdk::mcp_server` actually exports as `HyperServerRuntime`.
-    // Given the SDK structure, `HyperServer` is the struct that `create_server` returns and that has the `start` method.
-};
-
-fn setup_tracing_and_logging(log_level_str: &str, app_handle: &tauri::AppHandle) {
-    let level = match log_level_str.to_lowercase().as_str() {
-        "trace" => Level::TRACE,
-        "debug" => Level::DEBUG,
-        "info" => Level::INFO,
-        "warn" => Level::WARN,
-        "error" => Level::ERROR,
-        _ => Level::INFO,
-    };
-
-    let tauri_log_targets = [
-        tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::LogDir {
-            file_name: Some("app_backend.log".into()),
-        }),
-        tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::Webview),
-    ];
-
-    let log_plugin_instance = tauri_plugin_log::Builder::default()
-        .targets(tauri_log_targets)
-        .level_for("hyper", log::LevelFilter::Warn)
-        .level_for("rustls", log::LevelFilter::Warn)
-        .level(match level {
-            Level::TRACE => log::LevelFilter::Trace,
-            Level::DEBUG => log::LevelFilter::Debug,
-            Level::INFO => log::LevelFilter::Info,
-            Level::WARN => log::LevelFilter::Warn,
-            Level::ERROR => log::LevelFilter::Error,
-        })
-        .build();
-
-    app_handle.plugin(log_plugin_instance).expect("Failed to initialize tauri-plugin-log");
-}
-
-
-fn get_mcp_server_details(_app_config: &Config) -> McpInitializeResult {
-    McpInitializeResult {
-        server_info: McpImplementation {
-            name: "mcp-rg-editor-tauri-hosted".to_string(),
-            version: env!("CARGO_PKG_VERSION").to_string(),
-        },
-        capabilities: McpServerCapabilities {
-            tools: Some(McpServerCapabilitiesTools { list_changed: None }),
-            resources: Some(Default::default()),
-            prompts: Some(Default::default()),
-            ..Default::default()
-        },
-        meta: None,
-        instructions: Some(
-            "MCP Server hosted within Tauri. Tools interact with local system via Tauri plugins.".to_string()
-        ),
-        protocol_version: MCP_LATEST_PROTOCOL_VERSION.to_string(),
-    }
-}
-
-fn map_mcp_sdk_error_sync(err: McpSdkError) -> anyhow::Error {
-    anyhow::anyhow!("MCP SDK Error: {:?}", err)
+// Tests build the app on tauri's mock runtime, which needs no window system.
+#[cfg(not(test))]
+pub type AppRuntime = tauri::Wry;
+#[cfg(test)]
+pub type AppRuntime = tauri::test::MockRuntime;
+pub type AppHandle = tauri::AppHandle<AppRuntime>;
+
+/// Manages the state the command handlers and the MCP handler look up. The tests call it too,
+/// so a handler built there sees the same services as the app.
+fn manage_core_state(app_handle: &AppHandle, config_state: &Arc<config::ConfigState>) -> Arc<McpNotifier> {
+    app_handle.manage(config_state.clone());
+    app_handle.manage(Arc::new(utils::audit_logger::AuditLogger::new(config_state.clone())));
+    app_handle.manage(Arc::new(utils::fuzzy_search_logger::FuzzySearchLogger::new(config_state.clone())));
+    let active_sessions_map: ActiveSessionsMap = Default::default();
+    app_handle.manage(active_sessions_map);
+    app_handle.manage(Arc::new(shutdown::Shutdown::default()));
+    app_handle.manage(Arc::new(tokio::sync::Mutex::new(utils::sysinfo_cache::SysinfoCache::new())));
+
+    let mcp_notifier = Arc::new(McpNotifier::new());
+    app_handle.manage(mcp_notifier.clone());
+    app_handle.manage(Arc::new(utils::job_manager::JobManager::new(mcp_notifier.clone())));
+    app_handle.manage(mcp::resource_watcher::ResourceSubscriptions::spawn(mcp_notifier.clone()));
+    app_handle.manage(utils::fs_events::FsEventSubscriptions::spawn(app_handle.clone(), config_state.clone()));
+    let downstream_hub = mcp::downstream::DownstreamHub::new(&config_state.snapshot().downstream_servers, mcp_notifier.clone());
+    downstream_hub.spawn_connect();
+    app_handle.manage(downstream_hub);
+    mcp_notifier
 }
 
-
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
-        .setup(|app| {
+    // Runs before any window or config is created, so it works without FILES_ROOT.
+    if std::env::args().skip(1).any(|arg| arg == "--print-config-schema") {
+        println!("{}", serde_json::to_string_pretty(&config::config_json_schemas()).unwrap_or_default());
+        return;
+    }
+
+    tauri::Builder::<AppRuntime>::new()
+        .setup(move |app| {
             let app_handle = app.handle().clone();
             let config_state_arc = init_config_state(&app_handle);
 
-            let log_level_for_setup = config_state_arc.read().unwrap().log_level.clone();
-            setup_tracing_and_logging(&log_level_for_setup, &app_handle);
-
+            let config_for_setup = config_state_arc.snapshot();
+            logging::setup_tracing_and_logging(&config_for_setup.log_level, config_for_setup.otlp_endpoint.as_deref(), &app_handle);
+            config_validation::log_startup_self_check(&config_state_arc.snapshot());
 
-            app.manage(config_state_arc.clone());
 
-            let audit_logger = Arc::new(utils::audit_logger::AuditLogger::new(config_state_arc.clone()));
-            app.manage(audit_logger);
+            let mcp_notifier = manage_core_state(&app_handle, &config_state_arc);
+            tauri::async_runtime::spawn(shutdown::exit_on_signal(app_handle.clone()));
+            mcp::logging::install(mcp_notifier.clone());
 
-            let fuzzy_search_logger = Arc::new(utils::fuzzy_search_logger::FuzzySearchLogger::new(config_state_arc.clone()));
-            app.manage(fuzzy_search_logger);
-
-            let active_sessions_map: ActiveSessionsMap = Default::default();
-            app.manage(active_sessions_map);
-
-            let sysinfo_state_for_mcp_and_commands = Arc::new(tokio::sync::Mutex::new(sysinfo::System::new_all()));
-            app.manage(sysinfo_state_for_mcp_and_commands.clone());
+            config_watcher::spawn_config_watcher(app_handle.clone(), config_state_arc.clone());
 
 
             let mcp_app_handle_clone = app_handle.clone();
@@ -126,83 +89,11 @@ pub fn run() {
                 config_state: mcp_config_state_clone,
             };
 
-            tauri::async_runtime::spawn(async move {
-                tracing::info!("Attempting to start MCP server...");
-                let transport_mode_from_config = {
-                    let cfg_guard = mcp_launch_params.config_state.read().expect("Failed to read config for MCP transport");
-                    cfg_guard.mcp_transport_mode.clone()
-                };
-
-                if transport_mode_from_config == AppTransportMode::Disabled {
-                    tracing::info!("MCP_TRANSPORT is 'disabled'. MCP server will not be started.");
-                    return;
-                }
-
-                let mcp_server_details = {
-                    let cfg_guard = mcp_launch_params.config_state.read().expect("Failed to read config for MCP details");
-                    get_mcp_server_details(&cfg_guard)
-                };
-
-                let mcp_handler = EnhancedServerHandler::new(mcp_launch_params.app_handle.clone(), mcp_launch_params.config_state.clone());
-
-                match transport_mode_from_config {
-                    #[cfg(feature = "mcp-stdio-server")]
-                    AppTransportMode::Stdio => {
-                        tracing::info!("Starting MCP server with STDIO transport.");
-                        let mcp_transport_opts = McpTransportOptions::default();
-                        match McpStdioTransport::new(mcp_transport_opts) {
-                            Ok(transport) => {
-                                let mcp_server_runtime: McpServerRuntime = server_runtime::create_server(mcp_server_details, transport, mcp_handler);
-                                if let Err(e) = mcp_server_runtime.start().await.map_err(map_mcp_sdk_error_sync) {
-                                    tracing::error!("MCP STDIO Server failed to start or shut down with error: {:?}", e);
-                                } else {
-                                    tracing::info!("MCP STDIO Server shut down.");
-                                }
-                            }
-                            Err(e) => {
-                                tracing::error!("Failed to create MCP StdioTransport: {}", e);
-                            }
-                        }
-                    }
-                    #[cfg(feature = "mcp-sse-server")]
-                    AppTransportMode::Sse => {
-                        let (host, port) = {
-                            let cfg_guard = mcp_launch_params.config_state.read().expect("Failed to read config for SSE params");
-                            let sse_host = cfg_guard.mcp_sse_host.clone().unwrap_or_else(|| "127.0.0.1".to_string());
-                            let sse_port = cfg_guard.mcp_sse_port.unwrap_or(3030);
-                            (sse_host, sse_port)
-                        };
-                        tracing::info!("Starting MCP server with SSE transport on {}:{}", host, port);
-                        let mcp_sse_options = McpHyperServerOptions {
-                            host,
-                            port,
-                            // enable_cors: true, // REMOVED: This field does not exist in rust-mcp-sdk 0.2.6 HyperServerOptions
-                            ..Default::default()
-                        };
-                        let mcp_sse_server_runtime: McpHyperServerRuntime = create_mcp_sse_server(mcp_server_details, mcp_handler, mcp_sse_options);
-                         if let Err(e) = mcp_sse_server_runtime.start().await
-                            .map_err(|transport_server_err| { // transport_server_err is TransportServerError
-                                let mcp_sdk_err: McpSdkError = transport_server_err.into(); // Convert to McpSdkError
-                                map_mcp_sdk_error_sync(mcp_sdk_err) // Now this matches the function signature
-                            }) {
-                            tracing::error!("MCP SSE Server failed to start or shut down with error: {:?}", e);
-                        } else {
-                            tracing::info!("MCP SSE Server shut down.");
-                        }
-                    }
-                     _ => {
-                        if transport_mode_from_config == AppTransportMode::Stdio && !cfg!(feature="mcp-stdio-server") {
-                             tracing::error!("MCP_TRANSPORT is 'stdio' but 'mcp-stdio-server' feature is not enabled in Cargo.toml.");
-                        } else if transport_mode_from_config == AppTransportMode::Sse && !cfg!(feature="mcp-sse-server") {
-                             tracing::error!("MCP_TRANSPORT is 'sse' but 'mcp-sse-server' feature is not enabled in Cargo.toml.");
-                        } else if transport_mode_from_config != AppTransportMode::Stdio && transport_mode_from_config != AppTransportMode::Sse && transport_mode_from_config != AppTransportMode::Disabled {
-                             tracing::warn!("Unknown MCP_TRANSPORT mode configured: {:?}. MCP server not started.", transport_mode_from_config);
-                        } else if transport_mode_from_config != AppTransportMode::Disabled {
-                             tracing::info!("MCP server not started as the configured transport mode ({:?}) feature is not enabled.", transport_mode_from_config);
-                        }
-                    }
-                }
-            });
+            let mcp_server_control = McpServerControl::new(mcp_launch_params, mcp_notifier);
+            app.manage(mcp_server_control.clone());
+            tauri::async_runtime::spawn(async move { mcp_server_control.start().await });
+            // Desktops without a tray (some Linux sessions) still get the window.
+            if let Err(e) = tray::setup(&app_handle) { tracing::warn!(error = %e, "System tray unavailable"); }
 
 
             if which::which("rg").is_err() {
@@ -224,11 +115,31 @@ pub fn run() {
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             commands::greet,
             commands::config_commands::get_config_command,
             commands::config_commands::set_config_value_command,
+            commands::config_commands::get_config_schema_command,
+            commands::config_commands::export_config_command,
+            commands::config_commands::import_config_command,
+            commands::config_commands::pick_directory_for_files_root,
+            commands::config_commands::pick_allowed_directory,
+            commands::secret_commands::set_secret_command,
+            commands::secret_commands::delete_secret_command,
+            commands::mcp_commands::get_mcp_server_status,
+            commands::mcp_commands::get_mcp_server_stats,
+            commands::mcp_commands::restart_mcp_server,
+            commands::mcp_commands::set_mcp_transport,
+            commands::fs_event_commands::subscribe_fs_events,
+            commands::fs_event_commands::unsubscribe_fs_events,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // Without this the runtime drops the MCP tasks mid-call and leaks command sessions.
+            if let tauri::RunEvent::ExitRequested { code, api, .. } = &event {
+                shutdown::on_exit_requested(app_handle, *code, api);
+            }
+        });
 }
\ No newline at end of file
@@ -0,0 +1,83 @@
+use once_cell::sync::OnceCell;
+use tracing::Level;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+// Lets the tracing filter be swapped after startup; set once by setup_tracing_and_logging.
+static TRACING_FILTER_HANDLE: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+pub fn parse_log_level(log_level_str: &str) -> Option<Level> {
+    match log_level_str.to_lowercase().as_str() {
+        "trace" => Some(Level::TRACE),
+        "debug" => Some(Level::DEBUG),
+        "info" => Some(Level::INFO),
+        "warn" => Some(Level::WARN),
+        "error" => Some(Level::ERROR),
+        _ => None,
+    }
+}
+
+fn log_level_filter(level: Level) -> log::LevelFilter {
+    match level {
+        Level::TRACE => log::LevelFilter::Trace,
+        Level::DEBUG => log::LevelFilter::Debug,
+        Level::INFO => log::LevelFilter::Info,
+        Level::WARN => log::LevelFilter::Warn,
+        Level::ERROR => log::LevelFilter::Error,
+    }
+}
+
+fn tracing_filter(level: Level) -> EnvFilter {
+    EnvFilter::new(format!("{},hyper=warn,rustls=warn", level.as_str().to_lowercase()))
+}
+
+/// Installs the tracing subscriber (stderr, since stdout carries MCP STDIO traffic) and
+/// tauri-plugin-log for `log` records. Both filters can later be changed with `apply_log_level`.
+/// With `otlp_endpoint`, spans that pass the filter are also exported there.
+pub fn setup_tracing_and_logging(log_level_str: &str, otlp_endpoint: Option<&str>, app_handle: &crate::AppHandle) {
+    let level = parse_log_level(log_level_str).unwrap_or(Level::INFO);
+
+    let (filter_layer, reload_handle) = reload::Layer::new(tracing_filter(level));
+    let subscriber = Registry::default()
+        .with(filter_layer)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_ansi(false))
+        .with(crate::mcp::logging::McpLogLayer)
+        .with(crate::telemetry::start(otlp_endpoint));
+    // set_global_default rather than try_init: try_init would also claim the `log` logger,
+    // which tauri-plugin-log needs below.
+    match tracing::subscriber::set_global_default(subscriber) {
+        Ok(()) => { let _ = TRACING_FILTER_HANDLE.set(reload_handle); }
+        Err(e) => eprintln!("Tracing subscriber already installed; log level changes will not apply to it: {}", e),
+    }
+
+    let tauri_log_targets = [
+        tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::LogDir {
+            file_name: Some("app_backend.log".into()),
+        }),
+        tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::Webview),
+    ];
+
+    // The plugin itself lets everything through; log::set_max_level is the knob that
+    // apply_log_level turns, because the plugin's own level is fixed once it is built.
+    let log_plugin_instance = tauri_plugin_log::Builder::default()
+        .targets(tauri_log_targets)
+        .level_for("hyper", log::LevelFilter::Warn)
+        .level_for("rustls", log::LevelFilter::Warn)
+        .level(log::LevelFilter::Trace)
+        .build();
+
+    app_handle.plugin(log_plugin_instance).expect("Failed to initialize tauri-plugin-log");
+    log::set_max_level(log_level_filter(level));
+}
+
+/// Switches both tracing and `log` output to a new level without a restart.
+pub fn apply_log_level(log_level_str: &str) -> Result<(), String> {
+    let level = parse_log_level(log_level_str)
+        .ok_or_else(|| format!("Invalid log level '{}'. Valid options are trace, debug, info, warn, error.", log_level_str))?;
+    if let Some(handle) = TRACING_FILTER_HANDLE.get() {
+        handle.reload(tracing_filter(level)).map_err(|e| format!("Failed to reload tracing filter: {}", e))?;
+    }
+    log::set_max_level(log_level_filter(level));
+    tracing::info!(log_level = %level, "Log level changed");
+    Ok(())
+}
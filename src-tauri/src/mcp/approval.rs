@@ -0,0 +1,108 @@
+use crate::config::Config;
+use crate::mcp::protocol::ProtocolVersion;
+
+use regex::Regex;
+use rust_mcp_sdk::McpServer;
+use rust_mcp_schema::schema_utils::RequestFromServer;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::time::{timeout, Duration};
+use tokio_util::sync::CancellationToken;
+
+/// In approval_required_tools, stands for every tool with destructiveHint.
+pub const DESTRUCTIVE_ALIAS: &str = "destructive";
+// rust-mcp-schema has no typed request for it yet.
+const ELICITATION_METHOD: &str = "elicitation/create";
+// Long enough for a person to read the request and answer.
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+// Arguments shown to the user are cut off here, so a write_file body does not flood the prompt.
+const MAX_SHOWN_ARGS_CHARS: usize = 600;
+
+/// Why a call needs approval under approval_required_tools and approval_command_patterns, or
+/// None when it runs without asking. `destructive` is whether the tool has destructiveHint.
+pub fn approval_reason(config: &Config, tool_name: &str, args: &Value, destructive: bool) -> Option<String> {
+    // A dry run signals nothing.
+    if tool_name == "kill_processes_matching" && args.get("confirm").and_then(Value::as_bool) != Some(true) { return None; }
+    let listed = config.approval_required_tools.iter().any(|t| t == tool_name || (t == DESTRUCTIVE_ALIAS && destructive));
+    if listed { return Some(format!("'{}' is in approval_required_tools", tool_name)); }
+    if tool_name != "execute_command" { return None; }
+    let command = args.get("command").and_then(Value::as_str).unwrap_or_default();
+    for pattern in &config.approval_command_patterns {
+        match Regex::new(pattern) {
+            Ok(re) if re.is_match(command) => return Some(format!("the command matches approval_command_patterns entry '{}'", pattern)),
+            Ok(_) => {}
+            // Fails closed; validate_config reports the pattern.
+            Err(_) => return Some(format!("approval_command_patterns entry '{}' is not a valid regex", pattern)),
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovalDecision {
+    pub approved: bool,
+    // The client's accept, decline or cancel; timeout, cancelled or error when it gave none.
+    pub action: String,
+    // What the user typed into the note field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ApprovalDecision {
+    fn denied(action: &str, error: String) -> Self {
+        Self { approved: false, action: action.to_string(), note: None, error: Some(error) }
+    }
+}
+
+#[derive(Deserialize)]
+struct ElicitResult {
+    action: String,
+    #[serde(default)]
+    content: Option<Map<String, Value>>,
+}
+
+/// Asks the client, via elicitation/create, whether the call may run. Anything but an accepted
+/// form with `confirm: true` denies it, including clients without elicitation support.
+pub async fn request_approval(runtime: &dyn McpServer, tool_name: &str, args: &Value, reason: &str, cancel: &CancellationToken) -> ApprovalDecision {
+    let version = ProtocolVersion::of_session(runtime);
+    if !version.has_elicitation() {
+        return ApprovalDecision::denied("unsupported", format!("The client negotiated protocol {}, which has no elicitation", version.as_str()));
+    }
+    let mut shown_args = args.to_string();
+    if shown_args.chars().count() > MAX_SHOWN_ARGS_CHARS {
+        shown_args = format!("{}…", shown_args.chars().take(MAX_SHOWN_ARGS_CHARS).collect::<String>());
+    }
+    let request = RequestFromServer::CustomRequest(json!({
+        "method": ELICITATION_METHOD,
+        "params": {
+            "message": format!("Allow '{}' to run? It needs approval because {}.\n\nArguments: {}", tool_name, reason, shown_args),
+            "requestedSchema": {
+                "type": "object",
+                "properties": {
+                    "confirm": { "type": "boolean", "title": "Run this call", "description": format!("Let '{}' run with the arguments shown.", tool_name) },
+                    "note": { "type": "string", "title": "Note", "description": "Optional; recorded in the audit log with the decision." },
+                },
+                "required": ["confirm"],
+            },
+        },
+    }));
+    let response = tokio::select! {
+        response = timeout(APPROVAL_TIMEOUT, runtime.request(request)) => response,
+        _ = cancel.cancelled() => return ApprovalDecision::denied("cancelled", "The client cancelled the call while approval was pending".to_string()),
+    };
+    let value = match response {
+        Err(_) => return ApprovalDecision::denied("timeout", format!("No answer within {} s", APPROVAL_TIMEOUT.as_secs())),
+        // Also how clients without elicitation support answer.
+        Ok(Err(e)) => return ApprovalDecision::denied("error", format!("elicitation/create failed: {:?}", e)),
+        Ok(Ok(result)) => serde_json::to_value(result).unwrap_or_default(),
+    };
+    let Ok(result) = serde_json::from_value::<ElicitResult>(value) else {
+        return ApprovalDecision::denied("error", "The client's elicitation/create result has no action".to_string());
+    };
+    let field = |name: &str| result.content.as_ref().and_then(|c| c.get(name).cloned());
+    let confirmed = field("confirm").and_then(|v| v.as_bool()) == Some(true);
+    let note = field("note").and_then(|v| v.as_str().map(str::to_string)).filter(|n| !n.is_empty());
+    ApprovalDecision { approved: result.action == "accept" && confirmed, action: result.action, note, error: None }
+}
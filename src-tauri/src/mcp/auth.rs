@@ -0,0 +1,284 @@
+use crate::mcp::handler::changes_state;
+use crate::secrets;
+
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// What a network client's token lets it do. Clients without a token (stdio, or a network
+/// transport with no tokens configured) have full access, limited only by the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessScope {
+    #[default]
+    Full,
+    ReadOnly,
+}
+
+impl AccessScope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "full" => Some(Self::Full),
+            "read" | "readonly" | "read_only" => Some(Self::ReadOnly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::ReadOnly => "read",
+        }
+    }
+
+    pub fn allows_tool(self, tool_name: &str) -> bool {
+        self == Self::Full || !changes_state(tool_name)
+    }
+}
+
+/// Comma-separated `[scope:]token` entries, e.g. `full:abc123,read:def456`. Scope defaults to full.
+pub const AUTH_TOKENS_ENV: &str = "MCP_AUTH_TOKENS";
+/// Keyring secrets named `mcp_token.<scope>.<label>` hold bearer tokens.
+pub const TOKEN_SECRET_PREFIX: &str = "mcp_token.";
+/// Keyring secrets named `mcp_client.<scope>.<client_id>` hold OAuth client secrets.
+pub const CLIENT_SECRET_PREFIX: &str = "mcp_client.";
+/// Lifetime of tokens issued by the OAuth token endpoint.
+pub const ISSUED_TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Who a request authenticated as. The label names the token or OAuth client in logs and
+/// ties HTTP sessions to the client that created them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub label: String,
+    pub scope: AccessScope,
+}
+
+/// The network client behind one connection, which tools use to keep its state apart from
+/// other clients'.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIdentity {
+    // Owns command sessions: the token label when tokens are required, otherwise the
+    // connection, so anonymous clients are kept apart too.
+    pub owner: String,
+    // Token label and connection id, for logs and the audit log.
+    pub label: String,
+}
+
+struct IssuedToken {
+    identity: Identity,
+    expires_at: Instant,
+}
+
+/// Bearer tokens accepted by the network transports, loaded once at startup.
+#[derive(Default)]
+pub struct Authenticator {
+    // Set when any token or client is configured, even if reading it from the keyring failed,
+    // so a broken keyring locks the server instead of opening it.
+    enabled: bool,
+    tokens: Vec<(String, Identity)>,
+    // client_id -> (client_secret, scope)
+    clients: HashMap<String, (String, AccessScope)>,
+    issued: StdMutex<HashMap<String, IssuedToken>>,
+}
+
+fn is_auth_secret(name: &str) -> bool {
+    name.starts_with(TOKEN_SECRET_PREFIX) || name.starts_with(CLIENT_SECRET_PREFIX)
+}
+
+/// Whether network transports will require a token, without reading the keyring.
+pub fn auth_configured(secret_names: &[String]) -> bool {
+    std::env::var(AUTH_TOKENS_ENV).is_ok_and(|v| v.split(',').any(|e| !e.trim().is_empty())) || secret_names.iter().any(|n| is_auth_secret(n))
+}
+
+// Splits `<scope>.<name>` from a secret name after its prefix.
+fn scoped_secret_name(rest: &str) -> Option<(AccessScope, &str)> {
+    let (scope, name) = rest.split_once('.')?;
+    Some((AccessScope::parse(scope)?, name)).filter(|(_, name)| !name.is_empty())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl Authenticator {
+    /// Reads MCP_AUTH_TOKENS and the `mcp_token.*` / `mcp_client.*` keyring secrets listed in
+    /// `secret_names`. With none of them configured, authentication is off.
+    pub async fn load(secret_names: Vec<String>) -> Self {
+        let mut auth = Self::default();
+        if let Ok(value) = std::env::var(AUTH_TOKENS_ENV) {
+            for (index, entry) in value.split(',').map(str::trim).filter(|e| !e.is_empty()).enumerate() {
+                auth.enabled = true;
+                let (scope, token) = entry.split_once(':').and_then(|(scope, token)| Some((AccessScope::parse(scope)?, token))).unwrap_or((AccessScope::Full, entry));
+                if token.is_empty() { warn!(entry = index + 1, "Ignoring empty token in {}", AUTH_TOKENS_ENV); continue; }
+                auth.tokens.push((token.to_string(), Identity { label: format!("env-{}", index + 1), scope }));
+            }
+        }
+
+        let names: Vec<String> = secret_names.into_iter().filter(|n| is_auth_secret(n)).collect();
+        auth.enabled |= !names.is_empty();
+        let loaded = tauri::async_runtime::spawn_blocking(move || {
+            names.into_iter().map(|name| { let value = secrets::get_secret(&name); (name, value) }).collect::<Vec<_>>()
+        }).await.unwrap_or_default();
+        for (name, value) in loaded {
+            let value = match value {
+                Ok(Some(value)) => value,
+                Ok(None) => { warn!(secret = %name, "Auth secret is listed but missing from the keyring"); continue; }
+                Err(e) => { warn!(secret = %name, error = %e, "Auth secret could not be read; it will not be accepted"); continue; }
+            };
+            if let Some((scope, label)) = name.strip_prefix(TOKEN_SECRET_PREFIX).and_then(scoped_secret_name) {
+                auth.tokens.push((value, Identity { label: label.to_string(), scope }));
+            } else if let Some((scope, client_id)) = name.strip_prefix(CLIENT_SECRET_PREFIX).and_then(scoped_secret_name) {
+                auth.clients.insert(client_id.to_string(), (value, scope));
+            } else {
+                warn!(secret = %name, "Ignoring auth secret; expected <prefix><full|read>.<name>");
+            }
+        }
+        if auth.enabled {
+            info!(tokens = auth.tokens.len(), oauth_clients = auth.clients.len(), "Network MCP transports require a bearer token");
+        }
+        auth
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn has_oauth_clients(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    /// Connections that authenticated with the same token share their sessions, e.g. a client
+    /// reconnecting after a dropped stream.
+    pub fn client_identity(&self, identity: &Identity, connection_id: &str) -> ClientIdentity {
+        let owner = if self.enabled { identity.label.clone() } else { format!("connection:{}", connection_id) };
+        ClientIdentity { owner, label: format!("{}@{}", identity.label, connection_id) }
+    }
+
+    /// Checks an `Authorization: Bearer <token>` header value. Returns None when the request
+    /// must be rejected; with authentication off every request is anonymous with full access.
+    pub fn authenticate(&self, authorization: Option<&str>) -> Option<Identity> {
+        if !self.enabled {
+            return Some(Identity { label: "anonymous".to_string(), scope: AccessScope::Full });
+        }
+        let token = authorization.and_then(bearer_token)?;
+        if let Some((_, identity)) = self.tokens.iter().find(|(known, _)| constant_time_eq(known.as_bytes(), token.as_bytes())) {
+            return Some(identity.clone());
+        }
+        let mut issued = self.issued.lock().ok()?;
+        match issued.get(token) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.identity.clone()),
+            Some(_) => { issued.remove(token); None }
+            None => None,
+        }
+    }
+
+    /// OAuth 2.0 client credentials grant: a short-lived token for a known client and secret.
+    pub fn issue_token(&self, client_id: &str, client_secret: &str) -> Option<(String, Identity)> {
+        let (secret, scope) = self.clients.get(client_id)?;
+        if !constant_time_eq(secret.as_bytes(), client_secret.as_bytes()) { return None; }
+        let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        let identity = Identity { label: format!("oauth:{}", client_id), scope: *scope };
+        let mut issued = self.issued.lock().ok()?;
+        let now = Instant::now();
+        issued.retain(|_, entry| entry.expires_at > now);
+        issued.insert(token.clone(), IssuedToken { identity: identity.clone(), expires_at: now + ISSUED_TOKEN_TTL });
+        info!(client_id = %client_id, scope = scope.as_str(), "Issued OAuth access token");
+        Some((token, identity))
+    }
+}
+
+fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim()).filter(|t| !t.is_empty())
+}
+
+#[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+pub mod routes {
+    use super::Authenticator;
+
+    use axum::extract::State;
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use base64::Engine;
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    pub fn authorization_header(headers: &HeaderMap) -> Option<&str> {
+        headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok())
+    }
+
+    /// 401 with the `WWW-Authenticate` challenge MCP clients look for.
+    pub fn unauthorized() -> Response {
+        let challenge = "Bearer realm=\"mcp-rg-editor\", error=\"invalid_token\"";
+        (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, challenge)], Json(json!({ "error": "invalid_token" }))).into_response()
+    }
+
+    #[derive(Clone)]
+    struct OAuthState {
+        auth: Arc<Authenticator>,
+        // "https" when the server runs with TLS, for the issuer URL in the discovery document.
+        scheme: &'static str,
+    }
+
+    /// `POST /oauth/token` and its discovery document, mounted when OAuth clients are configured.
+    pub fn oauth_router(auth: Arc<Authenticator>, tls: bool) -> Router {
+        Router::new()
+            .route("/oauth/token", post(token))
+            .route("/.well-known/oauth-authorization-server", get(metadata))
+            .with_state(OAuthState { auth, scheme: if tls { "https" } else { "http" } })
+    }
+
+    #[derive(Deserialize)]
+    struct TokenRequest {
+        grant_type: String,
+        client_id: Option<String>,
+        client_secret: Option<String>,
+    }
+
+    fn oauth_error(status: StatusCode, error: &str) -> Response {
+        (status, Json(json!({ "error": error }))).into_response()
+    }
+
+    // RFC 6749 section 2.3.1: credentials come as HTTP Basic or in the form body.
+    fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+        let value = authorization_header(headers)?;
+        let (scheme, encoded) = value.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("basic") { return None; }
+        let decoded = String::from_utf8(base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()?).ok()?;
+        let (id, secret) = decoded.split_once(':')?;
+        Some((id.to_string(), secret.to_string()))
+    }
+
+    async fn token(State(OAuthState { auth, .. }): State<OAuthState>, headers: HeaderMap, Form(request): Form<TokenRequest>) -> Response {
+        if request.grant_type != "client_credentials" {
+            return oauth_error(StatusCode::BAD_REQUEST, "unsupported_grant_type");
+        }
+        let credentials = basic_credentials(&headers).or_else(|| request.client_id.zip(request.client_secret));
+        let Some((client_id, client_secret)) = credentials else { return oauth_error(StatusCode::UNAUTHORIZED, "invalid_client") };
+        match auth.issue_token(&client_id, &client_secret) {
+            Some((access_token, identity)) => Json(json!({
+                "access_token": access_token,
+                "token_type": "Bearer",
+                "expires_in": super::ISSUED_TOKEN_TTL.as_secs(),
+                "scope": identity.scope.as_str(),
+            })).into_response(),
+            None => oauth_error(StatusCode::UNAUTHORIZED, "invalid_client"),
+        }
+    }
+
+    async fn metadata(State(OAuthState { scheme, .. }): State<OAuthState>, headers: HeaderMap) -> Response {
+        let host = headers.get(header::HOST).and_then(|v| v.to_str().ok()).unwrap_or("127.0.0.1");
+        let issuer = format!("{}://{}", scheme, host);
+        Json(json!({
+            "issuer": issuer,
+            "token_endpoint": format!("{}/oauth/token", issuer),
+            "grant_types_supported": ["client_credentials"],
+            "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post"],
+            "response_types_supported": [],
+            "scopes_supported": ["full", "read"],
+        })).into_response()
+    }
+}
@@ -0,0 +1,73 @@
+use rust_mcp_schema::schema_utils::CallToolError;
+use rust_mcp_schema::{CallToolResult, CallToolResultContentItem};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const BATCH_TOOL: &str = "batch_tools";
+/// More calls than this are rejected before any runs.
+pub const MAX_BATCH_CALLS: usize = 32;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct BatchCallMCP {
+    /// Tool name, as in tools/list.
+    pub name: String,
+    /// The tool's arguments.
+    #[serde(default)]
+    pub arguments: Map<String, Value>,
+}
+
+fn default_stop_on_error() -> bool { true }
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct BatchToolsParamsMCP {
+    /// Tool calls to run in order. batch_tools itself cannot be one of them.
+    pub calls: Vec<BatchCallMCP>,
+    /// Skip the remaining calls after one fails. With false, every call runs.
+    #[serde(default = "default_stop_on_error", alias = "stopOnError")]
+    pub stop_on_error: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchCallResultMCP {
+    pub index: usize,
+    pub name: String,
+    pub ok: bool,
+    // The tool's result as it would have returned it: parsed JSON, or the text if it is not JSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchToolsResultMCP {
+    pub results: Vec<BatchCallResultMCP>,
+    pub succeeded: usize,
+    pub failed: usize,
+    // Calls not run because an earlier one failed with stop_on_error set.
+    pub skipped: usize,
+}
+
+fn content_value(result: &CallToolResult) -> Value {
+    let texts: Vec<&str> = result.content.iter().filter_map(|item| match item {
+        CallToolResultContentItem::TextContent(text) => Some(text.text.as_str()),
+        _ => None,
+    }).collect();
+    match texts.as_slice() {
+        [] => Value::Null,
+        [text] => serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string())),
+        _ => Value::Array(texts.iter().map(|t| serde_json::from_str(t).unwrap_or_else(|_| Value::String(t.to_string()))).collect()),
+    }
+}
+
+impl BatchCallResultMCP {
+    pub fn from_outcome(index: usize, name: String, outcome: Result<CallToolResult, CallToolError>) -> Self {
+        match outcome {
+            // A result flagged is_error counts as failed too.
+            Ok(result) if result.is_error == Some(true) => Self { index, name, ok: false, result: Some(content_value(&result)), error: Some("The tool reported an error".to_string()) },
+            Ok(result) => Self { index, name, ok: true, result: Some(content_value(&result)), error: None },
+            Err(e) => Self { index, name, ok: false, result: None, error: Some(e.to_string()) },
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::config::LimitsConfig;
+use crate::error::AppError;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex as StdMutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::{timeout, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+#[derive(Default)]
+struct QueueSize {
+    // The configured limits.max_concurrent_calls the semaphore was last sized for.
+    size: usize,
+    // Permits to retire as running calls finish, after the limit was lowered while they ran.
+    owed: usize,
+}
+
+/// Caps tool calls running at once across every transport and client. Calls over the cap wait
+/// in FIFO order (tokio's semaphore is fair) for up to limits.call_queue_timeout_ms.
+pub struct CallQueue {
+    semaphore: Semaphore,
+    size: StdMutex<QueueSize>,
+    waiting: AtomicUsize,
+}
+
+impl Default for CallQueue {
+    fn default() -> Self {
+        Self { semaphore: Semaphore::new(0), size: StdMutex::default(), waiting: AtomicUsize::new(0) }
+    }
+}
+
+/// One running call's slot; released on drop.
+pub struct CallSlot<'a> {
+    queue: &'a CallQueue,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl CallQueue {
+    // Limits can change at runtime, so the semaphore follows them on every call.
+    fn resize(&self, max: usize) {
+        let Ok(mut state) = self.size.lock() else { return };
+        if max > state.size {
+            let grow = max - state.size;
+            let repaid = grow.min(state.owed);
+            state.owed -= repaid;
+            self.semaphore.add_permits(grow - repaid);
+        } else if max < state.size {
+            let shrink = state.size - max;
+            state.owed += shrink - self.semaphore.forget_permits(shrink);
+        }
+        state.size = max;
+    }
+
+    #[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+    pub fn queued(&self) -> usize {
+        self.waiting.load(Ordering::Acquire)
+    }
+
+    /// Waits for a free slot. Fails when the wait exceeds limits.call_queue_timeout_ms, or when
+    /// the client cancels the call while it is queued.
+    pub async fn acquire(&self, limits: &LimitsConfig, cancel: &CancellationToken, tool_name: &str) -> Result<CallSlot<'_>, AppError> {
+        self.resize(limits.max_concurrent_calls);
+        if let Ok(permit) = self.semaphore.try_acquire() {
+            return Ok(CallSlot { queue: self, permit: Some(permit) });
+        }
+        let queued = self.waiting.fetch_add(1, Ordering::AcqRel) + 1;
+        debug!(tool = tool_name, queued, "Tool call queued for a limits.max_concurrent_calls slot");
+        let started = Instant::now();
+        let result = tokio::select! {
+            acquired = timeout(Duration::from_millis(limits.call_queue_timeout_ms), self.semaphore.acquire()) => acquired,
+            _ = cancel.cancelled() => {
+                self.waiting.fetch_sub(1, Ordering::AcqRel);
+                return Err(AppError::Cancelled(format!("'{}' while queued", tool_name)));
+            }
+        };
+        self.waiting.fetch_sub(1, Ordering::AcqRel);
+        match result {
+            Ok(Ok(permit)) => {
+                debug!(tool = tool_name, waited_ms = started.elapsed().as_millis() as u64, "Tool call left the queue");
+                Ok(CallSlot { queue: self, permit: Some(permit) })
+            }
+            // The semaphore is never closed.
+            Ok(Err(_)) => Err(AppError::Unknown("Tool call queue closed".to_string())),
+            Err(_) => {
+                let waited_ms = started.elapsed().as_millis() as u64;
+                let message = format!("'{}' waited {} ms for a free slot; limits.max_concurrent_calls is {} and limits.call_queue_timeout_ms is {}", tool_name, waited_ms, limits.max_concurrent_calls, limits.call_queue_timeout_ms);
+                Err(AppError::LimitExceeded { message, limit: "limits.call_queue_timeout_ms".to_string(), max: limits.call_queue_timeout_ms, actual: waited_ms })
+            }
+        }
+    }
+}
+
+impl Drop for CallSlot<'_> {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else { return };
+        if let Ok(mut state) = self.queue.size.lock() {
+            if state.owed > 0 {
+                state.owed -= 1;
+                permit.forget();
+            }
+        }
+    }
+}
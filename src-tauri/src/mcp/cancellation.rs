@@ -0,0 +1,68 @@
+use crate::error::AppError;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex as StdMutex;
+use tokio_util::sync::{CancellationToken, DropGuard};
+
+/// Tool calls running on one MCP connection, so `notifications/cancelled` can reach them. Each
+/// call's token is a child of the connection's `scope`, which is a child of the server's
+/// shutdown token: cancelling either stops every call below it.
+#[derive(Default)]
+pub struct InFlightCalls {
+    next_id: AtomicU64,
+    calls: StdMutex<HashMap<u64, CancellationToken>>,
+    scope: CancellationToken,
+}
+
+/// Registration of one running call; removed from `InFlightCalls` on drop.
+pub struct InFlightCall<'a> {
+    owner: &'a InFlightCalls,
+    id: u64,
+    pub token: CancellationToken,
+}
+
+impl InFlightCalls {
+    /// Calls on a connection that `parent` (the shutdown token) cancels as well.
+    pub fn within(parent: &CancellationToken) -> Self {
+        Self { scope: parent.child_token(), ..Self::default() }
+    }
+
+    pub fn begin(&self) -> InFlightCall<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = self.scope.child_token();
+        if let Ok(mut calls) = self.calls.lock() { calls.insert(id, token.clone()); }
+        InFlightCall { owner: self, id, token }
+    }
+
+    /// rust-mcp-sdk does not hand JSON-RPC request ids to the handler, so a cancellation cannot
+    /// be matched to one call. Clients wait for a call before sending the next one on a
+    /// connection, so every call in flight on it is cancelled. Returns how many were.
+    pub fn cancel_all(&self) -> usize {
+        let Ok(calls) = self.calls.lock() else { return 0 };
+        calls.values().for_each(CancellationToken::cancel);
+        calls.len()
+    }
+
+    /// Cancels the calls still running, and any begun later, once dropped: held by a network
+    /// session so a client that disconnects mid-call does not leave work behind.
+    pub fn cancel_on_drop(&self) -> DropGuard {
+        self.scope.clone().drop_guard()
+    }
+}
+
+impl Drop for InFlightCall<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut calls) = self.owner.calls.lock() { calls.remove(&self.id); }
+    }
+}
+
+/// Runs `fut` until it finishes or `cancel` fires. Only for work that stops cleanly when its
+/// future is dropped (reqwest requests, async directory walks); child processes must be killed.
+pub async fn cancellable<F: Future>(cancel: &CancellationToken, what: &str, fut: F) -> Result<F::Output, AppError> {
+    tokio::select! {
+        output = fut => Ok(output),
+        _ = cancel.cancelled() => Err(AppError::Cancelled(what.to_string())),
+    }
+}
@@ -0,0 +1,83 @@
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::mcp::resources::FILE_RESOURCE_TEMPLATE;
+use crate::utils::path_utils::{denied_glob_set, validate_and_normalize_path};
+
+use rust_mcp_schema::{CompleteRequestParamsRef, CompleteResultCompletion};
+use tauri_plugin_fs::FsExt;
+use tokio::fs as tokio_fs;
+use tracing::debug;
+
+// completion/complete may return at most 100 values.
+const MAX_COMPLETION_VALUES: usize = 100;
+
+fn empty_completion() -> CompleteResultCompletion {
+    CompleteResultCompletion { values: Vec::new(), total: None, has_more: None }
+}
+
+/// Completions for the path arguments this server defines: the `path` of the summarize_file
+/// prompt and of the file:///{path} resource template. Other references complete to nothing.
+pub async fn complete(deps: &ToolDependencies, reference: &CompleteRequestParamsRef, argument_name: &str, value: &str) -> Result<CompleteResultCompletion, AppError> {
+    match reference {
+        CompleteRequestParamsRef::PromptReference(prompt) if prompt.name == "summarize_file" && argument_name == "path" => complete_path(deps, value).await,
+        CompleteRequestParamsRef::ResourceReference(resource) if resource.uri == FILE_RESOURCE_TEMPLATE && argument_name == "path" => {
+            // The template already supplies the leading "/" of Unix paths.
+            if cfg!(windows) { return complete_path(deps, value).await; }
+            let mut completion = complete_path(deps, &format!("/{}", value)).await?;
+            completion.values = completion.values.into_iter().map(|v| v.trim_start_matches('/').to_string()).collect();
+            Ok(completion)
+        }
+        _ => Ok(empty_completion()),
+    }
+}
+
+// Lower is better: case-sensitive prefix, case-insensitive prefix, substring, then in-order characters.
+fn match_rank(name: &str, typed: &str) -> Option<u8> {
+    if typed.is_empty() || name.starts_with(typed) { return Some(0); }
+    let name_lower = name.to_lowercase();
+    let typed_lower = typed.to_lowercase();
+    if name_lower.starts_with(&typed_lower) { return Some(1); }
+    if name_lower.contains(&typed_lower) { return Some(2); }
+    let mut name_chars = name_lower.chars();
+    typed_lower.chars().all(|c| name_chars.any(|n| n == c)).then_some(3)
+}
+
+/// Entries of the directory `partial` points into, directories first, then by how well the
+/// last path segment matches. Values keep the form the client typed (relative stays relative)
+/// and directories end with "/" so the client can keep completing.
+pub async fn complete_path(deps: &ToolDependencies, partial: &str) -> Result<CompleteResultCompletion, AppError> {
+    let (dir_part, typed) = match partial.rfind(['/', '\\']) {
+        Some(idx) => (&partial[..=idx], &partial[idx + 1..]),
+        None => ("", partial),
+    };
+    let (dir, denied_set) = {
+        let config = deps.config_state.snapshot();
+        let dir_str = if dir_part.is_empty() { config.files_root.to_string_lossy().into_owned() } else { dir_part.to_string() };
+        match validate_and_normalize_path(&dir_str, &config, true, false) {
+            Ok(dir) => (dir, denied_glob_set(&config)?),
+            Err(e) => {
+                debug!(partial = %partial, error = %e, "No path completions outside the allowed directories");
+                return Ok(empty_completion());
+            }
+        }
+    };
+    if !deps.app_handle.fs_scope().is_allowed(&dir) { return Ok(empty_completion()); }
+    let Ok(mut read_dir) = tokio_fs::read_dir(&dir).await else { return Ok(empty_completion()) };
+
+    let mut candidates = Vec::new();
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') && !typed.starts_with('.') { continue; }
+        let Some(rank) = match_rank(&name, typed) else { continue };
+        if denied_set.is_match(entry.path()) { continue; }
+        let is_dir = entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false);
+        candidates.push((!is_dir, rank, name));
+    }
+    candidates.sort();
+
+    let total = candidates.len();
+    let values = candidates.into_iter().take(MAX_COMPLETION_VALUES)
+        .map(|(is_file, _, name)| format!("{}{}{}", dir_part, name, if is_file { "" } else { "/" }))
+        .collect();
+    Ok(CompleteResultCompletion { values, total: Some(total as i64), has_more: Some(total > MAX_COMPLETION_VALUES) })
+}
@@ -0,0 +1,41 @@
+use crate::error::AppError;
+
+use rust_mcp_schema::schema_utils::CallToolError;
+use rust_mcp_schema::RpcError;
+use serde_json::{json, Value};
+
+// Client-chosen ids end up in log lines, so they are kept short and printable.
+const MAX_CLIENT_ID_CHARS: usize = 128;
+
+/// The id tying one tool call's result, audit entries and log lines together: the client's
+/// `_meta.correlation_id` if it sent one, e.g. its own request id, or a new UUID.
+pub fn correlation_id(meta: Option<&Value>) -> Result<String, AppError> {
+    let Some(id) = meta.and_then(|m| m.get("correlation_id")) else { return Ok(uuid::Uuid::new_v4().to_string()) };
+    let valid = id.as_str().filter(|id| !id.is_empty() && id.chars().count() <= MAX_CLIENT_ID_CHARS && id.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c)));
+    valid.map(str::to_string).ok_or_else(|| AppError::InvalidInputArgument(format!("_meta.correlation_id must be 1-{} letters, digits or '-', '_', '.', ':', got {}", MAX_CLIENT_ID_CHARS, id)))
+}
+
+#[derive(Debug)]
+struct CorrelatedError(String);
+
+impl std::fmt::Display for CorrelatedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CorrelatedError {}
+
+/// The error with the call's correlation id appended, so an error shown in a client transcript
+/// leads to the matching audit entry and log lines. JSON-RPC errors also get it as
+/// `data.correlation_id`, next to the error code.
+pub fn with_correlation_id(error: CallToolError, correlation_id: &str) -> CallToolError {
+    let Some(rpc_error) = error.0.downcast_ref::<RpcError>() else {
+        return CallToolError::new(CorrelatedError(format!("{} (correlation id {})", error, correlation_id)));
+    };
+    let mut data = rpc_error.data.clone().filter(Value::is_object).unwrap_or_else(|| json!({}));
+    if let Some(data) = data.as_object_mut() {
+        data.insert("correlation_id".to_string(), Value::String(correlation_id.to_string()));
+    }
+    CallToolError::new(RpcError { code: rpc_error.code, message: format!("{} (correlation id {})", rpc_error.message, correlation_id), data: Some(data) })
+}
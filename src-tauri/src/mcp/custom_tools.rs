@@ -0,0 +1,337 @@
+use crate::config::{check_custom_tool_name, Config, CustomToolConfig};
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::mcp::policy::PATH_ARGUMENT_KEYS;
+use crate::mcp::schemas::schema_from_json;
+use crate::mcp::tool_registry::{Tool, ToolContext, ToolFuture, ToolRegistry};
+use crate::utils::limits::truncate_output;
+use crate::utils::path_utils::validate_and_normalize_path;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rust_mcp_schema::{ToolAnnotations, ToolInputSchema};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+pub const CUSTOM_TOOL_CATEGORY: &str = "custom";
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{([A-Za-z0-9_-]+)\}").expect("placeholder regex compiles"));
+
+/// What a custom tool call returns when the command or module exits with 0.
+#[derive(Debug, Serialize)]
+pub struct CustomToolOutputMCP {
+    // stdout, parsed when it is JSON.
+    pub output: Value,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub stderr: String,
+    pub exit_code: i32,
+    // Whether stdout or stderr was cut at limits.max_output_bytes.
+    pub truncated: bool,
+    pub duration_ms: u64,
+}
+
+struct CustomTool {
+    // Leaked once at startup; Tool names are 'static.
+    name: &'static str,
+    config: CustomToolConfig,
+    #[cfg(feature = "wasm-tools")]
+    module: Option<wasm::Module>,
+}
+
+impl Tool for CustomTool {
+    fn name(&self) -> &'static str { self.name }
+    fn category(&self) -> &'static str { CUSTOM_TOOL_CATEGORY }
+    fn input_schema(&self) -> ToolInputSchema { schema_from_json(self.config.input_schema.as_ref()) }
+    fn mutating(&self) -> bool { !self.config.read_only }
+    fn destructive(&self) -> bool { !self.config.read_only && self.config.destructive }
+
+    fn description(&self) -> String {
+        if self.config.description.is_empty() { format!("Custom tool '{}'.", self.name) } else { self.config.description.clone() }
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        let read_only = self.config.read_only;
+        ToolAnnotations {
+            title: None,
+            read_only_hint: Some(read_only),
+            destructive_hint: (!read_only).then_some(self.config.destructive),
+            idempotent_hint: (!read_only).then_some(false),
+            // A command can reach anything; a module only sees its stdin.
+            open_world_hint: Some(self.config.command.is_some()),
+        }
+    }
+
+    fn execute<'a>(&'a self, ctx: ToolContext<'a>, args: Value) -> ToolFuture<'a> {
+        Box::pin(async move {
+            let outcome = match &self.config.command {
+                Some(command) => run_command(ctx.deps, &self.config, command, &args).await,
+                #[cfg(feature = "wasm-tools")]
+                None => match &self.module {
+                    Some(module) => wasm::run(ctx.deps, &self.config, module, &args).await,
+                    None => Err(AppError::ConfigError(format!("Custom tool '{}' has no module", self.name))),
+                },
+                #[cfg(not(feature = "wasm-tools"))]
+                None => Err(AppError::ConfigError(format!("Custom tool '{}' needs a build with the wasm-tools feature", self.name))),
+            };
+            ctx.respond(outcome)
+        })
+    }
+}
+
+/// Adds custom_tools and the modules in wasm_tools_dir to `registry`. Tools that clash with a
+/// registered name or cannot be loaded are skipped with a warning, so one bad entry does not
+/// keep the server from starting.
+pub fn register(registry: &mut ToolRegistry, config: &Config) {
+    let discovered = config.wasm_tools_dir.as_deref().map(discover_wasm_tools).unwrap_or_default();
+    for tool_config in config.custom_tools.iter().cloned().chain(discovered) {
+        if let Err(e) = check_custom_tool_name(&tool_config.name) {
+            warn!(error = %e, "Skipping custom tool");
+            continue;
+        }
+        if registry.get(&tool_config.name).is_some() {
+            warn!(tool = %tool_config.name, "Skipping custom tool whose name is already registered");
+            continue;
+        }
+        #[cfg(feature = "wasm-tools")]
+        let module = match &tool_config.wasm {
+            Some(path) => match wasm::load(&resolve(path, &config.files_root)) {
+                Ok(module) => Some(module),
+                Err(e) => {
+                    warn!(tool = %tool_config.name, path = %path.display(), error = %format!("{:#}", e), "Skipping custom tool whose module does not load");
+                    continue;
+                }
+            },
+            None => None,
+        };
+        #[cfg(not(feature = "wasm-tools"))]
+        if tool_config.wasm.is_some() {
+            warn!(tool = %tool_config.name, "Skipping WASM custom tool; this build lacks the wasm-tools feature");
+            continue;
+        }
+        info!(tool = %tool_config.name, kind = if tool_config.command.is_some() { "command" } else { "wasm" }, "Registered custom tool");
+        let name: &'static str = Box::leak(tool_config.name.clone().into_boxed_str());
+        registry.register(CustomTool {
+            name,
+            config: tool_config,
+            #[cfg(feature = "wasm-tools")]
+            module,
+        });
+    }
+}
+
+/// One tool per `<name>.wasm` in `dir`. A `<name>.json` next to it can set the other
+/// CustomToolConfig fields (description, input_schema, read_only, ...).
+pub fn discover_wasm_tools(dir: &Path) -> Vec<CustomToolConfig> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!(dir = %dir.display(), error = %e, "Failed to read wasm_tools_dir");
+            return Vec::new();
+        }
+    };
+    let mut modules: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "wasm")).collect();
+    modules.sort();
+    modules.into_iter().filter_map(|path| {
+        let name = path.file_stem()?.to_string_lossy().into_owned();
+        let manifest_path = path.with_extension("json");
+        let mut manifest = match std::fs::read_to_string(&manifest_path) {
+            Ok(text) => match serde_json::from_str::<Value>(&text) {
+                Ok(Value::Object(map)) => map,
+                Ok(_) | Err(_) => {
+                    warn!(manifest = %manifest_path.display(), "Skipping WASM tool whose manifest is not a JSON object");
+                    return None;
+                }
+            },
+            Err(_) => serde_json::Map::new(),
+        };
+        manifest.insert("name".to_string(), Value::String(name));
+        manifest.insert("wasm".to_string(), Value::String(path.to_string_lossy().into_owned()));
+        manifest.remove("command");
+        match serde_json::from_value::<CustomToolConfig>(Value::Object(manifest)) {
+            Ok(tool) => Some(tool),
+            Err(e) => {
+                warn!(manifest = %manifest_path.display(), error = %e, "Skipping WASM tool with an invalid manifest");
+                None
+            }
+        }
+    }).collect()
+}
+
+/// Relative module and program paths start at files_root.
+pub fn resolve(path: &Path, files_root: &Path) -> PathBuf {
+    let expanded = PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).as_ref());
+    if expanded.is_absolute() { expanded } else { files_root.join(expanded) }
+}
+
+/// Whether the argument `name` is a path: one of PATH_ARGUMENT_KEYS, or `format: "path"` in input_schema.
+fn is_path_argument(tool: &CustomToolConfig, name: &str) -> bool {
+    PATH_ARGUMENT_KEYS.contains(&name) || tool.input_schema.as_ref().and_then(|s| s.pointer(&format!("/properties/{}/format", name))).and_then(Value::as_str) == Some("path")
+}
+
+/// `entry` with each `{name}` replaced by the argument `name`: strings as they are, other values
+/// as JSON. Path arguments must be strings inside the allowed directories and are passed
+/// normalized. An entry that only starts with `-` because of an argument is rejected unless the
+/// tool sets allow_dash_args.
+fn substitute(config: &Config, tool: &CustomToolConfig, entry: &str, args: &Value) -> Result<String, AppError> {
+    let mut failure = None;
+    let filled = PLACEHOLDER.replace_all(entry, |caps: &regex::Captures| {
+        let name = &caps[1];
+        let value = match args.get(name) {
+            Some(Value::String(s)) if is_path_argument(tool, name) => validate_and_normalize_path(s, config, false, !tool.read_only).map(|p| p.to_string_lossy().into_owned()),
+            Some(Value::Null) | None => Ok(String::new()),
+            Some(_) if is_path_argument(tool, name) => Err(AppError::InvalidInputArgument(format!("Argument '{}' of custom tool '{}' is a path and must be a string", name, tool.name))),
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(other) => Ok(other.to_string()),
+        };
+        value.unwrap_or_else(|e| {
+            failure.get_or_insert(e);
+            String::new()
+        })
+    }).into_owned();
+    if let Some(e) = failure { return Err(e); }
+    if filled.starts_with('-') && !entry.starts_with('-') && !tool.allow_dash_args {
+        return Err(AppError::InvalidInputArgument(format!("Custom tool '{}' does not accept argument values starting with '-' (set allow_dash_args to allow them): {}", tool.name, filled)));
+    }
+    Ok(filled)
+}
+
+fn timeout(config: &Config, tool: &CustomToolConfig) -> Duration {
+    let max = config.limits.max_timeout_ms;
+    Duration::from_millis(tool.timeout_ms.map_or(max, |t| t.min(max)))
+}
+
+fn output(tool: &CustomToolConfig, mut stdout: String, mut stderr: String, exit_code: i32, started: Instant, max_bytes: usize) -> Result<CustomToolOutputMCP, AppError> {
+    let truncated = truncate_output(&mut stdout, max_bytes) | truncate_output(&mut stderr, max_bytes);
+    if exit_code != 0 {
+        return Err(AppError::CommandExecutionError(format!("Custom tool '{}' exited with code {}: {}", tool.name, exit_code, stderr.trim())));
+    }
+    let output = serde_json::from_str(stdout.trim()).unwrap_or(Value::String(stdout));
+    Ok(CustomToolOutputMCP { output, stderr, exit_code, truncated, duration_ms: started.elapsed().as_millis() as u64 })
+}
+
+/// Runs the tool's command in files_root with its `args` filled in and the call's arguments on
+/// stdin. No shell is involved, so arguments cannot chain commands or expand variables, but
+/// the program still sees every value; see substitute() for what is checked.
+async fn run_command(deps: &ToolDependencies, tool: &CustomToolConfig, command: &str, args: &Value) -> Result<CustomToolOutputMCP, AppError> {
+    let config = deps.config_state.snapshot();
+    let argv = tool.args.iter().map(|entry| substitute(&config, tool, entry, args)).collect::<Result<Vec<String>, AppError>>()?;
+    debug!(tool = %tool.name, command = %command, args = ?argv, "Running custom tool command");
+    // Relative program paths would otherwise resolve against either directory, depending on the platform.
+    let program = if command.contains('/') || command.contains(std::path::MAIN_SEPARATOR) { resolve(Path::new(command), &config.files_root) } else { PathBuf::from(command) };
+    let started = Instant::now();
+    let mut child = Command::new(program)
+        .args(&argv)
+        .envs(&tool.env)
+        .current_dir(&config.files_root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| AppError::CommandExecutionError(format!("Failed to start custom tool '{}' ({}): {}", tool.name, command, e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = serde_json::to_vec(args)?;
+        // A command that never reads its stdin must not stall the call.
+        tokio::spawn(async move {
+            let _ = stdin.write_all(&input).await;
+        });
+    }
+    let result = tokio::select! {
+        result = child.wait_with_output() => result?,
+        _ = deps.cancel.cancelled() => return Err(AppError::Cancelled(format!("Custom tool '{}' was stopped", tool.name))),
+        _ = tokio::time::sleep(timeout(&config, tool)) => return Err(AppError::TimeoutError(format!("Custom tool '{}' ran longer than {} ms", tool.name, timeout(&config, tool).as_millis()))),
+    };
+    let exit_code = result.status.code().unwrap_or(-1);
+    output(tool, String::from_utf8_lossy(&result.stdout).into_owned(), String::from_utf8_lossy(&result.stderr).into_owned(), exit_code, started, config.limits.max_output_bytes)
+}
+
+/// WASI command modules (`_start`) run by wasmtime with no preopened directories, environment,
+/// arguments or sockets: they see only the call's arguments on stdin.
+#[cfg(feature = "wasm-tools")]
+mod wasm {
+    use super::{output, timeout, CustomToolOutputMCP};
+    use crate::config::CustomToolConfig;
+    use crate::error::AppError;
+    use crate::mcp::handler::ToolDependencies;
+
+    use once_cell::sync::Lazy;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+    use wasmtime::{Engine, Linker, Store, StoreLimits, StoreLimitsBuilder, UpdateDeadline};
+    use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+    use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+    use wasmtime_wasi::{I32Exit, WasiCtxBuilder};
+
+    pub use wasmtime::Module;
+
+    // How often running modules check their deadline and cancel token.
+    const EPOCH_TICK: Duration = Duration::from_millis(100);
+    const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+
+    // One engine for every module, with a thread advancing its epoch so modules can be interrupted.
+    static ENGINE: Lazy<Engine> = Lazy::new(|| {
+        let mut wasm_config = wasmtime::Config::new();
+        wasm_config.epoch_interruption(true);
+        let engine = Engine::new(&wasm_config).expect("wasmtime engine config is valid");
+        let ticker = engine.clone();
+        std::thread::Builder::new().name("wasm-epoch".to_string()).spawn(move || loop {
+            std::thread::sleep(EPOCH_TICK);
+            ticker.increment_epoch();
+        }).expect("failed to spawn the wasm epoch thread");
+        engine
+    });
+
+    struct WasmState {
+        wasi: WasiP1Ctx,
+        limits: StoreLimits,
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Module> {
+        Module::from_file(&ENGINE, path)
+    }
+
+    pub async fn run(deps: &ToolDependencies, tool: &CustomToolConfig, module: &Module, args: &serde_json::Value) -> Result<CustomToolOutputMCP, AppError> {
+        let config = deps.config_state.snapshot();
+        let (module, tool, cancel) = (module.clone(), tool.clone(), deps.cancel.clone());
+        let input = serde_json::to_vec(args)?;
+        let max_bytes = config.limits.max_output_bytes;
+        let deadline = Instant::now() + timeout(&config, &tool);
+        tauri::async_runtime::spawn_blocking(move || {
+            let started = Instant::now();
+            // One byte past the limit, so output() can tell it was cut.
+            let (stdout, stderr) = (MemoryOutputPipe::new(max_bytes + 1), MemoryOutputPipe::new(max_bytes + 1));
+            let wasi = WasiCtxBuilder::new().stdin(MemoryInputPipe::new(input)).stdout(stdout.clone()).stderr(stderr.clone()).build_p1();
+            let mut store = Store::new(&ENGINE, WasmState { wasi, limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY_BYTES).build() });
+            store.limiter(|state| &mut state.limits);
+            store.set_epoch_deadline(1);
+            let (name, stop_cancel) = (tool.name.clone(), cancel.clone());
+            store.epoch_deadline_callback(move |_| {
+                if stop_cancel.is_cancelled() || Instant::now() >= deadline { anyhow::bail!("custom tool '{}' was interrupted", name); }
+                Ok(UpdateDeadline::Continue(1))
+            });
+            let mut linker: Linker<WasmState> = Linker::new(&ENGINE);
+            let fail = |e: anyhow::Error| AppError::CommandExecutionError(format!("Custom tool '{}' failed: {:#}", tool.name, e));
+            preview1::add_to_linker_sync(&mut linker, |state| &mut state.wasi).map_err(fail)?;
+            let instance = linker.instantiate(&mut store, &module).map_err(fail)?;
+            let start = instance.get_typed_func::<(), ()>(&mut store, "_start").map_err(fail)?;
+            let exit_code = match start.call(&mut store, ()) {
+                Ok(()) => 0,
+                Err(e) => match e.downcast_ref::<I32Exit>() {
+                    Some(exit) => exit.0,
+                    None if cancel.is_cancelled() => return Err(AppError::Cancelled(format!("Custom tool '{}' was stopped", tool.name))),
+                    None if Instant::now() >= deadline => return Err(AppError::TimeoutError(format!("Custom tool '{}' ran longer than {} ms", tool.name, timeout(&config, &tool).as_millis()))),
+                    None => return Err(fail(e)),
+                },
+            };
+            drop(store);
+            let text = |pipe: MemoryOutputPipe| String::from_utf8_lossy(&pipe.contents()).into_owned();
+            output(&tool, text(stdout), text(stderr), exit_code, started, max_bytes)
+        }).await.map_err(|e| AppError::CommandExecutionError(format!("Custom tool task failed: {}", e)))?
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::LimitsConfig;
+use crate::error::AppError;
+
+use serde_json::{json, Map, Value};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Argument holding per-call metadata (a budget, a correlation id). rust-mcp-sdk drops
+/// `params._meta` of tools/call, so clients put it into the arguments; it is removed before the
+/// tool sees them.
+pub const CALL_META_ARGUMENT: &str = "_meta";
+// Tools clamp their own waits to the budget and return what they have at the deadline. Only
+// ones still running after this much longer are cancelled.
+const DEADLINE_GRACE: Duration = Duration::from_millis(250);
+
+/// How long the client is willing to wait for one call.
+#[derive(Debug, Clone, Copy)]
+pub struct CallBudget {
+    started: Instant,
+    budget: Option<Duration>,
+}
+
+impl CallBudget {
+    /// From `_meta.timeout_ms` (relative) or `_meta.deadline` (RFC 3339) of the call, falling
+    /// back to limits.call_deadline_ms. Clamped to limits.max_timeout_ms and to the deadline of
+    /// an enclosing call (`outer`, for calls inside batch_tools).
+    pub fn from_meta(meta: Option<&Value>, limits: &LimitsConfig, outer: Option<Instant>) -> Result<Self, AppError> {
+        let started = Instant::now();
+        let requested = match meta.and_then(Value::as_object) {
+            Some(meta) => match (meta.get("timeout_ms"), meta.get("deadline")) {
+                (Some(ms), _) => Some(Duration::from_millis(ms.as_u64().ok_or_else(|| AppError::InvalidInputArgument(format!("_meta.timeout_ms must be a non-negative integer, got {}", ms)))?)),
+                (None, Some(deadline)) => {
+                    let deadline = deadline.as_str().and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+                        .ok_or_else(|| AppError::InvalidInputArgument(format!("_meta.deadline must be an RFC 3339 timestamp, got {}", deadline)))?;
+                    let left = deadline.with_timezone(&chrono::Utc) - chrono::Utc::now();
+                    Some(left.to_std().unwrap_or(Duration::ZERO))
+                }
+                (None, None) => None,
+            },
+            None => None,
+        };
+        let configured = (limits.call_deadline_ms > 0).then(|| Duration::from_millis(limits.call_deadline_ms));
+        let mut budget = requested.or(configured).map(|b| b.min(Duration::from_millis(limits.max_timeout_ms)));
+        if let Some(outer) = outer {
+            let left = outer.saturating_duration_since(started);
+            budget = Some(budget.map_or(left, |b| b.min(left)));
+        }
+        if budget == Some(Duration::ZERO) {
+            return Err(AppError::TimeoutError("The call's deadline has already passed".to_string()));
+        }
+        Ok(Self { started, budget })
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.budget.map(|b| self.started + b)
+    }
+
+    pub fn expired(&self) -> bool {
+        self.deadline().is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Cancels `cancel` once the deadline and a short grace period have passed. The watchdog
+    /// stops when the returned guard is dropped.
+    pub fn arm(&self, cancel: &CancellationToken) -> Option<DeadlineWatchdog> {
+        let deadline = self.deadline()?;
+        let cancel = cancel.clone();
+        Some(DeadlineWatchdog(tokio::spawn(async move {
+            tokio::time::sleep_until(deadline + DEADLINE_GRACE).await;
+            cancel.cancel();
+        })))
+    }
+
+    /// The error for a call that failed after its deadline, in place of a plain cancellation.
+    pub fn exceeded(&self, tool_name: &str) -> AppError {
+        AppError::TimeoutError(format!("'{}' ran out of its {} ms budget after {} ms", tool_name, self.budget.unwrap_or_default().as_millis(), self.started.elapsed().as_millis()))
+    }
+
+    /// `_meta` for the result: elapsed and budgeted time, when the call had a budget.
+    pub fn result_meta(&self) -> Option<Map<String, Value>> {
+        let budget = self.budget?;
+        let elapsed = self.started.elapsed();
+        let meta = json!({ "elapsed_ms": elapsed.as_millis() as u64, "budget_ms": budget.as_millis() as u64, "remaining_ms": budget.saturating_sub(elapsed).as_millis() as u64 });
+        meta.as_object().cloned()
+    }
+}
+
+pub struct DeadlineWatchdog(JoinHandle<()>);
+
+impl Drop for DeadlineWatchdog {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
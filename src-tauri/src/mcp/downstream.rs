@@ -0,0 +1,447 @@
+use crate::config::DownstreamServerConfig;
+use crate::error::AppError;
+use crate::mcp::notifications::{McpNotifier, TOOLS_LIST_CHANGED_NOTIFICATION};
+use crate::secrets;
+
+use rust_mcp_schema::{CallToolResult, CallToolResultContentItem, ListToolsResult, TextContent, Tool};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::{oneshot, Mutex as TokioMutex};
+use tokio::time::{timeout, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Tool `query` of the downstream server `db` is exported as `db__query`.
+pub const NAMESPACE_SEPARATOR: &str = "__";
+// What this server speaks as a client; results are parsed with the rust-mcp-schema types of this revision.
+const CLIENT_PROTOCOL_VERSION: &str = "2025-03-26";
+// Includes launching the process, which for `npx` may mean a download.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const LIST_TIMEOUT: Duration = Duration::from_secs(15);
+const CALL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// A server that could not be reached is not tried again for this long, so tools/list stays fast.
+const RETRY_AFTER: Duration = Duration::from_secs(60);
+// HTTP servers have no channel for notifications/tools/list_changed here, so their tools are re-listed after this.
+const HTTP_TOOLS_TTL: Duration = Duration::from_secs(60);
+const MAX_TOOLS_PER_SERVER: usize = 1000;
+const SESSION_HEADER: &str = "mcp-session-id";
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+enum Transport {
+    Stdio { stdin: Arc<TokioMutex<ChildStdin>> },
+    Http { client: reqwest::Client, url: String, token: Option<String>, session_id: StdMutex<Option<String>>, protocol_version: StdMutex<Option<String>> },
+}
+
+/// One initialized connection to a downstream server.
+struct Connection {
+    server: String,
+    transport: Transport,
+    next_id: AtomicU64,
+    // STDIO requests waiting for their response, by id; HTTP answers on the request itself.
+    pending: Arc<StdMutex<HashMap<u64, oneshot::Sender<Value>>>>,
+    // Cancelled when the process exits, or to stop it.
+    closed: CancellationToken,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.closed.cancel();
+    }
+}
+
+async fn write_line(stdin: &TokioMutex<ChildStdin>, message: &Value) -> std::io::Result<()> {
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(format!("{}\n", message).as_bytes()).await?;
+    stdin.flush().await
+}
+
+/// Hands responses to their waiters, answers pings and marks the tools stale on list_changed.
+async fn read_stdio(stdout: tokio::process::ChildStdout, stdin: Arc<TokioMutex<ChildStdin>>, pending: Arc<StdMutex<HashMap<u64, oneshot::Sender<Value>>>>, tools_stale: Arc<AtomicBool>, notifier: Arc<McpNotifier>, server: String) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            debug!(server = %server, line = %line, "Ignoring non-JSON output of downstream MCP server");
+            continue;
+        };
+        match (message.get("method").and_then(Value::as_str), message.get("id")) {
+            (None, Some(id)) => {
+                let waiter = id.as_u64().and_then(|id| pending.lock().ok().and_then(|mut p| p.remove(&id)));
+                if let Some(waiter) = waiter { let _ = waiter.send(message); }
+            }
+            (Some(method), Some(id)) => {
+                // This client offers no roots, sampling or elicitation.
+                let reply = match method {
+                    "ping" => json!({ "jsonrpc": "2.0", "id": id, "result": {} }),
+                    _ => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": format!("Method not supported by this client: {}", method) } }),
+                };
+                if let Err(e) = write_line(&stdin, &reply).await { debug!(server = %server, error = %e, "Failed to answer downstream MCP server"); }
+            }
+            (Some("notifications/tools/list_changed"), None) => {
+                info!(server = %server, "Downstream MCP server changed its tools");
+                tools_stale.store(true, Ordering::Release);
+                notifier.notify(TOOLS_LIST_CHANGED_NOTIFICATION, json!({}));
+            }
+            _ => {}
+        }
+    }
+    // Dropping the senders fails the requests still waiting.
+    if let Ok(mut pending) = pending.lock() { pending.clear(); }
+}
+
+/// Reads a Streamable HTTP response, JSON or an SSE stream, until the response to `id` arrives.
+async fn read_http_response(mut response: reqwest::Response, id: u64) -> Result<Value, AppError> {
+    let is_stream = response.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).is_some_and(|t| t.starts_with("text/event-stream"));
+    if !is_stream {
+        return response.json::<Value>().await.map_err(|e| AppError::DownstreamError(format!("Invalid JSON response: {}", e)));
+    }
+    let mut buffer = String::new();
+    while let Some(chunk) = response.chunk().await? {
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(end) = buffer.find("\n\n") {
+            let event: String = buffer.drain(..end + 2).collect();
+            let data: Vec<&str> = event.lines().filter_map(|l| l.strip_prefix("data:")).map(str::trim_start).collect();
+            let Ok(message) = serde_json::from_str::<Value>(&data.join("\n")) else { continue };
+            if message.get("method").is_none() && message.get("id").and_then(Value::as_u64) == Some(id) { return Ok(message); }
+        }
+    }
+    Err(AppError::DownstreamError("The event stream ended without a response".to_string()))
+}
+
+impl Connection {
+    async fn open(config: &DownstreamServerConfig, tools_stale: Arc<AtomicBool>, notifier: Arc<McpNotifier>) -> Result<Arc<Self>, AppError> {
+        let pending: Arc<StdMutex<HashMap<u64, oneshot::Sender<Value>>>> = Arc::default();
+        let closed = CancellationToken::new();
+        let transport = match (&config.command, &config.url) {
+            (Some(command), _) => {
+                let mut child = Command::new(command).args(&config.args).envs(&config.env)
+                    .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn().map_err(|e| AppError::DownstreamError(format!("Cannot start '{}' for '{}': {}", command, config.name, e)))?;
+                let (Some(stdin), Some(stdout), Some(stderr)) = (child.stdin.take(), child.stdout.take(), child.stderr.take()) else {
+                    return Err(AppError::DownstreamError(format!("'{}' started without piped stdio", config.name)));
+                };
+                let stdin = Arc::new(TokioMutex::new(stdin));
+                tauri::async_runtime::spawn(read_stdio(stdout, stdin.clone(), pending.clone(), tools_stale, notifier, config.name.clone()));
+                let server = config.name.clone();
+                tauri::async_runtime::spawn(async move {
+                    let mut lines = BufReader::new(stderr).lines();
+                    while let Ok(Some(line)) = lines.next_line().await { debug!(server = %server, "{}", line); }
+                });
+                let (server, stop) = (config.name.clone(), closed.clone());
+                tauri::async_runtime::spawn(async move {
+                    tokio::select! {
+                        status = child.wait() => warn!(server = %server, status = ?status, "Downstream MCP server exited"),
+                        _ = stop.cancelled() => { let _ = child.kill().await; }
+                    }
+                    stop.cancel();
+                });
+                Transport::Stdio { stdin }
+            }
+            (None, Some(url)) => {
+                let token = match config.token_secret.clone() {
+                    Some(name) => tauri::async_runtime::spawn_blocking(move || secrets::get_secret(&name)).await.map_err(|e| AppError::Unknown(e.to_string()))??,
+                    None => None,
+                };
+                Transport::Http { client: reqwest::Client::new(), url: url.clone(), token, session_id: StdMutex::default(), protocol_version: StdMutex::default() }
+            }
+            (None, None) => return Err(AppError::ConfigError(format!("Downstream server '{}' has neither 'command' nor 'url'", config.name))),
+        };
+        let connection = Arc::new(Self { server: config.name.clone(), transport, next_id: AtomicU64::new(1), pending, closed });
+
+        let params = json!({
+            "protocolVersion": CLIENT_PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "mcp-rg-editor-tauri-hosted", "version": env!("CARGO_PKG_VERSION") },
+        });
+        let result = connection.request("initialize", params, &CancellationToken::new(), CONNECT_TIMEOUT).await?;
+        let version = result.get("protocolVersion").and_then(Value::as_str).unwrap_or_default().to_string();
+        info!(server = %config.name, server_info = %result.get("serverInfo").cloned().unwrap_or_default(), protocol_version = %version, "Connected to downstream MCP server");
+        if let Transport::Http { protocol_version, .. } = &connection.transport {
+            if let Ok(mut slot) = protocol_version.lock() { *slot = Some(version); }
+        }
+        connection.notify("notifications/initialized", json!({})).await;
+        Ok(connection)
+    }
+
+    fn is_open(&self) -> bool {
+        !self.closed.is_cancelled()
+    }
+
+    async fn post(&self, message: &Value, id: Option<u64>) -> Result<Option<Value>, AppError> {
+        let Transport::Http { client, url, token, session_id, protocol_version } = &self.transport else { return Ok(None) };
+        let mut request = client.post(url).header(reqwest::header::ACCEPT, "application/json, text/event-stream").json(message);
+        if let Some(token) = token { request = request.bearer_auth(token); }
+        if let Some(session) = session_id.lock().ok().and_then(|s| s.clone()) { request = request.header(SESSION_HEADER, session); }
+        if let Some(version) = protocol_version.lock().ok().and_then(|v| v.clone()) { request = request.header(PROTOCOL_VERSION_HEADER, version); }
+        let response = request.send().await?;
+        if let Some(session) = response.headers().get(SESSION_HEADER).and_then(|v| v.to_str().ok()) {
+            if let Ok(mut slot) = session_id.lock() { *slot = Some(session.to_string()); }
+        }
+        if response.status() == reqwest::StatusCode::NOT_FOUND && session_id.lock().map(|s| s.is_some()).unwrap_or(false) {
+            // The server dropped the session; the next call reconnects.
+            self.closed.cancel();
+        }
+        if !response.status().is_success() {
+            return Err(AppError::DownstreamError(format!("'{}' answered HTTP {}", self.server, response.status())));
+        }
+        match id {
+            Some(id) => read_http_response(response, id).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn exchange(&self, id: u64, message: &Value) -> Result<Value, AppError> {
+        match &self.transport {
+            Transport::Stdio { stdin } => {
+                let (tx, rx) = oneshot::channel();
+                if let Ok(mut pending) = self.pending.lock() { pending.insert(id, tx); }
+                write_line(stdin, message).await?;
+                rx.await.map_err(|_| AppError::DownstreamError(format!("'{}' exited before answering", self.server)))
+            }
+            Transport::Http { .. } => self.post(message, Some(id)).await?.ok_or_else(|| AppError::DownstreamError(format!("'{}' sent no response", self.server))),
+        }
+    }
+
+    async fn request(&self, method: &str, params: Value, cancel: &CancellationToken, wait: Duration) -> Result<Value, AppError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let outcome = tokio::select! {
+            response = timeout(wait, self.exchange(id, &message)) => response.map_err(|_| AppError::TimeoutError(format!("'{}' did not answer {} within {} s", self.server, method, wait.as_secs()))),
+            _ = cancel.cancelled() => Err(AppError::Cancelled(format!("{} on downstream server '{}'", method, self.server))),
+        };
+        let response = match outcome {
+            Ok(response) => response?,
+            Err(e) => {
+                if let Ok(mut pending) = self.pending.lock() { pending.remove(&id); }
+                self.notify("notifications/cancelled", json!({ "requestId": id, "reason": e.to_string() })).await;
+                return Err(e);
+            }
+        };
+        if let Some(error) = response.get("error") {
+            let message = error.get("message").and_then(Value::as_str).map(str::to_string).unwrap_or_else(|| error.to_string());
+            return Err(AppError::DownstreamError(format!("'{}' failed {}: {}", self.server, method, message)));
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    async fn notify(&self, method: &str, params: Value) {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        let sent = match &self.transport {
+            Transport::Stdio { stdin } => write_line(stdin, &message).await.map_err(AppError::from),
+            Transport::Http { .. } => self.post(&message, None).await.map(|_| ()),
+        };
+        if let Err(e) = sent { debug!(server = %self.server, method, error = %e, "Failed to notify downstream MCP server"); }
+    }
+}
+
+#[derive(Default)]
+struct ToolCache {
+    tools: Vec<Tool>,
+    listed_at: Option<Instant>,
+}
+
+struct DownstreamServer {
+    config: DownstreamServerConfig,
+    connection: TokioMutex<Option<Arc<Connection>>>,
+    tools: StdMutex<ToolCache>,
+    tools_stale: Arc<AtomicBool>,
+    failed_at: StdMutex<Option<Instant>>,
+    last_error: StdMutex<Option<String>>,
+}
+
+impl DownstreamServer {
+    async fn connection(&self, notifier: &Arc<McpNotifier>) -> Result<Arc<Connection>, AppError> {
+        let mut slot = self.connection.lock().await;
+        if let Some(connection) = slot.as_ref().filter(|c| c.is_open()) { return Ok(connection.clone()); }
+        if let Some(failed_at) = self.failed_at.lock().ok().and_then(|f| *f) {
+            if failed_at.elapsed() < RETRY_AFTER {
+                let error = self.last_error.lock().ok().and_then(|e| e.clone()).unwrap_or_default();
+                return Err(AppError::DownstreamError(format!("'{}' is unavailable: {}", self.config.name, error)));
+            }
+        }
+        let opened = match timeout(CONNECT_TIMEOUT, Connection::open(&self.config, self.tools_stale.clone(), notifier.clone())).await {
+            Ok(opened) => opened,
+            Err(_) => Err(AppError::TimeoutError(format!("Connecting to '{}' took longer than {} s", self.config.name, CONNECT_TIMEOUT.as_secs()))),
+        };
+        match opened {
+            Ok(connection) => {
+                if let Ok(mut failed_at) = self.failed_at.lock() { *failed_at = None; }
+                if let Ok(mut last_error) = self.last_error.lock() { *last_error = None; }
+                // A new connection may come with different tools.
+                self.tools_stale.store(true, Ordering::Release);
+                *slot = Some(connection.clone());
+                Ok(connection)
+            }
+            Err(e) => {
+                warn!(server = %self.config.name, error = %e, "Downstream MCP server is unavailable");
+                if let Ok(mut failed_at) = self.failed_at.lock() { *failed_at = Some(Instant::now()); }
+                if let Ok(mut last_error) = self.last_error.lock() { *last_error = Some(e.to_string()); }
+                Err(e)
+            }
+        }
+    }
+
+    fn cache_is_fresh(&self) -> bool {
+        let Ok(cache) = self.tools.lock() else { return false };
+        let ttl_expired = self.config.url.is_some() && cache.listed_at.map_or(true, |t| t.elapsed() > HTTP_TOOLS_TTL);
+        cache.listed_at.is_some() && !ttl_expired && !self.tools_stale.load(Ordering::Acquire)
+    }
+
+    /// The server's tools under their namespaced names. Listing failures fall back to the last listing.
+    async fn tools(&self, notifier: &Arc<McpNotifier>) -> Vec<Tool> {
+        if !self.cache_is_fresh() {
+            match self.list_tools(notifier).await {
+                Ok(tools) => if let Ok(mut cache) = self.tools.lock() { *cache = ToolCache { tools, listed_at: Some(Instant::now()) }; },
+                Err(e) => warn!(server = %self.config.name, error = %e, "Could not list the tools of a downstream MCP server"),
+            }
+        }
+        self.tools.lock().map(|cache| cache.tools.clone()).unwrap_or_default()
+    }
+
+    async fn list_tools(&self, notifier: &Arc<McpNotifier>) -> Result<Vec<Tool>, AppError> {
+        let connection = self.connection(notifier).await?;
+        // Cleared before listing, so a change during the listing is picked up next time.
+        self.tools_stale.store(false, Ordering::Release);
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor { Some(cursor) => json!({ "cursor": cursor }), None => json!({}) };
+            let result = connection.request("tools/list", params, &CancellationToken::new(), LIST_TIMEOUT).await?;
+            let page: ListToolsResult = serde_json::from_value(result).map_err(|e| AppError::DownstreamError(format!("'{}' sent an invalid tools/list result: {}", self.config.name, e)))?;
+            tools.extend(page.tools.into_iter().map(|mut tool| {
+                tool.name = format!("{}{}{}", self.config.name, NAMESPACE_SEPARATOR, tool.name);
+                tool.description = Some(format!("[{}] {}", self.config.name, tool.description.unwrap_or_default()));
+                tool
+            }));
+            match page.next_cursor {
+                Some(next) if tools.len() < MAX_TOOLS_PER_SERVER => cursor = Some(next),
+                _ => break,
+            }
+        }
+        debug!(server = %self.config.name, tools = tools.len(), "Listed downstream MCP tools");
+        Ok(tools)
+    }
+
+    fn cached_tool(&self, name: &str) -> Option<Tool> {
+        self.tools.lock().ok()?.tools.iter().find(|t| t.name == name).cloned()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DownstreamServerStatusMCP {
+    pub name: String,
+    pub transport: &'static str,
+    pub connected: bool,
+    pub tools: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The downstream servers from `downstream_servers`, connected on first use and reconnected
+/// after they exit. Every MCP session shares them.
+pub struct DownstreamHub {
+    servers: Vec<Arc<DownstreamServer>>,
+    notifier: Arc<McpNotifier>,
+}
+
+impl DownstreamHub {
+    pub fn new(configs: &[DownstreamServerConfig], notifier: Arc<McpNotifier>) -> Arc<Self> {
+        let servers = configs.iter().map(|config| Arc::new(DownstreamServer {
+            config: config.clone(),
+            connection: TokioMutex::default(),
+            tools: StdMutex::default(),
+            tools_stale: Arc::default(),
+            failed_at: StdMutex::default(),
+            last_error: StdMutex::default(),
+        })).collect();
+        Arc::new(Self { servers, notifier })
+    }
+
+    /// Connects to every server and lists its tools in the background, so the first tools/list is fast.
+    pub fn spawn_connect(self: &Arc<Self>) {
+        if self.servers.is_empty() { return; }
+        let hub = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let tools = hub.tools().await;
+            info!(servers = hub.servers.len(), tools = tools.len(), "Downstream MCP servers ready");
+        });
+    }
+
+    /// Every downstream tool, namespaced. Unreachable servers contribute their last listing, if any.
+    pub async fn tools(&self) -> Vec<Tool> {
+        let mut tools = Vec::new();
+        for server in &self.servers {
+            tools.extend(server.tools(&self.notifier).await);
+        }
+        tools
+    }
+
+    fn route<'a>(&self, name: &'a str) -> Option<(&Arc<DownstreamServer>, &'a str)> {
+        let (namespace, tool) = name.split_once(NAMESPACE_SEPARATOR)?;
+        self.servers.iter().find(|s| s.config.name == namespace).map(|server| (server, tool))
+    }
+
+    pub fn routes(&self, name: &str) -> bool {
+        self.route(name).is_some()
+    }
+
+    /// Whether a downstream tool may change anything. Only tools that declared readOnlyHint in
+    /// their last listing count as read-only; unknown ones fail closed.
+    pub fn is_mutating(&self, name: &str) -> bool {
+        let Some((server, _)) = self.route(name) else { return false };
+        let read_only = server.cached_tool(name).and_then(|t| t.annotations).and_then(|a| a.read_only_hint);
+        read_only != Some(true)
+    }
+
+    /// destructiveHint of a downstream tool, with MCP's default of true for tools that are not read-only.
+    pub fn is_destructive(&self, name: &str) -> bool {
+        if !self.is_mutating(name) { return false; }
+        let Some((server, _)) = self.route(name) else { return false };
+        server.cached_tool(name).and_then(|t| t.annotations).and_then(|a| a.destructive_hint) != Some(false)
+    }
+
+    /// Forwards a call to the downstream server that `name` is namespaced under, passing the
+    /// call's correlation id on in `_meta`.
+    pub async fn call_tool(&self, name: &str, arguments: Map<String, Value>, correlation_id: Option<&str>, cancel: &CancellationToken) -> Result<CallToolResult, AppError> {
+        let Some((server, tool)) = self.route(name) else {
+            return Err(AppError::InvalidInputArgument(format!("'{}' is not a downstream tool", name)));
+        };
+        let connection = server.connection(&self.notifier).await?;
+        let mut params = json!({ "name": tool, "arguments": arguments });
+        if let Some(correlation_id) = correlation_id { params["_meta"] = json!({ "correlation_id": correlation_id }); }
+        let result = connection.request("tools/call", params, cancel, CALL_TIMEOUT).await?;
+        // Content types newer than rust-mcp-schema knows are passed on as the raw result.
+        Ok(serde_json::from_value::<CallToolResult>(result.clone()).unwrap_or_else(|_| CallToolResult {
+            content: vec![CallToolResultContentItem::TextContent(TextContent::new(result.to_string(), None))],
+            is_error: result.get("isError").and_then(Value::as_bool),
+            meta: None,
+        }))
+    }
+
+    pub fn status(&self) -> Vec<DownstreamServerStatusMCP> {
+        self.servers.iter().map(|server| DownstreamServerStatusMCP {
+            name: server.config.name.clone(),
+            transport: if server.config.command.is_some() { "stdio" } else { "http" },
+            // Locked while connecting.
+            connected: server.connection.try_lock().map(|c| c.as_ref().is_some_and(|c| c.is_open())).unwrap_or(false),
+            tools: server.tools.lock().map(|c| c.tools.len()).unwrap_or(0),
+            error: server.last_error.lock().ok().and_then(|e| e.clone()),
+        }).collect()
+    }
+
+    /// Stops the STDIO servers; called on shutdown.
+    pub async fn shutdown(&self) {
+        for server in &self.servers {
+            if let Some(connection) = server.connection.lock().await.take() {
+                connection.closed.cancel();
+                info!(server = %server.config.name, "Disconnected from downstream MCP server");
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::error::AppError;
+use crate::mcp::handler::EnhancedServerHandler;
+use crate::mcp::notifications::{forward_notifications_to_runtime, McpNotifier};
+use crate::mcp::protocol::{add_structured_content, ProtocolVersion};
+
+use rust_mcp_sdk::mcp_server::{server_runtime, ServerRuntime};
+use rust_mcp_schema::InitializeResult;
+use rust_mcp_transport::{SseTransport, TransportOptions};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::io::{duplex, DuplexStream};
+use tokio_util::sync::DropGuard;
+
+// Per-direction buffer between a network connection and the MCP transport.
+const DUPLEX_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// One MCP session on a network transport. Write newline-delimited client
+/// messages to `input` and read the runtime's messages, one per line, from `output`.
+/// Dropping `input` ends `runtime.start()`; abort `forwarder` once it has.
+pub struct DuplexSession {
+    pub runtime: Arc<ServerRuntime>,
+    pub input: DuplexStream,
+    pub output: DuplexStream,
+    // Forwards McpNotifier events to this session.
+    pub forwarder: tauri::async_runtime::JoinHandle<()>,
+    // Cancels the session's running tool calls; keep it as long as the client is connected.
+    pub calls_guard: DropGuard,
+}
+
+impl DuplexSession {
+    pub fn new(server_details: InitializeResult, handler: EnhancedServerHandler, notifier: &McpNotifier) -> Result<Self, AppError> {
+        let (input, transport_read) = duplex(DUPLEX_BUFFER_SIZE);
+        let (transport_write, output) = duplex(DUPLEX_BUFFER_SIZE);
+        // The SDK writes transport errors here; they are already logged by the runtime.
+        let (transport_error, _) = duplex(DUPLEX_BUFFER_SIZE);
+        let transport = SseTransport::new(transport_read, transport_write, transport_error, TransportOptions::default())
+            .map_err(|e| AppError::McpSdkError(format!("Failed to create MCP transport: {:?}", e)))?;
+        let owner = handler.client_owner();
+        let calls_guard = handler.cancel_calls_on_drop();
+        let runtime = Arc::new(server_runtime::create_server(server_details, transport, handler));
+        let forwarder = tauri::async_runtime::spawn(forward_notifications_to_runtime(runtime.clone(), notifier.subscribe(), owner));
+        Ok(Self { runtime, input, output, forwarder, calls_guard })
+    }
+}
+
+/// The SDK transports read newline-delimited JSON, so each message is re-serialized onto one line.
+pub fn message_to_line(message: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(message) {
+        Ok(value) => serde_json::to_string(&value).unwrap_or_else(|_| message.replace(['\r', '\n'], " ")),
+        // Passed on so the runtime answers with a parse error.
+        Err(_) => message.replace(['\r', '\n'], " "),
+    }
+}
+
+/// Adapts a runtime message to the protocol version negotiated with the session's client before
+/// it is sent, e.g. adding structuredContent for 2025-06-18.
+pub fn outgoing_line(runtime: &ServerRuntime, line: String) -> String {
+    let version = ProtocolVersion::of_session(runtime);
+    if !version.has_structured_content() { return line; }
+    match serde_json::from_str::<Value>(&line) {
+        Ok(mut message) if add_structured_content(&mut message, version) => message.to_string(),
+        _ => line,
+    }
+}
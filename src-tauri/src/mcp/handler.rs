@@ -1,80 +1,387 @@
-use crate::config::Config;
+use crate::config::{ConfigState, LimitsConfig};
 use crate::error::AppError;
-use crate::mcp::schemas::*;
-use crate::mcp::tool_impl;
+use crate::mcp::approval;
+use crate::mcp::auth::{AccessScope, ClientIdentity};
+use crate::mcp::batch::{self, BatchCallResultMCP, BatchToolsParamsMCP, BatchToolsResultMCP};
+use crate::mcp::call_queue::CallQueue;
+use crate::mcp::cancellation::InFlightCalls;
+use crate::mcp::completion;
+use crate::mcp::correlation;
+use crate::mcp::deadline::{self, CallBudget};
+use crate::mcp::downstream::{DownstreamHub, NAMESPACE_SEPARATOR};
+use crate::mcp::logging;
+use crate::mcp::message_size;
+use crate::mcp::metrics::{CommandSessionCounts, Metrics, ProcessMemoryMCP, ServerStatsMCP};
+use crate::mcp::notifications::McpNotifier;
+use crate::mcp::policy::{self, CallFacts, PolicyOutcome};
+use crate::mcp::prompts;
+use crate::mcp::protocol::ProtocolVersion;
+use crate::mcp::rate_limit::RateLimiter;
+use crate::mcp::resource_watcher::ResourceSubscriptions;
+use crate::mcp::resources;
+use crate::mcp::roots::ClientRoots;
+use crate::mcp::sampling::{self, RawOutputStore};
+use crate::mcp::tool_impl::audit;
+use crate::mcp::tool_registry::{Tool, ToolContext, ToolRegistry};
+use crate::shutdown::Shutdown;
 use crate::utils::audit_logger::AuditLogger as AppAuditLogger;
 use crate::utils::fuzzy_search_logger::FuzzySearchLogger as AppFuzzySearchLogger;
-use crate::commands::terminal_commands::ActiveSessionsMap;
-use sysinfo::System as SysinfoSystem;
+use crate::utils::job_manager::JobManager;
+use crate::utils::redaction::{self, Redactor};
+use crate::utils::{dir_policy, path_cache};
+use crate::commands::terminal_commands::{ActiveSession, ActiveSessionsMap};
+use crate::utils::sysinfo_cache::{ProcessDetail, SysinfoState};
+use crate::AppHandle;
 
 use async_trait::async_trait;
 use rust_mcp_sdk::McpServer;
 use rust_mcp_sdk::mcp_server::ServerHandler;
 use rust_mcp_schema::{
-    CallToolRequest, CallToolResult, ListToolsRequest, ListToolsResult, Tool,
+    CallToolRequest, CallToolResult, ListToolsRequest, ListToolsResult,
+    ListResourcesRequest, ListResourcesResult, ListResourceTemplatesRequest, ListResourceTemplatesResult,
+    ListPromptsRequest, ListPromptsResult, GetPromptRequest, GetPromptResult,
+    CancelledNotification, InitializeRequest, InitializeResult, InitializedNotification, SetLevelRequest, RootsListChangedNotification, CompleteRequest, CompleteResult,
+    ReadResourceRequest, ReadResourceResult, SubscribeRequest, UnsubscribeRequest, Result as McpResult,
     CallToolResultContentItem, 
     TextContent, // MODIFIED: Removed JsonContent, TextContent is used.              
     schema_utils::CallToolError, RpcError, schema_utils::RpcErrorCodes, 
 };
-use serde_json::Value;
-use std::sync::{Arc, RwLock as StdRwLock};
-use tauri::{AppHandle, Manager};
-use tokio::sync::Mutex as TokioMutex;
-use tracing::{error, info, instrument};
+use serde_json::{Map, Value};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tauri::Manager;
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
 
 #[derive(Clone)]
 pub struct ToolDependencies {
     pub app_handle: AppHandle,
-    pub config_state: Arc<StdRwLock<Config>>,
+    pub config_state: Arc<ConfigState>,
     pub audit_logger: Arc<AppAuditLogger>,
     pub fuzzy_search_logger: Arc<AppFuzzySearchLogger>,
     pub active_sessions_map: ActiveSessionsMap,
-    pub sysinfo_state: Arc<TokioMutex<SysinfoSystem>>,
+    pub sysinfo_state: SysinfoState,
+    pub notifier: Arc<McpNotifier>,
+    pub jobs: Arc<JobManager>,
+    // Fires when the MCP client cancels the call these dependencies were handed to.
+    // UI commands get a token that is never cancelled.
+    pub cancel: CancellationToken,
+    // When the call's budget runs out (see deadline.rs); the cancel token fires shortly after.
+    pub deadline: Option<Instant>,
+    // Set for tool calls; ties their audit entries, log lines and result together.
+    pub correlation_id: Option<String>,
+    // The network client making the call. None for the UI and stdio, which see every session.
+    pub client: Option<ClientIdentity>,
+}
+
+impl ToolDependencies {
+    pub fn from_app_handle(app_handle: &AppHandle, config_state: Arc<ConfigState>) -> Self {
+        Self {
+            app_handle: app_handle.clone(),
+            config_state,
+            audit_logger: app_handle.state::<Arc<AppAuditLogger>>().inner().clone(),
+            fuzzy_search_logger: app_handle.state::<Arc<AppFuzzySearchLogger>>().inner().clone(),
+            active_sessions_map: app_handle.state::<ActiveSessionsMap>().inner().clone(),
+            sysinfo_state: app_handle.state::<SysinfoState>().inner().clone(),
+            notifier: app_handle.state::<Arc<McpNotifier>>().inner().clone(),
+            jobs: app_handle.state::<Arc<JobManager>>().inner().clone(),
+            cancel: CancellationToken::new(),
+            deadline: None,
+            correlation_id: None,
+            client: None,
+        }
+    }
+
+    fn for_call(&self, cancel: CancellationToken, deadline: Option<Instant>, correlation_id: &str) -> Self {
+        Self { cancel, deadline, correlation_id: Some(correlation_id.to_string()), ..self.clone() }
+    }
+
+    /// Time left before the call's deadline, if it has one.
+    pub fn remaining_budget(&self) -> Option<Duration> {
+        self.deadline.map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// utils::limits::call_timeout, also clamped to the call's remaining budget, so a tool with
+    /// its own wait returns what it has at the deadline.
+    pub fn call_timeout(&self, limits: &LimitsConfig, requested_ms: Option<u64>, default_ms: u64) -> Duration {
+        let timeout = crate::utils::limits::call_timeout(limits, requested_ms, default_ms);
+        self.remaining_budget().map_or(timeout, |left| timeout.min(left))
+    }
+
+    /// Whether the caller may see and control `session`: network clients only reach their own.
+    pub fn owns_session(&self, session: &ActiveSession) -> bool {
+        match &self.client {
+            None => true,
+            Some(client) => session.owner.as_deref() == Some(client.owner.as_str()),
+        }
+    }
+
+    /// The owner whose jobs the caller may see and cancel; None for the UI and stdio, which see all.
+    pub fn job_owner(&self) -> Option<&str> {
+        self.client.as_ref().map(|c| c.owner.as_str())
+    }
+
+    pub fn client_label(&self) -> Option<&str> {
+        self.client.as_ref().map(|c| c.label.as_str())
+    }
+
+    /// tool_name_prefix, or "" when unset.
+    fn tool_name_prefix(&self) -> String {
+        self.config_state.snapshot().tool_name_prefix.clone().unwrap_or_default()
+    }
+
+    /// The tool a client-facing name refers to. With tool_name_prefix set, unprefixed names are unknown.
+    fn resolve_tool_name<'a>(&self, name: &'a str) -> Option<&'a str> {
+        let prefix = self.tool_name_prefix();
+        if prefix.is_empty() { Some(name) } else { name.strip_prefix(prefix.as_str()) }
+    }
 }
 
 #[derive(Clone)]
 pub struct EnhancedServerHandler {
    deps: ToolDependencies,
+   resource_subscriptions: Arc<ResourceSubscriptions>,
+   in_flight: Arc<InFlightCalls>,
+   raw_outputs: Arc<RawOutputStore>,
+   client_roots: Arc<ClientRoots>,
+   // Shared by every connection, so reconnecting does not refill a client's buckets.
+   rate_limiter: Arc<RateLimiter>,
+   // Shared by every connection: limits.max_concurrent_calls is a server-wide cap.
+   call_queue: Arc<CallQueue>,
+   metrics: Arc<Metrics>,
+   shutdown: Arc<Shutdown>,
+   downstream: Arc<DownstreamHub>,
+   tools: Arc<ToolRegistry>,
+   // Set per connection by network transports from the client's token.
+   access: AccessScope,
 }
 
 impl EnhancedServerHandler {
-    pub fn new(app_handle: AppHandle, config_state: Arc<StdRwLock<Config>>) -> Self {
-        let audit_logger = app_handle.state::<Arc<AppAuditLogger>>().inner().clone();
-        let fuzzy_search_logger = app_handle.state::<Arc<AppFuzzySearchLogger>>().inner().clone();
-        let active_sessions_map = app_handle.state::<ActiveSessionsMap>().inner().clone();
-        let sysinfo_state = app_handle.state::<Arc<TokioMutex<SysinfoSystem>>>().inner().clone();
-
+    pub fn new(app_handle: AppHandle, config_state: Arc<ConfigState>) -> Self {
+        let resource_subscriptions = app_handle.state::<Arc<ResourceSubscriptions>>().inner().clone();
+        let shutdown = app_handle.state::<Arc<Shutdown>>().inner().clone();
+        let downstream = app_handle.state::<Arc<DownstreamHub>>().inner().clone();
+        let tools = Arc::new(ToolRegistry::with_custom_tools(&config_state.snapshot()));
         Self {
-            deps: ToolDependencies {
-                app_handle,
-                config_state,
-                audit_logger,
-                fuzzy_search_logger,
-                active_sessions_map,
-                sysinfo_state,
-            },
+            deps: ToolDependencies::from_app_handle(&app_handle, config_state),
+            resource_subscriptions,
+            in_flight: Arc::new(InFlightCalls::within(shutdown.calls_token())),
+            raw_outputs: Arc::default(),
+            client_roots: Arc::default(),
+            rate_limiter: Arc::default(),
+            call_queue: Arc::default(),
+            metrics: Arc::default(),
+            shutdown,
+            downstream,
+            tools,
+            access: AccessScope::Full,
+        }
+    }
+
+    /// changes_state() built-ins and custom tools without read_only, plus downstream tools without readOnlyHint.
+    fn is_mutating(&self, tool_name: &str) -> bool {
+        self.tools.get(tool_name).map_or_else(|| self.downstream.is_mutating(tool_name), |tool| tool.mutating())
+    }
+
+    fn is_destructive(&self, tool_name: &str) -> bool {
+        self.tools.get(tool_name).map_or_else(|| self.downstream.is_destructive(tool_name), |tool| tool.destructive())
+    }
+
+    fn tool_category(&self, tool_name: &str) -> &'static str {
+        if tool_name.contains(NAMESPACE_SEPARATOR) { return "downstream"; }
+        self.tools.get(tool_name).map_or("config", |tool| tool.category())
+    }
+
+    /// The client's token scope, which for downstream and custom tools only lets read-only ones through unless it is full.
+    fn allows_tool(&self, tool_name: &str) -> bool {
+        self.access.allows_tool(tool_name) && (self.access == AccessScope::Full || !self.is_mutating(tool_name))
+    }
+
+    fn call_facts<'a>(&self, tool_name: &'a str) -> CallFacts<'a> {
+        CallFacts {
+            tool: tool_name,
+            category: self.tool_category(tool_name),
+            mutating: self.is_mutating(tool_name),
+            destructive: self.is_destructive(tool_name),
+            scope_allows: self.allows_tool(tool_name),
+        }
+    }
+
+    #[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+    /// The handler for one network connection: the token's access, the client's own sessions, and
+    /// in-flight calls of its own so a cancellation from it stops none of another client's calls.
+    pub fn for_client(&self, access: AccessScope, client: ClientIdentity) -> Self {
+        Self { access, deps: ToolDependencies { client: Some(client), ..self.deps.clone() }, in_flight: Arc::new(InFlightCalls::within(self.shutdown.calls_token())), ..self.clone() }
+    }
+
+    #[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+    /// Cancels this connection's running calls when dropped; see InFlightCalls::cancel_on_drop.
+    pub fn cancel_calls_on_drop(&self) -> tokio_util::sync::DropGuard {
+        self.in_flight.cancel_on_drop()
+    }
+
+    #[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+    pub fn client_owner(&self) -> Option<String> {
+        self.deps.client.as_ref().map(|c| c.owner.clone())
+    }
+
+    #[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    #[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+    pub async fn render_metrics(&self) -> String {
+        self.metrics.render(self.call_queue.queued(), self.command_session_counts().await)
+    }
+
+    async fn command_session_counts(&self) -> CommandSessionCounts {
+        let sessions: Vec<_> = self.deps.active_sessions_map.lock().await.values().cloned().collect();
+        let mut running = 0;
+        for session in &sessions {
+            if session.exit_code.lock().await.is_none() { running += 1; }
+        }
+        CommandSessionCounts { running, finished: sessions.len() - running }
+    }
+
+    /// What get_server_stats reports, for MCP clients and the UI alike.
+    pub async fn server_stats(&self) -> ServerStatsMCP {
+        let command_sessions = self.command_session_counts().await;
+        let (process_table, memory) = {
+            let mut sys = self.deps.sysinfo_state.lock().await;
+            let memory = match sysinfo::get_current_pid() {
+                Ok(pid) if !sys.refresh_pids(&[pid], ProcessDetail::Full).is_empty() => sys.process(pid).map(|p| ProcessMemoryMCP { resident_bytes: p.memory(), virtual_bytes: p.virtual_memory() }),
+                _ => None,
+            };
+            (sys.process_table_stats(), memory)
+        };
+        ServerStatsMCP {
+            uptime_secs: self.metrics.uptime().as_secs(),
+            tools: self.metrics.tool_stats(),
+            calls_in_flight: self.metrics.in_flight(),
+            calls_queued: self.call_queue.queued(),
+            searches_running: crate::utils::limits::active_searches(),
+            command_sessions,
+            network_sessions: self.metrics.open_sessions(),
+            caches: [("path", path_cache::stats()), ("process_table", process_table), ("dir_policy", dir_policy::cache_stats())].into_iter().collect(),
+            memory,
         }
     }
 }
 
-fn mcp_call_tool_error_from_app_error(app_err: AppError, tool_name: &str) -> CallToolError {
+/// Tools that change files, run commands or signal processes. Hidden and rejected in read_only mode.
+pub const MUTATING_TOOLS: &[&str] = &[
+    "write_file",
+    "edit_block",
+    "create_directory",
+    "move_file",
+    "execute_command",
+    "force_terminate_session",
+    "kill_process",
+    "kill_processes_matching",
+    "set_process_priority",
+    "import_config",
+    // Each snapshot can prune older ones beyond snapshot_keep, so it is a write like restore.
+    "snapshot_workspace",
+    "restore_snapshot",
+    "git_add",
+    "git_stage_hunks",
+    "git_commit",
+    "git_create_branch",
+    "git_checkout",
+];
+
+/// Tools that change config every client shares. Hidden from read-scoped tokens and in read_only mode like MUTATING_TOOLS.
+pub const SHARED_CONFIG_TOOLS: &[&str] = &["use_profile", "set_active_workspace"];
+
+/// Whether a built-in tool changes state: the one check read_only mode and read-scoped tokens both use.
+pub fn changes_state(tool_name: &str) -> bool {
+    MUTATING_TOOLS.contains(&tool_name) || SHARED_CONFIG_TOOLS.contains(&tool_name)
+}
+
+/// Tools that overwrite files or config, run commands or kill processes: destructiveHint in tools/list, and
+/// what "destructive" in approval_required_tools stands for.
+pub const DESTRUCTIVE_TOOLS: &[&str] = &[
+    "write_file",
+    "edit_block",
+    "move_file",
+    "execute_command",
+    "force_terminate_session",
+    "kill_process",
+    "kill_processes_matching",
+    "restore_snapshot",
+    // Can replace the whole user config.toml.
+    "import_config",
+    // Switching branches overwrites working-tree files.
+    "git_checkout",
+];
+
+const TOOLS_PAGE_SIZE: usize = 20;
+// Enough for the reason a call failed; the full error went to the client.
+const AUDIT_RESULT_MESSAGE_MAX_BYTES: usize = 512;
+
+pub const TOOL_CATEGORIES: &[&str] = &["config", "filesystem", "search", "terminal", "process", "edit", "system", "git", "custom", "downstream"];
+
+}
+
+/// tools/list cursors are "<offset>" or "<category>:<offset>". The request params only carry a
+/// cursor, so a client filters by category by sending the bare category name as the first cursor.
+fn parse_tools_cursor(cursor: Option<&str>) -> Result<(Option<&'static str>, usize), AppError> {
+    let Some(cursor) = cursor.filter(|c| !c.is_empty()) else { return Ok((None, 0)) };
+    let invalid = || AppError::InvalidInputArgument(format!("Invalid tools/list cursor '{}'. Use a category ({}) or a cursor from a previous page.", cursor, TOOL_CATEGORIES.join(", ")));
+    let (category_part, offset_part) = match cursor.split_once(':') {
+        Some((category, offset)) => (Some(category), Some(offset)),
+        None if cursor.chars().all(|c| c.is_ascii_digit()) => (None, Some(cursor)),
+        None => (Some(cursor), None),
+    };
+    let category = match category_part {
+        Some(name) => Some(*TOOL_CATEGORIES.iter().find(|c| **c == name).ok_or_else(invalid)?),
+        None => None,
+    };
+    let offset = match offset_part {
+        Some(offset) => offset.parse::<usize>().map_err(|_| invalid())?,
+        None => 0,
+    };
+    Ok((category, offset))
+}
+
+pub fn mcp_call_tool_error_from_app_error(app_err: AppError, tool_name: &str) -> CallToolError {
     error!(error = %app_err, tool = tool_name, "Error during MCP tool execution");
-    
+    CallToolError::new(rpc_error_from_app_error(app_err))
+}
+
+fn rpc_error_from_app_error(app_err: AppError) -> RpcError {
+    let data = app_err.data();
     let (rpc_error_code_enum, message) = match app_err {
         AppError::InvalidInputArgument(ref msg) | 
-        AppError::PathNotAllowed(ref msg) | 
-        AppError::PathTraversal(ref msg) | 
+        AppError::PathNotAllowed { message: ref msg, .. } | 
+        AppError::ProcessNotAllowed(ref msg) | 
+        AppError::PathTraversal { message: ref msg, .. } | 
         AppError::InvalidPath(ref msg) => (RpcErrorCodes::INVALID_PARAMS, msg.clone()),
         AppError::CommandBlocked(ref cmd_name) => {
             (RpcErrorCodes::INTERNAL_ERROR, format!("Command blocked (Server Code -32001): {}", cmd_name))
         },
+        AppError::ReadOnlyMode(_) | AppError::LimitExceeded { .. } | AppError::LineLimitExceeded { .. } | AppError::RateLimited { .. } | AppError::Cancelled(_) | AppError::ShuttingDown(_) | AppError::ApprovalDenied(_) | AppError::PolicyDenied { .. } => (RpcErrorCodes::INVALID_REQUEST, app_err.to_string()),
         _ => (RpcErrorCodes::INTERNAL_ERROR, app_err.to_string()),
     };
     
-    CallToolError::new(RpcError::new(rpc_error_code_enum, message, None))
+    RpcError::new(rpc_error_code_enum, message, Some(data))
 }
 
-fn create_mcp_json_call_tool_result(value: Value) -> Result<CallToolResult, CallToolError> {
+/// `error` with its message and data run through `redactor`.
+fn redact_call_tool_error(error: CallToolError, redactor: &Redactor) -> CallToolError {
+    match error.0.downcast_ref::<RpcError>() {
+        Some(rpc_error) => CallToolError::new(RpcError { code: rpc_error.code, message: redactor.redact_text(&rpc_error.message).into_owned(), data: rpc_error.data.as_ref().map(|d| redactor.redact_value(d)) }),
+        None => match redactor.redact_text(&error.to_string()) {
+            std::borrow::Cow::Owned(message) => CallToolError::new(RpcError::new(RpcErrorCodes::INTERNAL_ERROR, message, None)),
+            std::borrow::Cow::Borrowed(_) => error,
+        },
+    }
+}
+
+pub fn create_mcp_json_call_tool_result(value: Value) -> Result<CallToolResult, CallToolError> {
     // MODIFIED: Reverted to serializing to string and using TextContent
     // as JsonContent variant is not available in the used schema version.
     let json_string = serde_json::to_string(&value)
@@ -87,157 +394,379 @@ fn create_mcp_json_call_tool_result(value: Value) -> Result<CallToolResult, Call
     Ok(CallToolResult { content: vec![content_item], meta: None, is_error: Some(false) })
 }
 
+/// Replaces a large result with a sampling summary. Any failure (no sampling support, the user
+/// declining, timeout) returns the original result, so summarize never loses output.
+async fn summarize_if_large(runtime: &dyn McpServer, store: &RawOutputStore, deps: &ToolDependencies, tool_name: &str, result: CallToolResult) -> Result<CallToolResult, CallToolError> {
+    let raw = match result.content.first() {
+        Some(CallToolResultContentItem::TextContent(text)) if text.text.len() > sampling::SUMMARIZE_THRESHOLD_BYTES => text.text.clone(),
+        _ => return Ok(result),
+    };
+    let max_input_bytes = deps.config_state.snapshot().limits.max_output_bytes;
+    match sampling::summarize_tool_output(runtime, store, tool_name, raw, max_input_bytes).await {
+        Ok(summary) => create_mcp_json_call_tool_result(serde_json::to_value(summary).unwrap()),
+        Err(e) => {
+            warn!(tool = tool_name, error = %e, "Returning the full result; summarizing it failed");
+            Ok(result)
+        }
+    }
+}
+
 
 #[async_trait]
 impl ServerHandler for EnhancedServerHandler {
     #[instrument(skip(self, _request, _runtime))]
     async fn handle_list_tools_request(
         &self,
-        _request: ListToolsRequest,
-        _runtime: &dyn McpServer,
+        request: ListToolsRequest,
+        runtime: &dyn McpServer,
     ) -> Result<ListToolsResult, RpcError> {
         info!("MCP: Handling list_tools request");
-        let tools = vec![
-            Tool { name: "mcp_get_config".to_string(), description: Some("Get the MCP server's current runtime configuration.".to_string()), input_schema: get_mcp_config_schema()},
-            Tool { name: "read_file".to_string(), description: Some("Read content of a local file or URL.".to_string()), input_schema: read_file_mcp_schema()},
-            Tool { name: "write_file".to_string(), description: Some("Write/append content to a file.".to_string()), input_schema: write_file_mcp_schema()},
-            Tool { name: "create_directory".to_string(), description: Some("Create directories, including nested ones.".to_string()), input_schema: create_directory_mcp_schema()},
-            Tool { name: "list_directory".to_string(), description: Some("List directory contents.".to_string()), input_schema: list_directory_mcp_schema()},
-            Tool { name: "move_file".to_string(), description: Some("Move or rename files or directories.".to_string()), input_schema: move_file_mcp_schema()},
-            Tool { name: "get_file_info".to_string(), description: Some("Get metadata for a file or directory.".to_string()), input_schema: get_file_info_mcp_schema()},
-            Tool { name: "read_multiple_files".to_string(), description: Some("Read multiple local files.".to_string()), input_schema: read_multiple_files_mcp_schema()},
-            Tool { name: "search_files".to_string(), description: Some("Find files/dirs by name.".to_string()), input_schema: search_files_mcp_schema()},
-            Tool { name: "search_code".to_string(), description: Some("Search code with Ripgrep.".to_string()), input_schema: search_code_mcp_schema()},
-            Tool { name: "execute_command".to_string(), description: Some("Run terminal commands. Output is streamed via events if using Tauri UI; for MCP, initial output/status returned.".to_string()), input_schema: execute_command_mcp_schema()},
-            Tool { name: "force_terminate_session".to_string(), description: Some("Stop a running command session by its ID.".to_string()), input_schema: force_terminate_mcp_schema()},
-            Tool { name: "list_sessions".to_string(), description: Some("List active command sessions.".to_string()), input_schema: list_sessions_mcp_schema()},
-            Tool { name: "read_session_output_status".to_string(), description: Some("Get status of a command session. For MCP, this might include buffered output if designed so.".to_string()), input_schema: read_session_output_status_mcp_schema()},
-            Tool { name: "list_processes".to_string(), description: Some("List system processes.".to_string()), input_schema: list_processes_mcp_schema()},
-            Tool { name: "kill_process".to_string(), description: Some("Terminate a system process by PID.".to_string()), input_schema: kill_process_mcp_schema()},
-            Tool { name: "edit_block".to_string(), description: Some("Apply targeted text replacements in a file.".to_string()), input_schema: edit_block_mcp_schema()},
-        ];
-        Ok(ListToolsResult { tools, meta: None, next_cursor: None })
-    }
-
-    #[instrument(skip(self, request, _runtime), fields(tool_name = %request.params.name))]
+        let cursor = request.params.and_then(|p| p.cursor);
+        let (category, offset) = parse_tools_cursor(cursor.as_deref()).map_err(rpc_error_from_app_error)?;
+        // 2024-11-05 has no annotations, and strict clients reject unknown fields.
+        let annotated = ProtocolVersion::of_session(runtime).has_tool_annotations();
+        let mut tools = self.tools.definitions(annotated);
+        for mut tool in self.downstream.tools().await {
+            if !annotated { tool.annotations = None; }
+            tools.push(tool);
+        }
+        let config = self.deps.config_state.snapshot();
+        tools.retain(|tool| policy::lists_tool(&config, &self.call_facts(&tool.name)));
+        if let Some(category) = category {
+            tools.retain(|tool| self.tool_category(&tool.name) == category);
+        }
+        let prefix = self.deps.tool_name_prefix();
+        if !prefix.is_empty() {
+            for tool in &mut tools {
+                tool.name = format!("{}{}", prefix, tool.name);
+            }
+        }
+        let page_end = (offset + TOOLS_PAGE_SIZE).min(tools.len());
+        let next_cursor = (page_end < tools.len()).then(|| match category {
+            Some(category) => format!("{}:{}", category, page_end),
+            None => page_end.to_string(),
+        });
+        let tools = tools.into_iter().skip(offset).take(TOOLS_PAGE_SIZE).collect();
+        Ok(ListToolsResult { tools, meta: None, next_cursor })
+    }
+
+    #[instrument(skip(self, request, runtime))]
+    async fn handle_list_resources_request(
+        &self,
+        request: ListResourcesRequest,
+        runtime: &dyn McpServer,
+    ) -> Result<ListResourcesResult, RpcError> {
+        let cursor = request.params.and_then(|p| p.cursor);
+        let (resources, next_cursor) = self.client_roots.scope(runtime, resources::list_resources(&self.deps, cursor)).await.map_err(rpc_error_from_app_error)?;
+        Ok(ListResourcesResult { resources, next_cursor, meta: None })
+    }
+
+    async fn handle_list_resource_templates_request(
+        &self,
+        _request: ListResourceTemplatesRequest,
+        _runtime: &dyn McpServer,
+    ) -> Result<ListResourceTemplatesResult, RpcError> {
+        Ok(ListResourceTemplatesResult { resource_templates: resources::resource_templates(), next_cursor: None, meta: None })
+    }
+
+    #[instrument(skip(self, request, runtime), fields(uri = %request.params.uri))]
+    async fn handle_read_resource_request(
+        &self,
+        request: ReadResourceRequest,
+        runtime: &dyn McpServer,
+    ) -> Result<ReadResourceResult, RpcError> {
+        let uri = request.params.uri.as_str();
+        self.deps.audit_logger.log_client_call(self.deps.client_label(), "mcp_resources_read", &serde_json::json!({ "uri": uri })).await;
+        let contents = self.client_roots.scope(runtime, resources::read_resource(&self.deps, uri)).await.map_err(|e| {
+            error!(error = %e, uri = %uri, "Error reading MCP resource");
+            rpc_error_from_app_error(e)
+        })?;
+        let result = ReadResourceResult { contents: vec![contents], meta: None };
+        let max_message_bytes = self.deps.config_state.snapshot().limits.max_message_bytes;
+        let result_bytes = message_size::message_bytes(&result);
+        if result_bytes > max_message_bytes {
+            warn!(uri = %uri, result_bytes, max_message_bytes, "Resource exceeds limits.max_message_bytes");
+            let data = serde_json::json!({ "error": "message_too_large", "result_bytes": result_bytes, "max_message_bytes": max_message_bytes });
+            return Err(RpcError::new(RpcErrorCodes::INVALID_REQUEST, format!("'{}' is {} bytes as a message, over limits.max_message_bytes ({}); read it with read_file and offset/length instead", uri, result_bytes, max_message_bytes), Some(data)));
+        }
+        Ok(result)
+    }
+
+    #[instrument(skip(self, request, runtime), fields(uri = %request.params.uri))]
+    async fn handle_subscribe_request(
+        &self,
+        request: SubscribeRequest,
+        runtime: &dyn McpServer,
+    ) -> Result<McpResult, RpcError> {
+        let uri = request.params.uri.as_str();
+        self.deps.audit_logger.log_client_call(self.deps.client_label(), "mcp_resources_subscribe", &serde_json::json!({ "uri": uri })).await;
+        let path = self.client_roots.scope(runtime, async { resources::resolve_resource_path(&self.deps, uri) }).await.map_err(rpc_error_from_app_error)?;
+        self.resource_subscriptions.subscribe(uri, path).map_err(rpc_error_from_app_error)?;
+        Ok(McpResult { meta: None, extra: None })
+    }
+
+    #[instrument(skip(self, request, _runtime), fields(uri = %request.params.uri))]
+    async fn handle_unsubscribe_request(
+        &self,
+        request: UnsubscribeRequest,
+        _runtime: &dyn McpServer,
+    ) -> Result<McpResult, RpcError> {
+        self.resource_subscriptions.unsubscribe(&request.params.uri).map_err(rpc_error_from_app_error)?;
+        Ok(McpResult { meta: None, extra: None })
+    }
+
+    async fn handle_initialize_request(
+        &self,
+        request: InitializeRequest,
+        runtime: &dyn McpServer,
+    ) -> Result<InitializeResult, RpcError> {
+        let params = &request.params;
+        let negotiated = ProtocolVersion::negotiate(&params.protocol_version);
+        info!(client_name = %params.client_info.name, client_version = %params.client_info.version, requested = %params.protocol_version, negotiated = negotiated.as_str(), "MCP: Client initializing");
+        if negotiated.as_str() != params.protocol_version {
+            warn!(requested = %params.protocol_version, offered = negotiated.as_str(), "MCP: Client asked for a protocol version this server does not speak");
+        }
+        self.deps.audit_logger.log_client_call(self.deps.client_label(), "mcp_initialize", &serde_json::json!({ "client_name": params.client_info.name, "client_version": params.client_info.version, "requested_protocol_version": params.protocol_version, "protocol_version": negotiated.as_str() })).await;
+        runtime.set_client_details(request.params.clone()).map_err(|e| RpcError::new(RpcErrorCodes::INTERNAL_ERROR, format!("Failed to store client details: {:?}", e), None))?;
+        let mut result = runtime.server_info().clone();
+        result.protocol_version = negotiated.as_str().to_string();
+        if !negotiated.has_tool_annotations() {
+            result.capabilities.completions = None;
+        }
+        Ok(result)
+    }
+
+    async fn handle_initialized_notification(
+        &self,
+        _notification: InitializedNotification,
+        runtime: &dyn McpServer,
+    ) -> Result<(), RpcError> {
+        self.client_roots.refresh(runtime).await;
+        Ok(())
+    }
+
+    async fn handle_roots_list_changed_notification(
+        &self,
+        _notification: RootsListChangedNotification,
+        runtime: &dyn McpServer,
+    ) -> Result<(), RpcError> {
+        info!("MCP: Client roots changed");
+        self.client_roots.refresh(runtime).await;
+        Ok(())
+    }
+
+    async fn handle_cancelled_notification(
+        &self,
+        notification: CancelledNotification,
+        _runtime: &dyn McpServer,
+    ) -> Result<(), RpcError> {
+        let cancelled = self.in_flight.cancel_all();
+        info!(request_id = ?notification.params.request_id, reason = ?notification.params.reason, cancelled, "MCP: Client cancelled a request");
+        Ok(())
+    }
+
+    async fn handle_set_level_request(
+        &self,
+        request: SetLevelRequest,
+        _runtime: &dyn McpServer,
+    ) -> Result<McpResult, RpcError> {
+        logging::set_level(&request.params.level);
+        Ok(McpResult { meta: None, extra: None })
+    }
+
+    async fn handle_complete_request(
+        &self,
+        request: CompleteRequest,
+        runtime: &dyn McpServer,
+    ) -> Result<CompleteResult, RpcError> {
+        let argument = &request.params.argument;
+        let completion = self.client_roots.scope(runtime, completion::complete(&self.deps, &request.params.ref_, &argument.name, &argument.value)).await.map_err(rpc_error_from_app_error)?;
+        Ok(CompleteResult { completion, meta: None })
+    }
+
+    async fn handle_list_prompts_request(
+        &self,
+        _request: ListPromptsRequest,
+        _runtime: &dyn McpServer,
+    ) -> Result<ListPromptsResult, RpcError> {
+        Ok(ListPromptsResult { prompts: prompts::list_prompts(), next_cursor: None, meta: None })
+    }
+
+    #[instrument(skip(self, request, runtime), fields(prompt = %request.params.name))]
+    async fn handle_get_prompt_request(
+        &self,
+        request: GetPromptRequest,
+        runtime: &dyn McpServer,
+    ) -> Result<GetPromptResult, RpcError> {
+        let name = request.params.name.as_str();
+        self.deps.audit_logger.log_client_call(self.deps.client_label(), "mcp_prompts_get", &serde_json::json!({ "name": name })).await;
+        self.client_roots.scope(runtime, prompts::get_prompt(&self.deps, name, request.params.arguments.clone())).await.map_err(|e| {
+            error!(error = %e, prompt = %name, "Error rendering MCP prompt");
+            rpc_error_from_app_error(e)
+        })
+    }
+
+    #[instrument(skip(self, request, runtime), fields(tool_name = %request.params.name))]
     async fn handle_call_tool_request(
         &self,
         request: CallToolRequest,
-        _runtime: &dyn McpServer,
+        runtime: &dyn McpServer,
     ) -> Result<CallToolResult, CallToolError> {
-        let tool_name = request.params.name.as_str();
-        let args_value = Value::Object(request.params.arguments.clone().unwrap_or_default());
-        info!(tool_name = %tool_name, "MCP: Handling call_tool request");
-        
-        self.deps.audit_logger.log_command_call(&format!("mcp_{}", tool_name), &args_value).await;
-
-        match tool_name {
-            "mcp_get_config" => {
-                let current_config_data = { 
-                    let config_guard = self.deps.config_state.read()
-                        .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INTERNAL_ERROR, format!("Config lock error: {}", e), None)))?;
-                    config_guard.clone()
-                };
-                let value_result = serde_json::to_value(current_config_data)
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INTERNAL_ERROR, format!("Failed to serialize config: {}", e), None)))?;
-                create_mcp_json_call_tool_result(value_result)
-            }
-            "read_file" => {
-                let params: tool_impl::filesystem::ReadFileParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::filesystem::mcp_read_file(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "write_file" => {
-                let params: tool_impl::filesystem::WriteFileParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::filesystem::mcp_write_file(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-             "create_directory" => {
-                let params: tool_impl::filesystem::CreateDirectoryParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::filesystem::mcp_create_directory(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "list_directory" => {
-                let params: tool_impl::filesystem::ListDirectoryParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::filesystem::mcp_list_directory(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "move_file" => {
-                let params: tool_impl::filesystem::MoveFileParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::filesystem::mcp_move_file(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "get_file_info" => {
-                let params: tool_impl::filesystem::GetFileInfoParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::filesystem::mcp_get_file_info(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "read_multiple_files" => {
-                let params: tool_impl::filesystem::ReadMultipleFilesParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::filesystem::mcp_read_multiple_files(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "search_files" => {
-                let params: tool_impl::filesystem::SearchFilesParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::filesystem::mcp_search_files(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "search_code" => {
-                let params: tool_impl::ripgrep::SearchCodeParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))?;
-                let result = tool_impl::ripgrep::mcp_search_code(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "execute_command" => {
-                let params: tool_impl::terminal::ExecuteCommandParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS.into(), e.to_string(), None)))?;
-                let result = tool_impl::terminal::mcp_execute_command(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "force_terminate_session" => {
-                let params: tool_impl::terminal::ForceTerminateParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS.into(), e.to_string(), None)))?;
-                let result = tool_impl::terminal::mcp_force_terminate_session(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "list_sessions" => {
-                let result = tool_impl::terminal::mcp_list_sessions(&self.deps).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
-            }
-            "read_session_output_status" => {
-                let params: tool_impl::terminal::ReadOutputStatusParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS.into(), e.to_string(), None)))?;
-                let result = tool_impl::terminal::mcp_read_session_output_status(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
+        let params = request.params;
+        let Some(tool_name) = self.deps.resolve_tool_name(&params.name) else {
+            warn!(tool_name = %params.name, "MCP: Tool called without tool_name_prefix");
+            return Err(CallToolError::unknown_tool(params.name.clone()));
+        };
+        let result = self.call_tool_with_metrics(tool_name, params.arguments.unwrap_or_default(), runtime, None).await?;
+        let max_message_bytes = self.deps.config_state.snapshot().limits.max_message_bytes;
+        Ok(message_size::limit_result(&self.raw_outputs, tool_name, result, max_message_bytes))
+    }
+}
+
+impl EnhancedServerHandler {
+    /// `outer_deadline` is the deadline of an enclosing batch_tools call. The span is what the
+    /// OTLP exporter sends per call, batched calls included; failures mark it as an error.
+    #[instrument(name = "tool_call", skip_all, fields(tool = %tool_name, otel.status_code = tracing::field::Empty))]
+    async fn call_tool_with_metrics(&self, tool_name: &str, arguments: Map<String, Value>, runtime: &dyn McpServer, outer_deadline: Option<Instant>) -> Result<CallToolResult, CallToolError> {
+        let started = std::time::Instant::now();
+        let _in_flight = self.metrics.begin_call();
+        let result = self.call_tool(tool_name, arguments, runtime, outer_deadline).await;
+        self.metrics.record_call(tool_name, result.is_ok(), started.elapsed());
+        if result.is_err() { tracing::Span::current().record("otel.status_code", "error"); }
+        result
+    }
+
+    /// Runs each call of a batch_tools call through the full call path, in order.
+    pub async fn run_batch(&self, params: BatchToolsParamsMCP, runtime: &dyn McpServer, cancel: &CancellationToken, deadline: Option<Instant>) -> Result<CallToolResult, CallToolError> {
+        let total = params.calls.len();
+        if total > batch::MAX_BATCH_CALLS {
+            return Err(mcp_call_tool_error_from_app_error(AppError::InvalidInputArgument(format!("batch_tools takes at most {} calls, got {}", batch::MAX_BATCH_CALLS, total)), batch::BATCH_TOOL));
+        }
+        let mut results = Vec::with_capacity(total);
+        for (index, call) in params.calls.into_iter().enumerate() {
+            if cancel.is_cancelled() {
+                return Err(mcp_call_tool_error_from_app_error(AppError::Cancelled(format!("batch_tools after {} of {} calls", index, total)), batch::BATCH_TOOL));
             }
-            "list_processes" => {
-                let result = tool_impl::process::mcp_list_processes(&self.deps).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
+            // Calls inside a batch use the same client-facing names as tools/call.
+            let entry = match self.deps.resolve_tool_name(&call.name) {
+                None => BatchCallResultMCP::from_outcome(index, call.name.clone(), Err(CallToolError::unknown_tool(call.name))),
+                Some(batch::BATCH_TOOL) => BatchCallResultMCP { index, name: call.name, ok: false, result: None, error: Some("batch_tools cannot be nested".to_string()) },
+                Some(tool_name) => {
+                    // Boxed as dyn Send, since this call path recurses into itself.
+                    let pending: Pin<Box<dyn Future<Output = Result<CallToolResult, CallToolError>> + Send + '_>> = Box::pin(self.call_tool_with_metrics(tool_name, call.arguments, runtime, deadline));
+                    let outcome = pending.await;
+                    BatchCallResultMCP::from_outcome(index, call.name, outcome)
+                }
+            };
+            let stop = !entry.ok && params.stop_on_error;
+            results.push(entry);
+            if stop { break; }
+        }
+        let failed = results.iter().filter(|r| !r.ok).count();
+        let summary = BatchToolsResultMCP { succeeded: results.len() - failed, failed, skipped: total - results.len(), results };
+        let mut result = create_mcp_json_call_tool_result(serde_json::to_value(summary).unwrap())?;
+        result.is_error = Some(failed > 0);
+        Ok(result)
+    }
+
+    /// Runs one call within its budget under a correlation id. The result's `_meta` carries the
+    /// id and the time taken against the budget; error messages end with the id.
+    #[instrument(skip_all, fields(tool_name = %tool_name, correlation_id = tracing::field::Empty))]
+    async fn call_tool(&self, tool_name: &str, mut arguments: Map<String, Value>, runtime: &dyn McpServer, outer_deadline: Option<Instant>) -> Result<CallToolResult, CallToolError> {
+        let meta = arguments.remove(deadline::CALL_META_ARGUMENT);
+        let correlation_id = correlation::correlation_id(meta.as_ref()).map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
+        tracing::Span::current().record("correlation_id", correlation_id.as_str());
+        let limits = self.deps.config_state.snapshot().limits.clone();
+        let budget = CallBudget::from_meta(meta.as_ref(), &limits, outer_deadline).map_err(|e| correlation::with_correlation_id(mcp_call_tool_error_from_app_error(e, tool_name), &correlation_id))?;
+        let started = Instant::now();
+        let redactor = redaction::redactor(&self.deps.config_state.snapshot());
+        let outcome = match self.run_tool(tool_name, arguments, runtime, &budget, &correlation_id).await {
+            // Usually the watchdog's cancellation.
+            Err(_) if budget.expired() => Err(correlation::with_correlation_id(mcp_call_tool_error_from_app_error(budget.exceeded(tool_name), tool_name), &correlation_id)),
+            Err(_) if self.shutdown.calls_cancelled() => Err(correlation::with_correlation_id(mcp_call_tool_error_from_app_error(AppError::ShuttingDown(format!("'{}' was cancelled after shutdown_drain_timeout_ms", tool_name)), tool_name), &correlation_id)),
+            Err(e) => Err(correlation::with_correlation_id(e, &correlation_id)),
+            Ok(mut result) => {
+                let result_meta = result.meta.get_or_insert_with(Map::new);
+                result_meta.insert("correlation_id".to_string(), Value::String(correlation_id.clone()));
+                if let Some(meta) = budget.result_meta() { result_meta.extend(meta); }
+                Ok(result)
             }
-            "kill_process" => {
-                let params: tool_impl::process::KillProcessParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS.into(), e.to_string(), None)))?;
-                let result = tool_impl::process::mcp_kill_process(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
+        };
+        // Errors often quote the arguments or the file they failed on.
+        let outcome = outcome.map_err(|e| redact_call_tool_error(e, &redactor));
+        self.audit_call_result(&correlation_id, &outcome, started.elapsed()).await;
+        outcome
+    }
+
+    /// Logs how a call ended under its correlation id, so query_audit_log can join it to the call's entry.
+    async fn audit_call_result(&self, correlation_id: &str, outcome: &Result<CallToolResult, CallToolError>, elapsed: Duration) {
+        let (ok, error_name, mut message) = match outcome {
+            Ok(result) => (result.is_error != Some(true), None, None),
+            Err(e) => {
+                let rpc_error = e.0.downcast_ref::<RpcError>();
+                let error_name = rpc_error.and_then(|r| r.data.as_ref()?.get("error")?.as_str().map(str::to_string));
+                (false, error_name, Some(rpc_error.map_or_else(|| e.to_string(), |r| r.message.clone())))
             }
-            "edit_block" => {
-                let params: tool_impl::edit::EditBlockParamsMCP = serde_json::from_value(args_value.clone())
-                    .map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS.into(), e.to_string(), None)))?;
-                let result = tool_impl::edit::mcp_edit_block(&self.deps, params).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
-                create_mcp_json_call_tool_result(serde_json::to_value(result).unwrap())
+        };
+        if let Some(message) = message.as_mut() { crate::utils::limits::truncate_output(message, AUDIT_RESULT_MESSAGE_MAX_BYTES); }
+        let entry = serde_json::json!({ "ok": ok, "error": error_name, "message": message, "duration_ms": elapsed.as_millis() as u64 });
+        self.deps.audit_logger.log_correlated_call(self.deps.client_label(), Some(correlation_id), audit::CALL_RESULT_AUDIT_COMMAND, &entry).await;
+    }
+
+    async fn run_tool(&self, tool_name: &str, arguments: Map<String, Value>, runtime: &dyn McpServer, budget: &CallBudget, correlation_id: &str) -> Result<CallToolResult, CallToolError> {
+        let Some(_running) = self.shutdown.begin_call() else {
+            return Err(mcp_call_tool_error_from_app_error(AppError::ShuttingDown(format!("'{}' was not started", tool_name)), tool_name));
+        };
+        let args_value = Value::Object(arguments);
+        info!(tool_name = %tool_name, client = ?self.deps.client_label(), "MCP: Handling call_tool request");
+        
+        self.deps.audit_logger.log_correlated_call(self.deps.client_label(), Some(correlation_id), &format!("mcp_{}", tool_name), &args_value).await;
+
+        // One snapshot for every check, so a concurrent reload cannot mix old and new policies.
+        let config = self.deps.config_state.snapshot();
+        let approval_reason = match policy::evaluate(&config, &self.call_facts(tool_name), &args_value) {
+            PolicyOutcome::Deny(e) => return Err(mcp_call_tool_error_from_app_error(e, tool_name)),
+            PolicyOutcome::Confirm(reason) => Some(reason),
+            PolicyOutcome::Allow => None,
+        };
+        let limits = &config.limits;
+        let owner = self.deps.client.as_ref().map(|c| c.owner.as_str());
+        self.rate_limiter.check(limits, owner, tool_name).map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?;
+
+        let call = self.in_flight.begin();
+        let _watchdog = budget.arm(&call.token);
+        if let Some(reason) = approval_reason {
+            // Before the queue, so a call waiting for a person holds no slot.
+            let decision = approval::request_approval(runtime, tool_name, &args_value, &reason, &call.token).await;
+            info!(tool_name = %tool_name, approved = decision.approved, action = %decision.action, "MCP: Approval decision");
+            self.deps.audit_logger.log_correlated_call(self.deps.client_label(), Some(correlation_id), "mcp_approval", &serde_json::json!({ "tool": tool_name, "reason": reason, "decision": decision })).await;
+            if !decision.approved {
+                let detail = decision.error.unwrap_or_else(|| format!("the client answered '{}'", decision.action));
+                return Err(mcp_call_tool_error_from_app_error(AppError::ApprovalDenied(format!("'{}' needs approval because {}; {}", tool_name, reason, detail)), tool_name));
             }
-            _ => {
-                error!("MCP: Unknown tool called: {}", tool_name);
-                Err(CallToolError::unknown_tool(tool_name.to_string()))
+        }
+        let tool = self.tools.get(tool_name);
+        if tool.is_none() && !self.downstream.routes(tool_name) {
+            error!("MCP: Unknown tool called: {}", tool_name);
+            return Err(CallToolError::unknown_tool(tool_name.to_string()));
+        }
+        let _slot = match tool {
+            Some(tool) if !tool.takes_call_slot() => None,
+            _ => Some(self.call_queue.acquire(limits, &call.token, tool_name).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name))?),
+        };
+        let deps = &self.deps.for_call(call.token.clone(), budget.deadline(), correlation_id);
+        let result = self.client_roots.scope(runtime, async {
+            match tool {
+                Some(tool) => tool.execute(ToolContext { name: tool.name(), deps, runtime, handler: self, raw_outputs: &self.raw_outputs }, args_value.clone()).await,
+                None => self.downstream.call_tool(tool_name, args_value.as_object().cloned().unwrap_or_default(), deps.correlation_id.as_deref(), &deps.cancel).await.map_err(|e| mcp_call_tool_error_from_app_error(e, tool_name)),
             }
+        }).await;
+        // Even a failed write, move or command may have changed what cached paths resolve to.
+        if self.is_mutating(tool_name) { path_cache::clear(); }
+        let result = result?;
+        if sampling::summarize_requested(tool_name, &args_value) {
+            return summarize_if_large(runtime, &self.raw_outputs, deps, tool_name, result).await;
         }
+        Ok(result)
     }
 }
\ No newline at end of file
@@ -0,0 +1,441 @@
+use crate::mcp::auth::{routes, Authenticator, Identity};
+use crate::mcp::duplex_session::DuplexSession;
+use crate::mcp::handler::EnhancedServerHandler;
+use crate::mcp::listener::{self, CorsOptions, TlsOptions};
+use crate::mcp::metrics::{self, OpenSession};
+use crate::mcp::notifications::McpNotifier;
+use crate::mcp::protocol::{add_structured_content, ProtocolVersion, PROTOCOL_VERSION_HEADER};
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use futures_util::stream::{self, FuturesUnordered, StreamExt};
+use rust_mcp_sdk::McpServer;
+use rust_mcp_sdk::mcp_server::ServerRuntime;
+use rust_mcp_schema::InitializeResult;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::{broadcast, oneshot, Mutex as TokioMutex};
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, error, info, warn};
+
+const SESSION_HEADER: &str = "mcp-session-id";
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+// Messages kept per session for clients that reconnect with Last-Event-ID.
+const EVENT_LOG_CAPACITY: usize = 1024;
+const STREAM_CHANNEL_CAPACITY: usize = 256;
+// Sessions without requests or an open GET stream for this long are closed.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct HttpServerOptions {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+    pub tls: Option<TlsOptions>,
+    pub cors: CorsOptions,
+    // Cancelled to stop the server: open sessions are closed and the port is released.
+    pub stop: CancellationToken,
+}
+
+#[derive(Clone)]
+struct StoredEvent {
+    id: u64,
+    data: Arc<String>,
+}
+
+impl StoredEvent {
+    fn to_sse(&self) -> Event {
+        Event::default().id(self.id.to_string()).data(self.data.as_str())
+    }
+}
+
+#[derive(Default)]
+struct EventLog {
+    next_id: u64,
+    events: VecDeque<StoredEvent>,
+}
+
+/// The parts of a session the output router and open streams share.
+struct SessionShared {
+    // Requests whose POST is still waiting for the response, by JSON-RPC id.
+    pending: StdMutex<HashMap<String, oneshot::Sender<StoredEvent>>>,
+    log: StdMutex<EventLog>,
+    // Notifications, server-to-client requests and responses nobody waits for any more.
+    stream_tx: broadcast::Sender<StoredEvent>,
+    get_streams: AtomicUsize,
+    // Ends open GET streams once the session is gone.
+    closed: CancellationToken,
+}
+
+impl SessionShared {
+    fn record(&self, data: String) -> StoredEvent {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.next_id += 1;
+        let event = StoredEvent { id: log.next_id, data: Arc::new(data) };
+        if log.events.len() >= EVENT_LOG_CAPACITY { log.events.pop_front(); }
+        log.events.push_back(event.clone());
+        event
+    }
+
+    fn events_after(&self, last_event_id: u64) -> Vec<StoredEvent> {
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.events.iter().filter(|e| e.id > last_event_id).cloned().collect()
+    }
+}
+
+// Counts open GET streams; while one is open, POST streams only carry their own responses.
+struct GetStreamGuard(Arc<SessionShared>);
+
+impl GetStreamGuard {
+    fn new(shared: Arc<SessionShared>) -> Self {
+        shared.get_streams.fetch_add(1, Ordering::Relaxed);
+        Self(shared)
+    }
+}
+
+impl Drop for GetStreamGuard {
+    fn drop(&mut self) {
+        self.0.get_streams.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+struct HttpSession {
+    input: TokioMutex<DuplexStream>,
+    runtime: Arc<ServerRuntime>,
+    // The client that sent initialize; only it may use the session.
+    identity: Identity,
+    shared: Arc<SessionShared>,
+    last_seen: StdMutex<Instant>,
+    tasks: Vec<tauri::async_runtime::JoinHandle<()>>,
+    _open: OpenSession,
+    _calls_guard: DropGuard,
+}
+
+impl HttpSession {
+    fn touch(&self) {
+        if let Ok(mut last_seen) = self.last_seen.lock() { *last_seen = Instant::now(); }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.shared.get_streams.load(Ordering::Relaxed) == 0
+            && self.last_seen.lock().map(|t| t.elapsed() > SESSION_IDLE_TIMEOUT).unwrap_or(true)
+    }
+
+    async fn write(&self, message: &Value) -> std::io::Result<()> {
+        // Value's Display is compact JSON, so the message stays on one line.
+        let line = message.to_string();
+        let mut input = self.input.lock().await;
+        input.write_all(line.as_bytes()).await?;
+        input.write_all(b"\n").await
+    }
+}
+
+impl Drop for HttpSession {
+    fn drop(&mut self) {
+        self.tasks.iter().for_each(|t| t.abort());
+        // Dropping the senders fails the POSTs still waiting for a response.
+        if let Ok(mut pending) = self.shared.pending.lock() { pending.clear(); }
+        self.shared.closed.cancel();
+    }
+}
+
+type Sessions = Arc<StdMutex<HashMap<String, Arc<HttpSession>>>>;
+
+#[derive(Clone)]
+struct HttpState {
+    sessions: Sessions,
+    server_details: Arc<InitializeResult>,
+    handler: EnhancedServerHandler,
+    notifier: Arc<McpNotifier>,
+    auth: Arc<Authenticator>,
+    cors: Arc<CorsOptions>,
+}
+
+/// Serves the MCP Streamable HTTP transport (protocols 2025-03-26 and 2025-06-18) on one endpoint: POST sends
+/// client messages and answers with JSON or an SSE stream, GET opens a stream for server
+/// messages, DELETE ends the session. Streams can be resumed with Last-Event-ID.
+pub async fn serve(options: HttpServerOptions, server_details: InitializeResult, handler: EnhancedServerHandler, notifier: Arc<McpNotifier>, auth: Arc<Authenticator>) -> anyhow::Result<()> {
+    let oauth = auth.has_oauth_clients().then(|| routes::oauth_router(auth.clone(), options.tls.is_some()));
+    let monitoring = metrics::routes::monitoring_router(handler.clone(), auth.clone(), "http");
+    let state = HttpState { sessions: Arc::default(), server_details: Arc::new(server_details), handler, notifier, auth, cors: Arc::new(options.cors.clone()) };
+    spawn_idle_sweeper(&state.sessions);
+    // Dropping a session ends its streams, which the graceful shutdown waits for.
+    let (sessions, stop) = (state.sessions.clone(), options.stop.clone());
+    let shutdown = async move {
+        stop.cancelled().await;
+        if let Ok(mut sessions) = sessions.lock() { sessions.clear(); }
+    };
+    let mut app = Router::new()
+        .route(&options.path, post(handle_post).get(handle_get).delete(handle_delete))
+        .with_state(state)
+        .merge(monitoring);
+    if let Some(oauth) = oauth { app = app.merge(oauth); }
+    let scheme = if options.tls.is_some() { "https" } else { "http" };
+    info!("MCP Streamable HTTP server listening on {}://{}:{}{}", scheme, options.host, options.port, options.path);
+    listener::serve(&options.host, options.port, app, options.tls.as_ref(), &options.cors, shutdown).await
+}
+
+fn spawn_idle_sweeper(sessions: &Sessions) {
+    let sessions = Arc::downgrade(sessions);
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SESSION_SWEEP_INTERVAL).await;
+            let Some(sessions) = sessions.upgrade() else { break };
+            let Ok(mut sessions) = sessions.lock() else { break };
+            sessions.retain(|id, session| {
+                let idle = session.is_idle();
+                if idle { info!(session_id = %id, "Closing idle MCP HTTP session"); }
+                !idle
+            });
+        }
+    });
+}
+
+fn start_session(state: &HttpState, identity: Identity) -> Result<(String, Arc<HttpSession>), String> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let client = state.auth.client_identity(&identity, &session_id);
+    let duplex = DuplexSession::new((*state.server_details).clone(), state.handler.for_client(identity.scope, client), &state.notifier).map_err(|e| e.to_string())?;
+    let (stream_tx, _) = broadcast::channel(STREAM_CHANNEL_CAPACITY);
+    let shared = Arc::new(SessionShared { pending: StdMutex::default(), log: StdMutex::default(), stream_tx, get_streams: AtomicUsize::new(0), closed: CancellationToken::new() });
+
+    let runtime = duplex.runtime.clone();
+    let runtime_task = tauri::async_runtime::spawn(async move {
+        if let Err(e) = runtime.start().await { warn!(error = ?e, "MCP HTTP session ended with an error"); }
+    });
+    let router_task = tauri::async_runtime::spawn(route_output(duplex.output, duplex.runtime.clone(), shared.clone()));
+
+    let session = Arc::new(HttpSession {
+        input: TokioMutex::new(duplex.input),
+        runtime: duplex.runtime.clone(),
+        identity,
+        shared,
+        last_seen: StdMutex::new(Instant::now()),
+        tasks: vec![runtime_task, router_task, duplex.forwarder],
+        _open: state.handler.metrics().open_session("http"),
+        _calls_guard: duplex.calls_guard,
+    });
+    if let Ok(mut sessions) = state.sessions.lock() { sessions.insert(session_id.clone(), session.clone()); }
+    info!(session_id = %session_id, client = %session.identity.label, scope = session.identity.scope.as_str(), "MCP HTTP session started");
+    Ok((session_id, session))
+}
+
+/// Hands each runtime message to the POST waiting for it, or to the session's streams.
+async fn route_output(output: DuplexStream, runtime: Arc<ServerRuntime>, shared: Arc<SessionShared>) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(mut line)) = lines.next_line().await {
+        if line.trim().is_empty() { continue; }
+        let mut value: Value = match serde_json::from_str(&line) {
+            Ok(v) => v,
+            Err(e) => { warn!(error = %e, "Dropping unparsable MCP runtime message"); continue; }
+        };
+        if add_structured_content(&mut value, ProtocolVersion::of_session(&*runtime)) { line = value.to_string(); }
+        let response_id = match (value.get("method"), value.get("id")) {
+            (None, Some(id)) => Some(id.to_string()),
+            _ => None,
+        };
+        let event = shared.record(line);
+        let waiter = response_id.and_then(|id| shared.pending.lock().ok().and_then(|mut p| p.remove(&id)));
+        let undelivered = match waiter {
+            Some(waiter) => waiter.send(event).err(),
+            None => Some(event),
+        };
+        // Receivers are optional; with none, the log still holds the event for a resumed stream.
+        if let Some(event) = undelivered { let _ = shared.stream_tx.send(event); }
+    }
+    debug!("MCP HTTP session output closed");
+}
+
+fn jsonrpc_error(status: StatusCode, code: i64, message: &str) -> Response {
+    let body = json!({ "jsonrpc": "2.0", "id": null, "error": { "code": code, "message": message } });
+    (status, [(header::CONTENT_TYPE, "application/json")], body.to_string()).into_response()
+}
+
+fn with_session_header(mut response: Response, session_id: &str) -> Response {
+    if let Ok(value) = HeaderValue::from_str(session_id) {
+        response.headers_mut().insert(HeaderName::from_static(SESSION_HEADER), value);
+    }
+    response
+}
+
+fn lookup_session(state: &HttpState, headers: &HeaderMap, identity: &Identity) -> Result<(String, Arc<HttpSession>), (StatusCode, &'static str)> {
+    let Some(session_id) = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) else {
+        return Err((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"));
+    };
+    let session = state.sessions.lock().ok().and_then(|s| s.get(session_id).cloned());
+    match session {
+        Some(session) if session.identity.label != identity.label => Err((StatusCode::FORBIDDEN, "MCP session belongs to a different client")),
+        // Clients that omit the header are taken to speak 2025-03-26, as the spec asks.
+        Some(session) if headers.get(PROTOCOL_VERSION_HEADER).is_some_and(|v| v.to_str().ok() != Some(ProtocolVersion::of_session(&*session.runtime).as_str())) => {
+            Err((StatusCode::BAD_REQUEST, "MCP-Protocol-Version does not match the protocol version negotiated for this session"))
+        }
+        Some(session) => { session.touch(); Ok((session_id.to_string(), session)) }
+        // 404 tells the client to start over with a new initialize.
+        None => Err((StatusCode::NOT_FOUND, "Unknown or expired MCP session")),
+    }
+}
+
+async fn handle_post(State(state): State<HttpState>, headers: HeaderMap, body: String) -> Response {
+    if !state.cors.origin_allowed(&headers) { return jsonrpc_error(StatusCode::FORBIDDEN, -32600, "Origin not allowed"); }
+    let Some(identity) = state.auth.authenticate(routes::authorization_header(&headers)) else { return routes::unauthorized() };
+    let (messages, is_batch) = match serde_json::from_str::<Value>(&body) {
+        Ok(Value::Array(batch)) if !batch.is_empty() => (batch, true),
+        Ok(Value::Array(_)) => return jsonrpc_error(StatusCode::BAD_REQUEST, -32600, "Empty JSON-RPC batch"),
+        Ok(message) => (vec![message], false),
+        Err(e) => return jsonrpc_error(StatusCode::BAD_REQUEST, -32700, &format!("Parse error: {}", e)),
+    };
+    let initialize = messages.iter().find(|m| m.get("method").and_then(Value::as_str) == Some("initialize"));
+    let is_initialize = initialize.is_some();
+    if is_batch {
+        // An initialize is answered with the version negotiate picks, so that one decides.
+        let version = match initialize {
+            Some(init) => ProtocolVersion::negotiate(init.pointer("/params/protocolVersion").and_then(Value::as_str).unwrap_or_default()),
+            None => match lookup_session(&state, &headers, &identity) {
+                Ok((_, session)) => ProtocolVersion::of_session(&*session.runtime),
+                Err((status, message)) => return jsonrpc_error(status, -32600, message),
+            },
+        };
+        if !version.has_jsonrpc_batches() {
+            return jsonrpc_error(StatusCode::BAD_REQUEST, -32600, &format!("JSON-RPC batches are not supported in protocol {}", version.as_str()));
+        }
+    }
+    let (session_id, session) = if is_initialize {
+        match start_session(&state, identity) {
+            Ok(started) => started,
+            Err(e) => {
+                error!(error = %e, "Failed to start MCP HTTP session");
+                return jsonrpc_error(StatusCode::INTERNAL_SERVER_ERROR, -32603, "Failed to start MCP session");
+            }
+        }
+    } else {
+        match lookup_session(&state, &headers, &identity) {
+            Ok(found) => found,
+            Err((status, message)) => return jsonrpc_error(status, -32600, message),
+        }
+    };
+
+    // Registered before writing, so a fast response cannot arrive before its waiter.
+    let mut waiters = Vec::new();
+    for message in &messages {
+        if let (Some(_), Some(id)) = (message.get("method"), message.get("id")) {
+            let (tx, rx) = oneshot::channel();
+            if let Ok(mut pending) = session.shared.pending.lock() { pending.insert(id.to_string(), tx); }
+            waiters.push(rx);
+        }
+    }
+    for message in &messages {
+        if let Err(e) = session.write(message).await {
+            error!(error = %e, session_id = %session_id, "Failed to pass message to MCP session");
+            return jsonrpc_error(StatusCode::INTERNAL_SERVER_ERROR, -32603, "MCP session is closed");
+        }
+    }
+    if waiters.is_empty() {
+        return with_session_header(StatusCode::ACCEPTED.into_response(), &session_id);
+    }
+
+    let wants_stream = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).is_some_and(|a| a.contains("text/event-stream"));
+    let response = if wants_stream {
+        post_event_stream(session.shared.clone(), waiters).into_response()
+    } else {
+        let mut responses: Vec<Value> = Vec::new();
+        for waiter in waiters {
+            if let Ok(event) = waiter.await {
+                if let Ok(value) = serde_json::from_str(&event.data) { responses.push(value); }
+            }
+        }
+        let body = if responses.len() == 1 && messages.len() == 1 { responses.remove(0) } else { Value::Array(responses) };
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+    };
+    with_session_header(response, &session_id)
+}
+
+struct PostStreamState {
+    waiters: FuturesUnordered<oneshot::Receiver<StoredEvent>>,
+    stream_rx: broadcast::Receiver<StoredEvent>,
+    shared: Arc<SessionShared>,
+}
+
+/// Streams the POST's responses as they arrive, and session messages (e.g. sampling requests
+/// made by a running tool) while no GET stream is open. Ends after the last response.
+fn post_event_stream(shared: Arc<SessionShared>, waiters: Vec<oneshot::Receiver<StoredEvent>>) -> impl IntoResponse {
+    let state = PostStreamState { waiters: waiters.into_iter().collect(), stream_rx: shared.stream_tx.subscribe(), shared };
+    let events = stream::unfold(state, |mut st| async move {
+        loop {
+            if st.waiters.is_empty() { return None; }
+            let next = {
+                let PostStreamState { waiters, stream_rx, shared } = &mut st;
+                tokio::select! {
+                    // An error means the session closed before answering.
+                    Some(result) = waiters.next() => result.ok(),
+                    // `shared` holds the sender, so this only fails when lagging.
+                    received = stream_rx.recv() => received.ok().filter(|_| shared.get_streams.load(Ordering::Relaxed) == 0),
+                }
+            };
+            if let Some(event) = next { return Some((Ok::<_, Infallible>(event.to_sse()), st)); }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn handle_get(State(state): State<HttpState>, headers: HeaderMap) -> Response {
+    if !state.cors.origin_allowed(&headers) { return jsonrpc_error(StatusCode::FORBIDDEN, -32600, "Origin not allowed"); }
+    let Some(identity) = state.auth.authenticate(routes::authorization_header(&headers)) else { return routes::unauthorized() };
+    let (session_id, session) = match lookup_session(&state, &headers, &identity) {
+        Ok(found) => found,
+        Err((status, message)) => return jsonrpc_error(status, -32600, message),
+    };
+    let shared = session.shared.clone();
+    // Subscribed before the replay is read, so nothing falls between the two.
+    let stream_rx = shared.stream_tx.subscribe();
+    let replay = match headers.get(LAST_EVENT_ID_HEADER).and_then(|v| v.to_str().ok()) {
+        Some(last) => match last.trim().parse::<u64>() {
+            Ok(last) => shared.events_after(last),
+            Err(_) => return jsonrpc_error(StatusCode::BAD_REQUEST, -32600, "Invalid Last-Event-ID"),
+        },
+        None => Vec::new(),
+    };
+    let replayed_up_to = replay.last().map(|e| e.id).unwrap_or(0);
+    debug!(session_id = %session_id, replayed = replay.len(), "MCP HTTP GET stream opened");
+
+    let guard = GetStreamGuard::new(shared);
+    let live = stream::unfold((stream_rx, guard), move |(mut rx, guard)| async move {
+        loop {
+            let received = tokio::select! {
+                received = rx.recv() => received,
+                _ = guard.0.closed.cancelled() => return None,
+            };
+            match received {
+                Ok(event) if event.id > replayed_up_to => return Some((Ok::<_, Infallible>(event.to_sse()), (rx, guard))),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => warn!(skipped, "MCP HTTP stream lagged; reconnect with Last-Event-ID to catch up"),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = stream::iter(replay.into_iter().map(|e| Ok::<_, Infallible>(e.to_sse()))).chain(live);
+    with_session_header(Sse::new(events).keep_alive(KeepAlive::default()).into_response(), &session_id)
+}
+
+async fn handle_delete(State(state): State<HttpState>, headers: HeaderMap) -> Response {
+    if !state.cors.origin_allowed(&headers) { return jsonrpc_error(StatusCode::FORBIDDEN, -32600, "Origin not allowed"); }
+    let Some(identity) = state.auth.authenticate(routes::authorization_header(&headers)) else { return routes::unauthorized() };
+    let (session_id, _) = match lookup_session(&state, &headers, &identity) {
+        Ok(found) => found,
+        Err((status, message)) => return jsonrpc_error(status, -32600, message),
+    };
+    if let Ok(mut sessions) = state.sessions.lock() { sessions.remove(&session_id); }
+    info!(session_id = %session_id, "MCP HTTP session closed by the client");
+    StatusCode::NO_CONTENT.into_response()
+}
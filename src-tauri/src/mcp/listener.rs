@@ -0,0 +1,174 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::AppHandle;
+
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method};
+use axum::Router;
+use std::future::Future;
+use std::path::PathBuf;
+use tauri::Manager;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::warn;
+
+const SELF_SIGNED_CERT_FILE: &str = "self-signed-cert.pem";
+const SELF_SIGNED_KEY_FILE: &str = "self-signed-key.pem";
+
+#[derive(Debug, Clone)]
+pub enum TlsOptions {
+    CertFiles { cert: PathBuf, key: PathBuf },
+    // Generated on first use into `dir` and reused, so clients can trust it once.
+    SelfSigned { dir: PathBuf },
+}
+
+impl TlsOptions {
+    /// A configured cert/key wins over mcp_tls_self_signed. None means plain HTTP.
+    pub fn from_config(config: &Config, app_handle: &AppHandle) -> Result<Option<Self>, AppError> {
+        if let (Some(cert), Some(key)) = (&config.mcp_tls_cert, &config.mcp_tls_key) {
+            return Ok(Some(Self::CertFiles { cert: cert.clone(), key: key.clone() }));
+        }
+        if !config.mcp_tls_self_signed { return Ok(None); }
+        let dir = app_handle.path().app_config_dir().map_err(|e| AppError::ConfigError(format!("No app config directory for the self-signed certificate: {}", e)))?;
+        Ok(Some(Self::SelfSigned { dir: dir.join("tls") }))
+    }
+}
+
+// Always allowed, since every MCP transport needs them.
+const MCP_REQUEST_HEADERS: &[&str] = &["content-type", "accept", "authorization", "mcp-session-id", "last-event-id"];
+
+/// Which browser origins may call a network transport, from the mcp_cors_* config keys.
+#[derive(Debug, Clone, Default)]
+pub struct CorsOptions {
+    pub allowed_origins: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+}
+
+impl CorsOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            allowed_origins: config.mcp_cors_allowed_origins.clone(),
+            allowed_headers: config.mcp_cors_allowed_headers.clone(),
+            allow_credentials: config.mcp_cors_allow_credentials,
+        }
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+
+    /// None without allowed origins: no CORS headers are sent, so browsers keep the same-origin policy.
+    pub fn layer(&self) -> Option<CorsLayer> {
+        if self.allowed_origins.is_empty() { return None; }
+        let origins = if self.allowed_origins.iter().any(|o| o == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(self.allowed_origins.iter().filter_map(|o| HeaderValue::from_str(o).map_err(|_| warn!(origin = %o, "Ignoring invalid CORS origin")).ok()))
+        };
+        let headers: Vec<HeaderName> = MCP_REQUEST_HEADERS.iter().map(|h| h.to_string()).chain(self.allowed_headers.iter().cloned())
+            .filter_map(|h| HeaderName::from_bytes(h.as_bytes()).map_err(|_| warn!(header = %h, "Ignoring invalid CORS header")).ok())
+            .collect();
+        Some(CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
+            .allow_headers(headers)
+            .expose_headers([HeaderName::from_static("mcp-session-id")])
+            .allow_credentials(self.allow_credentials))
+    }
+
+    /// Blocks DNS rebinding and cross-site requests: a browser page may only call this server
+    /// from a configured origin, a loopback origin or the host it addressed.
+    pub fn origin_allowed(&self, headers: &HeaderMap) -> bool {
+        let Some(origin) = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok()) else { return true };
+        if self.allows_origin(origin) { return true; }
+        let Ok(origin_url) = reqwest::Url::parse(origin) else { return false };
+        let Some(origin_host) = origin_url.host_str() else { return false };
+        if matches!(origin_host, "localhost" | "127.0.0.1" | "[::1]") { return true; }
+        let request_host = headers.get(header::HOST).and_then(|v| v.to_str().ok()).unwrap_or_default();
+        request_host.rsplit_once(':').map(|(host, _)| host).unwrap_or(request_host) == origin_host
+    }
+}
+
+// How long a stopped TLS listener waits for open connections before closing them.
+#[cfg(feature = "mcp-tls")]
+const TLS_SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Serves `app` on host:port, over TLS when `tls` is set, until `shutdown` completes and the
+/// open connections have finished. The port is released when this returns.
+pub async fn serve(host: &str, port: u16, app: Router, tls: Option<&TlsOptions>, cors: &CorsOptions, shutdown: impl Future<Output = ()> + Send + 'static) -> anyhow::Result<()> {
+    let app = match cors.layer() {
+        Some(layer) => app.layer(layer),
+        None => app,
+    };
+    match tls {
+        None => {
+            let listener = tokio::net::TcpListener::bind((host, port)).await?;
+            axum::serve(listener, app).with_graceful_shutdown(shutdown).await?;
+        }
+        #[cfg(feature = "mcp-tls")]
+        Some(tls) => {
+            let rustls_config = tls::rustls_config(tls, host).await?;
+            let addr = tokio::net::lookup_host((host, port)).await?.next().ok_or_else(|| anyhow::anyhow!("Cannot resolve listen address {}:{}", host, port))?;
+            let handle = axum_server::Handle::new();
+            let server = axum_server::bind_rustls(addr, rustls_config).handle(handle.clone()).serve(app.into_make_service());
+            tokio::pin!(server);
+            tokio::select! {
+                served = &mut server => served?,
+                _ = shutdown => {
+                    handle.graceful_shutdown(Some(TLS_SHUTDOWN_GRACE));
+                    server.await?;
+                }
+            }
+        }
+        // Refusing to start beats silently serving plain HTTP when TLS was asked for.
+        #[cfg(not(feature = "mcp-tls"))]
+        Some(_) => anyhow::bail!("TLS is configured but the mcp-tls feature is not compiled in"),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "mcp-tls")]
+mod tls {
+    use super::{TlsOptions, SELF_SIGNED_CERT_FILE, SELF_SIGNED_KEY_FILE};
+
+    use anyhow::Context;
+    use axum_server::tls_rustls::RustlsConfig;
+    use std::io::Write;
+    use std::path::Path;
+    use tracing::info;
+
+    pub async fn rustls_config(tls: &TlsOptions, host: &str) -> anyhow::Result<RustlsConfig> {
+        let (cert, key) = match tls {
+            TlsOptions::CertFiles { cert, key } => (cert.clone(), key.clone()),
+            TlsOptions::SelfSigned { dir } => {
+                let (cert, key) = (dir.join(SELF_SIGNED_CERT_FILE), dir.join(SELF_SIGNED_KEY_FILE));
+                if !cert.is_file() || !key.is_file() { generate_self_signed(dir, &cert, &key, host)?; }
+                (cert, key)
+            }
+        };
+        RustlsConfig::from_pem_file(&cert, &key).await.context(format!("Failed to load TLS certificate {} and key {}", cert.display(), key.display()))
+    }
+
+    // Readable by the owner only on Unix.
+    fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(path)?.write_all(contents.as_bytes())
+    }
+
+    // Valid for localhost, the listen host and this machine's name. Delete the files to regenerate it.
+    fn generate_self_signed(dir: &Path, cert_path: &Path, key_path: &Path, host: &str) -> anyhow::Result<()> {
+        let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()];
+        let listen_host = host.trim_start_matches('[').trim_end_matches(']');
+        for name in [Some(listen_host.to_string()), sysinfo::System::host_name()].into_iter().flatten() {
+            if !matches!(name.as_str(), "" | "0.0.0.0" | "::") && !names.contains(&name) { names.push(name); }
+        }
+        let certified = rcgen::generate_simple_self_signed(names.clone()).context("Failed to generate a self-signed certificate")?;
+        std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        std::fs::write(cert_path, certified.cert.pem()).context(format!("Failed to write {}", cert_path.display()))?;
+        write_private(key_path, &certified.key_pair.serialize_pem()).context(format!("Failed to write {}", key_path.display()))?;
+        info!(cert = %cert_path.display(), names = ?names, "Generated a self-signed TLS certificate");
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use crate::mcp::notifications::{McpNotifier, LOG_MESSAGE_NOTIFICATION};
+
+use once_cell::sync::OnceCell;
+use rust_mcp_schema::LoggingLevel;
+use serde_json::{json, Map, Value};
+use std::cell::Cell;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+// Set once the notifier exists; events before that only go to stderr and the log file.
+static NOTIFIER: OnceCell<Arc<McpNotifier>> = OnceCell::new();
+// Minimum severity sent to the client, as a syslog rank (see `rank`). Warning until logging/setLevel.
+static MIN_RANK: AtomicU8 = AtomicU8::new(3);
+
+thread_local! {
+    // Guards against an event logged while forwarding an event.
+    static FORWARDING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn rank(level: &LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+// tracing has no notice or above-error levels; TRACE is reported as debug.
+fn mcp_level(level: &Level) -> (u8, &'static str) {
+    match *level {
+        Level::TRACE | Level::DEBUG => (0, "debug"),
+        Level::INFO => (1, "info"),
+        Level::WARN => (3, "warning"),
+        Level::ERROR => (4, "error"),
+    }
+}
+
+pub fn install(notifier: Arc<McpNotifier>) {
+    let _ = NOTIFIER.set(notifier);
+}
+
+/// logging/setLevel. The level applies to every connected client, and events below the
+/// LOG_LEVEL config are filtered out before they reach this layer.
+pub fn set_level(level: &LoggingLevel) {
+    MIN_RANK.store(rank(level), Ordering::Relaxed);
+    tracing::info!(level = ?level, "MCP client log level set");
+}
+
+#[derive(Default)]
+struct FieldCollector {
+    fields: Map<String, Value>,
+}
+
+impl Visit for FieldCollector {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_string(), Value::from(value));
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.fields.insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+/// Sends tracing events as `notifications/message` (MCP logging) so clients without access to
+/// stderr or the app log still see warnings such as a missing `rg` or a denied path.
+pub struct McpLogLayer;
+
+impl<S: Subscriber> Layer<S> for McpLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let Some(notifier) = NOTIFIER.get() else { return };
+        let metadata = event.metadata();
+        let (event_rank, level_name) = mcp_level(metadata.level());
+        if event_rank < MIN_RANK.load(Ordering::Relaxed) { return; }
+        // Events about delivering notifications would feed back into this layer.
+        let target = metadata.target();
+        if target.starts_with("rust_mcp") || target.ends_with("::mcp::notifications") { return; }
+        if FORWARDING.with(|f| f.replace(true)) { return; }
+
+        let mut collector = FieldCollector::default();
+        event.record(&mut collector);
+        notifier.notify(LOG_MESSAGE_NOTIFICATION, json!({
+            "level": level_name,
+            "logger": target,
+            "data": Value::Object(collector.fields),
+        }));
+        FORWARDING.with(|f| f.set(false));
+    }
+}
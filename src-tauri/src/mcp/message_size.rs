@@ -0,0 +1,61 @@
+use crate::mcp::sampling::RawOutputStore;
+
+use rust_mcp_schema::{CallToolResult, CallToolResultContentItem, TextContent};
+use serde::Serialize;
+use tracing::warn;
+
+// Room for the JSON-RPC envelope around a result: jsonrpc, id and result keys.
+const ENVELOPE_BYTES: usize = 128;
+
+/// Bytes a result takes on the wire, envelope included.
+pub fn message_bytes<T: Serialize>(result: &T) -> usize {
+    serde_json::to_vec(result).map(|v| v.len()).unwrap_or(0) + ENVELOPE_BYTES
+}
+
+#[derive(Debug, Serialize)]
+pub struct OversizedResultMCP {
+    pub error: &'static str,
+    pub tool: String,
+    pub result_bytes: usize,
+    pub max_message_bytes: usize,
+    // Pass to fetch_raw_output for the result in chunks.
+    pub raw_handle: String,
+    pub raw_bytes: usize,
+    // "text" when the result was text only, "content_json" when it is the JSON of its content array (images included).
+    pub raw_format: &'static str,
+    pub hint: String,
+}
+
+/// Replaces a tools/call result larger than limits.max_message_bytes with a message_too_large
+/// error result, keeping the full result for fetch_raw_output. Clients and pipes that choke on
+/// one huge line get a small, explicit answer instead.
+pub fn limit_result(store: &RawOutputStore, tool_name: &str, result: CallToolResult, max_message_bytes: usize) -> CallToolResult {
+    let result_bytes = message_bytes(&result);
+    if result_bytes <= max_message_bytes { return result; }
+    let texts: Option<Vec<&str>> = result.content.iter().map(|item| match item {
+        CallToolResultContentItem::TextContent(text) => Some(text.text.as_str()),
+        _ => None,
+    }).collect();
+    let (raw, raw_format) = match texts {
+        Some(texts) => (texts.join("\n"), "text"),
+        None => (serde_json::to_string(&result.content).unwrap_or_default(), "content_json"),
+    };
+    let raw_bytes = raw.len();
+    let raw_handle = store.insert(raw);
+    warn!(tool = %tool_name, result_bytes, max_message_bytes, "Tool result exceeds limits.max_message_bytes; returning a raw_handle instead");
+    let oversized = OversizedResultMCP {
+        error: "message_too_large",
+        tool: tool_name.to_string(),
+        result_bytes,
+        max_message_bytes,
+        raw_handle,
+        raw_bytes,
+        raw_format,
+        hint: "Read it in chunks with fetch_raw_output (encoding: \"gzip_base64\" makes the chunks smaller), or narrow the call, e.g. with offset/length or a more specific pattern.".to_string(),
+    };
+    CallToolResult {
+        content: vec![CallToolResultContentItem::TextContent(TextContent::new(serde_json::to_string(&oversized).unwrap_or_default(), None))],
+        is_error: Some(true),
+        meta: None,
+    }
+}
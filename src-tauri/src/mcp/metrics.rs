@@ -0,0 +1,255 @@
+use crate::utils::cache_stats::CacheStatsMCP;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+// Upper bounds of the tool call latency histogram, in seconds.
+const LATENCY_BUCKETS_SECS: [f64; 10] = [0.005, 0.025, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0, 30.0, 120.0];
+// Tool names come from clients, so unknown names past this many share one series.
+const MAX_TOOL_SERIES: usize = 128;
+const OTHER_TOOL: &str = "other";
+
+#[derive(Default)]
+struct ToolStats {
+    ok: u64,
+    errors: u64,
+    // Per LATENCY_BUCKETS_SECS bound, not cumulative; rendering adds them up.
+    buckets: [u64; LATENCY_BUCKETS_SECS.len()],
+    duration_sum_secs: f64,
+}
+
+/// Counters for the /metrics endpoint, shared by every transport.
+pub struct Metrics {
+    started: Instant,
+    tools: StdMutex<BTreeMap<String, ToolStats>>,
+    in_flight: AtomicUsize,
+    open_sessions: StdMutex<BTreeMap<&'static str, usize>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self { started: Instant::now(), tools: StdMutex::default(), in_flight: AtomicUsize::new(0), open_sessions: StdMutex::default() }
+    }
+}
+
+/// Counts one tool call as in flight until dropped.
+pub struct InFlightGauge<'a>(&'a Metrics);
+
+impl Drop for InFlightGauge<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Counts one open MCP session of a network transport until dropped.
+pub struct OpenSession {
+    metrics: Arc<Metrics>,
+    transport: &'static str,
+}
+
+impl Drop for OpenSession {
+    fn drop(&mut self) {
+        if let Ok(mut open) = self.metrics.open_sessions.lock() {
+            if let Some(count) = open.get_mut(self.transport) { *count = count.saturating_sub(1); }
+        }
+    }
+}
+
+/// Command sessions by state, sampled when /metrics is scraped.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandSessionCounts {
+    pub running: usize,
+    pub finished: usize,
+}
+
+/// One tool's calls, as get_server_stats reports them.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallStatsMCP {
+    pub tool: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub mean_ms: f64,
+    // Estimated from the latency histogram, like Prometheus' histogram_quantile.
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessMemoryMCP {
+    pub resident_bytes: u64,
+    pub virtual_bytes: u64,
+}
+
+/// What get_server_stats returns. Counters cover every transport and reset on restart, like /metrics.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerStatsMCP {
+    pub uptime_secs: u64,
+    pub tools: Vec<ToolCallStatsMCP>,
+    pub calls_in_flight: usize,
+    pub calls_queued: usize,
+    pub searches_running: usize,
+    pub command_sessions: CommandSessionCounts,
+    // Open MCP sessions by network transport.
+    pub network_sessions: BTreeMap<&'static str, usize>,
+    pub caches: BTreeMap<&'static str, CacheStatsMCP>,
+    // None when the OS does not report this process.
+    pub memory: Option<ProcessMemoryMCP>,
+}
+
+impl Metrics {
+    pub fn begin_call(&self) -> InFlightGauge<'_> {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        InFlightGauge(self)
+    }
+
+    /// Also feeds the OTLP exporter, under the same capped tool name.
+    pub fn record_call(&self, tool_name: &str, ok: bool, duration: Duration) {
+        let Ok(mut tools) = self.tools.lock() else { return };
+        let name = if tools.contains_key(tool_name) || tools.len() < MAX_TOOL_SERIES { tool_name } else { OTHER_TOOL };
+        let stats = tools.entry(name.to_string()).or_default();
+        if ok { stats.ok += 1 } else { stats.errors += 1 }
+        let secs = duration.as_secs_f64();
+        stats.duration_sum_secs += secs;
+        if let Some(bucket) = LATENCY_BUCKETS_SECS.iter().position(|bound| secs <= *bound) { stats.buckets[bucket] += 1; }
+        drop(tools);
+        crate::telemetry::record_tool_call(name, ok, duration);
+    }
+
+    pub fn open_session(self: &Arc<Self>, transport: &'static str) -> OpenSession {
+        if let Ok(mut open) = self.open_sessions.lock() { *open.entry(transport).or_default() += 1; }
+        OpenSession { metrics: self.clone(), transport }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    pub fn open_sessions(&self) -> BTreeMap<&'static str, usize> {
+        self.open_sessions.lock().map(|open| open.clone()).unwrap_or_default()
+    }
+
+    pub fn tool_stats(&self) -> Vec<ToolCallStatsMCP> {
+        let Ok(tools) = self.tools.lock() else { return Vec::new() };
+        tools.iter().map(|(name, stats)| {
+            let calls = stats.ok + stats.errors;
+            ToolCallStatsMCP {
+                tool: name.clone(),
+                calls,
+                errors: stats.errors,
+                mean_ms: stats.duration_sum_secs * 1000.0 / calls.max(1) as f64,
+                p50_ms: latency_quantile_secs(&stats.buckets, calls, 0.5) * 1000.0,
+                p95_ms: latency_quantile_secs(&stats.buckets, calls, 0.95) * 1000.0,
+            }
+        }).collect()
+    }
+
+    /// Prometheus text exposition format, version 0.0.4.
+    pub fn render(&self, queued_calls: usize, command_sessions: CommandSessionCounts) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP mcp_uptime_seconds Seconds since the MCP server started.\n# TYPE mcp_uptime_seconds gauge\nmcp_uptime_seconds {}", self.uptime().as_secs());
+
+        let _ = writeln!(out, "# HELP mcp_tool_calls_total Tool calls by tool and outcome; rejected calls count as errors.\n# TYPE mcp_tool_calls_total counter");
+        let tools = self.tools.lock().map(|t| t.iter().map(|(name, s)| (name.clone(), s.ok, s.errors, s.buckets, s.duration_sum_secs)).collect::<Vec<_>>()).unwrap_or_default();
+        for (name, ok, errors, _, _) in &tools {
+            let tool = escape_label(name);
+            let _ = writeln!(out, "mcp_tool_calls_total{{tool=\"{}\",outcome=\"ok\"}} {}\nmcp_tool_calls_total{{tool=\"{}\",outcome=\"error\"}} {}", tool, ok, tool, errors);
+        }
+        let _ = writeln!(out, "# HELP mcp_tool_call_duration_seconds Tool call latency, including time spent queued.\n# TYPE mcp_tool_call_duration_seconds histogram");
+        for (name, ok, errors, buckets, sum) in &tools {
+            let tool = escape_label(name);
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS_SECS.iter().zip(buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "mcp_tool_call_duration_seconds_bucket{{tool=\"{}\",le=\"{}\"}} {}", tool, bound, cumulative);
+            }
+            let _ = writeln!(out, "mcp_tool_call_duration_seconds_bucket{{tool=\"{}\",le=\"+Inf\"}} {}", tool, ok + errors);
+            let _ = writeln!(out, "mcp_tool_call_duration_seconds_sum{{tool=\"{}\"}} {}\nmcp_tool_call_duration_seconds_count{{tool=\"{}\"}} {}", tool, sum, tool, ok + errors);
+        }
+
+        let _ = writeln!(out, "# HELP mcp_tool_calls_in_flight Tool calls running or queued.\n# TYPE mcp_tool_calls_in_flight gauge\nmcp_tool_calls_in_flight {}", self.in_flight.load(Ordering::Acquire));
+        let _ = writeln!(out, "# HELP mcp_tool_calls_queued Tool calls waiting for a limits.max_concurrent_calls slot.\n# TYPE mcp_tool_calls_queued gauge\nmcp_tool_calls_queued {}", queued_calls);
+
+        let _ = writeln!(out, "# HELP mcp_sessions_open Open MCP sessions by network transport.\n# TYPE mcp_sessions_open gauge");
+        if let Ok(open) = self.open_sessions.lock() {
+            for (transport, count) in open.iter() {
+                let _ = writeln!(out, "mcp_sessions_open{{transport=\"{}\"}} {}", transport, count);
+            }
+        }
+        let _ = writeln!(out, "# HELP mcp_command_sessions execute_command sessions by state; finished ones are kept for their output.\n# TYPE mcp_command_sessions gauge");
+        let _ = writeln!(out, "mcp_command_sessions{{state=\"running\"}} {}\nmcp_command_sessions{{state=\"finished\"}} {}", command_sessions.running, command_sessions.finished);
+        out
+    }
+}
+
+/// Interpolates linearly inside the bucket holding the `q` quantile. Calls slower than the
+/// last bound count as taking exactly that long.
+fn latency_quantile_secs(buckets: &[u64; LATENCY_BUCKETS_SECS.len()], calls: u64, q: f64) -> f64 {
+    let rank = q * calls as f64;
+    let mut below = 0;
+    let mut lower = 0.0;
+    for (bound, count) in LATENCY_BUCKETS_SECS.iter().zip(buckets) {
+        if *count > 0 && (below + count) as f64 >= rank {
+            return lower + (bound - lower) * (rank - below as f64) / *count as f64;
+        }
+        below += count;
+        lower = *bound;
+    }
+    lower
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+pub mod routes {
+    use crate::mcp::auth::{self, Authenticator};
+    use crate::mcp::handler::EnhancedServerHandler;
+
+    use axum::extract::State;
+    use axum::http::{header, HeaderMap};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct MonitoringState {
+        handler: EnhancedServerHandler,
+        auth: Arc<Authenticator>,
+        transport: &'static str,
+    }
+
+    /// `GET /healthz` (no token needed, for load balancers) and `GET /metrics` (a token when
+    /// tokens are configured, since tool names and call counts describe what clients do).
+    pub fn monitoring_router(handler: EnhancedServerHandler, auth: Arc<Authenticator>, transport: &'static str) -> Router {
+        Router::new()
+            .route("/healthz", get(healthz))
+            .route("/metrics", get(metrics))
+            .with_state(MonitoringState { handler, auth, transport })
+    }
+
+    async fn healthz(State(state): State<MonitoringState>) -> Response {
+        Json(json!({
+            "status": "ok",
+            "transport": state.transport,
+            "version": env!("CARGO_PKG_VERSION"),
+            "uptime_secs": state.handler.metrics().uptime().as_secs(),
+        })).into_response()
+    }
+
+    async fn metrics(State(state): State<MonitoringState>, headers: HeaderMap) -> Response {
+        if state.auth.authenticate(auth::routes::authorization_header(&headers)).is_none() { return auth::routes::unauthorized(); }
+        let body = state.handler.render_metrics().await;
+        ([(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], body).into_response()
+    }
+}
@@ -1,14 +1,56 @@
+pub mod approval;
+// Only the network servers check tokens; other builds use AccessScope alone.
+#[cfg_attr(not(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server")), allow(dead_code))]
+pub mod auth;
+pub mod batch;
+pub mod call_queue;
+pub mod cancellation;
+pub mod completion;
+pub mod correlation;
+pub mod custom_tools;
+pub mod deadline;
+pub mod downstream;
+// Test builds use it for test_transport; the SDK's hyper-server feature comes from the dev-dependencies.
+#[cfg(any(test, feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+#[cfg_attr(not(any(feature = "mcp-sse-server", feature = "mcp-ws-server")), allow(dead_code))]
+pub mod duplex_session;
 pub mod handler;
+#[cfg(feature = "mcp-http-server")]
+pub mod http_server;
+#[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+pub mod listener;
+pub mod logging;
+pub mod message_size;
+#[cfg_attr(not(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server")), allow(dead_code))]
+pub mod metrics;
+pub mod notifications;
+pub mod policy;
+pub mod prompts;
+pub mod protocol;
+pub mod rate_limit;
+pub mod resource_watcher;
+pub mod resources;
+pub mod roots;
+pub mod sampling;
 pub mod schemas;
+pub mod server_control;
+#[cfg(feature = "mcp-sse-server")]
+pub mod sse_server;
+// An in-process client for the tests, over the duplex transport of the network servers.
+#[cfg(test)]
+pub mod test_transport;
 pub mod tool_impl;
+pub mod tool_registry;
+#[cfg(feature = "mcp-ws-server")]
+pub mod ws_server;
 
-use tauri::AppHandle;
-use std::sync::{Arc, RwLock};
-use crate::config::Config;
+use crate::AppHandle;
+use std::sync::Arc;
+use crate::config::{Config, ConfigState};
 
 // Struct to pass to the MCP Server thread, if needed (currently handler takes AppHandle and Config directly)
 #[derive(Clone)]
 pub struct McpServerLaunchParams {
     pub app_handle: AppHandle,
-    pub config_state: Arc<RwLock<Config>>,
+    pub config_state: Arc<ConfigState>,
 }
\ No newline at end of file
@@ -0,0 +1,100 @@
+use rust_mcp_sdk::McpServer;
+use rust_mcp_schema::schema_utils::NotificationFromServer;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, error, warn};
+
+const NOTIFICATION_CHANNEL_CAPACITY: usize = 1024;
+
+pub const SESSION_OUTPUT_NOTIFICATION: &str = "notifications/session/output";
+pub const SESSION_EXITED_NOTIFICATION: &str = "notifications/session/exited";
+pub const SESSION_KILLED_NOTIFICATION: &str = "notifications/session/killed";
+pub const PROCESS_EXITED_NOTIFICATION: &str = "notifications/process/exited";
+pub const CONFIG_CHANGED_NOTIFICATION: &str = "notifications/config/changed";
+pub const RESOURCE_UPDATED_NOTIFICATION: &str = "notifications/resources/updated";
+pub const TOOLS_LIST_CHANGED_NOTIFICATION: &str = "notifications/tools/list_changed";
+pub const LOG_MESSAGE_NOTIFICATION: &str = "notifications/message";
+pub const SERVER_SHUTDOWN_NOTIFICATION: &str = "notifications/server/shutdown";
+pub const JOB_FINISHED_NOTIFICATION: &str = "notifications/job/finished";
+
+#[derive(Debug, Clone)]
+pub struct ServerNotificationEvent {
+    pub method: String,
+    pub params: Value,
+    // Set for events about one network client's sessions; only that client and stdio get them.
+    pub owner: Option<String>,
+}
+
+/// Fan-out point for server-initiated MCP notifications.
+/// Tool implementations publish here without needing a handle to the MCP runtime;
+/// each running transport subscribes and forwards events to its connected client.
+#[derive(Debug)]
+pub struct McpNotifier {
+    sender: broadcast::Sender<ServerNotificationEvent>,
+}
+
+impl McpNotifier {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(NOTIFICATION_CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn notify(&self, method: &str, params: Value) {
+        self.notify_client(None, method, params);
+    }
+
+    /// Publishes an event that only the client owning it (see ClientIdentity::owner) should see.
+    /// With no owner, every transport receives it.
+    pub fn notify_client(&self, owner: Option<&str>, method: &str, params: Value) {
+        let event = ServerNotificationEvent { method: method.to_string(), params, owner: owner.map(str::to_string) };
+        // send() only fails when no transport is subscribed (e.g. MCP_TRANSPORT=disabled).
+        if self.sender.send(event).is_err() {
+            debug!(method = %method, "No MCP transport subscribed; notification dropped");
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerNotificationEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for McpNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Forwards every published event to the given runtime until the notifier is dropped.
+/// The STDIO transport owns a single runtime, so it is wrapped in an Arc and handed here.
+/// Network transports hand each session's runtime here with the client's `owner`, so it
+/// gets no events owned by other clients.
+pub async fn forward_notifications_to_runtime<S>(
+    runtime: Arc<S>,
+    mut receiver: broadcast::Receiver<ServerNotificationEvent>,
+    owner: Option<String>,
+) where
+    S: McpServer + 'static,
+{
+    loop {
+        match receiver.recv().await {
+            Ok(event) if owner.is_some() && event.owner.is_some() && event.owner != owner => {}
+            Ok(event) => {
+                let notification = NotificationFromServer::CustomNotification(json!({
+                    "method": event.method,
+                    "params": event.params,
+                }));
+                if let Err(e) = runtime.send_notification(notification).await {
+                    error!(method = %event.method, error = ?e, "Failed to send MCP notification to client");
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(skipped, "MCP notification forwarder lagged; some notifications were dropped");
+            }
+            Err(RecvError::Closed) => {
+                debug!("MCP notifier closed; stopping notification forwarder");
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::config::{Config, PolicyEffect, PolicyRule};
+use crate::error::AppError;
+use crate::mcp::approval;
+use crate::utils::path_utils::{expand_tilde_path_buf, normalize_path_base};
+
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// Argument keys holding the paths a call touches; policy path rules and query_audit_log's path_contains look at these.
+pub const PATH_ARGUMENT_KEYS: &[&str] = &["path", "paths", "file_path", "source", "destination", "cwd"];
+
+/// What the handler knows about the tool being called, independent of its arguments.
+#[derive(Debug, Clone, Copy)]
+pub struct CallFacts<'a> {
+    pub tool: &'a str,
+    pub category: &'a str,
+    pub mutating: bool,
+    pub destructive: bool,
+    // Whether the client's token scope lets the tool through.
+    pub scope_allows: bool,
+}
+
+#[derive(Debug)]
+pub enum PolicyOutcome {
+    Allow,
+    // Run only once the client approves; the reason is shown in the prompt.
+    Confirm(String),
+    Deny(AppError),
+}
+
+enum ToolSelector {
+    Name(GlobMatcher),
+    Category(String),
+    Mutating,
+    Destructive,
+}
+
+struct CompiledRule {
+    tools: Vec<ToolSelector>,
+    // None for rules without paths.
+    paths: Option<GlobSet>,
+    effect: PolicyEffect,
+    reason: Option<String>,
+    // A rule that did not compile matches every call if it denies or confirms, and none if it allows.
+    broken: bool,
+}
+
+type CompiledRules = Arc<Vec<CompiledRule>>;
+
+// Rebuilt when policy_rules or files_root change.
+static COMPILED: Lazy<Mutex<Option<((Vec<PolicyRule>, PathBuf), CompiledRules)>>> = Lazy::new(|| Mutex::new(None));
+
+fn compiled_rules(config: &Config) -> CompiledRules {
+    let key = (config.policy_rules.clone(), config.files_root.clone());
+    let mut current = COMPILED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached, rules)) = current.as_ref() {
+        if *cached == key { return rules.clone(); }
+    }
+    let rules: CompiledRules = Arc::new(config.policy_rules.iter().enumerate().map(|(index, rule)| compile_rule(rule, &config.files_root).unwrap_or_else(|e| {
+        // Config::load rejects these, so only a config that skipped it gets here.
+        warn!(rule = index, error = %e, "Failing closed on a policy rule that does not compile");
+        CompiledRule { tools: Vec::new(), paths: None, effect: rule.effect, reason: rule.reason.clone(), broken: true }
+    })).collect());
+    *current = Some((key, rules.clone()));
+    rules
+}
+
+fn compile_rule(rule: &PolicyRule, files_root: &Path) -> Result<CompiledRule, globset::Error> {
+    let tools = rule.tools.iter().map(|selector| -> Result<ToolSelector, globset::Error> { Ok(match selector.as_str() {
+        "mutating" => ToolSelector::Mutating,
+        "destructive" => ToolSelector::Destructive,
+        s => match s.strip_prefix("category:") {
+            Some(category) => ToolSelector::Category(category.to_string()),
+            None => ToolSelector::Name(Glob::new(s)?.compile_matcher()),
+        },
+    }) }).collect::<Result<_, _>>()?;
+    let paths = if rule.paths.is_empty() { None } else {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &rule.paths {
+            let expanded = expand_tilde_path_buf(pattern).unwrap_or_else(|_| PathBuf::from(pattern));
+            let absolute = if expanded.is_absolute() { expanded } else { files_root.join(expanded) };
+            builder.add(Glob::new(&absolute.to_string_lossy())?);
+        }
+        Some(builder.build()?)
+    };
+    Ok(CompiledRule { tools, paths, effect: rule.effect, reason: rule.reason.clone(), broken: false })
+}
+
+impl CompiledRule {
+    fn matches_tool(&self, facts: &CallFacts) -> bool {
+        self.tools.is_empty() || self.tools.iter().any(|selector| match selector {
+            ToolSelector::Name(glob) => glob.is_match(facts.tool),
+            ToolSelector::Category(category) => category == facts.category,
+            ToolSelector::Mutating => facts.mutating,
+            ToolSelector::Destructive => facts.destructive,
+        })
+    }
+
+    // Denying and confirming rules match when any path does; allowing ones only when all do, so
+    // one path outside an allowed tree is not let through with the rest.
+    fn matches_paths(&self, paths: &[PathBuf]) -> bool {
+        let Some(set) = &self.paths else { return true };
+        if paths.is_empty() { return false; }
+        match self.effect {
+            PolicyEffect::Allow => paths.iter().all(|p| set.is_match(p)),
+            PolicyEffect::Deny | PolicyEffect::Confirm => paths.iter().any(|p| set.is_match(p)),
+        }
+    }
+
+    fn reason(&self, index: usize) -> String {
+        self.reason.clone().unwrap_or_else(|| format!("policy_rules[{}] matches it", index))
+    }
+}
+
+// read_only, git_read_only, the token scope and disabled_tools, which no rule overrides.
+fn builtin_denial(config: &Config, facts: &CallFacts) -> Option<AppError> {
+    if config.read_only && facts.mutating {
+        return Some(AppError::ReadOnlyMode(format!("'{}' is disabled", facts.tool)));
+    }
+    if config.git_read_only && facts.mutating && facts.category == "git" {
+        return Some(AppError::ReadOnlyMode(format!("'{}' is disabled by git_read_only", facts.tool)));
+    }
+    if !facts.scope_allows {
+        return Some(AppError::ReadOnlyMode(format!("'{}' needs a token with full access", facts.tool)));
+    }
+    if config.disabled_tools.iter().any(|t| t == facts.tool) {
+        return Some(AppError::InvalidInputArgument(format!("Tool '{}' is disabled by disabled_tools", facts.tool)));
+    }
+    None
+}
+
+/// The normalized values of the call's PATH_ARGUMENT_KEYS arguments.
+fn call_paths(config: &Config, args: &Value) -> Vec<PathBuf> {
+    let ttl = Duration::from_millis(config.limits.path_cache_ttl_ms);
+    PATH_ARGUMENT_KEYS.iter().filter_map(|key| args.get(key)).flat_map(|value| match value {
+        Value::String(s) => vec![s.as_str()],
+        Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }).filter_map(|p| normalize_path_base(p, &config.files_root, ttl).ok()).collect()
+}
+
+/// Decides a call before it runs: the built-in denials, then the first matching policy rule,
+/// then approval_required_tools and approval_command_patterns. An allow rule stops later rules
+/// but not the approval settings.
+pub fn evaluate(config: &Config, facts: &CallFacts, args: &Value) -> PolicyOutcome {
+    if let Some(error) = builtin_denial(config, facts) { return PolicyOutcome::Deny(error); }
+    let rules = compiled_rules(config);
+    let paths = if rules.iter().any(|r| r.paths.is_some()) { call_paths(config, args) } else { Vec::new() };
+    let matched = rules.iter().enumerate().find(|(_, rule)| match rule.broken {
+        true => rule.effect != PolicyEffect::Allow,
+        false => rule.matches_tool(facts) && rule.matches_paths(&paths),
+    });
+    match matched {
+        Some((index, rule)) if rule.effect == PolicyEffect::Deny => {
+            return PolicyOutcome::Deny(AppError::PolicyDenied { message: format!("'{}': {}", facts.tool, rule.reason(index)), rule: index });
+        }
+        Some((index, rule)) if rule.effect == PolicyEffect::Confirm => return PolicyOutcome::Confirm(rule.reason(index)),
+        _ => {}
+    }
+    match approval::approval_reason(config, facts.tool, args, facts.destructive) {
+        Some(reason) => PolicyOutcome::Confirm(reason),
+        None => PolicyOutcome::Allow,
+    }
+}
+
+/// Whether tools/list shows the tool: not when a built-in denial or a path-less deny rule would
+/// reject every call to it. Tools denied only under some paths stay listed.
+pub fn lists_tool(config: &Config, facts: &CallFacts) -> bool {
+    if builtin_denial(config, facts).is_some() { return false; }
+    let rules = compiled_rules(config);
+    let first = rules.iter().find(|rule| !rule.broken && rule.matches_tool(facts));
+    !first.is_some_and(|rule| rule.effect == PolicyEffect::Deny && rule.paths.is_none())
+}
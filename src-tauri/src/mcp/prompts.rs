@@ -0,0 +1,89 @@
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::mcp::resources::{file_uri, read_resource};
+use crate::utils::path_utils::validate_and_normalize_path;
+
+use rust_mcp_schema::{
+    EmbeddedResource, EmbeddedResourceResource, GetPromptResult, Prompt, PromptArgument, PromptMessage,
+    PromptMessageContent, ReadResourceResultContentsItem, Role, TextContent,
+};
+use std::collections::HashMap;
+
+fn argument(name: &str, description: &str, required: bool) -> PromptArgument {
+    PromptArgument { name: name.to_string(), description: Some(description.to_string()), required: Some(required) }
+}
+
+pub fn list_prompts() -> Vec<Prompt> {
+    vec![
+        Prompt {
+            name: "review_diff".to_string(),
+            description: Some("Review a unified diff for bugs, risky changes and missing tests.".to_string()),
+            arguments: vec![
+                argument("diff", "The unified diff to review (e.g. `git diff` output).", true),
+                argument("focus", "Optional area to concentrate on, such as error handling or performance.", false),
+            ],
+        },
+        Prompt {
+            name: "summarize_file".to_string(),
+            description: Some("Summarize a file; its contents are embedded as a resource.".to_string()),
+            arguments: vec![
+                argument("path", "File to summarize, absolute or relative to FILES_ROOT.", true),
+                argument("focus", "Optional question to answer about the file.", false),
+            ],
+        },
+        Prompt {
+            name: "explain_error".to_string(),
+            description: Some("Triage an error message or stack trace: likely cause, where to look, how to fix.".to_string()),
+            arguments: vec![
+                argument("error", "The error message or stack trace.", true),
+                argument("context", "Optional context: the command that was run, recent changes, environment.", false),
+            ],
+        },
+    ]
+}
+
+fn required_arg<'a>(arguments: &'a HashMap<String, String>, prompt: &str, name: &str) -> Result<&'a str, AppError> {
+    arguments.get(name).map(String::as_str).filter(|v| !v.trim().is_empty())
+        .ok_or_else(|| AppError::InvalidInputArgument(format!("Prompt '{}' requires the '{}' argument", prompt, name)))
+}
+
+fn user_text(text: String) -> PromptMessage {
+    PromptMessage { role: Role::User, content: PromptMessageContent::TextContent(TextContent::new(text, None)) }
+}
+
+/// Renders one of the `list_prompts` prompts with the client's arguments.
+pub async fn get_prompt(deps: &ToolDependencies, name: &str, arguments: Option<HashMap<String, String>>) -> Result<GetPromptResult, AppError> {
+    let arguments = arguments.unwrap_or_default();
+    let focus = |key: &str| arguments.get(key).filter(|v| !v.trim().is_empty()).cloned();
+    let (description, messages) = match name {
+        "review_diff" => {
+            let diff = required_arg(&arguments, name, "diff")?;
+            let mut text = String::from("Review the following diff. Point out bugs, behaviour changes that look unintended, risky edits and missing tests. Reference files and hunks, and keep stylistic remarks short.\n");
+            if let Some(f) = focus("focus") { text.push_str(&format!("Concentrate on: {}\n", f)); }
+            text.push_str(&format!("\n```diff\n{}\n```", diff));
+            ("Review a diff", vec![user_text(text)])
+        }
+        "summarize_file" => {
+            let path_arg = required_arg(&arguments, name, "path")?;
+            let path = validate_and_normalize_path(path_arg, &deps.config_state.snapshot(), true, false)?;
+            let uri = file_uri(&path).ok_or_else(|| AppError::InvalidPath(format!("{} cannot be expressed as a file:// URI", path.display())))?;
+            let resource = match read_resource(deps, &uri).await? {
+                ReadResourceResultContentsItem::TextResourceContents(text) => EmbeddedResourceResource::TextResourceContents(text),
+                ReadResourceResultContentsItem::BlobResourceContents(blob) => EmbeddedResourceResource::BlobResourceContents(blob),
+            };
+            let mut text = format!("Summarize {}: its purpose, main components and anything surprising. Keep it brief.", path.display());
+            if let Some(f) = focus("focus") { text.push_str(&format!(" In particular: {}", f)); }
+            let embedded = PromptMessage { role: Role::User, content: PromptMessageContent::EmbeddedResource(EmbeddedResource::new(resource, None)) };
+            ("Summarize a file", vec![user_text(text), embedded])
+        }
+        "explain_error" => {
+            let error = required_arg(&arguments, name, "error")?;
+            let mut text = String::from("Triage this error. Give the most likely cause, the frames or files to look at first, and a concrete fix or next diagnostic step. If the trace is truncated or ambiguous, say what extra output would settle it.\n");
+            if let Some(c) = focus("context") { text.push_str(&format!("Context: {}\n", c)); }
+            text.push_str(&format!("\n```\n{}\n```", error));
+            ("Explain an error", vec![user_text(text)])
+        }
+        other => return Err(AppError::InvalidInputArgument(format!("Unknown prompt '{}'", other))),
+    };
+    Ok(GetPromptResult { description: Some(description.to_string()), messages, meta: None })
+}
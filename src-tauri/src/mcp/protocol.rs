@@ -0,0 +1,86 @@
+use rust_mcp_sdk::McpServer;
+#[cfg(any(test, feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+use serde_json::Value;
+
+/// Streamable HTTP header carrying the negotiated version on every request after initialize (2025-06-18).
+#[cfg(feature = "mcp-http-server")]
+pub const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// The MCP revisions this server speaks, oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProtocolVersion {
+    V2024_11_05,
+    V2025_03_26,
+    V2025_06_18,
+}
+
+impl ProtocolVersion {
+    const ALL: [ProtocolVersion; 3] = [Self::V2024_11_05, Self::V2025_03_26, Self::V2025_06_18];
+    pub const LATEST: ProtocolVersion = Self::V2025_06_18;
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::V2024_11_05 => "2024-11-05",
+            Self::V2025_03_26 => "2025-03-26",
+            Self::V2025_06_18 => "2025-06-18",
+        }
+    }
+
+    pub fn parse(version: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.as_str() == version)
+    }
+
+    /// The version initialize answers with: the client's if this server speaks it, and the latest
+    /// otherwise, which the client may then accept or disconnect from.
+    pub fn negotiate(requested: &str) -> Self {
+        Self::parse(requested).unwrap_or(Self::LATEST)
+    }
+
+    /// The version negotiated with the session's client. Before initialize nothing newer than the
+    /// oldest version is assumed.
+    pub fn of_session(runtime: &dyn McpServer) -> Self {
+        runtime.client_info().map(|client| Self::negotiate(&client.protocol_version)).unwrap_or(Self::V2024_11_05)
+    }
+
+    /// Tool annotations and the completions capability (2025-03-26).
+    pub fn has_tool_annotations(self) -> bool {
+        self >= Self::V2025_03_26
+    }
+
+    /// elicitation/create requests to the client (2025-06-18).
+    pub fn has_elicitation(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+
+    /// structuredContent in tools/call results (2025-06-18).
+    #[cfg(any(test, feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+    pub fn has_structured_content(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+
+    /// 2025-06-18 drops JSON-RPC batches and has Streamable HTTP clients send MCP-Protocol-Version.
+    #[cfg(feature = "mcp-http-server")]
+    pub fn has_jsonrpc_batches(self) -> bool {
+        self < Self::V2025_06_18
+    }
+}
+
+/// Adds structuredContent to a tools/call result whose only content is a JSON object as text;
+/// the text stays for clients that read content. rust-mcp-schema has no field for it, so this
+/// works on the serialized message. Returns whether the message changed.
+#[cfg(any(test, feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+pub fn add_structured_content(message: &mut Value, version: ProtocolVersion) -> bool {
+    if !version.has_structured_content() { return false; }
+    // Of all results, only tools/call ones have a top-level content array.
+    let Some(result) = message.get_mut("result").and_then(Value::as_object_mut) else { return false };
+    if result.contains_key("structuredContent") { return false; }
+    let structured = match result.get("content").and_then(Value::as_array).map(Vec::as_slice) {
+        Some([item]) if item.get("type").and_then(Value::as_str) == Some("text") => {
+            item.get("text").and_then(Value::as_str).and_then(|text| serde_json::from_str::<Value>(text).ok()).filter(Value::is_object)
+        }
+        _ => None,
+    };
+    let Some(structured) = structured else { return false };
+    result.insert("structuredContent".to_string(), structured);
+    true
+}
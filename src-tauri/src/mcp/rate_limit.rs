@@ -0,0 +1,91 @@
+use crate::config::LimitsConfig;
+use crate::error::AppError;
+use crate::mcp::handler::MUTATING_TOOLS;
+
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+use std::time::Instant;
+
+// Buckets that have refilled completely carry no state, so they are dropped past this many.
+const MAX_IDLE_BUCKETS: usize = 1024;
+// Rate-limit key for calls without a network client (the STDIO transport).
+const LOCAL_CLIENT: &str = "stdio";
+
+/// How expensive a tool is, which picks its `limits.*_calls_per_minute` rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolClass {
+    Read,
+    Search,
+    // MUTATING_TOOLS: writes, commands and process signals.
+    Exec,
+}
+
+impl ToolClass {
+    pub fn of(tool_name: &str) -> Self {
+        match tool_name {
+            "search_code" | "search_files" => Self::Search,
+            name if MUTATING_TOOLS.contains(&name) => Self::Exec,
+            _ => Self::Read,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Search => "search",
+            Self::Exec => "exec",
+        }
+    }
+
+    fn calls_per_minute(self, limits: &LimitsConfig) -> u32 {
+        match self {
+            Self::Read => limits.read_calls_per_minute,
+            Self::Search => limits.search_calls_per_minute,
+            Self::Exec => limits.exec_calls_per_minute,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets per client and tool. Each holds up to one minute of calls and refills
+/// continuously, so a client can burst to the per-minute rate and then keeps to it.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: StdMutex<HashMap<(String, String), Bucket>>,
+}
+
+impl RateLimiter {
+    /// Takes one call from the bucket of `client` (None for STDIO) and `tool_name`. Rates are
+    /// read from `limits` on every call, so changes apply at once; 0 means unlimited.
+    pub fn check(&self, limits: &LimitsConfig, client: Option<&str>, tool_name: &str) -> Result<(), AppError> {
+        let class = ToolClass::of(tool_name);
+        let per_minute = class.calls_per_minute(limits);
+        if per_minute == 0 { return Ok(()); }
+        let capacity = per_minute as f64;
+        let per_second = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().map_err(|e| AppError::Unknown(format!("Rate limiter lock error: {}", e)))?;
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.updated).as_secs_f64() * per_second < capacity);
+        }
+        let bucket = buckets.entry((client.unwrap_or(LOCAL_CLIENT).to_string(), tool_name.to_string())).or_insert(Bucket { tokens: capacity, updated: now });
+        // Clamped, so lowering a rate takes effect on buckets filled under the old one.
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let retry_after_ms = ((1.0 - bucket.tokens) / per_second * 1000.0).ceil() as u64;
+        Err(AppError::RateLimited {
+            message: format!("'{}' is limited to {} calls per minute (limits.{}_calls_per_minute); retry in {} ms", tool_name, per_minute, class.as_str(), retry_after_ms),
+            tool_class: class.as_str().to_string(),
+            retry_after_ms,
+        })
+    }
+}
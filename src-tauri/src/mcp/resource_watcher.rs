@@ -0,0 +1,123 @@
+use crate::error::AppError;
+use crate::mcp::notifications::{McpNotifier, RESOURCE_UPDATED_NOTIFICATION};
+use crate::utils::path_cache;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::{debug, info, warn};
+
+// Coalesces the several events an editor or a build tool produces for one save.
+const UPDATE_DEBOUNCE: Duration = Duration::from_millis(200);
+
+struct Subscription {
+    path: PathBuf,
+    // Subscribe calls for this URI not yet matched by an unsubscribe.
+    count: usize,
+}
+
+#[derive(Default)]
+struct SubscriptionState {
+    by_uri: HashMap<String, Subscription>,
+}
+
+impl SubscriptionState {
+    fn uris_for_paths(&self, paths: &HashSet<PathBuf>) -> Vec<String> {
+        self.by_uri.iter().filter(|(_, s)| paths.contains(&s.path)).map(|(uri, _)| uri.clone()).collect()
+    }
+
+    fn parent_in_use(&self, dir: &Path) -> bool {
+        self.by_uri.values().any(|s| s.path.parent() == Some(dir))
+    }
+}
+
+/// resources/subscribe bookkeeping. Subscribed files are watched through their parent directory,
+/// so files replaced by rename (as most editors save) keep reporting, and each change is sent
+/// as `notifications/resources/updated` with the subscribed URI.
+pub struct ResourceSubscriptions {
+    state: StdMutex<SubscriptionState>,
+    watcher: StdMutex<Option<RecommendedWatcher>>,
+}
+
+impl ResourceSubscriptions {
+    /// Creates the watcher and the task that turns its events into notifications.
+    /// If the platform watcher is unavailable, subscribe calls fail instead.
+    pub fn spawn(notifier: Arc<McpNotifier>) -> Arc<Self> {
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel::<notify::Result<notify::Event>>();
+        let watcher = match notify::recommended_watcher(move |event| { let _ = event_tx.send(event); }) {
+            Ok(w) => Some(w),
+            Err(e) => { warn!(error = %e, "Failed to create resource watcher; resources/subscribe disabled"); None }
+        };
+        let subscriptions = Arc::new(Self { state: StdMutex::new(SubscriptionState::default()), watcher: StdMutex::new(watcher) });
+
+        let task_subscriptions = Arc::downgrade(&subscriptions);
+        tauri::async_runtime::spawn(async move {
+            while let Some(event) = event_rx.recv().await {
+                let mut changed: HashSet<PathBuf> = HashSet::new();
+                let mut collect = |event: notify::Result<notify::Event>| match event {
+                    Ok(ev) if !matches!(ev.kind, EventKind::Access(_)) => changed.extend(ev.paths),
+                    Ok(_) => {}
+                    Err(e) => warn!(error = %e, "Resource watcher error"),
+                };
+                collect(event);
+                tokio::time::sleep(UPDATE_DEBOUNCE).await;
+                while let Ok(event) = event_rx.try_recv() { collect(event); }
+                for path in &changed { path_cache::invalidate(path); }
+
+                let Some(subscriptions) = task_subscriptions.upgrade() else { break };
+                let uris = match subscriptions.state.lock() {
+                    Ok(state) => state.uris_for_paths(&changed),
+                    Err(_) => break,
+                };
+                for uri in uris {
+                    debug!(uri = %uri, "Subscribed resource changed");
+                    notifier.notify(RESOURCE_UPDATED_NOTIFICATION, json!({ "uri": uri }));
+                }
+            }
+            debug!("Resource watcher channel closed");
+        });
+        subscriptions
+    }
+
+    /// `path` must already be validated against the path rules.
+    pub fn subscribe(&self, uri: &str, path: PathBuf) -> Result<(), AppError> {
+        let dir = path.parent().map(PathBuf::from).ok_or_else(|| AppError::InvalidPath(format!("{} has no parent directory", path.display())))?;
+        let mut state = self.state.lock().map_err(|e| AppError::Unknown(format!("Resource subscription lock: {}", e)))?;
+        if let Some(existing) = state.by_uri.get_mut(uri) {
+            existing.count += 1;
+            return Ok(());
+        }
+        if !state.parent_in_use(&dir) {
+            let mut watcher_guard = self.watcher.lock().map_err(|e| AppError::Unknown(format!("Resource watcher lock: {}", e)))?;
+            let watcher = watcher_guard.as_mut().ok_or_else(|| AppError::Unknown("File watching is unavailable; resources/subscribe is disabled".to_string()))?;
+            watcher.watch(&dir, RecursiveMode::NonRecursive).map_err(|e| AppError::StdIoError(format!("Failed to watch {}: {}", dir.display(), e)))?;
+        }
+        info!(uri = %uri, path = %path.display(), "Resource subscribed");
+        state.by_uri.insert(uri.to_string(), Subscription { path, count: 1 });
+        Ok(())
+    }
+
+    /// Unsubscribing a URI that is not subscribed is not an error.
+    pub fn unsubscribe(&self, uri: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().map_err(|e| AppError::Unknown(format!("Resource subscription lock: {}", e)))?;
+        let Some(existing) = state.by_uri.get_mut(uri) else { return Ok(()) };
+        existing.count -= 1;
+        if existing.count > 0 { return Ok(()); }
+        let removed = state.by_uri.remove(uri).map(|s| s.path);
+        if let Some(dir) = removed.as_deref().and_then(Path::parent) {
+            if !state.parent_in_use(dir) {
+                if let Ok(mut watcher_guard) = self.watcher.lock() {
+                    if let Some(watcher) = watcher_guard.as_mut() {
+                        if let Err(e) = watcher.unwatch(dir) { debug!(dir = %dir.display(), error = %e, "Failed to unwatch resource directory"); }
+                    }
+                }
+            }
+        }
+        info!(uri = %uri, "Resource unsubscribed");
+        Ok(())
+    }
+}
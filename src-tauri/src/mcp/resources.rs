@@ -0,0 +1,135 @@
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::utils::limits::check_file_size;
+use crate::utils::path_utils::validate_and_normalize_path;
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+use rust_mcp_schema::{BlobResourceContents, ReadResourceResultContentsItem, Resource, ResourceTemplate, TextResourceContents};
+use std::path::{Path, PathBuf};
+use tauri_plugin_fs::FsExt;
+use tokio::fs as tokio_fs;
+use tracing::{debug, instrument};
+
+pub const FILE_RESOURCE_TEMPLATE: &str = "file:///{path}";
+
+// resources/list walks this deep below each allowed directory; deeper files are still
+// readable through the file:///{path} template.
+const RESOURCE_LIST_MAX_DEPTH: usize = 2;
+const RESOURCE_LIST_MAX_ENTRIES: usize = 1000;
+const RESOURCE_LIST_PAGE_SIZE: usize = 100;
+
+pub fn file_uri(path: &Path) -> Option<String> {
+    reqwest::Url::from_file_path(path).ok().map(|u| u.to_string())
+}
+
+pub fn path_from_file_uri(uri: &str) -> Result<PathBuf, AppError> {
+    let url = reqwest::Url::parse(uri).map_err(|e| AppError::InvalidInputArgument(format!("Invalid resource URI '{}': {}", uri, e)))?;
+    if url.scheme() != "file" {
+        return Err(AppError::InvalidInputArgument(format!("Unsupported resource URI scheme '{}'; only file:// is served", url.scheme())));
+    }
+    url.to_file_path().map_err(|_| AppError::InvalidInputArgument(format!("Resource URI '{}' is not a local file path", uri)))
+}
+
+/// The validated local path behind a file:// resource URI, under the same rules as read_file.
+pub fn resolve_resource_path(deps: &ToolDependencies, uri: &str) -> Result<PathBuf, AppError> {
+    let requested_path = path_from_file_uri(uri)?;
+    let path = validate_and_normalize_path(&requested_path.to_string_lossy(), &deps.config_state.snapshot(), true, false)?;
+    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("FS scope disallows read: {}", path.display()) }); }
+    Ok(path)
+}
+
+fn is_text_mime(mime_type: &str) -> bool {
+    mime_type.starts_with("text/") || matches!(mime_type, "application/json" | "application/xml" | "application/javascript" | "application/toml" | "application/x-sh")
+}
+
+pub fn resource_templates() -> Vec<ResourceTemplate> {
+    vec![ResourceTemplate {
+        name: "Local file".to_string(),
+        uri_template: FILE_RESOURCE_TEMPLATE.to_string(),
+        description: Some("Any file under the allowed directories, by absolute path.".to_string()),
+        mime_type: None,
+        annotations: None,
+    }]
+}
+
+/// Files below the allowed directories, breadth-first and capped, one page per call.
+/// The cursor is the offset of the next page.
+#[instrument(skip(deps))]
+pub async fn list_resources(deps: &ToolDependencies, cursor: Option<String>) -> Result<(Vec<Resource>, Option<String>), AppError> {
+    let offset = match cursor.as_deref() {
+        Some(c) => c.parse::<usize>().map_err(|_| AppError::InvalidInputArgument(format!("Invalid resources/list cursor '{}'", c)))?,
+        None => 0,
+    };
+    let roots: Vec<PathBuf> = {
+        let config = deps.config_state.snapshot();
+        // Full-filesystem entries like "/" are not enumerated.
+        config.allowed_directories.iter().filter(|d| d.parent().is_some()).cloned().collect()
+    };
+
+    let mut queue: std::collections::VecDeque<(PathBuf, usize)> = roots.into_iter().map(|r| (r, 0)).collect();
+    let mut files = Vec::new();
+    while let Some((dir, depth)) = queue.pop_front() {
+        if files.len() >= RESOURCE_LIST_MAX_ENTRIES { break; }
+        let Ok(mut read_dir) = tokio_fs::read_dir(&dir).await else { continue };
+        let mut entries = Vec::new();
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            if entry.file_name().to_string_lossy().starts_with('.') { continue; }
+            let Ok(file_type) = entry.file_type().await else { continue };
+            entries.push((entry.path(), file_type.is_dir()));
+        }
+        entries.sort();
+        for (path, is_dir) in entries {
+            if is_dir {
+                if depth < RESOURCE_LIST_MAX_DEPTH { queue.push_back((path, depth + 1)); }
+            } else if files.len() < RESOURCE_LIST_MAX_ENTRIES {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    let mut resources = Vec::new();
+    let page_end = (offset + RESOURCE_LIST_PAGE_SIZE).min(files.len());
+    for path in files.get(offset..page_end).unwrap_or_default() {
+        let allowed = validate_and_normalize_path(&path.to_string_lossy(), &deps.config_state.snapshot(), true, false).is_ok();
+        if !allowed || !deps.app_handle.fs_scope().is_allowed(path) { continue; }
+        let Some(uri) = file_uri(path) else { continue };
+        let size = tokio_fs::metadata(path).await.ok().map(|m| m.len() as i64);
+        resources.push(Resource {
+            name: path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            uri,
+            description: None,
+            mime_type: Some(mime_guess::from_path(path).first_or_octet_stream().to_string()),
+            size,
+            annotations: None,
+        });
+    }
+    let next_cursor = (page_end < files.len()).then(|| page_end.to_string());
+    Ok((resources, next_cursor))
+}
+
+/// Reads a file:// resource as text, or as a base64 blob when it is not UTF-8 text.
+#[instrument(skip(deps))]
+pub async fn read_resource(deps: &ToolDependencies, uri: &str) -> Result<ReadResourceResultContentsItem, AppError> {
+    let path = resolve_resource_path(deps, uri)?;
+    let limits = deps.config_state.snapshot().limits.clone();
+    let meta = tokio_fs::metadata(&path).await.map_err(|e| AppError::TokioIoError(e.to_string()))?;
+    if !meta.is_file() {
+        return Err(AppError::InvalidPath(format!("{} is not a file", path.display())));
+    }
+    check_file_size(&limits, path.display(), meta.len())?;
+
+    let bytes = tokio_fs::read(&path).await.map_err(|e| AppError::TokioIoError(e.to_string()))?;
+    let mime_type = mime_guess::from_path(&path).first_or_octet_stream().to_string();
+    debug!(path = %path.display(), mime = %mime_type, bytes = bytes.len(), "Serving file resource");
+    let uri = uri.to_string();
+    // Unknown extensions (Makefile, LICENSE, ...) are served as text when they decode as UTF-8.
+    let text_like = is_text_mime(&mime_type) || mime_type == "application/octet-stream";
+    if text_like && std::str::from_utf8(&bytes).is_ok() {
+        let mime_type = if mime_type == "application/octet-stream" { "text/plain".to_string() } else { mime_type };
+        let text = String::from_utf8(bytes).unwrap_or_default();
+        return Ok(ReadResourceResultContentsItem::TextResourceContents(TextResourceContents { uri, mime_type: Some(mime_type), text }));
+    }
+    Ok(ReadResourceResultContentsItem::BlobResourceContents(BlobResourceContents { uri, mime_type: Some(mime_type), blob: BASE64_STANDARD.encode(&bytes) }))
+}
@@ -0,0 +1,90 @@
+use crate::error::AppError;
+use crate::mcp::resources::path_from_file_uri;
+
+use rust_mcp_sdk::McpServer;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info, warn};
+
+const LIST_ROOTS_TIMEOUT: Duration = Duration::from_secs(10);
+
+tokio::task_local! {
+    // Roots of the client whose request is being handled; unset for UI commands.
+    static SESSION_ROOTS: Arc<Vec<PathBuf>>;
+}
+
+/// Roots reported by each connected client through roots/list. The SSE server shares one
+/// handler between sessions, so entries are keyed by the session's runtime. Every session sends
+/// notifications/initialized first, which refreshes its entry, so a reused key never keeps the
+/// roots of an earlier session.
+#[derive(Default)]
+pub struct ClientRoots {
+    by_session: StdMutex<HashMap<usize, Arc<Vec<PathBuf>>>>,
+}
+
+fn session_key(runtime: &dyn McpServer) -> usize {
+    runtime as *const dyn McpServer as *const () as usize
+}
+
+impl ClientRoots {
+    /// Asks the client for its roots. Clients without the roots capability, or that report no
+    /// roots, leave the session limited by allowed_directories alone.
+    pub async fn refresh(&self, runtime: &dyn McpServer) {
+        let key = session_key(runtime);
+        let roots = if runtime.client_supports_root_list() == Some(true) {
+            match timeout(LIST_ROOTS_TIMEOUT, runtime.list_roots(None)).await {
+                Ok(Ok(result)) => result.roots.iter().filter_map(|root| match path_from_file_uri(&root.uri) {
+                    Ok(path) => Some(dunce::canonicalize(&path).unwrap_or(path)),
+                    Err(e) => { warn!(uri = %root.uri, error = %e, "Ignoring client root"); None }
+                }).collect(),
+                Ok(Err(e)) => { warn!(error = ?e, "roots/list failed; client roots not applied"); Vec::new() }
+                Err(_) => { warn!("roots/list did not answer in time; client roots not applied"); Vec::new() }
+            }
+        } else {
+            Vec::new()
+        };
+        let Ok(mut by_session) = self.by_session.lock() else { return };
+        if roots.is_empty() {
+            debug!("Client reported no roots");
+            by_session.remove(&key);
+        } else {
+            info!(roots = ?roots, "Applying client roots");
+            by_session.insert(key, Arc::new(roots));
+        }
+    }
+
+    /// Runs `fut` with path validation limited to the session's roots, if it reported any.
+    pub async fn scope<F: Future>(&self, runtime: &dyn McpServer, fut: F) -> F::Output {
+        let roots = self.by_session.lock().ok().and_then(|by_session| by_session.get(&session_key(runtime)).cloned());
+        match roots {
+            Some(roots) => SESSION_ROOTS.scope(roots, fut).await,
+            None => fut.await,
+        }
+    }
+}
+
+/// The roots scope of the current task, for carrying it onto a blocking thread with `with_roots`.
+pub fn current_roots() -> Option<Arc<Vec<PathBuf>>> {
+    SESSION_ROOTS.try_with(Arc::clone).ok()
+}
+
+/// Runs `f` with path validation limited to `roots`, as `ClientRoots::scope` does for futures.
+pub fn with_roots<R>(roots: Option<Arc<Vec<PathBuf>>>, f: impl FnOnce() -> R) -> R {
+    match roots {
+        Some(roots) => SESSION_ROOTS.sync_scope(roots, f),
+        None => f(),
+    }
+}
+
+/// Called by path validation after the allowed_directories check, so the effective scope is
+/// the intersection of allowed_directories and the client's roots.
+pub fn check_client_roots(path: &Path) -> Result<(), AppError> {
+    SESSION_ROOTS.try_with(|roots| {
+        if roots.iter().any(|root| path.starts_with(root)) { return Ok(()); }
+        let listed: Vec<String> = roots.iter().map(|r| r.display().to_string()).collect();
+        Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("Path {} is outside the client's roots: {}", path.display(), listed.join(", ")) })
+    }).unwrap_or(Ok(()))
+}
@@ -0,0 +1,160 @@
+use crate::error::AppError;
+use crate::utils::limits::truncate_output;
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+use flate2::{write::GzEncoder, Compression};
+use rust_mcp_sdk::McpServer;
+use rust_mcp_schema::{CreateMessageRequestParams, CreateMessageResultContent, Role, SamplingMessage, SamplingMessageContent, TextContent};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info};
+
+/// Tools whose results can be condensed with `summarize: true`.
+pub const SUMMARIZABLE_TOOLS: &[&str] = &["search_code", "execute_command", "read_session_output_status"];
+
+// Results below this size are returned as they are, even with summarize: true.
+pub const SUMMARIZE_THRESHOLD_BYTES: usize = 32 * 1024;
+const SUMMARY_MAX_TOKENS: i64 = 1024;
+// The client may show the request to the user for approval before its model runs.
+const SAMPLING_TIMEOUT: Duration = Duration::from_secs(120);
+// Raw outputs kept for fetch_raw_output, summarized and oversized ones together; the oldest is dropped first.
+const RAW_OUTPUT_STORE_CAPACITY: usize = 32;
+
+#[derive(Default)]
+pub struct RawOutputStore {
+    outputs: StdMutex<VecDeque<(String, Arc<String>)>>,
+}
+
+impl RawOutputStore {
+    pub fn insert(&self, raw: String) -> String {
+        let handle = uuid::Uuid::new_v4().to_string();
+        if let Ok(mut outputs) = self.outputs.lock() {
+            if outputs.len() >= RAW_OUTPUT_STORE_CAPACITY { outputs.pop_front(); }
+            outputs.push_back((handle.clone(), Arc::new(raw)));
+        }
+        handle
+    }
+
+    fn get(&self, handle: &str) -> Option<Arc<String>> {
+        self.outputs.lock().ok()?.iter().find(|(h, _)| h == handle).map(|(_, raw)| raw.clone())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SummarizedOutputMCP {
+    pub tool: String,
+    pub summary: String,
+    pub model: String,
+    // Pass to fetch_raw_output for the full result.
+    pub raw_handle: String,
+    pub raw_bytes: usize,
+}
+
+/// Asks the client's model, via sampling/createMessage, to condense a large tool result, and
+/// keeps the raw result for fetch_raw_output. Errors leave the caller to return the raw result.
+pub async fn summarize_tool_output(runtime: &dyn McpServer, store: &RawOutputStore, tool_name: &str, raw: String, max_input_bytes: usize) -> Result<SummarizedOutputMCP, AppError> {
+    if runtime.client_supports_sampling() != Some(true) {
+        return Err(AppError::InvalidInputArgument("The client does not support sampling".to_string()));
+    }
+    let mut input = raw.clone();
+    let input_truncated = truncate_output(&mut input, max_input_bytes);
+    let prompt = format!(
+        "Summarize this `{}` tool result for a coding agent. Keep file paths, line numbers, error messages and counts; group repetitive entries and say how many there were.{}\n\n{}",
+        tool_name,
+        if input_truncated { " The result was cut off at the server's output limit." } else { "" },
+        input,
+    );
+    let params = CreateMessageRequestParams {
+        messages: vec![SamplingMessage { role: Role::User, content: SamplingMessageContent::TextContent(TextContent::new(prompt, None)) }],
+        max_tokens: SUMMARY_MAX_TOKENS,
+        system_prompt: Some("You condense large tool outputs. Be factual and brief; never invent entries.".to_string()),
+        include_context: None,
+        metadata: None,
+        model_preferences: None,
+        stop_sequences: Vec::new(),
+        temperature: None,
+    };
+    debug!(tool = %tool_name, bytes = raw.len(), "Requesting sampling/createMessage summary");
+    let result = timeout(SAMPLING_TIMEOUT, runtime.create_message(params)).await
+        .map_err(|_| AppError::TimeoutError("sampling/createMessage did not answer in time".to_string()))?
+        .map_err(|e| AppError::McpSdkError(format!("sampling/createMessage failed: {:?}", e)))?;
+    let summary = match result.content {
+        CreateMessageResultContent::TextContent(text) => text.text,
+        _ => return Err(AppError::McpSdkError("sampling/createMessage returned non-text content".to_string())),
+    };
+    let raw_bytes = raw.len();
+    let raw_handle = store.insert(raw);
+    info!(tool = %tool_name, raw_bytes, model = %result.model, "Summarized large tool output with client sampling");
+    Ok(SummarizedOutputMCP { tool: tool_name.to_string(), summary, model: result.model, raw_handle, raw_bytes })
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct FetchRawOutputParamsMCP {
+    /// raw_handle from a summarized or message_too_large result.
+    pub handle: String,
+    /// Byte offset to start at. Use the previous response's next_offset as a cursor.
+    #[serde(default)]
+    pub offset: usize,
+    /// Maximum raw bytes to return. Default and cap: the server output limit, or half the message limit if that is smaller.
+    #[serde(default, alias = "maxBytes")]
+    pub max_bytes: Option<usize>,
+    /// 'gzip_base64' returns each chunk gzip-compressed and base64-encoded, for smaller messages.
+    #[serde(default)]
+    pub encoding: RawOutputEncoding,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RawOutputEncoding {
+    #[default]
+    Text,
+    // Each chunk gzip-compressed on its own, then base64-encoded.
+    GzipBase64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FetchRawOutputResultMCP {
+    pub handle: String,
+    pub offset: usize,
+    pub total_bytes: usize,
+    pub encoding: RawOutputEncoding,
+    pub content: String,
+    // Offset of the next chunk, if there is more.
+    pub next_offset: Option<usize>,
+}
+
+/// One chunk of a result that was replaced by a summary or was too large to send. Offsets are
+/// bytes of the raw result, moved back to the nearest character boundary.
+pub fn fetch_raw_output(store: &RawOutputStore, params: FetchRawOutputParamsMCP, max_output_bytes: usize) -> Result<FetchRawOutputResultMCP, AppError> {
+    let raw = store.get(&params.handle).ok_or_else(|| AppError::InvalidInputArgument(format!("Unknown or expired raw output handle '{}'", params.handle)))?;
+    let floor_boundary = |mut idx: usize| { idx = idx.min(raw.len()); while !raw.is_char_boundary(idx) { idx -= 1; } idx };
+    let start = floor_boundary(params.offset);
+    let end = floor_boundary(start + params.max_bytes.unwrap_or(max_output_bytes).min(max_output_bytes));
+    let chunk = &raw[start..end];
+    let content = match params.encoding {
+        RawOutputEncoding::Text => chunk.to_string(),
+        RawOutputEncoding::GzipBase64 => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(chunk.as_bytes()).and_then(|_| encoder.finish()).map(|gz| BASE64_STANDARD.encode(gz))
+                .map_err(|e| AppError::StdIoError(format!("Failed to compress raw output: {}", e)))?
+        }
+    };
+    Ok(FetchRawOutputResultMCP {
+        handle: params.handle,
+        offset: start,
+        total_bytes: raw.len(),
+        encoding: params.encoding,
+        content,
+        next_offset: (end < raw.len()).then_some(end),
+    })
+}
+
+/// Whether the call asked for a summary of a large result.
+pub fn summarize_requested(tool_name: &str, args: &Value) -> bool {
+    SUMMARIZABLE_TOOLS.contains(&tool_name) && args.get("summarize").and_then(Value::as_bool).unwrap_or(false)
+}
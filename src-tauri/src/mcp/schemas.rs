@@ -1,27 +1,15 @@
+use crate::mcp::batch::BatchToolsParamsMCP;
+use crate::mcp::sampling::FetchRawOutputParamsMCP;
+use crate::mcp::tool_impl::{audit, config, edit, filesystem, git, jobs, process, ripgrep, script, snapshot, terminal};
+
 use rust_mcp_schema::ToolInputSchema;
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
 use serde_json::{json, Value, Map};
 use std::collections::HashMap;
 
-fn create_prop(type_str: &str, description: &str) -> Value {
-    json!({ "type": type_str, "description": description })
-}
-fn create_prop_with_default_bool(type_str: &str, description: &str, default_val: bool) -> Value {
-    json!({ "type": type_str, "description": description, "default": default_val })
-}
-fn create_prop_with_default_int(type_str: &str, description: &str, default_val: usize) -> Value {
-    json!({ "type": type_str, "description": description, "default": default_val })
-}
-fn create_prop_with_default_str(type_str: &str, description: &str, default_val: &str) -> Value {
-    json!({ "type": type_str, "description": description, "default": default_val })
-}
-fn create_array_prop(item_type_str: &str, description: &str) -> Value {
-    json!({ "type": "array", "items": { "type": item_type_str }, "description": description })
-}
-fn create_enum_prop(enum_values: Vec<&str>, default_value: &str, description: &str) -> Value {
-    json!({ "type": "string", "enum": enum_values, "default": default_value, "description": description })
-}
-
 const MCP_PATH_GUIDANCE: &str = "IMPORTANT: Paths should be absolute or tilde-expanded (~/...). The server will resolve them against its configured FILES_ROOT if relative, but absolute/tilde is preferred for clarity.";
+const SUMMARIZE_DESCRIPTION: &str = "If the result is large, return a summary written by the client's model (MCP sampling) plus a raw_handle for fetch_raw_output instead of the full result. Ignored if the client does not support sampling.";
 
 fn value_to_map(value: Value) -> Map<String, Value> {
     match value {
@@ -52,110 +40,124 @@ fn create_tool_input_schema(
     )
 }
 
+// The properties and required list of a tool's parameter struct, so the advertised schema is the
+// one its arguments are deserialized with. The `///` comments on the fields are the descriptions;
+// `path_fields` get MCP_PATH_GUIDANCE appended.
+fn derived_props<T: JsonSchema>(path_fields: &[&str]) -> (Vec<String>, HashMap<String, Value>) {
+    let generator = SchemaSettings::draft07().with(|s| {
+        s.inline_subschemas = true;
+        s.option_add_null_type = false;
+    }).into_generator();
+    let root = serde_json::to_value(generator.into_root_schema_for::<T>()).unwrap_or_default();
+    let mut props: HashMap<String, Value> = root.get("properties").and_then(Value::as_object).cloned().unwrap_or_default().into_iter().collect();
+    for (name, prop) in props.iter_mut() {
+        let Some(prop) = prop.as_object_mut() else { continue };
+        // An Option field's default carries no information.
+        if prop.get("default") == Some(&Value::Null) { prop.remove("default"); }
+        if path_fields.contains(&name.as_str()) {
+            let description = prop.get("description").and_then(Value::as_str).unwrap_or_default();
+            prop.insert("description".to_string(), json!(format!("{} {}", description, MCP_PATH_GUIDANCE).trim_start()));
+        }
+    }
+    let required = root.get("required").and_then(Value::as_array).map(|r| r.iter().filter_map(|v| v.as_str().map(str::to_string)).collect()).unwrap_or_default();
+    (required, props)
+}
+
+fn derived_schema<T: JsonSchema>(path_fields: &[&str]) -> ToolInputSchema {
+    let (required, props) = derived_props::<T>(path_fields);
+    create_tool_input_schema(required, props)
+}
+
+// summarize is read by the handler, not by the tool, so it is not on the parameter struct.
+fn derived_schema_with_summarize<T: JsonSchema>(path_fields: &[&str]) -> ToolInputSchema {
+    let (required, mut props) = derived_props::<T>(path_fields);
+    props.insert("summarize".to_string(), json!({ "type": "boolean", "description": SUMMARIZE_DESCRIPTION, "default": false }));
+    create_tool_input_schema(required, props)
+}
+
+/// The schema of a custom tool from its configured JSON Schema; only `properties` and `required` are kept.
+pub fn schema_from_json(schema: Option<&Value>) -> ToolInputSchema {
+    let props = schema.and_then(|s| s.get("properties")).and_then(Value::as_object).map(|p| p.clone().into_iter().collect()).unwrap_or_default();
+    let required = schema.and_then(|s| s.get("required")).and_then(Value::as_array).map(|r| r.iter().filter_map(|v| v.as_str().map(str::to_string)).collect()).unwrap_or_default();
+    create_tool_input_schema(required, props)
+}
+
 // --- MCP Tool Schemas ---
 pub fn get_mcp_config_schema() -> ToolInputSchema {
     create_tool_input_schema(vec![], HashMap::new())
 }
 
+pub fn validate_config_mcp_schema() -> ToolInputSchema {
+    create_tool_input_schema(vec![], HashMap::new())
+}
+
+pub fn use_profile_mcp_schema() -> ToolInputSchema {
+    derived_schema::<config::UseProfileParamsMCP>(&[])
+}
+
+pub fn get_config_schema_mcp_schema() -> ToolInputSchema {
+    create_tool_input_schema(vec![], HashMap::new())
+}
+
+pub fn show_effective_config_mcp_schema() -> ToolInputSchema {
+    create_tool_input_schema(vec![], HashMap::new())
+}
+
+pub fn set_active_workspace_mcp_schema() -> ToolInputSchema {
+    derived_schema::<config::SetActiveWorkspaceParamsMCP>(&[])
+}
+
+pub fn export_config_mcp_schema() -> ToolInputSchema {
+    derived_schema::<config::ExportConfigParamsMCP>(&[])
+}
+
+pub fn import_config_mcp_schema() -> ToolInputSchema {
+    derived_schema::<config::ImportConfigParamsMCP>(&[])
+}
+
 pub fn read_file_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("path".to_string(), create_prop("string", &format!("Path to the file or URL. {}", MCP_PATH_GUIDANCE)));
-    props.insert("is_url".to_string(), create_prop_with_default_bool("boolean", "True if 'path' is a URL.", false));
-    props.insert("offset".to_string(), create_prop_with_default_int("integer", "Line offset for text files.", 0));
-    props.insert("length".to_string(), json!({"type": "integer", "description": "Max lines to read for text files. Server default if not provided."}));
-    let req = vec!["path".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<filesystem::ReadFileParamsMCP>(&["path"])
 }
 
 pub fn write_file_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("path".to_string(), create_prop("string", &format!("File path. {}", MCP_PATH_GUIDANCE)));
-    props.insert("content".to_string(), create_prop("string", "Content to write."));
-    props.insert("mode".to_string(), create_enum_prop(vec!["rewrite", "append"], "rewrite", "Write mode."));
-    let req = vec!["path".to_string(), "content".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<filesystem::WriteFileParamsMCP>(&["path"])
 }
 
 pub fn create_directory_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("path".to_string(), create_prop("string", &format!("Directory path to create. {}", MCP_PATH_GUIDANCE)));
-    let req = vec!["path".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<filesystem::CreateDirectoryParamsMCP>(&["path"])
 }
 
 pub fn list_directory_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("path".to_string(), create_prop("string", &format!("Directory path to list. {}", MCP_PATH_GUIDANCE)));
-    let req = vec!["path".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<filesystem::ListDirectoryParamsMCP>(&["path"])
 }
 
 pub fn move_file_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("source".to_string(), create_prop("string", &format!("Source path. {}", MCP_PATH_GUIDANCE)));
-    props.insert("destination".to_string(), create_prop("string", &format!("Destination path. {}", MCP_PATH_GUIDANCE)));
-    let req = vec!["source".to_string(), "destination".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<filesystem::MoveFileParamsMCP>(&["source", "destination"])
 }
 
 pub fn get_file_info_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("path".to_string(), create_prop("string", &format!("File/directory path. {}", MCP_PATH_GUIDANCE)));
-    let req = vec!["path".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<filesystem::GetFileInfoParamsMCP>(&["path"])
 }
 
 pub fn read_multiple_files_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("paths".to_string(), create_array_prop("string", &format!("Array of file paths. {}", MCP_PATH_GUIDANCE)));
-    let req = vec!["paths".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<filesystem::ReadMultipleFilesParamsMCP>(&["paths"])
 }
 
 pub fn search_files_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("path".to_string(), create_prop("string", &format!("Root path for search. {}", MCP_PATH_GUIDANCE)));
-    props.insert("pattern".to_string(), create_prop("string", "Search pattern for file/dir names."));
-    props.insert("timeoutMs".to_string(), json!({"type": "integer", "description": "Timeout in ms. Default 30000."}));
-    props.insert("recursive".to_string(), create_prop_with_default_bool("boolean", "Search recursively.", true));
-    props.insert("max_depth".to_string(), create_prop_with_default_int("integer", "Max recursion depth.", 10));
-    let req = vec!["path".to_string(), "pattern".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<filesystem::SearchFilesParamsMCP>(&["path"])
 }
 
 
 pub fn search_code_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("pattern".to_string(), create_prop("string", "Ripgrep search pattern."));
-    props.insert("path".to_string(), create_prop_with_default_str("string", &format!("Directory to search. Default: FILES_ROOT. {}", MCP_PATH_GUIDANCE), "."));
-    props.insert("fixed_strings".to_string(), create_prop_with_default_bool("boolean", "Treat pattern as literal.", false));
-    props.insert("ignore_case".to_string(), create_prop_with_default_bool("boolean", "Case-insensitive search.", false));
-    props.insert("case_sensitive".to_string(), create_prop_with_default_bool("boolean", "Case-sensitive search.", false));
-    props.insert("line_numbers".to_string(), create_prop_with_default_bool("boolean", "Include line numbers.", true));
-    props.insert("context_lines".to_string(), create_prop_with_default_int("integer", "Context lines around match.", 0));
-    props.insert("file_pattern".to_string(), json!({"type": "string", "description": "Glob to filter files (e.g., \"*.rs\")."}));
-    props.insert("max_depth".to_string(), json!({"type": "integer", "description": "Max search depth."}));
-    props.insert("max_results".to_string(), create_prop_with_default_int("integer", "Max matches to return.", 1000));
-    props.insert("include_hidden".to_string(), create_prop_with_default_bool("boolean", "Search hidden files/dirs.", false));
-    props.insert("timeoutMs".to_string(), json!({"type": "integer", "description": "Timeout in ms. Default 30000."}));
-    let req = vec!["pattern".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema_with_summarize::<ripgrep::SearchCodeParamsMCP>(&["path"])
 }
 
 pub fn execute_command_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("command".to_string(), create_prop("string", "Command to execute."));
-    props.insert("timeout_ms".to_string(), create_prop_with_default_int("integer", "Timeout for initial output (ms).", 1000));
-    props.insert("shell".to_string(), json!({"type": "string", "description": "Specific shell (e.g., bash, powershell). Server default if not set."}));
-    let req = vec!["command".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema_with_summarize::<terminal::ExecuteCommandParamsMCP>(&[])
 }
 
 pub fn force_terminate_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("session_id".to_string(), create_prop("string", "ID of command session to terminate."));
-    let req = vec!["session_id".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<terminal::ForceTerminateParamsMCP>(&[])
 }
 
 pub fn list_sessions_mcp_schema() -> ToolInputSchema {
@@ -163,29 +165,137 @@ pub fn list_sessions_mcp_schema() -> ToolInputSchema {
 }
 
 pub fn read_session_output_status_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("session_id".to_string(), create_prop("string", "ID of command session."));
-    let req = vec!["session_id".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema_with_summarize::<terminal::ReadOutputStatusParamsMCP>(&[])
 }
 
-pub fn list_processes_mcp_schema() -> ToolInputSchema {
+pub fn batch_tools_mcp_schema() -> ToolInputSchema {
+    derived_schema::<BatchToolsParamsMCP>(&[])
+}
+
+pub fn fetch_raw_output_mcp_schema() -> ToolInputSchema {
+    derived_schema::<FetchRawOutputParamsMCP>(&[])
+}
+
+pub fn get_system_info_mcp_schema() -> ToolInputSchema {
+    create_tool_input_schema(vec![], HashMap::new())
+}
+
+pub fn get_server_stats_mcp_schema() -> ToolInputSchema {
     create_tool_input_schema(vec![], HashMap::new())
 }
 
+pub fn run_script_mcp_schema() -> ToolInputSchema {
+    derived_schema::<script::RunScriptParamsMCP>(&[])
+}
+
+pub fn get_job_status_mcp_schema() -> ToolInputSchema {
+    derived_schema::<jobs::JobIdParamsMCP>(&[])
+}
+
+pub fn cancel_job_mcp_schema() -> ToolInputSchema {
+    derived_schema::<jobs::JobIdParamsMCP>(&[])
+}
+
+pub fn list_jobs_mcp_schema() -> ToolInputSchema {
+    derived_schema::<jobs::ListJobsParamsMCP>(&[])
+}
+
+pub fn snapshot_workspace_mcp_schema() -> ToolInputSchema {
+    derived_schema::<snapshot::SnapshotWorkspaceParamsMCP>(&["path"])
+}
+
+pub fn restore_snapshot_mcp_schema() -> ToolInputSchema {
+    derived_schema::<snapshot::RestoreSnapshotParamsMCP>(&[])
+}
+
+pub fn list_snapshots_mcp_schema() -> ToolInputSchema {
+    derived_schema::<snapshot::ListSnapshotsParamsMCP>(&["path"])
+}
+
+pub fn find_repo_root_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::FindRepoRootParamsMCP>(&["path"])
+}
+
+pub fn git_status_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitStatusParamsMCP>(&["path"])
+}
+
+pub fn git_diff_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitDiffParamsMCP>(&["path"])
+}
+
+pub fn git_add_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitAddParamsMCP>(&[])
+}
+
+pub fn git_commit_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitCommitParamsMCP>(&["path"])
+}
+
+pub fn git_log_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitLogParamsMCP>(&["path"])
+}
+
+pub fn git_blame_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitBlameParamsMCP>(&["path"])
+}
+
+pub fn git_branch_list_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitBranchListParamsMCP>(&["path"])
+}
+
+pub fn git_create_branch_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitCreateBranchParamsMCP>(&["path"])
+}
+
+pub fn git_checkout_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitCheckoutParamsMCP>(&["path"])
+}
+
+pub fn git_stage_hunks_mcp_schema() -> ToolInputSchema {
+    derived_schema::<git::GitStageHunksParamsMCP>(&["path"])
+}
+
+pub fn query_audit_log_mcp_schema() -> ToolInputSchema {
+    derived_schema::<audit::QueryAuditLogParamsMCP>(&[])
+}
+
+pub fn list_processes_mcp_schema() -> ToolInputSchema {
+    derived_schema::<process::ListProcessesParamsMCP>(&[])
+}
+
 pub fn kill_process_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("pid".to_string(), create_prop("integer", "Process ID (PID) to terminate."));
-    let req = vec!["pid".to_string()];
-    create_tool_input_schema(req, props)
+    derived_schema::<process::KillProcessParamsMCP>(&[])
+}
+
+pub fn monitor_process_mcp_schema() -> ToolInputSchema {
+    derived_schema::<process::MonitorProcessParamsMCP>(&[])
+}
+
+pub fn set_process_priority_mcp_schema() -> ToolInputSchema {
+    derived_schema::<process::SetProcessPriorityParamsMCP>(&[])
+}
+
+pub fn watch_process_mcp_schema() -> ToolInputSchema {
+    derived_schema::<process::WatchProcessParamsMCP>(&[])
+}
+
+pub fn list_ports_mcp_schema() -> ToolInputSchema {
+    derived_schema::<process::ListPortsParamsMCP>(&[])
+}
+
+pub fn get_process_tree_mcp_schema() -> ToolInputSchema {
+    derived_schema::<process::GetProcessTreeParamsMCP>(&[])
+}
+
+pub fn kill_processes_matching_mcp_schema() -> ToolInputSchema {
+    derived_schema::<process::KillProcessesMatchingParamsMCP>(&[])
+}
+
+pub fn get_process_details_mcp_schema() -> ToolInputSchema {
+    derived_schema::<process::GetProcessDetailsParamsMCP>(&[])
 }
 
 pub fn edit_block_mcp_schema() -> ToolInputSchema {
-    let mut props = HashMap::new();
-    props.insert("file_path".to_string(), create_prop("string", &format!("File path. {}", MCP_PATH_GUIDANCE)));
-    props.insert("old_string".to_string(), create_prop("string", "Exact string to replace."));
-    props.insert("new_string".to_string(), create_prop("string", "String to replace with."));
-    props.insert("expected_replacements".to_string(), create_prop_with_default_int("integer", "Expected number of replacements (0 for all).", 1));
-    let req = vec!["file_path".to_string(), "old_string".to_string(), "new_string".to_string()];
-    create_tool_input_schema(req, props)
-}
\ No newline at end of file
+    derived_schema::<edit::EditBlockParamsMCP>(&["file_path"])
+}
@@ -0,0 +1,375 @@
+use crate::config::{Config, TransportMode};
+use crate::error::AppError;
+use crate::mcp::downstream::{DownstreamHub, DownstreamServerStatusMCP};
+use crate::mcp::handler::EnhancedServerHandler;
+use crate::mcp::notifications::{forward_notifications_to_runtime, McpNotifier};
+use crate::mcp::protocol::ProtocolVersion;
+use crate::mcp::McpServerLaunchParams;
+use crate::shutdown::Shutdown;
+
+use rust_mcp_sdk::McpServer;
+use rust_mcp_sdk::mcp_server::{server_runtime, ServerRuntime as McpServerRuntime};
+use rust_mcp_sdk::error::McpSdkError;
+use rust_mcp_schema::{InitializeResult as McpInitializeResult, Implementation as McpImplementation, ServerCapabilities as McpServerCapabilities, ServerCapabilitiesResources as McpServerCapabilitiesResources, ServerCapabilitiesTools as McpServerCapabilitiesTools};
+use rust_mcp_transport::{StdioTransport as McpStdioTransport, TransportOptions as McpTransportOptions};
+use serde::Serialize;
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard};
+use tauri::{Emitter, Manager};
+use tokio::sync::Mutex as TokioMutex;
+use tokio::time::{timeout, Duration};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// Webview event carrying an McpServerStatus whenever a transport starts or stops.
+pub const MCP_SERVER_STATUS_EVENT: &str = "mcp_server_status";
+// How long a stopping transport may take to close its sessions before its task is aborted.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn get_mcp_server_details(_app_config: &Config) -> McpInitializeResult {
+    McpInitializeResult {
+        server_info: McpImplementation {
+            name: "mcp-rg-editor-tauri-hosted".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        capabilities: McpServerCapabilities {
+            tools: Some(McpServerCapabilitiesTools { list_changed: Some(true) }),
+            resources: Some(McpServerCapabilitiesResources { list_changed: None, subscribe: Some(true) }),
+            prompts: Some(Default::default()),
+            completions: Some(Default::default()),
+            logging: Some(Default::default()),
+            ..Default::default()
+        },
+        meta: None,
+        instructions: Some(
+            "MCP Server hosted within Tauri. Tools interact with local system via Tauri plugins.".to_string()
+        ),
+        // initialize answers with the version negotiated with each client.
+        protocol_version: ProtocolVersion::LATEST.as_str().to_string(),
+    }
+}
+
+fn map_mcp_sdk_error_sync(err: McpSdkError) -> anyhow::Error {
+    anyhow::anyhow!("MCP SDK Error: {:?}", err)
+}
+
+// Logs why a transport is not running and hands the message on for its status.
+fn failed(message: String) -> Result<(), String> {
+    tracing::error!("{}", message);
+    Err(message)
+}
+
+/// Runs one MCP transport until it shuts down or `stop` is cancelled. Every transport shares the
+/// same handler, so clients on different transports see the same command sessions, watches and
+/// subscriptions.
+#[cfg_attr(not(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server")), allow(unused_variables))]
+async fn run_mcp_transport(transport: TransportMode, mcp_launch_params: McpServerLaunchParams, mcp_server_details: McpInitializeResult, mcp_handler: EnhancedServerHandler, mcp_notifier: Arc<McpNotifier>, stop: CancellationToken) -> Result<(), String> {
+    match transport {
+        #[cfg(feature = "mcp-stdio-server")]
+        TransportMode::Stdio => {
+            tracing::info!("Starting MCP server with STDIO transport.");
+            let mcp_transport_opts = McpTransportOptions::default();
+            match McpStdioTransport::new(mcp_transport_opts) {
+                Ok(transport) => {
+                    let mcp_server_runtime: Arc<McpServerRuntime> = Arc::new(server_runtime::create_server(mcp_server_details, transport, mcp_handler));
+                    tauri::async_runtime::spawn(forward_notifications_to_runtime(mcp_server_runtime.clone(), mcp_notifier.subscribe(), None));
+                    if let Err(e) = mcp_server_runtime.start().await.map_err(map_mcp_sdk_error_sync) {
+                        failed(format!("MCP STDIO Server failed to start or shut down with error: {:?}", e))
+                    } else {
+                        tracing::info!("MCP STDIO Server shut down.");
+                        Ok(())
+                    }
+                }
+                Err(e) => failed(format!("Failed to create MCP StdioTransport: {}", e)),
+            }
+        }
+        #[cfg(feature = "mcp-sse-server")]
+        TransportMode::Sse => {
+            let (sse_options, secret_names) = {
+                let cfg = mcp_launch_params.config_state.snapshot();
+                (crate::mcp::sse_server::SseServerOptions {
+                    host: cfg.mcp_sse_host.clone().unwrap_or_else(|| "127.0.0.1".to_string()),
+                    port: cfg.mcp_sse_port.unwrap_or(3030),
+                    tls: match crate::mcp::listener::TlsOptions::from_config(&cfg, &mcp_launch_params.app_handle) {
+                        Ok(tls) => tls,
+                        Err(e) => return failed(format!("MCP SSE Server not started: {}", e)),
+                    },
+                    cors: crate::mcp::listener::CorsOptions::from_config(&cfg),
+                    stop,
+                }, cfg.secret_names.clone())
+            };
+            let mcp_auth = Arc::new(crate::mcp::auth::Authenticator::load(secret_names).await);
+            tracing::info!("Starting MCP server with SSE transport on {}:{}", sse_options.host, sse_options.port);
+            if let Err(e) = crate::mcp::sse_server::serve(sse_options, mcp_server_details, mcp_handler, mcp_notifier.clone(), mcp_auth).await {
+                failed(format!("MCP SSE Server failed to start or shut down with error: {:?}", e))
+            } else {
+                tracing::info!("MCP SSE Server shut down.");
+                Ok(())
+            }
+        }
+        #[cfg(feature = "mcp-ws-server")]
+        TransportMode::WebSocket => {
+            let (ws_options, secret_names) = {
+                let cfg = mcp_launch_params.config_state.snapshot();
+                (crate::mcp::ws_server::WsServerOptions {
+                    host: cfg.mcp_ws_host.clone().unwrap_or_else(|| "127.0.0.1".to_string()),
+                    port: cfg.mcp_ws_port.unwrap_or(3031),
+                    path: cfg.mcp_ws_path.clone().unwrap_or_else(|| "/mcp".to_string()),
+                    tls: match crate::mcp::listener::TlsOptions::from_config(&cfg, &mcp_launch_params.app_handle) {
+                        Ok(tls) => tls,
+                        Err(e) => return failed(format!("MCP WebSocket Server not started: {}", e)),
+                    },
+                    cors: crate::mcp::listener::CorsOptions::from_config(&cfg),
+                    stop,
+                }, cfg.secret_names.clone())
+            };
+            let mcp_auth = Arc::new(crate::mcp::auth::Authenticator::load(secret_names).await);
+            tracing::info!("Starting MCP server with WebSocket transport on {}:{}{}", ws_options.host, ws_options.port, ws_options.path);
+            if let Err(e) = crate::mcp::ws_server::serve(ws_options, mcp_server_details, mcp_handler, mcp_notifier.clone(), mcp_auth).await {
+                failed(format!("MCP WebSocket Server failed to start or shut down with error: {:?}", e))
+            } else {
+                tracing::info!("MCP WebSocket Server shut down.");
+                Ok(())
+            }
+        }
+        #[cfg(feature = "mcp-http-server")]
+        TransportMode::Http => {
+            let (http_options, secret_names) = {
+                let cfg = mcp_launch_params.config_state.snapshot();
+                (crate::mcp::http_server::HttpServerOptions {
+                    host: cfg.mcp_http_host.clone().unwrap_or_else(|| "127.0.0.1".to_string()),
+                    port: cfg.mcp_http_port.unwrap_or(3032),
+                    path: cfg.mcp_http_path.clone().unwrap_or_else(|| "/mcp".to_string()),
+                    tls: match crate::mcp::listener::TlsOptions::from_config(&cfg, &mcp_launch_params.app_handle) {
+                        Ok(tls) => tls,
+                        Err(e) => return failed(format!("MCP HTTP Server not started: {}", e)),
+                    },
+                    cors: crate::mcp::listener::CorsOptions::from_config(&cfg),
+                    stop,
+                }, cfg.secret_names.clone())
+            };
+            let mcp_auth = Arc::new(crate::mcp::auth::Authenticator::load(secret_names).await);
+            tracing::info!("Starting MCP server with Streamable HTTP transport on {}:{}{}", http_options.host, http_options.port, http_options.path);
+            if let Err(e) = crate::mcp::http_server::serve(http_options, mcp_server_details, mcp_handler, mcp_notifier.clone(), mcp_auth).await {
+                failed(format!("MCP Streamable HTTP Server failed to start or shut down with error: {:?}", e))
+            } else {
+                tracing::info!("MCP Streamable HTTP Server shut down.");
+                Ok(())
+            }
+        }
+        // Reachable only for transports whose feature is not compiled in.
+        #[allow(unreachable_patterns)]
+        _ => {
+            let feature = match transport {
+                TransportMode::Stdio => "mcp-stdio-server",
+                TransportMode::Sse => "mcp-sse-server",
+                TransportMode::WebSocket => "mcp-ws-server",
+                TransportMode::Http => "mcp-http-server",
+            };
+            failed(format!("MCP_TRANSPORT includes '{}' but the '{}' feature is not enabled in Cargo.toml.", transport.as_str(), feature))
+        }
+    }
+}
+
+fn listen_address(config: &Config, transport: &TransportMode) -> Option<String> {
+    let host = |host: &Option<String>| host.clone().unwrap_or_else(|| "127.0.0.1".to_string());
+    match transport {
+        TransportMode::Stdio => None,
+        TransportMode::Sse => Some(format!("{}:{}/sse", host(&config.mcp_sse_host), config.mcp_sse_port.unwrap_or(3030))),
+        TransportMode::WebSocket => Some(format!("{}:{}{}", host(&config.mcp_ws_host), config.mcp_ws_port.unwrap_or(3031), config.mcp_ws_path.as_deref().unwrap_or("/mcp"))),
+        TransportMode::Http => Some(format!("{}:{}{}", host(&config.mcp_http_host), config.mcp_http_port.unwrap_or(3032), config.mcp_http_path.as_deref().unwrap_or("/mcp"))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportState {
+    Running,
+    // Shut down without an error, e.g. the STDIO client closed stdin.
+    Stopped,
+    // Did not start (port in use, bad TLS files, ...) or ended with an error; see `error`.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransportStatus {
+    pub transport: &'static str,
+    // host:port and path; None for STDIO.
+    pub address: Option<String>,
+    pub state: TransportState,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct McpServerStatus {
+    // True while at least one transport runs.
+    pub running: bool,
+    // Restarts since the app started.
+    pub restarts: u64,
+    // Network transports stopped by pause() until the next restart.
+    pub paused: bool,
+    pub transports: Vec<TransportStatus>,
+    // The downstream_servers whose tools are re-exported.
+    pub downstream: Vec<DownstreamServerStatusMCP>,
+}
+
+struct TransportRun {
+    id: u64,
+    transport: TransportMode,
+    stop: CancellationToken,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct StatusBoard {
+    restarts: u64,
+    paused: bool,
+    next_run_id: u64,
+    runs: Vec<(u64, TransportStatus)>,
+}
+
+/// Owns the running MCP transports, so the UI can restart them with new settings without
+/// restarting the app. The handler is created once and kept, so command sessions, watches,
+/// subscriptions and metrics survive a restart.
+pub struct McpServerControl {
+    launch_params: McpServerLaunchParams,
+    handler: EnhancedServerHandler,
+    notifier: Arc<McpNotifier>,
+    // Held across a whole restart, so two restarts cannot interleave.
+    runs: TokioMutex<Vec<TransportRun>>,
+    board: StdMutex<StatusBoard>,
+}
+
+impl McpServerControl {
+    pub fn new(launch_params: McpServerLaunchParams, notifier: Arc<McpNotifier>) -> Arc<Self> {
+        let handler = EnhancedServerHandler::new(launch_params.app_handle.clone(), launch_params.config_state.clone());
+        Arc::new(Self { launch_params, handler, notifier, runs: TokioMutex::default(), board: StdMutex::default() })
+    }
+
+    fn board(&self) -> MutexGuard<'_, StatusBoard> {
+        self.board.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts every transport in config.mcp_transports, STDIO included. Called once at launch.
+    pub async fn start(self: &Arc<Self>) {
+        tracing::info!("Attempting to start MCP server...");
+        let mut runs = self.runs.lock().await;
+        match self.spawn_configured(&mut runs, true) {
+            Ok(0) => tracing::info!("MCP_TRANSPORT is 'disabled'. MCP server will not be started."),
+            Ok(_) => {}
+            Err(e) => error!("MCP server not started: {}", e),
+        }
+        drop(runs);
+        self.publish();
+    }
+
+    /// Stops the network transports, closing their sessions and releasing their ports, then starts
+    /// the ones now in config.mcp_transports. A STDIO transport keeps running: its client owns this
+    /// process's stdin and could not reconnect. Also resumes a paused server.
+    pub async fn restart(self: &Arc<Self>) -> Result<McpServerStatus, AppError> {
+        if self.launch_params.app_handle.try_state::<Arc<Shutdown>>().is_some_and(|s| s.is_started()) {
+            return Err(AppError::ShuttingDown("The MCP server is shutting down and cannot be restarted".to_string()));
+        }
+        let mut runs = self.runs.lock().await;
+        info!("Restarting MCP network transports");
+        self.stop_network_transports(&mut runs).await;
+        {
+            let mut board = self.board();
+            board.restarts += 1;
+            board.paused = false;
+        }
+        let started = self.spawn_configured(&mut runs, false);
+        drop(runs);
+        self.publish();
+        started?;
+        Ok(self.status())
+    }
+
+    /// Stops the network transports like restart() but leaves them stopped until the next
+    /// restart. STDIO keeps running for the same reason as in restart().
+    pub async fn pause(self: &Arc<Self>) -> McpServerStatus {
+        let mut runs = self.runs.lock().await;
+        info!("Pausing MCP network transports");
+        self.stop_network_transports(&mut runs).await;
+        self.board().paused = true;
+        drop(runs);
+        self.publish();
+        self.status()
+    }
+
+    /// The handler every transport shares.
+    pub fn handler(&self) -> &EnhancedServerHandler {
+        &self.handler
+    }
+
+    pub fn status(&self) -> McpServerStatus {
+        let board = self.board();
+        let transports: Vec<TransportStatus> = board.runs.iter().map(|(_, status)| status.clone()).collect();
+        let downstream = self.launch_params.app_handle.try_state::<Arc<DownstreamHub>>().map(|hub| hub.status()).unwrap_or_default();
+        McpServerStatus { running: transports.iter().any(|t| t.state == TransportState::Running), restarts: board.restarts, paused: board.paused, transports, downstream }
+    }
+
+    fn publish(&self) {
+        self.launch_params.app_handle.emit(MCP_SERVER_STATUS_EVENT, self.status()).unwrap_or_else(|e| warn!("Emit mcp_server_status failed: {}", e));
+    }
+
+    fn spawn_configured(self: &Arc<Self>, runs: &mut Vec<TransportRun>, include_stdio: bool) -> Result<usize, AppError> {
+        let (transports, details) = {
+            let cfg = self.launch_params.config_state.snapshot();
+            let transports: Vec<(TransportMode, Option<String>)> = cfg.mcp_transports.iter()
+                .filter(|t| include_stdio || **t != TransportMode::Stdio)
+                .map(|t| (t.clone(), listen_address(&cfg, t)))
+                .collect();
+            (transports, get_mcp_server_details(&cfg))
+        };
+        let count = transports.len();
+        for (transport, address) in transports {
+            runs.push(self.spawn_transport(transport, address, details.clone()));
+        }
+        Ok(count)
+    }
+
+    fn spawn_transport(self: &Arc<Self>, transport: TransportMode, address: Option<String>, details: McpInitializeResult) -> TransportRun {
+        let id = {
+            let mut board = self.board();
+            board.next_run_id += 1;
+            let id = board.next_run_id;
+            board.runs.push((id, TransportStatus { transport: transport.as_str(), address, state: TransportState::Running, error: None }));
+            id
+        };
+        let stop = CancellationToken::new();
+        let (control, run_transport, run_stop) = (self.clone(), transport.clone(), stop.clone());
+        let task = tauri::async_runtime::spawn(async move {
+            let result = run_mcp_transport(run_transport, control.launch_params.clone(), details, control.handler.clone(), control.notifier.clone(), run_stop).await;
+            control.transport_exited(id, result);
+        });
+        TransportRun { id, transport, stop, task }
+    }
+
+    fn transport_exited(&self, id: u64, result: Result<(), String>) {
+        {
+            let mut board = self.board();
+            let Some((_, status)) = board.runs.iter_mut().find(|(run, _)| *run == id) else { return };
+            match result {
+                Ok(()) => status.state = TransportState::Stopped,
+                Err(e) => {
+                    status.state = TransportState::Failed;
+                    status.error = Some(e);
+                }
+            }
+        }
+        self.publish();
+    }
+
+    async fn stop_network_transports(&self, runs: &mut Vec<TransportRun>) {
+        let (stopping, kept): (Vec<_>, Vec<_>) = runs.drain(..).partition(|run| run.transport != TransportMode::Stdio);
+        *runs = kept;
+        stopping.iter().for_each(|run| run.stop.cancel());
+        for mut run in stopping {
+            if timeout(STOP_TIMEOUT, &mut run.task).await.is_err() {
+                warn!(transport = run.transport.as_str(), "MCP transport did not stop in time; aborting it");
+                run.task.abort();
+            }
+            self.board().runs.retain(|(id, _)| *id != run.id);
+        }
+    }
+}
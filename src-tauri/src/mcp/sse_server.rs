@@ -0,0 +1,159 @@
+use crate::mcp::auth::{routes, Authenticator, Identity};
+use crate::mcp::duplex_session::{message_to_line, outgoing_line, DuplexSession};
+use crate::mcp::handler::EnhancedServerHandler;
+use crate::mcp::listener::{self, CorsOptions, TlsOptions};
+use crate::mcp::metrics::{self, OpenSession};
+use crate::mcp::notifications::McpNotifier;
+
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use futures_util::stream::{self, StreamExt};
+use rust_mcp_sdk::McpServer;
+use rust_mcp_schema::InitializeResult;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::{mpsc, Mutex as TokioMutex};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, error, info, warn};
+
+// The endpoints the SDK's SSE server used, so existing client configs keep working.
+const SSE_PATH: &str = "/sse";
+const MESSAGES_PATH: &str = "/messages";
+const STREAM_CHANNEL_CAPACITY: usize = 256;
+
+pub struct SseServerOptions {
+    pub host: String,
+    pub port: u16,
+    pub tls: Option<TlsOptions>,
+    pub cors: CorsOptions,
+    // Cancelled to stop the server: open sessions are closed and the port is released.
+    pub stop: CancellationToken,
+}
+
+struct SseSession {
+    input: TokioMutex<DuplexStream>,
+    identity: Identity,
+    tasks: Vec<tauri::async_runtime::JoinHandle<()>>,
+    _open: OpenSession,
+    _calls_guard: DropGuard,
+}
+
+impl Drop for SseSession {
+    fn drop(&mut self) {
+        self.tasks.iter().for_each(|t| t.abort());
+    }
+}
+
+type Sessions = Arc<StdMutex<HashMap<String, Arc<SseSession>>>>;
+
+// Ends the session when its event stream is dropped, i.e. when the client disconnects.
+struct StreamGuard {
+    sessions: Sessions,
+    session_id: String,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        if let Ok(mut sessions) = self.sessions.lock() { sessions.remove(&self.session_id); }
+        info!(session_id = %self.session_id, "MCP SSE client disconnected");
+    }
+}
+
+#[derive(Clone)]
+struct SseState {
+    sessions: Sessions,
+    server_details: Arc<InitializeResult>,
+    handler: EnhancedServerHandler,
+    notifier: Arc<McpNotifier>,
+    auth: Arc<Authenticator>,
+    cors: Arc<CorsOptions>,
+}
+
+/// Serves the HTTP+SSE transport (protocol 2024-11-05): GET /sse opens a session's event stream,
+/// whose first `endpoint` event names the URL to POST client messages to.
+pub async fn serve(options: SseServerOptions, server_details: InitializeResult, handler: EnhancedServerHandler, notifier: Arc<McpNotifier>, auth: Arc<Authenticator>) -> anyhow::Result<()> {
+    let oauth = auth.has_oauth_clients().then(|| routes::oauth_router(auth.clone(), options.tls.is_some()));
+    let monitoring = metrics::routes::monitoring_router(handler.clone(), auth.clone(), "sse");
+    let state = SseState { sessions: Arc::default(), server_details: Arc::new(server_details), handler, notifier, auth, cors: Arc::new(options.cors.clone()) };
+    // Dropping a session ends its event stream, which the graceful shutdown waits for.
+    let (sessions, stop) = (state.sessions.clone(), options.stop.clone());
+    let shutdown = async move {
+        stop.cancelled().await;
+        if let Ok(mut sessions) = sessions.lock() { sessions.clear(); }
+    };
+    let mut app = Router::new()
+        .route(SSE_PATH, get(handle_sse))
+        .route(MESSAGES_PATH, post(handle_message))
+        .with_state(state)
+        .merge(monitoring);
+    if let Some(oauth) = oauth { app = app.merge(oauth); }
+    let scheme = if options.tls.is_some() { "https" } else { "http" };
+    info!("MCP SSE server listening on {}://{}:{}{}", scheme, options.host, options.port, SSE_PATH);
+    listener::serve(&options.host, options.port, app, options.tls.as_ref(), &options.cors, shutdown).await
+}
+
+async fn handle_sse(State(state): State<SseState>, headers: HeaderMap) -> Response {
+    if !state.cors.origin_allowed(&headers) { return (StatusCode::FORBIDDEN, "Origin not allowed").into_response(); }
+    let Some(identity) = state.auth.authenticate(routes::authorization_header(&headers)) else { return routes::unauthorized() };
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let client = state.auth.client_identity(&identity, &session_id);
+    let duplex = match DuplexSession::new((*state.server_details).clone(), state.handler.for_client(identity.scope, client), &state.notifier) {
+        Ok(duplex) => duplex,
+        Err(e) => {
+            error!(error = %e, "Failed to start MCP SSE session");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to start MCP session").into_response();
+        }
+    };
+
+    let (tx, rx) = mpsc::channel::<String>(STREAM_CHANNEL_CAPACITY);
+    let runtime = duplex.runtime.clone();
+    let runtime_task = tauri::async_runtime::spawn(async move {
+        if let Err(e) = runtime.start().await { warn!(error = ?e, "MCP SSE session ended with an error"); }
+    });
+    let (output, runtime) = (duplex.output, duplex.runtime.clone());
+    let reader_task = tauri::async_runtime::spawn(async move {
+        let mut lines = BufReader::new(output).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() { continue; }
+            if tx.send(outgoing_line(&runtime, line)).await.is_err() { break; }
+        }
+        debug!("MCP SSE session output closed");
+    });
+
+    info!(session_id = %session_id, client = %identity.label, scope = identity.scope.as_str(), "MCP SSE client connected");
+    let session = Arc::new(SseSession { input: TokioMutex::new(duplex.input), identity, tasks: vec![runtime_task, reader_task, duplex.forwarder], _open: state.handler.metrics().open_session("sse"), _calls_guard: duplex.calls_guard });
+    if let Ok(mut sessions) = state.sessions.lock() { sessions.insert(session_id.clone(), session); }
+
+    let endpoint = Event::default().event("endpoint").data(format!("{}?sessionId={}", MESSAGES_PATH, session_id));
+    let guard = StreamGuard { sessions: state.sessions.clone(), session_id };
+    let messages = stream::unfold((rx, guard), |(mut rx, guard)| async move {
+        let line = rx.recv().await?;
+        Some((Ok::<_, Infallible>(Event::default().event("message").data(line)), (rx, guard)))
+    });
+    let events = stream::once(async move { Ok::<_, Infallible>(endpoint) }).chain(messages);
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+async fn handle_message(State(state): State<SseState>, headers: HeaderMap, Query(query): Query<HashMap<String, String>>, body: String) -> Response {
+    if !state.cors.origin_allowed(&headers) { return (StatusCode::FORBIDDEN, "Origin not allowed").into_response(); }
+    let Some(identity) = state.auth.authenticate(routes::authorization_header(&headers)) else { return routes::unauthorized() };
+    let Some(session_id) = query.get("sessionId") else { return (StatusCode::BAD_REQUEST, "Missing sessionId").into_response() };
+    let session = state.sessions.lock().ok().and_then(|s| s.get(session_id).cloned());
+    let Some(session) = session else { return (StatusCode::NOT_FOUND, "Unknown or closed MCP session").into_response() };
+    if session.identity.label != identity.label {
+        return (StatusCode::FORBIDDEN, "MCP session belongs to a different client").into_response();
+    }
+    let line = message_to_line(&body);
+    let mut input = session.input.lock().await;
+    if input.write_all(line.as_bytes()).await.is_err() || input.write_all(b"\n").await.is_err() {
+        return (StatusCode::GONE, "MCP session is closed").into_response();
+    }
+    // The response arrives on the event stream.
+    StatusCode::ACCEPTED.into_response()
+}
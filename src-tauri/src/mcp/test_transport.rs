@@ -0,0 +1,247 @@
+use crate::config::{Config, ConfigState, DEFAULT_BLOCKED_COMMANDS};
+use crate::error::AppError;
+use crate::mcp::duplex_session::DuplexSession;
+use crate::mcp::handler::EnhancedServerHandler;
+use crate::mcp::notifications::McpNotifier;
+use crate::mcp::protocol::ProtocolVersion;
+use crate::mcp::server_control::get_mcp_server_details;
+use crate::AppRuntime;
+
+use rust_mcp_schema::InitializeResult;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use tauri_plugin_fs::FsExt;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines};
+use tokio::time::{timeout, Duration};
+use tokio_util::sync::DropGuard;
+use tracing::warn;
+
+// Generous, since a call may run a command or a search in the workspace.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Config::load reads FILES_ROOT, so tests running in parallel set it and load one at a time.
+static CONFIG_ENV: StdMutex<()> = StdMutex::new(());
+
+/// An MCP client driving an EnhancedServerHandler in-process, over the duplex transport the
+/// network servers use, so tools/list and tools/call round trips need no real stdio or socket.
+pub struct TestClient {
+    input: DuplexStream,
+    output: Lines<BufReader<DuplexStream>>,
+    next_id: u64,
+    tasks: Vec<tauri::async_runtime::JoinHandle<()>>,
+    _calls_guard: DropGuard,
+}
+
+impl TestClient {
+    /// Starts a session and completes the initialize handshake.
+    pub async fn connect(server_details: InitializeResult, handler: EnhancedServerHandler, notifier: &McpNotifier) -> Result<Self, AppError> {
+        let duplex = DuplexSession::new(server_details, handler, notifier)?;
+        let runtime = duplex.runtime.clone();
+        let runtime_task = tauri::async_runtime::spawn(async move {
+            if let Err(e) = runtime.start().await { warn!(error = ?e, "In-process MCP test session ended with an error"); }
+        });
+        let mut client = Self { input: duplex.input, output: BufReader::new(duplex.output).lines(), next_id: 1, tasks: vec![runtime_task, duplex.forwarder], _calls_guard: duplex.calls_guard };
+        client.request("initialize", json!({
+            "protocolVersion": ProtocolVersion::LATEST.as_str(),
+            "capabilities": {},
+            "clientInfo": { "name": "mcp-rg-editor-test-transport", "version": env!("CARGO_PKG_VERSION") },
+        })).await?;
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await?;
+        Ok(client)
+    }
+
+    /// Sends a request and waits for its response, skipping notifications sent meanwhile. A
+    /// JSON-RPC error response becomes McpSdkError carrying the error object.
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value, AppError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await?;
+        loop {
+            let line = match timeout(RESPONSE_TIMEOUT, self.output.next_line()).await {
+                Err(_) => return Err(AppError::TimeoutError(format!("No response to '{}' within {} s", method, RESPONSE_TIMEOUT.as_secs()))),
+                Ok(Err(e)) => return Err(e.into()),
+                Ok(Ok(None)) => return Err(AppError::McpSdkError(format!("The MCP session closed before answering '{}'", method))),
+                Ok(Ok(Some(line))) => line,
+            };
+            if line.trim().is_empty() { continue; }
+            let message: Value = serde_json::from_str(&line)?;
+            if message.get("id").and_then(Value::as_u64) != Some(id) || message.get("method").is_some() { continue; }
+            if let Some(error) = message.get("error") {
+                return Err(AppError::McpSdkError(format!("'{}' failed: {}", method, error)));
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    /// The names of the tools in tools/list.
+    pub async fn list_tools(&mut self) -> Result<Vec<String>, AppError> {
+        let result = self.request("tools/list", json!({})).await?;
+        let tools = result.get("tools").and_then(Value::as_array).cloned().unwrap_or_default();
+        Ok(tools.iter().filter_map(|t| t.get("name").and_then(Value::as_str).map(str::to_string)).collect())
+    }
+
+    /// The CallToolResult of tools/call, successful or not.
+    pub async fn call_tool(&mut self, name: &str, arguments: Value) -> Result<ToolOutcome, AppError> {
+        let result = self.request("tools/call", json!({ "name": name, "arguments": arguments })).await?;
+        let text = result.get("content").and_then(Value::as_array).into_iter().flatten()
+            .filter_map(|item| item.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>().join("\n");
+        Ok(ToolOutcome { is_error: result.get("isError").and_then(Value::as_bool).unwrap_or(false), text })
+    }
+
+    async fn send(&mut self, message: Value) -> Result<(), AppError> {
+        // serde_json writes a Value on one line, as the transport expects.
+        self.input.write_all(message.to_string().as_bytes()).await?;
+        self.input.write_all(b"\n").await?;
+        Ok(())
+    }
+}
+
+impl Drop for TestClient {
+    fn drop(&mut self) {
+        for task in &self.tasks { task.abort(); }
+    }
+}
+
+/// A tools/call result, reduced to what checks look at.
+#[derive(Debug, Clone)]
+pub struct ToolOutcome {
+    pub is_error: bool,
+    // The text items of the content, one per line.
+    pub text: String,
+}
+
+impl ToolOutcome {
+    /// The text parsed as JSON: built-in tools answer with their serialized result struct,
+    /// failed calls with the JSON-RPC error.
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_str(&self.text).ok()
+    }
+
+    /// The stable error name (`data.error`, e.g. "PATH_NOT_ALLOWED") of a failed call.
+    pub fn error_name(&self) -> Option<String> {
+        if !self.is_error { return None; }
+        self.json()?.pointer("/data/error").and_then(Value::as_str).map(str::to_string)
+    }
+}
+
+/// A mock-runtime app whose config is confined to a new, empty temporary directory, with a
+/// TestClient connected to a handler on it. The directory is removed on drop.
+pub struct TestWorkspace {
+    pub root: PathBuf,
+    pub client: TestClient,
+    // Holds the managed state the handler looks up.
+    _app: tauri::App<AppRuntime>,
+}
+
+impl TestWorkspace {
+    pub async fn start() -> Result<Self, AppError> {
+        let root = std::env::temp_dir().join(format!("mcp-rg-editor-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root)?;
+        let root = dunce::canonicalize(&root)?;
+        let app = tauri::test::mock_builder()
+            .plugin(tauri_plugin_fs::init())
+            .plugin(tauri_plugin_shell::init())
+            .build(tauri::test::mock_context(tauri::test::noop_assets()))
+            .map_err(|e| AppError::TauriApiError(format!("Failed to build the mock app: {}", e)))?;
+        // The mock context has no capabilities, so the FS scope only allows what is added here.
+        app.fs_scope().allow_directory(&root, true).map_err(|e| AppError::TauriApiError(format!("Failed to allow {} in the FS scope: {}", root.display(), e)))?;
+        let mut config = {
+            let _env = CONFIG_ENV.lock().unwrap_or_else(|e| e.into_inner());
+            std::env::set_var("FILES_ROOT", &root);
+            Config::load(app.handle())?
+        };
+        confine_config(&mut config, &root);
+        let config_state = Arc::new(ConfigState::new(config));
+        let notifier = crate::manage_core_state(app.handle(), &config_state);
+        let details = get_mcp_server_details(&config_state.snapshot());
+        let client = TestClient::connect(details, EnhancedServerHandler::new(app.handle().clone(), config_state), &notifier).await?;
+        Ok(Self { root, client, _app: app })
+    }
+}
+
+impl Drop for TestWorkspace {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Narrows the loaded config to the workspace with default policies, so the tests do not
+/// depend on the user's settings, roots, profiles or downstream servers.
+fn confine_config(config: &mut Config, workspace: &Path) {
+    config.files_root = workspace.to_path_buf();
+    config.workspaces.clear();
+    config.active_workspace = None;
+    config.allowed_directories = vec![workspace.to_path_buf()];
+    config.blocked_commands = DEFAULT_BLOCKED_COMMANDS.split(',').map(str::to_string).collect();
+    config.read_only = false;
+    config.disabled_tools.clear();
+    config.tool_name_prefix = None;
+    config.approval_required_tools.clear();
+    config.approval_command_patterns.clear();
+    config.downstream_servers.clear();
+    config.custom_tools.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TestWorkspace, ToolOutcome};
+    use serde_json::{json, Value};
+
+    fn assert_failed_with(outcome: &ToolOutcome, expected: &[&str]) {
+        let name = outcome.error_name();
+        assert!(name.as_deref().is_some_and(|name| expected.contains(&name)), "expected {}, got {:?}: {}", expected.join(" or "), name, outcome.text);
+    }
+
+    fn text_content(outcome: &ToolOutcome) -> Option<String> {
+        outcome.json()?.get("text_content").and_then(Value::as_str).map(str::to_string)
+    }
+
+    #[tokio::test]
+    async fn lists_the_file_and_command_tools() {
+        let mut workspace = TestWorkspace::start().await.expect("test workspace starts");
+        let tools = workspace.client.list_tools().await.expect("tools/list succeeds");
+        for name in ["read_file", "write_file", "edit_block", "execute_command"] {
+            assert!(tools.iter().any(|t| t == name), "{} missing from {:?}", name, tools);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_paths_outside_the_workspace() {
+        let mut workspace = TestWorkspace::start().await.expect("test workspace starts");
+        let outside = workspace.root.parent().expect("the workspace has a parent").join("outside.txt");
+        let relative = workspace.client.call_tool("read_file", json!({ "path": "../outside.txt" })).await.expect("tools/call succeeds");
+        assert_failed_with(&relative, &["PATH_TRAVERSAL", "PATH_NOT_ALLOWED"]);
+        let absolute = workspace.client.call_tool("read_file", json!({ "path": outside.display().to_string() })).await.expect("tools/call succeeds");
+        assert_failed_with(&absolute, &["PATH_TRAVERSAL", "PATH_NOT_ALLOWED"]);
+        let write = workspace.client.call_tool("write_file", json!({ "path": "../outside.txt", "content": "x" })).await.expect("tools/call succeeds");
+        assert_failed_with(&write, &["PATH_TRAVERSAL", "PATH_NOT_ALLOWED"]);
+        assert!(!outside.exists());
+    }
+
+    #[tokio::test]
+    async fn edit_block_applies_expected_replacements() {
+        let mut workspace = TestWorkspace::start().await.expect("test workspace starts");
+        let client = &mut workspace.client;
+        let written = client.call_tool("write_file", json!({ "path": "notes.txt", "content": "alpha\nbeta\nalpha\n" })).await.expect("tools/call succeeds");
+        assert!(!written.is_error, "{}", written.text);
+
+        let ambiguous = client.call_tool("edit_block", json!({ "file_path": "notes.txt", "old_string": "alpha", "new_string": "gamma" })).await.expect("tools/call succeeds");
+        assert_failed_with(&ambiguous, &["EDIT_FAILED"]);
+        let unchanged = client.call_tool("read_file", json!({ "path": "notes.txt" })).await.expect("tools/call succeeds");
+        assert_eq!(text_content(&unchanged).as_deref(), Some("alpha\nbeta\nalpha\n"), "{}", unchanged.text);
+
+        let edited = client.call_tool("edit_block", json!({ "file_path": "notes.txt", "old_string": "alpha", "new_string": "gamma", "expected_replacements": 2 })).await.expect("tools/call succeeds");
+        assert!(!edited.is_error, "{}", edited.text);
+        let read = client.call_tool("read_file", json!({ "path": "notes.txt" })).await.expect("tools/call succeeds");
+        assert_eq!(text_content(&read).as_deref(), Some("gamma\nbeta\ngamma\n"), "{}", read.text);
+    }
+
+    #[tokio::test]
+    async fn execute_command_refuses_blocked_commands() {
+        let mut workspace = TestWorkspace::start().await.expect("test workspace starts");
+        let outcome = workspace.client.call_tool("execute_command", json!({ "command": "sudo true" })).await.expect("tools/call succeeds");
+        assert_failed_with(&outcome, &["COMMAND_BLOCKED"]);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::mcp::policy;
+use crate::utils::log_rotation;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, instrument, warn};
+
+/// The audit command of the entry recording how a tool call ended, written after its `mcp_<tool>` entry under the same correlation id.
+pub const CALL_RESULT_AUDIT_COMMAND: &str = "mcp_call_result";
+const MAX_QUERY_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcomeFilterMCP {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct QueryAuditLogParamsMCP {
+    /// Only entries at or after this time: RFC 3339 (2026-10-13T08:00:00Z) or a UTC date (2026-10-13), which means its midnight.
+    #[serde(default)]
+    pub since: Option<String>,
+    /// Only entries before this time, in the same formats.
+    #[serde(default)]
+    pub until: Option<String>,
+    /// Only calls of this tool, e.g. edit_block. Without it, server events such as mcp_initialize are listed too.
+    #[serde(default)]
+    pub tool: Option<String>,
+    /// Case-insensitive substring of a path argument (path, paths, file_path, source, destination, cwd).
+    #[serde(default, alias = "pathContains")]
+    pub path_contains: Option<String>,
+    /// Only calls that succeeded ("ok") or failed ("error"). Entries without a recorded outcome never match.
+    #[serde(default)]
+    pub outcome: Option<AuditOutcomeFilterMCP>,
+    /// Matching entries to skip, newest first.
+    #[serde(default)]
+    pub offset: usize,
+    /// Entries to return, at most 500.
+    #[serde(default = "default_query_audit_log_limit_mcp")]
+    pub limit: usize,
+}
+fn default_query_audit_log_limit_mcp() -> usize { 50 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditCallOutcomeMCP {
+    pub ok: bool,
+    // The stable error name, e.g. PATH_NOT_ALLOWED.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntryMCP {
+    pub timestamp: String,
+    // mcp_<tool> for tool calls; mcp_initialize, mcp_approval, server_shutdown, ui_* and the like otherwise.
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    // As logged: content, old_string, new_string, command and pattern are already redacted.
+    pub arguments: Value,
+    // None for entries other than tool calls, and for calls logged before outcomes were or still running.
+    pub outcome: Option<AuditCallOutcomeMCP>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueryAuditLogResultMCP {
+    // Newest first.
+    pub entries: Vec<AuditEntryMCP>,
+    pub total_matches: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+    // The current audit log and the rotated segments that were read.
+    pub files_scanned: usize,
+}
+
+struct AuditQuery {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    tool: Option<String>,
+    path_contains: Option<String>,
+    outcome: Option<AuditOutcomeFilterMCP>,
+    // Network clients only see their own entries: the label, or any connection of the same token.
+    client: Option<(String, String)>,
+    // offset + limit: only the newest this many matches are kept while scanning.
+    keep: usize,
+}
+
+/// The newest `keep` matches in log order, and how many matched in total.
+struct MatchWindow {
+    entries: VecDeque<AuditEntryMCP>,
+    keep: usize,
+    total: usize,
+}
+
+impl MatchWindow {
+    /// Returns the match's position among all matches.
+    fn push(&mut self, entry: AuditEntryMCP) -> usize {
+        self.entries.push_back(entry);
+        if self.entries.len() > self.keep { self.entries.pop_front(); }
+        self.total += 1;
+        self.total - 1
+    }
+
+    fn get_mut(&mut self, position: usize) -> Option<&mut AuditEntryMCP> {
+        let evicted = self.total - self.entries.len();
+        position.checked_sub(evicted).and_then(|i| self.entries.get_mut(i))
+    }
+}
+
+struct ParsedLine {
+    timestamp: DateTime<Utc>,
+    timestamp_text: String,
+    client: Option<String>,
+    correlation_id: Option<String>,
+    command: String,
+    arguments: Value,
+}
+
+#[instrument(skip(deps, params), fields(since = ?params.since, until = ?params.until, tool = ?params.tool))]
+pub async fn mcp_query_audit_log(deps: &ToolDependencies, params: QueryAuditLogParamsMCP) -> Result<QueryAuditLogResultMCP, AppError> {
+    let limit = params.limit.clamp(1, MAX_QUERY_LIMIT);
+    let query = AuditQuery {
+        since: params.since.as_deref().map(|s| parse_time(s, "since")).transpose()?,
+        until: params.until.as_deref().map(|s| parse_time(s, "until")).transpose()?,
+        tool: params.tool.filter(|t| !t.is_empty()),
+        path_contains: params.path_contains.filter(|p| !p.is_empty()).map(|p| p.to_lowercase()),
+        outcome: params.outcome,
+        client: deps.client.as_ref().map(|c| (c.label.clone(), format!("{}@", c.owner))),
+        keep: params.offset.saturating_add(limit),
+    };
+    let audit_log_file = deps.config_state.snapshot().audit_log_file.clone();
+    let cancel = deps.cancel.clone();
+    let (window, files_scanned) = tokio::task::spawn_blocking(move || scan_audit_log(&audit_log_file, &query, &cancel))
+        .await
+        .map_err(|e| AppError::TokioIoError(format!("query_audit_log scan failed: {}", e)))??;
+    let total_matches = window.total;
+    let entries: Vec<AuditEntryMCP> = window.entries.into_iter().rev().skip(params.offset).take(limit).collect();
+    let next_offset = (params.offset + entries.len() < total_matches).then(|| params.offset + entries.len());
+    debug!(total_matches, returned = entries.len(), files_scanned, "Queried audit log");
+    Ok(QueryAuditLogResultMCP { entries, total_matches, next_offset, files_scanned })
+}
+
+/// Reads the rotated segments oldest first, then the current file, joining call results to their calls.
+/// Also returns how many files were read.
+fn scan_audit_log(audit_log_file: &Path, query: &AuditQuery, cancel: &CancellationToken) -> Result<(MatchWindow, usize), AppError> {
+    let mut files = log_rotation::rotated_segments(audit_log_file);
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    // A segment last modified before `since` holds nothing newer.
+    let mut files: Vec<PathBuf> = files.into_iter().filter(|(_, modified)| !query.since.is_some_and(|since| DateTime::<Utc>::from(*modified) < since)).map(|(path, _)| path).collect();
+    files.push(audit_log_file.to_path_buf());
+
+    let mut window = MatchWindow { entries: VecDeque::new(), keep: query.keep, total: 0 };
+    let mut files_scanned = 0;
+    // Correlation id -> the match still waiting for its result: its position among all matches, or the
+    // entry itself when the outcome filter decides whether it matches at all.
+    let mut pending: HashMap<String, usize> = HashMap::new();
+    let mut awaiting: HashMap<String, AuditEntryMCP> = HashMap::new();
+
+    for file in &files {
+        if cancel.is_cancelled() { return Err(AppError::Cancelled("query_audit_log".to_string())); }
+        let reader: Box<dyn Read> = match File::open(file) {
+            Ok(f) if file.extension().is_some_and(|e| e == "gz") => Box::new(GzDecoder::new(f)),
+            Ok(f) => Box::new(f),
+            // The current log does not exist before the first call; a segment may have just been pruned.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        files_scanned += 1;
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => { warn!(file = %file.display(), error = %e, "Stopped reading an unreadable audit log file"); break; }
+            };
+            let Some(parsed) = parse_line(&line) else { continue };
+            if parsed.command == CALL_RESULT_AUDIT_COMMAND {
+                let Some(id) = parsed.correlation_id.as_deref() else { continue };
+                let outcome = outcome_from(&parsed.arguments);
+                if let Some(mut entry) = awaiting.remove(id) {
+                    if query.outcome == Some(if outcome.ok { AuditOutcomeFilterMCP::Ok } else { AuditOutcomeFilterMCP::Error }) {
+                        entry.outcome = Some(outcome);
+                        window.push(entry);
+                    }
+                } else if let Some(position) = pending.remove(id) {
+                    if let Some(entry) = window.get_mut(position) { entry.outcome = Some(outcome); }
+                }
+                continue;
+            }
+            if query.since.is_some_and(|since| parsed.timestamp < since) || query.until.is_some_and(|until| parsed.timestamp >= until) { continue; }
+            let Some(entry) = query.matching(parsed) else { continue };
+            let call_id = entry.tool.is_some().then(|| entry.correlation_id.clone()).flatten();
+            match (call_id, query.outcome) {
+                (Some(id), Some(_)) => { awaiting.insert(id, entry); }
+                (_, Some(_)) => {}
+                (Some(id), None) => { let position = window.push(entry); pending.insert(id, position); }
+                (None, None) => { window.push(entry); }
+            }
+        }
+    }
+    Ok((window, files_scanned))
+}
+
+impl AuditQuery {
+    fn matching(&self, line: ParsedLine) -> Option<AuditEntryMCP> {
+        if let Some((label, owner_prefix)) = &self.client {
+            let client = line.client.as_deref()?;
+            if client != label && !client.starts_with(owner_prefix.as_str()) { return None; }
+        }
+        // Tool calls carry a correlation id; other mcp_* entries (mcp_initialize, mcp_prompts_get) do not.
+        let tool = line.correlation_id.as_ref().filter(|_| line.command != "mcp_approval").and(line.command.strip_prefix("mcp_")).map(str::to_string);
+        if self.tool.as_ref().is_some_and(|wanted| tool.as_ref() != Some(wanted)) { return None; }
+        if self.path_contains.as_deref().is_some_and(|needle| !policy::PATH_ARGUMENT_KEYS.iter().any(|key| line.arguments.get(key).is_some_and(|v| path_value_contains(v, needle)))) { return None; }
+        Some(AuditEntryMCP { timestamp: line.timestamp_text, command: line.command, tool, client: line.client, correlation_id: line.correlation_id, arguments: line.arguments, outcome: None })
+    }
+}
+
+fn path_value_contains(value: &Value, needle: &str) -> bool {
+    match value {
+        Value::String(s) => s.to_lowercase().contains(needle),
+        Value::Array(items) => items.iter().any(|item| path_value_contains(item, needle)),
+        _ => false,
+    }
+}
+
+/// `<rfc3339> [| CLIENT: <label>] [| CALL: <id>] | CMD: <name> | Arguments: <json>`, as AuditLogger writes it.
+fn parse_line(line: &str) -> Option<ParsedLine> {
+    let (head, arguments) = line.split_once(" | Arguments: ")?;
+    let mut fields = head.split(" | ");
+    let timestamp_text = fields.next()?.trim();
+    let timestamp = DateTime::parse_from_rfc3339(timestamp_text).ok()?.with_timezone(&Utc);
+    let (mut client, mut correlation_id, mut command) = (None, None, None);
+    for field in fields {
+        if let Some(value) = field.strip_prefix("CLIENT: ") { client = Some(value.to_string()); }
+        else if let Some(value) = field.strip_prefix("CALL: ") { correlation_id = Some(value.to_string()); }
+        else if let Some(value) = field.strip_prefix("CMD: ") { command = Some(value.trim_end().to_string()); }
+    }
+    let arguments = serde_json::from_str(arguments).unwrap_or_else(|_| Value::String(arguments.to_string()));
+    Some(ParsedLine { timestamp, timestamp_text: timestamp_text.to_string(), client, correlation_id, command: command?, arguments })
+}
+
+fn outcome_from(arguments: &Value) -> AuditCallOutcomeMCP {
+    let text = |key: &str| arguments.get(key).and_then(Value::as_str).map(str::to_string);
+    AuditCallOutcomeMCP {
+        ok: arguments.get("ok").and_then(Value::as_bool).unwrap_or(false),
+        error: text("error"),
+        message: text("message"),
+        duration_ms: arguments.get("duration_ms").and_then(Value::as_u64),
+    }
+}
+
+fn parse_time(value: &str, field: &str) -> Result<DateTime<Utc>, AppError> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) { return Ok(time.with_timezone(&Utc)); }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc())
+        .ok_or_else(|| AppError::InvalidInputArgument(format!("{} must be an RFC 3339 time or a YYYY-MM-DD date, got '{}'", field, value)))
+}
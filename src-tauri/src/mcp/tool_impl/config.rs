@@ -0,0 +1,126 @@
+use crate::config::{Config, WorkspaceRoot};
+use crate::config_layers::ConfigSource;
+use crate::config_profiles::apply_profile_switch;
+use crate::config_transfer::{export_config, import_config, ConfigExport, ConfigImportMode, ConfigImportOutcome};
+use crate::config_watcher::broadcast_config_change;
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use tracing::{info, instrument, warn};
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UseProfileParamsMCP {
+    /// Profile to switch to: 'safe', 'dev', 'ci' or a [profiles.<name>] table from config.toml.
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UseProfileResultMCP {
+    pub profile: String,
+    pub applied: Vec<String>,
+    // Set by the profile but only picked up after a restart (files_root, transport, ...).
+    pub restart_required: Vec<String>,
+    pub available_profiles: Vec<String>,
+}
+
+#[instrument(skip(deps), fields(profile = %params.name))]
+pub async fn mcp_use_profile(deps: &ToolDependencies, params: UseProfileParamsMCP) -> Result<UseProfileResultMCP, AppError> {
+    let app_handle = deps.app_handle.clone();
+    let profile_name = params.name.trim().to_string();
+    let name_for_load = profile_name.clone();
+    let reloaded = tokio::task::spawn_blocking(move || Config::load_with_profile(&app_handle, Some(&name_for_load)))
+        .await
+        .map_err(|e| AppError::ConfigError(format!("Profile load task failed: {}", e)))?
+        .map_err(|e| AppError::InvalidInputArgument(format!("{:#}", e)))?;
+
+    let (outcome, config_snapshot) = deps.config_state.update(|config| apply_profile_switch(config, &reloaded));
+    if !outcome.restart_required.is_empty() {
+        warn!(profile = %profile_name, keys = ?outcome.restart_required, "Profile sets keys that require a restart to take effect");
+    }
+    if outcome.applied.iter().any(|k| k == "log_level") {
+        if let Err(e) = crate::logging::apply_log_level(&reloaded.log_level) { warn!("{}", e); }
+    }
+    info!(profile = %profile_name, keys = ?outcome.applied, "Switched config profile");
+
+    broadcast_config_change(&deps.app_handle, "profile", &config_snapshot, &outcome.applied, &outcome.restart_required);
+
+    Ok(UseProfileResultMCP {
+        profile: profile_name,
+        applied: outcome.applied,
+        restart_required: outcome.restart_required,
+        available_profiles: reloaded.available_profiles,
+    })
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExportConfigParamsMCP {
+    /// Also export files_root, allowed_directories, mcp_log_dir and SSH identity files.
+    #[serde(default)]
+    pub include_machine_paths: bool,
+}
+
+pub fn mcp_export_config(deps: &ToolDependencies, params: ExportConfigParamsMCP) -> Result<ConfigExport, AppError> {
+    let config = deps.config_state.snapshot();
+    Ok(export_config(&config, params.include_machine_paths))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ImportConfigParamsMCP {
+    /// A blob previously returned by export_config.
+    #[schemars(with = "Map<String, Value>")]
+    pub config: Value,
+    /// 'merge' overwrites only the imported keys in the user config.toml; 'replace' makes it exactly the imported config.
+    #[serde(default)]
+    pub mode: ConfigImportMode,
+}
+
+#[instrument(skip(deps, params), fields(mode = ?params.mode))]
+pub async fn mcp_import_config(deps: &ToolDependencies, params: ImportConfigParamsMCP) -> Result<ConfigImportOutcome, AppError> {
+    let app_handle = deps.app_handle.clone();
+    let config_state = deps.config_state.clone();
+    tokio::task::spawn_blocking(move || import_config(&app_handle, &config_state, params.config, params.mode, false))
+        .await
+        .map_err(|e| AppError::ConfigError(format!("Config import task failed: {}", e)))?
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SetActiveWorkspaceParamsMCP {
+    /// Name of a configured workspace root; relative paths and new commands then use it.
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SetActiveWorkspaceResultMCP {
+    pub active_workspace: String,
+    pub files_root: PathBuf,
+    pub workspaces: Vec<WorkspaceRoot>,
+}
+
+#[instrument(skip(deps), fields(workspace = %params.name))]
+pub fn mcp_set_active_workspace(deps: &ToolDependencies, params: SetActiveWorkspaceParamsMCP) -> Result<SetActiveWorkspaceResultMCP, AppError> {
+    let ((result, switched), config_snapshot) = deps.config_state.try_update(|config| {
+        if config.workspaces.is_empty() {
+            return Err(AppError::InvalidInputArgument("No workspaces are configured; add [[workspaces]] entries to config.toml or set WORKSPACES".to_string()));
+        }
+        let workspace = config.workspaces.iter().find(|w| w.name == params.name).cloned().ok_or_else(|| {
+            let names: Vec<&str> = config.workspaces.iter().map(|w| w.name.as_str()).collect();
+            AppError::InvalidInputArgument(format!("Unknown workspace '{}'. Configured workspaces: {}", params.name, names.join(", ")))
+        })?;
+        let switched = config.active_workspace.as_deref() != Some(workspace.name.as_str());
+        config.files_root = workspace.path.clone();
+        config.active_workspace = Some(workspace.name.clone());
+        for key in ["files_root", "active_workspace"] {
+            config.value_sources.insert(key.to_string(), ConfigSource::Runtime);
+        }
+        Ok((SetActiveWorkspaceResultMCP { active_workspace: workspace.name, files_root: workspace.path, workspaces: config.workspaces.clone() }, switched))
+    })?;
+    if switched {
+        info!(workspace = %result.active_workspace, root = %result.files_root.display(), "Switched active workspace");
+        broadcast_config_change(&deps.app_handle, "workspace", &config_snapshot, &["files_root".to_string(), "active_workspace".to_string()], &[]);
+    }
+    Ok(result)
+}
@@ -1,13 +1,16 @@
-use crate::config::Config;
+use crate::config::ConfigState;
 use crate::error::AppError;
 use crate::mcp::handler::ToolDependencies;
 use crate::utils::fuzzy_search_logger::FuzzySearchLogEntry;
+use crate::utils::limits::check_file_size;
 use crate::utils::line_ending_handler::{detect_line_ending, normalize_line_endings, LineEndingStyle};
-use crate::utils::path_utils::validate_and_normalize_path;
+use crate::utils::text_file::read_text;
+use crate::utils::path_utils::{check_dir_policy_allows_write, validate_and_normalize_path};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock as StdRwLock}; // Added RwLock for config_state
+use std::sync::Arc;
 use tauri_plugin_fs::FsExt;
 use tokio::fs as tokio_fs; 
 #[allow(unused_imports)] 
@@ -18,11 +21,15 @@ use chrono::Utc;
 use diff;
 
 // --- MCP Specific Parameter Struct ---
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct EditBlockParamsMCP {
+    /// File path.
     pub file_path: String,
+    /// Exact string to replace.
     pub old_string: String,
+    /// String to replace with.
     pub new_string: String,
+    /// Expected number of replacements (0 for all).
     #[serde(default = "default_one_usize_mcp")]
     pub expected_replacements: usize,
 }
@@ -48,27 +55,29 @@ pub struct FuzzyMatchDetailsMCP {
 const FUZZY_SIMILARITY_THRESHOLD_MCP: f64 = 0.7;
 
 async fn read_file_for_edit_mcp_internal(
-    app_handle: &tauri::AppHandle,
+    app_handle: &crate::AppHandle,
     file_path_str: &str,
-    config_state: &Arc<StdRwLock<Config>> // MODIFIED: Accept Arc<RwLock<Config>>
+    config_state: &Arc<ConfigState> // MODIFIED: Accept Arc<ConfigState>
 ) -> Result<(String, PathBuf, LineEndingStyle), AppError> {
-    let path = { // Scope for config_guard
-        let config_guard = config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for path validation: {}", e)))?;
-        validate_and_normalize_path(file_path_str, &*config_guard, true, false)?
+    let (path, limits) = {
+        let config = config_state.snapshot();
+        let p = validate_and_normalize_path(file_path_str, &config, true, false)?;
+        // The file is read here but written back by the caller.
+        check_dir_policy_allows_write(&p, &config)?;
+        (p, config.limits.clone())
     };
     
     // Permission check using the plugin's scope API
     if !app_handle.fs_scope().is_allowed(&path) {
         error!("Read denied by FS scope for path: {}", path.display());
-        return Err(AppError::PathNotAllowed(format!("Read denied by FS scope: {}", path.display())));
+        return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("Read denied by FS scope: {}", path.display()) });
     }
     debug!(path = %path.display(), "FS scope check passed. Attempting to read file with tokio::fs");
+    let file_len = tokio_fs::metadata(&path).await.map_err(|e| AppError::TokioIoError(format!("Failed to stat file {}: {}", path.display(), e)))?.len();
+    check_file_size(&limits, path.display(), file_len)?;
 
-    let original_content = tokio_fs::read_to_string(&path).await
-        .map_err(|e| {
-            error!(path = %path.display(), error = %e, "Error from tokio_fs::read_to_string");
-            AppError::TokioIoError(format!("Failed to read file {}: {}", path.display(), e))
-        })?;
+    let original_content = read_text(&path).await
+        .inspect_err(|e| error!(path = %path.display(), error = %e, "Failed to read file for edit"))?;
     
     // MODIFIED: Detect line ending before moving original_content
     let line_ending_style = detect_line_ending(&original_content);
@@ -77,13 +86,13 @@ async fn read_file_for_edit_mcp_internal(
 }
 
 async fn write_file_after_edit_mcp(
-    app_handle: &tauri::AppHandle,
+    app_handle: &crate::AppHandle,
     path_obj: &PathBuf,
     content: String 
 ) -> Result<(), AppError> {
     if !app_handle.fs_scope().is_allowed(path_obj) {
          error!("Write denied by FS scope for path: {}", path_obj.display());
-        return Err(AppError::PathNotAllowed(format!("Write denied by FS scope: {}", path_obj.display())));
+        return Err(AppError::PathNotAllowed { path: path_obj.display().to_string(), message: format!("Write denied by FS scope: {}", path_obj.display()) });
     }
     debug!(path = %path_obj.display(), "FS scope check passed. Attempting to write file with tokio::fs");
 
@@ -106,18 +115,15 @@ pub async fn mcp_edit_block(
     let (original_content, validated_path, file_line_ending) = 
         read_file_for_edit_mcp_internal(&deps.app_handle, &params.file_path, &deps.config_state).await?;
 
-    let (fuzzy_log_path, _files_root_for_log) = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for log paths: {}", e)))?;
-        (config_guard.fuzzy_search_log_file.clone(), config_guard.files_root.clone())
-    };
+    let config = deps.config_state.snapshot();
+    let (fuzzy_log_path, _files_root_for_log) = (config.fuzzy_search_log_file.clone(), config.files_root.clone());
 
 
     let file_ext = validated_path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
 
     let norm_old = normalize_line_endings(&params.old_string, file_line_ending);
     let norm_new = normalize_line_endings(&params.new_string, file_line_ending);
-    let occurrences: Vec<_> = original_content.match_indices(&norm_old).collect();
-    let actual_occurrences = occurrences.len();
+    let actual_occurrences = original_content.matches(&norm_old).count();
 
     if (params.expected_replacements > 0 && actual_occurrences == params.expected_replacements) ||
        (params.expected_replacements == 0 && actual_occurrences > 0) {
@@ -176,14 +182,15 @@ pub async fn mcp_edit_block(
 fn find_best_fuzzy_match_internal(text: &str, query: &str) -> (String, f64) {
     if text.is_empty() || query.is_empty() { return ("".to_string(), 0.0); }
     let mut best_similarity = 0.0; let mut best_match_str = "";
-    let text_chars: Vec<char> = text.chars().collect(); let text_len = text_chars.len();
+    let text_len = text.chars().count();
     let query_len = query.chars().count(); if query_len == 0 { return ("".to_string(), 0.0); }
     let min_window_len = std::cmp::max(1, query_len.saturating_sub(query_len / 4));
     let max_window_len = std::cmp::min(text_len, query_len + query_len / 4);
     for window_len_chars in min_window_len..=max_window_len { if window_len_chars > text_len { continue; }
-        for i in 0..=(text_len - window_len_chars) {
-            let start_byte_idx = text.char_indices().nth(i).map(|(idx, _)| idx).unwrap_or(0);
-            let end_byte_idx = text.char_indices().nth(i + window_len_chars).map(|(idx, _)| idx).unwrap_or_else(|| text.len());
+        // Walks the window's start and end char boundaries together instead of copying the text into chars.
+        let mut end_byte_indices = text.char_indices().map(|(idx, _)| idx).chain(std::iter::once(text.len())).skip(window_len_chars);
+        for (start_byte_idx, _) in text.char_indices() {
+            let Some(end_byte_idx) = end_byte_indices.next() else { break };
             let window_str_slice = &text[start_byte_idx..end_byte_idx];
             let current_similarity = strsim::jaro_winkler(window_str_slice, query);
             if current_similarity > best_similarity { best_similarity = current_similarity; best_match_str = window_str_slice; }
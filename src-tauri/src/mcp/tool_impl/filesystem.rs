@@ -1,70 +1,110 @@
-use crate::config::Config;
+use crate::config::{Config, LimitsConfig};
 use crate::error::AppError;
+use crate::mcp::cancellation::cancellable;
 use crate::mcp::handler::ToolDependencies;
-use crate::utils::path_utils::validate_and_normalize_path;
+use crate::mcp::tool_impl::git::{search_scope, GitScope};
+use crate::utils::dir_policy::DirPolicy;
+use crate::utils::limits::{call_timeout, check_file_size, SearchSlot};
+use crate::utils::text_file::{line_window, read_text};
+use crate::utils::path_utils::{check_dir_policy_allows_write, is_denied_path, validate_and_normalize_path};
 use crate::utils::line_ending_handler::{detect_line_ending, normalize_line_endings, LineEndingStyle};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock as StdRwLock}; // MODIFIED: Added Arc, RwLock
+use std::sync::Arc;
 use tauri_plugin_fs::FsExt;
 use tokio::fs as tokio_fs; 
 use tokio::io::AsyncWriteExt; 
 
 use tracing::{debug, warn, instrument};
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
-use tokio::time::{timeout, Duration};
+use tokio::time::timeout;
 use chrono::{DateTime, Utc};
+use ignore::{WalkBuilder, WalkState};
+use tokio::sync::mpsc;
 
 
 // --- MCP Specific Parameter Structs ---
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct ReadFileParamsMCP {
+    /// Path to the file or URL.
     pub path: String,
+    /// True if 'path' is a URL.
     #[serde(default)]
     pub is_url: bool,
+    /// Line offset for text files.
     #[serde(default)]
     pub offset: usize,
+    /// Max lines to read for text files. Server default if not provided.
     pub length: Option<usize>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct ReadMultipleFilesParamsMCP {
+    /// Array of file paths.
     pub paths: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct WriteFileParamsMCP {
+    /// File path.
     pub path: String,
+    /// Content to write.
     pub content: String,
+    /// Write mode.
     #[serde(default = "default_rewrite_mode_mcp")]
     pub mode: WriteModeMCP,
 }
 fn default_rewrite_mode_mcp() -> WriteModeMCP { WriteModeMCP::Rewrite }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Serialize)]
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum WriteModeMCP { Rewrite, Append }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct CreateDirectoryParamsMCP { pub path: String }
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ListDirectoryParamsMCP { pub path: String }
-#[derive(Debug, Deserialize, Serialize)]
-pub struct MoveFileParamsMCP { pub source: String, pub destination: String }
-#[derive(Debug, Deserialize, Serialize)]
-pub struct GetFileInfoParamsMCP { pub path: String }
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct CreateDirectoryParamsMCP {
+    /// Directory path to create.
+    pub path: String,
+}
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ListDirectoryParamsMCP {
+    /// Directory path to list.
+    pub path: String,
+}
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MoveFileParamsMCP {
+    /// Source path.
+    pub source: String,
+    /// Destination path.
+    pub destination: String,
+}
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GetFileInfoParamsMCP {
+    /// File/directory path.
+    pub path: String,
+}
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct SearchFilesParamsMCP {
+    /// Root path for search.
     pub path: String,
+    /// Search pattern for file/dir names.
     pub pattern: String,
+    /// Timeout in ms. Default: the server search timeout.
     #[serde(rename = "timeoutMs")]
     pub timeout_ms: Option<u64>,
+    /// Search recursively.
     #[serde(default)]
     pub recursive: bool,
+    /// Max recursion depth.
     #[serde(default = "default_search_max_depth_mcp")]
     pub max_depth: usize,
+    /// Only files tracked by git in the repository containing path, and the directories holding them.
+    #[serde(default)]
+    pub tracked_only: bool,
+    /// Only files changed since this revision (e.g. main or HEAD~5), and the directories holding them.
+    pub changed_since: Option<String>,
 }
 fn default_search_max_depth_mcp() -> usize { 10 }
 
@@ -114,20 +154,36 @@ pub struct FileInfoResultMCP {
 pub struct SearchFilesResultMCP { pub path: String, pub pattern: String, pub matches: Vec<String>, pub timed_out: bool }
 
 
-const URL_FETCH_TIMEOUT_MS_MCP: u64 = 30000;
-const FILE_SEARCH_TIMEOUT_MS_MCP: u64 = 30000;
-
 fn is_image_mime_mcp(mime_type: &str) -> bool {
     mime_type.starts_with("image/") && (mime_type.ends_with("/png") || mime_type.ends_with("/jpeg") || mime_type.ends_with("/gif") || mime_type.ends_with("/webp"))
 }
 
-#[instrument(skip(http_client), fields(url = %url_str))]
+/// Reads the body chunk by chunk and stops as soon as it passes limits.max_file_bytes, so a server without Content-Length can't make us buffer it all.
+async fn read_body_capped(mut response: reqwest::Response, url_str: &str, limits: &LimitsConfig) -> Result<Vec<u8>, AppError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e|AppError::ReqwestError(e.to_string()))? {
+        check_file_size(limits, url_str, (body.len() + chunk.len()) as u64)?;
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[instrument(skip(http_client, limits), fields(url = %url_str))]
 async fn read_file_from_url_mcp_internal(
     http_client: &reqwest::Client,
     url_str: &str,
+    limits: &LimitsConfig,
 ) -> Result<FileContentMCP, AppError> {
     debug!("MCP Tool: Reading file from URL via reqwest");
-    let response_res = timeout(Duration::from_millis(URL_FETCH_TIMEOUT_MS_MCP), http_client.get(url_str).send()).await;
+    let mut request = http_client.get(url_str);
+    if let Some(host) = reqwest::Url::parse(url_str).ok().and_then(|u| u.host_str().map(str::to_string)) {
+        let token = tokio::task::spawn_blocking(move || crate::secrets::url_token_for_host(&host)).await.ok().flatten();
+        if let Some(token) = token {
+            debug!("Sending bearer token from keyring for this host");
+            request = request.bearer_auth(token);
+        }
+    }
+    let response_res = timeout(call_timeout(limits, None, limits.url_fetch_timeout_ms), request.send()).await;
 
     let response = match response_res {
         Ok(Ok(resp)) => resp,
@@ -140,13 +196,16 @@ async fn read_file_from_url_mcp_internal(
         let err_msg = response.text().await.unwrap_or_else(|_| "Unknown HTTP error".to_string());
         return Err(AppError::ReqwestError(format!("HTTP Error {}: {}", status, err_msg)));
     }
+    if let Some(content_length) = response.content_length() {
+        check_file_size(limits, url_str, content_length)?;
+    }
 
     let mime_type = response.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v|v.to_str().ok()).unwrap_or("application/octet-stream").split(';').next().unwrap_or_default().trim().to_string();
     if is_image_mime_mcp(&mime_type) {
-        let bytes = response.bytes().await.map_err(|e|AppError::ReqwestError(e.to_string()))?;
+        let bytes = read_body_capped(response, url_str, limits).await?;
         Ok(FileContentMCP { path: url_str.to_string(), text_content: None, image_data_base64: Some(BASE64_STANDARD.encode(&bytes)), mime_type, lines_read: None, total_lines: None, truncated: None, error: None })
     } else {
-        let text = response.text().await.map_err(|e|AppError::ReqwestError(e.to_string()))?;
+        let text = String::from_utf8_lossy(&read_body_capped(response, url_str, limits).await?).into_owned();
         let lines_count = text.lines().count();
         Ok(FileContentMCP { path: url_str.to_string(), text_content: Some(text), image_data_base64: None, mime_type, lines_read: Some(lines_count), total_lines: Some(lines_count), truncated: Some(false), error: None })
     }
@@ -156,61 +215,55 @@ async fn read_file_from_url_mcp_internal(
 pub async fn mcp_read_file(deps: &ToolDependencies, params: ReadFileParamsMCP) -> Result<FileContentMCP, AppError> {
     if params.is_url {
         let client = reqwest::Client::new();
-        // No config_guard needed for URL fetching, so it's not held across await.
-        return read_file_from_url_mcp_internal(&client, &params.path).await;
+        let limits = deps.config_state.snapshot().limits.clone();
+        return cancellable(&deps.cancel, &format!("read_file for {}", params.path), read_file_from_url_mcp_internal(&client, &params.path, &limits)).await?;
     }
 
-    let (path, read_limit) = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for read_file: {}", e)))?;
-        let p = validate_and_normalize_path(&params.path, &*config_guard, true, false)?;
-        let limit = params.length.unwrap_or(config_guard.file_read_line_limit);
-        (p, limit)
-    }; // config_guard is dropped here
+    let (path, read_limit, limits) = {
+        let config = deps.config_state.snapshot();
+        let p = validate_and_normalize_path(&params.path, &config, true, false)?;
+        let limit = params.length.unwrap_or(config.file_read_line_limit);
+        (p, limit, config.limits.clone())
+    };
 
-    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed(format!("FS scope disallows read: {}", path.display()))); }
+    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("FS scope disallows read: {}", path.display()) }); }
+    let file_len = tokio_fs::metadata(&path).await.map_err(|e| AppError::TokioIoError(e.to_string()))?.len();
+    check_file_size(&limits, path.display(), file_len)?;
 
     let mime_type = mime_guess::from_path(&path).first_or_octet_stream().to_string();
     if is_image_mime_mcp(&mime_type) {
         let bytes = tokio_fs::read(&path).await.map_err(|e| AppError::TokioIoError(e.to_string()))?;
         Ok(FileContentMCP { path: params.path, text_content: None, image_data_base64: Some(BASE64_STANDARD.encode(&bytes)), mime_type, lines_read: None, total_lines: None, truncated: None, error: None })
     } else {
-        let full_content = tokio_fs::read_to_string(&path).await.map_err(|e| AppError::TokioIoError(e.to_string()))?;
-        let lines_iter = full_content.lines();
-        let mut content_vec = Vec::new();
-        let mut current_line_idx = 0;
-        let mut total_lines_count = 0;
-        
-        for line_str in lines_iter {
-            total_lines_count += 1;
-            if current_line_idx >= params.offset && content_vec.len() < read_limit { content_vec.push(line_str.to_string()); }
-            current_line_idx += 1;
-            if content_vec.len() >= read_limit && (params.offset + content_vec.len()) < total_lines_count { break; }
-        }
-        let text_processed = content_vec.join("\n");
-        let lines_read = content_vec.len();
-        let truncated = params.offset > 0 || (lines_read == read_limit && (params.offset + lines_read) < total_lines_count);
-        Ok(FileContentMCP { path: params.path, text_content: Some(text_processed), image_data_base64: None, mime_type, lines_read: Some(lines_read), total_lines: Some(total_lines_count), truncated: Some(truncated), error: None })
+        let full_content = read_text(&path).await?;
+        let window = line_window(&full_content, params.offset, read_limit);
+        let lines_read = window.lines.len();
+        let truncated = params.offset > 0 || (lines_read == read_limit && (params.offset + lines_read) < window.total_lines);
+        Ok(FileContentMCP { path: params.path, text_content: Some(window.lines.join("\n")), image_data_base64: None, mime_type, lines_read: Some(lines_read), total_lines: Some(window.total_lines), truncated: Some(truncated), error: None })
     }
 }
 
 #[instrument(skip(deps, params), fields(path = %params.path, mode = ?params.mode))]
 pub async fn mcp_write_file(deps: &ToolDependencies, params: WriteFileParamsMCP) -> Result<FileOperationResultMCP, AppError> {
-    let (path, write_line_limit) = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for write_file: {}", e)))?;
-        let p = validate_and_normalize_path(&params.path, &*config_guard, false, true)?;
-        let limit = config_guard.file_write_line_limit;
-        (p, limit)
-    }; // config_guard is dropped here
+    let (path, write_line_limit, limits) = {
+        let config = deps.config_state.snapshot();
+        let p = validate_and_normalize_path(&params.path, &config, false, true)?;
+        let limit = DirPolicy::for_path(&p, config.root_for_path(&p))?.file_write_line_limit(config.file_write_line_limit);
+        (p, limit, config.limits.clone())
+    };
 
     let lines: Vec<&str> = params.content.lines().collect();
-    if lines.len() > write_line_limit { return Err(AppError::EditError(format!("Content exceeds line limit {}. Received {}.", write_line_limit, lines.len()))); }
+    if lines.len() > write_line_limit { return Err(AppError::LineLimitExceeded { message: format!("Content exceeds line limit {}. Received {}.", write_line_limit, lines.len()), limit: "file_write_line_limit".to_string(), max: write_line_limit as u64, actual: lines.len() as u64 }); }
 
     let final_content_str = if params.mode == WriteModeMCP::Append && tokio_fs::try_exists(&path).await.unwrap_or(false) {
         let existing_content_str = tokio_fs::read_to_string(&path).await.unwrap_or_default();
         normalize_line_endings(&params.content, detect_line_ending(&existing_content_str))
     } else { normalize_line_endings(&params.content, if cfg!(windows) {LineEndingStyle::CrLf} else {LineEndingStyle::Lf}) };
 
-    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed(format!("FS scope disallows write: {}", path.display()))); }
+    let existing_len = if params.mode == WriteModeMCP::Append { tokio_fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0) } else { 0 };
+    check_file_size(&limits, path.display(), existing_len + final_content_str.len() as u64)?;
+
+    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("FS scope disallows write: {}", path.display()) }); }
 
     if params.mode == WriteModeMCP::Append {
         let mut file = tokio_fs::OpenOptions::new().append(true).create(true).open(&path).await.map_err(|e| AppError::TokioIoError(e.to_string()))?;
@@ -224,28 +277,25 @@ pub async fn mcp_write_file(deps: &ToolDependencies, params: WriteFileParamsMCP)
 
 #[instrument(skip(deps, params), fields(path = %params.path))]
 pub async fn mcp_create_directory(deps: &ToolDependencies, params: CreateDirectoryParamsMCP) -> Result<FileOperationResultMCP, AppError> {
-    let path = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for create_directory: {}", e)))?;
-        validate_and_normalize_path(&params.path, &*config_guard, false, true)?
-    }; // config_guard is dropped here
-    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed(format!("FS scope disallows dir creation: {}", path.display()))); }
+    let path = validate_and_normalize_path(&params.path, &deps.config_state.snapshot(), false, true)?;
+    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("FS scope disallows dir creation: {}", path.display()) }); }
     tokio_fs::create_dir_all(&path).await.map_err(|e|AppError::TokioIoError(e.to_string()))?;
     Ok(FileOperationResultMCP { success: true, path: params.path, message: "Directory created.".to_string() })
 }
 
 #[instrument(skip(deps, params), fields(path = %params.path))]
 pub async fn mcp_list_directory(deps: &ToolDependencies, params: ListDirectoryParamsMCP) -> Result<ListDirectoryResultMCP, AppError> {
-    let path = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for list_directory: {}", e)))?;
-        validate_and_normalize_path(&params.path, &*config_guard, true, false)?
-    }; // config_guard is dropped here
-    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed(format!("FS scope disallows list: {}", path.display()))); }
+    let config = deps.config_state.snapshot();
+    let path = validate_and_normalize_path(&params.path, &config, true, false)?;
+    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("FS scope disallows list: {}", path.display()) }); }
     
     let mut entries_mcp = Vec::new();
     let mut read_dir = tokio_fs::read_dir(&path).await.map_err(|e| AppError::TokioIoError(e.to_string()))?;
     while let Some(entry_res) = read_dir.next_entry().await.map_err(|e| AppError::TokioIoError(e.to_string()))? {
         let entry = entry_res;
         let entry_path = entry.path();
+        // Not even the names of files read_file would refuse are listed.
+        if is_denied_path(&config, &entry_path) { continue; }
         let file_type = entry.file_type().await.map_err(|e| AppError::TokioIoError(e.to_string()))?;
         entries_mcp.push(DirEntryMCP {
             path: entry_path.to_string_lossy().into_owned(),
@@ -258,14 +308,15 @@ pub async fn mcp_list_directory(deps: &ToolDependencies, params: ListDirectoryPa
 
 #[instrument(skip(deps, params), fields(source = %params.source, dest = %params.destination))]
 pub async fn mcp_move_file(deps: &ToolDependencies, params: MoveFileParamsMCP) -> Result<FileOperationResultMCP, AppError> {
-    let (source_path, dest_path) = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for move_file: {}", e)))?;
-        let s_path = validate_and_normalize_path(&params.source, &*config_guard, true, false)?;
-        let d_path = validate_and_normalize_path(&params.destination, &*config_guard, false, true)?;
+    let (source_path, dest_path) = {
+        let config = deps.config_state.snapshot();
+        let s_path = validate_and_normalize_path(&params.source, &config, true, false)?;
+        check_dir_policy_allows_write(&s_path, &config)?;
+        let d_path = validate_and_normalize_path(&params.destination, &config, false, true)?;
         (s_path, d_path)
-    }; // config_guard is dropped here
+    };
     if !deps.app_handle.fs_scope().is_allowed(&source_path) || !deps.app_handle.fs_scope().is_allowed(&dest_path.parent().unwrap_or(&dest_path)) {
-        return Err(AppError::PathNotAllowed(format!("FS scope disallows move from {} or to {}", source_path.display(), dest_path.parent().unwrap_or(&dest_path).display())));
+        return Err(AppError::PathNotAllowed { path: source_path.display().to_string(), message: format!("FS scope disallows move from {} or to {}", source_path.display(), dest_path.parent().unwrap_or(&dest_path).display()) });
     }
     tokio_fs::rename(&source_path, &dest_path).await.map_err(|e|AppError::TokioIoError(e.to_string()))?;
     Ok(FileOperationResultMCP { success: true, path: params.destination.clone(), message: format!("Moved {} to {}.", params.source, params.destination) })
@@ -273,11 +324,8 @@ pub async fn mcp_move_file(deps: &ToolDependencies, params: MoveFileParamsMCP) -
 
 #[instrument(skip(deps, params), fields(path = %params.path))]
 pub async fn mcp_get_file_info(deps: &ToolDependencies, params: GetFileInfoParamsMCP) -> Result<FileInfoResultMCP, AppError> {
-    let path = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for get_file_info: {}", e)))?;
-        validate_and_normalize_path(&params.path, &*config_guard, true, false)?
-    }; // config_guard is dropped here
-    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed(format!("FS scope disallows info: {}", path.display()))); }
+    let path = validate_and_normalize_path(&params.path, &deps.config_state.snapshot(), true, false)?;
+    if !deps.app_handle.fs_scope().is_allowed(&path) { return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("FS scope disallows info: {}", path.display()) }); }
 
     let std_meta = tokio_fs::metadata(&path).await.map_err(|e| AppError::TokioIoError(e.to_string()))?;
 
@@ -311,23 +359,27 @@ pub async fn mcp_get_file_info(deps: &ToolDependencies, params: GetFileInfoParam
 pub async fn mcp_read_multiple_files(deps: &ToolDependencies, params: ReadMultipleFilesParamsMCP) -> Result<ReadMultipleFilesResultMCP, AppError> {
     let mut results = Vec::new();
     let http_client = reqwest::Client::new();
+    let limits = deps.config_state.snapshot().limits.clone();
 
     for path_str_from_params in params.paths {
+        if deps.cancel.is_cancelled() { return Err(AppError::Cancelled("read_multiple_files".to_string())); }
         let path_str = path_str_from_params.clone();
         let is_url = path_str.starts_with("http://") || path_str.starts_with("https://");
 
         let content_res = if is_url {
-             // No config_guard needed for URL fetching
-            read_file_from_url_mcp_internal(&http_client, &path_str).await
+             // No path validation needed for URL fetching
+            cancellable(&deps.cancel, &format!("read_multiple_files for {}", path_str), read_file_from_url_mcp_internal(&http_client, &path_str, &limits)).await?
         } else {
-            let validated_path_res = { // Scope for config_guard
-                let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for multi-read path validation: {}", e)))?;
-                validate_and_normalize_path(&path_str, &*config_guard, true, false)
-            }; // config_guard dropped
+            let validated_path_res = validate_and_normalize_path(&path_str, &deps.config_state.snapshot(), true, false);
 
             match validated_path_res {
                 Ok(val_path) => {
-                    if !deps.app_handle.fs_scope().is_allowed(&val_path) { Err(AppError::PathNotAllowed(format!("FS scope disallows read: {}", val_path.display()))) }
+                    let size_check = match tokio_fs::metadata(&val_path).await {
+                        Ok(meta) => check_file_size(&limits, val_path.display(), meta.len()),
+                        Err(e) => Err(AppError::TokioIoError(e.to_string())),
+                    };
+                    if !deps.app_handle.fs_scope().is_allowed(&val_path) { Err(AppError::PathNotAllowed { path: val_path.display().to_string(), message: format!("FS scope disallows read: {}", val_path.display()) }) }
+                    else if let Err(e) = size_check { Err(e) }
                     else {
                         let mime = mime_guess::from_path(&val_path).first_or_octet_stream().to_string();
                         if is_image_mime_mcp(&mime) {
@@ -352,84 +404,101 @@ pub async fn mcp_read_multiple_files(deps: &ToolDependencies, params: ReadMultip
     Ok(ReadMultipleFilesResultMCP { results })
 }
 
-#[instrument(skip(app_handle, pattern_lower, matches, config_state), fields(dir = %dir_to_search.display()))]
-async fn search_files_recursive_mcp_internal(
-    app_handle: &tauri::AppHandle,
-    dir_to_search: PathBuf,
-    pattern_lower: &str,
-    matches: &mut Vec<String>,
-    current_depth: usize,
-    max_depth: usize,
-    files_root_for_relative_path: &Path,
-    config_state: &Arc<StdRwLock<Config>>, // MODIFIED: Accept Arc<RwLock<Config>>
-) -> Result<(), AppError> {
-    if current_depth > max_depth { return Ok(()); }
-
-    if !app_handle.fs_scope().is_allowed(&dir_to_search) {
-        warn!(path = %dir_to_search.display(), "Search skipped: path not allowed by FS scope.");
-        return Ok(());
+// Matches the walker threads may queue before they wait for the call to collect them.
+const SEARCH_FILES_CHANNEL_CAPACITY: usize = 1024;
+
+fn search_dir_allowed_mcp(app_handle: &crate::AppHandle, config: &Config, dir: &Path) -> bool {
+    if !app_handle.fs_scope().is_allowed(dir) {
+        warn!(path = %dir.display(), "Search skipped: path not allowed by FS scope.");
+        return false;
     }
-    { // Scope for config_guard
-        let config_guard = config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for recursive search validation: {}", e)))?;
-        if validate_and_normalize_path(dir_to_search.to_str().unwrap_or_default(), &*config_guard, true, false).is_err() {
-            warn!(path = %dir_to_search.display(), "Search skipped: path not allowed by config.");
-            return Ok(());
-        }
-    } // config_guard dropped
+    if validate_and_normalize_path(dir.to_str().unwrap_or_default(), config, true, false).is_err() {
+        warn!(path = %dir.display(), "Search skipped: path not allowed by config.");
+        return false;
+    }
+    true
+}
 
-    let mut read_dir = match tokio_fs::read_dir(&dir_to_search).await {
-        Ok(rd) => rd,
-        Err(e) => {
-            warn!(path = %dir_to_search.display(), error = %e, "Could not read directory during search_files");
-            return Ok(()); 
-        }
-    };
-    
-    while let Some(entry_res) = read_dir.next_entry().await.map_err(|e| AppError::TokioIoError(e.to_string()))? {
-        let entry = entry_res;
-        let entry_name_os = entry.file_name();
-        let entry_name_lower = entry_name_os.to_string_lossy().to_lowercase();
-        let full_path = entry.path();
-
-        if entry_name_lower.contains(pattern_lower) {
-            if let Ok(relative_path) = full_path.strip_prefix(files_root_for_relative_path) {
-                 matches.push(relative_path.to_string_lossy().into_owned());
-            } else {
-                matches.push(full_path.to_string_lossy().into_owned());
+/// Walks `root` with ignore's parallel walker, one thread per core, sending each entry whose name
+/// contains `pattern_lower`. Nothing is filtered by .gitignore or hidden-file rules, and
+/// directories the scope or config disallow are not entered. The walk stops once the receiver
+/// is gone, i.e. when the call was cancelled or timed out. With a git `scope`, only tracked
+/// entries are sent and directories without tracked files are skipped.
+#[allow(clippy::too_many_arguments)]
+fn search_files_parallel_mcp_internal(
+    app_handle: crate::AppHandle,
+    config: Arc<Config>,
+    root: PathBuf,
+    pattern_lower: String,
+    max_depth: usize,
+    files_root_for_relative_path: PathBuf,
+    scope: Option<Arc<GitScope>>,
+    matches_tx: mpsc::Sender<String>,
+) {
+    // The root's children are at depth 1, and directories down to max_depth are listed.
+    let walker = WalkBuilder::new(&root).standard_filters(false).max_depth(Some(max_depth + 1)).build_parallel();
+    walker.run(|| {
+        let (app_handle, config, pattern_lower, files_root, scope, matches_tx) = (app_handle.clone(), config.clone(), pattern_lower.clone(), files_root_for_relative_path.clone(), scope.clone(), matches_tx.clone());
+        Box::new(move |entry_res| {
+            if matches_tx.is_closed() { return WalkState::Quit; }
+            let entry = match entry_res {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!(error = %e, "Could not read directory during search_files");
+                    return WalkState::Continue;
+                }
+            };
+            if entry.depth() == 0 { return WalkState::Continue; }
+            let full_path = entry.path();
+            if scope.as_ref().is_some_and(|scope| !scope.contains(full_path)) { return WalkState::Skip; }
+            if is_denied_path(&config, full_path) { return WalkState::Skip; }
+            if entry.file_name().to_string_lossy().to_lowercase().contains(&pattern_lower) {
+                let shown = full_path.strip_prefix(&files_root).unwrap_or(full_path).to_string_lossy().into_owned();
+                if matches_tx.blocking_send(shown).is_err() { return WalkState::Quit; }
             }
-        }
-        if entry.file_type().await.map_err(|e| AppError::TokioIoError(e.to_string()))?.is_dir() && current_depth < max_depth {
-            Box::pin(search_files_recursive_mcp_internal(app_handle, full_path, pattern_lower, matches, current_depth + 1, max_depth, files_root_for_relative_path, config_state)).await?;
-        }
-    }
-    Ok(())
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            if is_dir && entry.depth() <= max_depth && !search_dir_allowed_mcp(&app_handle, &config, full_path) { return WalkState::Skip; }
+            WalkState::Continue
+        })
+    });
 }
 
 #[instrument(skip(deps, params), fields(path = %params.path, pattern = %params.pattern))]
 pub async fn mcp_search_files(deps: &ToolDependencies, params: SearchFilesParamsMCP) -> Result<SearchFilesResultMCP, AppError> {
-    let (root_search_path, files_root_clone) = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock for search_files: {}", e)))?;
-        let rsp = validate_and_normalize_path(&params.path, &*config_guard, true, false)?;
-        let frc = config_guard.files_root.clone();
-        (rsp, frc)
-    }; // config_guard dropped
+    let (root_search_path, files_root_clone, limits) = {
+        let config = deps.config_state.snapshot();
+        let rsp = validate_and_normalize_path(&params.path, &config, true, false)?;
+        let frc = config.files_root.clone();
+        (rsp, frc, config.limits.clone())
+    };
+    let _search_slot = SearchSlot::acquire(&limits)?;
+    let scope = search_scope(deps, &root_search_path, params.tracked_only, params.changed_since.clone()).await?.map(Arc::new);
 
     let app_handle_clone = deps.app_handle.clone();
     let pattern_lower_clone = params.pattern.to_lowercase();
     let max_depth_clone = params.max_depth;
     let recursive_clone = params.recursive;
-    let config_state_clone = deps.config_state.clone(); // Clone Arc for passing to recursive
+    let config_state_clone = deps.config_state.clone();
 
 
     let search_operation = async {
         let mut matches = Vec::new();
 
         if recursive_clone {
-            Box::pin(search_files_recursive_mcp_internal(&app_handle_clone, root_search_path.clone(), &pattern_lower_clone, &mut matches, 0, max_depth_clone, &files_root_clone, &config_state_clone)).await?;
+            let config = config_state_clone.snapshot();
+            if !search_dir_allowed_mcp(&app_handle_clone, &config, &root_search_path) { return Ok(matches); }
+            let (matches_tx, mut matches_rx) = mpsc::channel(SEARCH_FILES_CHANNEL_CAPACITY);
+            let walk = tokio::task::spawn_blocking({
+                let (app_handle, root, pattern_lower, files_root, scope) = (app_handle_clone.clone(), root_search_path.clone(), pattern_lower_clone.clone(), files_root_clone.clone(), scope.clone());
+                move || search_files_parallel_mcp_internal(app_handle, config, root, pattern_lower, max_depth_clone, files_root, scope, matches_tx)
+            });
+            while let Some(found) = matches_rx.recv().await { matches.push(found); }
+            walk.await.map_err(|e| AppError::TokioIoError(format!("search_files walk failed: {}", e)))?;
         } else {
+            let config = config_state_clone.snapshot();
             if !app_handle_clone.fs_scope().is_allowed(&root_search_path) {
-                 let temp_config_guard_for_validation = config_state_clone.read().map_err(|e| AppError::ConfigError(format!("Config lock for non-recursive validation: {}", e)))?;
-                 if validate_and_normalize_path(root_search_path.to_str().unwrap_or_default(), &*temp_config_guard_for_validation, true, false).is_err() {
+                 let temp_config_for_validation = config_state_clone.snapshot();
+                 if validate_and_normalize_path(root_search_path.to_str().unwrap_or_default(), &*temp_config_for_validation, true, false).is_err() {
                     warn!(path = %root_search_path.display(), "Search skipped: path not allowed by scope or config.");
                     return Ok(matches);
                  }
@@ -439,6 +508,8 @@ pub async fn mcp_search_files(deps: &ToolDependencies, params: SearchFilesParams
                 let entry = entry_res;
                 let entry_name_os = entry.file_name();
                 let entry_name_lower = entry_name_os.to_string_lossy().to_lowercase();
+                if scope.as_ref().is_some_and(|scope| !scope.contains(&entry.path())) { continue; }
+                if is_denied_path(&config, &entry.path()) { continue; }
                  if entry_name_lower.contains(&pattern_lower_clone) {
                     if let Ok(relative_path) = entry.path().strip_prefix(&files_root_clone) {
                          matches.push(relative_path.to_string_lossy().into_owned());
@@ -446,11 +517,14 @@ pub async fn mcp_search_files(deps: &ToolDependencies, params: SearchFilesParams
                 }
             }
         }
+        // Walker threads finish in no particular order.
         matches.sort();
         Result::<Vec<String>, AppError>::Ok(matches)
     };
+    // Dropping the receiver on cancellation or timeout stops the walker threads at their next entry.
+    let search_operation = async { cancellable(&deps.cancel, "search_files", search_operation).await? };
     
-    match timeout(Duration::from_millis(params.timeout_ms.unwrap_or(FILE_SEARCH_TIMEOUT_MS_MCP)), search_operation).await {
+    match timeout(deps.call_timeout(&limits, params.timeout_ms, limits.search_timeout_ms), search_operation).await {
         Ok(Ok(m)) => Ok(SearchFilesResultMCP { path: params.path, pattern: params.pattern, matches: m, timed_out: false }),
         Ok(Err(e)) => Err(e),
         Err(_) => Ok(SearchFilesResultMCP { path: params.path, pattern: params.pattern, matches: vec![], timed_out: true }),
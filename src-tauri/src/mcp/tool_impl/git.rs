@@ -0,0 +1,877 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::mcp::roots;
+use crate::utils::limits::{check_file_size, truncate_output};
+use crate::utils::path_utils::{check_dir_policy_allows_write, is_denied_path, validate_and_normalize_path};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use git2::{ApplyLocation, ApplyOptions, BranchType, Commit, Delta, DiffHunk, DiffOptions, IndexAddOption, Oid, Repository, Sort, Status, StatusOptions};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, instrument};
+
+const DEFAULT_CONTEXT_LINES: u32 = 3;
+const MAX_LOG_LIMIT: usize = 500;
+pub const CHECKPOINT_REF_PREFIX: &str = "refs/mcp-checkpoints/";
+// The ranges of a hunk header, "@@ -12,7 +12,9 @@"; a count of 1 may be left out.
+static HUNK_RANGE: Lazy<Regex> = Lazy::new(|| Regex::new(r"-(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))?").expect("valid hunk range regex"));
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitStatusParamsMCP {
+    /// A file or directory in the repository; only changes under it are listed. Defaults to files_root.
+    pub path: Option<String>,
+    /// List untracked files. Defaults to true.
+    #[serde(default = "default_true")]
+    pub include_untracked: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitDiffParamsMCP {
+    /// A file or directory in the repository to limit the diff to. Defaults to files_root.
+    pub path: Option<String>,
+    /// Diff the index against HEAD (what would be committed) instead of the working tree against the index.
+    #[serde(default)]
+    pub staged: bool,
+    /// A revision (HEAD, a branch, a tag or a commit) to diff the working tree against; overrides staged.
+    pub base: Option<String>,
+    /// Lines of context around each hunk. Defaults to 3.
+    pub context_lines: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitAddParamsMCP {
+    /// Files or directories to stage, deleted ones included. All must be in the same repository; ignored files are skipped.
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitCommitParamsMCP {
+    /// Any path in the repository. Defaults to files_root.
+    pub path: Option<String>,
+    /// The commit message; the first line is the subject.
+    pub message: String,
+    /// Add a Signed-off-by trailer for the committer.
+    #[serde(default)]
+    pub signoff: bool,
+    /// Only report what would be committed.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitLogParamsMCP {
+    /// Only commits that changed this file or directory. Defaults to files_root; the repository root lists every commit.
+    pub path: Option<String>,
+    /// Where history starts: a branch, tag or commit. Defaults to HEAD.
+    pub rev: Option<String>,
+    /// Matching commits to skip, newest first.
+    #[serde(default)]
+    pub offset: usize,
+    /// Commits to return, at most 500.
+    #[serde(default = "default_git_log_limit")]
+    pub limit: usize,
+}
+fn default_git_log_limit() -> usize { 50 }
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitBlameParamsMCP {
+    /// The file to blame, as it is in the working tree.
+    pub path: String,
+    /// First line to blame, 1-based. Defaults to 1.
+    pub start_line: Option<usize>,
+    /// Last line to blame, inclusive. Defaults to the end of the file.
+    pub end_line: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitBranchListParamsMCP {
+    /// Any path in the repository. Defaults to files_root.
+    pub path: Option<String>,
+    /// Also list remote-tracking branches.
+    #[serde(default)]
+    pub include_remote: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitCreateBranchParamsMCP {
+    /// Any path in the repository. Defaults to files_root.
+    pub path: Option<String>,
+    /// The new branch's name, e.g. agent/fix-parser.
+    pub name: String,
+    /// Branch, tag or commit to start from. Defaults to HEAD.
+    pub start_point: Option<String>,
+    /// Switch to the new branch, which needs a clean working tree.
+    #[serde(default)]
+    pub checkout: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitCheckoutParamsMCP {
+    /// Any path in the repository. Defaults to files_root.
+    pub path: Option<String>,
+    /// The local branch to switch to.
+    pub branch: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GitStageHunksParamsMCP {
+    /// The file whose unstaged hunks to stage.
+    pub path: String,
+    /// Hunk headers as git_diff shows them, e.g. "@@ -12,7 +12,9 @@", or only their ranges ("-12,7 +12,9").
+    pub hunks: Vec<String>,
+    /// The context_lines git_diff was called with. Defaults to 3.
+    pub context_lines: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct FindRepoRootParamsMCP {
+    /// Any file or directory. Defaults to files_root.
+    pub path: Option<String>,
+}
+
+fn default_true() -> bool { true }
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitStatusEntryMCP {
+    // Relative to repo_root, with / separators.
+    pub path: String,
+    // added, modified, deleted, renamed or typechange.
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitStatusResultMCP {
+    pub repo_root: PathBuf,
+    // None on a detached HEAD.
+    pub branch: Option<String>,
+    pub staged: Vec<GitStatusEntryMCP>,
+    pub unstaged: Vec<GitStatusEntryMCP>,
+    pub untracked: Vec<String>,
+    pub conflicted: Vec<String>,
+    pub clean: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitDiffFileMCP {
+    pub path: String,
+    pub status: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitDiffResultMCP {
+    pub repo_root: PathBuf,
+    pub files: Vec<GitDiffFileMCP>,
+    // Unified diff with a diff --git header per file.
+    pub diff: String,
+    // Set when the diff was cut at limits.max_output_bytes; files is still complete.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitAddResultMCP {
+    pub repo_root: PathBuf,
+    // Everything staged after the add, not only what this call added.
+    pub staged: Vec<GitStatusEntryMCP>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitCommitResultMCP {
+    pub repo_root: PathBuf,
+    pub branch: Option<String>,
+    // The new commit's hash; None on a dry run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    // As committed, signoff included.
+    pub message: String,
+    pub files: Vec<GitStatusEntryMCP>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct GitCommitInfoMCP {
+    pub hash: String,
+    pub author: String,
+    pub email: String,
+    pub date: DateTime<Utc>,
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitLogResultMCP {
+    pub repo_root: PathBuf,
+    // Newest first.
+    pub commits: Vec<GitCommitInfoMCP>,
+    // Pass as offset for the next page; None at the end of history.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+/// Consecutive lines last changed by the same commit.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitBlameHunkMCP {
+    pub start_line: usize,
+    pub end_line: usize,
+    // None for lines changed in the working tree and not committed yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<GitCommitInfoMCP>,
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitBlameResultMCP {
+    pub repo_root: PathBuf,
+    pub path: String,
+    pub hunks: Vec<GitBlameHunkMCP>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitBranchMCP {
+    // Remote-tracking branches are named <remote>/<branch>.
+    pub name: String,
+    pub remote: bool,
+    pub current: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    // Commits ahead of and behind the upstream, when there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ahead: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behind: Option<usize>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitBranchListResultMCP {
+    pub repo_root: PathBuf,
+    pub current: Option<String>,
+    pub branches: Vec<GitBranchMCP>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitBranchResultMCP {
+    pub repo_root: PathBuf,
+    pub branch: String,
+    pub commit: String,
+    // Whether HEAD is now on the branch.
+    pub checked_out: bool,
+}
+
+/// A commit holding the working tree as it was before a bulk change, on a ref of its own.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitCheckpointMCP {
+    pub repo_root: PathBuf,
+    // Under refs/mcp-checkpoints/; `git restore --source=<reference> --worktree -- .` brings the files back.
+    pub reference: String,
+    pub commit: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GitStageHunksResultMCP {
+    pub repo_root: PathBuf,
+    pub path: String,
+    pub staged_hunks: Vec<String>,
+    // The file's unstaged hunks left unstaged.
+    pub remaining_hunks: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FindRepoRootResultMCP {
+    pub path: PathBuf,
+    pub in_repository: bool,
+    // The rest is only set inside a repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo_root: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub detached: bool,
+    // Staged, unstaged or untracked changes anywhere in the repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirty: Option<bool>,
+}
+
+/// The files a git-scoped search may report, as absolute paths, and every directory above them
+/// up to the repository root.
+#[derive(Debug, Default)]
+pub struct GitScope {
+    files: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl GitScope {
+    /// Files in the index of the repository containing `path`, or with `changed_since`, the files
+    /// that differ between that revision and the working tree (deleted ones left out).
+    pub fn load(config: &Config, path: &str, changed_since: Option<&str>) -> Result<Self, AppError> {
+        let target = open_repo(config, Some(path))?;
+        let repo = &target.repo;
+        let relative: Vec<String> = match changed_since {
+            Some(rev) => {
+                let tree = repo.revparse_single(rev).and_then(|object| object.peel_to_tree()).map_err(|e| AppError::InvalidInputArgument(format!("Unknown revision '{}': {}", rev, e.message())))?;
+                let diff = repo.diff_tree_to_workdir_with_index(Some(&tree), None)?;
+                diff.deltas().filter(|d| d.status() != Delta::Deleted).filter_map(|d| d.new_file().path().map(|p| p.to_string_lossy().into_owned())).collect()
+            }
+            None => repo.index()?.iter().map(|entry| String::from_utf8_lossy(&entry.path).into_owned()).collect(),
+        };
+        let mut scope = GitScope::default();
+        for file in relative {
+            let absolute = target.root.join(&file);
+            for dir in absolute.ancestors().skip(1) {
+                if !dir.starts_with(&target.root) || !scope.dirs.insert(dir.to_path_buf()) { break; }
+            }
+            scope.files.insert(absolute);
+        }
+        debug!(files = scope.files.len(), changed_since = ?changed_since, "Loaded git search scope");
+        Ok(scope)
+    }
+
+    pub fn contains_file(&self, path: &Path) -> bool {
+        self.files.contains(path)
+    }
+
+    /// A tracked file, or a directory with tracked files below it.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path) || self.dirs.contains(path)
+    }
+}
+
+/// The scope of a search_code or search_files call with tracked_only or changed_since, else None.
+pub async fn search_scope(deps: &ToolDependencies, path: &Path, tracked_only: bool, changed_since: Option<String>) -> Result<Option<GitScope>, AppError> {
+    if !tracked_only && changed_since.is_none() { return Ok(None); }
+    let path = path.to_string_lossy().into_owned();
+    Ok(Some(run_git(deps, move |config| GitScope::load(config, &path, changed_since.as_deref())).await?))
+}
+
+/// A repository found from a tool's `path`, with that path as a pathspec relative to its root.
+pub struct GitTarget {
+    pub repo: Repository,
+    pub root: PathBuf,
+    // None when `path` is the repository root.
+    pub pathspec: Option<String>,
+}
+
+/// Finds the repository containing `path` (default files_root), which may be a deleted file.
+/// The repository's root must pass path validation too, so one reaching above the allowed
+/// directories is refused.
+pub fn open_repo(config: &Config, path: Option<&str>) -> Result<GitTarget, AppError> {
+    let path_str = path.map(str::to_string).unwrap_or_else(|| config.files_root.to_string_lossy().into_owned());
+    let target = validate_and_normalize_path(&path_str, config, false, false)?;
+    let start = target.ancestors().find(|p| p.exists()).unwrap_or(&target);
+    let repo = Repository::discover(start).map_err(|e| match e.code() {
+        git2::ErrorCode::NotFound => AppError::InvalidInputArgument(format!("{} is not inside a git repository", target.display())),
+        _ => e.into(),
+    })?;
+    let workdir = repo.workdir().ok_or_else(|| AppError::GitError(format!("The repository at {} is bare", repo.path().display())))?;
+    let root = validate_and_normalize_path(&workdir.to_string_lossy(), config, true, false)?;
+    let pathspec = relative_path(&root, &target).filter(|p| !p.is_empty());
+    Ok(GitTarget { repo, root, pathspec })
+}
+
+/// `path` relative to `root` in git's form, with / separators.
+pub fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    Some(relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+}
+
+/// The checked-out branch, also for a branch without commits yet; None on a detached HEAD.
+pub fn current_branch(repo: &Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    head.symbolic_target().map(|target| target.trim_start_matches("refs/heads/").to_string())
+}
+
+/// Runs `f` on a blocking thread with the call's config and client roots.
+pub async fn run_git<T: Send + 'static>(deps: &ToolDependencies, f: impl FnOnce(&Config) -> Result<T, AppError> + Send + 'static) -> Result<T, AppError> {
+    let (config, session_roots) = (deps.config_state.snapshot(), roots::current_roots());
+    tokio::task::spawn_blocking(move || roots::with_roots(session_roots, || f(&config)))
+        .await
+        .map_err(|e| AppError::TokioIoError(format!("git task failed: {}", e)))?
+}
+
+fn delta_name(delta: Delta) -> &'static str {
+    match delta {
+        Delta::Added | Delta::Untracked | Delta::Copied => "added",
+        Delta::Deleted => "deleted",
+        Delta::Renamed => "renamed",
+        Delta::Typechange => "typechange",
+        _ => "modified",
+    }
+}
+
+fn staged_change(status: Status) -> Option<&'static str> {
+    if status.is_index_new() { Some("added") }
+    else if status.is_index_deleted() { Some("deleted") }
+    else if status.is_index_renamed() { Some("renamed") }
+    else if status.is_index_typechange() { Some("typechange") }
+    else if status.is_index_modified() { Some("modified") }
+    else { None }
+}
+
+fn unstaged_change(status: Status) -> Option<&'static str> {
+    if status.is_wt_deleted() { Some("deleted") }
+    else if status.is_wt_renamed() { Some("renamed") }
+    else if status.is_wt_typechange() { Some("typechange") }
+    else if status.is_wt_modified() { Some("modified") }
+    else { None }
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path))]
+pub async fn mcp_git_status(deps: &ToolDependencies, params: GitStatusParamsMCP) -> Result<GitStatusResultMCP, AppError> {
+    run_git(deps, move |config| {
+        let target = open_repo(config, params.path.as_deref())?;
+        git_status(&target, params.include_untracked)
+    }).await
+}
+
+pub fn git_status(target: &GitTarget, include_untracked: bool) -> Result<GitStatusResultMCP, AppError> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(include_untracked).recurse_untracked_dirs(true).include_ignored(false).renames_head_to_index(true).renames_index_to_workdir(true);
+    if let Some(spec) = &target.pathspec { options.pathspec(spec); }
+    let statuses = target.repo.statuses(Some(&mut options))?;
+
+    let (mut staged, mut unstaged, mut untracked, mut conflicted) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for entry in statuses.iter() {
+        let status = entry.status();
+        let Some(path) = entry.path().map(str::to_string) else { continue };
+        if status.is_conflicted() {
+            conflicted.push(path);
+            continue;
+        }
+        if let Some(change) = staged_change(status) {
+            let old_path = entry.head_to_index().filter(|_| status.is_index_renamed()).and_then(|d| d.old_file().path().map(|p| p.to_string_lossy().into_owned()));
+            let path = entry.head_to_index().and_then(|d| d.new_file().path().map(|p| p.to_string_lossy().into_owned())).unwrap_or_else(|| path.clone());
+            staged.push(GitStatusEntryMCP { path, status: change.to_string(), old_path });
+        }
+        if let Some(change) = unstaged_change(status) {
+            let old_path = entry.index_to_workdir().filter(|_| status.is_wt_renamed()).and_then(|d| d.old_file().path().map(|p| p.to_string_lossy().into_owned()));
+            unstaged.push(GitStatusEntryMCP { path: path.clone(), status: change.to_string(), old_path });
+        }
+        if status.is_wt_new() { untracked.push(path); }
+    }
+    let clean = staged.is_empty() && unstaged.is_empty() && untracked.is_empty() && conflicted.is_empty();
+    debug!(staged = staged.len(), unstaged = unstaged.len(), untracked = untracked.len(), "Read git status");
+    Ok(GitStatusResultMCP { repo_root: target.root.clone(), branch: current_branch(&target.repo), staged, unstaged, untracked, conflicted, clean })
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path, staged = params.staged, base = ?params.base))]
+pub async fn mcp_git_diff(deps: &ToolDependencies, params: GitDiffParamsMCP) -> Result<GitDiffResultMCP, AppError> {
+    run_git(deps, move |config| {
+        let target = open_repo(config, params.path.as_deref())?;
+        let repo = &target.repo;
+        let mut options = DiffOptions::new();
+        options.context_lines(params.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES));
+        if let Some(spec) = &target.pathspec { options.pathspec(spec); }
+        let mut diff = match (&params.base, params.staged) {
+            (Some(base), _) => {
+                let tree = repo.revparse_single(base).and_then(|object| object.peel_to_tree()).map_err(|e| AppError::InvalidInputArgument(format!("Unknown revision '{}': {}", base, e.message())))?;
+                repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))?
+            }
+            // Before the first commit everything in the index is new.
+            (None, true) => {
+                let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+                repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut options))?
+            }
+            (None, false) => repo.diff_index_to_workdir(None, Some(&mut options))?,
+        };
+        diff.find_similar(None)?;
+
+        let max_bytes = config.limits.max_output_bytes;
+        let mut files = Vec::new();
+        let mut text = String::new();
+        let mut truncated = false;
+        for index in 0..diff.deltas().len() {
+            let Some(delta) = diff.get_delta(index) else { continue };
+            // Files read_file refuses are left out of both the list and the patch, under either name of a rename.
+            if [delta.old_file().path(), delta.new_file().path()].into_iter().flatten().any(|p| is_denied_path(config, &target.root.join(p))) { continue; }
+            let path = delta.new_file().path().or_else(|| delta.old_file().path()).map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+            let Some(mut patch) = git2::Patch::from_diff(&diff, index)? else {
+                files.push(GitDiffFileMCP { path, status: delta_name(delta.status()).to_string(), additions: 0, deletions: 0 });
+                continue;
+            };
+            let (_, additions, deletions) = patch.line_stats()?;
+            files.push(GitDiffFileMCP { path, status: delta_name(delta.status()).to_string(), additions, deletions });
+            if !truncated {
+                text.push_str(&String::from_utf8_lossy(&patch.to_buf()?));
+                truncated = text.len() > max_bytes;
+            }
+        }
+        truncate_output(&mut text, max_bytes);
+        debug!(files = files.len(), bytes = text.len(), truncated, "Built git diff");
+        Ok(GitDiffResultMCP { repo_root: target.root.clone(), files, diff: text, truncated })
+    }).await
+}
+
+// Staging and committing write .git, so the repository root must not be read-only by policy.
+fn open_repo_for_write(config: &Config, path: Option<&str>) -> Result<GitTarget, AppError> {
+    let target = open_repo(config, path)?;
+    check_dir_policy_allows_write(&target.root, config)?;
+    Ok(target)
+}
+
+#[instrument(skip(deps, params), fields(paths = params.paths.len()))]
+pub async fn mcp_git_add(deps: &ToolDependencies, params: GitAddParamsMCP) -> Result<GitAddResultMCP, AppError> {
+    if params.paths.is_empty() { return Err(AppError::InvalidInputArgument("git_add needs at least one path".to_string())); }
+    run_git(deps, move |config| {
+        let mut target = open_repo_for_write(config, params.paths.first().map(String::as_str))?;
+        let mut specs = Vec::new();
+        for path in &params.paths {
+            let path = validate_and_normalize_path(path, config, false, false)?;
+            let spec = relative_path(&target.root, &path).ok_or_else(|| AppError::InvalidInputArgument(format!("{} is not in the repository at {}", path.display(), target.root.display())))?;
+            specs.push(spec);
+        }
+        // The repository root itself: no pathspec stages everything.
+        if specs.iter().any(String::is_empty) { specs.clear(); }
+        // A file read_file refuses is never staged, also when a directory or the root is.
+        let root = target.root.clone();
+        let mut skip_denied = |path: &Path, _: &[u8]| -> i32 { if is_denied_path(config, &root.join(path)) { 1 } else { 0 } };
+        let mut index = target.repo.index()?;
+        index.add_all(specs.iter(), IndexAddOption::DEFAULT, Some(&mut skip_denied as &mut git2::IndexMatchedPath))?;
+        // add_all skips deletions; update_all stages them.
+        index.update_all(specs.iter(), Some(&mut skip_denied as &mut git2::IndexMatchedPath))?;
+        index.write()?;
+        target.pathspec = None;
+        let staged = git_status(&target, false)?.staged;
+        debug!(specs = ?specs, staged = staged.len(), "Staged paths");
+        Ok(GitAddResultMCP { repo_root: target.root, staged })
+    }).await
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path, dry_run = params.dry_run))]
+pub async fn mcp_git_commit(deps: &ToolDependencies, params: GitCommitParamsMCP) -> Result<GitCommitResultMCP, AppError> {
+    let result = run_git(deps, move |config| {
+        let mut target = open_repo_for_write(config, params.path.as_deref())?;
+        target.pathspec = None;
+        if params.message.trim().is_empty() { return Err(AppError::InvalidInputArgument("The commit message is empty".to_string())); }
+        let repo = &target.repo;
+        let mut index = repo.index()?;
+        if index.has_conflicts() { return Err(AppError::GitError(format!("{} has unresolved conflicts", target.root.display()))); }
+        let files = git_status(&target, false)?.staged;
+        if files.is_empty() { return Err(AppError::InvalidInputArgument(format!("Nothing is staged in {}; stage changes with git_add first", target.root.display()))); }
+        let signature = repo.signature().map_err(|e| AppError::GitError(format!("No committer identity ({}); set user.name and user.email in git config", e.message())))?;
+
+        let mut message = params.message.trim_end().to_string();
+        if params.signoff {
+            let trailer = format!("Signed-off-by: {} <{}>", signature.name().unwrap_or_default(), signature.email().unwrap_or_default());
+            if !message.lines().any(|line| line == trailer) { message = format!("{}\n\n{}", message, trailer); }
+        }
+        message.push('\n');
+        let branch = current_branch(repo);
+        if params.dry_run {
+            return Ok(GitCommitResultMCP { repo_root: target.root.clone(), branch, commit: None, message, files, dry_run: true });
+        }
+
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&Commit> = parent.iter().collect();
+        let oid = repo.commit(Some("HEAD"), &signature, &signature, &message, &tree, &parents)?;
+        info!(commit = %oid, repo = %target.root.display(), files = files.len(), "Created git commit");
+        Ok(GitCommitResultMCP { repo_root: target.root.clone(), branch, commit: Some(oid.to_string()), message, files, dry_run: false })
+    }).await?;
+    if let Some(commit) = &result.commit {
+        deps.audit_logger.log_correlated_call(deps.client_label(), deps.correlation_id.as_deref(), "git_commit", &json!({ "repo": result.repo_root, "branch": result.branch, "commit": commit, "files": result.files.len() })).await;
+    }
+    Ok(result)
+}
+
+fn commit_info(commit: &Commit) -> GitCommitInfoMCP {
+    let author = commit.author();
+    GitCommitInfoMCP {
+        hash: commit.id().to_string(),
+        author: author.name().unwrap_or_default().to_string(),
+        email: author.email().unwrap_or_default().to_string(),
+        date: DateTime::from_timestamp(author.when().seconds(), 0).unwrap_or_default(),
+        subject: commit.summary().unwrap_or_default().to_string(),
+        body: commit.body().map(|b| b.trim().to_string()).filter(|b| !b.is_empty()),
+    }
+}
+
+// Whether `commit` changed anything under `pathspec` compared with its first parent.
+fn touches(repo: &Repository, commit: &Commit, pathspec: &str) -> Result<bool, AppError> {
+    let parent_tree = commit.parent(0).ok().map(|parent| parent.tree()).transpose()?;
+    let mut options = DiffOptions::new();
+    options.pathspec(pathspec);
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), Some(&mut options))?;
+    Ok(diff.deltas().len() > 0)
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path, rev = ?params.rev))]
+pub async fn mcp_git_log(deps: &ToolDependencies, params: GitLogParamsMCP) -> Result<GitLogResultMCP, AppError> {
+    run_git(deps, move |config| {
+        let target = open_repo(config, params.path.as_deref())?;
+        let repo = &target.repo;
+        let limit = params.limit.clamp(1, MAX_LOG_LIMIT);
+        let start = match &params.rev {
+            Some(rev) => repo.revparse_single(rev).and_then(|object| object.peel_to_commit()).map_err(|e| AppError::InvalidInputArgument(format!("Unknown revision '{}': {}", rev, e.message())))?.id(),
+            None => match repo.head().ok().and_then(|head| head.target()) {
+                Some(oid) => oid,
+                // A branch without commits has no history.
+                None => return Ok(GitLogResultMCP { repo_root: target.root.clone(), commits: Vec::new(), next_offset: None }),
+            },
+        };
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TIME)?;
+        walk.push(start)?;
+
+        let (mut commits, mut matched, mut more) = (Vec::new(), 0, false);
+        for oid in walk {
+            let commit = repo.find_commit(oid?)?;
+            if let Some(spec) = &target.pathspec {
+                if !touches(repo, &commit, spec)? { continue; }
+            }
+            matched += 1;
+            if matched <= params.offset { continue; }
+            if commits.len() == limit {
+                more = true;
+                break;
+            }
+            commits.push(commit_info(&commit));
+        }
+        let next_offset = more.then(|| params.offset + commits.len());
+        Ok(GitLogResultMCP { repo_root: target.root.clone(), commits, next_offset })
+    }).await
+}
+
+#[instrument(skip(deps, params), fields(path = %params.path))]
+pub async fn mcp_git_blame(deps: &ToolDependencies, params: GitBlameParamsMCP) -> Result<GitBlameResultMCP, AppError> {
+    run_git(deps, move |config| {
+        let target = open_repo(config, Some(&params.path))?;
+        let repo = &target.repo;
+        let file = validate_and_normalize_path(&params.path, config, true, false)?;
+        let relative = target.pathspec.clone().filter(|_| file.is_file()).ok_or_else(|| AppError::InvalidInputArgument(format!("{} is not a file in the repository", file.display())))?;
+        let size = std::fs::metadata(&file).map_err(|e| AppError::StdIoError(format!("{}: {}", file.display(), e)))?.len();
+        check_file_size(&config.limits, file.display(), size)?;
+        let contents = std::fs::read(&file).map_err(|e| AppError::StdIoError(format!("{}: {}", file.display(), e)))?;
+        let text = String::from_utf8_lossy(&contents);
+        let lines: Vec<&str> = text.lines().collect();
+
+        let start_line = params.start_line.unwrap_or(1).max(1);
+        let end_line = params.end_line.unwrap_or(lines.len()).min(lines.len());
+        if start_line > end_line { return Ok(GitBlameResultMCP { repo_root: target.root.clone(), path: relative, hunks: Vec::new() }); }
+
+        // Blaming the working-tree buffer attributes uncommitted lines to the zero id.
+        let committed = repo.blame_file(Path::new(&relative), None)
+            .map_err(|e| AppError::GitError(format!("Cannot blame {}: {}", relative, e.message())))?;
+        let blame = committed.blame_buffer(&contents)?;
+        let mut commits: HashMap<Oid, Option<GitCommitInfoMCP>> = HashMap::new();
+        let mut hunks = Vec::new();
+        for hunk in blame.iter() {
+            let first = hunk.final_start_line().max(start_line);
+            let last = (hunk.final_start_line() + hunk.lines_in_hunk()).saturating_sub(1).min(end_line);
+            if first > last { continue; }
+            let id = hunk.final_commit_id();
+            let commit = commits.entry(id).or_insert_with(|| if id.is_zero() { None } else { repo.find_commit(id).ok().map(|c| GitCommitInfoMCP { body: None, ..commit_info(&c) }) }).clone();
+            hunks.push(GitBlameHunkMCP { start_line: first, end_line: last, commit, lines: lines[first - 1..last].iter().map(|l| l.to_string()).collect() });
+        }
+        debug!(hunks = hunks.len(), "Blamed file");
+        Ok(GitBlameResultMCP { repo_root: target.root.clone(), path: relative, hunks })
+    }).await
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path))]
+pub async fn mcp_git_branch_list(deps: &ToolDependencies, params: GitBranchListParamsMCP) -> Result<GitBranchListResultMCP, AppError> {
+    run_git(deps, move |config| {
+        let target = open_repo(config, params.path.as_deref())?;
+        let repo = &target.repo;
+        let current = current_branch(repo);
+        let filter = if params.include_remote { None } else { Some(BranchType::Local) };
+        let mut branches = Vec::new();
+        for entry in repo.branches(filter)? {
+            let (branch, kind) = entry?;
+            let Some(name) = branch.name()?.map(str::to_string) else { continue };
+            let tip = branch.get().peel_to_commit().ok();
+            let upstream = branch.upstream().ok();
+            let (ahead, behind) = match (&tip, upstream.as_ref().and_then(|u| u.get().target())) {
+                (Some(tip), Some(upstream_oid)) => repo.graph_ahead_behind(tip.id(), upstream_oid).map_or((None, None), |(a, b)| (Some(a), Some(b))),
+                _ => (None, None),
+            };
+            branches.push(GitBranchMCP {
+                current: kind == BranchType::Local && current.as_deref() == Some(name.as_str()),
+                remote: kind == BranchType::Remote,
+                commit: tip.as_ref().map(|c| c.id().to_string()),
+                subject: tip.as_ref().and_then(|c| c.summary().map(str::to_string)),
+                upstream: upstream.as_ref().and_then(|u| u.name().ok().flatten().map(str::to_string)),
+                ahead,
+                behind,
+                name,
+            });
+        }
+        branches.sort_by(|a, b| (a.remote, &a.name).cmp(&(b.remote, &b.name)));
+        Ok(GitBranchListResultMCP { repo_root: target.root.clone(), current, branches })
+    }).await
+}
+
+// Checkout must not lose work: staged, unstaged and conflicted changes refuse it. Untracked files
+// only refuse it if the checkout would overwrite them, which libgit2's safe mode checks.
+fn check_clean_tree(target: &GitTarget) -> Result<(), AppError> {
+    let status = git_status(target, false)?;
+    let dirty = status.staged.len() + status.unstaged.len() + status.conflicted.len();
+    if dirty > 0 {
+        return Err(AppError::GitError(format!("{} has {} uncommitted change(s); commit or restore them before switching branches", target.root.display(), dirty)));
+    }
+    Ok(())
+}
+
+fn checkout_branch(target: &GitTarget, name: &str) -> Result<String, AppError> {
+    let repo = &target.repo;
+    let branch = repo.find_branch(name, BranchType::Local).map_err(|_| AppError::InvalidInputArgument(format!("No local branch '{}'; create it with git_create_branch", name)))?;
+    let reference = branch.get().name().ok_or_else(|| AppError::GitError(format!("Branch '{}' has a non-UTF-8 name", name)))?.to_string();
+    let commit = branch.get().peel_to_commit()?;
+    check_clean_tree(target)?;
+    repo.checkout_tree(commit.as_object(), Some(git2::build::CheckoutBuilder::new().safe()))?;
+    repo.set_head(&reference)?;
+    info!(branch = %name, commit = %commit.id(), repo = %target.root.display(), "Checked out git branch");
+    Ok(commit.id().to_string())
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path, name = %params.name))]
+pub async fn mcp_git_create_branch(deps: &ToolDependencies, params: GitCreateBranchParamsMCP) -> Result<GitBranchResultMCP, AppError> {
+    run_git(deps, move |config| {
+        let mut target = open_repo_for_write(config, params.path.as_deref())?;
+        target.pathspec = None;
+        if !git2::Branch::name_is_valid(&params.name)? {
+            return Err(AppError::InvalidInputArgument(format!("'{}' is not a valid branch name", params.name)));
+        }
+        let repo = &target.repo;
+        let start_point = params.start_point.as_deref().unwrap_or("HEAD");
+        let start = repo.revparse_single(start_point).and_then(|object| object.peel_to_commit()).map_err(|e| AppError::InvalidInputArgument(format!("Unknown revision '{}': {}", start_point, e.message())))?;
+        if repo.find_branch(&params.name, BranchType::Local).is_ok() {
+            return Err(AppError::InvalidInputArgument(format!("Branch '{}' already exists", params.name)));
+        }
+        if params.checkout { check_clean_tree(&target)?; }
+        repo.branch(&params.name, &start, false)?;
+        info!(branch = %params.name, commit = %start.id(), repo = %target.root.display(), "Created git branch");
+        let commit = if params.checkout { checkout_branch(&target, &params.name)? } else { start.id().to_string() };
+        Ok(GitBranchResultMCP { repo_root: target.root.clone(), branch: params.name, commit, checked_out: params.checkout })
+    }).await
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path, branch = %params.branch))]
+pub async fn mcp_git_checkout(deps: &ToolDependencies, params: GitCheckoutParamsMCP) -> Result<GitBranchResultMCP, AppError> {
+    run_git(deps, move |config| {
+        let mut target = open_repo_for_write(config, params.path.as_deref())?;
+        target.pathspec = None;
+        let commit = checkout_branch(&target, &params.branch)?;
+        Ok(GitBranchResultMCP { repo_root: target.root.clone(), branch: params.branch, commit, checked_out: true })
+    }).await
+}
+
+/// Commits the working tree of the repository containing `root`, untracked files included and
+/// ignored ones not, before `operation` rewrites `files` files. HEAD, the index and the branches
+/// stay as they are; only a new ref under refs/mcp-checkpoints/ points at the commit. Returns
+/// None when git_checkpoint_min_files is 0 or not below `files`, or `root` is not in a repository.
+pub fn checkpoint_before(config: &Config, root: &Path, files: usize, operation: &str) -> Result<Option<GitCheckpointMCP>, AppError> {
+    let min_files = config.git_checkpoint_min_files;
+    if min_files == 0 || files <= min_files { return Ok(None); }
+    if Repository::discover(root).is_err_and(|e| e.code() == git2::ErrorCode::NotFound) { return Ok(None); }
+    let target = open_repo(config, Some(&root.to_string_lossy()))?;
+    let repo = &target.repo;
+
+    // Staged in memory only: without index.write() the index file stays untouched.
+    let mut index = repo.index()?;
+    index.add_all(std::iter::empty::<&str>(), IndexAddOption::DEFAULT, None)?;
+    index.update_all(std::iter::empty::<&str>(), None)?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    let signature = repo.signature().or_else(|_| git2::Signature::now("mcp-rg-editor", "mcp-rg-editor@localhost"))?;
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<&Commit> = parent.iter().collect();
+    let message = format!("Checkpoint before {} of {} files", operation, files);
+    let oid = repo.commit(None, &signature, &signature, &message, &tree, &parents)?;
+    let reference = format!("{}{}-{}", CHECKPOINT_REF_PREFIX, Utc::now().format("%Y%m%dT%H%M%S%.3fZ"), operation);
+    repo.reference(&reference, oid, false, &message)?;
+    info!(reference = %reference, commit = %oid, repo = %target.root.display(), "Created git checkpoint");
+    Ok(Some(GitCheckpointMCP { repo_root: target.root.clone(), reference, commit: oid.to_string() }))
+}
+
+type HunkRange = (u32, u32, u32, u32);
+
+fn hunk_range(hunk: &DiffHunk) -> HunkRange {
+    (hunk.old_start(), hunk.old_lines(), hunk.new_start(), hunk.new_lines())
+}
+
+fn parse_hunk_range(header: &str) -> Result<HunkRange, AppError> {
+    let caps = HUNK_RANGE.captures(header).ok_or_else(|| AppError::InvalidInputArgument(format!("'{}' is not a hunk header like \"@@ -12,7 +12,9 @@\"", header)))?;
+    let number = |i: usize| caps.get(i).map_or(Ok(1), |m| m.as_str().parse::<u32>()).map_err(|e| AppError::InvalidInputArgument(format!("Bad number in hunk header '{}': {}", header, e)));
+    Ok((number(1)?, number(2)?, number(3)?, number(4)?))
+}
+
+fn hunk_header(hunk: &DiffHunk) -> String {
+    String::from_utf8_lossy(hunk.header()).trim_end().to_string()
+}
+
+#[instrument(skip(deps, params), fields(path = %params.path, hunks = params.hunks.len()))]
+pub async fn mcp_git_stage_hunks(deps: &ToolDependencies, params: GitStageHunksParamsMCP) -> Result<GitStageHunksResultMCP, AppError> {
+    if params.hunks.is_empty() { return Err(AppError::InvalidInputArgument("git_stage_hunks needs at least one hunk".to_string())); }
+    run_git(deps, move |config| {
+        let target = open_repo_for_write(config, Some(&params.path))?;
+        let repo = &target.repo;
+        let relative = target.pathspec.clone().ok_or_else(|| AppError::InvalidInputArgument(format!("{} is the repository root, not a file", target.root.display())))?;
+        let wanted = params.hunks.iter().map(|h| parse_hunk_range(h)).collect::<Result<Vec<_>, _>>()?;
+
+        // The same diff git_diff shows by default: the working tree against the index.
+        let mut options = DiffOptions::new();
+        options.context_lines(params.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES)).pathspec(&relative).disable_pathspec_match(true);
+        let diff = repo.diff_index_to_workdir(None, Some(&mut options))?;
+        let mut available: Vec<(HunkRange, String)> = Vec::new();
+        if let Some(patch) = git2::Patch::from_diff(&diff, 0)? {
+            for index in 0..patch.num_hunks() {
+                let (hunk, _) = patch.hunk(index)?;
+                available.push((hunk_range(&hunk), hunk_header(&hunk)));
+            }
+        }
+        if available.is_empty() { return Err(AppError::InvalidInputArgument(format!("{} has no unstaged changes", relative))); }
+        // All or nothing: one stale header means the caller's view of the file is out of date.
+        if let Some(missing) = params.hunks.iter().zip(&wanted).find(|(_, range)| !available.iter().any(|(r, _)| r == *range)) {
+            let headers: Vec<&str> = available.iter().map(|(_, h)| h.as_str()).collect();
+            return Err(AppError::InvalidInputArgument(format!("No unstaged hunk '{}' in {}; its hunks are now: {}", missing.0, relative, headers.join(", "))));
+        }
+
+        let mut apply_options = ApplyOptions::new();
+        apply_options.hunk_callback(|hunk| hunk.is_some_and(|h| wanted.contains(&hunk_range(&h))));
+        repo.apply(&diff, ApplyLocation::Index, Some(&mut apply_options))?;
+        let (staged, remaining): (Vec<_>, Vec<_>) = available.into_iter().partition(|(range, _)| wanted.contains(range));
+        info!(path = %relative, staged = staged.len(), remaining = remaining.len(), "Staged git hunks");
+        Ok(GitStageHunksResultMCP {
+            repo_root: target.root.clone(),
+            path: relative,
+            staged_hunks: staged.into_iter().map(|(_, h)| h).collect(),
+            remaining_hunks: remaining.into_iter().map(|(_, h)| h).collect(),
+        })
+    }).await
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path))]
+pub async fn mcp_find_repo_root(deps: &ToolDependencies, params: FindRepoRootParamsMCP) -> Result<FindRepoRootResultMCP, AppError> {
+    run_git(deps, move |config| {
+        let path_str = params.path.clone().unwrap_or_else(|| config.files_root.to_string_lossy().into_owned());
+        let path = validate_and_normalize_path(&path_str, config, true, false)?;
+        // Outside a repository is an answer, not an error.
+        if Repository::discover(&path).is_err_and(|e| e.code() == git2::ErrorCode::NotFound) {
+            return Ok(FindRepoRootResultMCP { path, in_repository: false, repo_root: None, branch: None, head: None, detached: false, dirty: None });
+        }
+        let mut target = open_repo(config, Some(&path_str))?;
+        target.pathspec = None;
+        let repo = &target.repo;
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).include_ignored(false).exclude_submodules(true);
+        let dirty = !repo.statuses(Some(&mut options))?.is_empty();
+        let head = repo.head().ok().and_then(|head| head.target()).map(|oid| oid.to_string());
+        Ok(FindRepoRootResultMCP { path, in_repository: true, repo_root: Some(target.root.clone()), branch: current_branch(repo), head, detached: repo.head_detached().unwrap_or(false), dirty: Some(dirty) })
+    }).await
+}
@@ -0,0 +1,43 @@
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::utils::job_manager::{JobInfoMCP, JobStatus};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct JobIdParamsMCP {
+    /// The job_id a tool returned when started in the background.
+    pub job_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ListJobsParamsMCP {
+    /// Only jobs with this status.
+    pub status: Option<JobStatus>,
+    /// Only jobs of this kind, e.g. run_script.
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ListJobsResultMCP {
+    // Newest first, without results; get_job_status has them.
+    pub jobs: Vec<JobInfoMCP>,
+    pub running: usize,
+}
+
+pub async fn mcp_get_job_status(deps: &ToolDependencies, params: JobIdParamsMCP) -> Result<JobInfoMCP, AppError> {
+    deps.jobs.status(&params.job_id, deps.job_owner())
+}
+
+pub async fn mcp_cancel_job(deps: &ToolDependencies, params: JobIdParamsMCP) -> Result<JobInfoMCP, AppError> {
+    deps.jobs.cancel(&params.job_id, deps.job_owner())
+}
+
+pub async fn mcp_list_jobs(deps: &ToolDependencies, params: ListJobsParamsMCP) -> Result<ListJobsResultMCP, AppError> {
+    let jobs: Vec<JobInfoMCP> = deps.jobs.list(deps.job_owner()).into_iter()
+        .filter(|job| !params.status.is_some_and(|s| s != job.status) && !params.kind.as_deref().is_some_and(|k| k != job.kind))
+        .collect();
+    let running = jobs.iter().filter(|job| job.status == JobStatus::Running).count();
+    Ok(ListJobsResultMCP { jobs, running })
+}
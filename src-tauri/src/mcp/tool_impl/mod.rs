@@ -7,4 +7,12 @@ pub mod filesystem;
 pub mod ripgrep;
 pub mod terminal;
 pub mod process; 
-pub mod edit;
\ No newline at end of file
+pub mod system;
+pub mod config;
+pub mod edit;
+pub mod audit;
+pub mod script;
+pub mod jobs;
+pub mod snapshot;
+pub mod git;
+
@@ -1,13 +1,269 @@
+use crate::config::KillScope;
 use crate::error::AppError;
 use crate::mcp::handler::ToolDependencies;
+use crate::mcp::notifications::PROCESS_EXITED_NOTIFICATION;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use sysinfo::{Pid, Signal, ProcessRefreshKind, Uid, System as SysinfoSystem}; // Keep SysinfoSystem import
+use once_cell::sync::Lazy;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex as StdMutex;
+use tauri::Emitter;
+use tauri_plugin_shell::ShellExt;
+use crate::utils::sysinfo_cache::{ProcessDetail, SysinfoCache};
+use sysinfo::{Pid, Signal, ProcessRefreshKind, Uid, Users, System as SysinfoSystem}; // Keep SysinfoSystem import
 use tokio::sync::MutexGuard; // Keep MutexGuard
 use tracing::{debug, instrument, warn}; // Keep warn
 
 // --- MCP Specific Parameter Structs ---
-#[derive(Debug, Deserialize)]
-pub struct KillProcessParamsMCP { pub pid: usize }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct KillProcessParamsMCP {
+    /// Process ID (PID) to terminate.
+    pub pid: usize,
+    /// Signal to send first. On Windows every non-KILL signal is a graceful close (taskkill without /F); KILL is TerminateProcess.
+    #[serde(default)]
+    pub signal: KillSignalMCP,
+    /// Time to wait for exit after a non-KILL signal before escalating.
+    #[serde(default = "default_grace_period_ms_mcp", alias = "gracePeriodMs")]
+    pub grace_period_ms: u64,
+    /// Send KILL if the process is still alive after the grace period.
+    #[serde(default = "default_true_mcp_process")]
+    pub escalate: bool,
+    /// Also kill all descendants of the PID (deepest first).
+    #[serde(default)]
+    pub tree: bool,
+}
+fn default_grace_period_ms_mcp() -> u64 { 200 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum KillSignalMCP {
+    #[default]
+    #[serde(alias = "Term", alias = "SIGTERM", alias = "term")]
+    Term,
+    #[serde(alias = "Kill", alias = "SIGKILL", alias = "kill")]
+    Kill,
+    #[serde(alias = "Hup", alias = "SIGHUP", alias = "hup")]
+    Hup,
+    #[serde(alias = "Int", alias = "SIGINT", alias = "int")]
+    Int,
+}
+
+impl KillSignalMCP {
+    fn to_sysinfo_signal(self) -> Signal {
+        match self {
+            KillSignalMCP::Term => Signal::Term,
+            KillSignalMCP::Kill => Signal::Kill,
+            KillSignalMCP::Hup => Signal::Hangup,
+            KillSignalMCP::Int => Signal::Interrupt,
+        }
+    }
+
+    fn display_name(self) -> &'static str {
+        match self {
+            KillSignalMCP::Term => "SIGTERM",
+            KillSignalMCP::Kill => "SIGKILL",
+            KillSignalMCP::Hup => "SIGHUP",
+            KillSignalMCP::Int => "SIGINT",
+        }
+    }
+}
+
+const KILL_POLL_INTERVAL_MS: u64 = 50;
+const HARD_KILL_WAIT_MS: u64 = 100;
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetProcessDetailsParamsMCP {
+    /// Process ID (PID) to inspect.
+    pub pid: usize,
+}
+
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct ListProcessesParamsMCP {
+    /// Case-insensitive substring to match against process names.
+    #[serde(default, alias = "nameFilter")]
+    pub name_filter: Option<String>,
+    /// User name or numeric user id that must own the process.
+    #[serde(default, alias = "userFilter")]
+    pub user_filter: Option<String>,
+    /// Sort order (cpu and mem descending, pid ascending).
+    #[serde(default, alias = "sortBy")]
+    pub sort_by: ProcessSortKeyMCP,
+    /// Maximum number of processes to return.
+    #[serde(default = "default_list_processes_limit_mcp")]
+    pub limit: usize,
+    /// Only list processes spawned (directly or indirectly) by this server.
+    #[serde(default, alias = "onlyOurChildren")]
+    pub only_our_children: bool,
+}
+fn default_list_processes_limit_mcp() -> usize { 100 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessSortKeyMCP {
+    #[default]
+    Cpu,
+    #[serde(alias = "memory")]
+    Mem,
+    Pid,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct MonitorProcessParamsMCP {
+    /// PID to monitor. Provide this or session_id.
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// execute_command session whose process should be monitored.
+    #[serde(default, alias = "sessionId")]
+    pub session_id: Option<String>,
+    /// Sampling interval in milliseconds (minimum 100).
+    #[serde(default = "default_monitor_interval_ms_mcp", alias = "intervalMs")]
+    pub interval_ms: u64,
+    /// Total monitoring duration in milliseconds (maximum 300000). Stops early if the process exits.
+    #[serde(default = "default_monitor_duration_ms_mcp", alias = "durationMs")]
+    pub duration_ms: u64,
+    /// Aggregate usage over the process and all its descendants.
+    #[serde(default = "default_true_mcp_process", alias = "includeChildren")]
+    pub include_children: bool,
+}
+fn default_monitor_interval_ms_mcp() -> u64 { 1000 }
+fn default_monitor_duration_ms_mcp() -> u64 { 10_000 }
+const MONITOR_MIN_INTERVAL_MS: u64 = 100;
+const MONITOR_MAX_DURATION_MS: u64 = 300_000;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SetProcessPriorityParamsMCP {
+    /// Process ID (PID) to reprioritize.
+    pub pid: u32,
+    /// Unix nice level from -20 (highest) to 19 (lowest). Mapped to the nearest priority class on Windows.
+    #[serde(default)]
+    pub nice: Option<i32>,
+    /// Portable priority class (Windows priority class, or a nice level on Unix). Provide this or nice.
+    #[serde(default, alias = "priorityClass")]
+    pub priority_class: Option<PriorityClassMCP>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityClassMCP {
+    Idle,
+    BelowNormal,
+    Normal,
+    AboveNormal,
+    High,
+    Realtime,
+}
+
+impl PriorityClassMCP {
+    fn nice_level(self) -> i32 {
+        match self {
+            PriorityClassMCP::Idle => 19,
+            PriorityClassMCP::BelowNormal => 10,
+            PriorityClassMCP::Normal => 0,
+            PriorityClassMCP::AboveNormal => -5,
+            PriorityClassMCP::High => -10,
+            PriorityClassMCP::Realtime => -20,
+        }
+    }
+
+    fn from_nice_level(nice: i32) -> Self {
+        match nice {
+            n if n >= 15 => PriorityClassMCP::Idle,
+            n if n >= 5 => PriorityClassMCP::BelowNormal,
+            n if n > -5 => PriorityClassMCP::Normal,
+            n if n > -10 => PriorityClassMCP::AboveNormal,
+            n if n > -20 => PriorityClassMCP::High,
+            _ => PriorityClassMCP::Realtime,
+        }
+    }
+
+    // Names accepted by System.Diagnostics.ProcessPriorityClass.
+    fn windows_name(self) -> &'static str {
+        match self {
+            PriorityClassMCP::Idle => "Idle",
+            PriorityClassMCP::BelowNormal => "BelowNormal",
+            PriorityClassMCP::Normal => "Normal",
+            PriorityClassMCP::AboveNormal => "AboveNormal",
+            PriorityClassMCP::High => "High",
+            PriorityClassMCP::Realtime => "RealTime",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct WatchProcessParamsMCP {
+    /// Process ID (PID) to watch.
+    pub pid: u32,
+    /// How often to check whether the process is still running (minimum 100).
+    #[serde(default = "default_watch_poll_interval_ms_mcp", alias = "pollIntervalMs")]
+    pub poll_interval_ms: u64,
+    /// Optional time after which the watch gives up and notifies with timed_out: true. Watches indefinitely if unset.
+    #[serde(default, alias = "timeoutSecs")]
+    pub timeout_secs: Option<u64>,
+}
+fn default_watch_poll_interval_ms_mcp() -> u64 { 500 }
+const WATCH_MIN_POLL_INTERVAL_MS: u64 = 100;
+// How long to wait for a session's event loop to record the exit code after its process disappears.
+const WATCH_SESSION_EXIT_CODE_WAIT_MS: u64 = 1000;
+const WATCH_MAX_ACTIVE: usize = 64;
+// Running watchers keyed by (pid, start time, owner) so a repeated watch_process call reuses the one already polling that process.
+static ACTIVE_WATCHES: Lazy<StdMutex<HashMap<(u32, u64, Option<String>), String>>> = Lazy::new(|| StdMutex::new(HashMap::new()));
+
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct ListPortsParamsMCP {
+    /// Only report sockets bound to this local port.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Socket protocol to report.
+    #[serde(default)]
+    pub protocol: PortProtocolMCP,
+    /// Only listening TCP sockets and bound UDP sockets. Set false to include established connections.
+    #[serde(default = "default_true_mcp_process", alias = "listeningOnly")]
+    pub listening_only: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PortProtocolMCP {
+    #[default]
+    All,
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Deserialize, Default, JsonSchema)]
+pub struct GetProcessTreeParamsMCP {
+    /// Optional PID to root the tree at. Defaults to every process without a living parent.
+    #[serde(default, alias = "rootPid")]
+    pub root_pid: Option<u32>,
+    /// Root the tree at processes spawned by execute_command sessions of this server.
+    #[serde(default, alias = "sessionsOnly")]
+    pub sessions_only: bool,
+    /// Optional maximum depth below each root.
+    #[serde(default, alias = "maxDepth")]
+    pub max_depth: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct KillProcessesMatchingParamsMCP {
+    /// Regex matched against process names (or full command lines with match_cmdline).
+    pub pattern: String,
+    /// Match against the full command line instead of the process name.
+    #[serde(default, alias = "matchCmdline")]
+    pub match_cmdline: bool,
+    /// Only list what would be killed.
+    #[serde(default = "default_true_mcp_process", alias = "dryRun")]
+    pub dry_run: bool,
+    /// Must be true (with dry_run false) to actually send signals.
+    #[serde(default)]
+    pub confirm: bool,
+    /// Signal to send first; survivors are escalated to KILL after the grace period.
+    #[serde(default)]
+    pub signal: KillSignalMCP,
+    /// Time to wait for exit before escalating to KILL.
+    #[serde(default = "default_grace_period_ms_mcp", alias = "gracePeriodMs")]
+    pub grace_period_ms: u64,
+}
+fn default_true_mcp_process() -> bool { true }
 
 // --- MCP Specific Result Structs ---
 #[derive(Debug, Serialize)]
@@ -15,67 +271,803 @@ pub struct ProcessInfoMCP {
     pid: String, name: String, cpu_usage: f32, memory_mb: u64,
     command: String, status: String, user: Option<String>, start_time_epoch_secs: u64,
 }
+#[derive(Debug, Serialize)]
+pub struct ListProcessesResultMCP {
+    pub processes: Vec<ProcessInfoMCP>,
+    // Number of processes that passed the filters before `limit` was applied.
+    pub total_matched: usize,
+    pub truncated: bool,
+}
+
 #[derive(Debug, Serialize)]
 pub struct KillProcessResultMCP { pub success: bool, pub message: String }
 
+#[derive(Debug, Serialize)]
+pub struct MatchedProcessMCP { pub pid: u32, pub name: String, pub command: String }
+#[derive(Debug, Serialize)]
+pub struct KillOutcomeMCP { pub pid: u32, pub success: bool, pub message: String }
+#[derive(Debug, Serialize)]
+pub struct KillProcessesMatchingResultMCP {
+    pub pattern: String,
+    pub dry_run: bool,
+    pub matched: Vec<MatchedProcessMCP>,
+    pub results: Vec<KillOutcomeMCP>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcessTreeNodeMCP {
+    pub pid: u32,
+    pub name: String,
+    pub command: String,
+    pub cpu_usage: f32,
+    pub memory_mb: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub children: Vec<ProcessTreeNodeMCP>,
+}
+#[derive(Debug, Serialize)]
+pub struct GetProcessTreeResultMCP { pub roots: Vec<ProcessTreeNodeMCP>, pub total_processes: usize }
+
+#[derive(Debug, Serialize)]
+pub struct SetProcessPriorityResultMCP {
+    pub success: bool,
+    pub pid: u32,
+    pub applied: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatchProcessResultMCP {
+    pub watch_id: String,
+    pub pid: u32,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortOwnerMCP { pub pid: u32, pub name: Option<String> }
+#[derive(Debug, Serialize)]
+pub struct PortInfoMCP {
+    pub protocol: String,
+    pub local_address: String,
+    pub local_port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    pub owners: Vec<PortOwnerMCP>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcessSampleMCP {
+    pub elapsed_ms: u64,
+    pub cpu_usage: f32,
+    pub memory_mb: u64,
+    pub virtual_memory_mb: u64,
+    // Bytes read/written since the previous sample.
+    pub disk_read_bytes: u64,
+    pub disk_written_bytes: u64,
+    pub process_count: usize,
+}
+#[derive(Debug, Serialize)]
+pub struct MonitorProcessResultMCP {
+    pub pid: u32,
+    pub interval_ms: u64,
+    pub samples: Vec<ProcessSampleMCP>,
+    pub max_cpu_usage: f32,
+    pub avg_cpu_usage: f32,
+    pub peak_memory_mb: u64,
+    pub total_disk_read_bytes: u64,
+    pub total_disk_written_bytes: u64,
+    // True if the process exited before the requested duration elapsed.
+    pub exited: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcessDetailsMCP {
+    pub pid: u32,
+    pub parent_pid: Option<u32>,
+    pub name: String,
+    pub command_line: Vec<String>,
+    pub exe: Option<String>,
+    pub cwd: Option<String>,
+    pub status: String,
+    pub cpu_usage: f32,
+    pub memory_mb: u64,
+    pub virtual_memory_mb: u64,
+    pub start_time_epoch_secs: u64,
+    pub run_time_secs: u64,
+    pub user_id: Option<String>,
+    pub user_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_file_count: Option<usize>,
+    // None unless expose_process_environment is enabled in config.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<Vec<String>>,
+}
+
 fn format_uid_mcp(uid_opt: Option<&Uid>) -> Option<String> {
     uid_opt.map(|uid| uid.to_string())
 }
 
-#[instrument(skip(deps))]
-pub async fn mcp_list_processes(deps: &ToolDependencies) -> Result<Vec<ProcessInfoMCP>, AppError> {
-    let mut sys_guard: MutexGuard<'_, SysinfoSystem> = deps.sysinfo_state.lock().await;
-    sys_guard.refresh_processes_specifics(ProcessRefreshKind::everything());
-    debug!("MCP Tool: Listing {} system processes.", sys_guard.processes().len());
-    Ok(sys_guard.processes().iter().map(|(p, process)| ProcessInfoMCP {
+#[instrument(skip(deps, params))]
+pub async fn mcp_list_processes(deps: &ToolDependencies, params: ListProcessesParamsMCP) -> Result<ListProcessesResultMCP, AppError> {
+    if params.limit == 0 {
+        return Err(AppError::InvalidInputArgument("limit must be at least 1".to_string()));
+    }
+    let name_filter = params.name_filter.as_deref().map(str::to_lowercase).filter(|f| !f.is_empty());
+    // Resolve a user name to its uid once, rather than per process.
+    let user_filter_uids: Option<HashSet<String>> = params.user_filter.as_deref().filter(|f| !f.is_empty()).map(|filter| {
+        let users = Users::new_with_refreshed_list();
+        std::iter::once(filter.to_string())
+            .chain(users.list().iter().filter(|u| u.name() == filter).map(|u| u.id().to_string()))
+            .collect()
+    });
+
+    let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+    sys_guard.refresh_processes_cached(ProcessDetail::Full);
+    let our_descendants: Option<HashSet<Pid>> = params.only_our_children
+        .then(|| collect_descendants_mcp(&sys_guard, Pid::from_u32(std::process::id())).into_iter().collect());
+
+    let mut matched: Vec<(&Pid, &sysinfo::Process)> = sys_guard.processes().iter()
+        .filter(|(pid, _)| our_descendants.as_ref().map_or(true, |d| d.contains(*pid)))
+        .filter(|(_, p)| name_filter.as_ref().map_or(true, |f| p.name().to_lowercase().contains(f.as_str())))
+        .filter(|(_, p)| user_filter_uids.as_ref().map_or(true, |uids| format_uid_mcp(p.user_id()).map_or(false, |uid| uids.contains(&uid))))
+        .collect();
+    match params.sort_by {
+        ProcessSortKeyMCP::Cpu => matched.sort_by(|a, b| b.1.cpu_usage().total_cmp(&a.1.cpu_usage())),
+        ProcessSortKeyMCP::Mem => matched.sort_by(|a, b| b.1.memory().cmp(&a.1.memory())),
+        ProcessSortKeyMCP::Pid => matched.sort_by_key(|(pid, _)| **pid),
+    }
+
+    let total_matched = matched.len();
+    debug!(total = sys_guard.processes().len(), matched = total_matched, "MCP Tool: Listing system processes.");
+    let processes = matched.into_iter().take(params.limit).map(|(p, process)| ProcessInfoMCP {
         pid: p.as_u32().to_string(), name: process.name().to_string(), cpu_usage: process.cpu_usage(),
         memory_mb: process.memory() / (1024 * 1024), command: process.cmd().join(" "),
         status: process.status().to_string(), user: format_uid_mcp(process.user_id()),
         start_time_epoch_secs: process.start_time(),
-    }).collect())
+    }).collect();
+    Ok(ListProcessesResultMCP { processes, total_matched, truncated: total_matched > params.limit })
 }
 
-#[instrument(skip(deps, params), fields(pid = %params.pid))]
+/// Snapshot of the kill-scope policy, taken before the sysinfo lock so no std guard is held across awaits.
+struct KillPolicyMCP {
+    scope: KillScope,
+    allowed_names: Vec<String>,
+    session_pids: HashSet<Pid>,
+}
+
+impl KillPolicyMCP {
+    async fn load(deps: &ToolDependencies) -> Result<Self, AppError> {
+        let config = deps.config_state.snapshot();
+        let (scope, allowed_names) = (config.kill_scope, config.kill_allowed_process_names.clone());
+        let session_pids = if scope == KillScope::Any { HashSet::new() } else { // Scope for sessions_guard
+            let sessions_guard = deps.active_sessions_map.lock().await;
+            sessions_guard.values().filter(|s| deps.owns_session(s)).filter_map(|s| s.pid.map(Pid::from_u32)).collect()
+        };
+        Ok(Self { scope, allowed_names, session_pids })
+    }
+
+    /// PIDs that may be signalled under `Sessions` scope: every session process and its descendants.
+    fn session_scope_pids(&self, sys: &SysinfoSystem) -> HashSet<Pid> {
+        let mut allowed = self.session_pids.clone();
+        for session_pid in &self.session_pids {
+            allowed.extend(collect_descendants_mcp(sys, *session_pid));
+        }
+        allowed
+    }
+
+    fn check(&self, sys: &SysinfoSystem, session_scope: &HashSet<Pid>, pid: Pid) -> Result<(), AppError> {
+        if self.scope == KillScope::Any || session_scope.contains(&pid) {
+            return Ok(());
+        }
+        let name = sys.process(pid).map(|p| p.name().to_string()).unwrap_or_default();
+        if self.allowed_names.iter().any(|allowed| allowed.eq_ignore_ascii_case(&name)) {
+            return Ok(());
+        }
+        Err(AppError::ProcessNotAllowed(format!("PID {} ({}) was not started by this server and is not in kill_allowed_process_names.", pid, name)))
+    }
+}
+
+#[instrument(skip(deps, params), fields(pid = %params.pid, tree = %params.tree))]
 pub async fn mcp_kill_process(deps: &ToolDependencies, params: KillProcessParamsMCP) -> Result<KillProcessResultMCP, AppError> {
-    let mut sys_guard: MutexGuard<'_, SysinfoSystem> = deps.sysinfo_state.lock().await;
-    let pid_to_kill = Pid::from(params.pid);
-    sys_guard.refresh_process_specifics(pid_to_kill, ProcessRefreshKind::everything());
-    let proc_name = match sys_guard.process(pid_to_kill) {
-        Some(p) => p.name().to_string(),
-        None => return Ok(KillProcessResultMCP { success: false, message: format!("PID {} not found.", params.pid) }),
+    let policy = KillPolicyMCP::load(deps).await?;
+    let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+    sys_guard.refresh_processes_cached(ProcessDetail::Basic);
+    let session_scope = policy.session_scope_pids(&sys_guard);
+    policy.check(&sys_guard, &session_scope, Pid::from(params.pid))?;
+    if !params.tree {
+        return Ok(kill_pid_with_escalation_mcp(&deps.app_handle, &mut sys_guard, params.pid, params.signal, params.grace_period_ms, params.escalate).await);
+    }
+
+    let descendants = collect_descendants_mcp(&sys_guard, Pid::from(params.pid));
+    let mut failures = Vec::new();
+    for child_pid in &descendants {
+        if let Err(e) = policy.check(&sys_guard, &session_scope, *child_pid) {
+            failures.push(e.to_string());
+            continue;
+        }
+        let outcome = kill_pid_with_escalation_mcp(&deps.app_handle, &mut sys_guard, child_pid.as_u32() as usize, params.signal, params.grace_period_ms, params.escalate).await;
+        if !outcome.success { failures.push(outcome.message); }
+    }
+    let root_outcome = kill_pid_with_escalation_mcp(&deps.app_handle, &mut sys_guard, params.pid, params.signal, params.grace_period_ms, params.escalate).await;
+    let mut message = format!("{} Killed {} of {} descendant(s).", root_outcome.message, descendants.len() - failures.len(), descendants.len());
+    if !failures.is_empty() { message.push_str(&format!(" Failures: {}", failures.join("; "))); }
+    Ok(KillProcessResultMCP { success: root_outcome.success && failures.is_empty(), message })
+}
+
+fn children_by_parent_mcp(sys: &SysinfoSystem) -> HashMap<Pid, Vec<Pid>> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    for (pid, process) in sys.processes() {
+        if let Some(parent) = process.parent() {
+            if parent != *pid { children.entry(parent).or_default().push(*pid); }
+        }
+    }
+    for kids in children.values_mut() { kids.sort(); }
+    children
+}
+
+/// All descendants of `root`, deepest first, so killing them in order never orphans a subtree.
+fn collect_descendants_mcp(sys: &SysinfoSystem, root: Pid) -> Vec<Pid> {
+    let children = children_by_parent_mcp(sys);
+    let mut ordered = Vec::new();
+    let mut visited = HashSet::from([root]);
+    let mut stack = vec![(root, false)];
+    while let Some((pid, expanded)) = stack.pop() {
+        if expanded {
+            if pid != root { ordered.push(pid); }
+            continue;
+        }
+        stack.push((pid, true));
+        for kid in children.get(&pid).into_iter().flatten() {
+            if visited.insert(*kid) { stack.push((*kid, false)); }
+        }
+    }
+    ordered
+}
+
+/// Registers a background watcher that emits `notifications/process/exited` (and the
+/// `process_exited` Tauri event) when `pid` exits. The exit code is only known for processes
+/// started by execute_command sessions; the OS does not report it for unrelated processes.
+/// A second call for a process this client already watches returns the existing watch_id, and at most
+/// WATCH_MAX_ACTIVE watchers run at once.
+#[instrument(skip(deps, params), fields(pid = %params.pid))]
+pub async fn mcp_watch_process(deps: &ToolDependencies, params: WatchProcessParamsMCP) -> Result<WatchProcessResultMCP, AppError> {
+    let pid = Pid::from_u32(params.pid);
+    let (name, start_time) = { // Scope for sys_guard
+        let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+        sys_guard.refresh_process_specifics(pid, ProcessRefreshKind::new());
+        let process = sys_guard.process(pid).ok_or_else(|| AppError::ProcessError(format!("PID {} not found.", params.pid)))?;
+        (process.name().to_string(), process.start_time())
+    };
+    let session = { // Scope for sessions_guard
+        let sessions_guard = deps.active_sessions_map.lock().await;
+        sessions_guard.values().find(|s| s.pid == Some(params.pid) && deps.owns_session(s)).cloned()
     };
 
-    if let Some(p) = sys_guard.process(pid_to_kill) {
-        if p.kill_with(Signal::Term).unwrap_or(false) {
-            tokio::time::sleep(tokio::time::Duration::from_millis(200)).await; // Use tokio::time::Duration
-            sys_guard.refresh_process_specifics(pid_to_kill, ProcessRefreshKind::everything());
-            if sys_guard.process(pid_to_kill).is_none() { return Ok(KillProcessResultMCP { success: true, message: format!("PID {} ({}) terminated with SIGTERM.", params.pid, proc_name) }); }
-            debug!(pid = ?pid_to_kill, "Process still alive after SIGTERM.");
-        } else {
-            debug!(pid = ?pid_to_kill, "Sending SIGTERM failed or process already gone.");
+    let owner = deps.client.as_ref().map(|c| c.owner.clone());
+    let watch_key = (params.pid, start_time, owner.clone());
+    let watch_id = { // Scope for watches_guard
+        let mut watches_guard = ACTIVE_WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = watches_guard.get(&watch_key) {
+            let session_id = session.map(|s| s.session_id.clone());
+            let message = format!("Already watching PID {} ({}) as {}; the {} notification will be sent when it exits.", params.pid, name, existing, PROCESS_EXITED_NOTIFICATION);
+            return Ok(WatchProcessResultMCP { watch_id: existing.clone(), pid: params.pid, name, session_id, message });
         }
+        if watches_guard.len() >= WATCH_MAX_ACTIVE {
+            return Err(AppError::LimitExceeded { message: format!("{} process watches are already running; wait for one to finish", watches_guard.len()), limit: "watch_process".to_string(), max: WATCH_MAX_ACTIVE as u64, actual: watches_guard.len() as u64 });
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        watches_guard.insert(watch_key.clone(), id.clone());
+        id
+    };
+    let poll_interval = tokio::time::Duration::from_millis(params.poll_interval_ms.max(WATCH_MIN_POLL_INTERVAL_MS));
+    let deadline = params.timeout_secs.map(|secs| tokio::time::Instant::now() + tokio::time::Duration::from_secs(secs));
+    let sysinfo_state = deps.sysinfo_state.clone();
+    let notifier = deps.notifier.clone();
+    let app_handle = deps.app_handle.clone();
+    let session_for_task = session.clone();
+    let watch_id_for_task = watch_id.clone();
+    let name_for_task = name.clone();
+
+    tokio::spawn(async move {
+        let watch_started = tokio::time::Instant::now();
+        let timed_out = loop {
+            tokio::time::sleep(poll_interval).await;
+            let alive = { // Scope for sys_guard
+                let mut sys_guard = sysinfo_state.lock().await;
+                // A different start time means the PID was reused by a new process.
+                sys_guard.refresh_process_specifics(pid, ProcessRefreshKind::new())
+                    && sys_guard.process(pid).map_or(false, |p| p.start_time() == start_time)
+            };
+            if !alive { break false; }
+            if deadline.map_or(false, |d| tokio::time::Instant::now() >= d) { break true; }
+        };
+
+        let exit_code = match (&session_for_task, timed_out) {
+            (Some(session), false) => {
+                let wait_deadline = tokio::time::Instant::now() + tokio::time::Duration::from_millis(WATCH_SESSION_EXIT_CODE_WAIT_MS);
+                loop {
+                    let code = *session.exit_code.lock().await;
+                    if code.is_some() || tokio::time::Instant::now() >= wait_deadline { break code; }
+                    tokio::time::sleep(tokio::time::Duration::from_millis(KILL_POLL_INTERVAL_MS)).await;
+                }
+            }
+            _ => None,
+        };
+        let payload = json!({
+            "watch_id": watch_id_for_task,
+            "pid": pid.as_u32(),
+            "name": name_for_task,
+            "session_id": session_for_task.as_ref().map(|s| s.session_id.clone()),
+            "exit_code": exit_code,
+            "timed_out": timed_out,
+            "watched_ms": watch_started.elapsed().as_millis() as u64,
+        });
+        debug!(pid = ?pid, timed_out, exit_code = ?exit_code, "Process watch finished.");
+        ACTIVE_WATCHES.lock().unwrap_or_else(|e| e.into_inner()).remove(&watch_key);
+        notifier.notify_client(owner.as_deref(), PROCESS_EXITED_NOTIFICATION, payload.clone());
+        app_handle.emit("process_exited", payload).unwrap_or_else(|e| warn!("Emit process_exited failed: {}", e));
+    });
+
+    let session_id = session.map(|s| s.session_id.clone());
+    let message = format!("Watching PID {} ({}). A {} notification will be sent when it exits.", params.pid, name, PROCESS_EXITED_NOTIFICATION);
+    Ok(WatchProcessResultMCP { watch_id, pid: params.pid, name, session_id, message })
+}
+
+/// Renices on Unix and sets the priority class on Windows. Both go through the system tools
+/// (`renice`, PowerShell) like graceful kills do, which avoids a platform FFI dependency.
+#[instrument(skip(deps, params), fields(pid = %params.pid))]
+pub async fn mcp_set_process_priority(deps: &ToolDependencies, params: SetProcessPriorityParamsMCP) -> Result<SetProcessPriorityResultMCP, AppError> {
+    let class = match (params.nice, params.priority_class) {
+        (Some(nice), None) => {
+            if !(-20..=19).contains(&nice) {
+                return Err(AppError::InvalidInputArgument(format!("nice must be between -20 and 19, got {}", nice)));
+            }
+            None
+        }
+        (None, Some(class)) => Some(class),
+        _ => return Err(AppError::InvalidInputArgument("Provide exactly one of nice or priority_class.".to_string())),
+    };
+
+    // Reprioritizing is gated by the same policy as killing: both act on processes the server may not own.
+    let policy = KillPolicyMCP::load(deps).await?;
+    let proc_name = { // Scope for sys_guard
+        let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+        sys_guard.refresh_processes_cached(ProcessDetail::Basic);
+        let pid = Pid::from_u32(params.pid);
+        let name = sys_guard.process(pid).map(|p| p.name().to_string())
+            .ok_or_else(|| AppError::ProcessError(format!("PID {} not found.", params.pid)))?;
+        let session_scope = policy.session_scope_pids(&sys_guard);
+        policy.check(&sys_guard, &session_scope, pid)?;
+        name
+    };
+
+    let (program, args, applied) = if cfg!(windows) {
+        let class = class.unwrap_or_else(|| PriorityClassMCP::from_nice_level(params.nice.unwrap_or(0)));
+        let script = format!("(Get-Process -Id {}).PriorityClass = '{}'", params.pid, class.windows_name());
+        ("powershell".to_string(), vec!["-NoProfile".to_string(), "-NonInteractive".to_string(), "-Command".to_string(), script], format!("priority class {}", class.windows_name()))
+    } else {
+        let nice = params.nice.unwrap_or_else(|| class.map_or(0, PriorityClassMCP::nice_level));
+        ("renice".to_string(), vec!["-n".to_string(), nice.to_string(), "-p".to_string(), params.pid.to_string()], format!("nice {}", nice))
+    };
+
+    let output = deps.app_handle.shell().command(program.clone()).args(args).output().await
+        .map_err(|e| AppError::CommandExecutionError(format!("Failed to run {}: {}", program, e)))?;
+    if output.status.success() {
+        debug!(pid = params.pid, applied = %applied, "MCP Tool: Changed process priority.");
+        Ok(SetProcessPriorityResultMCP { success: true, pid: params.pid, message: format!("Set PID {} ({}) to {}.", params.pid, proc_name, applied), applied })
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        warn!(pid = params.pid, stderr = %stderr, "Changing process priority failed.");
+        // Raising priority usually needs elevated privileges; surface the tool's own explanation.
+        Ok(SetProcessPriorityResultMCP { success: false, pid: params.pid, message: format!("Failed to set PID {} ({}) to {}: {}", params.pid, proc_name, applied, stderr), applied })
+    }
+}
+
+#[instrument(skip(deps, params))]
+pub async fn mcp_list_ports(deps: &ToolDependencies, params: ListPortsParamsMCP) -> Result<Vec<PortInfoMCP>, AppError> {
+    use netstat2::{AddressFamilyFlags, ProtocolFlags, ProtocolSocketInfo, TcpState};
+
+    let proto_flags = match params.protocol {
+        PortProtocolMCP::All => ProtocolFlags::TCP | ProtocolFlags::UDP,
+        PortProtocolMCP::Tcp => ProtocolFlags::TCP,
+        PortProtocolMCP::Udp => ProtocolFlags::UDP,
+    };
+    // Socket enumeration walks /proc or the OS tables synchronously.
+    let sockets = tokio::task::spawn_blocking(move || netstat2::get_sockets_info(AddressFamilyFlags::IPV4 | AddressFamilyFlags::IPV6, proto_flags))
+        .await
+        .map_err(|e| AppError::ProcessError(format!("Socket enumeration task failed: {}", e)))?
+        .map_err(|e| AppError::ProcessError(format!("Failed to list sockets: {}", e)))?;
+
+    let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+    sys_guard.refresh_processes_cached(ProcessDetail::Basic);
+    let owners_for = |pids: &[u32]| -> Vec<PortOwnerMCP> {
+        pids.iter().map(|pid| PortOwnerMCP { pid: *pid, name: sys_guard.process(Pid::from_u32(*pid)).map(|p| p.name().to_string()) }).collect()
+    };
+
+    let mut ports: Vec<PortInfoMCP> = sockets.iter().filter_map(|socket| {
+        let info = match &socket.protocol_socket_info {
+            ProtocolSocketInfo::Tcp(tcp) => {
+                if params.listening_only && tcp.state != TcpState::Listen { return None; }
+                PortInfoMCP {
+                    protocol: "tcp".to_string(), local_address: tcp.local_addr.to_string(), local_port: tcp.local_port,
+                    remote_address: (tcp.state != TcpState::Listen).then(|| tcp.remote_addr.to_string()),
+                    remote_port: (tcp.state != TcpState::Listen).then_some(tcp.remote_port),
+                    state: Some(tcp.state.to_string()), owners: owners_for(&socket.associated_pids),
+                }
+            }
+            ProtocolSocketInfo::Udp(udp) => PortInfoMCP {
+                protocol: "udp".to_string(), local_address: udp.local_addr.to_string(), local_port: udp.local_port,
+                remote_address: None, remote_port: None, state: None, owners: owners_for(&socket.associated_pids),
+            },
+        };
+        params.port.map_or(true, |port| port == info.local_port).then_some(info)
+    }).collect();
+    ports.sort_by(|a, b| a.local_port.cmp(&b.local_port).then_with(|| a.protocol.cmp(&b.protocol)));
+    debug!(count = ports.len(), "MCP Tool: Listed sockets.");
+    Ok(ports)
+}
+
+/// Refreshes just the monitored process (and its descendants if requested) and aggregates one
+/// sample over them, or returns None if `pid` is gone. Descendants are discovered from the
+/// TTL-cached process table; only the tracked PIDs get a full refresh each interval.
+fn sample_process_mcp(sys: &mut SysinfoCache, pid: Pid, include_children: bool, elapsed_ms: u64) -> Option<ProcessSampleMCP> {
+    let mut tracked = vec![pid];
+    if include_children {
+        sys.refresh_processes_cached(ProcessDetail::Basic);
+        tracked.extend(collect_descendants_mcp(sys, pid));
+    }
+    let pids = sys.refresh_pids(&tracked, ProcessDetail::Full);
+    if !pids.contains(&pid) { return None; }
+    let mut sample = ProcessSampleMCP { elapsed_ms, cpu_usage: 0.0, memory_mb: 0, virtual_memory_mb: 0, disk_read_bytes: 0, disk_written_bytes: 0, process_count: 0 };
+    let (mut memory, mut virtual_memory) = (0u64, 0u64);
+    for process in pids.iter().filter_map(|p| sys.process(*p)) {
+        let disk = process.disk_usage();
+        sample.cpu_usage += process.cpu_usage();
+        memory += process.memory();
+        virtual_memory += process.virtual_memory();
+        sample.disk_read_bytes += disk.read_bytes;
+        sample.disk_written_bytes += disk.written_bytes;
+        sample.process_count += 1;
+    }
+    sample.memory_mb = memory / (1024 * 1024);
+    sample.virtual_memory_mb = virtual_memory / (1024 * 1024);
+    Some(sample)
+}
+
+#[instrument(skip(deps, params))]
+pub async fn mcp_monitor_process(deps: &ToolDependencies, params: MonitorProcessParamsMCP) -> Result<MonitorProcessResultMCP, AppError> {
+    let pid_num = match (params.pid, &params.session_id) {
+        (Some(pid), None) => pid,
+        (None, Some(session_id)) => { // Scope for sessions_guard
+            let sessions_guard = deps.active_sessions_map.lock().await;
+            let session = sessions_guard.get(session_id).filter(|s| deps.owns_session(s)).ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+            session.pid.ok_or_else(|| AppError::ProcessError(format!("Session {} has no known PID.", session_id)))?
+        }
+        _ => return Err(AppError::InvalidInputArgument("Provide exactly one of pid or session_id.".to_string())),
+    };
+    let interval_ms = params.interval_ms.max(MONITOR_MIN_INTERVAL_MS);
+    // Ends with the samples taken so far at the call's deadline.
+    let duration_ms = params.duration_ms.min(MONITOR_MAX_DURATION_MS).min(deps.remaining_budget().map_or(u64::MAX, |left| left.as_millis() as u64));
+    let pid = Pid::from_u32(pid_num);
+
+    { // Prime CPU and disk counters; the first refresh only establishes a baseline.
+        let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+        if sample_process_mcp(&mut sys_guard, pid, params.include_children, 0).is_none() {
+            return Err(AppError::ProcessError(format!("PID {} not found.", pid_num)));
+        }
+    }
+
+    let started = tokio::time::Instant::now();
+    let mut samples = Vec::new();
+    let mut exited = false;
+    loop {
+        // The sysinfo lock is only held while sampling so other process tools are not blocked.
+        tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_millis(interval_ms)) => {}
+            _ = deps.cancel.cancelled() => return Err(AppError::Cancelled(format!("monitor_process for PID {}", pid_num))),
+        }
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        let sample = {
+            let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+            sample_process_mcp(&mut sys_guard, pid, params.include_children, elapsed_ms)
+        };
+        match sample {
+            Some(s) => samples.push(s),
+            None => { exited = true; break; }
+        }
+        if elapsed_ms >= duration_ms { break; }
+    }
+
+    let max_cpu_usage = samples.iter().map(|s| s.cpu_usage).fold(0.0, f32::max);
+    let avg_cpu_usage = if samples.is_empty() { 0.0 } else { samples.iter().map(|s| s.cpu_usage).sum::<f32>() / samples.len() as f32 };
+    let peak_memory_mb = samples.iter().map(|s| s.memory_mb).max().unwrap_or(0);
+    let total_disk_read_bytes = samples.iter().map(|s| s.disk_read_bytes).sum();
+    let total_disk_written_bytes = samples.iter().map(|s| s.disk_written_bytes).sum();
+    debug!(pid = pid_num, samples = samples.len(), exited, "MCP Tool: Finished monitoring process.");
+    Ok(MonitorProcessResultMCP { pid: pid_num, interval_ms, samples, max_cpu_usage, avg_cpu_usage, peak_memory_mb, total_disk_read_bytes, total_disk_written_bytes, exited })
+}
+
+fn build_tree_node_mcp(
+    sys: &SysinfoSystem,
+    children: &HashMap<Pid, Vec<Pid>>,
+    session_pids: &HashMap<u32, String>,
+    pid: Pid,
+    depth: usize,
+    max_depth: Option<usize>,
+    visited: &mut HashSet<Pid>,
+) -> Option<ProcessTreeNodeMCP> {
+    let process = sys.process(pid)?;
+    if !visited.insert(pid) { return None; }
+    let child_nodes = if max_depth.map_or(true, |max| depth < max) {
+        children.get(&pid).into_iter().flatten()
+            .filter_map(|kid| build_tree_node_mcp(sys, children, session_pids, *kid, depth + 1, max_depth, visited))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Some(ProcessTreeNodeMCP {
+        pid: pid.as_u32(), name: process.name().to_string(), command: process.cmd().join(" "),
+        cpu_usage: process.cpu_usage(), memory_mb: process.memory() / (1024 * 1024),
+        session_id: session_pids.get(&pid.as_u32()).cloned(), children: child_nodes,
+    })
+}
+
+#[instrument(skip(deps, params))]
+pub async fn mcp_get_process_tree(deps: &ToolDependencies, params: GetProcessTreeParamsMCP) -> Result<GetProcessTreeResultMCP, AppError> {
+    let session_pids: HashMap<u32, String> = { // Scope for sessions_guard
+        let sessions_guard = deps.active_sessions_map.lock().await;
+        sessions_guard.values().filter(|s| deps.owns_session(s)).filter_map(|s| s.pid.map(|pid| (pid, s.session_id.clone()))).collect()
+    };
+
+    let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+    sys_guard.refresh_processes_cached(ProcessDetail::Full);
+    let children = children_by_parent_mcp(&sys_guard);
+
+    let mut root_pids: Vec<Pid> = if let Some(root_pid) = params.root_pid {
+        let pid = Pid::from_u32(root_pid);
+        if sys_guard.process(pid).is_none() {
+            return Err(AppError::ProcessError(format!("PID {} not found.", root_pid)));
+        }
+        vec![pid]
+    } else if params.sessions_only {
+        session_pids.keys().map(|pid| Pid::from_u32(*pid)).filter(|pid| sys_guard.process(*pid).is_some()).collect()
+    } else {
+        sys_guard.processes().iter()
+            .filter(|(_, p)| p.parent().map_or(true, |parent| sys_guard.process(parent).is_none()))
+            .map(|(pid, _)| *pid)
+            .collect()
+    };
+    root_pids.sort();
+
+    let mut visited = HashSet::new();
+    let roots: Vec<ProcessTreeNodeMCP> = root_pids.into_iter()
+        .filter_map(|pid| build_tree_node_mcp(&sys_guard, &children, &session_pids, pid, 0, params.max_depth, &mut visited))
+        .collect();
+    debug!(roots = roots.len(), total = visited.len(), "MCP Tool: Built process tree.");
+    Ok(GetProcessTreeResultMCP { roots, total_processes: visited.len() })
+}
+
+#[instrument(skip(deps, params), fields(pattern = %params.pattern, dry_run = %params.dry_run))]
+pub async fn mcp_kill_processes_matching(deps: &ToolDependencies, params: KillProcessesMatchingParamsMCP) -> Result<KillProcessesMatchingResultMCP, AppError> {
+    let matcher = regex::Regex::new(&params.pattern)
+        .map_err(|e| AppError::InvalidInputArgument(format!("Invalid process pattern regex '{}': {}", params.pattern, e)))?;
+    if matcher.is_match("") {
+        return Err(AppError::InvalidInputArgument(format!("Pattern '{}' matches an empty string, so it matches every process; use a more specific pattern", params.pattern)));
+    }
+    if !params.dry_run && !params.confirm {
+        return Err(AppError::InvalidInputArgument("Refusing to kill without confirm: true. Run with dry_run: true first to review the matched processes.".to_string()));
+    }
+
+    let policy = KillPolicyMCP::load(deps).await?;
+    let session_shells = running_session_pids(deps).await;
+    let mut matched: Vec<MatchedProcessMCP> = {
+        let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+        sys_guard.refresh_processes_cached(ProcessDetail::Full);
+        let protected = protected_pids(&sys_guard, &session_shells);
+        let session_scope = policy.session_scope_pids(&sys_guard);
+        let candidates: Vec<MatchedProcessMCP> = sys_guard.processes().iter()
+            .filter(|(pid, _)| !protected.contains(&pid.as_u32()))
+            .map(|(pid, process)| MatchedProcessMCP { pid: pid.as_u32(), name: process.name().to_string(), command: process.cmd().join(" ") })
+            .collect();
+        let haystack = |m: &MatchedProcessMCP| if params.match_cmdline { m.command.clone() } else { m.name.clone() };
+        if candidates.len() > 1 && candidates.iter().all(|m| matcher.is_match(&haystack(m))) {
+            return Err(AppError::InvalidInputArgument(format!("Pattern '{}' matches every process on the system; use a more specific pattern", params.pattern)));
+        }
+        // Processes outside the kill scope are never matched, so dry runs show exactly what would be killed.
+        candidates.into_iter()
+            .filter(|m| matcher.is_match(&haystack(m)))
+            .filter(|m| policy.check(&sys_guard, &session_scope, Pid::from_u32(m.pid)).is_ok())
+            .collect()
+    };
+    matched.sort_by_key(|m| m.pid);
+
+    if params.dry_run {
+        let message = format!("Dry run: {} process(es) match '{}'. Re-run with dry_run: false and confirm: true to kill them.", matched.len(), params.pattern);
+        return Ok(KillProcessesMatchingResultMCP { pattern: params.pattern, dry_run: true, matched, results: Vec::new(), message });
+    }
+
+    let mut results = Vec::with_capacity(matched.len());
+    for m in &matched {
+        if deps.cancel.is_cancelled() || deps.deadline.is_some_and(|d| tokio::time::Instant::now() >= d) {
+            warn!(pattern = %params.pattern, remaining = matched.len() - results.len(), "kill_processes_matching stopped early");
+            break;
+        }
+        // Locked per PID, so other process tools can run during each grace period.
+        let mut sys_guard = deps.sysinfo_state.lock().await;
+        let outcome = kill_pid_with_escalation_mcp(&deps.app_handle, &mut sys_guard, m.pid as usize, params.signal, params.grace_period_ms, true).await;
+        drop(sys_guard);
+        results.push(KillOutcomeMCP { pid: m.pid, success: outcome.success, message: outcome.message });
+    }
+    let killed_count = results.iter().filter(|r| r.success).count();
+    warn!(pattern = %params.pattern, matched = matched.len(), killed = killed_count, "MCP Tool: kill_processes_matching sent signals");
+    let mut message = format!("Terminated {} of {} matching process(es).", killed_count, matched.len());
+    if results.len() < matched.len() {
+        message.push_str(&format!(" Stopped before the other {}: the call was cancelled or ran out of time.", matched.len() - results.len()));
     }
+    Ok(KillProcessesMatchingResultMCP { pattern: params.pattern, dry_run: false, matched, results, message })
+}
+
+/// PIDs of this server's command sessions that are still running; their shells are stopped
+/// with force_terminate_session, never by a pattern.
+async fn running_session_pids(deps: &ToolDependencies) -> HashSet<u32> {
+    let sessions = deps.active_sessions_map.lock().await;
+    let mut pids = HashSet::new();
+    for session in sessions.values() {
+        if let Some(pid) = session.pid {
+            if session.exit_code.lock().await.is_none() { pids.insert(pid); }
+        }
+    }
+    pids
+}
+
+/// Never signalled by kill_processes_matching, whatever the kill scope: init, this server and
+/// every process above it (the shell, terminal or desktop session that started it), and `session_shells`.
+fn protected_pids(sys: &SysinfoCache, session_shells: &HashSet<u32>) -> HashSet<u32> {
+    let mut protected: HashSet<u32> = session_shells.clone();
+    protected.insert(1);
+    let mut current = Some(Pid::from_u32(std::process::id()));
+    while let Some(pid) = current {
+        if !protected.insert(pid.as_u32()) { break; }
+        current = sys.process(pid).and_then(|p| p.parent());
+    }
+    protected
+}
+
+fn is_pid_alive_mcp(sys_guard: &mut MutexGuard<'_, SysinfoCache>, pid: Pid) -> bool {
+    sys_guard.refresh_process_specifics(pid, ProcessRefreshKind::new())
+}
 
+/// Polls until the process exits or `wait_ms` elapses; returns true if it exited.
+async fn wait_for_exit_mcp(sys_guard: &mut MutexGuard<'_, SysinfoCache>, pid: Pid, wait_ms: u64) -> bool {
+    let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_millis(wait_ms);
+    loop {
+        if !is_pid_alive_mcp(sys_guard, pid) { return true; }
+        if tokio::time::Instant::now() >= deadline { return false; }
+        tokio::time::sleep(tokio::time::Duration::from_millis(KILL_POLL_INTERVAL_MS)).await;
+    }
+}
+
+/// Asks the process to exit. Unix delivers the signal directly; Windows has no signals, so
+/// every non-KILL request becomes `taskkill /PID` (WM_CLOSE to the process's windows).
+async fn send_graceful_signal_mcp(app_handle: &crate::AppHandle, sys_guard: &mut MutexGuard<'_, SysinfoCache>, pid: Pid, signal: KillSignalMCP) -> bool {
+    if cfg!(windows) {
+        match app_handle.shell().command("taskkill").args(["/PID".to_string(), pid.as_u32().to_string()]).output().await {
+            Ok(output) => output.status.success(),
+            Err(e) => { warn!(pid = ?pid, error = %e, "Failed to run taskkill for graceful termination"); false }
+        }
+    } else {
+        sys_guard.process(pid).and_then(|p| p.kill_with(signal.to_sysinfo_signal())).unwrap_or(false)
+    }
+}
+
+/// Sends `signal`, waits up to `grace_period_ms` for the process to exit, then escalates to
+/// SIGKILL/TerminateProcess if `escalate` is set and the process is still alive.
+async fn kill_pid_with_escalation_mcp(
+    app_handle: &crate::AppHandle,
+    sys_guard: &mut MutexGuard<'_, SysinfoCache>,
+    pid_num: usize,
+    signal: KillSignalMCP,
+    grace_period_ms: u64,
+    escalate: bool,
+) -> KillProcessResultMCP {
+    let pid_to_kill = Pid::from(pid_num);
     sys_guard.refresh_process_specifics(pid_to_kill, ProcessRefreshKind::everything());
-    if let Some(p) = sys_guard.process(pid_to_kill) {
-        if p.kill_with(Signal::Kill).unwrap_or(false) {
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await; // Use tokio::time::Duration
-            sys_guard.refresh_process_specifics(pid_to_kill, ProcessRefreshKind::everything());
-            if sys_guard.process(pid_to_kill).is_none() { return Ok(KillProcessResultMCP { success: true, message: format!("PID {} ({}) terminated with SIGKILL.", params.pid, proc_name) }); }
-            else {
-                warn!(pid = ?pid_to_kill, "Process still running after SIGKILL.");
-                return Ok(KillProcessResultMCP { success: false, message: format!("Sent SIGKILL to PID {} ({}), but it may still be running.", params.pid, proc_name) });
+    let proc_name = match sys_guard.process(pid_to_kill) {
+        Some(p) => p.name().to_string(),
+        None => return KillProcessResultMCP { success: false, message: format!("PID {} not found.", pid_num) },
+    };
+    // Whatever happens below, the cached process table no longer reflects reality.
+    sys_guard.invalidate_process_table();
+
+    if signal != KillSignalMCP::Kill {
+        if send_graceful_signal_mcp(app_handle, sys_guard, pid_to_kill, signal).await {
+            if wait_for_exit_mcp(sys_guard, pid_to_kill, grace_period_ms).await {
+                return KillProcessResultMCP { success: true, message: format!("PID {} ({}) exited after {}.", pid_num, proc_name, signal.display_name()) };
             }
+            debug!(pid = ?pid_to_kill, signal = signal.display_name(), "Process still alive after grace period.");
         } else {
-            warn!(pid = ?pid_to_kill, "Failed to send SIGKILL.");
-            sys_guard.refresh_process_specifics(pid_to_kill, ProcessRefreshKind::everything());
-            if sys_guard.process(pid_to_kill).is_none() {
-                return Ok(KillProcessResultMCP { success: true, message: format!("PID {} ({}) no longer found after failed SIGKILL, likely terminated.", params.pid, proc_name) });
+            debug!(pid = ?pid_to_kill, signal = signal.display_name(), "Sending graceful signal failed or process already gone.");
+            if !is_pid_alive_mcp(sys_guard, pid_to_kill) {
+                return KillProcessResultMCP { success: true, message: format!("PID {} ({}) no longer found, likely terminated.", pid_num, proc_name) };
             }
-            return Ok(KillProcessResultMCP { success: false, message: format!("Failed to send SIGKILL to PID {} ({}).", params.pid, proc_name) });
         }
+        if !escalate {
+            return KillProcessResultMCP { success: false, message: format!("Sent {} to PID {} ({}), still running after {} ms; escalation disabled.", signal.display_name(), pid_num, proc_name, grace_period_ms) };
+        }
+    }
+
+    let hard_kill_sent = sys_guard.process(pid_to_kill).map(|p| p.kill_with(Signal::Kill).unwrap_or_else(|| p.kill())).unwrap_or(false);
+    if !hard_kill_sent {
+        warn!(pid = ?pid_to_kill, "Failed to send SIGKILL.");
+        if !is_pid_alive_mcp(sys_guard, pid_to_kill) {
+            return KillProcessResultMCP { success: true, message: format!("PID {} ({}) no longer found after failed SIGKILL, likely terminated.", pid_num, proc_name) };
+        }
+        return KillProcessResultMCP { success: false, message: format!("Failed to send SIGKILL to PID {} ({}).", pid_num, proc_name) };
+    }
+    if wait_for_exit_mcp(sys_guard, pid_to_kill, HARD_KILL_WAIT_MS).await {
+        KillProcessResultMCP { success: true, message: format!("PID {} ({}) terminated with SIGKILL.", pid_num, proc_name) }
     } else {
-        debug!(pid = ?pid_to_kill, "Process not found before SIGKILL, assuming terminated.");
-        return Ok(KillProcessResultMCP { success: true, message: format!("PID {} ({}) no longer found, likely terminated.", params.pid, proc_name) });
+        warn!(pid = ?pid_to_kill, "Process still running after SIGKILL.");
+        KillProcessResultMCP { success: false, message: format!("Sent SIGKILL to PID {} ({}), but it may still be running.", pid_num, proc_name) }
     }
-}
\ No newline at end of file
+}
+
+/// Counts entries in /proc/<pid>/fd. Only Linux exposes this cheaply; elsewhere the field is omitted.
+fn count_open_files_mcp(pid: u32) -> Option<usize> {
+    #[cfg(target_os = "linux")] {
+        std::fs::read_dir(format!("/proc/{}/fd", pid)).ok().map(|entries| entries.count())
+    }
+    #[cfg(not(target_os = "linux"))] {
+        let _ = pid;
+        None
+    }
+}
+
+#[instrument(skip(deps, params), fields(pid = %params.pid))]
+pub async fn mcp_get_process_details(deps: &ToolDependencies, params: GetProcessDetailsParamsMCP) -> Result<ProcessDetailsMCP, AppError> {
+    let expose_environment = {
+        let config = deps.config_state.snapshot();
+        config.expose_process_environment
+    };
+
+    let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+    let pid = Pid::from(params.pid);
+    sys_guard.refresh_process_specifics(pid, ProcessRefreshKind::everything());
+    let process = sys_guard.process(pid).ok_or_else(|| AppError::ProcessError(format!("PID {} not found.", params.pid)))?;
+
+    let user_id = process.user_id().cloned();
+    let details = ProcessDetailsMCP {
+        pid: pid.as_u32(),
+        parent_pid: process.parent().map(|p| p.as_u32()),
+        name: process.name().to_string(),
+        command_line: process.cmd().to_vec(),
+        exe: process.exe().map(|p| p.to_string_lossy().into_owned()),
+        cwd: process.cwd().map(|p| p.to_string_lossy().into_owned()),
+        status: process.status().to_string(),
+        cpu_usage: process.cpu_usage(),
+        memory_mb: process.memory() / (1024 * 1024),
+        virtual_memory_mb: process.virtual_memory() / (1024 * 1024),
+        start_time_epoch_secs: process.start_time(),
+        run_time_secs: process.run_time(),
+        user_id: format_uid_mcp(user_id.as_ref()),
+        user_name: None,
+        open_file_count: count_open_files_mcp(pid.as_u32()),
+        environment: if expose_environment { Some(process.environ().to_vec()) } else { None },
+    };
+    drop(sys_guard);
+
+    let user_name = user_id.and_then(|uid| {
+        let users = Users::new_with_refreshed_list();
+        users.get_user_by_id(&uid).map(|u| u.name().to_string())
+    });
+    debug!(pid = details.pid, "MCP Tool: Collected process details.");
+    Ok(ProcessDetailsMCP { user_name, ..details })
+}
@@ -1,39 +1,61 @@
+use crate::config::WorkspaceRoot;
 use crate::error::AppError;
 use crate::mcp::handler::ToolDependencies;
-use crate::utils::path_utils::validate_and_normalize_path;
+use crate::mcp::tool_impl::git::{search_scope, GitScope};
+use crate::utils::limits::SearchSlot;
+use crate::utils::path_utils::{check_workspace_rules, denied_glob_set, validate_and_normalize_path};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use tauri_plugin_shell::ShellExt; 
-use tokio::time::{timeout, Duration};
+use globset::GlobSet;
+use std::path::{Path, PathBuf};
+use tauri_plugin_shell::{process::CommandEvent, ShellExt};
 use tracing::{debug, error, instrument, warn};
 
 // --- MCP Specific Parameter Structs ---
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct SearchCodeParamsMCP {
+    /// Ripgrep search pattern.
     pub pattern: String,
+    /// Directory to search. Default: FILES_ROOT.
     #[serde(default)]
     pub path: String,
+    /// Treat pattern as literal.
     #[serde(default, alias = "fixedStrings")]
     pub fixed_strings: bool,
+    /// Case-insensitive search.
     #[serde(default, alias = "ignoreCase")]
     pub ignore_case: bool,
+    /// Case-sensitive search.
     #[serde(default)]
     pub case_sensitive: bool,
+    /// Include line numbers.
     #[serde(default = "default_true_mcp_rg")]
     pub line_numbers: bool,
+    /// Context lines around match.
     #[serde(alias = "contextLines")]
     pub context_lines: Option<usize>,
+    /// Glob to filter files (e.g., "*.rs").
     #[serde(default, alias = "filePattern")]
     pub file_pattern: Option<String>,
+    /// Max search depth.
     #[serde(alias = "maxDepth")]
     pub max_depth: Option<usize>,
+    /// Max matches to return.
     #[serde(default = "default_usize_1000_mcp_rg")]
     pub max_results: usize,
+    /// Search hidden files/dirs.
     #[serde(default, alias = "includeHidden")]
     pub include_hidden: bool,
+    /// Timeout in ms. Default: the server search timeout.
     #[serde(default, rename = "timeoutMs")]
     pub timeout_ms: Option<u64>,
+    /// Only search files tracked by git in the repository containing path, skipping untracked build output.
+    #[serde(default, alias = "trackedOnly")]
+    pub tracked_only: bool,
+    /// Only search files changed since this revision (e.g. main or HEAD~5), staged and unstaged changes included.
+    #[serde(default, alias = "changedSince")]
+    pub changed_since: Option<String>,
 }
 fn default_true_mcp_rg() -> bool { true }
 fn default_usize_1000_mcp_rg() -> usize { 1000 }
@@ -57,6 +79,8 @@ pub struct SearchCodeResultMCP {
     pub matches: Vec<RipgrepMatchMCP>,
     pub stats: SearchStatsMCP,
     pub timed_out: bool,
+    // Matches stopped at max_results or limits.max_output_bytes.
+    pub truncated: bool,
     pub error_message: Option<String>,
 }
 
@@ -72,15 +96,17 @@ pub async fn mcp_search_code(
     let rg_exe_path = get_rg_path_mcp()?;
     debug!("MCP Tool: search_code with params: {:?}", params);
 
-    let (search_path_validated, files_root_for_stripping) = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock: {}", e)))?;
+    let (search_path_validated, files_root_for_stripping, denied_globs, denied_set, limits, workspaces) = {
+        let config = deps.config_state.snapshot();
         let search_dir_str = if params.path.is_empty() || params.path == "." {
-            config_guard.files_root.to_str().unwrap_or(".").to_string()
+            config.files_root.to_str().unwrap_or(".").to_string()
         } else { params.path.clone() };
-        let spv = validate_and_normalize_path(&search_dir_str, &*config_guard, true, false)?;
-        let frfs = config_guard.files_root.clone();
-        (spv, frfs)
-    }; // config_guard dropped here
+        let spv = validate_and_normalize_path(&search_dir_str, &config, true, false)?;
+        let frfs = config.files_root.clone();
+        (spv, frfs, config.denied_globs.clone(), denied_glob_set(&config)?, config.limits.clone(), config.workspaces.clone())
+    };
+    let _search_slot = SearchSlot::acquire(&limits)?;
+    let scope = search_scope(deps, &search_path_validated, params.tracked_only, params.changed_since.clone()).await?;
 
 
     let mut rg_args = Vec::new();
@@ -91,6 +117,8 @@ pub async fn mcp_search_code(
     else if params.ignore_case { rg_args.push("-i".to_string()); }
     if let Some(context) = params.context_lines { if context > 0 { rg_args.push("-C".to_string()); rg_args.push(context.to_string()); }}
     if let Some(glob) = &params.file_pattern { if !glob.is_empty() { rg_args.push("-g".to_string()); rg_args.push(glob.clone()); }}
+    // Later -g flags win in rg, so these exclusions override the caller's file_pattern.
+    for denied in &denied_globs { rg_args.push("-g".to_string()); rg_args.push(format!("!{}", denied)); }
     if let Some(depth) = params.max_depth { rg_args.push("--max-depth".to_string()); rg_args.push(depth.to_string()); }
     rg_args.push("--max-count".to_string()); rg_args.push(params.max_results.to_string());
     if params.include_hidden { rg_args.push("--hidden".to_string()); }
@@ -98,76 +126,147 @@ pub async fn mcp_search_code(
     rg_args.push(search_path_validated.to_string_lossy().to_string());
 
     let start_time = std::time::Instant::now();
-    let command_future = deps.app_handle.shell().command(rg_exe_path.to_string_lossy().to_string())
+    // Spawned rather than run with .output() so a timeout or cancellation can kill rg.
+    let (mut rx, rg_child) = deps.app_handle.shell().command(rg_exe_path.to_string_lossy().to_string())
         .args(rg_args.clone())
         .current_dir(&search_path_validated)
-        .output();
-    
-    let timeout_duration = Duration::from_millis(params.timeout_ms.unwrap_or(30000));
-
-    match timeout(timeout_duration, command_future).await {
-        Ok(Ok(output)) => {
-            let elapsed_ms = start_time.elapsed().as_millis() as u64;
-            let mut error_message_opt: Option<String> = None;
-
-            if !output.status.success() && output.status.code() != Some(1) {
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                error!("Ripgrep command failed with status {:?}: {}", output.status, stderr);
-                if output.stdout.is_empty() {
-                     return Err(AppError::RipgrepError(format!("rg failed (status: {:?}): {}", output.status, stderr)));
-                }
-                error_message_opt = Some(format!("rg reported errors (status: {:?}): {}", output.status, stderr));
-            }
-            if !output.stderr.is_empty() && error_message_opt.is_none() {
-                 let stderr_str = String::from_utf8_lossy(&output.stderr).to_string();
-                 if !stderr_str.trim().is_empty() {
-                    error_message_opt = Some(format!("rg stderr: {}", stderr_str));
-                 }
-            }
+        .set_raw_out(true)
+        .spawn()
+        .map_err(|e| AppError::RipgrepError(format!("Failed to start rg: {}", e)))?;
+    let mut rg_child = Some(rg_child);
+    let mut kill_rg = || if let Some(child) = rg_child.take() { let _ = child.kill(); };
 
-            let stdout = String::from_utf8(output.stdout).map_err(|e| AppError::RipgrepError(format!("rg output not UTF-8: {}", e)))?;
-            let mut matches = Vec::new();
-            let mut matched_lines_count = 0;
-
-            for line_str in stdout.lines() {
-                if line_str.trim().is_empty() { continue; }
-                match serde_json::from_str::<serde_json::Value>(line_str) {
-                    Ok(json_val) => {
-                        let entry_type = json_val.get("type").and_then(|t| t.as_str());
-                        if let Some(data) = json_val.get("data") {
-                            let path_abs_str = data.get("path").and_then(|p|p.get("text")).and_then(|t|t.as_str()).unwrap_or_default();
-                            let line_num = data.get("line_number").and_then(|n|n.as_u64()).unwrap_or(0);
-                            let mut match_text_content = String::new();
-                            if entry_type == Some("match") {
-                                if let Some(subs) = data.get("submatches").and_then(|s|s.as_array()) {
-                                    for sub in subs { if let Some(txt_val) = sub.get("match").and_then(|m|m.get("text")) { match_text_content.push_str(txt_val.as_str().unwrap_or(""));}}
-                                }
-                                matched_lines_count +=1;
-                            } else if entry_type == Some("context") {
-                                if let Some(txt_val) = data.get("lines").and_then(|l|l.get("text")) { match_text_content.push_str(txt_val.as_str().unwrap_or(""));}
-                            } else { continue; }
-                            
-                            let absolute_match_path = PathBuf::from(path_abs_str);
-                            let display_path = match absolute_match_path.strip_prefix(&files_root_for_stripping) {
-                                Ok(p) => p.to_string_lossy().into_owned(),
-                                Err(_) => path_abs_str.to_string(),
-                            };
-                            matches.push(RipgrepMatchMCP { file: display_path, line: line_num, match_text: match_text_content.trim_end().to_string() });
-                        }
-                    }
-                    Err(e) => { warn!(error = %e, line = %line_str, "Failed to parse rg JSON line"); }
+    let timeout_duration = deps.call_timeout(&limits, params.timeout_ms, limits.search_timeout_ms);
+    let deadline = tokio::time::sleep(timeout_duration);
+    tokio::pin!(deadline);
+    let mut parser = RgJsonParser::new(&files_root_for_stripping, &denied_set, &workspaces, scope.as_ref(), params.max_results, limits.max_output_bytes);
+    let mut stderr = Vec::new();
+    let mut exit_code = None;
+
+    // Lines are parsed as rg writes them, so the deadline and the result caps apply while it runs.
+    let timed_out = loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Some(CommandEvent::Stdout(bytes)) => {
+                    parser.feed(&bytes);
+                    if parser.truncated { kill_rg(); break false; }
                 }
+                Some(CommandEvent::Stderr(bytes)) => if stderr.len() < limits.max_output_bytes { stderr.extend(bytes) },
+                Some(CommandEvent::Terminated(payload)) => exit_code = payload.code,
+                Some(CommandEvent::Error(msg)) => stderr.extend(msg.into_bytes()),
+                Some(_) => {}
+                None => { parser.finish(); break false; }
+            },
+            _ = &mut deadline => { kill_rg(); break true; }
+            _ = deps.cancel.cancelled() => {
+                kill_rg();
+                return Err(AppError::Cancelled(format!("search_code for '{}'", params.pattern)));
             }
-            Ok(SearchCodeResultMCP { matches, stats: SearchStatsMCP { matched_lines: matched_lines_count, elapsed_ms }, timed_out: false, error_message: error_message_opt })
-        },
-        Ok(Err(e)) => {
-            error!("Error executing ripgrep command via tauri-plugin-shell: {:?}", e);
-            Err(AppError::RipgrepError(format!("Shell execution error for ripgrep: {:?}", e)))
         }
-        Err(_) => {
-            let elapsed_ms = start_time.elapsed().as_millis() as u64;
-            warn!(pattern = %params.pattern, path = %params.path, timeout = timeout_duration.as_millis(), "Ripgrep search timed out");
-            Ok(SearchCodeResultMCP { matches: vec![], stats: SearchStatsMCP { matched_lines: 0, elapsed_ms }, timed_out: true, error_message: Some("Search operation timed out.".to_string()) })
+    };
+    let elapsed_ms = start_time.elapsed().as_millis() as u64;
+    let stats = SearchStatsMCP { matched_lines: parser.matched_lines, elapsed_ms };
+
+    if timed_out {
+        warn!(pattern = %params.pattern, path = %params.path, timeout = timeout_duration.as_millis(), matches = parser.matches.len(), "Ripgrep search timed out");
+        return Ok(SearchCodeResultMCP { matches: parser.matches, stats, timed_out: true, truncated: parser.truncated, error_message: Some("Search operation timed out.".to_string()) });
+    }
+
+    let mut error_message_opt: Option<String> = None;
+    // rg exits with 1 when nothing matched; a search stopped at a cap was killed on purpose.
+    if !parser.truncated && exit_code != Some(0) && exit_code != Some(1) {
+        let stderr = String::from_utf8_lossy(&stderr).to_string();
+        error!("Ripgrep command failed with exit code {:?}: {}", exit_code, stderr);
+        if !parser.saw_output {
+            return Err(AppError::RipgrepError(format!("rg failed (exit code: {:?}): {}", exit_code, stderr)));
+        }
+        error_message_opt = Some(format!("rg reported errors (exit code: {:?}): {}", exit_code, stderr));
+    }
+    if !stderr.is_empty() && error_message_opt.is_none() {
+        let stderr_str = String::from_utf8_lossy(&stderr).to_string();
+        if !stderr_str.trim().is_empty() {
+            error_message_opt = Some(format!("rg stderr: {}", stderr_str));
+        }
+    }
+    Ok(SearchCodeResultMCP { matches: parser.matches, stats, timed_out: false, truncated: parser.truncated, error_message: error_message_opt })
+}
+
+/// Turns rg's `--json` lines into matches as the output arrives, so memory grows with the
+/// matches kept instead of with everything rg prints.
+struct RgJsonParser<'a> {
+    files_root: &'a Path,
+    denied_set: &'a GlobSet,
+    workspaces: &'a [WorkspaceRoot],
+    // Set for tracked_only and changed_since searches; matches in other files are dropped.
+    scope: Option<&'a GitScope>,
+    max_results: usize,
+    max_output_bytes: usize,
+    // The unterminated end of the last chunk.
+    pending: Vec<u8>,
+    saw_output: bool,
+    matches: Vec<RipgrepMatchMCP>,
+    matched_lines: usize,
+    output_bytes: usize,
+    // Set once max_results or max_output_bytes is reached; later output is ignored.
+    truncated: bool,
+}
+
+impl<'a> RgJsonParser<'a> {
+    fn new(files_root: &'a Path, denied_set: &'a GlobSet, workspaces: &'a [WorkspaceRoot], scope: Option<&'a GitScope>, max_results: usize, max_output_bytes: usize) -> Self {
+        Self { files_root, denied_set, workspaces, scope, max_results, max_output_bytes, pending: Vec::new(), saw_output: false, matches: Vec::new(), matched_lines: 0, output_bytes: 0, truncated: false }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) {
+        self.saw_output |= !chunk.is_empty();
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.extend_from_slice(chunk);
+        let mut consumed = 0;
+        while let Some(len) = memchr::memchr(b'\n', &pending[consumed..]) {
+            if !self.truncated { self.parse_line(&pending[consumed..consumed + len]); }
+            consumed += len + 1;
         }
+        pending.drain(..consumed);
+        self.pending = pending;
+    }
+
+    fn finish(&mut self) {
+        let rest = std::mem::take(&mut self.pending);
+        if !self.truncated { self.parse_line(&rest); }
+    }
+
+    fn parse_line(&mut self, line: &[u8]) {
+        if line.iter().all(u8::is_ascii_whitespace) { return; }
+        let json_val = match serde_json::from_slice::<serde_json::Value>(line) {
+            Ok(json_val) => json_val,
+            Err(e) => { warn!(error = %e, line = %String::from_utf8_lossy(line), "Failed to parse rg JSON line"); return; }
+        };
+        let entry_type = json_val.get("type").and_then(|t| t.as_str());
+        let Some(data) = json_val.get("data") else { return };
+        let path_abs_str = data.get("path").and_then(|p|p.get("text")).and_then(|t|t.as_str()).unwrap_or_default();
+        if self.scope.is_some_and(|scope| !scope.contains_file(Path::new(path_abs_str))) { return; }
+        let line_num = data.get("line_number").and_then(|n|n.as_u64()).unwrap_or(0);
+        let mut match_text_content = String::new();
+        if entry_type == Some("match") {
+            if let Some(subs) = data.get("submatches").and_then(|s|s.as_array()) {
+                for sub in subs { if let Some(txt_val) = sub.get("match").and_then(|m|m.get("text")) { match_text_content.push_str(txt_val.as_str().unwrap_or(""));}}
+            }
+            self.matched_lines +=1;
+        } else if entry_type == Some("context") {
+            if let Some(txt_val) = data.get("lines").and_then(|l|l.get("text")) { match_text_content.push_str(txt_val.as_str().unwrap_or(""));}
+        } else { return; }
+
+        let absolute_match_path = PathBuf::from(path_abs_str);
+        if self.denied_set.is_match(&absolute_match_path) { return; }
+        if check_workspace_rules(self.workspaces, &absolute_match_path, &absolute_match_path).is_err() { return; }
+        let display_path = match absolute_match_path.strip_prefix(self.files_root) {
+            Ok(p) => p.to_string_lossy().into_owned(),
+            Err(_) => path_abs_str.to_string(),
+        };
+        let match_text = match_text_content.trim_end().to_string();
+        self.output_bytes += display_path.len() + match_text.len();
+        if self.output_bytes > self.max_output_bytes { self.truncated = true; return; }
+        self.matches.push(RipgrepMatchMCP { file: display_path, line: line_num, match_text });
+        // --max-count only caps matches per file.
+        if self.matched_lines >= self.max_results { self.truncated = true; }
     }
-}
\ No newline at end of file
+}
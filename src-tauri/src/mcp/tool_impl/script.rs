@@ -0,0 +1,230 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::mcp::roots;
+use crate::utils::job_manager::{JobHandle, JobStartedMCP};
+use crate::utils::limits::{call_timeout, check_file_size, truncate_output};
+use crate::utils::path_utils::validate_and_normalize_path;
+
+use ignore::WalkBuilder;
+use regex::Regex;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, instrument};
+
+const DEFAULT_SCRIPT_TIMEOUT_MS: u64 = 30_000;
+const MAX_OPERATIONS: u64 = 50_000_000;
+const MAX_COLLECTION_SIZE: usize = 100_000;
+const MAX_SEARCH_MATCHES: usize = 1_000;
+const MAX_PRINTED_LINES: usize = 1_000;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct RunScriptParamsMCP {
+    /// The rhai script. The value of its last expression is the result. Host functions: read_file(path), read_lines(path), list_dir(path), exists(path), search(regex, path), from_json(text), to_json(value) and print(value).
+    pub script: String,
+    /// Passed to the script as the constant `input`.
+    #[serde(default)]
+    pub input: Option<Value>,
+    /// Default 30000, capped at limits.max_timeout_ms.
+    pub timeout_ms: Option<u64>,
+    /// Return a job_id at once and run the script as a background job (see get_job_status). The script can then report progress(fraction, message).
+    #[serde(default)]
+    pub background: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RunScriptResultMCP {
+    pub result: Value,
+    // What the script printed, one entry per print call.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub printed: Vec<String>,
+    pub files_read: usize,
+    pub duration_ms: u64,
+}
+
+/// What the host functions may touch: paths pass the same validation as read_file, and every
+/// function gives up once the call is cancelled or out of time.
+struct Host {
+    config: Arc<Config>,
+    cancel: CancellationToken,
+    deadline: Instant,
+    files_read: AtomicUsize,
+    // Set for background runs, where progress() updates the job.
+    job: Option<JobHandle>,
+}
+
+impl Host {
+    fn stopped(&self) -> bool {
+        self.cancel.is_cancelled() || Instant::now() >= self.deadline
+    }
+
+    fn path(&self, path: &str) -> Result<PathBuf, AppError> {
+        validate_and_normalize_path(path, &self.config, true, false)
+    }
+
+    fn read_file(&self, path: &str) -> Result<String, AppError> {
+        let path = self.path(path)?;
+        let size = std::fs::metadata(&path).map_err(|e| AppError::StdIoError(format!("{}: {}", path.display(), e)))?.len();
+        check_file_size(&self.config.limits, path.display(), size)?;
+        let text = std::fs::read_to_string(&path).map_err(|e| AppError::StdIoError(format!("{}: {}", path.display(), e)))?;
+        self.files_read.fetch_add(1, Ordering::Relaxed);
+        Ok(text)
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Value, AppError> {
+        let dir = self.path(path)?;
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&dir).map_err(|e| AppError::StdIoError(format!("{}: {}", dir.display(), e)))?.flatten() {
+            let entry_path = entry.path();
+            // Entries hidden by denied_globs, which list_directory leaves out too, or outside the client's roots are skipped.
+            if self.path(&entry_path.to_string_lossy()).is_err() { continue; }
+            let metadata = entry.metadata().ok();
+            entries.push(json!({
+                "name": entry.file_name().to_string_lossy(),
+                "path": entry_path.to_string_lossy(),
+                "is_dir": metadata.as_ref().is_some_and(|m| m.is_dir()),
+                "size": metadata.as_ref().map_or(0, |m| m.len()),
+            }));
+        }
+        entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+        Ok(Value::Array(entries))
+    }
+
+    /// Lines matching `pattern` in the text files under `path`, honouring .gitignore.
+    fn search(&self, pattern: &str, path: &str) -> Result<Value, AppError> {
+        let re = Regex::new(pattern).map_err(|e| AppError::InvalidInputArgument(format!("Invalid search regex '{}': {}", pattern, e)))?;
+        let root = self.path(path)?;
+        let mut matches = Vec::new();
+        for entry in WalkBuilder::new(&root).build().flatten() {
+            if self.stopped() || matches.len() >= MAX_SEARCH_MATCHES { break; }
+            if !entry.file_type().is_some_and(|t| t.is_file()) { continue; }
+            let Ok(file) = self.path(&entry.path().to_string_lossy()) else { continue };
+            if !std::fs::metadata(&file).is_ok_and(|m| m.len() <= self.config.limits.max_file_bytes) { continue; }
+            // Binary and non-UTF-8 files are skipped.
+            let Ok(text) = std::fs::read_to_string(&file) else { continue };
+            self.files_read.fetch_add(1, Ordering::Relaxed);
+            for (index, line) in text.lines().enumerate().filter(|(_, line)| re.is_match(line)) {
+                matches.push(json!({ "path": file.to_string_lossy(), "line": index + 1, "text": line }));
+                if matches.len() >= MAX_SEARCH_MATCHES { break; }
+            }
+        }
+        Ok(Value::Array(matches))
+    }
+}
+
+fn script_error(e: AppError) -> Box<EvalAltResult> {
+    e.to_string().into()
+}
+
+fn to_dynamic(value: Value) -> Result<Dynamic, Box<EvalAltResult>> {
+    rhai::serde::to_dynamic(value)
+}
+
+/// An engine with the standard library and the host functions only: no eval, no modules, and
+/// no way to write files, run commands or open connections.
+fn build_engine(host: &Arc<Host>, printed: &Arc<Mutex<Vec<String>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.disable_symbol("eval");
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(64);
+    engine.set_max_expr_depths(64, 64);
+    engine.set_max_string_size(host.config.limits.max_output_bytes);
+    engine.set_max_array_size(MAX_COLLECTION_SIZE);
+    engine.set_max_map_size(MAX_COLLECTION_SIZE);
+
+    let progress_host = host.clone();
+    engine.on_progress(move |_| progress_host.stopped().then_some(Dynamic::UNIT));
+    let print_sink = printed.clone();
+    engine.on_print(move |text| {
+        let mut printed = print_sink.lock().unwrap_or_else(|e| e.into_inner());
+        if printed.len() < MAX_PRINTED_LINES { printed.push(text.to_string()); }
+    });
+
+    let h = host.clone();
+    engine.register_fn("read_file", move |path: &str| h.read_file(path).map_err(script_error));
+    let h = host.clone();
+    engine.register_fn("read_lines", move |path: &str| -> Result<rhai::Array, Box<EvalAltResult>> {
+        Ok(h.read_file(path).map_err(script_error)?.lines().map(|line| Dynamic::from(line.to_string())).collect())
+    });
+    let h = host.clone();
+    engine.register_fn("list_dir", move |path: &str| to_dynamic(h.list_dir(path).map_err(script_error)?));
+    let h = host.clone();
+    engine.register_fn("exists", move |path: &str| -> Result<bool, Box<EvalAltResult>> { Ok(h.path(path).map_err(script_error)?.exists()) });
+    let h = host.clone();
+    engine.register_fn("search", move |pattern: &str, path: &str| to_dynamic(h.search(pattern, path).map_err(script_error)?));
+    let h = host.clone();
+    engine.register_fn("progress", move |fraction: f64, message: &str| {
+        if let Some(job) = &h.job { job.set_progress(Some(fraction), Some(message.to_string())); }
+    });
+    engine.register_fn("from_json", |text: &str| -> Result<Dynamic, Box<EvalAltResult>> {
+        to_dynamic(serde_json::from_str(text).map_err(|e| format!("from_json: {}", e))?)
+    });
+    engine.register_fn("to_json", |value: Dynamic| -> Result<String, Box<EvalAltResult>> {
+        let value: Value = rhai::serde::from_dynamic(&value)?;
+        Ok(value.to_string())
+    });
+    engine
+}
+
+#[instrument(skip(deps, params), fields(script_bytes = params.script.len()))]
+pub async fn mcp_run_script(deps: &ToolDependencies, params: RunScriptParamsMCP) -> Result<RunScriptResultMCP, AppError> {
+    execute(deps.config_state.snapshot(), deps.cancel.clone(), roots::current_roots(), params, None).await
+}
+
+/// Starts the script as a job that outlives the call; cancel_job or its timeout stops it.
+pub fn mcp_start_script_job(deps: &ToolDependencies, params: RunScriptParamsMCP) -> Result<JobStartedMCP, AppError> {
+    let (config, session_roots) = (deps.config_state.snapshot(), roots::current_roots());
+    let mut description = params.script.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default().to_string();
+    if truncate_output(&mut description, 80) { description.push_str("..."); }
+    Ok(deps.jobs.spawn("run_script", description, deps.job_owner().map(str::to_string), move |job| async move {
+        let cancel = job.cancel_token().clone();
+        let result = execute(config, cancel, session_roots, params, Some(job)).await?;
+        Ok(serde_json::to_value(result)?)
+    }))
+}
+
+// The roots are passed in because path validation reads them from the task, which neither the
+// blocking thread nor a job inherits.
+async fn execute(config: Arc<Config>, cancel: CancellationToken, session_roots: Option<Arc<Vec<PathBuf>>>, params: RunScriptParamsMCP, job: Option<JobHandle>) -> Result<RunScriptResultMCP, AppError> {
+    let timeout = call_timeout(&config.limits, params.timeout_ms, DEFAULT_SCRIPT_TIMEOUT_MS);
+    let host = Arc::new(Host { config: config.clone(), cancel, deadline: Instant::now() + timeout, files_read: AtomicUsize::new(0), job });
+    let max_output_bytes = config.limits.max_output_bytes;
+    let started = Instant::now();
+    let (host, result, printed) = tokio::task::spawn_blocking(move || roots::with_roots(session_roots, || {
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let engine = build_engine(&host, &printed);
+        let run = || -> Result<Value, Box<EvalAltResult>> {
+            let mut scope = Scope::new();
+            scope.push_constant("input", to_dynamic(params.input.unwrap_or(Value::Null))?);
+            let value = engine.eval_with_scope::<Dynamic>(&mut scope, &params.script)?;
+            rhai::serde::from_dynamic(&value)
+        };
+        let result = run();
+        drop(engine);
+        let printed = std::mem::take(&mut *printed.lock().unwrap_or_else(|e| e.into_inner()));
+        (host, result, printed)
+    }))
+    .await
+    .map_err(|e| AppError::TokioIoError(format!("run_script task failed: {}", e)))?;
+
+    let result = result.map_err(|e| match *e {
+        EvalAltResult::ErrorTerminated(..) if host.cancel.is_cancelled() => AppError::Cancelled("run_script was stopped".to_string()),
+        EvalAltResult::ErrorTerminated(..) => AppError::TimeoutError(format!("run_script ran longer than {} ms", timeout.as_millis())),
+        e => AppError::InvalidInputArgument(format!("Script failed: {}", e)),
+    })?;
+    let size = result.to_string().len();
+    if size > max_output_bytes {
+        return Err(AppError::LimitExceeded { message: format!("The script's result is {} bytes; limits.max_output_bytes is {}", size, max_output_bytes), limit: "limits.max_output_bytes".to_string(), max: max_output_bytes as u64, actual: size as u64 });
+    }
+    let files_read = host.files_read.load(Ordering::Relaxed);
+    debug!(files_read, printed = printed.len(), "Ran script");
+    Ok(RunScriptResultMCP { result, printed, files_read, duration_ms: started.elapsed().as_millis() as u64 })
+}
@@ -0,0 +1,407 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use crate::mcp::roots;
+use crate::mcp::tool_impl::git::{checkpoint_before, GitCheckpointMCP};
+use crate::utils::job_manager::{JobHandle, JobStartedMCP};
+use crate::utils::path_utils::validate_and_normalize_path;
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SnapshotWorkspaceParamsMCP {
+    /// Directory to snapshot. Defaults to files_root.
+    pub path: Option<String>,
+    /// Free text kept in the manifest, e.g. "before refactor".
+    pub label: Option<String>,
+    /// Globs, relative to the snapshotted directory, of files to leave out on top of .gitignore and .git.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Return a job_id at once and take the snapshot as a background job (see get_job_status).
+    #[serde(default)]
+    pub background: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct RestoreSnapshotParamsMCP {
+    /// The id snapshot_workspace or list_snapshots returned.
+    pub snapshot_id: String,
+    /// Only report what would change. Defaults to true; pass false to restore.
+    #[serde(default = "default_true")]
+    pub dry_run: bool,
+    /// Also delete files that were not in the snapshot. Ignored and excluded files are never touched.
+    #[serde(default)]
+    pub delete_extra: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ListSnapshotsParamsMCP {
+    /// Only snapshots of this directory.
+    pub path: Option<String>,
+}
+
+fn default_true() -> bool { true }
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SnapshotFileMCP {
+    // Relative to the manifest's root, with / separators.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// `<id>.json` next to `<id>.tar.gz` in snapshot_dir.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SnapshotManifestMCP {
+    pub id: String,
+    pub root: PathBuf,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    pub files: Vec<SnapshotFileMCP>,
+    // Files left out for being over limits.max_file_bytes; restore leaves them alone.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SnapshotWorkspaceResultMCP {
+    pub snapshot_id: String,
+    pub root: PathBuf,
+    pub archive: PathBuf,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+    // Older snapshots of the same directory deleted to stay within snapshot_keep.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pruned: Vec<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RestoreSnapshotResultMCP {
+    pub snapshot_id: String,
+    pub root: PathBuf,
+    pub dry_run: bool,
+    // Files whose content differs from the snapshot.
+    pub changed: Vec<String>,
+    // Files in the snapshot that no longer exist.
+    pub missing: Vec<String>,
+    // Files not in the snapshot; deleted only with delete_extra.
+    pub extra: Vec<String>,
+    pub unchanged: usize,
+    // What was actually written and deleted; 0 on a dry run.
+    pub restored: usize,
+    pub deleted: usize,
+    // Taken before restoring when git_checkpoint_min_files is reached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<GitCheckpointMCP>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SnapshotSummaryMCP {
+    pub snapshot_id: String,
+    pub root: PathBuf,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub archive_bytes: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ListSnapshotsResultMCP {
+    // Newest first.
+    pub snapshots: Vec<SnapshotSummaryMCP>,
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> AppError {
+    AppError::StdIoError(format!("{}: {}", path.display(), e))
+}
+
+fn check_cancelled(cancel: &CancellationToken) -> Result<(), AppError> {
+    if cancel.is_cancelled() { return Err(AppError::Cancelled("Snapshot operation was cancelled".to_string())); }
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Ids are generated by snapshot_workspace; anything else could point outside snapshot_dir.
+fn check_snapshot_id(id: &str) -> Result<(), AppError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(AppError::InvalidInputArgument(format!("Invalid snapshot id '{}'", id)));
+    }
+    Ok(())
+}
+
+fn manifest_path(config: &Config, id: &str) -> PathBuf {
+    config.snapshot_dir.join(format!("{}.json", id))
+}
+
+fn archive_path(config: &Config, id: &str) -> PathBuf {
+    config.snapshot_dir.join(format!("{}.tar.gz", id))
+}
+
+fn read_manifest(path: &Path) -> Result<SnapshotManifestMCP, AppError> {
+    let text = std::fs::read_to_string(path).map_err(|e| io_error(path, e))?;
+    serde_json::from_str(&text).map_err(|e| AppError::SerdeJsonError(format!("Invalid snapshot manifest {}: {}", path.display(), e)))
+}
+
+fn load_manifests(config: &Config) -> Vec<SnapshotManifestMCP> {
+    let Ok(entries) = std::fs::read_dir(&config.snapshot_dir) else { return Vec::new() };
+    let mut manifests: Vec<SnapshotManifestMCP> = entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| read_manifest(&path).map_err(|e| warn!(error = %e, "Skipping unreadable snapshot manifest")).ok())
+        .collect();
+    manifests.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    manifests
+}
+
+fn exclude_set(patterns: &[String]) -> Result<GlobSet, AppError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| AppError::InvalidInputArgument(format!("Invalid exclude glob '{}': {}", pattern, e)))?);
+    }
+    builder.build().map_err(|e| AppError::InvalidInputArgument(format!("Invalid exclude globs: {}", e)))
+}
+
+fn relative_key(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    Some(relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+}
+
+/// The files a snapshot covers: what .gitignore keeps, dotfiles included, minus .git, the
+/// exclude globs, snapshot_dir itself and anything path validation rejects. Symlinks are not
+/// followed or stored.
+fn tracked_files(config: &Config, root: &Path, exclude: &GlobSet, cancel: &CancellationToken) -> Result<BTreeMap<String, PathBuf>, AppError> {
+    let snapshot_dir = config.snapshot_dir.clone();
+    let walker = WalkBuilder::new(root).hidden(false).follow_links(false)
+        .filter_entry(move |entry| entry.file_name() != ".git" && !entry.path().starts_with(&snapshot_dir))
+        .build();
+    let mut files = BTreeMap::new();
+    for entry in walker.flatten() {
+        check_cancelled(cancel)?;
+        if !entry.file_type().is_some_and(|t| t.is_file()) { continue; }
+        let Some(key) = relative_key(root, entry.path()) else { continue };
+        if exclude.is_match(&key) { continue; }
+        let Ok(path) = validate_and_normalize_path(&entry.path().to_string_lossy(), config, true, false) else { continue };
+        files.insert(key, path);
+    }
+    Ok(files)
+}
+
+#[instrument(skip(deps, params), fields(path = ?params.path))]
+pub async fn mcp_snapshot_workspace(deps: &ToolDependencies, params: SnapshotWorkspaceParamsMCP) -> Result<SnapshotWorkspaceResultMCP, AppError> {
+    let config = deps.config_state.snapshot();
+    let cancel = deps.cancel.clone();
+    let session_roots = roots::current_roots();
+    run_blocking(session_roots, move || take_snapshot(&config, &cancel, params, None)).await
+}
+
+/// Takes the snapshot as a job; cancel_job stops it and removes the partial archive.
+pub fn mcp_start_snapshot_job(deps: &ToolDependencies, params: SnapshotWorkspaceParamsMCP) -> Result<JobStartedMCP, AppError> {
+    let (config, session_roots) = (deps.config_state.snapshot(), roots::current_roots());
+    let description = params.label.clone().unwrap_or_else(|| params.path.clone().unwrap_or_else(|| config.files_root.display().to_string()));
+    Ok(deps.jobs.spawn("snapshot_workspace", description, deps.job_owner().map(str::to_string), move |job| async move {
+        let cancel = job.cancel_token().clone();
+        let result = run_blocking(session_roots, move || take_snapshot(&config, &cancel, params, Some(job))).await?;
+        Ok(serde_json::to_value(result)?)
+    }))
+}
+
+#[instrument(skip(deps, params), fields(snapshot_id = %params.snapshot_id, dry_run = params.dry_run))]
+pub async fn mcp_restore_snapshot(deps: &ToolDependencies, params: RestoreSnapshotParamsMCP) -> Result<RestoreSnapshotResultMCP, AppError> {
+    let config = deps.config_state.snapshot();
+    let cancel = deps.cancel.clone();
+    run_blocking(roots::current_roots(), move || restore(&config, &cancel, params)).await
+}
+
+pub async fn mcp_list_snapshots(deps: &ToolDependencies, params: ListSnapshotsParamsMCP) -> Result<ListSnapshotsResultMCP, AppError> {
+    let config = deps.config_state.snapshot();
+    let root = params.path.as_deref().map(|p| validate_and_normalize_path(p, &config, false, false)).transpose()?;
+    let snapshots = load_manifests(&config).into_iter()
+        .filter(|m| !root.as_ref().is_some_and(|r| *r != m.root))
+        .map(|m| SnapshotSummaryMCP {
+            archive_bytes: std::fs::metadata(archive_path(&config, &m.id)).map_or(0, |meta| meta.len()),
+            total_bytes: m.files.iter().map(|f| f.size).sum(),
+            file_count: m.files.len(),
+            snapshot_id: m.id,
+            root: m.root,
+            created_at: m.created_at,
+            label: m.label,
+        })
+        .collect();
+    Ok(ListSnapshotsResultMCP { snapshots })
+}
+
+// Path validation reads the client's roots from the task, which the blocking thread does not inherit.
+async fn run_blocking<T: Send + 'static>(session_roots: Option<Arc<Vec<PathBuf>>>, f: impl FnOnce() -> Result<T, AppError> + Send + 'static) -> Result<T, AppError> {
+    tokio::task::spawn_blocking(move || roots::with_roots(session_roots, f))
+        .await
+        .map_err(|e| AppError::TokioIoError(format!("Snapshot task failed: {}", e)))?
+}
+
+fn take_snapshot(config: &Config, cancel: &CancellationToken, params: SnapshotWorkspaceParamsMCP, job: Option<JobHandle>) -> Result<SnapshotWorkspaceResultMCP, AppError> {
+    let started = Instant::now();
+    let root_str = params.path.clone().unwrap_or_else(|| config.files_root.to_string_lossy().into_owned());
+    let root = validate_and_normalize_path(&root_str, config, true, false)?;
+    if !root.is_dir() { return Err(AppError::InvalidPath(format!("{} is not a directory", root.display()))); }
+    let exclude = exclude_set(&params.exclude)?;
+    let files = tracked_files(config, &root, &exclude, cancel)?;
+
+    let id = format!("{}-{}", Utc::now().format("%Y%m%dT%H%M%SZ"), &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    let archive = archive_path(config, &id);
+    let tmp_archive = archive.with_extension("gz.tmp");
+    let written = write_archive(config, cancel, &files, &tmp_archive, job.as_ref());
+    let (manifest_files, skipped) = match written {
+        Ok(written) => written,
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp_archive);
+            return Err(e);
+        }
+    };
+    std::fs::rename(&tmp_archive, &archive).map_err(|e| io_error(&archive, e))?;
+
+    let manifest = SnapshotManifestMCP { id: id.clone(), root: root.clone(), created_at: Utc::now(), label: params.label, exclude: params.exclude, files: manifest_files, skipped };
+    let manifest_file = manifest_path(config, &id);
+    std::fs::write(&manifest_file, serde_json::to_vec_pretty(&manifest)?).map_err(|e| io_error(&manifest_file, e))?;
+    let pruned = prune(config, &root);
+    let total_bytes = manifest.files.iter().map(|f| f.size).sum();
+    info!(snapshot_id = %id, root = %root.display(), files = manifest.files.len(), total_bytes, "Took workspace snapshot");
+    Ok(SnapshotWorkspaceResultMCP { snapshot_id: id, root, archive, file_count: manifest.files.len(), total_bytes, skipped: manifest.skipped, pruned, duration_ms: started.elapsed().as_millis() as u64 })
+}
+
+fn write_archive(config: &Config, cancel: &CancellationToken, files: &BTreeMap<String, PathBuf>, archive: &Path, job: Option<&JobHandle>) -> Result<(Vec<SnapshotFileMCP>, Vec<String>), AppError> {
+    let file = File::create(archive).map_err(|e| io_error(archive, e))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(BufWriter::new(file), Compression::default()));
+    let (mut manifest_files, mut skipped) = (Vec::new(), Vec::new());
+    for (index, (key, path)) in files.iter().enumerate() {
+        check_cancelled(cancel)?;
+        if let Some(job) = job { job.set_progress(Some(index as f64 / files.len().max(1) as f64), Some(key.clone())); }
+        let Ok(metadata) = std::fs::metadata(path) else { continue };
+        if metadata.len() > config.limits.max_file_bytes {
+            skipped.push(key.clone());
+            continue;
+        }
+        // A file removed since the walk is simply not in the snapshot.
+        let Ok(bytes) = std::fs::read(path) else { continue };
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&metadata);
+        header.set_size(bytes.len() as u64);
+        builder.append_data(&mut header, key, bytes.as_slice()).map_err(|e| io_error(archive, e))?;
+        manifest_files.push(SnapshotFileMCP { path: key.clone(), size: bytes.len() as u64, sha256: sha256_hex(&bytes) });
+    }
+    builder.into_inner().map_err(|e| io_error(archive, e))?.finish().map_err(|e| io_error(archive, e))?.flush().map_err(|e| io_error(archive, e))?;
+    Ok((manifest_files, skipped))
+}
+
+// Keeps the newest snapshot_keep snapshots of `root`; failures only cost disk space.
+fn prune(config: &Config, root: &Path) -> Vec<String> {
+    if config.snapshot_keep == 0 { return Vec::new(); }
+    let mut pruned = Vec::new();
+    for manifest in load_manifests(config).into_iter().filter(|m| m.root == root).skip(config.snapshot_keep) {
+        let _ = std::fs::remove_file(archive_path(config, &manifest.id));
+        match std::fs::remove_file(manifest_path(config, &manifest.id)) {
+            Ok(()) => pruned.push(manifest.id),
+            Err(e) => warn!(snapshot_id = %manifest.id, error = %e, "Failed to prune snapshot"),
+        }
+    }
+    pruned
+}
+
+fn restore(config: &Config, cancel: &CancellationToken, params: RestoreSnapshotParamsMCP) -> Result<RestoreSnapshotResultMCP, AppError> {
+    check_snapshot_id(&params.snapshot_id)?;
+    let manifest_file = manifest_path(config, &params.snapshot_id);
+    if !manifest_file.exists() { return Err(AppError::InvalidInputArgument(format!("No snapshot with id '{}'", params.snapshot_id))); }
+    let manifest = read_manifest(&manifest_file)?;
+    let root = validate_and_normalize_path(&manifest.root.to_string_lossy(), config, false, true)?;
+
+    let (mut changed, mut missing, mut unchanged) = (Vec::new(), Vec::new(), 0);
+    for file in &manifest.files {
+        check_cancelled(cancel)?;
+        match std::fs::read(root.join(&file.path)) {
+            Ok(bytes) if sha256_hex(&bytes) == file.sha256 => unchanged += 1,
+            Ok(_) => changed.push(file.path.clone()),
+            Err(_) => missing.push(file.path.clone()),
+        }
+    }
+    let in_snapshot: HashSet<&str> = manifest.files.iter().map(|f| f.path.as_str()).chain(manifest.skipped.iter().map(String::as_str)).collect();
+    let extra: Vec<String> = if root.is_dir() {
+        tracked_files(config, &root, &exclude_set(&manifest.exclude)?, cancel)?.into_keys().filter(|key| !in_snapshot.contains(key.as_str())).collect()
+    } else {
+        Vec::new()
+    };
+
+    let (mut restored, mut deleted, mut checkpoint) = (0, 0, None);
+    if !params.dry_run {
+        let rewrites = changed.len() + missing.len() + if params.delete_extra { extra.len() } else { 0 };
+        if rewrites > 0 { checkpoint = checkpoint_before(config, &root, rewrites, "restore_snapshot")?; }
+        let wanted: HashSet<&str> = changed.iter().chain(missing.iter()).map(String::as_str).collect();
+        if !wanted.is_empty() { restored = extract(config, cancel, &manifest, &root, &wanted)?; }
+        if params.delete_extra {
+            for key in &extra {
+                check_cancelled(cancel)?;
+                let path = validate_and_normalize_path(&root.join(key).to_string_lossy(), config, true, true)?;
+                std::fs::remove_file(&path).map_err(|e| io_error(&path, e))?;
+                deleted += 1;
+            }
+        }
+        info!(snapshot_id = %manifest.id, restored, deleted, "Restored workspace snapshot");
+    }
+    debug!(changed = changed.len(), missing = missing.len(), extra = extra.len(), unchanged, "Compared workspace with snapshot");
+    Ok(RestoreSnapshotResultMCP { snapshot_id: manifest.id, root, dry_run: params.dry_run, changed, missing, extra, unchanged, restored, deleted, checkpoint })
+}
+
+/// Writes the `wanted` files from the archive, each through a temporary file renamed into place
+/// after its hash matched the manifest.
+fn extract(config: &Config, cancel: &CancellationToken, manifest: &SnapshotManifestMCP, root: &Path, wanted: &HashSet<&str>) -> Result<usize, AppError> {
+    let hashes: BTreeMap<&str, &str> = manifest.files.iter().map(|f| (f.path.as_str(), f.sha256.as_str())).collect();
+    let archive = archive_path(config, &manifest.id);
+    let file = File::open(&archive).map_err(|e| io_error(&archive, e))?;
+    let mut tar = tar::Archive::new(GzDecoder::new(BufReader::new(file)));
+    let mut restored = 0;
+    for entry in tar.entries().map_err(|e| io_error(&archive, e))? {
+        check_cancelled(cancel)?;
+        let mut entry = entry.map_err(|e| io_error(&archive, e))?;
+        let key = entry.path().map_err(|e| io_error(&archive, e))?.to_string_lossy().into_owned();
+        if !wanted.contains(key.as_str()) { continue; }
+        let target = validate_and_normalize_path(&root.join(&key).to_string_lossy(), config, false, true)?;
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes).map_err(|e| io_error(&archive, e))?;
+        if hashes.get(key.as_str()).is_some_and(|hash| *hash != sha256_hex(&bytes)) {
+            return Err(AppError::StdIoError(format!("Snapshot {} is corrupt: {} does not match its manifest hash", manifest.id, key)));
+        }
+        if let Some(parent) = target.parent() { std::fs::create_dir_all(parent).map_err(|e| io_error(parent, e))?; }
+        let tmp = target.with_file_name(format!(".{}.mcp-restore.tmp", target.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()));
+        std::fs::write(&tmp, &bytes).map_err(|e| io_error(&tmp, e))?;
+        std::fs::rename(&tmp, &target).map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            io_error(&target, e)
+        })?;
+        restored += 1;
+    }
+    Ok(restored)
+}
@@ -0,0 +1,123 @@
+use crate::error::AppError;
+use crate::mcp::handler::ToolDependencies;
+use serde::Serialize;
+use crate::utils::sysinfo_cache::SysinfoCache;
+use sysinfo::{Disks, System as SysinfoSystem};
+use tokio::sync::MutexGuard;
+use tracing::{debug, instrument};
+
+#[derive(Debug, Serialize)]
+pub struct CpuInfoMCP {
+    pub brand: String,
+    pub vendor_id: String,
+    pub logical_cores: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub physical_cores: Option<usize>,
+    pub frequency_mhz: u64,
+    pub global_usage_percent: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryInfoMCP {
+    pub total_mb: u64,
+    pub available_mb: u64,
+    pub used_mb: u64,
+    pub total_swap_mb: u64,
+    pub used_swap_mb: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiskInfoMCP {
+    pub name: String,
+    pub mount_point: String,
+    pub file_system: String,
+    pub kind: String,
+    pub removable: bool,
+    pub total_gb: f64,
+    pub available_gb: f64,
+    pub used_percent: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadAverageMCP { pub one: f64, pub five: f64, pub fifteen: f64 }
+
+#[derive(Debug, Serialize)]
+pub struct SystemInfoMCP {
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    pub long_os_version: Option<String>,
+    pub kernel_version: Option<String>,
+    pub host_name: Option<String>,
+    pub arch: String,
+    pub cpu: CpuInfoMCP,
+    pub memory: MemoryInfoMCP,
+    pub disks: Vec<DiskInfoMCP>,
+    // Always zero on Windows, which has no load average.
+    pub load_average: LoadAverageMCP,
+    pub uptime_secs: u64,
+    pub boot_time_epoch_secs: u64,
+}
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+#[instrument(skip(deps))]
+pub async fn mcp_get_system_info(deps: &ToolDependencies) -> Result<SystemInfoMCP, AppError> {
+    let (cpu, memory) = { // Scope for sys_guard
+        let mut sys_guard: MutexGuard<'_, SysinfoCache> = deps.sysinfo_state.lock().await;
+        sys_guard.refresh_cpu();
+        sys_guard.refresh_memory();
+        let first_cpu = sys_guard.cpus().first();
+        let cpu = CpuInfoMCP {
+            brand: first_cpu.map(|c| c.brand().trim().to_string()).unwrap_or_default(),
+            vendor_id: first_cpu.map(|c| c.vendor_id().to_string()).unwrap_or_default(),
+            logical_cores: sys_guard.cpus().len(),
+            physical_cores: sys_guard.physical_core_count(),
+            frequency_mhz: first_cpu.map(|c| c.frequency()).unwrap_or(0),
+            global_usage_percent: sys_guard.global_cpu_info().cpu_usage(),
+        };
+        let memory = MemoryInfoMCP {
+            total_mb: sys_guard.total_memory() / BYTES_PER_MB,
+            available_mb: sys_guard.available_memory() / BYTES_PER_MB,
+            used_mb: sys_guard.used_memory() / BYTES_PER_MB,
+            total_swap_mb: sys_guard.total_swap() / BYTES_PER_MB,
+            used_swap_mb: sys_guard.used_swap() / BYTES_PER_MB,
+        };
+        (cpu, memory)
+    };
+
+    // Disk enumeration is independent of the shared System, so it runs without the lock.
+    let disks = tokio::task::spawn_blocking(|| {
+        Disks::new_with_refreshed_list().list().iter().map(|disk| {
+            let total = disk.total_space();
+            let available = disk.available_space();
+            DiskInfoMCP {
+                name: disk.name().to_string_lossy().into_owned(),
+                mount_point: disk.mount_point().to_string_lossy().into_owned(),
+                file_system: disk.file_system().to_string_lossy().into_owned(),
+                kind: disk.kind().to_string(),
+                removable: disk.is_removable(),
+                total_gb: total as f64 / BYTES_PER_GB,
+                available_gb: available as f64 / BYTES_PER_GB,
+                used_percent: if total == 0 { 0.0 } else { ((total - available) as f64 / total as f64 * 100.0) as f32 },
+            }
+        }).collect::<Vec<_>>()
+    }).await.map_err(|e| AppError::Unknown(format!("Disk enumeration task failed: {}", e)))?;
+
+    let load = SysinfoSystem::load_average();
+    debug!(disks = disks.len(), "MCP Tool: Collected system info.");
+    Ok(SystemInfoMCP {
+        os_name: SysinfoSystem::name(),
+        os_version: SysinfoSystem::os_version(),
+        long_os_version: SysinfoSystem::long_os_version(),
+        kernel_version: SysinfoSystem::kernel_version(),
+        host_name: SysinfoSystem::host_name(),
+        arch: std::env::consts::ARCH.to_string(),
+        cpu,
+        memory,
+        disks,
+        load_average: LoadAverageMCP { one: load.one, five: load.five, fifteen: load.fifteen },
+        uptime_secs: SysinfoSystem::uptime(),
+        boot_time_epoch_secs: SysinfoSystem::boot_time(),
+    })
+}
@@ -1,9 +1,16 @@
-use crate::config::Config;
+use crate::config::{blocked_command_regex, Config, BLOCKED_COMMAND_REGEX_PREFIX};
 use crate::error::AppError;
 use crate::mcp::handler::ToolDependencies;
-use crate::commands::terminal_commands::ActiveSession;
+use crate::utils::dir_policy::DirPolicy;
+use crate::utils::limits::truncate_output;
+use crate::utils::execution_target::{build_container_command, build_ssh_command, map_local_path_to_remote, ExecutionTarget};
+use crate::utils::shell_quoting::{join_command_line, shell_invocation_args, split_command_line, ShellKind};
+use crate::mcp::notifications::{SESSION_EXITED_NOTIFICATION, SESSION_KILLED_NOTIFICATION, SESSION_OUTPUT_NOTIFICATION};
+use crate::commands::terminal_commands::{ActiveSession, DroppedOutput, SessionOutput, SessionOutputLine};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tauri::{Emitter};
 use tauri_plugin_shell::{process::CommandEvent, ShellExt, process::Command as TauriShellCommand}; 
@@ -14,19 +21,43 @@ use uuid::Uuid;
 use chrono::Utc;
 use serde_json::json;
 
+// Finished sessions kept for read_session_output_status; the oldest go when a new session starts.
+const MAX_FINISHED_SESSIONS: usize = 50;
+
 // --- MCP Specific Parameter Structs ---
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct ExecuteCommandParamsMCP {
+    /// Command to execute.
     pub command: String,
+    /// Timeout for initial output (ms). Default 1000.
     #[serde(rename = "timeout_ms")]
     pub timeout_ms: Option<u64>,
+    /// Specific shell (e.g., bash, powershell). Server default if not set.
     pub shell: Option<String>,
+    /// If set, 'command' is the program to run and these are its arguments, passed as-is without any shell parsing or quoting.
+    #[serde(default, alias = "rawArgs")]
+    pub raw_args: Option<Vec<String>>,
+    /// Where to run the command: 'local' (default), 'ssh:<name>' for a host configured in SSH_HOSTS, or 'container:<name>' for a CONTAINER_TARGETS entry. Remote commands start in the host's remote_root; container commands in the mapped workdir.
+    #[serde(default, alias = "executionTarget")]
+    pub execution_target: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ForceTerminateParamsMCP { pub session_id: String }
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ReadOutputStatusParamsMCP { pub session_id: String }
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ForceTerminateParamsMCP {
+    /// ID of command session to terminate.
+    pub session_id: String,
+}
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ReadOutputStatusParamsMCP {
+    /// ID of command session.
+    pub session_id: String,
+    /// First output line index to return (inclusive). Use the previous response's next_line as a cursor. Default 0.
+    #[serde(default, alias = "fromLine", alias = "cursor")]
+    pub from_line: Option<usize>,
+    /// Output line index to stop at (exclusive). Default: end of output, capped by the server read line limit.
+    #[serde(default, alias = "toLine")]
+    pub to_line: Option<usize>,
+}
 
 // --- MCP Specific Result Structs ---
 #[derive(Debug, Serialize)]
@@ -42,70 +73,132 @@ pub struct ExecuteCommandResultMCP {
 #[derive(Debug, Serialize)]
 pub struct ForceTerminateResultMCP { pub session_id: String, pub success: bool, pub message: String }
 #[derive(Debug, Serialize)]
-pub struct SessionInfoMCP { pub session_id: String, pub command: String, pub pid: Option<u32>, pub is_running: bool, pub start_time_iso: String, pub runtime_ms: u128 }
+pub struct SessionInfoMCP { pub session_id: String, pub command: String, pub pid: Option<u32>, pub is_running: bool, pub start_time_iso: String, pub runtime_ms: u128, pub execution_target: String }
 #[derive(Debug, Serialize)]
-pub struct ReadOutputStatusResultMCP { pub session_id: String, pub is_running: bool, pub exit_code: Option<i32>, pub message: String, pub recent_output: Option<String> }
+pub struct ReadOutputStatusResultMCP {
+    pub session_id: String, pub is_running: bool, pub exit_code: Option<i32>, pub message: String, pub recent_output: Option<String>,
+    pub lines: Vec<SessionOutputLine>,
+    pub total_lines: usize,
+    // Pass back as `from_line` to continue reading where this response stopped.
+    pub next_line: usize,
+    // Older output each stream discarded to stay within limits.max_session_output_bytes.
+    pub dropped_stdout: DroppedOutput,
+    pub dropped_stderr: DroppedOutput,
+}
 
 
-fn is_command_blocked_mcp(command_str: &str, config: &Config) -> bool {
-    let first_command_word = command_str.trim_start().split_whitespace().next().unwrap_or("");
+fn is_command_blocked_mcp(command_str: &str, config: &Config, dir_policy: &DirPolicy) -> bool {
+    let command_line = command_str.trim();
+    let first_command_word = command_line.split_whitespace().next().unwrap_or("");
     if first_command_word.is_empty() { return false; }
-    match config.get_blocked_command_regexes() {
-        Ok(regexes) => regexes.iter().any(|regex| regex.is_match(first_command_word)),
-        Err(e) => { warn!("Error compiling blocked command regexes: {}. Blocking {} as precaution.", e, first_command_word); config.blocked_commands.iter().any(|b| b == first_command_word)}
-    }
+    config.blocked_commands.iter().chain(dir_policy.blocked_commands()).any(|blocked| {
+        let is_regex_entry = blocked.starts_with(BLOCKED_COMMAND_REGEX_PREFIX);
+        match blocked_command_regex(blocked) {
+            Ok(regex) => regex.is_match(command_line),
+            // A broken policy regex fails closed rather than letting everything through.
+            Err(e) if is_regex_entry => { warn!("{:#}. Blocking {} as precaution.", e, first_command_word); true }
+            Err(e) => { warn!("Error compiling blocked command regex: {}. Blocking {} as precaution.", e, first_command_word); blocked == first_command_word }
+        }
+    })
 }
 
 #[instrument(skip(deps, params), fields(command = %params.command))]
 pub async fn mcp_execute_command(deps: &ToolDependencies, params: ExecuteCommandParamsMCP) -> Result<ExecuteCommandResultMCP, AppError> {
-    let (cwd_path, shell_to_use_opt, is_blocked) = { // Scope for config_guard
-        let config_guard = deps.config_state.read().map_err(|e| AppError::ConfigError(format!("Config lock: {}", e)))?;
-        let blocked = is_command_blocked_mcp(&params.command, &*config_guard);
-        let cwd = config_guard.files_root.clone();
-        let shell_opt = params.shell.clone().or_else(|| config_guard.default_shell.clone());
-        (cwd, shell_opt, blocked)
-    }; // config_guard is dropped here
+    let (cwd_path, shell_to_use_opt, is_blocked, execution_target, files_root, limits) = {
+        let config = deps.config_state.snapshot();
+        // Commands run in files_root, so policy files from there upwards apply.
+        let dir_policy = DirPolicy::for_path(&config.files_root, &config.files_root)?;
+        let blocked = match &params.raw_args {
+            // No shell parses a raw invocation: entries see the program's name followed by the joined arguments.
+            Some(raw_args) => {
+                let program_stem = std::path::Path::new(params.command.trim()).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                is_command_blocked_mcp(&join_command_line(ShellKind::Posix, &program_stem, raw_args), &config, &dir_policy)
+            }
+            None => is_command_blocked_mcp(&params.command, &config, &dir_policy),
+        };
+        let target = ExecutionTarget::resolve(params.execution_target.as_deref(), &config)?;
+        let cwd = config.files_root.clone();
+        // The configured default shell describes this machine, so remote targets only honor an explicit shell.
+        let shell_opt = if target.is_local() { params.shell.clone().or_else(|| config.default_shell.clone()) } else { params.shell.clone() };
+        (cwd.clone(), shell_opt, blocked, target, cwd, config.limits.clone())
+    };
 
     if is_blocked {
         return Err(AppError::CommandBlocked(params.command.clone()));
     }
 
+    // Finished sessions stay in the map for scrollback, so only running ones count.
+    let running_sessions = {
+        let sessions_map_guard = deps.active_sessions_map.lock().await;
+        let mut running = 0;
+        for session in sessions_map_guard.values() {
+            if session.exit_code.lock().await.is_none() { running += 1; }
+        }
+        running
+    };
+    if running_sessions >= limits.max_concurrent_sessions {
+        return Err(AppError::LimitExceeded { message: format!("{} command sessions are running; limits.max_concurrent_sessions is {}", running_sessions, limits.max_concurrent_sessions), limit: "limits.max_concurrent_sessions".to_string(), max: limits.max_concurrent_sessions as u64, actual: running_sessions as u64 });
+    }
+
     let session_id = Uuid::new_v4().to_string();
 
-    let (mut command_obj, _shell_args_for_scope_check, _program_name_for_scope_check): (TauriShellCommand, Vec<String>, String) =
-        if let Some(shell_path_str) = &shell_to_use_opt {
-            let mut args = Vec::new();
-            if shell_path_str.contains("powershell") || shell_path_str.contains("cmd.exe") { args.push("-Command".to_string()); }
-            else { args.push("-c".to_string()); }
-            args.push(params.command.clone());
-            (deps.app_handle.shell().command(shell_path_str.clone()).args(args.clone()), args, shell_path_str.clone())
+    // Remote and container targets always run a POSIX command line.
+    let remote_command = match (&params.raw_args, &shell_to_use_opt) {
+        (Some(raw_args), _) => join_command_line(ShellKind::Posix, params.command.trim(), raw_args),
+        (None, Some(shell)) => join_command_line(ShellKind::Posix, shell, &shell_invocation_args(ShellKind::Posix, &params.command)),
+        (None, None) => params.command.clone(),
+    };
+    let mut cleanup_command: Option<Vec<String>> = None;
+
+    let (mut command_obj, session_command_str): (TauriShellCommand, String) =
+        if let ExecutionTarget::Ssh(host) = &execution_target {
+            let remote_cwd = host.remote_root.as_deref().and_then(|root| map_local_path_to_remote(&cwd_path, &files_root, root));
+            (build_ssh_command(&deps.app_handle, host, &remote_command, remote_cwd.as_deref())?, remote_command)
+        } else if let ExecutionTarget::Container(container_target) = &execution_target {
+            let container_cmd = build_container_command(&deps.app_handle, container_target, &remote_command, &files_root, &cwd_path, &session_id)?;
+            cleanup_command = container_cmd.cleanup_command;
+            (container_cmd.command, remote_command)
+        } else if let Some(raw_args) = &params.raw_args {
+            let program = params.command.trim();
+            if program.is_empty() { return Err(AppError::CommandExecutionError("Empty command".into())); }
+            let display_kind = if cfg!(windows) { ShellKind::Cmd } else { ShellKind::Posix };
+            (deps.app_handle.shell().command(program.to_string()).args(raw_args.clone()), join_command_line(display_kind, program, raw_args))
+        } else if let Some(shell_path_str) = &shell_to_use_opt {
+            let args = shell_invocation_args(ShellKind::detect(shell_path_str), &params.command);
+            (deps.app_handle.shell().command(shell_path_str.clone()).args(args), params.command.clone())
         } else {
-            let mut parts = params.command.split_whitespace();
+            let mut parts = split_command_line(&params.command).into_iter();
             let prog = parts.next().ok_or_else(|| AppError::CommandExecutionError("Empty command".into()))?;
-            let args: Vec<String> = parts.map(String::from).collect();
-            (deps.app_handle.shell().command(prog.to_string()).args(args.clone()), args, prog.to_string())
+            let args: Vec<String> = parts.collect();
+            (deps.app_handle.shell().command(prog).args(args), params.command.clone())
         };
 
     command_obj = command_obj.current_dir(cwd_path);
 
-    debug!(shell = ?shell_to_use_opt, command = %params.command, "MCP Tool: Spawning command via tauri-plugin-shell");
+    debug!(shell = ?shell_to_use_opt, command = %params.command, target = %execution_target.label(), "MCP Tool: Spawning command via tauri-plugin-shell");
     let (mut rx, child_proc_handle) = command_obj.spawn().map_err(|e| AppError::CommandExecutionError(format!("Spawn failed: {}", e)))?;
     let pid_val = child_proc_handle.pid();
 
     let active_session_arc = Arc::new(ActiveSession {
         process_child: Arc::new(TokioMutex::new(Some(child_proc_handle))),
-        command_str: params.command.clone(),
+        command_str: session_command_str,
         exit_code: Arc::new(TokioMutex::new(None)),
         start_time_system: std::time::SystemTime::now(),
         session_id: session_id.clone(),
         pid: Some(pid_val),
+        output: Arc::new(TokioMutex::new(SessionOutput::new(limits.max_session_output_bytes))),
+        execution_target: execution_target.label(),
+        cleanup_command,
+        owner: deps.client.as_ref().map(|c| c.owner.clone()),
     });
     
-    // This await was the problematic one with the config_guard potentially still alive.
-    // Now config_guard is dropped, so this should be fine.
-    deps.active_sessions_map.lock().await.insert(session_id.clone(), active_session_arc.clone());
+    {
+        let mut sessions_map_guard = deps.active_sessions_map.lock().await;
+        prune_finished_sessions(&mut sessions_map_guard).await;
+        sessions_map_guard.insert(session_id.clone(), active_session_arc.clone());
+    }
 
-    let initial_output_timeout_ms = params.timeout_ms.unwrap_or(1000);
+    let initial_output_timeout = deps.call_timeout(&limits, params.timeout_ms, 1000);
     let mut initial_stdout_lines = Vec::new();
     let mut initial_stderr_lines = Vec::new();
     let mut timed_out_flag = false;
@@ -113,7 +206,13 @@ pub async fn mcp_execute_command(deps: &ToolDependencies, params: ExecuteCommand
 
     let output_collection_start_time = TokioInstant::now();
     loop {
-        if output_collection_start_time.elapsed() > Duration::from_millis(initial_output_timeout_ms) {
+        if deps.cancel.is_cancelled() {
+            // The session was never handed to the client, so nothing else would stop it.
+            info!(sid = %session_id, pid = pid_val, "execute_command cancelled by the client; terminating session");
+            mcp_force_terminate_session(deps, ForceTerminateParamsMCP { session_id: session_id.clone() }).await?;
+            return Err(AppError::Cancelled(format!("execute_command '{}'", params.command)));
+        }
+        if output_collection_start_time.elapsed() > initial_output_timeout {
             if early_exit_code.is_none() { timed_out_flag = true; }
             break;
         }
@@ -121,8 +220,16 @@ pub async fn mcp_execute_command(deps: &ToolDependencies, params: ExecuteCommand
         match timeout(Duration::from_millis(50), rx.recv()).await {
             Ok(Some(event)) => { 
                 match event {
-                    CommandEvent::Stdout(line) => initial_stdout_lines.push(String::from_utf8_lossy(&line).into_owned()),
-                    CommandEvent::Stderr(line) => initial_stderr_lines.push(String::from_utf8_lossy(&line).into_owned()),
+                    CommandEvent::Stdout(line) => {
+                        let text = String::from_utf8_lossy(&line).into_owned();
+                        active_session_arc.append_output("stdout", text.clone()).await;
+                        initial_stdout_lines.push(text);
+                    }
+                    CommandEvent::Stderr(line) => {
+                        let text = String::from_utf8_lossy(&line).into_owned();
+                        active_session_arc.append_output("stderr", text.clone()).await;
+                        initial_stderr_lines.push(text);
+                    }
                     CommandEvent::Terminated(payload) => { early_exit_code = payload.code; break; }
                     CommandEvent::Error(msg) => { error!("Cmd error during initial read: {}", msg); early_exit_code = Some(-1); break; }
                     _ => {} 
@@ -135,12 +242,15 @@ pub async fn mcp_execute_command(deps: &ToolDependencies, params: ExecuteCommand
         }
     }
 
-    let combined_initial_output = format!("STDOUT:\n{}\nSTDERR:\n{}", initial_stdout_lines.join("\n"), initial_stderr_lines.join("\n"));
+    let mut combined_initial_output = format!("STDOUT:\n{}\nSTDERR:\n{}", initial_stdout_lines.join("\n"), initial_stderr_lines.join("\n"));
+    if truncate_output(&mut combined_initial_output, limits.max_output_bytes) {
+        combined_initial_output.push_str("\n[output truncated at limits.max_output_bytes; use read_session_output_status for the rest]");
+    }
 
     let app_handle_clone = deps.app_handle.clone();
+    let notifier_clone = deps.notifier.clone();
     let session_id_clone_for_task = session_id.clone();
     let active_session_clone_for_task = active_session_arc.clone();
-    let sessions_map_clone_for_task = deps.active_sessions_map.clone();
 
     if early_exit_code.is_none() {
         tokio::spawn(async move {
@@ -149,23 +259,29 @@ pub async fn mcp_execute_command(deps: &ToolDependencies, params: ExecuteCommand
                     Some(event_from_channel) => {
                         match event_from_channel {
                             CommandEvent::Stdout(line) => {
-                                app_handle_clone.emit_to("main", &format!("terminal_output_{}", session_id_clone_for_task), json!({"type": "stdout", "data": String::from_utf8_lossy(&line).into_owned()})).unwrap_or_else(|e| error!("Emit stdout failed: {}", e));
+                                let data = String::from_utf8_lossy(&line).into_owned();
+                                let line_index = active_session_clone_for_task.append_output("stdout", data.clone()).await;
+                                notifier_clone.notify_client(active_session_clone_for_task.owner.as_deref(), SESSION_OUTPUT_NOTIFICATION, json!({"session_id": session_id_clone_for_task, "stream": "stdout", "line": line_index, "data": data}));
+                                app_handle_clone.emit_to("main", &format!("terminal_output_{}", session_id_clone_for_task), json!({"type": "stdout", "data": data})).unwrap_or_else(|e| error!("Emit stdout failed: {}", e));
                             }
                             CommandEvent::Stderr(line) => {
-                                app_handle_clone.emit_to("main", &format!("terminal_output_{}", session_id_clone_for_task), json!({"type": "stderr", "data": String::from_utf8_lossy(&line).into_owned()})).unwrap_or_else(|e| error!("Emit stderr failed: {}", e));
+                                let data = String::from_utf8_lossy(&line).into_owned();
+                                let line_index = active_session_clone_for_task.append_output("stderr", data.clone()).await;
+                                notifier_clone.notify_client(active_session_clone_for_task.owner.as_deref(), SESSION_OUTPUT_NOTIFICATION, json!({"session_id": session_id_clone_for_task, "stream": "stderr", "line": line_index, "data": data}));
+                                app_handle_clone.emit_to("main", &format!("terminal_output_{}", session_id_clone_for_task), json!({"type": "stderr", "data": data})).unwrap_or_else(|e| error!("Emit stderr failed: {}", e));
                             }
                             CommandEvent::Terminated(payload) => {
                                 info!(sid = %session_id_clone_for_task, code = ?payload.code, "Background task: Command terminated");
                                 *active_session_clone_for_task.exit_code.lock().await = payload.code;
+                                notifier_clone.notify_client(active_session_clone_for_task.owner.as_deref(), SESSION_EXITED_NOTIFICATION, json!({"session_id": session_id_clone_for_task, "exit_code": payload.code, "signal": payload.signal}));
                                 app_handle_clone.emit_to("main", &format!("terminal_output_{}", session_id_clone_for_task), json!({"type": "terminated", "code": payload.code, "signal": payload.signal })).unwrap_or_else(|e| error!("Emit terminated failed: {}", e));
-                                sessions_map_clone_for_task.lock().await.remove(&session_id_clone_for_task);
                                 break;
                             }
                             CommandEvent::Error(message) => { 
                                 error!(sid = %session_id_clone_for_task, message = %message, "Background task: Command error in stream");
                                 *active_session_clone_for_task.exit_code.lock().await = Some(-1); 
+                                notifier_clone.notify_client(active_session_clone_for_task.owner.as_deref(), SESSION_EXITED_NOTIFICATION, json!({"session_id": session_id_clone_for_task, "exit_code": -1, "error": message}));
                                 app_handle_clone.emit_to("main", &format!("terminal_output_{}", session_id_clone_for_task), json!({"type": "error", "data": message })).unwrap_or_else(|e| error!("Emit error failed: {}", e));
-                                sessions_map_clone_for_task.lock().await.remove(&session_id_clone_for_task);
                                 break;
                             }
                             _other_event => { /* e.g. CommandEvent::Pid - can ignore or log */ }
@@ -176,8 +292,9 @@ pub async fn mcp_execute_command(deps: &ToolDependencies, params: ExecuteCommand
                         if active_session_clone_for_task.exit_code.lock().await.is_none() {
                             *active_session_clone_for_task.exit_code.lock().await = Some(0);
                         }
+                        let exit_code_on_close = *active_session_clone_for_task.exit_code.lock().await;
+                        notifier_clone.notify_client(active_session_clone_for_task.owner.as_deref(), SESSION_EXITED_NOTIFICATION, json!({"session_id": session_id_clone_for_task, "exit_code": exit_code_on_close}));
                         app_handle_clone.emit_to("main", &format!("terminal_output_{}", session_id_clone_for_task), json!({"type": "finished_stream_closed"})).unwrap_or_else(|e| error!("Emit finished failed: {}", e));
-                        sessions_map_clone_for_task.lock().await.remove(&session_id_clone_for_task);
                         break;
                     }
                 }
@@ -186,7 +303,7 @@ pub async fn mcp_execute_command(deps: &ToolDependencies, params: ExecuteCommand
         });
     } else {
         *active_session_arc.exit_code.lock().await = early_exit_code;
-        deps.active_sessions_map.lock().await.remove(&session_id);
+        deps.notifier.notify_client(active_session_arc.owner.as_deref(), SESSION_EXITED_NOTIFICATION, json!({"session_id": session_id, "exit_code": early_exit_code}));
     }
 
     let final_exit_code = *active_session_arc.exit_code.lock().await;
@@ -197,16 +314,48 @@ pub async fn mcp_execute_command(deps: &ToolDependencies, params: ExecuteCommand
     Ok(ExecuteCommandResultMCP { session_id, pid: Some(pid_val), initial_output: combined_initial_output, timed_out: timed_out_flag, exit_code: final_exit_code, message })
 }
 
+/// Drops the earliest-started finished sessions beyond MAX_FINISHED_SESSIONS, so their output
+/// buffers do not pile up over a long run.
+async fn prune_finished_sessions(sessions: &mut HashMap<String, Arc<ActiveSession>>) {
+    let mut finished = Vec::new();
+    for (id, session) in sessions.iter() {
+        if session.exit_code.lock().await.is_some() { finished.push((session.start_time_system, id.clone())); }
+    }
+    if finished.len() <= MAX_FINISHED_SESSIONS { return; }
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() - MAX_FINISHED_SESSIONS) {
+        debug!(session_id = %id, "Dropping finished session");
+        sessions.remove(id);
+    }
+}
+
+/// Runs the session's cleanup command (e.g. removing a throwaway container) after its client process is killed.
+async fn run_session_cleanup(deps: &ToolDependencies, session: &ActiveSession) {
+    let Some(cleanup) = &session.cleanup_command else { return; };
+    let Some((program, args)) = cleanup.split_first() else { return; };
+    match deps.app_handle.shell().command(program.clone()).args(args.to_vec()).output().await {
+        Ok(output) if output.status.success() => debug!(sid = %session.session_id, "Session cleanup command succeeded"),
+        Ok(output) => warn!(sid = %session.session_id, stderr = %String::from_utf8_lossy(&output.stderr), "Session cleanup command failed"),
+        Err(e) => warn!(sid = %session.session_id, error = %e, "Failed to run session cleanup command"),
+    }
+}
+
+/// Another client's session is reported as not found, so its ids are not confirmed either.
+async fn visible_session(deps: &ToolDependencies, session_id: &str) -> Option<Arc<ActiveSession>> {
+    deps.active_sessions_map.lock().await.get(session_id).filter(|s| deps.owns_session(s)).cloned()
+}
 
 pub async fn mcp_force_terminate_session(deps: &ToolDependencies, params: ForceTerminateParamsMCP) -> Result<ForceTerminateResultMCP, AppError> {
     let session_id_to_terminate = params.session_id;
-    if let Some(session_arc) = deps.active_sessions_map.lock().await.get(&session_id_to_terminate).cloned() {
+    if let Some(session_arc) = visible_session(deps, &session_id_to_terminate).await {
         let mut child_opt_guard = session_arc.process_child.lock().await;
         if let Some(child_to_kill_instance) = child_opt_guard.take() {
             match child_to_kill_instance.kill() {
                 Ok(_) => {
                     info!(sid = %session_id_to_terminate, pid = ?session_arc.pid, "MCP Tool: Termination signal sent.");
                     *session_arc.exit_code.lock().await = Some(-9);
+                    run_session_cleanup(deps, &session_arc).await;
+                    deps.notifier.notify_client(session_arc.owner.as_deref(), SESSION_KILLED_NOTIFICATION, json!({"session_id": session_id_to_terminate, "pid": session_arc.pid, "exit_code": -9}));
                     deps.active_sessions_map.lock().await.remove(&session_id_to_terminate);
                     Ok(ForceTerminateResultMCP { session_id: session_id_to_terminate, success: true, message: "Termination signal sent.".into() })
                 }
@@ -220,7 +369,9 @@ pub async fn mcp_force_terminate_session(deps: &ToolDependencies, params: ForceT
                 }
             }
         } else {
-            Ok(ForceTerminateResultMCP { session_id: session_id_to_terminate, success: true, message: "Process already terminated or not found in session's Option.".into() })
+            // Finished sessions are kept for scrollback; terminating one just releases it.
+            deps.active_sessions_map.lock().await.remove(&session_id_to_terminate);
+            Ok(ForceTerminateResultMCP { session_id: session_id_to_terminate, success: true, message: "Process already terminated; session output released.".into() })
         }
     } else { Err(AppError::SessionNotFound(session_id_to_terminate)) }
 }
@@ -231,7 +382,7 @@ pub async fn mcp_list_sessions(deps: &ToolDependencies) -> Result<Vec<SessionInf
     let mut infos = Vec::new();
     let now_sys_time = std::time::SystemTime::now();
 
-    for (id, session_arc) in sessions_map_guard.iter() {
+    for (id, session_arc) in sessions_map_guard.iter().filter(|(_, s)| deps.owns_session(s)) {
         let exit_code_val = *session_arc.exit_code.lock().await;
         infos.push(SessionInfoMCP {
             session_id: id.clone(),
@@ -240,6 +391,7 @@ pub async fn mcp_list_sessions(deps: &ToolDependencies) -> Result<Vec<SessionInf
             is_running: exit_code_val.is_none(),
             start_time_iso: chrono::DateTime::<Utc>::from(session_arc.start_time_system).to_rfc3339(),
             runtime_ms: now_sys_time.duration_since(session_arc.start_time_system).unwrap_or_default().as_millis(),
+            execution_target: session_arc.execution_target.clone(),
         });
     }
     Ok(infos)
@@ -247,14 +399,37 @@ pub async fn mcp_list_sessions(deps: &ToolDependencies) -> Result<Vec<SessionInf
 
 pub async fn mcp_read_session_output_status(deps: &ToolDependencies, params: ReadOutputStatusParamsMCP) -> Result<ReadOutputStatusResultMCP, AppError> {
     let session_id_to_read = params.session_id;
-    if let Some(session_arc) = deps.active_sessions_map.lock().await.get(&session_id_to_read).cloned() {
+    let (read_limit, max_output_bytes) = {
+        let config = deps.config_state.snapshot();
+        (config.file_read_line_limit, config.limits.max_output_bytes)
+    };
+    if let Some(session_arc) = visible_session(deps, &session_id_to_read).await {
         let exit_code_val = *session_arc.exit_code.lock().await;
+        let output_guard = session_arc.output.lock().await;
+        let total_lines = output_guard.total_lines();
+        let from_line = params.from_line.unwrap_or(0).min(total_lines);
+        let mut to_line = params.to_line.unwrap_or(total_lines).min(total_lines).min(from_line.saturating_add(read_limit)).max(from_line);
+        let mut lines: Vec<SessionOutputLine> = output_guard.lines(from_line, to_line);
+        let (dropped_stdout, dropped_stderr) = (output_guard.dropped_stdout(), output_guard.dropped_stderr());
+        drop(output_guard);
+        // Stop early rather than splitting a line when the range exceeds max_output_bytes.
+        let mut output_bytes = 0usize;
+        if let Some(over) = lines.iter().position(|l| { output_bytes += l.text.len() + 1; output_bytes > max_output_bytes }) {
+            lines.truncate(over.max(1));
+            to_line = lines[lines.len() - 1].index + 1;
+        }
+        let recent_output = if lines.is_empty() { None } else { Some(lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>().join("\n")) };
         Ok(ReadOutputStatusResultMCP {
             session_id: session_id_to_read,
             is_running: exit_code_val.is_none(),
             exit_code: exit_code_val,
-            message: "Session status. For UI, output is streamed via Tauri events. MCP client cannot directly access this stream without further adaptation.".into(),
-            recent_output: None
+            message: format!("Returned output lines {}..{} of {}. Pass next_line as from_line to continue.", from_line, to_line, total_lines),
+            recent_output,
+            lines,
+            total_lines,
+            next_line: to_line,
+            dropped_stdout,
+            dropped_stderr,
         })
     } else {
         Ok(ReadOutputStatusResultMCP {
@@ -262,7 +437,12 @@ pub async fn mcp_read_session_output_status(deps: &ToolDependencies, params: Rea
             is_running: false,
             exit_code: None,
             message: "Session not found or already terminated and cleaned up.".into(),
-            recent_output: None
+            recent_output: None,
+            lines: Vec::new(),
+            total_lines: 0,
+            next_line: 0,
+            dropped_stdout: DroppedOutput::default(),
+            dropped_stderr: DroppedOutput::default(),
         })
     }
-}
\ No newline at end of file
+}
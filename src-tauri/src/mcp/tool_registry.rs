@@ -0,0 +1,354 @@
+use crate::config::Config;
+use crate::error::AppError;
+use crate::mcp::batch;
+use crate::mcp::custom_tools;
+use crate::mcp::handler::{create_mcp_json_call_tool_result, mcp_call_tool_error_from_app_error, EnhancedServerHandler, ToolDependencies, changes_state, DESTRUCTIVE_TOOLS};
+use crate::mcp::sampling::{self, RawOutputStore};
+use crate::mcp::schemas::*;
+use crate::mcp::tool_impl;
+
+use rust_mcp_sdk::McpServer;
+use rust_mcp_schema::schema_utils::{CallToolError, RpcErrorCodes};
+use rust_mcp_schema::{CallToolResult, RpcError, ToolAnnotations, ToolInputSchema};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<CallToolResult, CallToolError>> + Send + 'a>>;
+
+/// Tools that reach outside the local machine's files and processes (URLs, arbitrary commands, remote targets).
+const OPEN_WORLD_TOOLS: &[&str] = &["read_file", "read_multiple_files", "execute_command"];
+
+/// What a tool gets to run one call with. The handler has already checked, audited, rate-limited
+/// and (unless the tool opts out) queued the call.
+#[derive(Clone, Copy)]
+pub struct ToolContext<'a> {
+    pub name: &'static str,
+    // Carries the call's cancel token, deadline and correlation id.
+    pub deps: &'a ToolDependencies,
+    pub runtime: &'a dyn McpServer,
+    pub handler: &'a EnhancedServerHandler,
+    pub raw_outputs: &'a RawOutputStore,
+}
+
+impl ToolContext<'_> {
+    /// The call's arguments as the tool's parameter struct.
+    pub fn params<P: DeserializeOwned>(&self, args: Value) -> Result<P, CallToolError> {
+        serde_json::from_value(args).map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INVALID_PARAMS, e.to_string(), None)))
+    }
+
+    /// A tool's outcome as a JSON text result, or as the error it failed with.
+    pub fn respond<R: Serialize>(&self, outcome: Result<R, AppError>) -> Result<CallToolResult, CallToolError> {
+        let result = outcome.map_err(|e| mcp_call_tool_error_from_app_error(e, self.name))?;
+        let value = serde_json::to_value(result).map_err(|e| CallToolError::new(RpcError::new(RpcErrorCodes::INTERNAL_ERROR, format!("Failed to serialize result: {}", e), None)))?;
+        create_mcp_json_call_tool_result(value)
+    }
+}
+
+/// A tool this server offers: what tools/list shows for it and what tools/call runs.
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// One of handler::TOOL_CATEGORIES, for category-filtered tools/list pages.
+    fn category(&self) -> &'static str;
+
+    fn description(&self) -> String;
+
+    fn input_schema(&self) -> ToolInputSchema;
+
+    /// MCP hints so clients can auto-approve read-only tools and confirm destructive ones.
+    /// They are advisory: read_only mode and kill_scope are what actually restrict tools.
+    fn annotations(&self) -> ToolAnnotations {
+        let name = self.name();
+        // batch_tools can run any of them, so it is hinted like the most dangerous one.
+        let read_only = !self.mutating() && name != "batch_tools";
+        // (destructive, idempotent)
+        let (destructive, idempotent) = match name {
+            name if DESTRUCTIVE_TOOLS.contains(&name) => (true, false),
+            "batch_tools" => (true, false),
+            "snapshot_workspace" | "git_stage_hunks" | "git_commit" | "git_create_branch" => (false, false),
+            _ => (false, true),
+        };
+        ToolAnnotations {
+            title: None,
+            read_only_hint: Some(read_only),
+            // Only meaningful for tools that modify something.
+            destructive_hint: (!read_only).then_some(destructive),
+            idempotent_hint: (!read_only).then_some(idempotent),
+            open_world_hint: Some(OPEN_WORLD_TOOLS.contains(&name)),
+        }
+    }
+
+    /// Hidden in read_only mode and from read tokens.
+    fn mutating(&self) -> bool {
+        changes_state(self.name())
+    }
+
+    /// Covered by `destructive` in approval_required_tools and policy_rules.
+    fn destructive(&self) -> bool {
+        DESTRUCTIVE_TOOLS.contains(&self.name())
+    }
+
+    /// Whether the call waits for a limits.max_concurrent_calls slot before it runs.
+    fn takes_call_slot(&self) -> bool {
+        true
+    }
+
+    fn execute<'a>(&'a self, ctx: ToolContext<'a>, args: Value) -> ToolFuture<'a>;
+}
+
+type RunFn = Box<dyn for<'a> Fn(ToolContext<'a>, Value) -> ToolFuture<'a> + Send + Sync>;
+
+/// A tool defined by its metadata and a closure, which is how every built-in tool is registered.
+pub struct BuiltinTool {
+    name: &'static str,
+    category: &'static str,
+    description: String,
+    schema: fn() -> ToolInputSchema,
+    takes_call_slot: bool,
+    run: RunFn,
+}
+
+impl BuiltinTool {
+    pub fn new<F>(name: &'static str, category: &'static str, description: impl Into<String>, schema: fn() -> ToolInputSchema, run: F) -> Self
+    where
+        F: for<'a> Fn(ToolContext<'a>, Value) -> ToolFuture<'a> + Send + Sync + 'static,
+    {
+        Self { name, category, description: description.into(), schema, takes_call_slot: true, run: Box::new(run) }
+    }
+
+    pub fn without_call_slot(self) -> Self {
+        Self { takes_call_slot: false, ..self }
+    }
+}
+
+impl Tool for BuiltinTool {
+    fn name(&self) -> &'static str { self.name }
+    fn category(&self) -> &'static str { self.category }
+    fn description(&self) -> String { self.description.clone() }
+    fn input_schema(&self) -> ToolInputSchema { (self.schema)() }
+    fn takes_call_slot(&self) -> bool { self.takes_call_slot }
+
+    fn execute<'a>(&'a self, ctx: ToolContext<'a>, args: Value) -> ToolFuture<'a> {
+        (self.run)(ctx, args)
+    }
+}
+
+/// The tools this server implements itself, in tools/list order, followed by the custom tools.
+/// Downstream tools are listed and routed by DownstreamHub instead, since they come and go with
+/// the downstream servers.
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn register(&mut self, tool: impl Tool + 'static) {
+        debug_assert!(self.get(tool.name()).is_none(), "tool '{}' registered twice", tool.name());
+        self.tools.push(Box::new(tool));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Tool> {
+        self.tools.iter().find(|tool| tool.name() == name).map(|tool| tool.as_ref())
+    }
+
+    /// The tools/list entries, annotated or not depending on the client's protocol version.
+    pub fn definitions(&self, annotated: bool) -> Vec<rust_mcp_schema::Tool> {
+        self.tools.iter().map(|tool| rust_mcp_schema::Tool {
+            name: tool.name().to_string(),
+            description: Some(tool.description()),
+            input_schema: tool.input_schema(),
+            annotations: annotated.then(|| tool.annotations()),
+        }).collect()
+    }
+
+    /// The built-in tools plus custom_tools and the modules in wasm_tools_dir.
+    pub fn with_custom_tools(config: &Config) -> Self {
+        let mut registry = Self::builtin();
+        custom_tools::register(&mut registry, config);
+        registry
+    }
+
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        registry.register(BuiltinTool::new("mcp_get_config", "config", "Get the MCP server's current runtime configuration.", get_mcp_config_schema, |ctx, _args| Box::pin(async move {
+            let config = Config::clone(&ctx.deps.config_state.snapshot());
+            ctx.respond(Ok(config))
+        })));
+        registry.register(BuiltinTool::new("validate_config", "config", "Check the effective config for problems (missing allowed directories, invalid blocked commands, missing rg/shell/ssh/container runtime, unsafe SSE host) and return structured errors and warnings.", validate_config_mcp_schema, |ctx, _args| Box::pin(async move {
+            // The SSE port is bound by this very server once tools can be called.
+            let report = crate::config_validation::validate_config(&ctx.deps.config_state.snapshot(), false);
+            ctx.respond(Ok(report))
+        })));
+        registry.register(BuiltinTool::new("use_profile", "config", "Switch to a named config profile ('safe', 'dev', 'ci' or a user-defined one) that bundles allowed directories, blocked commands, limits and kill/environment policies. Returns the keys that changed.", use_profile_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::config::mcp_use_profile(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("get_config_schema", "config", "Get JSON Schemas for the runtime config (as returned by mcp_get_config), settings.json and config.toml, for generating forms or validating input.", get_config_schema_mcp_schema, |ctx, _args| Box::pin(async move {
+            ctx.respond(Ok(crate::config::config_json_schemas()))
+        })));
+        registry.register(BuiltinTool::new("show_effective_config", "config", "Show every effective config value with the layer it came from (default, system/user/workspace config.toml, env, settings.json, runtime) and which config files were loaded.", show_effective_config_mcp_schema, |ctx, _args| Box::pin(async move {
+            let report = crate::config_layers::effective_config_report(&ctx.deps.config_state.snapshot());
+            ctx.respond(Ok(report))
+        })));
+        registry.register(BuiltinTool::new("set_active_workspace", "config", "Select which configured workspace root relative paths resolve against and commands start in. Paths in every root stay accessible, subject to each root's allowed/denied rules.", set_active_workspace_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::config::mcp_set_active_workspace(ctx.deps, ctx.params(args)?))
+        })));
+        registry.register(BuiltinTool::new("export_config", "config", "Export the effective config as one portable JSON blob (config.toml key names). Secrets are never included; machine-specific paths only on request.", export_config_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::config::mcp_export_config(ctx.deps, ctx.params(args)?))
+        })));
+        registry.register(BuiltinTool::new("import_config", "config", "Import a blob from export_config into the user config.toml (merge or replace, keeping a .bak) and hot-reload it. Keys that affect security (allowed directories, denied_globs, blocked_commands, kill_scope, approvals, policy rules, custom tools, downstream servers, ...) can only be imported from the app. Reports keys that still need a restart or are overridden by a higher layer.", import_config_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::config::mcp_import_config(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("read_file", "filesystem", "Read content of a local file or URL.", read_file_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::filesystem::mcp_read_file(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("write_file", "filesystem", "Write/append content to a file.", write_file_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::filesystem::mcp_write_file(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("create_directory", "filesystem", "Create directories, including nested ones.", create_directory_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::filesystem::mcp_create_directory(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("list_directory", "filesystem", "List directory contents.", list_directory_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::filesystem::mcp_list_directory(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("move_file", "filesystem", "Move or rename files or directories.", move_file_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::filesystem::mcp_move_file(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("get_file_info", "filesystem", "Get metadata for a file or directory.", get_file_info_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::filesystem::mcp_get_file_info(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("read_multiple_files", "filesystem", "Read multiple local files.", read_multiple_files_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::filesystem::mcp_read_multiple_files(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("search_files", "search", "Find files/dirs by name.", search_files_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::filesystem::mcp_search_files(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("search_code", "search", "Search code with Ripgrep.", search_code_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::ripgrep::mcp_search_code(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("execute_command", "terminal", "Run terminal commands. Output is streamed via events if using Tauri UI; for MCP, initial output/status returned.", execute_command_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::terminal::mcp_execute_command(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("force_terminate_session", "terminal", "Stop a running command session by its ID.", force_terminate_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::terminal::mcp_force_terminate_session(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("list_sessions", "terminal", "List command sessions, including finished ones whose output is still readable.", list_sessions_mcp_schema, |ctx, _args| Box::pin(async move {
+            ctx.respond(tool_impl::terminal::mcp_list_sessions(ctx.deps).await)
+        })));
+        registry.register(BuiltinTool::new("read_session_output_status", "terminal", "Get status and output of a command session. Output lines are indexed; re-read any range with from_line/to_line.", read_session_output_status_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::terminal::mcp_read_session_output_status(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("get_system_info", "system", "Get OS and kernel version, CPU model and core counts, memory and swap, per-mount disk usage, load average and uptime.", get_system_info_mcp_schema, |ctx, _args| Box::pin(async move {
+            ctx.respond(tool_impl::system::mcp_get_system_info(ctx.deps).await)
+        })));
+        // Takes no queue slot, so it still answers while limits.max_concurrent_calls is exhausted.
+        registry.register(BuiltinTool::new("get_server_stats", "system", "Get this server's health: uptime, per-tool call counts, errors and p50/p95 latencies, running calls, searches and command sessions, open client sessions, cache hit rates and memory usage.", get_server_stats_mcp_schema, |ctx, _args| Box::pin(async move {
+            ctx.respond(Ok(ctx.handler.server_stats().await))
+        })).without_call_slot());
+        registry.register(BuiltinTool::new("run_script", "system", "Run a rhai script server-side to do a multi-step read, search and transform in one call. Scripts can read files, list directories and search with regexes inside the allowed directories, but cannot write, run commands or reach the network. The value of the last expression is returned as JSON.", run_script_mcp_schema, |ctx, args| Box::pin(async move {
+            let params: tool_impl::script::RunScriptParamsMCP = ctx.params(args)?;
+            if params.background { return ctx.respond(tool_impl::script::mcp_start_script_job(ctx.deps, params)); }
+            ctx.respond(tool_impl::script::mcp_run_script(ctx.deps, params).await)
+        })));
+        registry.register(BuiltinTool::new("get_job_status", "system", "Get a background job's status, progress and, once it has finished, its result or error.", get_job_status_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::jobs::mcp_get_job_status(ctx.deps, ctx.params(args)?).await)
+        })).without_call_slot());
+        registry.register(BuiltinTool::new("cancel_job", "system", "Cancel a running background job.", cancel_job_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::jobs::mcp_cancel_job(ctx.deps, ctx.params(args)?).await)
+        })).without_call_slot());
+        registry.register(BuiltinTool::new("list_jobs", "system", "List background jobs, newest first, optionally by status (running, succeeded, failed, cancelled) or kind.", list_jobs_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::jobs::mcp_list_jobs(ctx.deps, ctx.params(args)?).await)
+        })).without_call_slot());
+        registry.register(BuiltinTool::new("snapshot_workspace", "filesystem", "Checkpoint a directory, files_root by default, into a compressed archive with a manifest of every file's SHA-256. Honours .gitignore, skips .git and files over limits.max_file_bytes. Returns a snapshot_id for restore_snapshot.", snapshot_workspace_mcp_schema, |ctx, args| Box::pin(async move {
+            let params: tool_impl::snapshot::SnapshotWorkspaceParamsMCP = ctx.params(args)?;
+            if params.background { return ctx.respond(tool_impl::snapshot::mcp_start_snapshot_job(ctx.deps, params)); }
+            ctx.respond(tool_impl::snapshot::mcp_snapshot_workspace(ctx.deps, params).await)
+        })));
+        registry.register(BuiltinTool::new("restore_snapshot", "filesystem", "Roll a directory back to a snapshot: rewrites changed and missing files and, with delete_extra, deletes files added since. Runs as a dry run listing the differences unless dry_run is false.", restore_snapshot_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::snapshot::mcp_restore_snapshot(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("list_snapshots", "filesystem", "List workspace snapshots, newest first, with their label, file count and size.", list_snapshots_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::snapshot::mcp_list_snapshots(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("find_repo_root", "git", "Find the git repository enclosing a path: its root, current branch, HEAD commit and whether it has uncommitted changes. Answers in_repository: false outside a repository.", find_repo_root_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_find_repo_root(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_status", "git", "Show the git status of the repository containing a path: the branch and the staged, unstaged, untracked and conflicted files, without running git.", git_status_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_status(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_diff", "git", "Show a unified diff of unstaged changes, staged changes (staged: true) or the working tree against a revision (base), for the whole repository or one path, with per-file addition and deletion counts.", git_diff_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_diff(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_add", "git", "Stage files or directories, deletions included, in their git repository. Returns everything staged afterwards.", git_add_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_add(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_commit", "git", "Commit the staged changes of a git repository with a message and optional Signed-off-by trailer. dry_run: true shows the files and message that would be committed.", git_commit_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_commit(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_log", "git", "List commits newest first with hash, author, date, subject and body, optionally only those touching a path or starting from a revision, paged with offset/limit.", git_log_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_log(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_blame", "git", "Show which commit last changed each line of a file, or of a line range, grouped into hunks with the commit's author, date and subject. Uncommitted lines have no commit.", git_blame_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_blame(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_stage_hunks", "git", "Stage only some of a file's unstaged changes, picked by the hunk headers git_diff returned (e.g. \"@@ -12,7 +12,9 @@\"), to build a focused commit. Fails without staging anything if a header no longer matches.", git_stage_hunks_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_stage_hunks(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_branch_list", "git", "List a repository's local branches, and remote-tracking ones with include_remote, with their tip commit, upstream and ahead/behind counts.", git_branch_list_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_branch_list(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_create_branch", "git", "Create a local git branch from HEAD or a start_point, and optionally switch to it (checkout: true, which needs a clean working tree).", git_create_branch_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_create_branch(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("git_checkout", "git", "Switch to a local git branch. Refused while there are staged, unstaged or conflicted changes, or when untracked files would be overwritten.", git_checkout_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::git::mcp_git_checkout(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("query_audit_log", "system", "Query the audit log, rotated files included, newest first: filter by time range (since/until), tool, path substring and outcome (ok/error), and page with offset/limit. Each entry has its arguments and, for tool calls, whether they succeeded, the error and the duration.", query_audit_log_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::audit::mcp_query_audit_log(ctx.deps, ctx.params(args)?).await)
+        })));
+        // Takes no queue slot itself: each call in it waits for its own, so a batch cannot
+        // deadlock on limits.max_concurrent_calls.
+        registry.register(BuiltinTool::new(batch::BATCH_TOOL, "system", format!("Run up to {} tool calls in order in one round trip, e.g. read_file, edit_block, read_file. Stops at the first failing call unless stop_on_error is false, and returns every call's result or error. Each call is checked, rate-limited and audited like a separate call.", batch::MAX_BATCH_CALLS), batch_tools_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.handler.run_batch(ctx.params(args)?, ctx.runtime, &ctx.deps.cancel, ctx.deps.deadline).await
+        })).without_call_slot());
+        registry.register(BuiltinTool::new("fetch_raw_output", "system", "Read the full output behind a summarized result (search_code, execute_command or read_session_output_status called with summarize: true) or a message_too_large error in byte chunks, optionally gzip-compressed.", fetch_raw_output_mcp_schema, |ctx, args| Box::pin(async move {
+            let params = ctx.params(args)?;
+            // Half the message limit leaves room for JSON escaping.
+            let max_chunk_bytes = { let c = ctx.deps.config_state.snapshot(); c.limits.max_output_bytes.min(c.limits.max_message_bytes / 2) };
+            ctx.respond(sampling::fetch_raw_output(ctx.raw_outputs, params, max_chunk_bytes))
+        })));
+        registry.register(BuiltinTool::new("list_processes", "process", "List system processes with optional name/user filters, sorting by cpu, mem or pid, and a result limit (default 100).", list_processes_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_list_processes(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("get_process_details", "process", "Get command line, cwd, parent PID, start time, user, open file count and (if enabled in config) environment for a PID.", get_process_details_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_get_process_details(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("monitor_process", "process", "Sample CPU, memory and disk I/O of a PID or session at an interval for a duration and return the time series with a summary.", monitor_process_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_monitor_process(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("set_process_priority", "process", "Change a process's scheduling priority (nice level on Unix, priority class on Windows), e.g. to deprioritize a long build. Raising priority usually requires elevated privileges.", set_process_priority_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_set_process_priority(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("watch_process", "process", "Watch a PID in the background and receive a notifications/process/exited notification (with exit code for session processes) when it exits.", watch_process_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_watch_process(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("list_ports", "process", "List listening sockets (or all connections) with their local address, port and owning processes.", list_ports_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_list_ports(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("get_process_tree", "process", "Show parent/child process relationships, optionally rooted at a PID or at the sessions started by this server.", get_process_tree_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_get_process_tree(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("kill_process", "process", "Terminate a system process by PID (or its whole subtree) with a chosen signal, optional grace period and escalation to KILL.", kill_process_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_kill_process(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("kill_processes_matching", "process", "Kill processes whose name or command line matches a regex. Dry-run by default; requires confirm: true to send signals. PID 1, this server, its ancestors and its session shells are never matched.", kill_processes_matching_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::process::mcp_kill_processes_matching(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry.register(BuiltinTool::new("edit_block", "edit", "Apply targeted text replacements in a file.", edit_block_mcp_schema, |ctx, args| Box::pin(async move {
+            ctx.respond(tool_impl::edit::mcp_edit_block(ctx.deps, ctx.params(args)?).await)
+        })));
+        registry
+    }
+}
@@ -0,0 +1,117 @@
+use crate::mcp::auth::{routes, Authenticator, Identity};
+use crate::mcp::duplex_session::{message_to_line, outgoing_line, DuplexSession};
+use crate::mcp::handler::EnhancedServerHandler;
+use crate::mcp::listener::{self, CorsOptions, TlsOptions};
+use crate::mcp::metrics;
+use crate::mcp::notifications::McpNotifier;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use futures_util::{SinkExt, StreamExt};
+use rust_mcp_sdk::McpServer;
+use rust_mcp_schema::InitializeResult;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+pub struct WsServerOptions {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+    pub tls: Option<TlsOptions>,
+    pub cors: CorsOptions,
+    // Cancelled to stop the server: open connections are closed and the port is released.
+    pub stop: CancellationToken,
+}
+
+#[derive(Clone)]
+struct WsState {
+    server_details: Arc<InitializeResult>,
+    handler: EnhancedServerHandler,
+    notifier: Arc<McpNotifier>,
+    auth: Arc<Authenticator>,
+    cors: Arc<CorsOptions>,
+    stop: CancellationToken,
+}
+
+/// Serves MCP over WebSocket at `ws://host:port/path` (`wss://` with TLS), one JSON-RPC message per text frame.
+/// Each connection gets its own MCP runtime, which also receives server notifications.
+pub async fn serve(options: WsServerOptions, server_details: InitializeResult, handler: EnhancedServerHandler, notifier: Arc<McpNotifier>, auth: Arc<Authenticator>) -> anyhow::Result<()> {
+    let oauth = auth.has_oauth_clients().then(|| routes::oauth_router(auth.clone(), options.tls.is_some()));
+    let monitoring = metrics::routes::monitoring_router(handler.clone(), auth.clone(), "websocket");
+    let state = WsState { server_details: Arc::new(server_details), handler, notifier, auth, cors: Arc::new(options.cors.clone()), stop: options.stop.clone() };
+    let mut app = Router::new().route(&options.path, get(upgrade)).with_state(state).merge(monitoring);
+    if let Some(oauth) = oauth { app = app.merge(oauth); }
+    let scheme = if options.tls.is_some() { "wss" } else { "ws" };
+    info!("MCP WebSocket server listening on {}://{}:{}{}", scheme, options.host, options.port, options.path);
+    listener::serve(&options.host, options.port, app, options.tls.as_ref(), &options.cors, options.stop.clone().cancelled_owned()).await
+}
+
+// Browsers cannot set headers on a WebSocket handshake, so the token may also come as ?access_token=.
+async fn upgrade(ws: WebSocketUpgrade, State(state): State<WsState>, headers: HeaderMap, Query(query): Query<HashMap<String, String>>) -> Response {
+    // CORS does not cover WebSocket handshakes, so cross-site pages are turned away here.
+    if !state.cors.origin_allowed(&headers) {
+        warn!("Rejected MCP WebSocket connection from a disallowed origin");
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    let query_token = query.get("access_token").map(|token| format!("Bearer {}", token));
+    let Some(identity) = state.auth.authenticate(routes::authorization_header(&headers).or(query_token.as_deref())) else {
+        warn!("Rejected MCP WebSocket connection without a valid token");
+        return routes::unauthorized();
+    };
+    ws.protocols(["mcp"]).on_upgrade(move |socket| run_session(socket, state, identity)).into_response()
+}
+
+async fn run_session(socket: WebSocket, state: WsState, identity: Identity) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let connection_id = uuid::Uuid::new_v4().to_string();
+    let client = state.auth.client_identity(&identity, &connection_id);
+    let session = match DuplexSession::new((*state.server_details).clone(), state.handler.for_client(identity.scope, client), &state.notifier) {
+        Ok(session) => session,
+        Err(e) => { error!(error = %e, "Failed to start MCP session for WebSocket connection"); return; }
+    };
+    let (mut client_in, client_out) = (session.input, session.output);
+    let _calls_guard = session.calls_guard;
+    let _open = state.handler.metrics().open_session("websocket");
+    info!(connection_id = %connection_id, client = %identity.label, scope = identity.scope.as_str(), "MCP WebSocket client connected");
+
+    let runtime = session.runtime.clone();
+    let outbound = tauri::async_runtime::spawn(async move {
+        let mut lines = BufReader::new(client_out).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() { continue; }
+            if ws_tx.send(Message::Text(outgoing_line(&runtime, line))).await.is_err() { break; }
+        }
+        let _ = ws_tx.close().await;
+    });
+    // Dropping client_in at the end closes the transport's input, which ends runtime.start().
+    let inbound = tauri::async_runtime::spawn(async move {
+        while let Some(frame) = ws_rx.next().await {
+            let text = match frame {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Binary(bytes)) => String::from_utf8_lossy(&bytes).into_owned(),
+                Ok(Message::Close(_)) => break,
+                // axum answers pings itself.
+                Ok(_) => continue,
+                Err(e) => { debug!(error = %e, "WebSocket read failed"); break; }
+            };
+            let line = message_to_line(&text);
+            if client_in.write_all(line.as_bytes()).await.is_err() || client_in.write_all(b"\n").await.is_err() { break; }
+        }
+    });
+
+    tokio::select! {
+        started = session.runtime.start() => if let Err(e) = started { warn!(error = ?e, "MCP WebSocket session ended with an error"); },
+        _ = state.stop.cancelled() => debug!(connection_id = %connection_id, "Closing MCP WebSocket connection; the server is stopping"),
+    }
+    inbound.abort();
+    outbound.abort();
+    session.forwarder.abort();
+    info!(connection_id = %connection_id, client = %identity.label, "MCP WebSocket client disconnected");
+}
@@ -0,0 +1,65 @@
+use crate::error::AppError;
+use tracing::{debug, info};
+
+/// Service name that every secret is stored under in the OS keyring
+/// (Keychain, Windows Credential Manager, Secret Service).
+pub const KEYRING_SERVICE: &str = "mcp-rg-editor";
+
+/// Prefix for bearer tokens sent by read_file with `is_url: true`, e.g. `url_token.api.github.com`.
+pub const URL_TOKEN_SECRET_PREFIX: &str = "url_token.";
+
+fn validate_secret_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty() && name.len() <= 128 && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ':'));
+    if !valid {
+        return Err(AppError::InvalidInputArgument(format!("Invalid secret name '{}': use 1-128 characters from [A-Za-z0-9._:-]", name)));
+    }
+    Ok(())
+}
+
+fn keyring_entry(name: &str) -> Result<keyring::Entry, AppError> {
+    validate_secret_name(name)?;
+    keyring::Entry::new(KEYRING_SERVICE, name).map_err(|e| AppError::ConfigError(format!("Keyring unavailable for secret '{}': {}", name, e)))
+}
+
+// The keyring calls below block (D-Bus on Linux); call them from spawn_blocking in async code.
+
+pub fn set_secret(name: &str, value: &str) -> Result<(), AppError> {
+    if value.is_empty() {
+        return Err(AppError::InvalidInputArgument("Secret value cannot be empty; use delete_secret to remove it".to_string()));
+    }
+    keyring_entry(name)?.set_password(value).map_err(|e| AppError::ConfigError(format!("Failed to store secret '{}' in the keyring: {}", name, e)))?;
+    info!(secret = %name, "Stored secret in OS keyring");
+    Ok(())
+}
+
+pub fn get_secret(name: &str) -> Result<Option<String>, AppError> {
+    match keyring_entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(AppError::ConfigError(format!("Failed to read secret '{}' from the keyring: {}", name, e))),
+    }
+}
+
+/// Returns false if there was no such secret.
+pub fn delete_secret(name: &str) -> Result<bool, AppError> {
+    match keyring_entry(name)?.delete_password() {
+        Ok(()) => {
+            info!(secret = %name, "Deleted secret from OS keyring");
+            Ok(true)
+        }
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(AppError::ConfigError(format!("Failed to delete secret '{}' from the keyring: {}", name, e))),
+    }
+}
+
+/// The bearer token stored for a URL's host, if any. Keyring failures are treated as "no token"
+/// so an unavailable keyring never breaks plain URL reads.
+pub fn url_token_for_host(host: &str) -> Option<String> {
+    match get_secret(&format!("{}{}", URL_TOKEN_SECRET_PREFIX, host)) {
+        Ok(token) => token,
+        Err(e) => {
+            debug!(host = %host, error = %e, "No URL token available from keyring");
+            None
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::config::{expand_tilde, Config, KillScope};
+use crate::config_layers::ConfigSource;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+use tracing::{info, warn};
+
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// Bumped whenever a key is renamed or reshaped; add a migration to SETTINGS_MIGRATIONS with it.
+pub const SETTINGS_SCHEMA_VERSION: u32 = 2;
+
+type SettingsMigration = fn(&mut serde_json::Map<String, Value>);
+
+// SETTINGS_MIGRATIONS[i] upgrades a version i + 1 file to version i + 2.
+const SETTINGS_MIGRATIONS: &[SettingsMigration] = &[migrate_v1_to_v2];
+
+fn migrate_v1_to_v2(_settings: &mut serde_json::Map<String, Value>) {
+    // Version 1 files were written before schemaVersion existed; their keys are unchanged.
+}
+
+/// Values saved from the UI with `persist: true`. Every field is optional: only keys the
+/// user explicitly persisted are written, and they override .env values on the next start.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UserAppSettings {
+    // Files without it are version 1.
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files_root: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_directories: Option<Vec<PathBuf>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_commands: Option<Vec<String>>,
+    // An empty string means "use the system default shell".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_shell: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_read_line_limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_write_line_limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expose_process_environment: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kill_scope: Option<KillScope>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kill_allowed_process_names: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_tools: Option<Vec<String>>,
+    // Names of secrets stored in the OS keyring by set_secret; never the values.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_names: Option<Vec<String>>,
+    // Keys this build does not know (from a newer version or added by hand), written back unchanged.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl UserAppSettings {
+    /// Reads settings.json; a missing file is an empty settings set. Older files are migrated
+    /// to SETTINGS_SCHEMA_VERSION and rewritten, keeping a `settings.json.v<N>.bak` copy first.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path).context(format!("Failed to read settings file: {}", path.display()))?;
+        let mut value: Value = serde_json::from_str(&contents).context(format!("Invalid JSON in settings file: {}", path.display()))?;
+        let settings_map = value.as_object_mut().context(format!("Settings file is not a JSON object: {}", path.display()))?;
+
+        let file_version = settings_map.get("schemaVersion").and_then(Value::as_u64).map(|v| v as u32).unwrap_or(1);
+        let migrated = file_version < SETTINGS_SCHEMA_VERSION;
+        if migrated {
+            let backup_path = path.with_extension(format!("json.v{}.bak", file_version));
+            std::fs::copy(path, &backup_path).context(format!("Failed to back up settings file to {}", backup_path.display()))?;
+            for migration in SETTINGS_MIGRATIONS.iter().skip(file_version.saturating_sub(1) as usize) {
+                migration(settings_map);
+            }
+            settings_map.insert("schemaVersion".to_string(), Value::from(SETTINGS_SCHEMA_VERSION));
+        } else if file_version > SETTINGS_SCHEMA_VERSION {
+            warn!(path = %path.display(), file_version, supported_version = SETTINGS_SCHEMA_VERSION, "Settings file is from a newer version; unknown keys are kept but ignored");
+        }
+
+        let settings: Self = serde_json::from_value(value).context(format!("Invalid settings file: {}", path.display()))?;
+        if migrated {
+            settings.save_atomic(path)?;
+            info!(path = %path.display(), from_version = file_version, to_version = SETTINGS_SCHEMA_VERSION, "Migrated settings file");
+        }
+        Ok(settings)
+    }
+
+    /// Writes to a temp file in the same directory and renames it over the original,
+    /// so a crash mid-write never leaves a truncated settings.json behind.
+    pub fn save_atomic(&self, path: &Path) -> Result<()> {
+        let parent_dir = path.parent().context("Settings path has no parent directory")?;
+        std::fs::create_dir_all(parent_dir).context(format!("Failed to create settings directory: {}", parent_dir.display()))?;
+        let tmp_path = path.with_extension(format!("json.tmp-{}", std::process::id()));
+        {
+            let mut tmp_file = std::fs::File::create(&tmp_path).context(format!("Failed to create {}", tmp_path.display()))?;
+            // Never stamp a lower version over a file written by a newer build.
+            let stamped = Self { schema_version: self.schema_version.max(SETTINGS_SCHEMA_VERSION), ..self.clone() };
+            tmp_file.write_all(serde_json::to_string_pretty(&stamped)?.as_bytes())?;
+            tmp_file.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, path).map_err(|e| {
+            let _ = std::fs::remove_file(&tmp_path);
+            anyhow::anyhow!("Failed to replace {}: {}", path.display(), e)
+        })
+    }
+
+    /// Copies the current in-memory value of a set_config_value key into these settings.
+    /// Returns false for keys that are not persistable.
+    pub fn record_key(&mut self, key: &str, config: &Config) -> bool {
+        match key {
+            "filesRoot" => self.files_root = Some(config.files_root.clone()),
+            "allowedDirectories" => self.allowed_directories = Some(config.allowed_directories.clone()),
+            "blockedCommands" => self.blocked_commands = Some(config.blocked_commands.clone()),
+            "defaultShell" => self.default_shell = Some(config.default_shell.clone().unwrap_or_default()),
+            "logLevel" => self.log_level = Some(config.log_level.clone()),
+            "fileReadLineLimit" => self.file_read_line_limit = Some(config.file_read_line_limit),
+            "fileWriteLineLimit" => self.file_write_line_limit = Some(config.file_write_line_limit),
+            "exposeProcessEnvironment" => self.expose_process_environment = Some(config.expose_process_environment),
+            "killScope" => self.kill_scope = Some(config.kill_scope),
+            "killAllowedProcessNames" => self.kill_allowed_process_names = Some(config.kill_allowed_process_names.clone()),
+            "disabledTools" => self.disabled_tools = Some(config.disabled_tools.clone()),
+            "secretNames" => self.secret_names = Some(config.secret_names.clone()),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Overlays persisted values onto a freshly loaded config. `files_root` is handled by
+    /// Config::load itself because everything else is resolved relative to it.
+    pub fn apply_to(&self, config: &mut Config) {
+        let mut applied: Vec<&str> = Vec::new();
+        if let Some(dirs) = &self.allowed_directories {
+            let mut dirs: Vec<PathBuf> = dirs.iter().map(|d| d.canonicalize().unwrap_or_else(|_| d.clone())).collect();
+            if !dirs.iter().any(|d| d == &config.files_root) {
+                dirs.push(config.files_root.clone());
+            }
+            dirs.sort();
+            dirs.dedup();
+            config.allowed_directories = dirs;
+            applied.push("allowed_directories");
+        }
+        if let Some(cmds) = &self.blocked_commands { config.blocked_commands = cmds.clone(); applied.push("blocked_commands"); }
+        if let Some(shell) = &self.default_shell {
+            config.default_shell = if shell.trim().is_empty() { None } else { Some(shell.trim().to_string()) };
+            applied.push("default_shell");
+        }
+        if let Some(level) = &self.log_level { config.log_level = level.clone(); applied.push("log_level"); }
+        if let Some(limit) = self.file_read_line_limit { config.file_read_line_limit = limit; applied.push("file_read_line_limit"); }
+        if let Some(limit) = self.file_write_line_limit { config.file_write_line_limit = limit; applied.push("file_write_line_limit"); }
+        if let Some(expose) = self.expose_process_environment { config.expose_process_environment = expose; applied.push("expose_process_environment"); }
+        if let Some(scope) = self.kill_scope { config.kill_scope = scope; applied.push("kill_scope"); }
+        if let Some(names) = &self.kill_allowed_process_names { config.kill_allowed_process_names = names.clone(); applied.push("kill_allowed_process_names"); }
+        if let Some(tools) = &self.disabled_tools { config.disabled_tools = tools.clone(); applied.push("disabled_tools"); }
+        if let Some(names) = &self.secret_names { config.secret_names = names.clone(); applied.push("secret_names"); }
+        for key in applied {
+            config.value_sources.insert(key.to_string(), ConfigSource::Settings);
+        }
+    }
+}
+
+pub fn settings_file_path(app_handle: &crate::AppHandle) -> Result<PathBuf> {
+    let config_dir = app_handle.path().app_config_dir().context("Failed to get app config directory from Tauri")?;
+    Ok(config_dir.join(SETTINGS_FILE_NAME))
+}
+
+/// Persisted files_root, already tilde-expanded, if the user saved one.
+pub fn persisted_files_root(settings: &UserAppSettings) -> Option<PathBuf> {
+    let root = settings.files_root.as_ref()?;
+    match expand_tilde(&root.to_string_lossy()) {
+        Ok(p) => Some(p),
+        Err(e) => {
+            warn!(path = %root.display(), error = %e, "Ignoring persisted filesRoot that could not be expanded");
+            None
+        }
+    }
+}
+
+/// Read-modify-write of settings.json for one key after it was changed in memory.
+pub fn persist_config_key(settings_path: &Path, key: &str, config: &Config) -> Result<()> {
+    let mut settings = UserAppSettings::load(settings_path)?;
+    if !settings.record_key(key, config) {
+        anyhow::bail!("Config key '{}' cannot be persisted", key);
+    }
+    settings.save_atomic(settings_path)?;
+    info!(key = %key, path = %settings_path.display(), "Persisted config key to settings file");
+    Ok(())
+}
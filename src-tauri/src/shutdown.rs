@@ -0,0 +1,177 @@
+use crate::config::{ConfigState, ShutdownSessionPolicy};
+use crate::mcp::downstream::DownstreamHub;
+use crate::mcp::handler::ToolDependencies;
+use crate::mcp::notifications::SERVER_SHUTDOWN_NOTIFICATION;
+use crate::mcp::tool_impl::terminal::{mcp_force_terminate_session, ForceTerminateParamsMCP};
+use crate::AppHandle;
+
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tauri::{ExitRequestApi, Manager};
+use tokio::sync::Notify;
+use tokio::time::{sleep, timeout_at, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+// Time for notification forwarders to hand the shutdown notification to their clients.
+const FINAL_NOTIFICATION_GRACE: Duration = Duration::from_millis(250);
+// Time for calls cancelled after the drain timeout to stop their walks, searches and requests.
+const CANCELLED_CALLS_GRACE: Duration = Duration::from_secs(2);
+
+/// Server-wide shutdown state. Once started, tool calls are refused; `run` waits for the
+/// ones already running, then cancels those that outlast the drain timeout.
+#[derive(Default)]
+pub struct Shutdown {
+    started: AtomicBool,
+    finished: AtomicBool,
+    running_calls: AtomicUsize,
+    idle: Notify,
+    // Parent of every connection's call tokens (see InFlightCalls).
+    calls: CancellationToken,
+}
+
+/// A tool call shutdown waits for; released on drop.
+pub struct RunningCall<'a>(&'a Shutdown);
+
+impl Drop for RunningCall<'_> {
+    fn drop(&mut self) {
+        if self.0.running_calls.fetch_sub(1, Ordering::AcqRel) == 1 { self.0.idle.notify_waiters(); }
+    }
+}
+
+impl Shutdown {
+    /// None once shutdown has started.
+    pub fn begin_call(&self) -> Option<RunningCall<'_>> {
+        // Counted before the check, so `run` cannot miss a call that slips in as it starts.
+        self.running_calls.fetch_add(1, Ordering::AcqRel);
+        let call = RunningCall(self);
+        (!self.started.load(Ordering::Acquire)).then_some(call)
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::Acquire)
+    }
+
+    pub fn calls_token(&self) -> &CancellationToken {
+        &self.calls
+    }
+
+    /// Whether running calls were cancelled because they outlasted the drain timeout.
+    pub fn calls_cancelled(&self) -> bool {
+        self.calls.is_cancelled()
+    }
+
+    /// True for the caller that started shutdown; later calls return false.
+    fn start(&self) -> bool {
+        !self.started.swap(true, Ordering::AcqRel)
+    }
+
+    /// Waits until no tool call is running or `deadline` passes. Returns how many still run.
+    async fn drain(&self, deadline: Instant) -> usize {
+        loop {
+            let idle = self.idle.notified();
+            let running = self.running_calls.load(Ordering::Acquire);
+            if running == 0 { return 0; }
+            if timeout_at(deadline, idle).await.is_err() { return self.running_calls.load(Ordering::Acquire); }
+        }
+    }
+}
+
+/// Stops new tool calls, waits up to shutdown_drain_timeout_ms for running ones, terminates or
+/// detaches command sessions per shutdown_session_policy, stops downstream MCP servers, then
+/// notifies clients and writes a final audit entry.
+async fn run(app_handle: &AppHandle, shutdown: &Shutdown, reason: &str) {
+    let Some(config_state) = app_handle.try_state::<Arc<ConfigState>>().map(|s| s.inner().clone()) else { return };
+    let (policy, drain_timeout_ms) = { let c = config_state.snapshot(); (c.shutdown_session_policy, c.shutdown_drain_timeout_ms) };
+    info!(reason, policy = ?policy, drain_timeout_ms, "Shutting down; no new tool calls are accepted");
+
+    let mut unfinished_calls = shutdown.drain(Instant::now() + Duration::from_millis(drain_timeout_ms)).await;
+    if unfinished_calls > 0 {
+        warn!(unfinished_calls, "Cancelling tool calls still running after shutdown_drain_timeout_ms");
+        shutdown.calls.cancel();
+        unfinished_calls = shutdown.drain(Instant::now() + CANCELLED_CALLS_GRACE).await;
+        if unfinished_calls > 0 { warn!(unfinished_calls, "Tool calls still running after being cancelled"); }
+    }
+
+    let deps = ToolDependencies::from_app_handle(app_handle, config_state);
+    // Jobs outlive the calls that started them, so nothing waits for them.
+    let cancelled_jobs = deps.jobs.cancel_all();
+    if cancelled_jobs > 0 { info!(cancelled_jobs, "Cancelled running jobs"); }
+    let sessions: Vec<_> = deps.active_sessions_map.lock().await.values().cloned().collect();
+    let mut running = Vec::new();
+    for session in sessions {
+        if session.exit_code.lock().await.is_none() { running.push(session); }
+    }
+    let mut terminated = Vec::new();
+    let mut detached = Vec::new();
+    for session in running {
+        let entry = json!({ "session_id": session.session_id, "pid": session.pid, "command": session.command_str });
+        match policy {
+            ShutdownSessionPolicy::Terminate => match mcp_force_terminate_session(&deps, ForceTerminateParamsMCP { session_id: session.session_id.clone() }).await {
+                Ok(result) if result.success => terminated.push(entry),
+                Ok(result) => { warn!(sid = %session.session_id, message = %result.message, "Failed to terminate session on shutdown"); detached.push(entry); }
+                Err(e) => { warn!(sid = %session.session_id, error = %e, "Failed to terminate session on shutdown"); detached.push(entry); }
+            },
+            ShutdownSessionPolicy::Detach => {
+                info!(sid = %session.session_id, pid = ?session.pid, "Leaving session running after shutdown");
+                detached.push(entry);
+            }
+        }
+    }
+
+    if let Some(hub) = app_handle.try_state::<Arc<DownstreamHub>>() {
+        hub.shutdown().await;
+    }
+
+    let status = json!({
+        "reason": reason,
+        "unfinished_calls": unfinished_calls,
+        "cancelled_jobs": cancelled_jobs,
+        "terminated_sessions": terminated,
+        "detached_sessions": detached,
+    });
+    deps.notifier.notify(SERVER_SHUTDOWN_NOTIFICATION, status.clone());
+    sleep(FINAL_NOTIFICATION_GRACE).await;
+    // Awaits the write, so the audit log is complete before the process exits.
+    deps.audit_logger.log_command_call("server_shutdown", &status).await;
+    deps.audit_logger.flush().await;
+    if let Err(e) = tokio::task::spawn_blocking(crate::telemetry::shutdown).await { warn!(error = %e, "OTLP flush task failed"); }
+    info!(terminated = terminated.len(), detached = detached.len(), "Shutdown complete");
+}
+
+/// Handles RunEvent::ExitRequested: the first request is held back until `run` finishes, which
+/// then exits with the requested code.
+pub fn on_exit_requested(app_handle: &AppHandle, code: Option<i32>, api: &ExitRequestApi) {
+    let Some(shutdown) = app_handle.try_state::<Arc<Shutdown>>().map(|s| s.inner().clone()) else { return };
+    if shutdown.finished.load(Ordering::Acquire) { return; }
+    api.prevent_exit();
+    if !shutdown.start() { return; }
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        run(&app_handle, &shutdown, if code.is_some() { "exit" } else { "last window closed" }).await;
+        shutdown.finished.store(true, Ordering::Release);
+        app_handle.exit(code.unwrap_or(0));
+    });
+}
+
+/// Turns SIGTERM and Ctrl+C into a normal app exit, so they shut down gracefully too.
+pub async fn exit_on_signal(app_handle: AppHandle) {
+    #[cfg(unix)]
+    {
+        let Ok(mut sigterm) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) else {
+            warn!("Cannot listen for SIGTERM; it will end the app without a graceful shutdown");
+            return;
+        };
+        tokio::select! {
+            _ = sigterm.recv() => info!("Received SIGTERM"),
+            _ = tokio::signal::ctrl_c() => info!("Received Ctrl+C"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        if tokio::signal::ctrl_c().await.is_err() { return; }
+        info!("Received Ctrl+C");
+    }
+    app_handle.exit(0);
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// A tracing layer exporting spans over OTLP, boxed so builds without the `otel` feature can
+/// return the same type.
+pub type OtlpLayer<S> = Box<dyn Layer<S> + Send + Sync>;
+
+/// Starts OTLP export of spans and tool call metrics to `endpoint` and returns the layer that
+/// feeds it spans. None without an endpoint, without the `otel` feature (config_validation
+/// reports that) or when the exporters cannot be built. Call once, before tracing is set up.
+#[cfg(feature = "otel")]
+pub fn start<S>(endpoint: Option<&str>) -> Option<OtlpLayer<S>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync,
+{
+    let endpoint = endpoint?;
+    match otlp::start(endpoint) {
+        Ok(tracer) => Some(Box::new(tracing_opentelemetry::layer().with_tracer(tracer))),
+        // Tracing is not installed yet.
+        Err(e) => { eprintln!("OTLP export is off: {}", e); None }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn start<S>(_endpoint: Option<&str>) -> Option<OtlpLayer<S>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync,
+{
+    None
+}
+
+/// Adds one tool call to the exported counter and latency histogram. `tool` is already capped
+/// to the series Metrics keeps, so clients cannot create unbounded attribute values.
+#[cfg(feature = "otel")]
+pub fn record_tool_call(tool: &str, ok: bool, duration: Duration) {
+    otlp::record_tool_call(tool, ok, duration);
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn record_tool_call(_tool: &str, _ok: bool, _duration: Duration) {}
+
+/// Exports what is still buffered and stops the exporters. Blocks, so run it off the runtime's
+/// worker threads.
+#[cfg(feature = "otel")]
+pub fn shutdown() {
+    otlp::shutdown();
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn shutdown() {}
+
+/// The resource every OTLP export describes the server with.
+#[cfg(feature = "otel")]
+pub use otlp::resource;
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use crate::error::AppError;
+
+    use once_cell::sync::OnceCell;
+    use opentelemetry::metrics::{Counter, Histogram, MeterProvider as _};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+    use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::{runtime, Resource};
+    use std::time::Duration;
+    use tracing::warn;
+
+    const SERVICE_NAME: &str = "mcp-rg-editor";
+
+    struct Exporters {
+        tracer_provider: TracerProvider,
+        meter_provider: SdkMeterProvider,
+        tool_calls: Counter<u64>,
+        tool_call_duration: Histogram<f64>,
+    }
+
+    static EXPORTERS: OnceCell<Exporters> = OnceCell::new();
+
+    pub fn start(endpoint: &str) -> Result<Tracer, AppError> {
+        // The batch span processor, the periodic metric reader and the gRPC channels spawn onto
+        // the Tokio runtime, and setup runs outside of it.
+        let _runtime = tauri::async_runtime::handle().inner().enter();
+        let resource = resource();
+
+        let span_exporter = opentelemetry_otlp::SpanExporter::builder().with_tonic().with_endpoint(endpoint).build()
+            .map_err(|e| AppError::ConfigError(format!("Failed to create the OTLP span exporter for {}: {}", endpoint, e)))?;
+        let tracer_provider = TracerProvider::builder().with_batch_exporter(span_exporter, runtime::Tokio).with_resource(resource.clone()).build();
+
+        let metric_exporter = opentelemetry_otlp::MetricExporter::builder().with_tonic().with_endpoint(endpoint).build()
+            .map_err(|e| AppError::ConfigError(format!("Failed to create the OTLP metric exporter for {}: {}", endpoint, e)))?;
+        let meter_provider = SdkMeterProvider::builder().with_reader(PeriodicReader::builder(metric_exporter, runtime::Tokio).build()).with_resource(resource).build();
+
+        let meter = meter_provider.meter(SERVICE_NAME);
+        let tool_calls = meter.u64_counter("mcp.tool.calls").with_description("Tool calls by tool and outcome; rejected calls count as errors.").build();
+        // Milliseconds, so the SDK's default histogram buckets fit.
+        let tool_call_duration = meter.f64_histogram("mcp.tool.call.duration").with_unit("ms").with_description("Tool call latency, including time spent queued.").build();
+        let tracer = tracer_provider.tracer(SERVICE_NAME);
+        EXPORTERS.set(Exporters { tracer_provider, meter_provider, tool_calls, tool_call_duration })
+            .map_err(|_| AppError::ConfigError("OTLP export was already started".to_string()))?;
+        Ok(tracer)
+    }
+
+    pub fn resource() -> Resource {
+        Resource::new([KeyValue::new("service.name", SERVICE_NAME), KeyValue::new("service.version", env!("CARGO_PKG_VERSION"))])
+    }
+
+    pub fn record_tool_call(tool: &str, ok: bool, duration: Duration) {
+        let Some(exporters) = EXPORTERS.get() else { return };
+        let tool = KeyValue::new("tool", tool.to_string());
+        exporters.tool_calls.add(1, &[tool.clone(), KeyValue::new("outcome", if ok { "ok" } else { "error" })]);
+        exporters.tool_call_duration.record(duration.as_secs_f64() * 1000.0, &[tool]);
+    }
+
+    pub fn shutdown() {
+        let Some(exporters) = EXPORTERS.get() else { return };
+        if let Err(e) = exporters.tracer_provider.shutdown() { warn!(error = %e, "Failed to flush OTLP spans"); }
+        if let Err(e) = exporters.meter_provider.shutdown() { warn!(error = %e, "Failed to flush OTLP metrics"); }
+    }
+}
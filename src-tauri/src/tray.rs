@@ -0,0 +1,133 @@
+use crate::config::ConfigState;
+use crate::config_layers::ConfigSource;
+use crate::config_watcher::broadcast_config_change;
+use crate::mcp::server_control::{McpServerControl, TransportState};
+use crate::utils::audit_logger::{audit_log, AuditLogger};
+use crate::{AppHandle, AppRuntime};
+
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{TrayIcon, TrayIconBuilder};
+use tauri::Manager;
+use tauri_plugin_opener::OpenerExt;
+use tracing::{debug, info, warn};
+
+// Client sessions come and go without an event, so the tray is also refreshed on a timer.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The tray icon and the menu items whose text follows the server state.
+pub struct Tray {
+    icon: TrayIcon<AppRuntime>,
+    status: MenuItem<AppRuntime>,
+    pause: MenuItem<AppRuntime>,
+    read_only: CheckMenuItem<AppRuntime>,
+}
+
+/// Adds the tray icon. Needs McpServerControl and ConfigState to be managed already.
+pub fn setup(app: &AppHandle) -> tauri::Result<()> {
+    let read_only = app.state::<Arc<ConfigState>>().snapshot().read_only;
+    let status = MenuItem::with_id(app, "status", "MCP server starting", false, None::<&str>)?;
+    let pause = MenuItem::with_id(app, "pause", "Pause MCP server", true, None::<&str>)?;
+    let read_only = CheckMenuItem::with_id(app, "read_only", "Read-only mode", true, read_only, None::<&str>)?;
+    let open_logs = MenuItem::with_id(app, "open_logs", "Open log folder", true, None::<&str>)?;
+    let show = MenuItem::with_id(app, "show", "Show window", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&status, &PredefinedMenuItem::separator(app)?, &pause, &read_only, &open_logs, &PredefinedMenuItem::separator(app)?, &show, &quit])?;
+
+    let mut builder = TrayIconBuilder::with_id("main").tooltip("MCP-RG-Editor").menu(&menu).show_menu_on_left_click(true).on_menu_event(on_menu_event);
+    if let Some(icon) = app.default_window_icon() { builder = builder.icon(icon.clone()); }
+    let icon = builder.build(app)?;
+    app.manage(Tray { icon, status, pause, read_only });
+    refresh(app);
+
+    let refresh_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut ticks = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            ticks.tick().await;
+            refresh(&refresh_handle);
+        }
+    });
+    Ok(())
+}
+
+/// Shows whether the server runs, on which transports, how many network clients are connected
+/// and whether read-only mode is on, in the menu and the tooltip.
+pub fn refresh(app: &AppHandle) {
+    let (Some(tray), Some(control), Some(config_state)) = (app.try_state::<Tray>(), app.try_state::<Arc<McpServerControl>>(), app.try_state::<Arc<ConfigState>>()) else { return };
+    let status = control.status();
+    let read_only = config_state.snapshot().read_only;
+    let transports: Vec<&str> = status.transports.iter().filter(|t| t.state == TransportState::Running).map(|t| t.transport).collect();
+    let clients = network_clients(&control);
+    let state = if status.paused { "paused" } else if status.running { "running" } else { "stopped" };
+    let summary = match transports.is_empty() {
+        true => format!("MCP server {}", state),
+        false => format!("MCP server {} ({}), {} network client{}", state, transports.join(", "), clients, if clients == 1 { "" } else { "s" }),
+    };
+    let tooltip = format!("MCP-RG-Editor: {}{}", summary, if read_only { ", read-only" } else { "" });
+
+    let updated = tray.status.set_text(&summary)
+        .and_then(|_| tray.pause.set_text(if status.paused { "Resume MCP server" } else { "Pause MCP server" }))
+        .and_then(|_| tray.read_only.set_checked(read_only))
+        .and_then(|_| tray.icon.set_tooltip(Some(&tooltip)));
+    if let Err(e) = updated { debug!(error = %e, "Failed to update the tray"); }
+}
+
+#[cfg(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server"))]
+fn network_clients(control: &McpServerControl) -> usize {
+    control.handler().metrics().open_sessions().values().sum()
+}
+
+#[cfg(not(any(feature = "mcp-sse-server", feature = "mcp-ws-server", feature = "mcp-http-server")))]
+fn network_clients(_control: &McpServerControl) -> usize {
+    0
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id.as_ref() {
+        "pause" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let control = app.state::<Arc<McpServerControl>>().inner().clone();
+                if control.status().paused {
+                    audit_log(&app.state::<Arc<AuditLogger>>(), "ui_tray_resume_mcp_server", &serde_json::Value::Null).await;
+                    if let Err(e) = control.restart().await { warn!(error = %e, "Tray: failed to resume the MCP server"); }
+                } else {
+                    audit_log(&app.state::<Arc<AuditLogger>>(), "ui_tray_pause_mcp_server", &serde_json::Value::Null).await;
+                    control.pause().await;
+                }
+                refresh(&app);
+            });
+        }
+        "read_only" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let config_state = app.state::<Arc<ConfigState>>().inner().clone();
+                let (read_only, config) = config_state.update(|config| {
+                    config.read_only = !config.read_only;
+                    config.value_sources.insert("read_only".to_string(), ConfigSource::Runtime);
+                    config.read_only
+                });
+                audit_log(&app.state::<Arc<AuditLogger>>(), "ui_tray_set_read_only", &json!({ "read_only": read_only })).await;
+                info!(read_only, "Tray: read-only mode toggled for this session");
+                broadcast_config_change(&app, "tray", &config, &["read_only".to_string()], &[]);
+                refresh(&app);
+            });
+        }
+        "open_logs" => {
+            let log_dir = app.state::<Arc<ConfigState>>().snapshot().mcp_log_dir.clone();
+            if let Err(e) = app.opener().open_path(log_dir.to_string_lossy(), None::<&str>) { warn!(path = %log_dir.display(), error = %e, "Tray: failed to open the log folder"); }
+        }
+        "show" => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+        // Goes through ExitRequested, so the usual shutdown runs.
+        "quit" => app.exit(0),
+        other => debug!(id = other, "Unhandled tray menu event"),
+    }
+}
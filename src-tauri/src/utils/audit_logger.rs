@@ -1,102 +1,72 @@
 // FILE: src-tauri/src/utils/audit_logger.rs
 // IMPORTANT NOTE: Rewrite the entire file.
-use crate::config::Config;
-use anyhow::Result;
+use crate::config::ConfigState;
+use crate::utils::audit_sinks::{self, AuditRecord, AuditSink, QueuedSink};
+use crate::utils::redaction;
 use chrono::Utc;
 use serde_json::Value;
-use std::path::PathBuf;
-use std::sync::{Arc, RwLock as StdRwLock};
+use std::sync::Arc;
+use std::time::Duration;
 use tauri::State;
-use tokio::fs::{self, OpenOptions};
-use tokio::io::AsyncWriteExt;
 use tracing::error;
 
-#[derive(Debug)]
+// How long shutdown waits for each queued sink to deliver what it holds.
+const SINK_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Writes every audit entry to the sinks in `audit_sinks`.
 pub struct AuditLogger {
-    log_file_path: PathBuf,
-    max_size_bytes: u64,
+    config_state: Arc<ConfigState>,
+    // Awaited by each log call, so the entry is written when it returns.
+    direct: Vec<Arc<dyn AuditSink>>,
+    queued: Vec<QueuedSink>,
 }
 
-impl AuditLogger {
-    pub fn new(config_state: Arc<StdRwLock<Config>>) -> Self {
-        let config_guard = config_state.read().unwrap();
-        let log_file_path = config_guard.audit_log_file.clone();
-        let max_size_bytes = config_guard.audit_log_max_size_bytes;
-        drop(config_guard);
-
-        if let Some(parent_dir) = log_file_path.parent() {
-            if !parent_dir.exists() {
-                if let Err(e) = std::fs::create_dir_all(parent_dir) {
-                    error!(path = %parent_dir.display(), error = %e, "Failed to create audit log directory");
-                }
-            }
-        }
-        Self {
-            log_file_path,
-            max_size_bytes,
-        }
+impl std::fmt::Debug for AuditLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sinks: Vec<&str> = self.direct.iter().map(|s| s.kind().as_str()).chain(self.queued.iter().map(|s| s.kind().as_str())).collect();
+        f.debug_struct("AuditLogger").field("sinks", &sinks).finish()
     }
+}
 
-    async fn rotate_log_if_needed(&self) -> Result<()> {
-        if !self.log_file_path.exists() {
-            return Ok(());
-        }
-
-        let metadata = fs::metadata(&self.log_file_path).await?;
-        if metadata.len() >= self.max_size_bytes {
-            let timestamp = Utc::now().format("%Y-%m-%d_%H-%M-%S").to_string();
-            let file_stem = self
-                .log_file_path
-                .file_stem()
-                .unwrap_or_default()
-                .to_string_lossy();
-            let extension = self
-                .log_file_path
-                .extension()
-                .unwrap_or_default()
-                .to_string_lossy();
-
-            let backup_file_name = format!("{}_{}.{}", file_stem, timestamp, extension);
-            let backup_path = self.log_file_path.with_file_name(backup_file_name);
-
-            fs::rename(&self.log_file_path, backup_path).await?;
+impl AuditLogger {
+    pub fn new(config_state: Arc<ConfigState>) -> Self {
+        let config = config_state.snapshot();
+        let (mut direct, mut queued) = (Vec::new(), Vec::new());
+        for sink in audit_sinks::build_sinks(&config) {
+            if sink.queued() { queued.push(QueuedSink::spawn(sink)); } else { direct.push(sink); }
         }
-        Ok(())
+        Self { config_state, direct, queued }
     }
 
     pub async fn log_command_call(&self, command_name: &str, arguments: &Value) {
-        if let Err(e) = self.try_log_command_call(command_name, arguments).await {
-            error!(command = %command_name, error = %e, "Failed to write audit log");
-        }
+        self.log_client_call(None, command_name, arguments).await;
     }
 
-    async fn try_log_command_call(&self, command_name: &str, arguments: &Value) -> Result<()> {
-        self.rotate_log_if_needed().await?;
+    /// Like log_command_call, attributing the call to a network client when there is one.
+    pub async fn log_client_call(&self, client: Option<&str>, command_name: &str, arguments: &Value) {
+        self.log_correlated_call(client, None, command_name, arguments).await;
+    }
 
-        let timestamp = Utc::now().to_rfc3339();
+    /// Like log_client_call, tagging the entry with the correlation id of the tool call it belongs to.
+    pub async fn log_correlated_call(&self, client: Option<&str>, correlation_id: Option<&str>, command_name: &str, arguments: &Value) {
+        // Before any sink sees the entry, so secrets never reach disk or the network.
+        let sanitized_args = redaction::redactor(&self.config_state.snapshot()).redact_value(arguments);
 
-        let mut sanitized_args = arguments.clone();
-        if let Some(obj) = sanitized_args.as_object_mut() {
-            for key_to_sanitize in ["content", "old_string", "new_string", "command", "pattern"] { // Added "pattern"
-                if let Some(val_mut) = obj.get_mut(key_to_sanitize) {
-                    if val_mut.is_string() && val_mut.as_str().unwrap_or("").len() > 256 {
-                        *val_mut = Value::String(format!("<{} truncated for log>", key_to_sanitize));
-                    }
-                }
+        let record = Arc::new(AuditRecord { timestamp: Utc::now(), client: client.map(str::to_string), correlation_id: correlation_id.map(str::to_string), command: command_name.to_string(), arguments: sanitized_args });
+        for sink in &self.direct {
+            if let Err(e) = sink.write(std::slice::from_ref(&record)).await {
+                error!(command = %command_name, sink = sink.kind().as_str(), error = %e, "Failed to write audit log");
             }
         }
+        for sink in &self.queued { sink.send(record.clone()); }
+    }
 
-        let args_string = serde_json::to_string(&sanitized_args)?;
-        let log_entry = format!("{} | CMD: {:<25} | Arguments: {}\n", timestamp, command_name, args_string);
-
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.log_file_path)
-            .await?;
-
-        file.write_all(log_entry.as_bytes()).await?;
-        Ok(())
+    /// Delivers the entries the queued sinks still hold. Called once, after the last entry.
+    pub async fn flush(&self) {
+        for sink in &self.queued { sink.flush(SINK_FLUSH_TIMEOUT).await; }
+        for sink in &self.direct {
+            if let Err(e) = sink.flush().await { error!(sink = sink.kind().as_str(), error = %e, "Failed to flush audit sink"); }
+        }
     }
 }
 
@@ -106,4 +76,4 @@ pub async fn audit_log(
     arguments: &Value,
 ) {
     logger_state.inner().log_command_call(command_name, arguments).await;
-}
\ No newline at end of file
+}
@@ -0,0 +1,396 @@
+use crate::config::{AuditSinkKind, Config, SyslogAddress};
+use crate::secrets;
+use crate::utils::limits::truncate_output;
+use crate::utils::log_rotation::{RotatingLog, RotationPolicy};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot, Mutex as TokioMutex, OnceCell};
+use tracing::{debug, warn};
+
+// Entries a queued sink holds while its destination is slow or down; past this, new ones are dropped.
+const QUEUE_CAPACITY: usize = 4096;
+// Entries handed to a queued sink's write at once, e.g. one webhook POST.
+const MAX_BATCH: usize = 100;
+const DELIVERY_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+// rsyslog and syslog-ng accept 8 KiB by default; longer messages are cut.
+const SYSLOG_MAX_MESSAGE_BYTES: usize = 8192;
+// RFC 5424 facility 13, "log audit".
+const SYSLOG_FACILITY: u8 = 13;
+const SYSLOG_NOTICE: u8 = 5;
+const SYSLOG_WARNING: u8 = 4;
+// The structured data id, under the IANA example enterprise number.
+const SYSLOG_SD_ID: &str = "mcp@32473";
+const APP_NAME: &str = "mcp-rg-editor";
+
+/// One audit entry, its arguments already through the redactor.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    pub command: String,
+    pub arguments: Value,
+}
+
+impl AuditRecord {
+    /// The audit log line, as query_audit_log parses it back.
+    pub fn to_line(&self) -> String {
+        let mut line = self.timestamp.to_rfc3339();
+        if let Some(client) = &self.client { line.push_str(&format!(" | CLIENT: {}", client)); }
+        if let Some(correlation_id) = &self.correlation_id { line.push_str(&format!(" | CALL: {}", correlation_id)); }
+        line.push_str(&format!(" | CMD: {:<25} | Arguments: {}\n", self.command, self.arguments));
+        line
+    }
+
+    // Failed tool calls are reported as such, so syslog and OTLP filters can pick them out.
+    fn is_failure(&self) -> bool {
+        self.command == crate::mcp::tool_impl::audit::CALL_RESULT_AUDIT_COMMAND && self.arguments.get("ok").and_then(Value::as_bool) == Some(false)
+    }
+}
+
+/// A destination for audit entries, one per `audit_sinks` entry.
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    fn kind(&self) -> AuditSinkKind;
+
+    /// Writes `records`, oldest first.
+    async fn write(&self, records: &[Arc<AuditRecord>]) -> Result<()>;
+
+    /// Delivers whatever the sink still buffers. Called once, during shutdown.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether entries reach the sink through a queue instead of being awaited by the call that
+    /// logged them, so a slow or unreachable destination never holds up a tool call.
+    fn queued(&self) -> bool {
+        true
+    }
+}
+
+/// The sinks `config.audit_sinks` lists. One that cannot be set up is logged and left out, so the others still work.
+pub fn build_sinks(config: &Config) -> Vec<Arc<dyn AuditSink>> {
+    let mut sinks: Vec<Arc<dyn AuditSink>> = Vec::new();
+    for kind in &config.audit_sinks {
+        let sink: Result<Arc<dyn AuditSink>> = match kind {
+            AuditSinkKind::File => Ok(Arc::new(FileSink::new(config))),
+            AuditSinkKind::Syslog => SyslogSink::new(config).map(|s| Arc::new(s) as Arc<dyn AuditSink>),
+            AuditSinkKind::Webhook => WebhookSink::new(config).map(|s| Arc::new(s) as Arc<dyn AuditSink>),
+            #[cfg(feature = "otel")]
+            AuditSinkKind::Otlp => otlp::OtlpSink::new(config).map(|s| Arc::new(s) as Arc<dyn AuditSink>),
+            // config_validation reports this.
+            #[cfg(not(feature = "otel"))]
+            AuditSinkKind::Otlp => Err(anyhow::anyhow!("the otel feature is not compiled in")),
+        };
+        match sink {
+            Ok(sink) => sinks.push(sink),
+            Err(e) => warn!(sink = kind.as_str(), error = %e, "Audit sink is off"),
+        }
+    }
+    sinks
+}
+
+/// audit_log_file, rotated per the audit_log_* keys.
+pub struct FileSink {
+    log: RotatingLog,
+}
+
+impl FileSink {
+    pub fn new(config: &Config) -> Self {
+        Self { log: RotatingLog::new(config.audit_log_file.clone(), RotationPolicy::from_config(config)) }
+    }
+}
+
+#[async_trait]
+impl AuditSink for FileSink {
+    fn kind(&self) -> AuditSinkKind {
+        AuditSinkKind::File
+    }
+
+    async fn write(&self, records: &[Arc<AuditRecord>]) -> Result<()> {
+        let lines: String = records.iter().map(|r| r.to_line()).collect();
+        self.log.append(&lines, None).await
+    }
+
+    // The file is local and quick, and shutdown relies on the last entry being on disk once logged.
+    fn queued(&self) -> bool {
+        false
+    }
+}
+
+/// RFC 5424 messages over UDP or a Unix datagram socket.
+pub struct SyslogSink {
+    address: SyslogAddress,
+    hostname: String,
+    pid: u32,
+    // Opened on first use and after a failed send, e.g. when the daemon was restarted.
+    socket: TokioMutex<Option<SyslogSocket>>,
+}
+
+enum SyslogSocket {
+    Udp(tokio::net::UdpSocket),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixDatagram),
+}
+
+impl SyslogSink {
+    pub fn new(config: &Config) -> Result<Self> {
+        let address = config.audit_syslog_address.as_deref().map(SyslogAddress::from_str).transpose()?.unwrap_or_else(SyslogAddress::local);
+        // HOSTNAME is printable ASCII without spaces, at most 255 characters.
+        let hostname: String = sysinfo::System::host_name().unwrap_or_default().chars().filter(|c| c.is_ascii_graphic()).take(255).collect();
+        let hostname = if hostname.is_empty() { "-".to_string() } else { hostname };
+        Ok(Self { address, hostname, pid: std::process::id(), socket: TokioMutex::new(None) })
+    }
+
+    async fn connect(&self) -> Result<SyslogSocket> {
+        match &self.address {
+            SyslogAddress::Udp(host_port) => {
+                let target = tokio::net::lookup_host(host_port.as_str()).await?.next().with_context(|| format!("'{}' did not resolve", host_port))?;
+                let socket = tokio::net::UdpSocket::bind(if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }).await?;
+                socket.connect(target).await?;
+                Ok(SyslogSocket::Udp(socket))
+            }
+            #[cfg(unix)]
+            SyslogAddress::Unix(path) => {
+                let socket = tokio::net::UnixDatagram::unbound()?;
+                socket.connect(path).with_context(|| format!("Failed to connect to {}", path.display()))?;
+                Ok(SyslogSocket::Unix(socket))
+            }
+            // SyslogAddress::from_str only accepts unix:// on Unix.
+            #[cfg(not(unix))]
+            SyslogAddress::Unix(path) => anyhow::bail!("Unix sockets are not supported here: {}", path.display()),
+        }
+    }
+
+    fn message(&self, record: &AuditRecord) -> String {
+        let severity = if record.is_failure() { SYSLOG_WARNING } else { SYSLOG_NOTICE };
+        // MSGID is at most 32 printable ASCII characters.
+        let msg_id: String = record.command.chars().map(|c| if c.is_ascii_graphic() { c } else { '_' }).take(32).collect();
+        let params: Vec<String> = [("client", &record.client), ("call", &record.correlation_id)].into_iter()
+            .filter_map(|(name, value)| value.as_ref().map(|v| format!("{}=\"{}\"", name, escape_sd_value(v))))
+            .collect();
+        let structured_data = if params.is_empty() { "-".to_string() } else { format!("[{} {}]", SYSLOG_SD_ID, params.join(" ")) };
+        let mut message = format!("<{}>1 {} {} {} {} {} {} {}",
+            SYSLOG_FACILITY * 8 + severity, record.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true), self.hostname, APP_NAME, self.pid, msg_id, structured_data, record.arguments);
+        truncate_output(&mut message, SYSLOG_MAX_MESSAGE_BYTES);
+        message
+    }
+}
+
+fn escape_sd_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace(']', "\\]")
+}
+
+#[async_trait]
+impl AuditSink for SyslogSink {
+    fn kind(&self) -> AuditSinkKind {
+        AuditSinkKind::Syslog
+    }
+
+    async fn write(&self, records: &[Arc<AuditRecord>]) -> Result<()> {
+        let mut socket = self.socket.lock().await;
+        if socket.is_none() { *socket = Some(self.connect().await?); }
+        for record in records {
+            let message = self.message(record);
+            let sent = match socket.as_ref() {
+                Some(SyslogSocket::Udp(udp)) => udp.send(message.as_bytes()).await,
+                #[cfg(unix)]
+                Some(SyslogSocket::Unix(unix)) => unix.send(message.as_bytes()).await,
+                None => unreachable!("connected above"),
+            };
+            if let Err(e) = sent {
+                *socket = None;
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// POSTs `{"entries": [...]}` batches of AuditRecord JSON to audit_webhook_url.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    token_secret: Option<String>,
+    // Read from the keyring on first use.
+    token: OnceCell<Option<String>>,
+}
+
+impl WebhookSink {
+    pub fn new(config: &Config) -> Result<Self> {
+        let url = config.audit_webhook_url.clone().context("audit_webhook_url is not set")?;
+        let client = reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build()?;
+        Ok(Self { client, url, token_secret: config.audit_webhook_token_secret.clone(), token: OnceCell::new() })
+    }
+
+    async fn token(&self) -> Option<&str> {
+        self.token.get_or_init(|| async {
+            let name = self.token_secret.clone()?;
+            let token = tauri::async_runtime::spawn_blocking(move || secrets::get_secret(&name)).await.map_err(anyhow::Error::from).and_then(|r| r.map_err(anyhow::Error::from));
+            token.unwrap_or_else(|e| { warn!(error = %e, "Sending audit entries to the webhook without a token"); None })
+        }).await.as_deref()
+    }
+}
+
+#[async_trait]
+impl AuditSink for WebhookSink {
+    fn kind(&self) -> AuditSinkKind {
+        AuditSinkKind::Webhook
+    }
+
+    async fn write(&self, records: &[Arc<AuditRecord>]) -> Result<()> {
+        let mut request = self.client.post(&self.url).json(&json!({ "entries": records }));
+        if let Some(token) = self.token().await { request = request.bearer_auth(token); }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use super::{AuditRecord, AuditSink, APP_NAME};
+    use crate::config::{AuditSinkKind, Config};
+
+    use anyhow::{Context, Result};
+    use async_trait::async_trait;
+    use opentelemetry::logs::{AnyValue, LogRecord as _, Logger as _, LoggerProvider as _, Severity};
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::logs::{Logger, LoggerProvider};
+    use opentelemetry_sdk::runtime;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    /// OTLP log records to otlp_endpoint, one per entry, with the arguments as the body.
+    pub struct OtlpSink {
+        provider: LoggerProvider,
+        logger: Logger,
+    }
+
+    impl OtlpSink {
+        pub fn new(config: &Config) -> Result<Self> {
+            let endpoint = config.otlp_endpoint.as_deref().context("otlp_endpoint is not set")?;
+            // The batch log processor spawns onto the Tokio runtime.
+            let _runtime = tauri::async_runtime::handle().inner().enter();
+            let exporter = opentelemetry_otlp::LogExporter::builder().with_tonic().with_endpoint(endpoint).build()
+                .with_context(|| format!("Failed to create the OTLP log exporter for {}", endpoint))?;
+            let provider = LoggerProvider::builder().with_batch_exporter(exporter, runtime::Tokio).with_resource(crate::telemetry::resource()).build();
+            let logger = provider.logger(format!("{}.audit", APP_NAME));
+            Ok(Self { provider, logger })
+        }
+    }
+
+    #[async_trait]
+    impl AuditSink for OtlpSink {
+        fn kind(&self) -> AuditSinkKind {
+            AuditSinkKind::Otlp
+        }
+
+        async fn write(&self, records: &[Arc<AuditRecord>]) -> Result<()> {
+            for record in records {
+                let (severity, severity_text) = if record.is_failure() { (Severity::Warn, "WARN") } else { (Severity::Info, "INFO") };
+                let mut log_record = self.logger.create_log_record();
+                log_record.set_timestamp(SystemTime::from(record.timestamp));
+                log_record.set_observed_timestamp(SystemTime::now());
+                log_record.set_severity_number(severity);
+                log_record.set_severity_text(severity_text);
+                log_record.set_body(AnyValue::from(record.arguments.to_string()));
+                log_record.add_attribute("audit.command", record.command.clone());
+                if let Some(client) = &record.client { log_record.add_attribute("audit.client", client.clone()); }
+                if let Some(correlation_id) = &record.correlation_id { log_record.add_attribute("audit.correlation_id", correlation_id.clone()); }
+                self.logger.emit(log_record);
+            }
+            Ok(())
+        }
+
+        async fn flush(&self) -> Result<()> {
+            let provider = self.provider.clone();
+            tauri::async_runtime::spawn_blocking(move || provider.shutdown()).await??;
+            Ok(())
+        }
+    }
+}
+
+enum QueueMessage {
+    Record(Arc<AuditRecord>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// A sink fed by a background task, which batches entries and retries failed writes.
+pub struct QueuedSink {
+    kind: AuditSinkKind,
+    sender: mpsc::Sender<QueueMessage>,
+    dropped: AtomicU64,
+}
+
+impl QueuedSink {
+    pub fn spawn(sink: Arc<dyn AuditSink>) -> Self {
+        let kind = sink.kind();
+        let (sender, mut receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tauri::async_runtime::spawn(async move {
+            while let Some(first) = receiver.recv().await {
+                let (mut batch, mut flush_reply) = (Vec::new(), None);
+                let mut next = Some(first);
+                while let Some(message) = next.take() {
+                    match message {
+                        QueueMessage::Record(record) => batch.push(record),
+                        QueueMessage::Flush(reply) => { flush_reply = Some(reply); break; }
+                    }
+                    if batch.len() < MAX_BATCH { next = receiver.try_recv().ok(); }
+                }
+                if !batch.is_empty() { deliver(sink.as_ref(), &batch).await; }
+                if let Some(reply) = flush_reply {
+                    if let Err(e) = sink.flush().await { warn!(sink = kind.as_str(), error = %e, "Failed to flush audit sink"); }
+                    let _ = reply.send(());
+                }
+            }
+        });
+        Self { kind, sender, dropped: AtomicU64::new(0) }
+    }
+
+    pub fn kind(&self) -> AuditSinkKind {
+        self.kind
+    }
+
+    pub fn send(&self, record: Arc<AuditRecord>) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(QueueMessage::Record(record)) {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            // Once per power of two, so an unreachable destination does not flood the log.
+            if dropped.is_power_of_two() { warn!(sink = self.kind.as_str(), dropped, "Audit sink queue is full; dropping entries"); }
+        }
+    }
+
+    /// Waits up to `timeout` for the entries queued so far to be written and the sink flushed.
+    pub async fn flush(&self, timeout: Duration) {
+        let (reply, done) = oneshot::channel();
+        let flushed = tokio::time::timeout(timeout, async {
+            self.sender.send(QueueMessage::Flush(reply)).await.is_ok() && done.await.is_ok()
+        }).await;
+        if !matches!(flushed, Ok(true)) { warn!(sink = self.kind.as_str(), "Audit sink did not finish writing before shutdown"); }
+    }
+}
+
+async fn deliver(sink: &dyn AuditSink, batch: &[Arc<AuditRecord>]) {
+    for attempt in 1..=DELIVERY_ATTEMPTS {
+        match sink.write(batch).await {
+            Ok(()) => return,
+            Err(e) if attempt == DELIVERY_ATTEMPTS => warn!(sink = sink.kind().as_str(), entries = batch.len(), error = %e, "Dropped audit entries the sink could not take"),
+            Err(e) => {
+                debug!(sink = sink.kind().as_str(), attempt, error = %e, "Audit sink write failed; retrying");
+                tokio::time::sleep(RETRY_DELAY * attempt).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Hits and misses of one cache since the app started.
+#[derive(Debug, Default)]
+pub struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// A cache's counters as get_server_stats reports them.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStatsMCP {
+    pub hits: u64,
+    pub misses: u64,
+    // hits / (hits + misses); None until the cache was first asked.
+    pub hit_rate: Option<f64>,
+    pub entries: usize,
+}
+
+impl CacheCounters {
+    pub const fn new() -> Self {
+        Self { hits: AtomicU64::new(0), misses: AtomicU64::new(0) }
+    }
+
+    pub fn record(&self, hit: bool) {
+        if hit { self.hits.fetch_add(1, Ordering::Relaxed); } else { self.misses.fetch_add(1, Ordering::Relaxed); }
+    }
+
+    pub fn stats(&self, entries: usize) -> CacheStatsMCP {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+        CacheStatsMCP { hits, misses, hit_rate: (lookups > 0).then(|| hits as f64 / lookups as f64), entries }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::error::AppError;
+use crate::utils::cache_stats::{CacheCounters, CacheStatsMCP};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::debug;
+
+pub const DIR_POLICY_FILE_NAME: &str = ".mcp-rg.toml";
+
+/// A `.mcp-rg.toml` dropped into a project directory. It can only tighten the global config
+/// for paths under that directory, never loosen it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirPolicyFile {
+    // Globs relative to the directory holding the policy file, e.g. "vendor/**" or "*.lock".
+    #[serde(default)]
+    read_only_globs: Vec<String>,
+    #[serde(default)]
+    blocked_commands: Vec<String>,
+    file_write_line_limit: Option<usize>,
+}
+
+#[derive(Debug)]
+struct LoadedDirPolicy {
+    policy_file: PathBuf,
+    base_dir: PathBuf,
+    read_only: GlobSet,
+    blocked_commands: Vec<String>,
+    file_write_line_limit: Option<usize>,
+}
+
+// Parsed policy files keyed by path, reparsed when their mtime changes.
+static POLICY_CACHE: Lazy<Mutex<HashMap<PathBuf, (SystemTime, Arc<LoadedDirPolicy>)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static POLICY_CACHE_COUNTERS: CacheCounters = CacheCounters::new();
+
+fn load_policy_file(policy_file: &Path) -> Result<Option<Arc<LoadedDirPolicy>>, AppError> {
+    let modified = match std::fs::metadata(policy_file) {
+        Ok(meta) if meta.is_file() => meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        _ => return Ok(None),
+    };
+    if let Some((cached_mtime, cached)) = POLICY_CACHE.lock().unwrap().get(policy_file) {
+        if *cached_mtime == modified { POLICY_CACHE_COUNTERS.record(true); return Ok(Some(cached.clone())); }
+    }
+    POLICY_CACHE_COUNTERS.record(false);
+
+    let contents = std::fs::read_to_string(policy_file).map_err(|e| AppError::ConfigError(format!("Failed to read policy file {}: {}", policy_file.display(), e)))?;
+    let parsed: DirPolicyFile = toml::from_str(&contents).map_err(|e| AppError::ConfigError(format!("Invalid policy file {}: {}", policy_file.display(), e)))?;
+    let mut builder = GlobSetBuilder::new();
+    for pattern in &parsed.read_only_globs {
+        let glob = Glob::new(pattern).map_err(|e| AppError::ConfigError(format!("Invalid read_only_globs entry '{}' in {}: {}", pattern, policy_file.display(), e)))?;
+        builder.add(glob);
+    }
+    let read_only = builder.build().map_err(|e| AppError::ConfigError(format!("Invalid read_only_globs in {}: {}", policy_file.display(), e)))?;
+    crate::config::check_blocked_commands(&parsed.blocked_commands).map_err(|e| AppError::ConfigError(format!("Invalid blocked_commands in {}: {:#}", policy_file.display(), e)))?;
+    let loaded = Arc::new(LoadedDirPolicy {
+        policy_file: policy_file.to_path_buf(),
+        base_dir: policy_file.parent().map(PathBuf::from).unwrap_or_default(),
+        read_only,
+        blocked_commands: parsed.blocked_commands,
+        file_write_line_limit: parsed.file_write_line_limit,
+    });
+    debug!(policy_file = %policy_file.display(), "Loaded directory policy");
+    POLICY_CACHE.lock().unwrap().insert(policy_file.to_path_buf(), (modified, loaded.clone()));
+    Ok(Some(loaded))
+}
+
+pub fn cache_stats() -> CacheStatsMCP {
+    POLICY_CACHE_COUNTERS.stats(POLICY_CACHE.lock().unwrap().len())
+}
+
+/// Every directory policy that applies to a path, nearest directory first.
+#[derive(Debug, Default)]
+pub struct DirPolicy {
+    policies: Vec<Arc<LoadedDirPolicy>>,
+}
+
+impl DirPolicy {
+    /// Collects `.mcp-rg.toml` files from `path` (or its directory) up to and including
+    /// `files_root`. A policy file that fails to parse is an error rather than being ignored,
+    /// because ignoring it would silently loosen the rules it was written to enforce.
+    pub fn for_path(path: &Path, files_root: &Path) -> Result<Self, AppError> {
+        let start_dir = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+        let mut policies = Vec::new();
+        for dir in start_dir.ancestors() {
+            if let Some(policy) = load_policy_file(&dir.join(DIR_POLICY_FILE_NAME))? {
+                policies.push(policy);
+            }
+            if dir == files_root { break; }
+        }
+        Ok(Self { policies })
+    }
+
+    /// The policy file whose read_only_globs match `path`, if any.
+    pub fn read_only_match(&self, path: &Path) -> Option<&Path> {
+        self.policies.iter().find(|p| {
+            path.strip_prefix(&p.base_dir).map(|rel| p.read_only.is_match(rel)).unwrap_or(false)
+        }).map(|p| p.policy_file.as_path())
+    }
+
+    pub fn blocked_commands(&self) -> impl Iterator<Item = &String> {
+        self.policies.iter().flat_map(|p| p.blocked_commands.iter())
+    }
+
+    /// The smallest of the global limit and every applicable policy limit.
+    pub fn file_write_line_limit(&self, global_limit: usize) -> usize {
+        self.policies.iter().filter_map(|p| p.file_write_line_limit).fold(global_limit, usize::min)
+    }
+}
@@ -0,0 +1,154 @@
+use crate::config::{Config, ContainerTargetConfig, SshHostConfig};
+use crate::error::AppError;
+use crate::utils::shell_quoting::{quote_arg, ShellKind};
+use crate::AppHandle;
+
+use std::path::Path;
+use tauri_plugin_shell::{process::Command as TauriShellCommand, ShellExt};
+
+pub const LOCAL_TARGET_NAME: &str = "local";
+
+/// Where `execute_command` runs a command. Every backend produces a plugin-shell
+/// `Command`, so sessions, output scrollback and force_terminate work the same for all of them.
+#[derive(Debug, Clone)]
+pub enum ExecutionTarget {
+    Local,
+    Ssh(SshHostConfig),
+    Container(ContainerTargetConfig),
+}
+
+impl ExecutionTarget {
+    /// Resolves `"local"`, `"ssh:<name>"`, `"container:<name>"` or a bare configured name (ssh hosts first).
+    pub fn resolve(target_name: Option<&str>, config: &Config) -> Result<Self, AppError> {
+        let name = match target_name.map(str::trim) {
+            None | Some("") | Some(LOCAL_TARGET_NAME) => return Ok(ExecutionTarget::Local),
+            Some(n) => n,
+        };
+        let ssh_match = || {
+            let host_name = name.strip_prefix("ssh:").unwrap_or(name);
+            config.ssh_hosts.iter().find(|h| h.name == host_name).cloned().map(ExecutionTarget::Ssh)
+        };
+        let container_match = || {
+            let container_name = name.strip_prefix("container:").unwrap_or(name);
+            config.container_targets.iter().find(|c| c.name == container_name).cloned().map(ExecutionTarget::Container)
+        };
+        let resolved = if name.starts_with("container:") { container_match() } else { ssh_match().or_else(|| if name.starts_with("ssh:") { None } else { container_match() }) };
+        resolved.ok_or_else(|| AppError::InvalidInputArgument(format!(
+                "Unknown execution_target '{}'. Available: {}", name, available_target_names(config).join(", ")
+            )))
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            ExecutionTarget::Local => LOCAL_TARGET_NAME.to_string(),
+            ExecutionTarget::Ssh(host) => format!("ssh:{}", host.name),
+            ExecutionTarget::Container(target) => format!("container:{}", target.name),
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        matches!(self, ExecutionTarget::Local)
+    }
+}
+
+pub fn available_target_names(config: &Config) -> Vec<String> {
+    std::iter::once(LOCAL_TARGET_NAME.to_string())
+        .chain(config.ssh_hosts.iter().map(|h| format!("ssh:{}", h.name)))
+        .chain(config.container_targets.iter().map(|c| format!("container:{}", c.name)))
+        .collect()
+}
+
+/// Maps a local path under `files_root` onto the remote root, e.g. `<FILES_ROOT>/api` -> `/srv/work/api`.
+pub fn map_local_path_to_remote(local_path: &Path, files_root: &Path, remote_root: &str) -> Option<String> {
+    let relative = local_path.strip_prefix(files_root).ok()?;
+    let mut remote = remote_root.trim_end_matches('/').to_string();
+    for component in relative.components() {
+        remote.push('/');
+        remote.push_str(&component.as_os_str().to_string_lossy());
+    }
+    if remote.is_empty() { remote.push('/'); }
+    Some(remote)
+}
+
+/// Builds an `ssh` invocation running `remote_command` on `host` (remote shells are assumed POSIX).
+/// `-tt` allocates a remote pty so killing the local ssh client hangs up the remote process tree.
+pub fn build_ssh_command(
+    app_handle: &AppHandle,
+    host: &SshHostConfig,
+    remote_command: &str,
+    remote_cwd: Option<&str>,
+) -> Result<TauriShellCommand, AppError> {
+    let ssh_path = which::which("ssh")
+        .map_err(|e| AppError::CommandExecutionError(format!("ssh client not found: {}. Install OpenSSH to use ssh execution targets.", e)))?;
+
+    let remote_line = match remote_cwd {
+        Some(cwd) => format!("cd {} && {}", quote_arg(ShellKind::Posix, cwd), remote_command),
+        None => remote_command.to_string(),
+    };
+
+    let mut args: Vec<String> = vec!["-tt".into(), "-o".into(), "BatchMode=yes".into()];
+    if let Some(port) = host.port {
+        args.push("-p".into());
+        args.push(port.to_string());
+    }
+    if let Some(identity) = &host.identity_file {
+        args.push("-i".into());
+        args.push(identity.to_string_lossy().into_owned());
+    }
+    args.push(match &host.user {
+        Some(user) => format!("{}@{}", user, host.host),
+        None => host.host.clone(),
+    });
+    args.push("--".into());
+    args.push(remote_line);
+
+    Ok(app_handle.shell().command(ssh_path.to_string_lossy().to_string()).args(args))
+}
+
+/// A container invocation plus the command that must run after the session is killed.
+/// Killing the docker/podman client does not stop what it started, so throwaway containers
+/// are given a known name and removed explicitly.
+pub struct ContainerCommand {
+    pub command: TauriShellCommand,
+    pub cleanup_command: Option<Vec<String>>,
+}
+
+/// Builds `<runtime> exec` or `<runtime> run --rm` for `target`, running `command_line` with
+/// `sh -c` inside the container. In run mode FILES_ROOT is bind-mounted at `target.workdir`
+/// and `local_cwd` is mapped onto that mount.
+pub fn build_container_command(
+    app_handle: &AppHandle,
+    target: &ContainerTargetConfig,
+    command_line: &str,
+    files_root: &Path,
+    local_cwd: &Path,
+    session_id: &str,
+) -> Result<ContainerCommand, AppError> {
+    let runtime_name = target.runtime.clone().unwrap_or_else(|| "docker".to_string());
+    let runtime_path = which::which(&runtime_name)
+        .map_err(|e| AppError::CommandExecutionError(format!("Container runtime '{}' not found: {}", runtime_name, e)))?;
+    let runtime_path_str = runtime_path.to_string_lossy().to_string();
+    let container_cwd = map_local_path_to_remote(local_cwd, files_root, &target.workdir).unwrap_or_else(|| target.workdir.clone());
+
+    let (args, cleanup_command): (Vec<String>, Option<Vec<String>>) = match (&target.container, &target.image) {
+        (Some(container), _) => (
+            vec!["exec".into(), "-i".into(), "-w".into(), container_cwd, container.clone(), "sh".into(), "-c".into(), command_line.to_string()],
+            None,
+        ),
+        (None, Some(image)) => {
+            let container_name = format!("mcp-rg-{}", session_id);
+            let mut run_args: Vec<String> = vec![
+                "run".into(), "--rm".into(), "-i".into(), "--init".into(),
+                "--name".into(), container_name.clone(),
+                "-v".into(), format!("{}:{}", files_root.to_string_lossy(), target.workdir),
+                "-w".into(), container_cwd,
+            ];
+            run_args.extend(target.extra_run_args.iter().cloned());
+            run_args.extend([image.clone(), "sh".into(), "-c".into(), command_line.to_string()]);
+            (run_args, Some(vec![runtime_path_str.clone(), "rm".into(), "-f".into(), container_name]))
+        }
+        (None, None) => return Err(AppError::ConfigError(format!("Container target '{}' has neither 'container' nor 'image' set", target.name))),
+    };
+
+    Ok(ContainerCommand { command: app_handle.shell().command(runtime_path_str).args(args), cleanup_command })
+}
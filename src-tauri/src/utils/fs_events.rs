@@ -0,0 +1,129 @@
+use crate::config::ConfigState;
+use crate::error::AppError;
+use crate::utils::path_cache;
+use crate::utils::path_utils::validate_and_normalize_path;
+use crate::AppHandle;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+use tauri::Emitter;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::{debug, info, warn};
+
+pub const FS_CHANGED_EVENT: &str = "fs_changed";
+
+// Longer than the resource watcher's: a file browser refresh is costlier than a notification,
+// and a build or a bulk edit touches many files at once.
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(300);
+const MAX_SUBSCRIPTIONS: usize = 32;
+// Past this, the event only says that something under the subscribed path changed.
+const MAX_PATHS_PER_EVENT: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FsSubscriptionUI {
+    pub subscription_id: String,
+    pub path: PathBuf,
+    pub recursive: bool,
+}
+
+/// The `fs_changed` payload: the paths that changed under one subscription since the last event.
+#[derive(Debug, Clone, Serialize)]
+pub struct FsChangedEventUI {
+    pub subscription_id: String,
+    pub path: PathBuf,
+    pub paths: Vec<PathBuf>,
+    pub truncated: bool,
+}
+
+struct Subscription {
+    info: FsSubscriptionUI,
+    // Dropping it stops the watch.
+    _watcher: RecommendedWatcher,
+}
+
+/// Watches directories for the UI and emits debounced `fs_changed` events, so views such as
+/// the file browser refresh whenever MCP tools, terminals or other programs change the workspace.
+pub struct FsEventSubscriptions {
+    subscriptions: StdMutex<HashMap<String, Subscription>>,
+    event_tx: mpsc::UnboundedSender<(String, notify::Result<notify::Event>)>,
+    config_state: Arc<ConfigState>,
+}
+
+impl FsEventSubscriptions {
+    /// Starts the task that batches watcher events and emits them to the webview.
+    pub fn spawn(app_handle: AppHandle, config_state: Arc<ConfigState>) -> Arc<Self> {
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel::<(String, notify::Result<notify::Event>)>();
+        let subscriptions = Arc::new(Self { subscriptions: StdMutex::new(HashMap::new()), event_tx, config_state });
+
+        let task_subscriptions = Arc::downgrade(&subscriptions);
+        tauri::async_runtime::spawn(async move {
+            while let Some(first) = event_rx.recv().await {
+                let mut changed: HashMap<String, BTreeSet<PathBuf>> = HashMap::new();
+                let mut collect = |(id, event): (String, notify::Result<notify::Event>)| match event {
+                    Ok(ev) if !matches!(ev.kind, EventKind::Access(_)) => changed.entry(id).or_default().extend(ev.paths),
+                    Ok(_) => {}
+                    Err(e) => warn!(subscription_id = %id, error = %e, "File system watcher error"),
+                };
+                collect(first);
+                tokio::time::sleep(EVENT_DEBOUNCE).await;
+                while let Ok(event) = event_rx.try_recv() { collect(event); }
+
+                let Some(subscriptions) = task_subscriptions.upgrade() else { break };
+                for event in subscriptions.changed_events(changed) {
+                    debug!(subscription_id = %event.subscription_id, paths = event.paths.len(), "Emitting fs_changed");
+                    app_handle.emit(FS_CHANGED_EVENT, event).unwrap_or_else(|e| warn!("Emit fs_changed failed: {}", e));
+                }
+            }
+            debug!("File system event channel closed");
+        });
+        subscriptions
+    }
+
+    // Paths hidden by the path rules (denied_globs, a files_root changed since subscribing) are left out.
+    fn changed_events(&self, changed: HashMap<String, BTreeSet<PathBuf>>) -> Vec<FsChangedEventUI> {
+        let config = self.config_state.snapshot();
+        let Ok(subscriptions) = self.subscriptions.lock() else { return Vec::new() };
+        let mut events = Vec::new();
+        for (id, paths) in changed {
+            // Events can still arrive for a subscription that was just removed.
+            let Some(subscription) = subscriptions.get(&id) else { continue };
+            paths.iter().for_each(|path| path_cache::invalidate(path));
+            let mut visible: Vec<PathBuf> = paths.into_iter().filter(|path| validate_and_normalize_path(&path.to_string_lossy(), &config, false, false).is_ok()).collect();
+            if visible.is_empty() { continue; }
+            let truncated = visible.len() > MAX_PATHS_PER_EVENT;
+            visible.truncate(MAX_PATHS_PER_EVENT);
+            events.push(FsChangedEventUI { subscription_id: id, path: subscription.info.path.clone(), paths: visible, truncated });
+        }
+        events
+    }
+
+    pub fn subscribe(&self, path: &str, recursive: bool) -> Result<FsSubscriptionUI, AppError> {
+        let path = validate_and_normalize_path(path, &self.config_state.snapshot(), true, false)?;
+        let mut subscriptions = self.subscriptions.lock().map_err(|e| AppError::Unknown(format!("File system subscription lock: {}", e)))?;
+        if subscriptions.len() >= MAX_SUBSCRIPTIONS {
+            return Err(AppError::InvalidInputArgument(format!("At most {} file system subscriptions can be active; unsubscribe_fs_events one first", MAX_SUBSCRIPTIONS)));
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let (tx, watcher_id) = (self.event_tx.clone(), id.clone());
+        let mut watcher = notify::recommended_watcher(move |event| { let _ = tx.send((watcher_id.clone(), event)); })
+            .map_err(|e| AppError::Unknown(format!("File watching is unavailable: {}", e)))?;
+        let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        watcher.watch(&path, mode).map_err(|e| AppError::StdIoError(format!("Failed to watch {}: {}", path.display(), e)))?;
+        let info = FsSubscriptionUI { subscription_id: id.clone(), path, recursive };
+        info!(subscription_id = %id, path = %info.path.display(), recursive, "UI subscribed to file system events");
+        subscriptions.insert(id, Subscription { info: info.clone(), _watcher: watcher });
+        Ok(info)
+    }
+
+    /// Returns whether the subscription existed; an unknown id is not an error.
+    pub fn unsubscribe(&self, subscription_id: &str) -> Result<bool, AppError> {
+        let mut subscriptions = self.subscriptions.lock().map_err(|e| AppError::Unknown(format!("File system subscription lock: {}", e)))?;
+        let removed = subscriptions.remove(subscription_id).is_some();
+        if removed { info!(subscription_id = %subscription_id, "UI unsubscribed from file system events"); }
+        Ok(removed)
+    }
+}
@@ -1,16 +1,21 @@
 // FILE: src-tauri/src/utils/fuzzy_search_logger.rs
 // IMPORTANT NOTE: Rewrite the entire file.
-use crate::config::Config;
+use crate::config::ConfigState;
+use crate::utils::log_rotation::{RotatingLog, RotationPolicy};
 use anyhow::Result;
 use chrono::Utc;
 use serde::Serialize;
-use std::path::PathBuf;
-use std::sync::{Arc, RwLock as StdRwLock};
-use tokio::fs::{self, OpenOptions};
-use tokio::io::AsyncWriteExt;
-use tokio::sync::Mutex as TokioMutex;
+use std::sync::Arc;
 use tracing::error;
 
+const HEADERS: &[&str] = &[
+    "timestamp", "searchText", "foundText", "similarity",
+    "executionTime_ms", "exactMatchCount", "expectedReplacements",
+    "fuzzyThreshold", "belowThreshold", "diff", "searchLength",
+    "foundLength", "fileExtension", "characterCodes",
+    "uniqueCharacterCount", "diffLength",
+];
+
 #[derive(Debug, Serialize)]
 pub struct FuzzySearchLogEntry {
     pub timestamp: chrono::DateTime<Utc>,
@@ -33,79 +38,16 @@ pub struct FuzzySearchLogEntry {
 
 #[derive(Debug)]
 pub struct FuzzySearchLogger {
-    log_file_path: PathBuf,
-    initialized: TokioMutex<bool>,
-    max_size_bytes: u64,
+    log: RotatingLog,
+    // Written at the top of every new file, including each one started by a rotation.
+    header: String,
 }
 
 impl FuzzySearchLogger {
-    pub fn new(config_state: Arc<StdRwLock<Config>>) -> Self {
-        let config_guard = config_state.read().unwrap();
-        let log_file_path = config_guard.fuzzy_search_log_file.clone();
-        let max_size_bytes = config_guard.audit_log_max_size_bytes; // Reuse audit log size for now
-        drop(config_guard);
-
-        if let Some(parent_dir) = log_file_path.parent() {
-            if !parent_dir.exists() {
-                if let Err(e) = std::fs::create_dir_all(parent_dir) {
-                    error!(path = %parent_dir.display(), error = %e, "Failed to create fuzzy search log directory");
-                }
-            }
-        }
-        Self {
-            log_file_path,
-            initialized: TokioMutex::new(false),
-            max_size_bytes,
-        }
-    }
-
-    async fn rotate_log_if_needed(&self) -> Result<()> {
-        if !self.log_file_path.exists() {
-            return Ok(());
-        }
-        let metadata = fs::metadata(&self.log_file_path).await?;
-        if metadata.len() >= self.max_size_bytes {
-            let timestamp = Utc::now().format("%Y-%m-%d_%H-%M-%S").to_string();
-            let file_stem = self.log_file_path.file_stem().unwrap_or_default().to_string_lossy();
-            let extension = self.log_file_path.extension().unwrap_or_default().to_string_lossy();
-            let backup_file_name = format!("{}_{}.{}", file_stem, timestamp, extension);
-            let backup_path = self.log_file_path.with_file_name(backup_file_name);
-            fs::rename(&self.log_file_path, backup_path).await?;
-            let mut initialized_guard = self.initialized.lock().await;
-            *initialized_guard = false; // Force re-initialization of headers
-        }
-        Ok(())
-    }
-
-    async fn ensure_log_file_initialized(&self) -> Result<()> {
-        let mut initialized_guard = self.initialized.lock().await;
-        if *initialized_guard {
-            return Ok(());
-        }
-
-        let exists = match tokio::fs::metadata(&self.log_file_path).await {
-            Ok(_) => true,
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
-            Err(e) => return Err(e.into()),
-        };
-
-        if !exists {
-            let headers = [
-                "timestamp", "searchText", "foundText", "similarity",
-                "executionTime_ms", "exactMatchCount", "expectedReplacements",
-                "fuzzyThreshold", "belowThreshold", "diff", "searchLength",
-                "foundLength", "fileExtension", "characterCodes",
-                "uniqueCharacterCount", "diffLength",
-            ].join("\t");
-            let mut file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .open(&self.log_file_path)
-                .await?;
-            file.write_all(format!("{}\n", headers).as_bytes()).await?;
-        }
-        *initialized_guard = true;
-        Ok(())
+    /// Rotated and retained like the audit log.
+    pub fn new(config_state: Arc<ConfigState>) -> Self {
+        let config = config_state.snapshot();
+        Self { log: RotatingLog::new(config.fuzzy_search_log_file.clone(), RotationPolicy::from_config(&config)), header: format!("{}\n", HEADERS.join("\t")) }
     }
 
     pub async fn log(&self, entry: &FuzzySearchLogEntry) {
@@ -115,9 +57,6 @@ impl FuzzySearchLogger {
     }
 
     async fn try_log(&self, entry: &FuzzySearchLogEntry) -> Result<()> {
-        self.rotate_log_if_needed().await?;
-        self.ensure_log_file_initialized().await?;
-
         let escape = |s: &str| s.replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r");
 
         let log_line = format!(
@@ -139,12 +78,6 @@ impl FuzzySearchLogger {
             entry.unique_character_count,
             entry.diff_length
         );
-
-        let mut file = OpenOptions::new()
-            .append(true)
-            .open(&self.log_file_path)
-            .await?;
-        file.write_all(log_line.as_bytes()).await?;
-        Ok(())
+        self.log.append(&log_line, Some(&self.header)).await
     }
 }
\ No newline at end of file
@@ -0,0 +1,254 @@
+use crate::error::AppError;
+use crate::mcp::notifications::{McpNotifier, JOB_FINISHED_NOTIFICATION};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+// Finished jobs kept for get_job_status; older ones are dropped once there are more.
+const MAX_FINISHED_JOBS: usize = 200;
+const FINISHED_JOB_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// What get_job_status and list_jobs report about a job.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct JobInfoMCP {
+    pub job_id: String,
+    // What started it, e.g. run_script.
+    pub kind: String,
+    pub description: String,
+    pub status: JobStatus,
+    // 0.0 to 1.0, for jobs that know how far along they are.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_message: Option<String>,
+    pub started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_ms: u64,
+    // Only in get_job_status, once the job succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Returned instead of the result by tools asked to run in the background.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct JobStartedMCP {
+    pub job_id: String,
+    pub kind: String,
+}
+
+#[derive(Debug)]
+struct JobState {
+    status: JobStatus,
+    progress: Option<f64>,
+    progress_message: Option<String>,
+    finished_at: Option<DateTime<Utc>>,
+    finished: Option<Instant>,
+    result: Option<Value>,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Job {
+    id: String,
+    kind: String,
+    description: String,
+    // The network client that started it (ClientIdentity::owner); None for stdio and the UI.
+    owner: Option<String>,
+    cancel: CancellationToken,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    state: Mutex<JobState>,
+}
+
+impl Job {
+    fn state(&self) -> std::sync::MutexGuard<'_, JobState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn info(&self, with_result: bool) -> JobInfoMCP {
+        let state = self.state();
+        let duration = state.finished.map_or_else(|| self.started.elapsed(), |f| f.duration_since(self.started));
+        JobInfoMCP {
+            job_id: self.id.clone(),
+            kind: self.kind.clone(),
+            description: self.description.clone(),
+            status: state.status,
+            progress: state.progress,
+            progress_message: state.progress_message.clone(),
+            started_at: self.started_at,
+            finished_at: state.finished_at,
+            duration_ms: duration.as_millis() as u64,
+            result: if with_result { state.result.clone() } else { None },
+            error: state.error.clone(),
+        }
+    }
+}
+
+/// What a running job gets: its cancel token and a way to report progress.
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    job: Arc<Job>,
+}
+
+impl JobHandle {
+    /// Fires on cancel_job and on shutdown; long loops should check it between steps.
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.job.cancel
+    }
+
+    /// `fraction` is clamped to 0.0..=1.0.
+    pub fn set_progress(&self, fraction: Option<f64>, message: Option<String>) {
+        let mut state = self.job.state();
+        if state.status != JobStatus::Running { return; }
+        state.progress = fraction.map(|f| f.clamp(0.0, 1.0));
+        if message.is_some() { state.progress_message = message; }
+    }
+}
+
+/// Long operations started by tools, which return a job id at once and keep running past the
+/// call. Terminal sessions predate this and keep their own bookkeeping.
+#[derive(Debug)]
+pub struct JobManager {
+    jobs: Mutex<HashMap<String, Arc<Job>>>,
+    notifier: Arc<McpNotifier>,
+}
+
+impl JobManager {
+    pub fn new(notifier: Arc<McpNotifier>) -> Self {
+        Self { jobs: Mutex::new(HashMap::new()), notifier }
+    }
+
+    fn jobs(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Job>>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts `run` as a job and returns its id. Its outcome becomes the job's result or error,
+    /// and a notifications/job/finished goes to the job's owner when it ends.
+    pub fn spawn<F, Fut>(self: &Arc<Self>, kind: &str, description: impl Into<String>, owner: Option<String>, run: F) -> JobStartedMCP
+    where
+        F: FnOnce(JobHandle) -> Fut,
+        Fut: Future<Output = Result<Value, AppError>> + Send + 'static,
+    {
+        let job = Arc::new(Job {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            description: description.into(),
+            owner,
+            cancel: CancellationToken::new(),
+            started_at: Utc::now(),
+            started: Instant::now(),
+            state: Mutex::new(JobState { status: JobStatus::Running, progress: None, progress_message: None, finished_at: None, finished: None, result: None, error: None }),
+        });
+        {
+            let mut jobs = self.jobs();
+            prune_finished(&mut jobs);
+            jobs.insert(job.id.clone(), job.clone());
+        }
+        info!(job_id = %job.id, kind = %job.kind, description = %job.description, "Started job");
+        let future = run(JobHandle { job: job.clone() });
+        let manager = self.clone();
+        let started = JobStartedMCP { job_id: job.id.clone(), kind: job.kind.clone() };
+        tauri::async_runtime::spawn(async move {
+            let outcome = tokio::select! {
+                outcome = future => outcome,
+                _ = job.cancel.cancelled() => Err(AppError::Cancelled(format!("Job {} was cancelled", job.id))),
+            };
+            manager.finish(&job, outcome);
+        });
+        started
+    }
+
+    fn finish(&self, job: &Job, outcome: Result<Value, AppError>) {
+        {
+            let mut state = job.state();
+            state.finished_at = Some(Utc::now());
+            state.finished = Some(Instant::now());
+            match outcome {
+                Ok(result) => {
+                    state.status = JobStatus::Succeeded;
+                    state.progress = Some(1.0);
+                    state.result = Some(result);
+                }
+                Err(AppError::Cancelled(message)) => {
+                    state.status = JobStatus::Cancelled;
+                    state.error = Some(message);
+                }
+                Err(e) => {
+                    state.status = JobStatus::Failed;
+                    state.error = Some(e.to_string());
+                }
+            }
+        }
+        let info = job.info(false);
+        match info.status {
+            JobStatus::Failed => warn!(job_id = %job.id, kind = %job.kind, error = ?info.error, "Job failed"),
+            _ => info!(job_id = %job.id, kind = %job.kind, status = ?info.status, duration_ms = info.duration_ms, "Job finished"),
+        }
+        self.notifier.notify_client(job.owner.as_deref(), JOB_FINISHED_NOTIFICATION, serde_json::to_value(&info).unwrap_or_default());
+    }
+
+    // Network clients only see the jobs they started; `owner` None sees all of them.
+    fn visible(&self, job_id: &str, owner: Option<&str>) -> Result<Arc<Job>, AppError> {
+        let job = self.jobs().get(job_id).cloned();
+        job.filter(|job| owner.is_none() || job.owner.as_deref() == owner).ok_or_else(|| AppError::InvalidInputArgument(format!("No job with id '{}'", job_id)))
+    }
+
+    pub fn status(&self, job_id: &str, owner: Option<&str>) -> Result<JobInfoMCP, AppError> {
+        Ok(self.visible(job_id, owner)?.info(true))
+    }
+
+    /// Newest first.
+    pub fn list(&self, owner: Option<&str>) -> Vec<JobInfoMCP> {
+        let mut jobs: Vec<Arc<Job>> = self.jobs().values().filter(|job| owner.is_none() || job.owner.as_deref() == owner).cloned().collect();
+        jobs.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        jobs.iter().map(|job| job.info(false)).collect()
+    }
+
+    /// Cancelling a finished job changes nothing and returns its status.
+    pub fn cancel(&self, job_id: &str, owner: Option<&str>) -> Result<JobInfoMCP, AppError> {
+        let job = self.visible(job_id, owner)?;
+        if job.state().status == JobStatus::Running {
+            debug!(job_id = %job_id, "Cancelling job");
+            job.cancel.cancel();
+        }
+        Ok(job.info(false))
+    }
+
+    /// Cancels every running job; returns how many there were.
+    pub fn cancel_all(&self) -> usize {
+        let jobs = self.jobs();
+        let running: Vec<&Arc<Job>> = jobs.values().filter(|job| job.state().status == JobStatus::Running).collect();
+        running.iter().for_each(|job| job.cancel.cancel());
+        running.len()
+    }
+}
+
+fn prune_finished(jobs: &mut HashMap<String, Arc<Job>>) {
+    jobs.retain(|_, job| !job.state().finished.is_some_and(|f| f.elapsed() >= FINISHED_JOB_RETENTION));
+    let mut finished: Vec<(Instant, String)> = jobs.values().filter_map(|job| job.state().finished.map(|f| (f, job.id.clone()))).collect();
+    if finished.len() <= MAX_FINISHED_JOBS { return; }
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() - MAX_FINISHED_JOBS) {
+        jobs.remove(id);
+    }
+}
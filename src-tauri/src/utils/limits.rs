@@ -0,0 +1,53 @@
+use crate::config::LimitsConfig;
+use crate::error::AppError;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::time::Duration;
+
+static ACTIVE_SEARCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// The timeout for one call: the caller's `timeout_ms` (or `default_ms`), clamped to `max_timeout_ms`.
+pub fn call_timeout(limits: &LimitsConfig, requested_ms: Option<u64>, default_ms: u64) -> Duration {
+    Duration::from_millis(requested_ms.unwrap_or(default_ms).min(limits.max_timeout_ms))
+}
+
+pub fn check_file_size(limits: &LimitsConfig, what: impl std::fmt::Display, size: u64) -> Result<(), AppError> {
+    if size > limits.max_file_bytes {
+        return Err(AppError::LimitExceeded { message: format!("{} is {} bytes; limits.max_file_bytes is {}", what, size, limits.max_file_bytes), limit: "limits.max_file_bytes".to_string(), max: limits.max_file_bytes, actual: size });
+    }
+    Ok(())
+}
+
+/// Cuts `text` to at most `max_bytes` on a char boundary. Returns whether anything was cut.
+pub fn truncate_output(text: &mut String, max_bytes: usize) -> bool {
+    if text.len() <= max_bytes {
+        return false;
+    }
+    let mut cut = max_bytes;
+    while !text.is_char_boundary(cut) { cut -= 1; }
+    text.truncate(cut);
+    true
+}
+
+/// Searches holding a slot right now.
+pub fn active_searches() -> usize {
+    ACTIVE_SEARCHES.load(Ordering::Acquire)
+}
+
+/// One of the `max_concurrent_searches` slots; released on drop.
+pub struct SearchSlot;
+
+impl SearchSlot {
+    pub fn acquire(limits: &LimitsConfig) -> Result<Self, AppError> {
+        let max = limits.max_concurrent_searches;
+        ACTIVE_SEARCHES.fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| (active < max).then_some(active + 1))
+            .map(|_| SearchSlot)
+            .map_err(|active| AppError::LimitExceeded { message: format!("{} searches are already running; limits.max_concurrent_searches is {}", active, max), limit: "limits.max_concurrent_searches".to_string(), max: max as u64, actual: active as u64 })
+    }
+}
+
+impl Drop for SearchSlot {
+    fn drop(&mut self) {
+        ACTIVE_SEARCHES.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::config::Config;
+
+use anyhow::Result;
+use chrono::Utc;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex as TokioMutex;
+use tracing::{debug, warn};
+
+// One maintenance run at a time, so two of them never compress the same segment.
+static MAINTENANCE: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// When a log file is rotated and how long its rotated segments are kept (the `audit_log_*` keys).
+#[derive(Debug, Clone)]
+pub struct RotationPolicy {
+    // 0 never rotates by size.
+    pub max_size_bytes: u64,
+    pub max_age: Option<Duration>,
+    // Rotated segments kept per log, newest first; 0 keeps any number.
+    pub keep_files: usize,
+    // Rotated segments last modified longer ago than this are deleted.
+    pub retention: Option<Duration>,
+    pub compress: bool,
+}
+
+impl RotationPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        let hours = |h: u64| (h > 0).then(|| Duration::from_secs(h.saturating_mul(3600)));
+        Self {
+            max_size_bytes: config.audit_log_max_size_bytes,
+            max_age: hours(config.audit_log_max_age_hours),
+            keep_files: config.audit_log_keep_files,
+            retention: hours(config.audit_log_retention_days.saturating_mul(24)),
+            compress: config.audit_log_compress,
+        }
+    }
+}
+
+/// An append-only log file, rotated to `<stem>_<timestamp>.<ext>` segments that are then
+/// gzipped and pruned in the background.
+#[derive(Debug)]
+pub struct RotatingLog {
+    path: PathBuf,
+    policy: RotationPolicy,
+    // When the current file was started; also serializes writes, so entries never interleave.
+    segment_started: TokioMutex<Option<SystemTime>>,
+}
+
+impl RotatingLog {
+    /// Also compresses and prunes segments left by earlier runs.
+    pub fn new(path: PathBuf, policy: RotationPolicy) -> Self {
+        if let Some(parent_dir) = path.parent() {
+            if let Err(e) = std::fs::create_dir_all(parent_dir) {
+                warn!(path = %parent_dir.display(), error = %e, "Failed to create log directory");
+            }
+        }
+        spawn_maintenance(path.clone(), policy.clone());
+        Self { path, policy, segment_started: TokioMutex::new(None) }
+    }
+
+    /// Appends `entry`, rotating first when the file has reached the size or age limit. `header`
+    /// starts every new file.
+    pub async fn append(&self, entry: &str, header: Option<&str>) -> Result<()> {
+        let mut segment_started = self.segment_started.lock().await;
+        let new_file = match fs::metadata(&self.path).await {
+            Ok(metadata) => {
+                // Files from an earlier run count from their creation, or from now where the OS does not record it.
+                let started = *segment_started.get_or_insert_with(|| metadata.created().unwrap_or_else(|_| SystemTime::now()));
+                let too_big = self.policy.max_size_bytes > 0 && metadata.len() >= self.policy.max_size_bytes;
+                let too_old = self.policy.max_age.is_some_and(|max| started.elapsed().unwrap_or_default() >= max);
+                let rotate = metadata.len() > 0 && (too_big || too_old);
+                if rotate { self.rotate().await?; }
+                rotate
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path).await?;
+        if new_file {
+            *segment_started = Some(SystemTime::now());
+            if let Some(header) = header { file.write_all(header.as_bytes()).await?; }
+        }
+        file.write_all(entry.as_bytes()).await?;
+        // tokio finishes file writes in the background; flush so the entry is on disk when this returns.
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn rotate(&self) -> Result<()> {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = self.path.extension().unwrap_or_default().to_string_lossy();
+        // Milliseconds, so two rotations within a second do not overwrite each other.
+        let segment = self.path.with_file_name(format!("{}_{}.{}", stem, Utc::now().format("%Y-%m-%d_%H-%M-%S-%3f"), extension));
+        fs::rename(&self.path, &segment).await?;
+        debug!(log = %self.path.display(), segment = %segment.display(), "Rotated log file");
+        spawn_maintenance(self.path.clone(), self.policy.clone());
+        Ok(())
+    }
+}
+
+/// Gzips and prunes the rotated segments of `path` off the async runtime, so writes do not wait.
+fn spawn_maintenance(path: PathBuf, policy: RotationPolicy) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let _running = MAINTENANCE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut segments = rotated_segments(&path);
+        if policy.compress {
+            for (segment, _) in segments.iter_mut().filter(|(s, _)| s.extension().is_some_and(|e| e != "gz")) {
+                match compress_segment(segment) {
+                    Ok(gz_path) => *segment = gz_path,
+                    Err(e) => warn!(segment = %segment.display(), error = %e, "Failed to compress rotated log segment"),
+                }
+            }
+        }
+        segments.sort_by(|a, b| b.1.cmp(&a.1));
+        for (index, (segment, modified)) in segments.iter().enumerate() {
+            let over_count = policy.keep_files > 0 && index >= policy.keep_files;
+            let expired = policy.retention.is_some_and(|max| modified.elapsed().unwrap_or_default() >= max);
+            if !over_count && !expired { continue; }
+            match std::fs::remove_file(segment) {
+                Ok(()) => debug!(segment = %segment.display(), "Deleted rotated log segment"),
+                Err(e) => warn!(segment = %segment.display(), error = %e, "Failed to delete rotated log segment"),
+            }
+        }
+    });
+}
+
+/// `<stem>_<timestamp>.<ext>` and `.<ext>.gz` files next to `path`, with when they were last modified.
+pub fn rotated_segments(path: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) else { return Vec::new() };
+    let prefix = format!("{}_", stem.to_string_lossy());
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let (plain, gzipped) = (format!(".{}", extension), format!(".{}.gz", extension));
+    let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+    entries.flatten().filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let timestamp = name.strip_prefix(&prefix)?;
+        // Timestamps start with the year, which keeps `<stem>_<other name>` files out.
+        if !timestamp.starts_with(|c: char| c.is_ascii_digit()) || !(name.ends_with(&plain) || name.ends_with(&gzipped)) { return None; }
+        let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+        Some((entry.path(), modified))
+    }).collect()
+}
+
+/// Writes `<segment>.gz` through a temporary file and removes the segment.
+fn compress_segment(segment: &Path) -> std::io::Result<PathBuf> {
+    let file_name = segment.file_name().unwrap_or_default().to_string_lossy();
+    let gz_path = segment.with_file_name(format!("{}.gz", file_name));
+    let tmp_path = segment.with_file_name(format!("{}.gz.tmp", file_name));
+    let result = (|| {
+        let mut input = File::open(segment)?;
+        let mut encoder = GzEncoder::new(File::create(&tmp_path)?, Compression::default());
+        std::io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?.sync_all()?;
+        std::fs::rename(&tmp_path, &gz_path)
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    std::fs::remove_file(segment)?;
+    Ok(gz_path)
+}
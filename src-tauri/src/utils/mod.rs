@@ -1,5 +1,18 @@
 pub mod audit_logger;
+pub mod audit_sinks;
+pub mod cache_stats;
+pub mod dir_policy;
+pub mod execution_target;
+pub mod fs_events;
 pub mod fuzzy_search_logger;
+pub mod job_manager;
+pub mod limits;
 pub mod line_ending_handler;
+pub mod log_rotation;
+pub mod path_cache;
 pub mod path_utils;
+pub mod redaction;
+pub mod shell_quoting;
+pub mod sysinfo_cache;
+pub mod text_file;
 // pub mod terminal_session_manager; // If we create a dedicated manager
\ No newline at end of file
@@ -0,0 +1,65 @@
+use crate::utils::cache_stats::{CacheCounters, CacheStatsMCP};
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Past this many entries, expired ones are dropped, and everything if that is not enough.
+const MAX_ENTRIES: usize = 4096;
+
+static PATH_CACHE: Lazy<Mutex<HashMap<PathBuf, CachedPath>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// Only lookups with a non-zero TTL count, since the others never consult the cache.
+static COUNTERS: CacheCounters = CacheCounters::new();
+
+/// What the filesystem said about one absolute path.
+#[derive(Debug, Clone)]
+pub struct CachedPath {
+    looked_up: Instant,
+    // None when canonicalize failed, e.g. for a file that is about to be created.
+    pub canonical: Option<PathBuf>,
+    pub exists: bool,
+    pub is_dir: bool,
+}
+
+/// Looks up `path` (absolute, not necessarily canonical) at most once per `ttl`; a zero `ttl`
+/// always asks the filesystem. The file watcher and the server's own mutating calls invalidate
+/// entries early, the TTL bounds how stale other outside changes can be.
+pub fn lookup(path: &Path, ttl: Duration) -> CachedPath {
+    if !ttl.is_zero() {
+        if let Some(entry) = PATH_CACHE.lock().unwrap().get(path) {
+            if entry.looked_up.elapsed() < ttl { COUNTERS.record(true); return entry.clone(); }
+        }
+        COUNTERS.record(false);
+    }
+    let metadata = std::fs::metadata(path).ok();
+    let entry = CachedPath {
+        looked_up: Instant::now(),
+        canonical: dunce::canonicalize(path).ok(),
+        exists: metadata.is_some(),
+        is_dir: metadata.is_some_and(|m| m.is_dir()),
+    };
+    if !ttl.is_zero() {
+        let mut cache = PATH_CACHE.lock().unwrap();
+        if cache.len() >= MAX_ENTRIES {
+            cache.retain(|_, e| e.looked_up.elapsed() < ttl);
+            if cache.len() >= MAX_ENTRIES { cache.clear(); }
+        }
+        cache.insert(path.to_path_buf(), entry.clone());
+    }
+    entry
+}
+
+/// Forgets `path` and everything under it, by the looked-up path or by what it resolved to.
+pub fn invalidate(path: &Path) {
+    PATH_CACHE.lock().unwrap().retain(|key, e| !key.starts_with(path) && !e.canonical.as_deref().is_some_and(|c| c.starts_with(path)));
+}
+
+pub fn clear() {
+    PATH_CACHE.lock().unwrap().clear();
+}
+
+pub fn stats() -> CacheStatsMCP {
+    COUNTERS.stats(PATH_CACHE.lock().unwrap().len())
+}
@@ -1,6 +1,13 @@
-use crate::config::Config;
+use crate::config::{workspace_containing, Config, WorkspaceRoot};
 use crate::error::AppError;
+use crate::utils::dir_policy::DirPolicy;
+use crate::utils::path_cache;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
 use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing::debug;
 // use std::sync::RwLockReadGuard; // No longer needed as argument type
 use shellexpand;
@@ -12,7 +19,8 @@ pub fn expand_tilde_path_buf(path_str: &str) -> Result<PathBuf, AppError> {
 
 /// Normalizes a path: expands tilde, makes it absolute relative to files_root if it's relative,
 /// and then attempts to canonicalize it. Falls back to a simplified absolute path if canonicalization fails.
-fn normalize_path_base(path_str: &str, files_root: &Path) -> Result<PathBuf, AppError> {
+/// Canonical paths come from the path cache, `ttl` old at most.
+pub fn normalize_path_base(path_str: &str, files_root: &Path, ttl: Duration) -> Result<PathBuf, AppError> {
     let expanded_path = expand_tilde_path_buf(path_str)?;
 
     let absolute_path = if expanded_path.is_absolute() { // Corrected: removed mut
@@ -21,9 +29,9 @@ fn normalize_path_base(path_str: &str, files_root: &Path) -> Result<PathBuf, App
         files_root.join(expanded_path)
     };
 
-    match dunce::canonicalize(&absolute_path) {
-        Ok(canonical_path) => Ok(canonical_path),
-        Err(_) => {
+    match path_cache::lookup(&absolute_path, ttl).canonical {
+        Some(canonical_path) => Ok(canonical_path),
+        None => {
             let mut components_vec = Vec::new();
             for component in absolute_path.components() {
                 match component {
@@ -54,6 +62,78 @@ fn normalize_path_base(path_str: &str, files_root: &Path) -> Result<PathBuf, App
     }
 }
 
+// The compiled denied_globs, rebuilt only when the configured patterns change.
+static DENIED_GLOB_CACHE: Lazy<Mutex<Option<(Vec<String>, Arc<GlobSet>)>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn compile_denied_globs(patterns: &[String]) -> Result<GlobSet, AppError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| AppError::ConfigError(format!("Invalid denied_globs entry '{}': {}", pattern, e)))?);
+    }
+    builder.build().map_err(|e| AppError::ConfigError(format!("Invalid denied_globs: {}", e)))
+}
+
+pub fn denied_glob_set(config: &Config) -> Result<Arc<GlobSet>, AppError> {
+    let mut cache = DENIED_GLOB_CACHE.lock().unwrap();
+    if let Some((patterns, set)) = cache.as_ref() {
+        if patterns == &config.denied_globs { return Ok(set.clone()); }
+    }
+    let set = Arc::new(compile_denied_globs(&config.denied_globs)?);
+    *cache = Some((config.denied_globs.clone(), set.clone()));
+    Ok(set)
+}
+
+// Compiled per-workspace denied_globs, keyed by their patterns.
+static WORKSPACE_GLOB_CACHE: Lazy<Mutex<HashMap<Vec<String>, Arc<GlobSet>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Applies the rules of the workspace root containing `path`: its denied_globs (relative to the
+/// root) and, when it lists allowed_directories, that `path_for_dir_checks` lies under one of them.
+pub fn check_workspace_rules(workspaces: &[WorkspaceRoot], path: &Path, path_for_dir_checks: &Path) -> Result<(), AppError> {
+    let Some(workspace) = workspace_containing(workspaces, path) else { return Ok(()) };
+    if !workspace.denied_globs.is_empty() {
+        let set = {
+            let mut cache = WORKSPACE_GLOB_CACHE.lock().unwrap();
+            match cache.get(&workspace.denied_globs) {
+                Some(set) => set.clone(),
+                None => {
+                    let set = Arc::new(compile_denied_globs(&workspace.denied_globs)?);
+                    cache.insert(workspace.denied_globs.clone(), set.clone());
+                    set
+                }
+            }
+        };
+        if path.strip_prefix(&workspace.path).map(|rel| set.is_match(rel)).unwrap_or(false) {
+            return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("Path {} matches a denied_globs pattern of workspace '{}'", path.display(), workspace.name) });
+        }
+    }
+    if !workspace.allowed_directories.is_empty() && !workspace.allowed_directories.iter().any(|d| path_for_dir_checks.starts_with(d)) {
+        return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("Path {} is outside the allowed_directories of workspace '{}'", path.display(), workspace.name) });
+    }
+    Ok(())
+}
+
+/// Whether validate_and_normalize_path would refuse `path` (absolute, need not exist) for
+/// matching denied_globs or its workspace's rules. Listings use it to leave such entries out.
+pub fn is_denied_path(config: &Config, path: &Path) -> bool {
+    match denied_glob_set(config) {
+        Ok(set) if set.is_match(path) => true,
+        Ok(_) => check_workspace_rules(&config.workspaces, path, path).is_err(),
+        // A broken pattern refuses every path there, so it hides every entry here.
+        Err(_) => true,
+    }
+}
+
+/// Rejects writes to paths matched by a `read_only_globs` entry of an applicable `.mcp-rg.toml`.
+/// validate_and_normalize_path already does this for write/create; call it directly for
+/// operations that modify a file they first validated for reading (edit_block, move source).
+pub fn check_dir_policy_allows_write(path: &Path, config: &Config) -> Result<(), AppError> {
+    let dir_policy = DirPolicy::for_path(path, config.root_for_path(path))?;
+    if let Some(policy_file) = dir_policy.read_only_match(path) {
+        return Err(AppError::PathNotAllowed { path: path.display().to_string(), message: format!("Path {} is read-only by policy file {}", path.display(), policy_file.display()) });
+    }
+    Ok(())
+}
+
 pub fn validate_and_normalize_path(
     target_path_str: &str,
     config: &Config, // Changed from &RwLockReadGuard<Config> to &Config
@@ -62,10 +142,18 @@ pub fn validate_and_normalize_path(
 ) -> Result<PathBuf, AppError> {
     debug!(target_path = %target_path_str, check_existence, for_write_or_create, "Validating path access");
 
-    let normalized_target_path = normalize_path_base(target_path_str, &config.files_root)?;
+    let ttl = Duration::from_millis(config.limits.path_cache_ttl_ms);
+    let normalized_target_path = normalize_path_base(target_path_str, &config.files_root, ttl)?;
+    let target_exists = path_cache::lookup(&normalized_target_path, ttl).exists;
     debug!(normalized_target_path = %normalized_target_path.display(), "Initial normalized target path");
 
-    let path_for_dir_checks = if for_write_or_create && !normalized_target_path.exists() {
+    // Checked on the canonical path, so a symlink into ~/.ssh is caught as well.
+    if denied_glob_set(config)?.is_match(&normalized_target_path) {
+        debug!(path = %normalized_target_path.display(), "Path matches denied_globs");
+        return Err(AppError::PathNotAllowed { path: normalized_target_path.display().to_string(), message: format!("Path {} matches a denied_globs pattern", normalized_target_path.display()) });
+    }
+
+    let path_for_dir_checks = if for_write_or_create && !target_exists {
         normalized_target_path.parent().ok_or_else(|| AppError::InvalidPath(format!("Cannot determine parent directory for write/create: {}", normalized_target_path.display())))?.to_path_buf()
     } else {
         normalized_target_path.clone()
@@ -75,17 +163,22 @@ pub fn validate_and_normalize_path(
     let is_files_root_broad = config.files_root == Path::new("/") ||
                               (cfg!(windows) && config.files_root.parent().is_none() && config.files_root.is_absolute());
 
-    if !is_files_root_broad && !normalized_target_path.starts_with(&config.files_root) {
+    let in_workspace_root = config.workspace_for_path(&normalized_target_path).is_some();
+    if !is_files_root_broad && !in_workspace_root && !normalized_target_path.starts_with(&config.files_root) {
         debug!(path = %normalized_target_path.display(), root = %config.files_root.display(), "Path is outside files_root");
-        return Err(AppError::PathTraversal(format!(
+        if !config.workspaces.is_empty() {
+            let roots: Vec<String> = config.workspaces.iter().map(|w| format!("{} ({})", w.name, w.path.display())).collect();
+            return Err(AppError::PathTraversal { path: normalized_target_path.display().to_string(), message: format!("Path {} is outside every workspace root: {}", normalized_target_path.display(), roots.join(", ")) });
+        }
+        return Err(AppError::PathTraversal { path: normalized_target_path.display().to_string(), message: format!(
             "Path {} is outside of the configured root directory {}",
             normalized_target_path.display(),
             config.files_root.display()
-        )));
+        ) });
     }
 
     let is_globally_allowed_by_config = config.allowed_directories.iter().any(|ad_config_path| {
-        let normalized_ad = normalize_path_base(ad_config_path.to_str().unwrap_or(""), &config.files_root)
+        let normalized_ad = normalize_path_base(ad_config_path.to_str().unwrap_or(""), &config.files_root, ttl)
                                 .unwrap_or_else(|_| ad_config_path.clone());
         normalized_ad == Path::new("/") || (cfg!(windows) && normalized_ad.parent().is_none() && normalized_ad.is_absolute())
     });
@@ -94,7 +187,7 @@ pub fn validate_and_normalize_path(
         debug!("Access globally allowed by an allowed_directory entry like '/' or 'C:\\'");
     } else {
         let is_specifically_allowed = config.allowed_directories.iter().any(|allowed_dir_config_entry| {
-            let normalized_allowed_dir = normalize_path_base(allowed_dir_config_entry.to_str().unwrap_or_default(), &config.files_root)
+            let normalized_allowed_dir = normalize_path_base(allowed_dir_config_entry.to_str().unwrap_or_default(), &config.files_root, ttl)
                 .unwrap_or_else(|_| allowed_dir_config_entry.clone());
             
             debug!(check_path = %path_for_dir_checks.display(), against_allowed_dir = %normalized_allowed_dir.display(), "Checking specific allowance");
@@ -103,27 +196,36 @@ pub fn validate_and_normalize_path(
 
         if !is_specifically_allowed {
             debug!(path = %normalized_target_path.display(), checked_against = %path_for_dir_checks.display(), allowed_dirs = ?config.allowed_directories, "Path not in allowed_directories");
-            return Err(AppError::PathNotAllowed(format!(
+            return Err(AppError::PathNotAllowed { path: normalized_target_path.display().to_string(), message: format!(
                 "Operation on path {} (effective check on {}) is not within any allowed directories. Allowed: {:?}",
                 normalized_target_path.display(), path_for_dir_checks.display(), config.allowed_directories
-            )));
+            ) });
         }
     }
 
+    crate::mcp::roots::check_client_roots(&path_for_dir_checks)?;
+
+    check_workspace_rules(&config.workspaces, &normalized_target_path, &path_for_dir_checks)?;
+
+    if for_write_or_create {
+        check_dir_policy_allows_write(&normalized_target_path, config)?;
+    }
+
     if check_existence {
-        let path_to_check_existence = if for_write_or_create && !normalized_target_path.exists() {
+        let path_to_check_existence = if for_write_or_create && !target_exists {
             &path_for_dir_checks 
         } else {
             &normalized_target_path
         };
+        let existing = path_cache::lookup(path_to_check_existence, ttl);
 
-        if !path_to_check_existence.exists() {
+        if !existing.exists {
             return Err(AppError::InvalidPath(format!(
                 "Required path (or parent for write/create) does not exist: {}",
                 path_to_check_existence.display()
             )));
         }
-        if for_write_or_create && path_to_check_existence != &normalized_target_path && !path_to_check_existence.is_dir()  {
+        if for_write_or_create && path_to_check_existence != &normalized_target_path && !existing.is_dir  {
              return Err(AppError::InvalidPath(format!(
                 "Parent path for write/create is not a directory: {}",
                 path_to_check_existence.display()
@@ -0,0 +1,130 @@
+use crate::config::Config;
+use crate::utils::limits::truncate_output;
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+// Arguments holding file contents or command lines; past redact_max_value_bytes they are replaced whole.
+const CONTENT_KEYS: &[&str] = &["content", "old_string", "new_string", "command", "pattern"];
+
+// Secret shapes redacted in every string; redact_patterns adds to them. Where a pattern has a
+// `secret` group, only that part of the match is replaced, so `password=` stays readable.
+const BUILTIN_PATTERNS: &[&str] = &[
+    r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?(?:-----END [A-Z ]*PRIVATE KEY-----|$)",
+    r"(?i)\bbearer\s+(?P<secret>[A-Za-z0-9._~+/=-]{8,})",
+    r"\bAKIA[0-9A-Z]{16}\b",
+    r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+    r"\bgithub_pat_[A-Za-z0-9_]{22,}\b",
+    r"\bxox[abprs]-[A-Za-z0-9-]{10,}\b",
+    r"\bsk-[A-Za-z0-9_-]{20,}\b",
+    r"\beyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}\b",
+    r#"(?i)\b(?:password|passwd|pwd|secret|token|api[_-]?key|access[_-]?key)["']?\s*[:=]\s*["']?(?P<secret>[^\s"',;&]{4,})"#,
+    r"://[^/\s:@]+:(?P<secret>[^/\s@]+)@",
+];
+
+// Argument keys whose values are replaced whole, at any depth (e.g. an `env` map's GITHUB_TOKEN); redact_keys adds to it.
+const BUILTIN_KEY_PATTERN: &str = r"(?i)(password|passwd|secret|token|api[_-]?key|access[_-]?key|private[_-]?key|credential|authorization|cookie)";
+
+/// Strips secrets and oversized contents from what is persisted to the audit sinks or sent back
+/// in error messages.
+#[derive(Debug)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    keys: Vec<Regex>,
+    max_value_bytes: usize,
+    max_string_bytes: usize,
+}
+
+type RedactionSettings = (Vec<String>, Vec<String>, usize, usize);
+
+// Rebuilt when the redact_* keys change, so a reload applies to the next entry.
+static CURRENT: Lazy<Mutex<Option<(RedactionSettings, Arc<Redactor>)>>> = Lazy::new(|| Mutex::new(None));
+
+/// The redactor for `config`'s redact_* keys, compiled once per distinct setting.
+pub fn redactor(config: &Config) -> Arc<Redactor> {
+    let settings = (config.redact_patterns.clone(), config.redact_keys.clone(), config.redact_max_value_bytes, config.redact_max_string_bytes);
+    let mut current = CURRENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached, redactor)) = current.as_ref() {
+        if *cached == settings { return redactor.clone(); }
+    }
+    let redactor = Arc::new(Redactor::new(&settings.0, &settings.1, settings.2, settings.3).unwrap_or_else(|e| {
+        // Config::load rejects these, so only a config that skipped it gets here.
+        warn!(error = %format!("{:#}", e), "Ignoring redact_patterns and redact_keys");
+        Redactor::new(&[], &[], settings.2, settings.3).expect("built-in redaction patterns compile")
+    }));
+    *current = Some((settings, redactor.clone()));
+    redactor
+}
+
+/// Rejects redact_patterns or redact_keys entries that do not compile.
+pub fn check_patterns(entries: &[String], key: &str) -> Result<()> {
+    entries.iter().try_for_each(|entry| Regex::new(entry).map(|_| ()).context(format!("Invalid {} regex: {}", key, entry)))
+}
+
+impl Redactor {
+    pub fn new(patterns: &[String], keys: &[String], max_value_bytes: usize, max_string_bytes: usize) -> Result<Self> {
+        let compile = |p: &str| Regex::new(p).context(format!("Invalid redaction regex: {}", p));
+        Ok(Self {
+            patterns: BUILTIN_PATTERNS.iter().copied().chain(patterns.iter().map(String::as_str)).map(compile).collect::<Result<_>>()?,
+            keys: std::iter::once(BUILTIN_KEY_PATTERN).chain(keys.iter().map(String::as_str)).map(compile).collect::<Result<_>>()?,
+            max_value_bytes,
+            max_string_bytes,
+        })
+    }
+
+    /// `text` with every pattern match replaced by [REDACTED].
+    pub fn redact_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if !pattern.is_match(&text) { continue; }
+            let replaced = pattern.replace_all(&text, |caps: &Captures| {
+                let (whole, secret) = (caps.get(0).expect("group 0 always matches"), caps.name("secret"));
+                match secret {
+                    Some(secret) => format!("{}{}{}", &whole.as_str()[..secret.start() - whole.start()], REDACTED, &whole.as_str()[secret.end() - whole.start()..]),
+                    None => REDACTED.to_string(),
+                }
+            }).into_owned();
+            text = Cow::Owned(replaced);
+        }
+        text
+    }
+
+    /// A copy of `value` with secret keys and pattern matches redacted, contents past
+    /// redact_max_value_bytes replaced and other strings cut to redact_max_string_bytes.
+    pub fn redact_value(&self, value: &Value) -> Value {
+        let mut value = value.clone();
+        self.redact_in_place(None, &mut value);
+        value
+    }
+
+    fn redact_in_place(&self, key: Option<&str>, value: &mut Value) {
+        if let Some(key) = key {
+            if !matches!(value, Value::Null | Value::Bool(_)) && self.keys.iter().any(|k| k.is_match(key)) {
+                *value = Value::String(REDACTED.to_string());
+                return;
+            }
+        }
+        match value {
+            Value::String(text) => {
+                if let Some(key) = key.filter(|k| CONTENT_KEYS.contains(k)) {
+                    if self.max_value_bytes > 0 && text.len() > self.max_value_bytes {
+                        *text = format!("<{} truncated for log>", key);
+                        return;
+                    }
+                }
+                if let Cow::Owned(redacted) = self.redact_text(text) { *text = redacted; }
+                if self.max_string_bytes > 0 && truncate_output(text, self.max_string_bytes) { text.push_str("...[truncated]"); }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_in_place(None, item)),
+            Value::Object(map) => map.iter_mut().for_each(|(k, v)| self.redact_in_place(Some(k), v)),
+            _ => {}
+        }
+    }
+}
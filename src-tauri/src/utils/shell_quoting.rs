@@ -0,0 +1,211 @@
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    Cmd,
+    PowerShell,
+    Posix,
+}
+
+impl ShellKind {
+    /// Classifies a shell by its executable name, so `C:\Windows\System32\cmd.exe`,
+    /// `pwsh` and `/usr/bin/bash` all resolve correctly regardless of path or extension.
+    pub fn detect(shell: &str) -> Self {
+        let stem = Path::new(shell)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match stem.as_str() {
+            "cmd" => ShellKind::Cmd,
+            "powershell" | "pwsh" => ShellKind::PowerShell,
+            _ => ShellKind::Posix,
+        }
+    }
+}
+
+/// Arguments that make `shell` run `command` exactly as written.
+///
+/// - cmd.exe: `/D /S /C <command>`; `/S` makes cmd strip only the outer quotes that the
+///   process launcher adds, so quotes, carets and `&` inside the command keep their meaning.
+/// - PowerShell: `-EncodedCommand` with UTF-16LE base64, which bypasses argv quoting entirely.
+/// - POSIX shells: `-c <command>`.
+pub fn shell_invocation_args(kind: ShellKind, command: &str) -> Vec<String> {
+    match kind {
+        ShellKind::Cmd => vec!["/D".to_string(), "/S".to_string(), "/C".to_string(), command.to_string()],
+        ShellKind::PowerShell => {
+            let utf16_bytes: Vec<u8> = command.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+            vec![
+                "-NoProfile".to_string(),
+                "-NonInteractive".to_string(),
+                "-EncodedCommand".to_string(),
+                BASE64_STANDARD.encode(utf16_bytes),
+            ]
+        }
+        ShellKind::Posix => vec!["-c".to_string(), command.to_string()],
+    }
+}
+
+/// Quotes a single argument so that `kind` passes it through as one literal word.
+///
+/// cmd.exe has no quoting that stops it expanding `%NAME%` (or `!NAME!` with delayed expansion):
+/// carets are literal between double quotes, so such arguments reach the program unchanged only
+/// through `raw_args`, which never starts a shell.
+pub fn quote_arg(kind: ShellKind, arg: &str) -> String {
+    match kind {
+        ShellKind::Posix => {
+            if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c)) {
+                arg.to_string()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        }
+        ShellKind::PowerShell => {
+            if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./\\:,+".contains(c)) {
+                arg.to_string()
+            } else {
+                // Single-quoted PowerShell strings are literal; a quote is escaped by doubling it.
+                format!("'{}'", arg.replace('\'', "''"))
+            }
+        }
+        ShellKind::Cmd => {
+            let needs_quotes = arg.is_empty() || arg.chars().any(|c| c.is_whitespace() || "\"&|<>^()%!,;=".contains(c));
+            if !needs_quotes {
+                return arg.to_string();
+            }
+            // MSVC argv rules: backslashes are literal unless they precede a quote.
+            let mut quoted = String::from("\"");
+            let mut backslashes = 0;
+            for ch in arg.chars() {
+                match ch {
+                    '\\' => backslashes += 1,
+                    '"' => {
+                        quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                        backslashes = 0;
+                    }
+                    _ => {
+                        quoted.push_str(&"\\".repeat(backslashes));
+                        backslashes = 0;
+                    }
+                }
+                if ch != '\\' {
+                    quoted.push(ch);
+                }
+            }
+            quoted.push_str(&"\\".repeat(backslashes * 2));
+            quoted.push('"');
+            quoted
+        }
+    }
+}
+
+/// Renders a program and its argument vector as a command line for `kind`.
+/// Used to display and block-check `raw_args` invocations that never touch a shell.
+pub fn join_command_line(kind: ShellKind, program: &str, args: &[String]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .map(|part| quote_arg(kind, part))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Splits a command line into words, honoring double quotes everywhere and
+/// single quotes / backslash escapes on non-Windows hosts.
+/// Used when no shell is configured so `cmd "arg with spaces"` is not torn apart.
+pub fn split_command_line(command: &str) -> Vec<String> {
+    let posix_rules = !cfg!(windows);
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut in_double = false;
+    let mut in_single = false;
+    let mut chars = command.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' if posix_rules && !in_double => {
+                in_single = !in_single;
+                in_word = true;
+            }
+            '"' if !in_single => {
+                in_double = !in_double;
+                in_word = true;
+            }
+            '\\' if posix_rules && !in_single => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_word = true;
+            }
+            c if c.is_whitespace() && !in_double && !in_single => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cmd_quotes_spaces_and_empty_args() {
+        assert_eq!(quote_arg(ShellKind::Cmd, "plain.txt"), "plain.txt");
+        assert_eq!(quote_arg(ShellKind::Cmd, "a b"), r#""a b""#);
+        assert_eq!(quote_arg(ShellKind::Cmd, ""), r#""""#);
+    }
+
+    #[test]
+    fn cmd_escapes_quotes_and_backslashes_like_msvc_argv() {
+        assert_eq!(quote_arg(ShellKind::Cmd, r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_arg(ShellKind::Cmd, r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(quote_arg(ShellKind::Cmd, r"C:\dir\"), r"C:\dir\");
+        assert_eq!(quote_arg(ShellKind::Cmd, r"C:\my dir\"), r#""C:\my dir\\""#);
+    }
+
+    #[test]
+    fn cmd_keeps_metacharacters_literal_inside_quotes() {
+        assert_eq!(quote_arg(ShellKind::Cmd, "50%"), r#""50%""#);
+        assert_eq!(quote_arg(ShellKind::Cmd, "a^b"), r#""a^b""#);
+        assert_eq!(quote_arg(ShellKind::Cmd, "a&b"), r#""a&b""#);
+    }
+
+    #[test]
+    fn powershell_uses_literal_single_quotes() {
+        assert_eq!(quote_arg(ShellKind::PowerShell, r"C:\dir\file.txt"), r"C:\dir\file.txt");
+        assert_eq!(quote_arg(ShellKind::PowerShell, "a b"), "'a b'");
+        assert_eq!(quote_arg(ShellKind::PowerShell, ""), "''");
+        assert_eq!(quote_arg(ShellKind::PowerShell, "it's"), "'it''s'");
+        assert_eq!(quote_arg(ShellKind::PowerShell, r#"say "hi""#), r#"'say "hi"'"#);
+        assert_eq!(quote_arg(ShellKind::PowerShell, r"C:\my dir\"), r"'C:\my dir\'");
+        assert_eq!(quote_arg(ShellKind::PowerShell, "50%"), "'50%'");
+        assert_eq!(quote_arg(ShellKind::PowerShell, "a^b"), "'a^b'");
+        assert_eq!(quote_arg(ShellKind::PowerShell, "a&b"), "'a&b'");
+    }
+
+    #[test]
+    fn powershell_command_is_utf16le_base64() {
+        let args = shell_invocation_args(ShellKind::PowerShell, "echo 'é'");
+        let decoded = BASE64_STANDARD.decode(args.last().unwrap()).unwrap();
+        let units: Vec<u16> = decoded.chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        assert_eq!(String::from_utf16(&units).unwrap(), "echo 'é'");
+    }
+
+    #[test]
+    fn detects_shell_kind_from_path() {
+        assert_eq!(ShellKind::detect("cmd.exe"), ShellKind::Cmd);
+        assert_eq!(ShellKind::detect("pwsh"), ShellKind::PowerShell);
+        assert_eq!(ShellKind::detect("/usr/bin/bash"), ShellKind::Posix);
+    }
+}
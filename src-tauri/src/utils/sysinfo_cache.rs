@@ -0,0 +1,98 @@
+use crate::utils::cache_stats::{CacheCounters, CacheStatsMCP};
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, System};
+use tokio::sync::Mutex as TokioMutex;
+
+/// How long a process-table refresh is reused by later tool calls.
+/// Short enough that agents see processes they just started, long enough that a burst of
+/// process tools on a host with thousands of processes walks /proc (or the OS tables) once.
+const PROCESS_TABLE_TTL: Duration = Duration::from_millis(1000);
+
+pub type SysinfoState = Arc<TokioMutex<SysinfoCache>>;
+
+/// Detail needed from a process-table refresh. `Full` includes command lines, CPU, memory,
+/// disk usage and users; `Basic` is enough for names, parents and liveness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProcessDetail {
+    Basic,
+    Full,
+}
+
+impl ProcessDetail {
+    fn refresh_kind(self) -> ProcessRefreshKind {
+        match self {
+            ProcessDetail::Basic => ProcessRefreshKind::new(),
+            ProcessDetail::Full => ProcessRefreshKind::everything(),
+        }
+    }
+}
+
+/// The shared sysinfo `System` plus when its process table was last refreshed.
+/// Derefs to `System`, so callers still use the sysinfo API directly once refreshed.
+#[derive(Debug)]
+pub struct SysinfoCache {
+    system: System,
+    last_process_refresh: Option<(Instant, ProcessDetail)>,
+    process_table_counters: CacheCounters,
+}
+
+impl SysinfoCache {
+    /// Starts empty instead of `System::new_all()`; each subsystem is loaded on first use.
+    pub fn new() -> Self {
+        Self { system: System::new(), last_process_refresh: None, process_table_counters: CacheCounters::new() }
+    }
+
+    /// Refreshes the whole process table unless a refresh of at least `detail` happened within the TTL.
+    pub fn refresh_processes_cached(&mut self, detail: ProcessDetail) {
+        if let Some((at, cached_detail)) = self.last_process_refresh {
+            if cached_detail >= detail && at.elapsed() < PROCESS_TABLE_TTL {
+                self.process_table_counters.record(true);
+                return;
+            }
+        }
+        self.process_table_counters.record(false);
+        self.system.refresh_processes_specifics(detail.refresh_kind());
+        self.last_process_refresh = Some((Instant::now(), detail));
+    }
+
+    /// Forces the next cached refresh to hit the OS, e.g. after a kill changed the process table.
+    pub fn invalidate_process_table(&mut self) {
+        self.last_process_refresh = None;
+    }
+
+    /// How often refresh_processes_cached reused the table; entries are the processes in it.
+    pub fn process_table_stats(&self) -> CacheStatsMCP {
+        self.process_table_counters.stats(self.system.processes().len())
+    }
+
+    /// Refreshes only `pids`, bypassing the TTL. Returns the PIDs that still exist.
+    pub fn refresh_pids(&mut self, pids: &[Pid], detail: ProcessDetail) -> Vec<Pid> {
+        pids.iter()
+            .copied()
+            .filter(|pid| self.system.refresh_process_specifics(*pid, detail.refresh_kind()))
+            .collect()
+    }
+}
+
+impl Default for SysinfoCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for SysinfoCache {
+    type Target = System;
+
+    fn deref(&self) -> &System {
+        &self.system
+    }
+}
+
+impl DerefMut for SysinfoCache {
+    fn deref_mut(&mut self) -> &mut System {
+        &mut self.system
+    }
+}
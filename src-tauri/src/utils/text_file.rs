@@ -0,0 +1,27 @@
+use crate::error::AppError;
+
+use std::path::Path;
+
+/// Reads `path`, whose size the caller already checked, as UTF-8 text into one buffer. Files
+/// are copied rather than memory-mapped: write_file, edit_block and editors rewrite files in
+/// place, and a mapping of a file truncated under it faults the whole app.
+pub async fn read_text(path: &Path) -> Result<String, AppError> {
+    tokio::fs::read_to_string(path).await.map_err(|e| AppError::TokioIoError(format!("Failed to read file {}: {}", path.display(), e)))
+}
+
+/// Lines `offset..offset + limit` of a text, split like `str::lines`, and how many it has.
+pub struct LineWindow<'a> {
+    pub lines: Vec<&'a str>,
+    pub total_lines: usize,
+}
+
+/// Finds the window by scanning for newlines with memchr, so only the lines returned are
+/// split, and counts every line of the text.
+pub fn line_window(text: &str, offset: usize, limit: usize) -> LineWindow<'_> {
+    let bytes = text.as_bytes();
+    let total_lines = memchr::memchr_iter(b'\n', bytes).count() + usize::from(!bytes.is_empty() && !bytes.ends_with(b"\n"));
+    let line_end = |from: usize, nth: usize| memchr::memchr_iter(b'\n', &bytes[from..]).nth(nth).map_or(bytes.len(), |i| from + i + 1);
+    let start = if offset == 0 { 0 } else { line_end(0, offset - 1) };
+    let end = if limit == 0 { start } else { line_end(start, limit - 1) };
+    LineWindow { lines: text[start..end].lines().collect(), total_lines }
+}
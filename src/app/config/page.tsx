@@ -4,7 +4,7 @@
 
 import { useEffect, useState, useCallback, ChangeEvent } from "react";
 import { invoke } from "@tauri-apps/api/core";
-// import { emit, listen } from "@tauri-apps/api/event"; // Not used in this version
+import { listen } from "@tauri-apps/api/event";
 import { toast } from "sonner";
 
 import { Button } from "@/components/ui/button";
@@ -102,6 +102,16 @@ export default function ConfigPage() {
     void fetchConfig();
   }, [fetchConfig]);
 
+  // Config can also change from config files, MCP tools (use_profile) or another window.
+  useEffect(() => {
+    const unlistenPromise = listen("config_changed", () => {
+      void fetchConfig();
+    });
+    return () => {
+      void unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, [fetchConfig]);
+
   const handleInputChange = (
     e: ChangeEvent<HTMLInputElement | HTMLTextAreaElement>,
   ) => {
@@ -116,7 +126,7 @@ export default function ConfigPage() {
   const handleSaveSetting = async (key: string, value: unknown) => {
     try {
       const result = await invoke<string>("set_config_value_command", {
-        payload: { key, value },
+        payload: { key, value, persist: true },
       });
       toast.success(result || `Successfully updated ${key}.`, {
         description: "Setting Saved",